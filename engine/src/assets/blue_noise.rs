@@ -0,0 +1,34 @@
+use super::{AssetKey, Assets};
+
+pub use noise::{BlueNoise, BlueNoiseError};
+
+/// Error that may occur when blue noise asset is loaded.
+#[derive(Debug, thiserror::Error)]
+pub enum LoadBlueNoiseError {
+    #[error("Failed to load blue noise: `{source}`")]
+    Load {
+        #[from]
+        source: goods::Error,
+    },
+
+    #[error("Failed to decode blue noise: `{source}`")]
+    Decode {
+        #[from]
+        source: BlueNoiseError,
+    },
+}
+
+/// Loads pregenerated blue noise file through assets cache.
+///
+/// Files are produced with `blue-noise` tool or `pack_blue_noise` function
+/// from `wilds-noise` crate.
+/// Files packed at build time can be embedded instead
+/// and decoded with `BlueNoise::decode`.
+pub async fn load_blue_noise(
+    assets: &Assets,
+    key: AssetKey,
+) -> Result<BlueNoise, LoadBlueNoiseError> {
+    let bytes = assets.load::<Box<[u8]>>(key).await?;
+    let blue_noise = BlueNoise::decode(&bytes)?;
+    Ok(blue_noise)
+}
@@ -0,0 +1,170 @@
+use {
+    super::GltfRepr,
+    nalgebra as na,
+    ncollide3d::shape::{self, ConvexHull, ShapeHandle},
+    std::{fmt, sync::Arc},
+};
+
+/// Kind of collider generated for gltf node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize)]
+pub enum ColliderKind {
+    /// Convex hull of all mesh vertices.
+    #[serde(rename = "convex")]
+    Convex,
+
+    /// Triangle mesh of all mesh triangles.
+    #[serde(rename = "trimesh")]
+    TriMesh,
+}
+
+#[derive(serde::Deserialize)]
+struct NodeExtras {
+    #[serde(default)]
+    collider: Option<ColliderKind>,
+}
+
+/// Collider shapes generated for gltf nodes.
+#[derive(Clone)]
+pub struct GltfColliders {
+    shapes: Arc<[Option<ShapeHandle<f32>>]>,
+}
+
+impl GltfColliders {
+    /// Returns collider shape for node with specified index.
+    pub fn get(&self, node: usize) -> Option<&ShapeHandle<f32>> {
+        self.shapes.get(node)?.as_ref()
+    }
+}
+
+impl fmt::Debug for GltfColliders {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let count = self.shapes.iter().filter(|s| s.is_some()).count();
+        f.debug_struct("GltfColliders")
+            .field("count", &count)
+            .finish()
+    }
+}
+
+/// Determines collider kind for node.
+///
+/// `collider` field in node extras takes precedence.
+/// Otherwise names ending with `-convcol` get convex hull colliders
+/// and names ending with `-col` get trimesh colliders.
+fn node_collider_kind(node: &gltf::Node<'_>) -> Option<ColliderKind> {
+    if let Some(extras) = node.extras() {
+        match serde_json::from_str::<NodeExtras>(extras.get()) {
+            Ok(NodeExtras {
+                collider: Some(kind),
+            }) => return Some(kind),
+            Ok(_) => {}
+            Err(err) => {
+                tracing::debug!("Failed to parse node extras: {}", err)
+            }
+        }
+    }
+
+    let name = node.name()?;
+    if name.ends_with("-convcol") || name.ends_with("_convcol") {
+        Some(ColliderKind::Convex)
+    } else if name.ends_with("-col") || name.ends_with("_col") {
+        Some(ColliderKind::TriMesh)
+    } else {
+        None
+    }
+}
+
+/// Generates collider shapes for nodes that request them.
+/// Node scale is baked into the shape as colliders can't be scaled.
+pub fn load_gltf_colliders(repr: &GltfRepr) -> GltfColliders {
+    let shapes = repr
+        .gltf
+        .nodes()
+        .map(|node| {
+            if !repr.config.generate_colliders {
+                return None;
+            }
+
+            let kind = node_collider_kind(&node)?;
+            let shape = node_collider(repr, &node, kind);
+
+            if shape.is_none() {
+                tracing::warn!(
+                    "Failed to generate {:?} collider for node {:?}",
+                    kind,
+                    node.name(),
+                );
+            }
+
+            shape
+        })
+        .collect();
+
+    GltfColliders { shapes }
+}
+
+fn node_collider(
+    repr: &GltfRepr,
+    node: &gltf::Node<'_>,
+    kind: ColliderKind,
+) -> Option<ShapeHandle<f32>> {
+    let mesh = node.mesh()?;
+    let (_, _, scale) = node.transform().decomposed();
+    let scale = na::Vector3::from(scale);
+
+    let mut points = Vec::new();
+    let mut triangles = Vec::new();
+
+    for primitive in mesh.primitives() {
+        let reader = primitive.reader(|buffer| match buffer.source() {
+            gltf::buffer::Source::Bin => repr.gltf.blob.as_deref(),
+            gltf::buffer::Source::Uri(uri) => {
+                repr.buffers.get(uri).map(|b| &**b)
+            }
+        });
+
+        let positions = match reader.read_positions() {
+            Some(positions) => positions,
+            None => continue,
+        };
+
+        let base = points.len();
+        points.extend(positions.map(|p| {
+            na::Point3::from(na::Vector3::from(p).component_mul(&scale))
+        }));
+
+        if kind == ColliderKind::TriMesh {
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                tracing::debug!("Non-triangle primitive skipped in trimesh");
+                continue;
+            }
+
+            let indices: Vec<usize> = match reader.read_indices() {
+                Some(indices) => {
+                    indices.into_u32().map(|i| base + i as usize).collect()
+                }
+                None => (base..points.len()).collect(),
+            };
+
+            triangles.extend(
+                indices
+                    .chunks_exact(3)
+                    .map(|t| na::Point3::new(t[0], t[1], t[2])),
+            );
+        }
+    }
+
+    match kind {
+        ColliderKind::Convex => {
+            let hull = ConvexHull::try_from_points(&points)?;
+            Some(ShapeHandle::new(hull))
+        }
+        ColliderKind::TriMesh => {
+            if triangles.is_empty() {
+                return None;
+            }
+
+            let trimesh = shape::TriMesh::new(points, triangles, None);
+            Some(ShapeHandle::new(trimesh))
+        }
+    }
+}
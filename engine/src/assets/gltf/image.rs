@@ -2,14 +2,89 @@ use {
     super::{GltfLoadingError, GltfRepr},
     crate::{assets::image_view_from_dyn_image, renderer::Context},
     illume::*,
+    std::borrow::Cow,
 };
 
+/// Encoding of color values in image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ColorSpace {
+    /// Color is sRGB encoded.
+    /// Used for base color and emissive textures.
+    Srgb,
+
+    /// Values are stored as is.
+    /// Used for normal, occlusion and metallic-roughness textures.
+    Linear,
+}
+
+/// Returns color space for each image in gltf.
+/// Color space is derived from material slots image is used in
+/// unless overridden by image name or uri.
+pub fn gltf_image_color_spaces(repr: &GltfRepr) -> Vec<ColorSpace> {
+    let mut spaces = vec![None; repr.gltf.images().len()];
+
+    let mut used_as = |texture: gltf::Texture, space: ColorSpace| {
+        let image = texture.source().index();
+        match spaces[image] {
+            None => spaces[image] = Some(space),
+            Some(used) if used != space => tracing::warn!(
+                "Image {} is used both as {:?} and {:?}. {:?} is chosen",
+                image,
+                used,
+                space,
+                used,
+            ),
+            Some(_) => {}
+        }
+    };
+
+    for material in repr.gltf.materials() {
+        let pbr = material.pbr_metallic_roughness();
+        if let Some(info) = pbr.base_color_texture() {
+            used_as(info.texture(), ColorSpace::Srgb);
+        }
+        if let Some(info) = material.emissive_texture() {
+            used_as(info.texture(), ColorSpace::Srgb);
+        }
+        if let Some(info) = pbr.metallic_roughness_texture() {
+            used_as(info.texture(), ColorSpace::Linear);
+        }
+        if let Some(info) = material.normal_texture() {
+            used_as(info.texture(), ColorSpace::Linear);
+        }
+        if let Some(info) = material.occlusion_texture() {
+            used_as(info.texture(), ColorSpace::Linear);
+        }
+    }
+
+    let overrides = &repr.config.color_space_overrides;
+
+    repr.gltf
+        .images()
+        .zip(spaces)
+        .map(|(image, space)| {
+            let by_name = image.name().and_then(|name| overrides.get(name));
+            let by_uri = match image.source() {
+                gltf::image::Source::Uri { uri, .. } => overrides.get(uri),
+                gltf::image::Source::View { .. } => None,
+            };
+
+            by_name
+                .or(by_uri)
+                .copied()
+                .or(space)
+                .unwrap_or(ColorSpace::Linear)
+        })
+        .collect()
+}
+
 pub fn load_gltf_image(
     repr: &GltfRepr,
     image: gltf::Image,
+    space: ColorSpace,
     ctx: &mut Context,
 ) -> Result<ImageView, GltfLoadingError> {
-    match image.source() {
+    let dyn_image = match image.source() {
         gltf::image::Source::View { view, .. } => {
             let view_source = match view.buffer().source() {
                 gltf::buffer::Source::Bin => repr.gltf.blob.as_deref(),
@@ -26,17 +101,26 @@ pub fn load_gltf_image(
             }
 
             let view_bytes = &source_bytes[view.offset()..][..view.length()];
-            let dyn_image = image::load_from_memory(view_bytes)?;
-            match image_view_from_dyn_image(&dyn_image, ctx) {
-                Ok(view) => Ok(view),
-                Err(CreateImageError::OutOfMemory { source }) => {
-                    Err(GltfLoadingError::OutOfMemory { source })
-                }
-                Err(CreateImageError::Unsupported { info }) => {
-                    Err(GltfLoadingError::UnsupportedImage { info })
-                }
-            }
+            Cow::Owned(::image::load_from_memory(view_bytes)?)
+        }
+        gltf::image::Source::Uri { uri, .. } => Cow::Borrowed(
+            repr.images
+                .get(uri)
+                .ok_or(GltfLoadingError::MissingSource)?,
+        ),
+    };
+
+    let srgb = space == ColorSpace::Srgb;
+    match image_view_from_dyn_image(&dyn_image, srgb, ctx) {
+        Ok(view) => Ok(view),
+        Err(CreateImageError::OutOfMemory { source }) => {
+            Err(GltfLoadingError::OutOfMemory { source })
+        }
+        Err(CreateImageError::Unsupported { info }) => {
+            Err(GltfLoadingError::UnsupportedImage { info })
+        }
+        Err(CreateImageError::Invalid { source }) => {
+            Err(GltfLoadingError::InvalidImage { source })
         }
-        gltf::image::Source::Uri { uri, .. } => Ok(repr.images[uri].clone()),
     }
 }
@@ -1,6 +1,6 @@
 use {
     super::GltfLoadingError,
-    crate::renderer::{Material, Texture},
+    crate::renderer::{AlphaMode, Material, ParameterBlock, Texture},
 };
 
 pub fn load_gltf_material(
@@ -64,5 +64,18 @@ pub fn load_gltf_material(
             .map(|info| info.scale())
             .unwrap_or(0.0)
             .into(),
+
+        alpha_mode: match material.alpha_mode() {
+            gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+            gltf::material::AlphaMode::Mask => AlphaMode::Mask {
+                cutoff: material.alpha_cutoff().into(),
+            },
+            gltf::material::AlphaMode::Blend => AlphaMode::Blend,
+        },
+
+        double_sided: material.double_sided(),
+
+        params: ParameterBlock::new(),
+        virtual_texture: None,
     })
 }
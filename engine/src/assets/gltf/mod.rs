@@ -1,34 +1,57 @@
+mod collider;
 mod image;
 mod material;
 mod prefab;
 mod primitive;
 mod sampler;
 mod skin;
+mod tangent;
 mod texture;
 
 use {
     self::{
-        image::load_gltf_image, material::load_gltf_material,
-        primitive::load_gltf_primitive, sampler::load_gltf_sampler,
+        collider::{load_gltf_colliders, GltfColliders},
+        image::{gltf_image_color_spaces, load_gltf_image},
+        material::load_gltf_material,
+        primitive::load_gltf_primitive,
+        sampler::load_gltf_sampler,
         texture::load_gltf_texture,
     },
-    super::{append_key, image::ImageAsset, AssetKey, Assets, Format},
-    crate::renderer::{Context, Renderable},
-    ::image::ImageError,
+    super::{append_key, AssetKey, Assets, Format},
+    crate::renderer::{Context, MeshRetention, Renderable},
+    ::image::{DynamicImage, ImageError},
     futures::{
         future::{try_join_all, BoxFuture},
         try_join,
     },
     gltf::accessor::{DataType, Dimensions},
     goods::SyncAsset,
-    illume::{BufferUsage, ImageInfo, ImageView, OutOfMemory},
+    illume::{BufferUsage, ImageInfo, InvalidImageInfo, OutOfMemory},
     std::{collections::HashMap, sync::Arc},
 };
 
-#[derive(Debug)]
+pub use self::{collider::ColliderKind, image::ColorSpace};
+
+#[derive(Clone, Debug)]
 pub struct GltfFormat {
     pub mesh_vertices_usage: BufferUsage,
     pub mesh_indices_usage: BufferUsage,
+
+    /// Store only vertex attributes present in primitives.
+    /// Meshes with slim vertices are not drawn by ray tracing passes
+    /// which expect `PositionNormalTangent3dUV` vertices.
+    pub slim_vertices: bool,
+
+    /// What happens to CPU-side mesh data after upload.
+    pub mesh_retention: MeshRetention,
+
+    /// Generate colliders for nodes marked with `collider` extras field
+    /// or name suffix. See `ColliderKind`.
+    pub generate_colliders: bool,
+
+    /// Color spaces of images by name or uri.
+    /// Takes precedence over color space derived from materials.
+    pub color_space_overrides: HashMap<String, ColorSpace>,
 }
 
 impl GltfFormat {
@@ -36,6 +59,10 @@ impl GltfFormat {
         GltfFormat {
             mesh_indices_usage: BufferUsage::INDEX,
             mesh_vertices_usage: BufferUsage::VERTEX,
+            slim_vertices: true,
+            mesh_retention: MeshRetention::Drop,
+            generate_colliders: true,
+            color_space_overrides: HashMap::new(),
         }
     }
 
@@ -45,8 +72,28 @@ impl GltfFormat {
                 | BufferUsage::DEVICE_ADDRESS,
             mesh_vertices_usage: BufferUsage::STORAGE
                 | BufferUsage::DEVICE_ADDRESS,
+            slim_vertices: false,
+            mesh_retention: MeshRetention::Drop,
+            generate_colliders: true,
+            color_space_overrides: HashMap::new(),
         }
     }
+
+    /// Sets policy for CPU-side mesh data.
+    pub fn with_mesh_retention(mut self, retention: MeshRetention) -> Self {
+        self.mesh_retention = retention;
+        self
+    }
+
+    /// Overrides color space of image with specified name or uri.
+    pub fn with_color_space(
+        mut self,
+        image: impl Into<String>,
+        space: ColorSpace,
+    ) -> Self {
+        self.color_space_overrides.insert(image.into(), space);
+        self
+    }
 }
 
 /// gltf scenes with initialized resources.
@@ -54,6 +101,7 @@ impl GltfFormat {
 pub struct GltfAsset {
     gltf: gltf::Gltf,
     renderables: Arc<[Box<[Renderable]>]>,
+    colliders: GltfColliders,
 }
 
 impl SyncAsset for GltfAsset {
@@ -68,7 +116,8 @@ impl SyncAsset for GltfAsset {
         let images = repr
             .gltf
             .images()
-            .map(|image| load_gltf_image(&repr, image, ctx))
+            .zip(gltf_image_color_spaces(&repr))
+            .map(|(image, space)| load_gltf_image(&repr, image, space, ctx))
             .collect::<Result<Vec<_>, _>>()?;
 
         let samplers = repr
@@ -111,9 +160,12 @@ impl SyncAsset for GltfAsset {
             })
             .collect::<Result<_, _>>()?;
 
+        let colliders = load_gltf_colliders(&repr);
+
         Ok(GltfAsset {
             gltf: repr.gltf,
             renderables,
+            colliders,
         })
     }
 }
@@ -123,7 +175,7 @@ impl SyncAsset for GltfAsset {
 pub struct GltfRepr {
     gltf: gltf::Gltf,
     buffers: HashMap<String, Arc<[u8]>>,
-    images: HashMap<String, ImageView>,
+    images: HashMap<String, DynamicImage>,
     config: GltfFormat,
 }
 
@@ -158,13 +210,9 @@ impl Format<GltfAsset, AssetKey> for GltfFormat {
                     try_join_all(gltf.images().filter_map(
                         |b| match b.source() {
                             gltf::image::Source::View { .. } => None,
-                            gltf::image::Source::Uri { uri, .. } => {
-                                Some(
-                                    assets.load::<ImageAsset>(append_key(
-                                        &key, uri,
-                                    )),
-                                )
-                            }
+                            gltf::image::Source::Uri { uri, .. } => Some(
+                                assets.load::<Arc<[u8]>>(append_key(&key, uri)),
+                            ),
                         },
                     ));
 
@@ -191,8 +239,10 @@ impl Format<GltfAsset, AssetKey> for GltfFormat {
                         buffers: buffers_uri.zip(buffers).collect(),
                         images: images_uri
                             .zip(images)
-                            .map(|(uri, texture)| (uri, texture.into_inner()))
-                            .collect(),
+                            .map(|(uri, bytes)| {
+                                Ok((uri, ::image::load_from_memory(&bytes)?))
+                            })
+                            .collect::<Result<_, GltfLoadingError>>()?,
                         config: self,
                         gltf,
                     })
@@ -266,6 +316,9 @@ pub enum GltfLoadingError {
 
     #[error("Combination paramters `{info:?}` is unsupported")]
     UnsupportedImage { info: ImageInfo },
+
+    #[error("Invalid image: `{source}`")]
+    InvalidImage { source: InvalidImageInfo },
 }
 
 fn align_vec(bytes: &mut Vec<u8>, align_mask: usize) {
@@ -2,6 +2,7 @@ use {
     super::GltfAsset,
     crate::{
         assets::Prefab,
+        physics::{BodyStatus, Colliders, RigidBodyDesc},
         renderer::Renderable,
         scene::{Global3, Local3},
     },
@@ -65,6 +66,7 @@ impl Prefab for GltfAsset {
                     None => world.insert_one(entity, global).unwrap(),
                 };
 
+                attach_collider(entity, &node, &self, world);
                 spawn_children(entity, &node, &self, world);
             }
             _ => {
@@ -111,10 +113,29 @@ fn spawn_node(
         None => spawn_empty(base, &node, world),
     };
 
+    attach_collider(entity, &node, asset, world);
     spawn_children(entity, &node, asset, world);
     entity
 }
 
+/// Attaches static body with collider generated for the node.
+fn attach_collider(
+    entity: Entity,
+    node: &Node<'_>,
+    asset: &GltfAsset,
+    world: &mut World,
+) {
+    if let Some(shape) = asset.colliders.get(node.index()) {
+        let body = RigidBodyDesc::<f32>::new()
+            .status(BodyStatus::Static)
+            .build();
+
+        world
+            .insert(entity, (body, Colliders::from(shape.clone())))
+            .unwrap();
+    }
+}
+
 fn spawn_children(
     entity: Entity,
     node: &Node<'_>,
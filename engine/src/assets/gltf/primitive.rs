@@ -1,18 +1,23 @@
 use {
-    super::{align_vec, GltfLoadingError, GltfRepr},
+    super::{
+        align_vec, tangent::generate_tangents, GltfLoadingError, GltfRepr,
+    },
     crate::renderer::{
-        Binding, Context, FromBytes, Indices, Joints, Material, MeshBuilder,
-        Normal3d, Position3d, PositionNormalTangent3dUV, Renderable, Skin,
-        Tangent3d, VertexType, Weights, UV,
+        Binding, BindingData, Context, FromBytes, Indices, IndicesData, Joints,
+        Material, Mesh, MeshBuilder, MeshData, MeshRetention, Normal3d,
+        Position3d, PositionNormalTangent3dUV, Renderable, Semantics, Skin,
+        Tangent3d, VertexAttributes, VertexLayout, VertexType, Weights, UV,
     },
     byteorder::{ByteOrder as _, LittleEndian},
     gltf::accessor::{Accessor, DataType, Dimensions},
     illume::*,
     std::{
+        borrow::Cow,
         convert::{TryFrom as _, TryInto as _},
         marker::PhantomData,
         mem::size_of,
         ops::Range,
+        sync::Arc,
     },
 };
 
@@ -38,8 +43,8 @@ pub fn load_gltf_primitive(
 
     let mut loaded_data = Vec::new();
 
-    let (vectors, skin, vertex_count) =
-        load_vertices(repr, primitive.clone(), &mut loaded_data)?;
+    let (vectors, layout, skin, vertex_count) =
+        load_vertices(repr, primitive.clone(), &mut loaded_data, ctx)?;
 
     let mut count = vertex_count;
     let indices = primitive
@@ -56,12 +61,19 @@ pub fn load_gltf_primitive(
     let count = count.try_into().map_err(|_| OutOfMemory)?;
     let vertex_count = vertex_count.try_into().map_err(|_| OutOfMemory)?;
 
+    let mut usage =
+        repr.config.mesh_indices_usage | repr.config.mesh_vertices_usage;
+
+    if repr.config.mesh_retention == MeshRetention::Download {
+        usage |= BufferUsage::TRANSFER_SRC;
+    }
+
     let buffer = ctx.create_buffer_static(
         BufferInfo {
             align: 255,
             size: u64::try_from(loaded_data.len()).map_err(|_| OutOfMemory)?,
-            usage: repr.config.mesh_indices_usage
-                | repr.config.mesh_vertices_usage,
+            usage,
+            capture_address: None,
         },
         &loaded_data,
     )?;
@@ -71,7 +83,7 @@ pub fn load_gltf_primitive(
     bindings.push(Binding {
         buffer: buffer.clone(),
         offset: vectors.start as u64,
-        layout: PositionNormalTangent3dUV::layout(),
+        layout,
     });
 
     if let Some(skin) = skin {
@@ -102,7 +114,12 @@ pub fn load_gltf_primitive(
         topology,
     };
 
-    let mesh = mesh.build(count, vertex_count);
+    let mut mesh = mesh.build(count, vertex_count);
+
+    if repr.config.mesh_retention == MeshRetention::Retain {
+        let data = retained_data(&mesh, &loaded_data);
+        mesh = mesh.with_data(Arc::new(data));
+    }
 
     let material = match primitive.material().index() {
         Some(material) => materials[material].clone(),
@@ -112,6 +129,42 @@ pub fn load_gltf_primitive(
     Ok(Renderable { mesh, material })
 }
 
+/// Copies mesh data from loaded bytes.
+/// All bindings and indices of the mesh are expected
+/// to be sourced from the same buffer filled with `loaded_data`.
+fn retained_data(mesh: &Mesh, loaded_data: &[u8]) -> MeshData<'static> {
+    let slice = |offset: u64, size: u64| {
+        let start = offset as usize;
+        let end = start + size as usize;
+        Cow::Owned(loaded_data[start..end].to_vec())
+    };
+
+    let mut data = MeshData::new(mesh.topology());
+
+    for binding in mesh.bindings() {
+        data.bindings.push(BindingData {
+            data: slice(
+                binding.offset,
+                u64::from(binding.layout.stride)
+                    * u64::from(mesh.vertex_count()),
+            ),
+            layout: binding.layout.clone(),
+        });
+    }
+
+    if let Some(indices) = mesh.indices() {
+        data.indices = Some(IndicesData {
+            data: slice(
+                indices.offset,
+                u64::from(indices.index_type.size()) * u64::from(mesh.count()),
+            ),
+            index_type: indices.index_type,
+        });
+    }
+
+    data
+}
+
 enum IndicesAux {
     U16(Range<usize>),
     U32(Range<usize>),
@@ -639,7 +692,11 @@ fn load_vertices(
     repr: &GltfRepr,
     primitive: gltf::mesh::Primitive<'_>,
     output: &mut Vec<u8>,
-) -> Result<(Range<usize>, Option<Range<usize>>, usize), GltfLoadingError> {
+    ctx: &mut Context,
+) -> Result<
+    (Range<usize>, VertexLayout, Option<Range<usize>>, usize),
+    GltfLoadingError,
+> {
     let position = primitive
         .get(&gltf::Semantic::Positions)
         .ok_or(GltfLoadingError::MissingPositionAttribute)?;
@@ -652,6 +709,7 @@ fn load_vertices(
         .map(|normals| load_vertex_attribute::<Normal3d>(repr, normals))
         .transpose()?;
 
+    let has_normals = normals_attribute_iter.is_some();
     let normals_attribute_iter =
         iter_or_defaults(normals_attribute_iter, Normal3d([0.0; 3]));
 
@@ -660,6 +718,7 @@ fn load_vertices(
         .map(|tangents| load_vertex_attribute::<Tangent3d>(repr, tangents))
         .transpose()?;
 
+    let mut has_tangents = tangents_attribute_iter.is_some();
     let tangents_attribute_iter =
         iter_or_defaults(tangents_attribute_iter, Tangent3d([0.0; 4]));
 
@@ -668,6 +727,7 @@ fn load_vertices(
         .map(|uv| load_vertex_attribute::<UV>(repr, uv))
         .transpose()?;
 
+    let has_uv = uv_attribute_iter.is_some();
     let uv_attribute_iter = iter_or_defaults(uv_attribute_iter, UV([0.0; 2]));
 
     let vertex_iter = position_attribute_iter
@@ -675,18 +735,68 @@ fn load_vertices(
         .zip(tangents_attribute_iter)
         .zip(uv_attribute_iter);
 
-    let start = output.len();
-    let count = vertex_iter
-        .map(|(((position, normal), tangent), uv)| {
-            let vertex = PositionNormalTangent3dUV {
+    let mut vertices = vertex_iter
+        .map(
+            |(((position, normal), tangent), uv)| PositionNormalTangent3dUV {
                 position,
                 normal,
                 tangent,
                 uv,
-            };
-            output.extend_from_slice(bytemuck::bytes_of(&vertex));
-        })
-        .count();
+            },
+        )
+        .collect::<Vec<_>>();
+
+    if !has_tangents {
+        if has_normals
+            && has_uv
+            && primitive.mode() == gltf::mesh::Mode::Triangles
+        {
+            let indices = primitive
+                .reader(|buffer| match buffer.source() {
+                    gltf::buffer::Source::Bin => repr.gltf.blob.as_deref(),
+                    gltf::buffer::Source::Uri(uri) => {
+                        repr.buffers.get(uri).map(|b| &**b)
+                    }
+                })
+                .read_indices()
+                .map(|indices| indices.into_u32().collect::<Vec<_>>());
+
+            has_tangents = generate_tangents(&mut vertices, indices.as_deref());
+
+            if !has_tangents {
+                tracing::warn!("Failed to generate tangents for primitive");
+            }
+        } else {
+            tracing::debug!(
+                "Tangents are not generated for primitive without normals or texture coordinates or not a triangle list"
+            );
+        }
+    }
+
+    let start = output.len();
+    let count = vertices.len();
+
+    let layout = if repr.config.slim_vertices {
+        let mut attributes = VertexAttributes::POSITION;
+        if has_normals {
+            attributes |= VertexAttributes::NORMAL;
+        }
+        if has_tangents {
+            attributes |= VertexAttributes::TANGENT;
+        }
+        if has_uv {
+            attributes |= VertexAttributes::UV0;
+        }
+
+        let layout = ctx.vertex_layouts.layout(attributes);
+        for vertex in &vertices {
+            write_vertex(vertex, &layout, output);
+        }
+        layout
+    } else {
+        output.extend_from_slice(bytemuck::cast_slice(&vertices));
+        PositionNormalTangent3dUV::layout()
+    };
 
     let vectors = start..output.len();
 
@@ -719,8 +829,31 @@ fn load_vertices(
 
         let skin = vectors.end..output.len();
 
-        Ok((vectors, Some(skin), count))
+        Ok((vectors, layout, Some(skin), count))
     } else {
-        Ok((vectors, None, count))
+        Ok((vectors, layout, None, count))
+    }
+}
+
+/// Writes attributes of vertex present in layout.
+fn write_vertex(
+    vertex: &PositionNormalTangent3dUV,
+    layout: &VertexLayout,
+    output: &mut Vec<u8>,
+) {
+    let start = output.len();
+    output.resize(start + layout.stride as usize, 0);
+
+    for location in layout.locations.iter() {
+        let bytes = match location.semantics {
+            Some(Semantics::Position3d) => bytemuck::bytes_of(&vertex.position),
+            Some(Semantics::Normal3d) => bytemuck::bytes_of(&vertex.normal),
+            Some(Semantics::Tangent3d) => bytemuck::bytes_of(&vertex.tangent),
+            Some(Semantics::UV) => bytemuck::bytes_of(&vertex.uv),
+            _ => continue,
+        };
+
+        output[start + location.offset as usize..][..bytes.len()]
+            .copy_from_slice(bytes);
     }
 }
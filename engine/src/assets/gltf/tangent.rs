@@ -0,0 +1,73 @@
+use crate::renderer::{PositionNormalTangent3dUV, Tangent3d};
+
+/// Triangle list with vertices in which tangents are generated.
+struct Triangles<'a> {
+    vertices: &'a mut [PositionNormalTangent3dUV],
+    indices: Option<&'a [u32]>,
+}
+
+impl Triangles<'_> {
+    fn vertex(&self, face: usize, vert: usize) -> usize {
+        let index = face * 3 + vert;
+        match self.indices {
+            Some(indices) => indices[index] as usize,
+            None => index,
+        }
+    }
+}
+
+impl mikktspace::Geometry for Triangles<'_> {
+    fn num_faces(&self) -> usize {
+        match self.indices {
+            Some(indices) => indices.len() / 3,
+            None => self.vertices.len() / 3,
+        }
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.vertices[self.vertex(face, vert)].position.0
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.vertices[self.vertex(face, vert)].normal.0
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        self.vertices[self.vertex(face, vert)].uv.0
+    }
+
+    fn set_tangent_encoded(
+        &mut self,
+        tangent: [f32; 4],
+        face: usize,
+        vert: usize,
+    ) {
+        let vertex = self.vertex(face, vert);
+        self.vertices[vertex].tangent = Tangent3d(tangent);
+    }
+}
+
+/// Generates tangents for triangle list using MikkTSpace algorithm.
+/// Vertices shared between faces keep tangent of the last face.
+///
+/// Returns `false` if generation failed.
+pub fn generate_tangents(
+    vertices: &mut [PositionNormalTangent3dUV],
+    indices: Option<&[u32]>,
+) -> bool {
+    if let Some(indices) = indices {
+        if indices
+            .iter()
+            .any(|&index| index as usize >= vertices.len())
+        {
+            tracing::error!("Index is out of bounds of vertices");
+            return false;
+        }
+    }
+
+    mikktspace::generate_tangents(&mut Triangles { vertices, indices })
+}
@@ -33,7 +33,7 @@ impl SyncAsset for ImageAsset {
         ctx: &mut Context,
     ) -> Result<Self, CreateImageError> {
         let image = image.to_rgba8();
-        image_view_from_dyn_image(&DynamicImage::ImageRgba8(image), ctx)
+        image_view_from_dyn_image(&DynamicImage::ImageRgba8(image), false, ctx)
             .map(|image| ImageAsset { image })
     }
 }
@@ -60,13 +60,16 @@ impl<K> AssetDefaultFormat<K> for ImageAsset {
     type DefaultFormat = GuessImageFormat;
 }
 
+/// Creates sampled image with content of `image`.
+/// 8-bit images are created with sRGB format if `srgb` is set.
 pub fn image_view_from_dyn_image(
     image: &DynamicImage,
+    srgb: bool,
     ctx: &mut Context,
 ) -> Result<ImageView, CreateImageError> {
     use illume::Format;
 
-    let format = match &image {
+    let linear = match &image {
         DynamicImage::ImageLuma8(_) => Format::R8Unorm,
         DynamicImage::ImageLumaA8(_) => Format::RG8Unorm,
         DynamicImage::ImageRgb8(_) => Format::RGB8Unorm,
@@ -79,6 +82,15 @@ pub fn image_view_from_dyn_image(
         DynamicImage::ImageRgba16(_) => Format::RGBA16Unorm,
     };
 
+    let format = if srgb {
+        linear.to_srgb().unwrap_or_else(|| {
+            tracing::warn!("No sRGB counterpart for {:?}", linear);
+            linear
+        })
+    } else {
+        linear
+    };
+
     let (w, h) = image.dimensions();
 
     let bytes8;
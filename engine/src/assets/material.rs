@@ -1,7 +1,10 @@
 use {
     crate::{
         assets::{append_key, AssetKey, Assets, Handle, ImageAsset},
-        renderer::{Context, Material, Texture},
+        renderer::{
+            AlphaMode, Context, Material, ParameterBlock, Texture,
+            MATERIAL_SCALARS, MATERIAL_TEXTURES,
+        },
     },
     illume::{OutOfMemory, Sampler, SamplerInfo},
     ordered_float::OrderedFloat,
@@ -100,6 +103,20 @@ pub struct MaterialInfo {
 
     #[serde(default = "defaults::normal_factor")]
     pub normal_factor: OrderedFloat<f32>,
+
+    #[serde(default)]
+    pub alpha_mode: AlphaMode,
+
+    #[serde(default)]
+    pub double_sided: bool,
+
+    /// Custom scalar parameters in slot order.
+    #[serde(default)]
+    pub scalars: Vec<OrderedFloat<f32>>,
+
+    /// Custom textures in slot order.
+    #[serde(default)]
+    pub textures: Vec<TextureInfo>,
 }
 
 mod defaults {
@@ -132,6 +149,27 @@ impl MaterialInfo {
         prefix: Option<&AssetKey>,
         assets: &Assets,
     ) -> MaterialRepr {
+        if self.scalars.len() > MATERIAL_SCALARS {
+            tracing::warn!(
+                "Material declares {} scalars. Only {} are supported",
+                self.scalars.len(),
+                MATERIAL_SCALARS,
+            );
+        }
+
+        if self.textures.len() > MATERIAL_TEXTURES {
+            tracing::warn!(
+                "Material declares {} textures. Only {} are supported",
+                self.textures.len(),
+                MATERIAL_TEXTURES,
+            );
+        }
+
+        let mut scalars = [OrderedFloat(0.0); MATERIAL_SCALARS];
+        for (slot, value) in scalars.iter_mut().zip(self.scalars) {
+            *slot = value;
+        }
+
         MaterialRepr {
             albedo: self.albedo.map(|info| info.load(prefix, assets)),
             albedo_factor: self.albedo_factor,
@@ -144,6 +182,15 @@ impl MaterialInfo {
             emissive_factor: self.emissive_factor,
             normal: self.normal.map(|info| info.load(prefix, assets)),
             normal_factor: self.normal_factor,
+            alpha_mode: self.alpha_mode,
+            double_sided: self.double_sided,
+            scalars,
+            textures: self
+                .textures
+                .into_iter()
+                .take(MATERIAL_TEXTURES)
+                .map(|info| info.load(prefix, assets))
+                .collect(),
         }
     }
 }
@@ -159,6 +206,10 @@ pub struct MaterialRepr {
     pub emissive_factor: [OrderedFloat<f32>; 3],
     pub normal: Option<TextureRepr>,
     pub normal_factor: OrderedFloat<f32>,
+    pub alpha_mode: AlphaMode,
+    pub double_sided: bool,
+    pub scalars: [OrderedFloat<f32>; MATERIAL_SCALARS],
+    pub textures: Vec<TextureRepr>,
 }
 
 impl MaterialRepr {
@@ -188,6 +239,14 @@ impl MaterialRepr {
                 .map(|normal| normal.prebuild(ctx))
                 .transpose()?,
             normal_factor: self.normal_factor,
+            alpha_mode: self.alpha_mode,
+            double_sided: self.double_sided,
+            scalars: self.scalars,
+            textures: self
+                .textures
+                .into_iter()
+                .map(|texture| texture.prebuild(ctx))
+                .collect::<Result<_, _>>()?,
         })
     }
 }
@@ -203,10 +262,20 @@ pub struct MaterialPrebuild {
     pub emissive_factor: [OrderedFloat<f32>; 3],
     pub normal: Option<TexturePrebuild>,
     pub normal_factor: OrderedFloat<f32>,
+    pub alpha_mode: AlphaMode,
+    pub double_sided: bool,
+    pub scalars: [OrderedFloat<f32>; MATERIAL_SCALARS],
+    pub textures: Vec<TexturePrebuild>,
 }
 
 impl MaterialPrebuild {
     pub async fn finish(self) -> Result<Material, goods::Error> {
+        let mut params = ParameterBlock::new();
+        params.scalars = self.scalars;
+        for (slot, texture) in params.textures.iter_mut().zip(self.textures) {
+            *slot = Some(texture.finish().await?);
+        }
+
         Ok(Material {
             albedo: match self.albedo {
                 Some(albedo) => Some(albedo.finish().await?),
@@ -231,6 +300,10 @@ impl MaterialPrebuild {
                 None => None,
             },
             normal_factor: self.normal_factor,
+            alpha_mode: self.alpha_mode,
+            double_sided: self.double_sided,
+            params,
+            virtual_texture: None,
         })
     }
 }
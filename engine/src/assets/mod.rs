@@ -1,10 +1,11 @@
+mod blue_noise;
 mod gltf;
 mod image;
 mod material;
 mod terrain;
 
 pub use {
-    self::{gltf::*, image::*, material::*, terrain::*},
+    self::{blue_noise::*, gltf::*, image::*, material::*, terrain::*},
     goods::*,
 };
 
@@ -9,7 +9,7 @@ use {
         renderer::{
             Context, Material, Mesh, MeshBuilder, Normal3d, Position3d,
             PositionNormalTangent3dUV, Renderable, Tangent3d, VertexType as _,
-            UV,
+            VirtualTexture, VirtualTextureError, VirtualTextureInfo, UV,
         },
         scene::Global3,
     },
@@ -150,6 +150,7 @@ pub fn create_terrain_mesh(
             align: 255,
             size: total_size as u64,
             usage: buffer_usage,
+            capture_address: None,
         },
         &data,
     )?;
@@ -195,6 +196,7 @@ pub fn image_heightmap_alpha<P: Pixel>(
 pub struct TerrainRepr {
     heightmap: DynamicImage,
     material: MaterialRepr,
+    virtual_texture: Option<VirtualTextureInfo>,
     buffer_usage: BufferUsage,
     factor: f32,
 }
@@ -218,6 +220,12 @@ pub enum TerrainError {
         #[from]
         source: goods::Error,
     },
+
+    #[error("Failed to create virtual texture: `{source}`")]
+    VirtualTexture {
+        #[from]
+        source: VirtualTextureError,
+    },
 }
 
 impl From<OutOfMemory> for TerrainError {
@@ -243,11 +251,22 @@ impl Asset for TerrainAsset {
         let mesh = create_terrain_mesh(w, h, &f, repr.buffer_usage, ctx);
         let material = repr.material.prebuild(ctx);
 
+        // Terrain texture coordinates are grid coordinates.
+        let virtual_texture = repr.virtual_texture.map(|info| {
+            let uv_scale = 1.0 / (w.max(2) - 1) as f32;
+            VirtualTexture::new(info, uv_scale, ctx)
+        });
+
         Box::pin(async move {
+            let mut material = material?.finish().await?;
+            if let Some(virtual_texture) = virtual_texture {
+                material.virtual_texture = Some(virtual_texture?);
+            }
+
             Ok(TerrainAsset {
                 mesh: mesh?,
                 shape,
-                material: material?.finish().await?,
+                material,
             })
         })
     }
@@ -260,6 +279,11 @@ pub struct TerrainInfo {
     #[serde(flatten)]
     material: MaterialInfo,
 
+    /// Streaming albedo and normal layers.
+    /// Page key patterns are relative to terrain key.
+    #[serde(default)]
+    virtual_texture: Option<VirtualTextureInfo>,
+
     factor: f32,
 }
 
@@ -288,6 +312,14 @@ impl Format<TerrainAsset, AssetKey> for TerrainFormat {
             assets.load::<Box<[u8]>>(append_key(&key, &info.heightmap));
         let material = info.material.load(Some(&key), assets);
 
+        let virtual_texture = info.virtual_texture.map(|mut vt| {
+            vt.albedo = append_key(&key, &vt.albedo).to_string();
+            vt.normal = vt
+                .normal
+                .map(|normal| append_key(&key, &normal).to_string());
+            vt
+        });
+
         let mut buffer_usage = BufferUsage::empty();
 
         if self.raster {
@@ -308,6 +340,7 @@ impl Format<TerrainAsset, AssetKey> for TerrainFormat {
             Ok(TerrainRepr {
                 heightmap,
                 material,
+                virtual_texture,
                 buffer_usage,
                 factor,
             })
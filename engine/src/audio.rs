@@ -0,0 +1,85 @@
+//! Components that connect scene with audio playback.
+//!
+//! Renderer traces occlusion rays from listener to every `AudioSource`
+//! against scene acceleration structure and writes `AudioOcclusion`
+//! to source entities. Audio playback applies its gain and low-pass
+//! cutoff to the source's signal.
+
+/// Point that hears audio sources.
+/// Only first `AudioListener` in the world is used.
+/// Without one the camera of the view hears.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AudioListener;
+
+/// Point that emits audio.
+#[derive(Clone, Copy, Debug)]
+pub struct AudioSource {
+    /// Radius of the source.
+    /// Occlusion rays are spread over sphere of this radius,
+    /// so partially occluded sources are muffled partially.
+    pub radius: f32,
+}
+
+impl Default for AudioSource {
+    fn default() -> Self {
+        AudioSource { radius: 0.5 }
+    }
+}
+
+/// Cutoff frequency of low-pass filter for unoccluded source.
+/// Effectively disables filter.
+pub const LOW_PASS_OPEN: f32 = 20000.0;
+
+/// Cutoff frequency of low-pass filter for fully occluded source.
+pub const LOW_PASS_OCCLUDED: f32 = 800.0;
+
+/// Gain of fully occluded source.
+pub const OCCLUDED_GAIN: f32 = 0.3;
+
+/// Occlusion of audio source as seen from listener.
+///
+/// Written by renderer a couple of frames after source positions
+/// are sampled, as results are read back asynchronously.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AudioOcclusion {
+    /// Fraction of occlusion rays that reached the source.
+    pub visibility: f32,
+
+    /// Attenuation to apply to source.
+    pub gain: f32,
+
+    /// Cutoff frequency of low-pass filter in Hz.
+    pub low_pass: f32,
+}
+
+impl AudioOcclusion {
+    /// Unoccluded source.
+    pub const OPEN: Self = AudioOcclusion {
+        visibility: 1.0,
+        gain: 1.0,
+        low_pass: LOW_PASS_OPEN,
+    };
+
+    /// Returns occlusion parameters for fraction of rays
+    /// that reached the source.
+    pub fn from_visibility(visibility: f32) -> Self {
+        let visibility = visibility.max(0.0).min(1.0);
+
+        // Cutoff is interpolated in log space as pitch is perceived.
+        let low_pass = (LOW_PASS_OCCLUDED.ln()
+            + (LOW_PASS_OPEN.ln() - LOW_PASS_OCCLUDED.ln()) * visibility)
+            .exp();
+
+        AudioOcclusion {
+            visibility,
+            gain: OCCLUDED_GAIN + (1.0 - OCCLUDED_GAIN) * visibility,
+            low_pass,
+        }
+    }
+}
+
+impl Default for AudioOcclusion {
+    fn default() -> Self {
+        AudioOcclusion::OPEN
+    }
+}
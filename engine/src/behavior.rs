@@ -0,0 +1,665 @@
+//! Behavior trees for non-player characters.
+//!
+//! Entities with `Behavior` component are driven by behavior tree
+//! loaded from RON asset.
+//! Trees are built from composites (`Sequence`, `Selector`, `Utility`),
+//! decorators (`Invert`, `Succeed`, `Repeat`, `Cooldown`)
+//! and leaves (`Wait`, `Check`, `Set`, `MoveTo`, `Action`).
+//! Leaves communicate through entity's `Blackboard`.
+//! Custom leaves are registered with `BehaviorSystem::register_action`.
+//!
+//! Number of nodes evaluated per frame is limited by budget.
+//! Agents that don't fit into budget are ticked on next frames.
+
+use {
+    crate::{
+        assets::{AssetKey, Assets},
+        engine::{Engine, System, SystemContext},
+        navmesh::NavAgent,
+        scene::Global3,
+    },
+    flume::{Receiver, Sender},
+    hecs::{Entity, World},
+    nalgebra as na,
+    ordered_float::OrderedFloat,
+    std::{collections::HashMap, sync::Arc},
+    type_map::TypeMap,
+};
+
+/// Default number of nodes evaluated per frame.
+pub const DEFAULT_BUDGET: usize = 1024;
+
+/// Component that attaches behavior tree asset to an entity.
+#[derive(Clone, Debug)]
+pub struct Behavior {
+    pub key: AssetKey,
+}
+
+impl Behavior {
+    pub fn new(key: AssetKey) -> Self {
+        Behavior { key }
+    }
+}
+
+/// Value stored in blackboard.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Value {
+    Bool(bool),
+    Number(f64),
+    Point([f32; 3]),
+    Text(String),
+    #[serde(skip)]
+    Entity(Entity),
+}
+
+/// Per-entity memory shared by behavior tree nodes.
+/// Inserted by `BehaviorSystem` if missing.
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct Blackboard {
+    values: HashMap<String, Value>,
+}
+
+impl Blackboard {
+    pub fn new() -> Self {
+        Blackboard::default()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.values.get(key)
+    }
+
+    pub fn set(&mut self, key: impl Into<String>, value: Value) {
+        self.values.insert(key.into(), value);
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Value> {
+        self.values.remove(key)
+    }
+
+    pub fn number(&self, key: &str) -> Option<f64> {
+        match self.values.get(key)? {
+            Value::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    pub fn point(&self, key: &str) -> Option<na::Point3<f32>> {
+        match self.values.get(key)? {
+            Value::Point(point) => Some((*point).into()),
+            _ => None,
+        }
+    }
+}
+
+/// Result of node evaluation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Success,
+    Failure,
+    Running,
+}
+
+/// Behavior tree node as written in asset.
+#[derive(Clone, Debug, serde::Deserialize)]
+pub enum NodeDesc {
+    /// Runs children in order until one fails.
+    Sequence(Vec<NodeDesc>),
+
+    /// Runs children in order until one succeeds.
+    Selector(Vec<NodeDesc>),
+
+    /// Runs child with highest score.
+    /// Scores are numbers read from blackboard. Missing scores are zero.
+    Utility(Vec<UtilityDesc>),
+
+    /// Inverts result of child.
+    Invert(Box<NodeDesc>),
+
+    /// Succeeds when child completes.
+    Succeed(Box<NodeDesc>),
+
+    /// Repeats child until it fails or succeeds `count` times.
+    Repeat {
+        #[serde(default)]
+        count: Option<u32>,
+        node: Box<NodeDesc>,
+    },
+
+    /// Fails if child completed less than `seconds` ago.
+    Cooldown { seconds: f32, node: Box<NodeDesc> },
+
+    /// Keeps running for specified number of seconds.
+    Wait(f32),
+
+    /// Succeeds if blackboard value equals specified one.
+    /// Without value succeeds if key is set.
+    Check {
+        key: String,
+        #[serde(default)]
+        value: Option<Value>,
+    },
+
+    /// Writes value into blackboard.
+    Set { key: String, value: Value },
+
+    /// Moves entity with `NavAgent` to point stored under the key.
+    MoveTo(String),
+
+    /// Runs action registered with `BehaviorSystem::register_action`.
+    Action(String),
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct UtilityDesc {
+    /// Blackboard key of the score.
+    pub score: String,
+    pub node: NodeDesc,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BehaviorError {
+    #[error("Failed to load behavior tree: `{source}`")]
+    Load {
+        #[from]
+        source: goods::Error,
+    },
+
+    #[error("Failed to parse behavior tree: `{source}`")]
+    Parse {
+        #[from]
+        source: ron::Error,
+    },
+}
+
+/// Context passed to registered actions.
+pub struct ActionContext<'a> {
+    pub entity: Entity,
+
+    /// World to access entity components.
+    /// Entity's `Blackboard` is borrowed and must be accessed
+    /// through `blackboard` field.
+    pub world: &'a World,
+    pub blackboard: &'a mut Blackboard,
+    pub resources: &'a mut TypeMap,
+
+    /// Time since last tick of the entity.
+    pub delta: f32,
+}
+
+/// Leaf node behavior implemented in code.
+pub trait Action: 'static {
+    fn run(&mut self, ctx: ActionContext<'_>) -> Status;
+}
+
+impl<F> Action for F
+where
+    F: FnMut(ActionContext<'_>) -> Status + 'static,
+{
+    fn run(&mut self, ctx: ActionContext<'_>) -> Status {
+        self(ctx)
+    }
+}
+
+/// Behavior tree compiled into flat array of nodes.
+#[derive(Debug)]
+struct Tree {
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug)]
+enum Node {
+    Sequence(Vec<usize>),
+    Selector(Vec<usize>),
+    Utility(Vec<(String, usize)>),
+    Invert(usize),
+    Succeed(usize),
+    Repeat(Option<u32>, usize),
+    Cooldown(f32, usize),
+    Wait(f32),
+    Check(String, Option<Value>),
+    Set(String, Value),
+    MoveTo(String),
+    Action(String),
+}
+
+impl Tree {
+    fn compile(root: NodeDesc) -> Self {
+        let mut nodes = Vec::new();
+        compile_node(root, &mut nodes);
+        Tree { nodes }
+    }
+}
+
+/// Compiles node and returns its index.
+/// Root node always gets index 0.
+fn compile_node(desc: NodeDesc, nodes: &mut Vec<Node>) -> usize {
+    let index = nodes.len();
+    nodes.push(Node::Wait(0.0));
+
+    let children = |descs: Vec<NodeDesc>, nodes: &mut Vec<Node>| -> Vec<_> {
+        descs
+            .into_iter()
+            .map(|desc| compile_node(desc, nodes))
+            .collect()
+    };
+
+    let node = match desc {
+        NodeDesc::Sequence(descs) => Node::Sequence(children(descs, nodes)),
+        NodeDesc::Selector(descs) => Node::Selector(children(descs, nodes)),
+        NodeDesc::Utility(options) => Node::Utility(
+            options
+                .into_iter()
+                .map(|option| (option.score, compile_node(option.node, nodes)))
+                .collect(),
+        ),
+        NodeDesc::Invert(desc) => Node::Invert(compile_node(*desc, nodes)),
+        NodeDesc::Succeed(desc) => Node::Succeed(compile_node(*desc, nodes)),
+        NodeDesc::Repeat { count, node } => {
+            Node::Repeat(count, compile_node(*node, nodes))
+        }
+        NodeDesc::Cooldown { seconds, node } => {
+            Node::Cooldown(seconds, compile_node(*node, nodes))
+        }
+        NodeDesc::Wait(seconds) => Node::Wait(seconds),
+        NodeDesc::Check { key, value } => Node::Check(key, value),
+        NodeDesc::Set { key, value } => Node::Set(key, value),
+        NodeDesc::MoveTo(key) => Node::MoveTo(key),
+        NodeDesc::Action(name) => Node::Action(name),
+    };
+
+    nodes[index] = node;
+    index
+}
+
+/// Evaluation state of a node.
+#[derive(Clone, Copy, Debug, Default)]
+struct NodeState {
+    /// Running child for composites, repetitions for `Repeat`
+    /// or movement flag for `MoveTo`.
+    counter: usize,
+
+    /// Elapsed time for `Wait`.
+    time: f32,
+
+    /// Time when `Cooldown` allows to run child again.
+    /// Kept when node completes.
+    ready: f32,
+}
+
+/// Evaluation state of an agent.
+/// Inserted by `BehaviorSystem`.
+struct AgentState {
+    tree: Arc<Tree>,
+    nodes: Vec<NodeState>,
+    time: f32,
+    delta: f32,
+}
+
+impl AgentState {
+    fn new(tree: Arc<Tree>) -> Self {
+        AgentState {
+            nodes: vec![NodeState::default(); tree.nodes.len()],
+            tree,
+            time: 0.0,
+            delta: 0.0,
+        }
+    }
+}
+
+/// System that evaluates behavior trees.
+pub struct BehaviorSystem {
+    assets: Assets,
+    trees: HashMap<AssetKey, Option<Arc<Tree>>>,
+    actions: HashMap<String, Box<dyn Action>>,
+    sender: Sender<(AssetKey, Result<NodeDesc, BehaviorError>)>,
+    receiver: Receiver<(AssetKey, Result<NodeDesc, BehaviorError>)>,
+    budget: usize,
+    cursor: usize,
+}
+
+impl BehaviorSystem {
+    pub fn new(engine: &Engine) -> Self {
+        let (sender, receiver) = flume::unbounded();
+
+        BehaviorSystem {
+            assets: engine.assets.clone(),
+            trees: HashMap::new(),
+            actions: HashMap::new(),
+            sender,
+            receiver,
+            budget: DEFAULT_BUDGET,
+            cursor: 0,
+        }
+    }
+
+    /// Sets maximum number of nodes evaluated per frame.
+    pub fn with_budget(mut self, budget: usize) -> Self {
+        self.budget = budget.max(1);
+        self
+    }
+
+    /// Registers action available as `Action(name)` leaf.
+    pub fn register_action(&mut self, name: &str, action: impl Action) {
+        self.actions.insert(name.to_owned(), Box::new(action));
+    }
+
+    fn request_load(&mut self, key: AssetKey) {
+        tracing::info!("Loading behavior tree '{}'", key);
+
+        self.trees.insert(key.clone(), None);
+
+        let assets = self.assets.clone();
+        let sender = self.sender.clone();
+
+        smol::spawn(async move {
+            let result = async {
+                let bytes = assets.load::<Box<[u8]>>(key.clone()).await?;
+                Ok::<_, BehaviorError>(ron::de::from_bytes(&bytes)?)
+            }
+            .await;
+
+            let _ = sender.send((key, result));
+        })
+        .detach();
+    }
+
+    fn compile_loaded(&mut self) {
+        for (key, result) in self.receiver.try_iter() {
+            match result {
+                Ok(root) => {
+                    let tree = Tree::compile(root);
+                    self.trees.insert(key, Some(Arc::new(tree)));
+                }
+                Err(err) => {
+                    tracing::error!(
+                        "Failed to load behavior tree '{}': {}",
+                        key,
+                        err
+                    );
+                }
+            }
+        }
+    }
+}
+
+impl System for BehaviorSystem {
+    fn run(&mut self, ctx: SystemContext<'_>) {
+        self.compile_loaded();
+
+        let delta = ctx.clocks.sim_delta.as_secs_f32();
+
+        let agents: Vec<(Entity, AssetKey)> = ctx
+            .world
+            .query::<&Behavior>()
+            .iter()
+            .map(|(entity, behavior)| (entity, behavior.key.clone()))
+            .collect();
+
+        let mut insert = Vec::new();
+
+        for (entity, key) in &agents {
+            let tree = match self.trees.get(key) {
+                Some(Some(tree)) => tree,
+                Some(None) => continue,
+                None => {
+                    self.request_load(key.clone());
+                    continue;
+                }
+            };
+
+            match ctx.world.get_mut::<AgentState>(*entity) {
+                Ok(mut state) if Arc::ptr_eq(&state.tree, tree) => {
+                    state.delta += delta;
+                }
+                _ => insert.push((*entity, AgentState::new(tree.clone()))),
+            }
+        }
+
+        for (entity, state) in insert {
+            let _ = ctx.world.insert_one(entity, state);
+            if ctx.world.get::<Blackboard>(entity).is_err() {
+                let _ = ctx.world.insert_one(entity, Blackboard::new());
+            }
+        }
+
+        if agents.is_empty() {
+            return;
+        }
+
+        let mut budget = self.budget;
+        let start = self.cursor % agents.len();
+        let mut ticked = 0;
+
+        while ticked < agents.len() && budget > 0 {
+            let (entity, _) = agents[(start + ticked) % agents.len()];
+            ticked += 1;
+
+            let world = &*ctx.world;
+            let (mut state, mut blackboard) = match (
+                world.get_mut::<AgentState>(entity),
+                world.get_mut::<Blackboard>(entity),
+            ) {
+                (Ok(state), Ok(blackboard)) => (state, blackboard),
+                _ => continue,
+            };
+
+            let state = &mut *state;
+            state.time += state.delta;
+
+            let mut tick = Tick {
+                tree: &state.tree,
+                nodes: &mut state.nodes,
+                entity,
+                world,
+                blackboard: &mut *blackboard,
+                resources: &mut *ctx.resources,
+                actions: &mut self.actions,
+                delta: state.delta,
+                time: state.time,
+                budget: &mut budget,
+            };
+
+            tick.run(0);
+            state.delta = 0.0;
+        }
+
+        self.cursor = start + ticked;
+    }
+}
+
+struct Tick<'a> {
+    tree: &'a Tree,
+    nodes: &'a mut [NodeState],
+    entity: Entity,
+    world: &'a World,
+    blackboard: &'a mut Blackboard,
+    resources: &'a mut TypeMap,
+    actions: &'a mut HashMap<String, Box<dyn Action>>,
+    delta: f32,
+    time: f32,
+    budget: &'a mut usize,
+}
+
+impl Tick<'_> {
+    /// Evaluates node.
+    /// Returns `Running` without evaluation if budget is exhausted.
+    fn run(&mut self, index: usize) -> Status {
+        if *self.budget == 0 {
+            return Status::Running;
+        }
+        *self.budget -= 1;
+
+        let status = self.eval(index);
+        if status != Status::Running {
+            self.nodes[index] = NodeState {
+                ready: self.nodes[index].ready,
+                ..NodeState::default()
+            };
+        }
+        status
+    }
+
+    fn eval(&mut self, index: usize) -> Status {
+        let tree = self.tree;
+
+        match &tree.nodes[index] {
+            Node::Sequence(children) => {
+                self.composite(index, children, Status::Success)
+            }
+            Node::Selector(children) => {
+                self.composite(index, children, Status::Failure)
+            }
+            Node::Utility(options) => {
+                if self.nodes[index].counter == 0 {
+                    let best = options
+                        .iter()
+                        .enumerate()
+                        .max_by_key(|(_, (score, _))| {
+                            OrderedFloat(
+                                self.blackboard.number(score).unwrap_or(0.0),
+                            )
+                        })
+                        .map(|(i, _)| i);
+
+                    match best {
+                        Some(best) => self.nodes[index].counter = best + 1,
+                        None => return Status::Failure,
+                    }
+                }
+
+                let child = options[self.nodes[index].counter - 1].1;
+                self.run(child)
+            }
+            Node::Invert(child) => match self.run(*child) {
+                Status::Success => Status::Failure,
+                Status::Failure => Status::Success,
+                Status::Running => Status::Running,
+            },
+            Node::Succeed(child) => match self.run(*child) {
+                Status::Running => Status::Running,
+                _ => Status::Success,
+            },
+            Node::Repeat(count, child) => match self.run(*child) {
+                Status::Success => {
+                    self.nodes[index].counter += 1;
+                    match count {
+                        Some(count)
+                            if self.nodes[index].counter >= *count as usize =>
+                        {
+                            Status::Success
+                        }
+                        // Next repetition starts on next tick.
+                        _ => Status::Running,
+                    }
+                }
+                status => status,
+            },
+            Node::Cooldown(seconds, child) => {
+                if self.nodes[index].ready > self.time {
+                    return Status::Failure;
+                }
+
+                let status = self.run(*child);
+                if status != Status::Running {
+                    self.nodes[index].ready = self.time + seconds;
+                }
+                status
+            }
+            Node::Wait(seconds) => {
+                let state = &mut self.nodes[index];
+                state.time += self.delta;
+                if state.time >= *seconds {
+                    Status::Success
+                } else {
+                    Status::Running
+                }
+            }
+            Node::Check(key, value) => {
+                let passed = match (self.blackboard.get(key), value) {
+                    (Some(_), None) => true,
+                    (Some(stored), Some(value)) => stored == value,
+                    (None, _) => false,
+                };
+
+                if passed {
+                    Status::Success
+                } else {
+                    Status::Failure
+                }
+            }
+            Node::Set(key, value) => {
+                self.blackboard.set(key.clone(), value.clone());
+                Status::Success
+            }
+            Node::MoveTo(key) => self.move_to(index, key),
+            Node::Action(name) => match self.actions.get_mut(name) {
+                Some(action) => action.run(ActionContext {
+                    entity: self.entity,
+                    world: self.world,
+                    blackboard: &mut *self.blackboard,
+                    resources: &mut *self.resources,
+                    delta: self.delta,
+                }),
+                None => {
+                    tracing::warn!(
+                        "Behavior action '{}' is not registered",
+                        name
+                    );
+                    Status::Failure
+                }
+            },
+        }
+    }
+
+    /// Runs children in order while they return `proceed`.
+    fn composite(
+        &mut self,
+        index: usize,
+        children: &[usize],
+        proceed: Status,
+    ) -> Status {
+        while let Some(&child) = children.get(self.nodes[index].counter) {
+            match self.run(child) {
+                Status::Running => return Status::Running,
+                status if status != proceed => return status,
+                _ => self.nodes[index].counter += 1,
+            }
+        }
+        proceed
+    }
+
+    fn move_to(&mut self, index: usize, key: &str) -> Status {
+        let target = match self.blackboard.point(key) {
+            Some(target) => target,
+            None => return Status::Failure,
+        };
+
+        let mut agent = match self.world.get_mut::<NavAgent>(self.entity) {
+            Ok(agent) => agent,
+            Err(_) => return Status::Failure,
+        };
+
+        if self.nodes[index].counter == 0 {
+            agent.set_target(target);
+            self.nodes[index].counter = 1;
+            return Status::Running;
+        }
+
+        if agent.target().is_some() {
+            return Status::Running;
+        }
+
+        // Agent stopped. Check whether target is reached.
+        let position = match self.world.get::<Global3>(self.entity) {
+            Ok(global) => na::Point3::from(global.iso.translation.vector),
+            Err(_) => return Status::Failure,
+        };
+
+        let offset = target - position;
+        if offset.x.hypot(offset.z) <= agent.tolerance * 2.0 {
+            Status::Success
+        } else {
+            Status::Failure
+        }
+    }
+}
@@ -1,6 +1,10 @@
 use {
     super::Camera,
-    crate::engine::{System, SystemContext},
+    crate::{
+        engine::{System, SystemContext},
+        physics::SceneQuery,
+        scene::Global3,
+    },
     hecs::Entity,
     nalgebra as na,
     std::f32::consts::FRAC_PI_2,
@@ -24,7 +28,10 @@ bitflags::bitflags! {
     }
 }
 
-/// System to fly camera freely.
+/// System to move camera behind followed entity.
+///
+/// Camera position is smoothed with spring-damper
+/// and pulled in when geometry blocks the view.
 pub struct FollowingCameraSystem {
     pitch: f32,
     yaw: f32,
@@ -33,6 +40,16 @@ pub struct FollowingCameraSystem {
     yaw_factor: f32,
     speed: f32,
     direction: Direction,
+
+    stiffness: f32,
+    damping: f32,
+    look_ahead: f32,
+    collision_radius: f32,
+    min_distance: f32,
+
+    position: Option<na::Point3<f32>>,
+    velocity: na::Vector3<f32>,
+    target: Option<na::Point3<f32>>,
 }
 
 impl FollowingCameraSystem {
@@ -45,6 +62,16 @@ impl FollowingCameraSystem {
             yaw_factor: 1.0,
             speed: 1.0,
             direction: Direction::empty(),
+
+            stiffness: 50.0,
+            damping: 2.0 * 50f32.sqrt(),
+            look_ahead: 0.0,
+            collision_radius: 0.2,
+            min_distance: 0.5,
+
+            position: None,
+            velocity: na::Vector3::zeros(),
+            target: None,
         }
     }
 
@@ -58,6 +85,29 @@ impl FollowingCameraSystem {
         self.speed = speed;
         self
     }
+
+    /// Sets spring-damper parameters for camera smoothing.
+    /// Damping of `2 * sqrt(stiffness)` is critical.
+    pub fn with_spring(mut self, stiffness: f32, damping: f32) -> Self {
+        self.stiffness = stiffness;
+        self.damping = damping;
+        self
+    }
+
+    /// Sets how far ahead of followed entity camera looks,
+    /// in seconds of its current velocity.
+    pub fn with_look_ahead(mut self, look_ahead: f32) -> Self {
+        self.look_ahead = look_ahead;
+        self
+    }
+
+    /// Sets radius of the sphere cast to keep camera out of geometry
+    /// and minimal distance camera can be pulled in to.
+    pub fn with_collision(mut self, radius: f32, min_distance: f32) -> Self {
+        self.collision_radius = radius;
+        self.min_distance = min_distance;
+        self
+    }
 }
 
 impl System for FollowingCameraSystem {
@@ -124,34 +174,77 @@ impl System for FollowingCameraSystem {
         let found = world
             .query::<&FollowingCamera>()
             .with::<Camera>()
-            .with::<na::Isometry3<f32>>()
+            .with::<Global3>()
             .iter()
             .next()
             .map(|(e, f)| (e, *f));
 
-        if let Some((camera, following)) = found {
-            let mut iso = world
-                .get::<na::Isometry3<f32>>(following.follows)
-                .ok()
-                .as_deref()
-                .cloned()
-                .unwrap_or_else(na::Isometry3::identity);
-
-            let rotation = na::UnitQuaternion::from_euler_angles(
-                0.0,
-                -self.pitch,
-                self.yaw,
-            );
-
-            let translation =
-                rotation.transform_vector(&na::Vector3::z_axis()).into();
-
-            iso *= na::Isometry3 {
-                rotation,
-                translation,
-            };
-
-            *world.get_mut::<na::Isometry3<f32>>(camera).unwrap() = iso;
+        let (camera, following) = match found {
+            Some(found) => found,
+            None => return,
+        };
+
+        let target = match world.get::<Global3>(following.follows) {
+            Ok(global) => global.iso,
+            Err(_) => return,
+        };
+
+        let target_position = na::Point3::from(target.translation.vector);
+
+        // Look ahead along followed entity velocity.
+        let target_velocity = match self.target.replace(target_position) {
+            Some(prev) if delta > 0.0 => (target_position - prev) / delta,
+            _ => na::Vector3::zeros(),
+        };
+        let focus = target_position + target_velocity * self.look_ahead;
+
+        let rotation = target.rotation
+            * na::UnitQuaternion::from_euler_angles(0.0, -self.pitch, self.yaw);
+
+        let desired = focus
+            + rotation.transform_vector(&na::Vector3::z()) * self.distance;
+
+        // Spring-damper towards desired position.
+        let mut position = match self.position {
+            Some(position) => {
+                let offset = desired - position;
+                let acceleration =
+                    offset * self.stiffness - self.velocity * self.damping;
+                self.velocity += acceleration * delta;
+                position + self.velocity * delta
+            }
+            None => desired,
+        };
+
+        // Pull camera in front of geometry between it and followed entity.
+        if let Some(query) = ctx.resources.get::<SceneQuery>() {
+            let offset = position - focus;
+            let distance = offset.norm();
+
+            if distance > 0.0 {
+                let direction = na::Unit::new_unchecked(offset / distance);
+
+                if let Some(toi) = query.cast_sphere(
+                    &focus,
+                    &direction,
+                    self.collision_radius,
+                    distance,
+                    &[following.follows],
+                ) {
+                    position = focus
+                        + direction.into_inner()
+                            * toi.max(self.min_distance.min(distance));
+                    self.velocity = na::Vector3::zeros();
+                }
+            }
         }
+
+        self.position = Some(position);
+
+        // Camera looks along negative Z axis.
+        let view =
+            na::Isometry3::look_at_rh(&position, &focus, &na::Vector3::y());
+
+        world.get_mut::<Global3>(camera).unwrap().iso = view.inverse();
     }
 }
@@ -1,12 +1,22 @@
 pub mod following;
 pub mod free;
+pub mod rig;
 
 use nalgebra as na;
 
+/// Camera projection.
+///
+/// Renderer unprojects pixels with inverse of the projection,
+/// so any invertible projection is supported.
 #[derive(Clone, Copy, Debug)]
 pub enum Camera {
     Perspective(na::Perspective3<f32>),
+
+    /// Parallel projection, e.g. for top-down views.
     Orthographic(na::Orthographic3<f32>),
+
+    /// Custom projection matrix, e.g. oblique projection.
+    /// Must map view space to clip space with depth in `-1..1`.
     Matrix(na::Projective3<f32>),
 }
 
@@ -19,3 +29,63 @@ impl Camera {
         }
     }
 }
+
+/// Physical camera parameters.
+///
+/// Attach to camera entity to control exposure and depth of field.
+/// Cameras without settings are pinhole cameras with unit exposure.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Deserialize)]
+#[serde(default)]
+pub struct CameraSettings {
+    /// Aperture as f-number.
+    pub aperture: f32,
+
+    /// Shutter time in seconds.
+    pub shutter: f32,
+
+    /// Sensor sensitivity.
+    pub iso: f32,
+
+    /// Distance to the plane in focus.
+    pub focus_distance: f32,
+
+    /// Focal length of the lens.
+    /// Uses the same units as focus distance.
+    pub focal_length: f32,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        CameraSettings::new()
+    }
+}
+
+impl CameraSettings {
+    pub const fn new() -> Self {
+        CameraSettings {
+            aperture: 16.0,
+            shutter: 1.0 / 100.0,
+            iso: 100.0,
+            focus_distance: 10.0,
+            focal_length: 0.05,
+        }
+    }
+
+    /// Returns exposure value at ISO 100.
+    pub fn ev100(&self) -> f32 {
+        (self.aperture * self.aperture / self.shutter * 100.0 / self.iso).log2()
+    }
+
+    /// Returns multiplier for scene radiance.
+    ///
+    /// Exposure is relative to default settings,
+    /// so default camera keeps radiance unchanged.
+    pub fn exposure(&self) -> f32 {
+        (CameraSettings::new().ev100() - self.ev100()).exp2()
+    }
+
+    /// Returns radius of the lens for depth of field.
+    pub fn lens_radius(&self) -> f32 {
+        self.focal_length / (2.0 * self.aperture)
+    }
+}
@@ -0,0 +1,382 @@
+use {
+    crate::{
+        engine::{System, SystemContext},
+        scene::Global3,
+    },
+    nalgebra as na,
+};
+
+/// Easing function applied to interpolation between keyframes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Default for Easing {
+    fn default() -> Self {
+        Easing::Linear
+    }
+}
+
+impl Easing {
+    /// Maps `t` in `0..=1` to eased value in `0..=1`.
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.min(1.0).max(0.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// Keyframe of the camera path.
+#[derive(Clone, Copy, Debug)]
+pub struct CameraKey {
+    /// Time of the keyframe in seconds since path start.
+    pub time: f32,
+
+    /// Camera position.
+    pub position: na::Point3<f32>,
+
+    /// Point camera looks at.
+    pub target: na::Point3<f32>,
+
+    /// Easing of the segment that starts at this keyframe.
+    pub easing: Easing,
+}
+
+/// Cinematic camera path component.
+///
+/// Moves rigged camera along Catmull-Rom spline through keyframes.
+/// Look-at target is interpolated the same way.
+#[derive(Clone, Debug)]
+pub struct CameraPath {
+    keys: Vec<CameraKey>,
+    time: f32,
+    looping: bool,
+}
+
+impl CameraPath {
+    pub fn new(mut keys: Vec<CameraKey>) -> Self {
+        keys.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+
+        CameraPath {
+            keys,
+            time: 0.0,
+            looping: false,
+        }
+    }
+
+    /// Makes path restart after last keyframe.
+    pub fn with_looping(mut self) -> Self {
+        self.looping = true;
+        self
+    }
+
+    /// Returns time of the last keyframe.
+    pub fn duration(&self) -> f32 {
+        self.keys.last().map_or(0.0, |key| key.time)
+    }
+
+    /// Returns time since path start.
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Returns `true` if non-looping path reached its last keyframe.
+    pub fn is_finished(&self) -> bool {
+        !self.looping && self.time >= self.duration()
+    }
+
+    /// Rewinds path to the start.
+    pub fn restart(&mut self) {
+        self.time = 0.0;
+    }
+
+    /// Advances path time.
+    pub fn advance(&mut self, delta: f32) {
+        self.time += delta;
+
+        let duration = self.duration();
+        if self.looping && duration > 0.0 {
+            self.time %= duration;
+        }
+    }
+
+    /// Returns camera pose at current time.
+    /// Returns `None` if path has no keyframes.
+    pub fn sample(&self) -> Option<na::Isometry3<f32>> {
+        let last = self.keys.len().checked_sub(1)?;
+        let time = self.time.min(self.duration());
+
+        let segment = self.keys[..last]
+            .iter()
+            .rposition(|key| key.time <= time)
+            .unwrap_or(0);
+
+        let next = (segment + 1).min(last);
+        let prev = segment.saturating_sub(1);
+        let after = (segment + 2).min(last);
+
+        let start = &self.keys[segment];
+        let end = &self.keys[next];
+
+        let length = end.time - start.time;
+        let t = if length > 0.0 {
+            start.easing.apply((time - start.time) / length)
+        } else {
+            0.0
+        };
+
+        let position = catmull_rom(
+            &self.keys[prev].position,
+            &start.position,
+            &end.position,
+            &self.keys[after].position,
+            t,
+        );
+
+        let target = catmull_rom(
+            &self.keys[prev].target,
+            &start.target,
+            &end.target,
+            &self.keys[after].target,
+            t,
+        );
+
+        // Camera looks along negative Z axis.
+        let view =
+            na::Isometry3::look_at_rh(&position, &target, &na::Vector3::y());
+
+        Some(view.inverse())
+    }
+}
+
+/// Trauma-based camera shake component.
+///
+/// Impacts add trauma that decays over time.
+/// Shake magnitude is proportional to squared trauma.
+#[derive(Clone, Copy, Debug)]
+pub struct CameraShake {
+    trauma: f32,
+    time: f32,
+
+    /// Trauma lost per second.
+    pub decay: f32,
+
+    /// Maximum rotation in radians at full trauma.
+    pub max_angle: f32,
+
+    /// Maximum offset at full trauma.
+    pub max_offset: f32,
+
+    /// Shake oscillation frequency in hertz.
+    pub frequency: f32,
+}
+
+impl Default for CameraShake {
+    fn default() -> Self {
+        CameraShake::new()
+    }
+}
+
+impl CameraShake {
+    pub const fn new() -> Self {
+        CameraShake {
+            trauma: 0.0,
+            time: 0.0,
+            decay: 1.0,
+            max_angle: 0.1,
+            max_offset: 0.1,
+            frequency: 15.0,
+        }
+    }
+
+    /// Adds trauma.
+    /// Total trauma is clamped to `1.0`.
+    pub fn add_trauma(&mut self, trauma: f32) {
+        self.trauma = (self.trauma + trauma).min(1.0).max(0.0);
+    }
+
+    pub fn trauma(&self) -> f32 {
+        self.trauma
+    }
+
+    /// Advances shake time and decays trauma.
+    pub fn advance(&mut self, delta: f32) {
+        self.time += delta;
+        self.trauma = (self.trauma - self.decay * delta).max(0.0);
+    }
+
+    /// Returns offset to apply to camera pose.
+    pub fn offset(&self) -> na::Isometry3<f32> {
+        let shake = self.trauma * self.trauma;
+        if shake <= 0.0 {
+            return na::Isometry3::identity();
+        }
+
+        let t = self.time * self.frequency * 2.0 * std::f32::consts::PI;
+
+        let rotation = na::UnitQuaternion::from_euler_angles(
+            self.max_angle * shake * wobble(t, 0.0),
+            self.max_angle * shake * wobble(t, 1.0),
+            self.max_angle * shake * wobble(t, 2.0),
+        );
+
+        let translation = na::Translation3::new(
+            self.max_offset * shake * wobble(t, 3.0),
+            self.max_offset * shake * wobble(t, 4.0),
+            self.max_offset * shake * wobble(t, 5.0),
+        );
+
+        na::Isometry3::from_parts(translation, rotation)
+    }
+}
+
+/// Camera rig component.
+///
+/// Rig combines pose of gameplay camera with `CameraPath` and `CameraShake`
+/// components of the same entity.
+/// Renderer uses rig pose instead of camera's `Global3`,
+/// so camera controllers are not affected by the rig.
+#[derive(Clone, Copy, Debug)]
+pub struct CameraRig {
+    /// Time in seconds to blend between gameplay camera and path.
+    pub blend_time: f32,
+
+    weight: f32,
+    pose: Option<Global3>,
+}
+
+impl Default for CameraRig {
+    fn default() -> Self {
+        CameraRig::new()
+    }
+}
+
+impl CameraRig {
+    pub const fn new() -> Self {
+        CameraRig {
+            blend_time: 1.0,
+            weight: 0.0,
+            pose: None,
+        }
+    }
+
+    pub fn with_blend_time(mut self, blend_time: f32) -> Self {
+        self.blend_time = blend_time;
+        self
+    }
+
+    /// Returns weight of the path pose in final pose.
+    pub fn weight(&self) -> f32 {
+        self.weight
+    }
+
+    /// Returns final camera pose.
+    /// Returns `None` until rig system runs.
+    pub fn pose(&self) -> Option<&Global3> {
+        self.pose.as_ref()
+    }
+}
+
+/// System that updates camera rigs.
+///
+/// Must run after systems that move gameplay cameras.
+pub struct CameraRigSystem;
+
+impl CameraRigSystem {
+    pub fn new() -> Self {
+        CameraRigSystem
+    }
+}
+
+impl System for CameraRigSystem {
+    fn run(&mut self, ctx: SystemContext<'_>) {
+        let delta = ctx.clocks.delta.as_secs_f32();
+
+        let mut query = ctx.world.query::<(
+            &Global3,
+            &mut CameraRig,
+            Option<&mut CameraPath>,
+            Option<&mut CameraShake>,
+        )>();
+
+        for (_, (global, rig, path, shake)) in query.iter() {
+            let mut iso = global.iso;
+
+            // Blend into path while it plays and out when it is finished.
+            let path_pose = match path {
+                Some(path) => {
+                    path.advance(delta);
+                    let active = !path.is_finished();
+                    path.sample().map(|pose| (pose, active))
+                }
+                None => None,
+            };
+
+            let target = match path_pose {
+                Some((_, true)) => 1.0,
+                _ => 0.0,
+            };
+
+            rig.weight = if rig.blend_time > 0.0 {
+                let step = delta / rig.blend_time;
+                if rig.weight < target {
+                    (rig.weight + step).min(target)
+                } else {
+                    (rig.weight - step).max(target)
+                }
+            } else {
+                target
+            };
+
+            if let Some((pose, _)) = path_pose {
+                let weight = Easing::EaseInOut.apply(rig.weight);
+                iso = iso.lerp_slerp(&pose, weight);
+            }
+
+            if let Some(shake) = shake {
+                shake.advance(delta);
+                iso *= shake.offset();
+            }
+
+            rig.pose = Some(Global3 {
+                iso,
+                skew: global.skew,
+            });
+        }
+    }
+}
+
+/// Returns smooth pseudo-random value in `-1..=1`.
+fn wobble(t: f32, seed: f32) -> f32 {
+    let phase = seed * 1.618;
+    ((t + phase).sin() + (t * 2.31 + phase * 3.7).sin() * 0.5) / 1.5
+}
+
+/// Evaluates uniform Catmull-Rom spline segment between `p1` and `p2`.
+fn catmull_rom(
+    p0: &na::Point3<f32>,
+    p1: &na::Point3<f32>,
+    p2: &na::Point3<f32>,
+    p3: &na::Point3<f32>,
+    t: f32,
+) -> na::Point3<f32> {
+    let t2 = t * t;
+    let t3 = t2 * t;
+
+    let v = (p1.coords * 2.0
+        + (p2.coords - p0.coords) * t
+        + (p0.coords * 2.0 - p1.coords * 5.0 + p2.coords * 4.0 - p3.coords)
+            * t2
+        + (p1.coords * 3.0 - p0.coords - p2.coords * 3.0 + p3.coords) * t3)
+        * 0.5;
+
+    na::Point3::from(v)
+}
@@ -10,8 +10,45 @@ pub struct Clocks {
     /// Instant of last step.
     last: Instant,
 
-    /// Instant of last fixed step.
-    last_fixed: Instant,
+    /// Simulation time elapsed since clocks start.
+    sim: Duration,
+
+    /// Simulation time of last fixed step.
+    last_fixed: Duration,
+
+    /// Factor applied to real time to get simulation time.
+    scale: f32,
+}
+
+/// Resource that controls rate at which simulation time flows.
+/// Scale of `0.5` slows simulation down twice and `0.0` pauses it.
+///
+/// Only simulation deltas are scaled.
+/// Renderer and camera controllers keep running on real time
+/// so menus and photo mode stay responsive while simulation is frozen.
+#[derive(Clone, Copy, Debug)]
+pub struct TimeScale {
+    pub scale: f32,
+}
+
+impl Default for TimeScale {
+    fn default() -> Self {
+        TimeScale::new()
+    }
+}
+
+impl TimeScale {
+    pub const fn new() -> Self {
+        TimeScale { scale: 1.0 }
+    }
+
+    pub const fn paused() -> Self {
+        TimeScale { scale: 0.0 }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.scale <= 0.0
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -24,6 +61,14 @@ pub struct ClockIndex {
 
     /// Instant when clocks were started.
     pub start: Instant,
+
+    /// Simulation time delta since previous step.
+    /// This is `delta` multiplied by time scale.
+    pub sim_delta: Duration,
+
+    /// Point on simulation timeline of this step.
+    /// Starts at `start` and advances by `sim_delta` each step.
+    pub sim_step: Instant,
 }
 
 impl Clocks {
@@ -36,10 +81,19 @@ impl Clocks {
         Clocks {
             start: now,
             last: now,
-            last_fixed: now,
+            sim: Duration::from_secs(0),
+            last_fixed: Duration::from_secs(0),
+            scale: 1.0,
         }
     }
 
+    /// Sets factor applied to real time to get simulation time.
+    /// Takes effect on next step.
+    /// Negative scale is treated as zero.
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale.max(0.0);
+    }
+
     /// Advances clocks step.
     /// Step timestamp monotonically increases.
     /// It  case it can be the same as previous step.
@@ -61,15 +115,24 @@ impl Clocks {
         let now = Instant::now();
         let delta = now - self.last;
         self.last = now;
+
+        let sim_delta = delta.mul_f32(self.scale);
+        self.sim += sim_delta;
+
         ClockIndex {
             delta,
             step: self.last,
             start: self.start,
+            sim_delta,
+            sim_step: self.start + self.sim,
         }
     }
 
     /// Advances clocks with fixed steps.
     /// Returns iterator over fixed steps clock indices.
+    /// Fixed steps follow simulation time up to last `step` call,
+    /// so no steps are produced while clocks are paused.
+    /// Both `step` and `sim_step` of fixed steps are on simulation timeline.
     ///
     /// # Example
     /// ```
@@ -85,7 +148,7 @@ impl Clocks {
     /// }
     /// ```
     pub fn fixed_steps(&mut self, fixed: Duration) -> FixedClockStepIter<'_> {
-        let now = Instant::now();
+        let now = self.sim;
         FixedClockStepIter {
             clocks: self,
             fixed,
@@ -98,7 +161,7 @@ impl Clocks {
 pub struct FixedClockStepIter<'a> {
     clocks: &'a mut Clocks,
     fixed: Duration,
-    now: Instant,
+    now: Duration,
 }
 
 impl<'a> FixedClockStepIter<'a> {
@@ -117,10 +180,13 @@ impl<'a> Iterator for FixedClockStepIter<'a> {
             None
         } else {
             self.clocks.last_fixed += self.fixed;
+            let step = self.clocks.start + self.clocks.last_fixed;
             Some(ClockIndex {
                 delta: self.fixed,
-                step: self.clocks.last_fixed,
+                step,
                 start: self.clocks.start,
+                sim_delta: self.fixed,
+                sim_step: step,
             })
         }
     }
@@ -1,4 +1,42 @@
-use {color_eyre::Report, eyre::WrapErr, std::path::PathBuf};
+//! Engine configuration.
+//!
+//! Configuration is assembled from layers, each overriding previous ones:
+//! defaults, config file, command line arguments and environment variables.
+//!
+//! Command line overrides are passed as `--cfg renderer.motion_blur=true`.
+//! Environment overrides use `WILDS_CFG_` prefix with `__` separating
+//! sections, e.g. `WILDS_CFG_CAMERA__APERTURE=8.0`.
+//! Values are parsed as RON and fall back to strings.
+//!
+//! Config file is reloaded while running when modified.
+//! Only renderer constants, camera settings and console variables
+//! are applied on reload.
+
+use {
+    crate::{
+        camera::CameraSettings,
+        cvar::{CVarValue, CVars},
+        engine::{System, SystemContext},
+        renderer::RenderConstants,
+    },
+    color_eyre::Report,
+    eyre::{eyre, WrapErr},
+    ron::{Map, Value},
+    std::{
+        collections::BTreeMap,
+        path::{Path, PathBuf},
+        time::{Duration, Instant, SystemTime},
+    },
+};
+
+/// Environment variable with path to config file.
+const CONFIG_PATH_ENV: &str = "WILDS_ENGINE_CONFIG_PATH";
+
+/// Prefix of environment variables that override config values.
+const OVERRIDE_ENV_PREFIX: &str = "WILDS_CFG_";
+
+/// Interval between checks for modified config file.
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(1);
 
 #[derive(Clone, Debug, serde::Deserialize)]
 #[serde(untagged)]
@@ -6,28 +44,276 @@ pub enum AssetSource {
     FileSystem { path: PathBuf },
 }
 
-#[derive(Clone, Debug, serde::Deserialize)]
+/// Engine configuration.
+/// Missing fields are set to defaults.
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+#[serde(default)]
 pub struct Config {
     pub sources: Vec<AssetSource>,
+
+    /// Renderer constants.
+    /// Applied to renderer console variables on reload.
+    pub renderer: RenderConstants,
+
+    /// Settings for gameplay cameras.
+    /// Applied to all `CameraSettings` components on reload.
+    pub camera: CameraSettings,
+
+    /// Values of console variables by name.
+    pub cvars: BTreeMap<String, CVarValue>,
 }
 
 impl Config {
+    /// Loads config from default layers.
     pub async fn load_default() -> Result<Self, Report> {
-        // Load from predefined file path for desktop platforms.
-        let path = std::env::var("WILDS_ENGINE_CONFIG_PATH")
-            .map(PathBuf::from)
-            .unwrap_or_else(|_| PathBuf::from("./cfg.ron"));
+        ConfigLoader::from_env().load_async().await
+    }
+
+    /// Loads config from file without overrides.
+    pub async fn load(path: PathBuf) -> Result<Self, Report> {
+        ConfigLoader::new(path).load_async().await
+    }
+
+    /// Applies console variables from this config.
+    pub fn apply_cvars(&self, cvars: &mut CVars) {
+        for (name, value) in &self.cvars {
+            cvars.set_or_defer(name, value.clone());
+        }
+    }
+}
+
+/// Loads config from file and applies overrides.
+#[derive(Clone, Debug)]
+pub struct ConfigLoader {
+    path: PathBuf,
+
+    /// Overrides by dot-separated key in order of application.
+    overrides: Vec<(String, String)>,
+}
+
+impl ConfigLoader {
+    /// Returns loader for specified file without overrides.
+    pub fn new(path: PathBuf) -> Self {
+        ConfigLoader {
+            path,
+            overrides: Vec::new(),
+        }
+    }
+
+    /// Returns loader with config path and overrides taken
+    /// from command line arguments and environment variables.
+    ///
+    /// Config path is taken from `WILDS_ENGINE_CONFIG_PATH` variable
+    /// or `--config` argument and defaults to `./cfg.ron`.
+    pub fn from_env() -> Self {
+        let mut path = None;
+        let mut overrides = Vec::new();
+
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            if arg == "--config" {
+                path = args.next().map(PathBuf::from);
+            } else if arg == "--cfg" {
+                overrides
+                    .extend(args.next().as_deref().and_then(parse_override));
+            } else if arg.starts_with("--cfg=") {
+                overrides.extend(parse_override(&arg["--cfg=".len()..]));
+            }
+        }
+
+        let mut vars: Vec<_> = std::env::vars()
+            .filter(|(name, _)| name.starts_with(OVERRIDE_ENV_PREFIX))
+            .collect();
+
+        // Apply in deterministic order.
+        vars.sort();
+
+        for (name, value) in vars {
+            let key = name[OVERRIDE_ENV_PREFIX.len()..]
+                .to_lowercase()
+                .replace("__", ".");
+            overrides.push((key, value));
+        }
+
+        if let Ok(env_path) = std::env::var(CONFIG_PATH_ENV) {
+            path = Some(PathBuf::from(env_path));
+        }
+
+        ConfigLoader {
+            path: path.unwrap_or_else(|| PathBuf::from("./cfg.ron")),
+            overrides,
+        }
+    }
+
+    /// Adds override applied after already added ones.
+    pub fn with_override(
+        mut self,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Self {
+        self.overrides.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Loads config.
+    /// Missing config file is treated as empty.
+    pub fn load(&self) -> Result<Config, Report> {
+        let mut value = match std::fs::read_to_string(&self.path) {
+            Ok(source) => ron::de::from_str(&source).wrap_err_with(|| {
+                format!("Failed to parse config '{}'", self.path.display())
+            })?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                tracing::warn!(
+                    "Config '{}' not found, using defaults",
+                    self.path.display()
+                );
+                Value::Map(Map::new())
+            }
+            Err(err) => {
+                return Err(err).wrap_err_with(|| {
+                    format!("Failed to read config '{}'", self.path.display())
+                })
+            }
+        };
+
+        for (key, raw) in &self.overrides {
+            let override_value = ron::de::from_str(raw)
+                .unwrap_or_else(|_| Value::String(raw.clone()));
+
+            // Console variable names contain dots themselves.
+            let path: Vec<&str> = match key.strip_prefix("cvars.") {
+                Some(name) => vec!["cvars", name],
+                None => key.split('.').collect(),
+            };
 
-        let config = Self::load(path).await?;
-        Ok(config)
+            set_value(&mut value, &path, override_value).wrap_err_with(
+                || format!("Failed to apply config override '{}'", key),
+            )?;
+        }
+
+        value.into_rust().wrap_err("Invalid config")
     }
 
-    #[cfg(not(target = "wasm32"))]
+    #[cfg(not(target_arch = "wasm32"))]
     #[tracing::instrument]
-    pub async fn load(path: PathBuf) -> Result<Self, Report> {
-        smol::unblock(move || {
-            Ok(ron::de::from_reader(std::fs::File::open(&path)?)?)
-        })
-        .await
+    pub async fn load_async(&self) -> Result<Config, Report> {
+        let loader = self.clone();
+        smol::unblock(move || loader.load()).await
+    }
+}
+
+/// Parses `key=value` override.
+fn parse_override(arg: &str) -> Option<(String, String)> {
+    let mut split = arg.splitn(2, '=');
+    let key = split.next()?;
+    match split.next() {
+        Some(value) => Some((key.to_owned(), value.to_owned())),
+        None => {
+            tracing::warn!("Config override '{}' has no value", arg);
+            None
+        }
+    }
+}
+
+/// Sets value at path of section names, creating missing sections.
+fn set_value(
+    target: &mut Value,
+    path: &[&str],
+    value: Value,
+) -> Result<(), Report> {
+    let map = match target {
+        Value::Map(map) => map,
+        _ => return Err(eyre!("Value is not a section")),
+    };
+
+    match path {
+        [] => Err(eyre!("Empty key")),
+        [name] => {
+            map.insert(Value::String((*name).to_owned()), value);
+            Ok(())
+        }
+        [name, rest @ ..] => {
+            let name = Value::String((*name).to_owned());
+            let mut section =
+                map.remove(&name).unwrap_or_else(|| Value::Map(Map::new()));
+            set_value(&mut section, rest, value)?;
+            map.insert(name, section);
+            Ok(())
+        }
     }
 }
+
+/// System that reloads config file when it is modified
+/// and applies tunable sections.
+pub struct ConfigReloadSystem {
+    loader: ConfigLoader,
+    config: Config,
+    modified: Option<SystemTime>,
+    last_check: Instant,
+}
+
+impl ConfigReloadSystem {
+    /// Creates system with config that was loaded with the loader.
+    pub fn new(loader: ConfigLoader, config: Config) -> Self {
+        ConfigReloadSystem {
+            modified: modified(&loader.path),
+            loader,
+            config,
+            last_check: Instant::now(),
+        }
+    }
+}
+
+impl System for ConfigReloadSystem {
+    fn run(&mut self, ctx: SystemContext<'_>) {
+        if self.last_check.elapsed() < RELOAD_CHECK_INTERVAL {
+            return;
+        }
+        self.last_check = Instant::now();
+
+        let time = modified(&self.loader.path);
+        if time.is_none() || time == self.modified {
+            return;
+        }
+        self.modified = time;
+
+        let config = match self.loader.load() {
+            Ok(config) => config,
+            Err(err) => {
+                tracing::error!("Failed to reload config: {:#}", err);
+                return;
+            }
+        };
+
+        tracing::info!("Config reloaded");
+
+        // Sections are applied only when changed,
+        // so runtime tweaks survive unrelated edits.
+        let cvars = ctx.resources.entry::<CVars>().or_insert_with(CVars::new);
+
+        if config.renderer != self.config.renderer {
+            config.renderer.write_cvars(cvars);
+        }
+
+        if config.cvars != self.config.cvars {
+            config.apply_cvars(cvars);
+        }
+
+        if config.camera != self.config.camera {
+            for (_, settings) in ctx.world.query::<&mut CameraSettings>().iter()
+            {
+                *settings = config.camera;
+            }
+        }
+
+        self.config = config;
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).ok()?.modified().ok()
+}
@@ -0,0 +1,485 @@
+//! In-game developer console.
+//!
+//! Console is toggled with grave key and drawn with `TextOverlay`.
+//! It executes registered commands and gets, sets and toggles
+//! console variables. Tracing warnings and errors are printed to console.
+
+use {
+    crate::{
+        cvar::CVars,
+        engine::{System, SystemContext},
+        renderer::TextOverlay,
+    },
+    color_eyre::Report,
+    hecs::World,
+    once_cell::sync::Lazy,
+    parking_lot::Mutex,
+    std::{
+        collections::{BTreeMap, VecDeque},
+        fmt::{self, Display, Write as _},
+    },
+    tracing::{field::Field, Level, Subscriber},
+    tracing_subscriber::layer::{Context, Layer},
+    type_map::TypeMap,
+    winit::event::{
+        ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent,
+    },
+};
+
+/// Maximum number of lines kept in console output.
+const MAX_OUTPUT_LINES: usize = 256;
+
+/// Warnings and errors captured by `ConsoleLayer`.
+static CAPTURED: Lazy<Mutex<VecDeque<(LineKind, String)>>> =
+    Lazy::new(|| Mutex::new(VecDeque::new()));
+
+/// Tracing layer that sends warnings and errors to console.
+pub struct ConsoleLayer;
+
+impl<S> Layer<S> for ConsoleLayer
+where
+    S: Subscriber,
+{
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+        let kind = match *event.metadata().level() {
+            Level::ERROR => LineKind::Error,
+            Level::WARN => LineKind::Warning,
+            _ => return,
+        };
+
+        let mut visitor = MessageVisitor(String::new());
+        event.record(&mut visitor);
+
+        let mut captured = CAPTURED.lock();
+        if captured.len() >= MAX_OUTPUT_LINES {
+            captured.pop_front();
+        }
+        captured.push_back((kind, visitor.0));
+    }
+}
+
+struct MessageVisitor(String);
+
+impl tracing::field::Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.0, "{:?}", value);
+        } else {
+            let _ = write!(self.0, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LineKind {
+    Input,
+    Output,
+    Warning,
+    Error,
+}
+
+impl LineKind {
+    fn color(&self) -> [u8; 3] {
+        match self {
+            LineKind::Input => [180, 200, 255],
+            LineKind::Output => [220, 220, 220],
+            LineKind::Warning => [255, 210, 80],
+            LineKind::Error => [255, 90, 90],
+        }
+    }
+}
+
+#[derive(Default)]
+struct Output {
+    lines: VecDeque<(LineKind, String)>,
+}
+
+impl Output {
+    fn push(&mut self, kind: LineKind, line: String) {
+        if self.lines.len() >= MAX_OUTPUT_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back((kind, line));
+    }
+}
+
+/// Context of command execution.
+pub struct CommandContext<'a> {
+    pub world: &'a mut World,
+    pub resources: &'a mut TypeMap,
+    output: &'a mut Output,
+}
+
+impl CommandContext<'_> {
+    /// Prints line to console.
+    pub fn print(&mut self, line: impl Display) {
+        self.output.push(LineKind::Output, line.to_string());
+    }
+}
+
+type Command =
+    Box<dyn FnMut(&[&str], &mut CommandContext<'_>) -> Result<(), Report>>;
+
+struct CommandEntry {
+    description: &'static str,
+    run: Command,
+}
+
+/// Commands handled by console itself.
+const BUILTIN_COMMANDS: [(&str, &str); 7] = [
+    ("help", "Lists commands"),
+    ("cvars", "Lists console variables"),
+    ("get", "get <cvar> - prints console variable"),
+    ("set", "set <cvar> <value> - sets console variable"),
+    ("toggle", "toggle <cvar> - flips boolean console variable"),
+    ("reset", "reset <cvar> - resets console variable to default"),
+    ("clear", "Clears console output"),
+];
+
+/// Whether console is open.
+/// Systems may ignore keyboard input while it is.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ConsoleState {
+    pub open: bool,
+}
+
+/// Developer console system.
+pub struct ConsoleSystem {
+    commands: BTreeMap<String, CommandEntry>,
+    output: Output,
+    history: Vec<String>,
+
+    /// Index into history while browsing it.
+    history_cursor: Option<usize>,
+    input: String,
+    open: bool,
+}
+
+impl ConsoleSystem {
+    pub fn new() -> Self {
+        ConsoleSystem {
+            commands: BTreeMap::new(),
+            output: Output::default(),
+            history: Vec::new(),
+            history_cursor: None,
+            input: String::new(),
+            open: false,
+        }
+    }
+
+    /// Registers command.
+    ///
+    /// Command receives arguments following command name.
+    /// Errors are printed to console.
+    pub fn register_command<F>(
+        &mut self,
+        name: &str,
+        description: &'static str,
+        command: F,
+    ) where
+        F: FnMut(&[&str], &mut CommandContext<'_>) -> Result<(), Report>
+            + 'static,
+    {
+        self.commands.insert(
+            name.to_owned(),
+            CommandEntry {
+                description,
+                run: Box::new(command),
+            },
+        );
+    }
+
+    pub fn with_command<F>(
+        mut self,
+        name: &str,
+        description: &'static str,
+        command: F,
+    ) -> Self
+    where
+        F: FnMut(&[&str], &mut CommandContext<'_>) -> Result<(), Report>
+            + 'static,
+    {
+        self.register_command(name, description, command);
+        self
+    }
+
+    /// Executes command line.
+    pub fn execute(
+        &mut self,
+        line: &str,
+        world: &mut World,
+        resources: &mut TypeMap,
+    ) {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (name, args) = match words.split_first() {
+            Some((name, args)) => (*name, args),
+            None => return,
+        };
+
+        if let Err(err) = self.execute_words(name, args, world, resources) {
+            self.output.push(LineKind::Error, format!("{:#}", err));
+        }
+    }
+
+    fn execute_words(
+        &mut self,
+        name: &str,
+        args: &[&str],
+        world: &mut World,
+        resources: &mut TypeMap,
+    ) -> Result<(), Report> {
+        let output = &mut self.output;
+
+        match (name, args) {
+            ("help", _) => {
+                for (name, description) in BUILTIN_COMMANDS.iter() {
+                    output.push(
+                        LineKind::Output,
+                        format!("{} - {}", name, description),
+                    );
+                }
+                for (name, entry) in &self.commands {
+                    output.push(
+                        LineKind::Output,
+                        format!("{} - {}", name, entry.description),
+                    );
+                }
+            }
+            ("cvars", _) => {
+                for (name, var) in cvars_mut(resources).iter() {
+                    output.push(
+                        LineKind::Output,
+                        format!(
+                            "{} = {} ({})",
+                            name,
+                            var.value(),
+                            var.description()
+                        ),
+                    );
+                }
+            }
+            ("clear", _) => output.lines.clear(),
+            ("get", [name]) => print_cvar(output, cvars_mut(resources), name),
+            ("set", [name, value]) => {
+                let cvars = cvars_mut(resources);
+                cvars.set_str(name, value)?;
+                print_cvar(output, cvars, name);
+            }
+            ("toggle", [name]) => {
+                let cvars = cvars_mut(resources);
+                cvars.toggle(name)?;
+                print_cvar(output, cvars, name);
+            }
+            ("reset", [name]) => {
+                let cvars = cvars_mut(resources);
+                cvars.reset(name)?;
+                print_cvar(output, cvars, name);
+            }
+            _ => {
+                if let Some(entry) = self.commands.get_mut(name) {
+                    let mut ctx = CommandContext {
+                        world,
+                        resources,
+                        output,
+                    };
+                    return (entry.run)(args, &mut ctx);
+                }
+
+                // Console variable name alone prints it,
+                // followed by value sets it.
+                let cvars = cvars_mut(resources);
+                if cvars.get(name).is_none() {
+                    return Err(eyre::eyre!(
+                        "Unknown command or invalid arguments `{}`",
+                        name
+                    ));
+                }
+
+                match args {
+                    [] => {}
+                    [value] => cvars.set_str(name, value)?,
+                    _ => return Err(eyre::eyre!("Expected single value")),
+                }
+                print_cvar(output, cvars, name);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Completes last word of the input.
+    fn complete(&mut self, resources: &TypeMap) {
+        let split = self.input.rfind(' ').map_or(0, |index| index + 1);
+        let (head, word) = self.input.split_at(split);
+
+        let mut candidates: Vec<String> = Vec::new();
+        let cvar_names = resources
+            .get::<CVars>()
+            .into_iter()
+            .flat_map(|cvars| cvars.iter().map(|(name, _)| name.to_owned()));
+
+        match head.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [] => {
+                candidates.extend(
+                    BUILTIN_COMMANDS.iter().map(|(name, _)| (*name).to_owned()),
+                );
+                candidates.extend(self.commands.keys().cloned());
+                candidates.extend(cvar_names);
+            }
+            ["get"] | ["set"] | ["toggle"] | ["reset"] => {
+                candidates.extend(cvar_names)
+            }
+            _ => {}
+        }
+
+        candidates.retain(|candidate| candidate.starts_with(word));
+        candidates.sort();
+        candidates.dedup();
+
+        match candidates.as_slice() {
+            [] => {}
+            [single] => {
+                self.input = format!("{}{} ", head, single);
+            }
+            [first, rest @ ..] => {
+                let common = rest.iter().fold(first.len(), |len, candidate| {
+                    first
+                        .chars()
+                        .zip(candidate.chars())
+                        .take(len)
+                        .take_while(|(a, b)| a == b)
+                        .count()
+                });
+
+                self.input = format!("{}{}", head, &first[..common]);
+                self.output.push(LineKind::Output, candidates.join("  "));
+            }
+        }
+    }
+
+    fn browse_history(&mut self, up: bool) {
+        if self.history.is_empty() {
+            return;
+        }
+
+        let cursor = match (self.history_cursor, up) {
+            (None, true) => Some(self.history.len() - 1),
+            (None, false) => None,
+            (Some(cursor), true) => Some(cursor.saturating_sub(1)),
+            (Some(cursor), false) if cursor + 1 < self.history.len() => {
+                Some(cursor + 1)
+            }
+            (Some(_), false) => None,
+        };
+
+        self.history_cursor = cursor;
+        self.input = match cursor {
+            Some(cursor) => self.history[cursor].clone(),
+            None => String::new(),
+        };
+    }
+
+    fn draw(&self, overlay: &mut TextOverlay) {
+        let rows = overlay.rows() / 2;
+        if rows < 2 {
+            return;
+        }
+
+        overlay.fill(0, 0, overlay.cols(), rows);
+
+        let output_rows = rows - 1;
+        let skip = self.output.lines.len().saturating_sub(output_rows as usize);
+        for (row, (kind, line)) in
+            self.output.lines.iter().skip(skip).enumerate()
+        {
+            overlay.print(0, row as u32, line, kind.color());
+        }
+
+        let input = format!("> {}_", self.input);
+        overlay.print(0, rows - 1, &input, [255, 255, 255]);
+    }
+}
+
+impl System for ConsoleSystem {
+    fn run(&mut self, ctx: SystemContext<'_>) {
+        for (kind, line) in CAPTURED.lock().drain(..) {
+            self.output.push(kind, line);
+        }
+
+        let mut execute = Vec::new();
+
+        for event in ctx.input.read() {
+            let event = match event {
+                Event::WindowEvent { event, .. } => event,
+                _ => continue,
+            };
+
+            match event {
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            virtual_keycode: Some(key),
+                            state: ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                } => match key {
+                    VirtualKeyCode::Grave => self.open = !self.open,
+                    VirtualKeyCode::Escape if self.open => self.open = false,
+                    VirtualKeyCode::Return if self.open => {
+                        let line = std::mem::take(&mut self.input);
+                        self.history_cursor = None;
+                        if !line.trim().is_empty() {
+                            self.history.push(line.clone());
+                            execute.push(line);
+                        }
+                    }
+                    VirtualKeyCode::Back if self.open => {
+                        self.input.pop();
+                    }
+                    VirtualKeyCode::Up if self.open => {
+                        self.browse_history(true)
+                    }
+                    VirtualKeyCode::Down if self.open => {
+                        self.browse_history(false)
+                    }
+                    VirtualKeyCode::Tab if self.open => {
+                        self.complete(ctx.resources)
+                    }
+                    _ => {}
+                },
+                WindowEvent::ReceivedCharacter(c)
+                    if self.open && *c != '`' && !c.is_control() =>
+                {
+                    self.input.push(*c);
+                }
+                _ => {}
+            }
+        }
+
+        for line in execute {
+            self.output.push(LineKind::Input, format!("> {}", line));
+            self.execute(&line, ctx.world, ctx.resources);
+        }
+
+        ctx.resources.insert(ConsoleState { open: self.open });
+
+        if self.open {
+            let overlay = ctx
+                .resources
+                .entry::<TextOverlay>()
+                .or_insert_with(TextOverlay::new);
+            self.draw(overlay);
+        }
+    }
+}
+
+fn cvars_mut(resources: &mut TypeMap) -> &mut CVars {
+    resources.entry::<CVars>().or_insert_with(CVars::new)
+}
+
+fn print_cvar(output: &mut Output, cvars: &CVars, name: &str) {
+    if let Some(var) = cvars.get(name) {
+        output.push(LineKind::Output, format!("{} = {}", name, var.value()));
+    }
+}
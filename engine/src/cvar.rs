@@ -0,0 +1,425 @@
+//! Console variables.
+//!
+//! Typed variables registered by subsystems and tuned at runtime
+//! from console or config.
+//! Systems observe changes by comparing versions.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Display},
+};
+
+/// Value of console variable.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+#[serde(untagged)]
+pub enum CVarValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Enum(String),
+}
+
+impl Display for CVarValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CVarValue::Bool(value) => write!(f, "{}", value),
+            CVarValue::Int(value) => write!(f, "{}", value),
+            CVarValue::Float(value) => write!(f, "{}", value),
+            CVarValue::Enum(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl From<bool> for CVarValue {
+    fn from(value: bool) -> Self {
+        CVarValue::Bool(value)
+    }
+}
+
+impl From<i64> for CVarValue {
+    fn from(value: i64) -> Self {
+        CVarValue::Int(value)
+    }
+}
+
+impl From<f64> for CVarValue {
+    fn from(value: f64) -> Self {
+        CVarValue::Float(value)
+    }
+}
+
+/// Type of console variable.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CVarKind {
+    Bool,
+    Int,
+    Float,
+
+    /// One of listed variants.
+    Enum(&'static [&'static str]),
+}
+
+impl Display for CVarKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CVarKind::Bool => f.write_str("bool"),
+            CVarKind::Int => f.write_str("int"),
+            CVarKind::Float => f.write_str("float"),
+            CVarKind::Enum(variants) => {
+                write!(f, "one of [{}]", variants.join(", "))
+            }
+        }
+    }
+}
+
+impl CVarKind {
+    /// Converts value to this kind.
+    /// Integers are accepted for floats.
+    fn coerce(&self, value: CVarValue) -> Option<CVarValue> {
+        match (self, value) {
+            (CVarKind::Bool, value @ CVarValue::Bool(_)) => Some(value),
+            (CVarKind::Int, value @ CVarValue::Int(_)) => Some(value),
+            (CVarKind::Float, value @ CVarValue::Float(_)) => Some(value),
+            (CVarKind::Float, CVarValue::Int(value)) => {
+                Some(CVarValue::Float(value as f64))
+            }
+            (CVarKind::Enum(variants), CVarValue::Enum(value))
+                if variants.contains(&&*value) =>
+            {
+                Some(CVarValue::Enum(value))
+            }
+            _ => None,
+        }
+    }
+
+    /// Parses value of this kind.
+    fn parse(&self, value: &str) -> Option<CVarValue> {
+        match self {
+            CVarKind::Bool => match value {
+                "true" | "on" | "1" => Some(CVarValue::Bool(true)),
+                "false" | "off" | "0" => Some(CVarValue::Bool(false)),
+                _ => None,
+            },
+            CVarKind::Int => value.parse().ok().map(CVarValue::Int),
+            CVarKind::Float => value.parse().ok().map(CVarValue::Float),
+            CVarKind::Enum(variants) => variants
+                .iter()
+                .find(|variant| **variant == value)
+                .map(|variant| CVarValue::Enum((*variant).to_owned())),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CVarError {
+    #[error("Console variable `{name}` is not registered")]
+    NotFound { name: String },
+
+    #[error("Console variable `{name}` expects {kind}, got `{value}`")]
+    InvalidValue {
+        name: String,
+        kind: CVarKind,
+        value: String,
+    },
+}
+
+/// Registered console variable.
+#[derive(Clone, Debug)]
+pub struct CVar {
+    kind: CVarKind,
+    value: CVarValue,
+    default: CVarValue,
+    description: &'static str,
+    version: u64,
+}
+
+impl CVar {
+    pub fn kind(&self) -> CVarKind {
+        self.kind
+    }
+
+    pub fn value(&self) -> &CVarValue {
+        &self.value
+    }
+
+    pub fn default(&self) -> &CVarValue {
+        &self.default
+    }
+
+    pub fn description(&self) -> &'static str {
+        self.description
+    }
+
+    /// Returns registry version at which value was last changed.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+}
+
+/// Registry of console variables.
+///
+/// Values set before variable is registered are applied on registration,
+/// so config may set variables of subsystems that are not created yet.
+#[derive(Debug, Default)]
+pub struct CVars {
+    vars: BTreeMap<String, CVar>,
+    pending: HashMap<String, CVarValue>,
+    version: u64,
+}
+
+impl CVars {
+    pub fn new() -> Self {
+        CVars::default()
+    }
+
+    /// Registers variable.
+    ///
+    /// Registering variable again replaces its definition.
+    /// Current value is kept if it matches new kind.
+    pub fn register(
+        &mut self,
+        name: &str,
+        kind: CVarKind,
+        default: CVarValue,
+        description: &'static str,
+    ) {
+        let default = match kind.coerce(default) {
+            Some(default) => default,
+            None => {
+                tracing::error!(
+                    "Default value of console variable `{}` is not {}",
+                    name,
+                    kind
+                );
+                return;
+            }
+        };
+
+        let mut value = self
+            .vars
+            .remove(name)
+            .and_then(|old| kind.coerce(old.value))
+            .unwrap_or_else(|| default.clone());
+
+        if let Some(pending) = self.pending.remove(name) {
+            match kind.coerce(pending.clone()) {
+                Some(pending) => value = pending,
+                None => tracing::warn!(
+                    "Ignoring value `{}` of console variable `{}`, expected {}",
+                    pending,
+                    name,
+                    kind
+                ),
+            }
+        }
+
+        self.version += 1;
+        self.vars.insert(
+            name.to_owned(),
+            CVar {
+                kind,
+                value,
+                default,
+                description,
+                version: self.version,
+            },
+        );
+    }
+
+    pub fn register_bool(
+        &mut self,
+        name: &str,
+        default: bool,
+        description: &'static str,
+    ) {
+        self.register(
+            name,
+            CVarKind::Bool,
+            CVarValue::Bool(default),
+            description,
+        )
+    }
+
+    pub fn register_int(
+        &mut self,
+        name: &str,
+        default: i64,
+        description: &'static str,
+    ) {
+        self.register(name, CVarKind::Int, CVarValue::Int(default), description)
+    }
+
+    pub fn register_float(
+        &mut self,
+        name: &str,
+        default: f64,
+        description: &'static str,
+    ) {
+        self.register(
+            name,
+            CVarKind::Float,
+            CVarValue::Float(default),
+            description,
+        )
+    }
+
+    pub fn register_enum(
+        &mut self,
+        name: &str,
+        variants: &'static [&'static str],
+        default: &str,
+        description: &'static str,
+    ) {
+        self.register(
+            name,
+            CVarKind::Enum(variants),
+            CVarValue::Enum(default.to_owned()),
+            description,
+        )
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CVar> {
+        self.vars.get(name)
+    }
+
+    /// Iterates over registered variables sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &CVar)> {
+        self.vars.iter().map(|(name, var)| (&**name, var))
+    }
+
+    pub fn get_bool(&self, name: &str) -> Option<bool> {
+        match self.get(name)?.value {
+            CVarValue::Bool(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_int(&self, name: &str) -> Option<i64> {
+        match self.get(name)?.value {
+            CVarValue::Int(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_float(&self, name: &str) -> Option<f64> {
+        match self.get(name)?.value {
+            CVarValue::Float(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_enum(&self, name: &str) -> Option<&str> {
+        match &self.get(name)?.value {
+            CVarValue::Enum(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Sets value of registered variable.
+    pub fn set(
+        &mut self,
+        name: &str,
+        value: CVarValue,
+    ) -> Result<(), CVarError> {
+        let var =
+            self.vars.get_mut(name).ok_or_else(|| CVarError::NotFound {
+                name: name.to_owned(),
+            })?;
+
+        let value = var.kind.coerce(value.clone()).ok_or_else(|| {
+            CVarError::InvalidValue {
+                name: name.to_owned(),
+                kind: var.kind,
+                value: value.to_string(),
+            }
+        })?;
+
+        if var.value != value {
+            self.version += 1;
+            var.value = value;
+            var.version = self.version;
+        }
+
+        Ok(())
+    }
+
+    /// Parses and sets value of registered variable.
+    pub fn set_str(
+        &mut self,
+        name: &str,
+        value: &str,
+    ) -> Result<(), CVarError> {
+        let var = self.vars.get(name).ok_or_else(|| CVarError::NotFound {
+            name: name.to_owned(),
+        })?;
+
+        let parsed =
+            var.kind
+                .parse(value)
+                .ok_or_else(|| CVarError::InvalidValue {
+                    name: name.to_owned(),
+                    kind: var.kind,
+                    value: value.to_owned(),
+                })?;
+
+        self.set(name, parsed)
+    }
+
+    /// Sets value of variable.
+    /// If variable is not registered yet value is applied on registration.
+    pub fn set_or_defer(&mut self, name: &str, value: CVarValue) {
+        if self.vars.contains_key(name) {
+            if let Err(err) = self.set(name, value) {
+                tracing::warn!("{}", err);
+            }
+        } else {
+            self.pending.insert(name.to_owned(), value);
+        }
+    }
+
+    /// Flips boolean variable and returns new value.
+    pub fn toggle(&mut self, name: &str) -> Result<bool, CVarError> {
+        let var = self.vars.get(name).ok_or_else(|| CVarError::NotFound {
+            name: name.to_owned(),
+        })?;
+
+        match var.value {
+            CVarValue::Bool(value) => {
+                self.set(name, CVarValue::Bool(!value))?;
+                Ok(!value)
+            }
+            _ => Err(CVarError::InvalidValue {
+                name: name.to_owned(),
+                kind: var.kind,
+                value: "toggle".to_owned(),
+            }),
+        }
+    }
+
+    /// Resets variable to its default value.
+    pub fn reset(&mut self, name: &str) -> Result<(), CVarError> {
+        let default = self
+            .vars
+            .get(name)
+            .ok_or_else(|| CVarError::NotFound {
+                name: name.to_owned(),
+            })?
+            .default
+            .clone();
+
+        self.set(name, default)
+    }
+
+    /// Returns version that is incremented on every change.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Iterates over variables changed after specified version.
+    pub fn changed_since(
+        &self,
+        version: u64,
+    ) -> impl Iterator<Item = (&str, &CVar)> {
+        self.iter().filter(move |(_, var)| var.version > version)
+    }
+}
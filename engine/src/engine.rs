@@ -2,8 +2,12 @@ use {
     crate::{
         assets::{AssetKey, Assets, Prefab},
         broker::EventBroker,
-        clocks::{ClockIndex, Clocks},
-        config::{AssetSource, Config},
+        clocks::{ClockIndex, Clocks, TimeScale},
+        config::{AssetSource, Config, ConfigLoader, ConfigReloadSystem},
+        cvar::CVars,
+        lifecycle::{self, DespawnEvents, LifecycleSystem},
+        renderer::{Renderer, ViewTarget},
+        schedule::{ParallelSystem, Schedule, SharedResources},
     },
     bumpalo::Bump,
     cfg_if::cfg_if,
@@ -12,12 +16,15 @@ use {
     futures::future::TryFutureExt as _,
     goods::{Asset, AssetDefaultFormat},
     hecs::{Entity, World},
+    parking_lot::Mutex,
     std::{
         cell::Cell,
         error::Error,
         future::Future,
+        path::PathBuf,
         pin::Pin,
         rc::Rc,
+        sync::Arc,
         task::{Context, Poll},
         time::Duration,
     },
@@ -36,6 +43,14 @@ pub use winit::event::{
 
 pub type InputEvents = EventBroker<Event<'static, ()>>;
 
+/// Renderer handed back by application when it exits.
+/// `Engine::run` shuts it down with its views
+/// before event loop exits.
+pub struct RendererExit {
+    pub renderer: Renderer,
+    pub views: Vec<ViewTarget>,
+}
+
 pub struct SystemContext<'a> {
     pub input: &'a InputEvents,
     pub world: &'a mut World,
@@ -61,13 +76,18 @@ where
 pub struct Engine {
     pub world: World,
     pub resources: TypeMap,
+
+    /// Resources accessible from parallel systems.
+    pub shared_resources: SharedResources,
     pub assets: Assets,
     pub input: InputEvents,
-    schedule: Vec<Box<dyn System>>,
-    fixed_schedule: Vec<Box<dyn System>>,
+    schedule: Schedule,
+    fixed_schedule: Schedule,
     shared: Rc<Shared>,
+    prefabs: PrefabLoader,
     recv_make_prefabs: Receiver<MakePrefab>,
-    send_make_prefabs: Sender<MakePrefab>,
+    asset_roots: Vec<PathBuf>,
+    config: Config,
     clocks: Clocks,
     fixed_step_delta: Duration,
 }
@@ -80,8 +100,10 @@ impl Engine {
     pub fn load_prefab<P>(&self, key: AssetKey, info: P::Info) -> Entity
     where
         P: Prefab + AssetDefaultFormat<AssetKey> + Clone,
+        P::Info: Clone,
+        P::DefaultFormat: Clone + Send + 'static,
     {
-        self.load_prefab_with_format(key, info, P::DefaultFormat::default())
+        self.prefabs.load_prefab::<P>(&self.world, key, info)
     }
 
     /// Loads asset and enqueue it for spawning.
@@ -96,12 +118,11 @@ impl Engine {
     ) -> Entity
     where
         P: Prefab + Asset + Clone,
-        F: goods::Format<P, AssetKey>,
+        P::Info: Clone,
+        F: goods::Format<P, AssetKey> + Clone + Send + 'static,
     {
-        tracing::info!("Loading prefab '{}'", key);
-
-        let handle = self.assets.load_with_format(key.clone(), format);
-        self.make_prefab(key, info, handle.map_err(Report::from))
+        self.prefabs
+            .load_prefab_with_format(&self.world, key, info, format)
     }
 
     pub fn make_prefab<P, F>(
@@ -114,23 +135,44 @@ impl Engine {
         P: Prefab + Send + 'static,
         F: Future<Output = Result<P, Report>> + Send + 'static,
     {
-        let entity = self.world.reserve_entity();
-        let send_make_prefabs = self.send_make_prefabs.clone();
+        self.prefabs.make_prefab(&self.world, key, info, prefab)
+    }
 
-        smol::spawn(async move {
-            let prefab = prefab.await;
+    /// Despawns entity with its children, running `OnDespawn` hooks.
+    /// Despawned entities are reported in `DespawnEvents` resource.
+    pub fn despawn(&mut self, entity: Entity) {
+        let despawned = lifecycle::despawn(&mut self.world, entity);
+        if despawned.is_empty() {
+            return;
+        }
 
-            tracing::error!("Prefab loaded");
+        self.prefabs.forget_despawned(&despawned);
 
-            let loaded = match prefab {
-                Ok(prefab) => MakePrefab::spawn(key, prefab, info, entity),
-                Err(err) => MakePrefab::Error(key, err, entity),
-            };
-            let _ = send_make_prefabs.send(loaded);
-        })
-        .detach();
+        if !self.resources.contains::<DespawnEvents>() {
+            self.resources.insert(DespawnEvents::new());
+        }
 
-        entity
+        let events = self.resources.get_mut::<DespawnEvents>().unwrap();
+        for entity in despawned {
+            events.add(entity);
+        }
+    }
+
+    /// Returns loader that enqueues prefabs for spawning into this engine.
+    /// Unlike `Engine` it can be kept by systems.
+    pub fn prefab_loader(&self) -> PrefabLoader {
+        self.prefabs.clone()
+    }
+
+    /// Returns file system directories assets are loaded from.
+    pub fn asset_roots(&self) -> &[PathBuf] {
+        &self.asset_roots
+    }
+
+    /// Returns config loaded at startup.
+    /// Tunable sections are applied to resources and components on reload.
+    pub fn config(&self) -> &Config {
+        &self.config
     }
 
     fn build_prefabs(&mut self) {
@@ -142,16 +184,56 @@ impl Engine {
                 }
                 MakePrefab::Error(key, err, entity) => {
                     tracing::error!("Failed to load prefab '{}': {}", key, err);
+                    self.prefabs.forget(entity);
                     let _ = self.world.despawn(entity);
                 }
             }
         }
     }
 
+    /// Replaces asset cache with empty one and loads prefabs again.
+    ///
+    /// Used to recreate device resources of assets after device is lost.
+    /// New cache builds assets with context passed to `Assets::process`.
+    /// Entities of prefabs loaded with `load_prefab*` methods
+    /// are despawned and prefabs are spawned again as new entities.
+    /// Prefabs made with `make_prefab` are not tracked
+    /// and must be made again by their owners.
+    pub fn reload_assets(&mut self) {
+        tracing::info!("Reloading assets");
+
+        self.assets = create_assets(&self.config);
+        *self.prefabs.assets.lock() = self.assets.clone();
+
+        // Prefabs built by old cache must not be spawned.
+        // Their entities are tracked and are despawned below.
+        for loaded in self.recv_make_prefabs.try_iter() {
+            if let MakePrefab::Error(key, err, entity) = loaded {
+                tracing::error!("Failed to load prefab '{}': {}", key, err);
+                self.prefabs.forget(entity);
+                let _ = self.world.despawn(entity);
+            }
+        }
+
+        let loaded = std::mem::take(&mut *self.prefabs.loaded.lock());
+
+        for prefab in loaded {
+            // Skip prefabs despawned by game.
+            if !self.world.contains(prefab.entity) {
+                continue;
+            }
+
+            self.despawn(prefab.entity);
+            (prefab.reload)(&self.prefabs, &self.world);
+        }
+    }
+
+    /// Builds window on event loop thread.
+    /// Window is shared with views that present to it.
     pub fn build_window(
         &mut self,
         builder: WindowBuilder,
-    ) -> Result<Window, Report> {
+    ) -> Result<Arc<Window>, Report> {
         let elwt = self.shared.event_loop_ptr.get();
         if elwt.is_null() {
             unreachable!()
@@ -169,34 +251,42 @@ impl Engine {
         };
 
         let window = builder.build(elwt)?;
-        Ok(window)
+        Ok(Arc::new(window))
     }
 
-    pub fn advance(&mut self, bump: &Bump) {
+    /// Runs all systems once.
+    /// Each system has its own bump allocator
+    /// that is reset before the system runs.
+    /// Simulation time is scaled by `TimeScale` resource if present.
+    pub fn advance(&mut self) {
         self.build_prefabs();
 
+        let scale = self.resources.get::<TimeScale>().map_or(1.0, |t| t.scale);
+        self.clocks.set_scale(scale);
         let clocks = self.clocks.step();
 
-        for system in &mut self.schedule {
-            system.run(SystemContext {
-                world: &mut self.world,
-                resources: &mut self.resources,
-                input: &self.input,
+        self.schedule.run(
+            &mut self.world,
+            &mut self.resources,
+            &self.shared_resources,
+            &self.input,
+            clocks,
+        );
+
+        for clocks in self.clocks.fixed_steps(self.fixed_step_delta) {
+            self.fixed_schedule.run(
+                &mut self.world,
+                &mut self.resources,
+                &self.shared_resources,
+                &self.input,
                 clocks,
-                bump,
-            });
+            );
         }
 
-        for clocks in self.clocks.fixed_steps(self.fixed_step_delta) {
-            for system in &mut self.fixed_schedule {
-                system.run(SystemContext {
-                    world: &mut self.world,
-                    resources: &mut self.resources,
-                    input: &self.input,
-                    clocks,
-                    bump,
-                });
-            }
+        // Despawned prefabs are not loaded again by `reload_assets`.
+        if let Some(events) = self.resources.get::<DespawnEvents>() {
+            let despawned: Vec<_> = events.read().copied().collect();
+            self.prefabs.forget_despawned(&despawned);
         }
 
         self.input.clear();
@@ -207,7 +297,7 @@ impl Engine {
     where
         S: System + 'static,
     {
-        self.schedule.push(Box::new(system));
+        self.schedule.add_system(Box::new(system));
         self
     }
 
@@ -216,7 +306,33 @@ impl Engine {
     where
         S: System + 'static,
     {
-        self.fixed_schedule.push(Box::new(system));
+        self.fixed_schedule.add_system(Box::new(system));
+        self
+    }
+
+    /// Adds a system that may run concurrently with other parallel systems
+    /// added after last exclusive system or barrier.
+    pub fn add_parallel_system<S>(&mut self, system: S) -> &mut Self
+    where
+        S: ParallelSystem + 'static,
+    {
+        self.schedule.add_parallel_system(Box::new(system));
+        self
+    }
+
+    /// Adds a system that may run concurrently with other parallel systems
+    /// added after last exclusive system or barrier.
+    pub fn add_fixed_step_parallel_system<S>(&mut self, system: S) -> &mut Self
+    where
+        S: ParallelSystem + 'static,
+    {
+        self.fixed_schedule.add_parallel_system(Box::new(system));
+        self
+    }
+
+    /// Parallel systems added after barrier run after systems added before.
+    pub fn add_barrier(&mut self) -> &mut Self {
+        self.schedule.add_barrier();
         self
     }
 
@@ -242,39 +358,35 @@ impl Engine {
     /// Instead it calls provided closure with create engine instance
     /// and drive it to completion.
     /// Along with polling winit's event-loop for window events.
+    /// Runs application closure with event loop.
+    ///
+    /// When the closure resolves, renderer it returns is shut down,
+    /// waiting for frames in flight, and then event loop exits.
     pub fn run<F, A>(closure: F) -> Result<(), Report>
     where
         F: FnOnce(Self) -> A,
-        A: Future<Output = Result<(), Report>> + 'static,
+        A: Future<Output = Result<Option<RendererExit>, Report>> + 'static,
     {
-        let config = smol::block_on(Self::load_config())?;
+        let config_loader = ConfigLoader::from_env();
+        let config = smol::block_on(Self::load_config(&config_loader))?;
 
-        let registry = config
+        let assets = create_assets(&config);
+
+        let asset_roots = config
             .sources
             .iter()
-            .fold(goods::RegistryBuilder::<AssetKey>::new(), |builder, source| match source {
+            .filter_map(|source| match source {
                 AssetSource::FileSystem { path } => {
-                    cfg_if! {
-                        if #[cfg(target_arch = "wasm32")] {
-                            tracing::error!("FileSystem asset source with path '{}' ignored on WASM target", path.display());
-                            Ok(builder)
-                        } else {
-                            let path = match std::env::current_dir() {
-                                Ok(cd) => { cd.join(path) }
-                                Err(err) => {
-                                    tracing::error!("Failed to fetch current dir: {}", err);
-                                    path.clone()
-                                }
-                            };
-                            builder.with(goods::FileSource::new(path))
-                        }
+                    if cfg!(target_arch = "wasm32") {
+                        return None;
+                    }
+                    match std::env::current_dir() {
+                        Ok(cd) => Some(cd.join(path)),
+                        Err(_) => Some(path.clone()),
                     }
                 }
-            });
-
-        let registry = registry.with(goods::DataUrlSource);
-
-        let assets = Assets::new(registry.build(), goods::Smol);
+            })
+            .collect();
 
         let shared = Rc::new(Shared {
             event_loop_ptr: Cell::new(std::ptr::null()),
@@ -284,16 +396,40 @@ impl Engine {
 
         let (send_make_prefabs, recv_make_prefabs) = bounded(512);
 
+        let prefabs = PrefabLoader {
+            assets: Arc::new(Mutex::new(assets.clone())),
+            sender: send_make_prefabs,
+            loaded: Arc::new(Mutex::new(Vec::new())),
+        };
+
+        let mut cvars = CVars::new();
+        config.renderer.register_cvars(&mut cvars);
+        config.apply_cvars(&mut cvars);
+
+        let mut resources = TypeMap::new();
+        resources.insert(cvars);
+
+        // Reload config before any other system.
+        let mut schedule = Schedule::new();
+        schedule.add_system(Box::new(ConfigReloadSystem::new(
+            config_loader,
+            config.clone(),
+        )));
+        schedule.add_system(Box::new(LifecycleSystem));
+
         let engine = Engine {
             assets,
-            schedule: Vec::new(),
-            fixed_schedule: Vec::new(),
+            schedule,
+            fixed_schedule: Schedule::new(),
             world: World::new(),
-            resources: TypeMap::new(),
+            resources,
+            shared_resources: SharedResources::new(),
             input: EventBroker::new(),
             shared: shared.clone(),
+            prefabs,
             recv_make_prefabs,
-            send_make_prefabs,
+            asset_roots,
+            config,
             fixed_step_delta: Duration::from_millis(10),
             clocks: Clocks::new(),
         };
@@ -332,6 +468,13 @@ impl Engine {
                         ready: &shared.waiting_for_event,
                     })
                 {
+                    let result = result.and_then(|exit| match exit {
+                        Some(RendererExit { renderer, views }) => {
+                            renderer.shutdown(views)
+                        }
+                        None => Ok(()),
+                    });
+
                     // No place where we could return this error.
                     // log and panic are only options.
                     if let Err(err) = result {
@@ -339,6 +482,8 @@ impl Engine {
                     }
 
                     // Exit when closure resolves.
+                    // Event loop never returns, so stop logging now.
+                    crate::logging::shutdown();
                     *flow = ControlFlow::Exit;
                     app_opt = None;
                 } else {
@@ -354,11 +499,11 @@ impl Engine {
         })
     }
 
-    async fn load_config() -> Result<Config, Report> {
+    async fn load_config(loader: &ConfigLoader) -> Result<Config, Report> {
         tracing::info!("Running at {}", std::env::current_dir()?.display());
 
         // Now load config.
-        let config = Config::load_default().await?;
+        let config = loader.load_async().await?;
         tracing::info!("Config loaded: {:?}", config);
         Ok(config)
     }
@@ -374,14 +519,11 @@ struct AppEventWaitFuture<'a, A> {
 
 impl<'a, A> Future for AppEventWaitFuture<'a, A>
 where
-    A: Future<Output = Result<(), Report>>,
+    A: Future,
 {
-    type Output = Poll<Result<(), Report>>;
+    type Output = Poll<A::Output>;
 
-    fn poll(
-        self: Pin<&mut Self>,
-        ctx: &mut Context,
-    ) -> Poll<Poll<Result<(), Report>>> {
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Poll<A::Output>> {
         let this = self.get_mut();
         let poll = Future::poll(this.app.as_mut(), ctx);
         match poll {
@@ -398,6 +540,177 @@ struct Shared {
     waiting_for_event: Cell<bool>,
 }
 
+/// Creates asset cache over sources listed in config.
+fn create_assets(config: &Config) -> Assets {
+    let registry = config
+        .sources
+        .iter()
+        .fold(goods::RegistryBuilder::<AssetKey>::new(), |builder, source| match source {
+            AssetSource::FileSystem { path } => {
+                cfg_if! {
+                    if #[cfg(target_arch = "wasm32")] {
+                        tracing::error!("FileSystem asset source with path '{}' ignored on WASM target", path.display());
+                        Ok(builder)
+                    } else {
+                        let path = match std::env::current_dir() {
+                            Ok(cd) => { cd.join(path) }
+                            Err(err) => {
+                                tracing::error!("Failed to fetch current dir: {}", err);
+                                path.clone()
+                            }
+                        };
+                        builder.with(goods::FileSource::new(path))
+                    }
+                }
+            }
+        });
+
+    let registry = registry.with(goods::DataUrlSource);
+
+    Assets::new(registry.build(), goods::Smol)
+}
+
+/// Enqueues prefabs for spawning into the engine world.
+///
+/// Prefabs are spawned at the beginning of next `Engine::advance`
+/// after they are loaded.
+#[derive(Clone)]
+pub struct PrefabLoader {
+    assets: Arc<Mutex<Assets>>,
+    sender: Sender<MakePrefab>,
+    loaded: Arc<Mutex<Vec<LoadedPrefab>>>,
+}
+
+/// Prefab loaded from assets.
+/// Kept to load it again when asset cache is replaced.
+struct LoadedPrefab {
+    entity: Entity,
+    reload: Box<dyn Fn(&PrefabLoader, &World) -> Entity + Send>,
+}
+
+impl PrefabLoader {
+    /// Loads asset and enqueue it for spawning.
+    /// Retuns `Entity` that will be supplied to `spawn` method after asset is
+    /// loaded.
+    /// If asset loading fails that `Entity` will be despawned.
+    pub fn load_prefab<P>(
+        &self,
+        world: &World,
+        key: AssetKey,
+        info: P::Info,
+    ) -> Entity
+    where
+        P: Prefab + AssetDefaultFormat<AssetKey> + Clone,
+        P::Info: Clone,
+        P::DefaultFormat: Clone + Send + 'static,
+    {
+        self.load_prefab_with_format(
+            world,
+            key,
+            info,
+            P::DefaultFormat::default(),
+        )
+    }
+
+    /// Loads asset and enqueue it for spawning.
+    /// Retuns `Entity` that will be supplied to `spawn` method after asset is
+    /// loaded.
+    /// If asset loading fails that `Entity` will be despawned.
+    pub fn load_prefab_with_format<P, F>(
+        &self,
+        world: &World,
+        key: AssetKey,
+        info: P::Info,
+        format: F,
+    ) -> Entity
+    where
+        P: Prefab + Asset + Clone,
+        P::Info: Clone,
+        F: goods::Format<P, AssetKey> + Clone + Send + 'static,
+    {
+        tracing::info!("Loading prefab '{}'", key);
+
+        let handle = self
+            .assets
+            .lock()
+            .load_with_format(key.clone(), format.clone());
+
+        let entity = self.make_prefab(
+            world,
+            key.clone(),
+            info.clone(),
+            handle.map_err(Report::from),
+        );
+
+        self.loaded.lock().push(LoadedPrefab {
+            entity,
+            reload: Box::new(move |loader, world| {
+                loader.load_prefab_with_format::<P, F>(
+                    world,
+                    key.clone(),
+                    info.clone(),
+                    format.clone(),
+                )
+            }),
+        });
+
+        entity
+    }
+
+    pub fn make_prefab<P, F>(
+        &self,
+        world: &World,
+        key: AssetKey,
+        info: P::Info,
+        prefab: F,
+    ) -> Entity
+    where
+        P: Prefab + Send + 'static,
+        F: Future<Output = Result<P, Report>> + Send + 'static,
+    {
+        let entity = world.reserve_entity();
+        let sender = self.sender.clone();
+
+        smol::spawn(async move {
+            let prefab = prefab.await;
+
+            tracing::error!("Prefab loaded");
+
+            let loaded = match prefab {
+                Ok(prefab) => MakePrefab::spawn(key, prefab, info, entity),
+                Err(err) => MakePrefab::Error(key, err, entity),
+            };
+            let _ = sender.send(loaded);
+        })
+        .detach();
+
+        entity
+    }
+}
+
+impl PrefabLoader {
+    /// Returns asset cache prefabs are loaded from.
+    pub(crate) fn assets(&self) -> Assets {
+        self.assets.lock().clone()
+    }
+
+    /// Stops tracking prefab that failed to load.
+    fn forget(&self, entity: Entity) {
+        self.loaded.lock().retain(|prefab| prefab.entity != entity);
+    }
+
+    /// Stops tracking prefabs of despawned entities.
+    fn forget_despawned(&self, despawned: &[Entity]) {
+        if despawned.is_empty() {
+            return;
+        }
+
+        self.loaded
+            .lock()
+            .retain(|prefab| !despawned.contains(&prefab.entity));
+    }
+}
+
 enum MakePrefab {
     Spawn(AssetKey, Box<dyn FnOnce(&mut World) + Send>),
     Error(AssetKey, Report, Entity),
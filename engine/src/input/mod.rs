@@ -1 +1,215 @@
+//! Input mapping for local players.
+//!
+//! Window events are translated into `InputEvent`s which are
+//! mapped to game-defined actions by per-player `ActionMap`s.
+//! Several local players may share one window for split-screen,
+//! each with own camera and own map.
 
+use {
+    hecs::Entity,
+    smallvec::SmallVec,
+    std::collections::HashMap,
+    winit::event::{
+        ElementState, Event, KeyboardInput, MouseButton, MouseScrollDelta,
+        VirtualKeyCode, WindowEvent,
+    },
+};
+
+/// Maximum number of local players.
+/// Matches number of splits a view can have.
+pub const MAX_LOCAL_PLAYERS: usize = crate::renderer::MAX_VIEW_SPLITS;
+
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub enum InputEvent {
+    KeyboardInput {
+        code: VirtualKeyCode,
+        state: ElementState,
+    },
+    MouseInput {
+        button: MouseButton,
+        state: ElementState,
+    },
+    MouseWheelUp,
+    MouseWheelDown,
+}
+
+impl InputEvent {
+    /// Returns input event that window event represents, if any.
+    pub fn from_window_event(event: &WindowEvent<'_>) -> Option<Self> {
+        match *event {
+            WindowEvent::MouseWheel {
+                delta: MouseScrollDelta::LineDelta(_, y),
+                ..
+            } => {
+                if y > 0.1 {
+                    Some(InputEvent::MouseWheelUp)
+                } else if y < -0.1 {
+                    Some(InputEvent::MouseWheelDown)
+                } else {
+                    None
+                }
+            }
+            WindowEvent::MouseInput { button, state, .. } => {
+                Some(InputEvent::MouseInput { button, state })
+            }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        virtual_keycode: Some(code),
+                        state,
+                        ..
+                    },
+                ..
+            } => Some(InputEvent::KeyboardInput { code, state }),
+            _ => None,
+        }
+    }
+}
+
+/// Maps input events to actions.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct ActionMap<A> {
+    map: HashMap<InputEvent, A>,
+}
+
+impl<A> ActionMap<A> {
+    pub fn new() -> Self {
+        ActionMap {
+            map: HashMap::new(),
+        }
+    }
+
+    /// Maps input event to action.
+    /// Returns action previously mapped to the event.
+    pub fn insert(&mut self, input: InputEvent, action: A) -> Option<A> {
+        self.map.insert(input, action)
+    }
+
+    pub fn get(&self, input: &InputEvent) -> Option<A>
+    where
+        A: Copy,
+    {
+        self.map.get(input).copied()
+    }
+}
+
+impl<A> Default for ActionMap<A> {
+    fn default() -> Self {
+        ActionMap::new()
+    }
+}
+
+/// Player sitting in front of this machine.
+#[derive(Clone, Debug)]
+pub struct LocalPlayer<A> {
+    /// Camera entity player sees the world through.
+    /// `None` means first camera found in the world.
+    pub camera: Option<Entity>,
+    pub actions: ActionMap<A>,
+}
+
+/// Local players sharing one window.
+///
+/// Each player has own action map, so players may use
+/// different parts of keyboard or different devices.
+/// Cameras of players are drawn into splits of the view
+/// with `Renderer::set_view_splits`.
+#[derive(Clone, Debug)]
+pub struct LocalPlayers<A> {
+    players: Vec<LocalPlayer<A>>,
+}
+
+impl<A> LocalPlayers<A> {
+    pub fn new() -> Self {
+        LocalPlayers {
+            players: Vec::new(),
+        }
+    }
+
+    /// Adds local player and returns its index.
+    pub fn add(
+        &mut self,
+        camera: Option<Entity>,
+        actions: ActionMap<A>,
+    ) -> usize {
+        assert!(
+            self.players.len() < MAX_LOCAL_PLAYERS,
+            "At most {} local players are supported",
+            MAX_LOCAL_PLAYERS
+        );
+
+        self.players.push(LocalPlayer { camera, actions });
+        self.players.len() - 1
+    }
+
+    /// Removes local player.
+    /// Players after it are shifted down.
+    pub fn remove(&mut self, index: usize) -> LocalPlayer<A> {
+        self.players.remove(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.players.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.players.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&LocalPlayer<A>> {
+        self.players.get(index)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut LocalPlayer<A>> {
+        self.players.get_mut(index)
+    }
+
+    /// Returns cameras of players in order.
+    pub fn cameras(&self) -> SmallVec<[Option<Entity>; MAX_LOCAL_PLAYERS]> {
+        self.players.iter().map(|player| player.camera).collect()
+    }
+
+    /// Translates window event into actions of players
+    /// whose maps contain it.
+    /// Returns pairs of player index and action.
+    pub fn translate<T>(
+        &self,
+        event: &Event<'_, T>,
+    ) -> SmallVec<[(usize, A); MAX_LOCAL_PLAYERS]>
+    where
+        A: Copy,
+    {
+        let input = match event {
+            Event::WindowEvent { event, .. } => {
+                match InputEvent::from_window_event(event) {
+                    Some(input) => input,
+                    None => return SmallVec::new(),
+                }
+            }
+            _ => return SmallVec::new(),
+        };
+
+        self.players
+            .iter()
+            .enumerate()
+            .filter_map(|(index, player)| {
+                player.actions.get(&input).map(|action| (index, action))
+            })
+            .collect()
+    }
+}
+
+impl<A> Default for LocalPlayers<A> {
+    fn default() -> Self {
+        LocalPlayers::new()
+    }
+}
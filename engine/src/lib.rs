@@ -3,18 +3,35 @@
 
 pub mod animate;
 pub mod assets;
+pub mod audio;
+pub mod behavior;
 pub mod broker;
 pub mod camera;
 pub mod clocks;
 pub mod config;
+pub mod console;
+pub mod cvar;
 pub mod debug;
 pub mod engine;
 pub mod fps_counter;
+pub mod input;
+pub mod lifecycle;
 pub mod light;
+pub mod logging;
+pub mod navmesh;
+pub mod particles;
+pub mod photo;
 pub mod physics;
 pub mod renderer;
+pub mod save;
+pub mod scatter;
 pub mod scene;
+pub mod schedule;
+pub mod script;
+pub mod time_of_day;
 pub mod util;
+pub mod water;
+pub mod weather;
 
 // use {
 //     stats_alloc::{StatsAlloc, INSTRUMENTED_SYSTEM},
@@ -0,0 +1,218 @@
+//! Entity despawn lifecycle and prefab pooling.
+//!
+//! Entities should be despawned with `despawn` or by attaching `Despawn`
+//! component instead of calling `World::despawn` directly.
+//! This despawns children attached with `Local3` as well and runs
+//! `OnDespawn` hooks before components are dropped.
+//!
+//! Renderer and physics release state of despawned entities on their own:
+//! physics reports removed bodies on next step and renderer frees BLASes
+//! and descriptor indices of meshes and textures a few frames after they
+//! were last drawn.
+
+use {
+    crate::{
+        assets::{AssetKey, Prefab},
+        broker::EventBroker,
+        engine::{Engine, PrefabLoader, System, SystemContext},
+        scene::Local3,
+    },
+    eyre::Report,
+    goods::AssetDefaultFormat,
+    hecs::{Entity, World},
+    parking_lot::Mutex,
+    std::{collections::HashMap, sync::Arc},
+};
+
+type Hook = Box<dyn FnOnce(Entity, &mut World) + Send + Sync>;
+
+/// Hooks invoked when entity is despawned.
+///
+/// Hooks run in order they were added, while entity
+/// and all its components are still alive.
+pub struct OnDespawn {
+    hooks: Vec<Hook>,
+}
+
+impl OnDespawn {
+    pub fn new() -> Self {
+        OnDespawn { hooks: Vec::new() }
+    }
+
+    /// Adds hook to this component.
+    pub fn with<F>(mut self, hook: F) -> Self
+    where
+        F: FnOnce(Entity, &mut World) + Send + Sync + 'static,
+    {
+        self.push(hook);
+        self
+    }
+
+    /// Adds hook to this component.
+    pub fn push<F>(&mut self, hook: F)
+    where
+        F: FnOnce(Entity, &mut World) + Send + Sync + 'static,
+    {
+        self.hooks.push(Box::new(hook));
+    }
+}
+
+impl Default for OnDespawn {
+    fn default() -> Self {
+        OnDespawn::new()
+    }
+}
+
+/// Marks entity to be despawned by `LifecycleSystem`.
+#[derive(Clone, Copy, Debug)]
+pub struct Despawn;
+
+/// Resource with entities despawned during last frame.
+/// Cleared by `LifecycleSystem` before new events are added.
+pub type DespawnEvents = EventBroker<Entity>;
+
+/// Despawns entity together with its `Local3` descendants.
+///
+/// Descendants are despawned before their parents.
+/// `OnDespawn` hooks of each entity are invoked right before it is despawned.
+///
+/// Returns all despawned entities.
+/// Returns empty vector if entity does not exist.
+pub fn despawn(world: &mut World, entity: Entity) -> Vec<Entity> {
+    if !world.contains(entity) {
+        return Vec::new();
+    }
+
+    let mut entities = vec![entity];
+    let mut next = 0;
+    while next < entities.len() {
+        let parent = entities[next];
+        entities.extend(
+            world
+                .query::<&Local3>()
+                .iter()
+                .filter(|(_, local)| local.parent == parent)
+                .map(|(child, _)| child),
+        );
+        next += 1;
+    }
+
+    for &entity in entities.iter().rev() {
+        if let Ok(on_despawn) = world.remove_one::<OnDespawn>(entity) {
+            for hook in on_despawn.hooks {
+                hook(entity, world);
+            }
+        }
+
+        // Hook may despawn entity itself.
+        let _ = world.despawn(entity);
+    }
+
+    entities
+}
+
+/// System that despawns entities marked with `Despawn`.
+///
+/// Inserts `DespawnEvents` resource if it is missing.
+/// Added to engine schedule by default.
+pub struct LifecycleSystem;
+
+impl System for LifecycleSystem {
+    fn run(&mut self, ctx: SystemContext<'_>) {
+        if !ctx.resources.contains::<DespawnEvents>() {
+            ctx.resources.insert(DespawnEvents::new());
+        }
+
+        let events = ctx.resources.get_mut::<DespawnEvents>().unwrap();
+        events.clear();
+
+        let marked: Vec<_> = ctx
+            .world
+            .query::<&Despawn>()
+            .iter()
+            .map(|(entity, _)| entity)
+            .collect();
+
+        for entity in marked {
+            for entity in despawn(ctx.world, entity) {
+                events.add(entity);
+            }
+        }
+    }
+}
+
+/// Pool of loaded prefabs.
+///
+/// First spawn of a prefab loads it asynchronously,
+/// subsequent spawns clone pooled prefab and spawn it immediately,
+/// sharing meshes, textures and acceleration structures
+/// with other instances.
+pub struct PrefabPool<P> {
+    loader: PrefabLoader,
+    prefabs: Arc<Mutex<HashMap<AssetKey, P>>>,
+}
+
+impl<P> Clone for PrefabPool<P> {
+    fn clone(&self) -> Self {
+        PrefabPool {
+            loader: self.loader.clone(),
+            prefabs: self.prefabs.clone(),
+        }
+    }
+}
+
+impl<P> PrefabPool<P>
+where
+    P: Prefab + AssetDefaultFormat<AssetKey> + Clone + Send + 'static,
+{
+    pub fn new(engine: &Engine) -> Self {
+        PrefabPool {
+            loader: engine.prefab_loader(),
+            prefabs: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Spawns prefab instance.
+    ///
+    /// If prefab is pooled it is spawned immediately.
+    /// Otherwise returned `Entity` is spawned at the beginning of
+    /// `Engine::advance` after prefab is loaded.
+    pub fn spawn(
+        &self,
+        world: &mut World,
+        key: AssetKey,
+        info: P::Info,
+    ) -> Entity {
+        let pooled = self.prefabs.lock().get(&key).cloned();
+
+        if let Some(prefab) = pooled {
+            let entity = world.reserve_entity();
+            prefab.spawn(info, world, entity);
+            return entity;
+        }
+
+        tracing::info!("Loading pooled prefab '{}'", key);
+
+        let handle = self.loader.assets().load::<P>(key.clone());
+        let prefabs = self.prefabs.clone();
+        let pool_key = key.clone();
+
+        self.loader.make_prefab(world, key, info, async move {
+            let prefab = handle.await.map_err(Report::from)?;
+            prefabs.lock().insert(pool_key, prefab.clone());
+            Ok(prefab)
+        })
+    }
+
+    /// Checks if prefab is loaded into the pool.
+    pub fn is_pooled(&self, key: &AssetKey) -> bool {
+        self.prefabs.lock().contains_key(key)
+    }
+
+    /// Removes prefab from the pool.
+    /// Resources it shares with spawned instances are freed
+    /// when last instance is despawned.
+    pub fn evict(&self, key: &AssetKey) {
+        self.prefabs.lock().remove(key);
+    }
+}
@@ -17,3 +17,14 @@ pub struct DirectionalLight {
 pub struct SkyLight {
     pub radiance: [f32; 3],
 }
+
+/// Exponential distance fog applied when combining final image.
+/// Only first `Fog` component in the world is used.
+#[derive(Clone, Copy, Debug)]
+pub struct Fog {
+    /// Extinction per unit of distance.
+    pub density: f32,
+
+    /// Radiance scattered towards viewer by fog.
+    pub color: [f32; 3],
+}
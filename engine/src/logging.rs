@@ -0,0 +1,261 @@
+//! Logging setup.
+//!
+//! Traces are written to console and as JSON into rotating files.
+//! Panics are logged with backtrace and crash context,
+//! and file output is flushed before the process dies.
+//! Records are written to files by dedicated thread,
+//! which keeps running after flush and stops on `shutdown`.
+
+use {
+    color_eyre::Report,
+    once_cell::sync::Lazy,
+    parking_lot::Mutex,
+    std::{
+        borrow::Cow,
+        collections::BTreeMap,
+        fmt::Write as _,
+        io::{self, Write},
+        path::PathBuf,
+        thread::JoinHandle,
+        time::Duration,
+    },
+    tracing_subscriber::{layer::SubscriberExt as _, EnvFilter},
+};
+
+/// File writer thread. Taken on shutdown.
+static WRITER: Lazy<Mutex<Option<FileWriter>>> = Lazy::new(|| Mutex::new(None));
+
+/// How long `flush` waits for file writer thread.
+/// Writer thread itself may be panicking.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
+
+type CrashContext = BTreeMap<&'static str, Cow<'static, str>>;
+
+/// Values reported with panics.
+static CRASH_CONTEXT: Lazy<Mutex<CrashContext>> =
+    Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+/// How often log file is rotated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogRotation {
+    Hourly,
+    Daily,
+    Never,
+}
+
+#[derive(Clone, Debug)]
+pub struct LogConfig {
+    /// Directory for log files.
+    pub directory: PathBuf,
+
+    /// Log file name prefix.
+    /// Rotated files get date suffix.
+    pub file_prefix: String,
+
+    pub rotation: LogRotation,
+
+    /// Number of log files to keep.
+    /// Oldest files are removed on startup.
+    pub max_files: usize,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            directory: PathBuf::from("logs"),
+            file_prefix: "wilds.log".to_owned(),
+            rotation: LogRotation::Daily,
+            max_files: 7,
+        }
+    }
+}
+
+/// Installs global tracing subscriber and panic hook.
+///
+/// Must be called once, after other panic hooks are installed,
+/// e.g. after `color_eyre::install`.
+pub fn init(config: LogConfig) -> Result<(), Report> {
+    std::fs::create_dir_all(&config.directory)?;
+    remove_old_files(&config);
+
+    let appender = match config.rotation {
+        LogRotation::Hourly => tracing_appender::rolling::hourly(
+            &config.directory,
+            &config.file_prefix,
+        ),
+        LogRotation::Daily => tracing_appender::rolling::daily(
+            &config.directory,
+            &config.file_prefix,
+        ),
+        LogRotation::Never => tracing_appender::rolling::never(
+            &config.directory,
+            &config.file_prefix,
+        ),
+    };
+
+    let (sender, receiver) = flume::unbounded();
+    let thread = std::thread::Builder::new()
+        .name("log-writer".to_owned())
+        .spawn(move || write_records(appender, receiver))?;
+
+    *WRITER.lock() = Some(FileWriter {
+        sender: sender.clone(),
+        thread,
+    });
+
+    let writer = move || RecordWriter {
+        sender: sender.clone(),
+    };
+
+    tracing::subscriber::set_global_default(
+        tracing_subscriber::registry()
+            .with(EnvFilter::from_default_env())
+            .with(tracing_subscriber::fmt::layer().pretty())
+            .with(tracing_subscriber::fmt::layer().json().with_writer(writer))
+            .with(tracing_error::ErrorLayer::default())
+            .with(crate::console::ConsoleLayer),
+    )?;
+
+    let next_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let backtrace = backtrace::Backtrace::new();
+
+        let mut context = String::new();
+        // Panic may happen while context is being updated.
+        if let Some(values) = CRASH_CONTEXT.try_lock() {
+            for (key, value) in values.iter() {
+                let _ = write!(context, "{}={}; ", key, value);
+            }
+        }
+
+        tracing::error!(
+            panic = %info,
+            context = %context,
+            backtrace = ?backtrace,
+            "Application panicked"
+        );
+
+        flush();
+        next_hook(info);
+    }));
+
+    Ok(())
+}
+
+/// Flushes log files.
+/// Waits until records emitted before this call are written.
+pub fn flush() {
+    // Panic may happen while writer is being shut down.
+    let sender = match WRITER.try_lock() {
+        Some(writer) => match &*writer {
+            Some(writer) => writer.sender.clone(),
+            None => return,
+        },
+        None => return,
+    };
+
+    let (ack, done) = flume::bounded(1);
+    if sender.send(Message::Flush(ack)).is_ok() {
+        let _ = done.recv_timeout(FLUSH_TIMEOUT);
+    }
+}
+
+/// Flushes log files and stops file writer thread.
+/// Records emitted after this call are not written to files.
+pub fn shutdown() {
+    if let Some(writer) = WRITER.lock().take() {
+        let _ = writer.sender.send(Message::Shutdown);
+        let _ = writer.thread.join();
+    }
+}
+
+/// Sets value reported with panics, e.g. current render pass.
+pub fn set_crash_context(
+    key: &'static str,
+    value: impl Into<Cow<'static, str>>,
+) {
+    CRASH_CONTEXT.lock().insert(key, value.into());
+}
+
+enum Message {
+    Record(Vec<u8>),
+
+    /// Flushes file and acknowledges through the sender.
+    Flush(flume::Sender<()>),
+    Shutdown,
+}
+
+struct FileWriter {
+    sender: flume::Sender<Message>,
+    thread: JoinHandle<()>,
+}
+
+/// Writer used by subscriber. Sends records to file writer thread.
+struct RecordWriter {
+    sender: flume::Sender<Message>,
+}
+
+impl Write for RecordWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Records emitted after shutdown are dropped.
+        let _ = self.sender.send(Message::Record(buf.to_vec()));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn write_records(mut file: impl Write, receiver: flume::Receiver<Message>) {
+    for message in receiver.iter() {
+        match message {
+            Message::Record(record) => {
+                if let Err(err) = file.write_all(&record) {
+                    eprintln!("Failed to write log record: {}", err);
+                }
+            }
+            Message::Flush(ack) => {
+                let _ = file.flush();
+                let _ = ack.send(());
+            }
+            Message::Shutdown => break,
+        }
+    }
+
+    let _ = file.flush();
+}
+
+/// Removes old log files, leaving space for the new one.
+fn remove_old_files(config: &LogConfig) {
+    let entries = match std::fs::read_dir(&config.directory) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    let mut files: Vec<_> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .map_or(false, |name| name.starts_with(&config.file_prefix))
+        })
+        .map(|entry| entry.path())
+        .collect();
+
+    // Date suffixes sort chronologically.
+    files.sort();
+
+    let keep = config.max_files.saturating_sub(1);
+    let remove = files.len().saturating_sub(keep);
+    for path in &files[..remove] {
+        if let Err(err) = std::fs::remove_file(path) {
+            eprintln!(
+                "Failed to remove log file '{}': {}",
+                path.display(),
+                err
+            );
+        }
+    }
+}
@@ -0,0 +1,1028 @@
+//! Navigation mesh generation and path-finding.
+//!
+//! Level geometry is voxelized into heightfield columns.
+//! Walkable surfaces with enough clearance are eroded by agent radius
+//! and merged into rectangular polygons.
+//! Paths are found with A* over polygons and straightened
+//! with funnel algorithm.
+
+use {
+    crate::{
+        engine::{System, SystemContext},
+        physics::{Colliders, RigidBody},
+        renderer::{IndexType, PrimitiveTopology, Renderable, Semantics},
+        scene::Global3,
+    },
+    hecs::World,
+    nalgebra as na,
+    ncollide3d::shape::{HeightField, TriMesh},
+    ordered_float::OrderedFloat,
+    std::{
+        cmp::Reverse,
+        collections::{BinaryHeap, HashMap},
+        convert::TryFrom as _,
+        ops::Range,
+    },
+};
+
+pub type Triangle = [na::Point3<f32>; 3];
+
+/// Parameters of navigation mesh generation.
+#[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
+pub struct NavMeshConfig {
+    /// Size of heightfield cell along X and Z axes.
+    pub cell_size: f32,
+
+    /// Surfaces closer than this along Y axis are merged.
+    pub cell_height: f32,
+
+    /// Minimal clearance above walkable surface.
+    pub agent_height: f32,
+
+    /// Walkable area is shrunk by this distance from obstacles and edges.
+    pub agent_radius: f32,
+
+    /// Maximal height difference agent can step over.
+    pub max_climb: f32,
+
+    /// Maximal walkable slope in radians.
+    pub max_slope: f32,
+}
+
+impl Default for NavMeshConfig {
+    fn default() -> Self {
+        NavMeshConfig {
+            cell_size: 0.25,
+            cell_height: 0.1,
+            agent_height: 1.8,
+            agent_radius: 0.4,
+            max_climb: 0.4,
+            max_slope: std::f32::consts::FRAC_PI_4,
+        }
+    }
+}
+
+/// Walkable polygon of the navigation mesh.
+#[derive(Clone, Debug)]
+pub struct NavPolygon {
+    /// Corners starting at minimal X and Z, then maximal X,
+    /// then maximal X and Z, then maximal Z.
+    pub vertices: [na::Point3<f32>; 4],
+
+    /// Connections to adjacent polygons.
+    pub links: Vec<NavLink>,
+}
+
+impl NavPolygon {
+    pub fn center(&self) -> na::Point3<f32> {
+        let sum = self
+            .vertices
+            .iter()
+            .fold(na::Vector3::zeros(), |acc, v| acc + v.coords);
+        na::Point3::from(sum / 4.0)
+    }
+
+    fn contains_xz(&self, point: &na::Point3<f32>) -> bool {
+        let [min, _, max, _] = &self.vertices;
+        point.x >= min.x
+            && point.x <= max.x
+            && point.z >= min.z
+            && point.z <= max.z
+    }
+
+    /// Height of the polygon surface at XZ coordinates of the point.
+    fn height_at(&self, point: &na::Point3<f32>) -> f32 {
+        let [v0, v1, v2, v3] = &self.vertices;
+        let u = ((point.x - v0.x) / (v2.x - v0.x)).max(0.0).min(1.0);
+        let w = ((point.z - v0.z) / (v2.z - v0.z)).max(0.0).min(1.0);
+
+        // `v1` is at max X and min Z, `v3` is at min X and max Z.
+        let near = v0.y + (v1.y - v0.y) * u;
+        let far = v3.y + (v2.y - v3.y) * u;
+        near + (far - near) * w
+    }
+}
+
+/// Connection between adjacent polygons.
+#[derive(Clone, Copy, Debug)]
+pub struct NavLink {
+    /// Index of adjacent polygon.
+    pub polygon: usize,
+
+    /// Endpoints of shared edge.
+    pub portal: [na::Point3<f32>; 2],
+}
+
+/// Navigation mesh built from level geometry.
+#[derive(Clone, Debug)]
+pub struct NavMesh {
+    polygons: Vec<NavPolygon>,
+    config: NavMeshConfig,
+}
+
+impl NavMesh {
+    /// Builds navigation mesh from triangles in world space.
+    pub fn build(triangles: &[Triangle], config: NavMeshConfig) -> Self {
+        let field = Heightfield::voxelize(triangles, &config);
+        let polygons = field.build_polygons(&config);
+
+        tracing::info!(
+            "Navigation mesh built with {} polygons",
+            polygons.len()
+        );
+
+        NavMesh { polygons, config }
+    }
+
+    /// Builds navigation mesh from geometry of the world.
+    /// See `world_triangles`.
+    pub fn from_world(world: &World, config: NavMeshConfig) -> Self {
+        NavMesh::build(&world_triangles(world), config)
+    }
+
+    pub fn polygons(&self) -> &[NavPolygon] {
+        &self.polygons
+    }
+
+    pub fn config(&self) -> &NavMeshConfig {
+        &self.config
+    }
+
+    /// Finds polygon under the point.
+    /// Polygons above the point by no more than `max_climb`
+    /// and below by no more than `agent_height` are considered.
+    pub fn find_polygon(&self, point: &na::Point3<f32>) -> Option<usize> {
+        self.polygons
+            .iter()
+            .enumerate()
+            .filter(|(_, polygon)| polygon.contains_xz(point))
+            .filter_map(|(index, polygon)| {
+                let dy = point.y - polygon.height_at(point);
+                if dy >= -self.config.max_climb
+                    && dy <= self.config.agent_height
+                {
+                    Some((index, dy.abs()))
+                } else {
+                    None
+                }
+            })
+            .min_by_key(|&(_, dy)| OrderedFloat(dy))
+            .map(|(index, _)| index)
+    }
+
+    /// Finds path between two points.
+    /// Returned path starts at `from` and ends at `to`
+    /// projected onto the navigation mesh.
+    pub fn find_path(
+        &self,
+        from: na::Point3<f32>,
+        to: na::Point3<f32>,
+    ) -> Option<Vec<na::Point3<f32>>> {
+        let start = self.find_polygon(&from)?;
+        let goal = self.find_polygon(&to)?;
+
+        let from = na::Point3::new(
+            from.x,
+            self.polygons[start].height_at(&from),
+            from.z,
+        );
+        let to =
+            na::Point3::new(to.x, self.polygons[goal].height_at(&to), to.z);
+
+        let corridor = self.find_corridor(start, goal, &to)?;
+
+        let mut portals = Vec::with_capacity(corridor.len() + 1);
+        portals.push((from, from));
+
+        for pair in corridor.windows(2) {
+            let link = self.polygons[pair[0]]
+                .links
+                .iter()
+                .find(|link| link.polygon == pair[1])
+                .unwrap();
+
+            let [a, b] = link.portal;
+            let center = self.polygons[pair[0]].center();
+            let mid = na::center(&a, &b);
+
+            // Order endpoints as (left, right) along travel direction.
+            if triarea2(&center, &mid, &a) > 0.0 {
+                portals.push((b, a));
+            } else {
+                portals.push((a, b));
+            }
+        }
+
+        portals.push((to, to));
+        Some(string_pull(&portals))
+    }
+
+    /// A* search over polygons.
+    fn find_corridor(
+        &self,
+        start: usize,
+        goal: usize,
+        to: &na::Point3<f32>,
+    ) -> Option<Vec<usize>> {
+        let centers: Vec<_> =
+            self.polygons.iter().map(NavPolygon::center).collect();
+
+        let mut came_from = HashMap::new();
+        let mut cost = HashMap::new();
+        let mut open = BinaryHeap::new();
+
+        cost.insert(start, 0.0f32);
+        open.push(Reverse((OrderedFloat(0.0f32), start)));
+
+        while let Some(Reverse((_, current))) = open.pop() {
+            if current == goal {
+                let mut corridor = vec![goal];
+                let mut node = goal;
+                while let Some(&prev) = came_from.get(&node) {
+                    corridor.push(prev);
+                    node = prev;
+                }
+                corridor.reverse();
+                return Some(corridor);
+            }
+
+            let current_cost = cost[&current];
+
+            for link in &self.polygons[current].links {
+                let next = link.polygon;
+                let next_cost = current_cost
+                    + na::distance(&centers[current], &centers[next]);
+
+                if cost.get(&next).map_or(true, |&c| next_cost < c) {
+                    cost.insert(next, next_cost);
+                    came_from.insert(next, current);
+
+                    let estimate = next_cost + na::distance(&centers[next], to);
+                    open.push(Reverse((OrderedFloat(estimate), next)));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// Collects triangles of level geometry from the world.
+///
+/// Renderables are used only if their meshes retain CPU-side data.
+/// Trimesh and heightfield colliders are used as well.
+pub fn world_triangles(world: &World) -> Vec<Triangle> {
+    let mut triangles = Vec::new();
+
+    for (_, (renderable, global)) in
+        world.query::<(&Renderable, &Global3)>().iter()
+    {
+        let transform = global.to_homogeneous();
+        let start = triangles.len();
+        mesh_triangles(renderable, &mut triangles);
+        for triangle in &mut triangles[start..] {
+            for vertex in triangle {
+                *vertex = transform.transform_point(vertex);
+            }
+        }
+    }
+
+    for (_, (colliders, global)) in
+        world.query::<(&Colliders, &Global3)>().iter()
+    {
+        let transform = global.to_homogeneous();
+        for desc in colliders.iter() {
+            let shape = desc.get_shape();
+
+            if let Some(trimesh) = shape.as_shape::<TriMesh<f32>>() {
+                let points = trimesh.points();
+                triangles.extend(trimesh.faces().iter().map(|face| {
+                    let i = face.indices;
+                    [
+                        transform.transform_point(&points[i.x]),
+                        transform.transform_point(&points[i.y]),
+                        transform.transform_point(&points[i.z]),
+                    ]
+                }));
+            } else if let Some(heightfield) =
+                shape.as_shape::<HeightField<f32>>()
+            {
+                triangles.extend(heightfield.triangles().map(|triangle| {
+                    [
+                        transform.transform_point(triangle.a()),
+                        transform.transform_point(triangle.b()),
+                        transform.transform_point(triangle.c()),
+                    ]
+                }));
+            }
+        }
+    }
+
+    triangles
+}
+
+fn mesh_triangles(renderable: &Renderable, output: &mut Vec<Triangle>) {
+    let data = match renderable.mesh.data() {
+        Some(data) => data,
+        None => return,
+    };
+
+    if data.topology != PrimitiveTopology::TriangleList {
+        return;
+    }
+
+    let (binding, location) = match data.bindings.iter().find_map(|b| {
+        b.layout
+            .locations
+            .iter()
+            .find(|l| l.semantics == Some(Semantics::Position3d))
+            .map(|l| (b, l))
+    }) {
+        Some(found) => found,
+        None => return,
+    };
+
+    let stride = binding.layout.stride as usize;
+    let offset = location.offset as usize;
+    let bytes = &*binding.data;
+
+    let position = |index: usize| -> Option<na::Point3<f32>> {
+        let start = index * stride + offset;
+        let bytes = bytes.get(start..start + 12)?;
+        let mut coords = [0.0f32; 3];
+        for (i, c) in coords.iter_mut().enumerate() {
+            let mut raw = [0; 4];
+            raw.copy_from_slice(&bytes[i * 4..i * 4 + 4]);
+            *c = f32::from_le_bytes(raw);
+        }
+        Some(coords.into())
+    };
+
+    let indices: Vec<usize> = match &data.indices {
+        Some(indices) => match indices.index_type {
+            IndexType::U16 => indices
+                .data
+                .chunks_exact(2)
+                .map(|b| u16::from_le_bytes([b[0], b[1]]) as usize)
+                .collect(),
+            IndexType::U32 => indices
+                .data
+                .chunks_exact(4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+                .collect(),
+        },
+        None => (0..bytes.len() / stride.max(1)).collect(),
+    };
+
+    output.extend(indices.chunks_exact(3).filter_map(|t| {
+        Some([position(t[0])?, position(t[1])?, position(t[2])?])
+    }));
+}
+
+/// Component that moves entity along path on navigation mesh.
+/// Requires `NavMesh` resource.
+#[derive(Clone, Debug)]
+pub struct NavAgent {
+    /// Movement speed in units per second.
+    pub speed: f32,
+
+    /// Distance at which waypoint is considered reached.
+    pub tolerance: f32,
+
+    target: Option<na::Point3<f32>>,
+    path: Vec<na::Point3<f32>>,
+}
+
+impl NavAgent {
+    pub fn new(speed: f32) -> Self {
+        NavAgent {
+            speed,
+            tolerance: 0.1,
+            target: None,
+            path: Vec::new(),
+        }
+    }
+
+    /// Sets destination. Path is found on next system run.
+    pub fn set_target(&mut self, target: na::Point3<f32>) {
+        self.target = Some(target);
+        self.path.clear();
+    }
+
+    /// Stops the agent.
+    pub fn stop(&mut self) {
+        self.target = None;
+        self.path.clear();
+    }
+
+    pub fn target(&self) -> Option<&na::Point3<f32>> {
+        self.target.as_ref()
+    }
+
+    /// Remaining waypoints.
+    pub fn path(&self) -> &[na::Point3<f32>] {
+        &self.path
+    }
+
+    /// Returns desired velocity and advances path.
+    fn steer(
+        &mut self,
+        position: &na::Point3<f32>,
+        navmesh: &NavMesh,
+    ) -> Option<na::Vector3<f32>> {
+        let target = self.target?;
+
+        if self.path.is_empty() {
+            match navmesh.find_path(*position, target) {
+                Some(path) => {
+                    // First waypoint is current position.
+                    self.path = path.into_iter().skip(1).rev().collect();
+                }
+                None => {
+                    tracing::debug!("No path to {}", target);
+                    self.target = None;
+                    return None;
+                }
+            }
+        }
+
+        while let Some(waypoint) = self.path.last() {
+            let offset = waypoint - position;
+            let flat = na::Vector3::new(offset.x, 0.0, offset.z);
+
+            if flat.norm() > self.tolerance {
+                return Some(flat.normalize() * self.speed);
+            }
+
+            self.path.pop();
+        }
+
+        self.target = None;
+        None
+    }
+}
+
+/// Moves entities with `NavAgent` component.
+///
+/// Agents with rigid bodies are driven by setting horizontal velocity.
+/// Other agents are moved directly.
+pub struct NavAgentSystem;
+
+impl System for NavAgentSystem {
+    fn run(&mut self, ctx: SystemContext<'_>) {
+        let navmesh = match ctx.resources.get::<NavMesh>() {
+            Some(navmesh) => navmesh,
+            None => return,
+        };
+
+        let delta = ctx.clocks.sim_delta.as_secs_f32();
+
+        for (_, (agent, global, body)) in ctx
+            .world
+            .query::<(&mut NavAgent, &mut Global3, Option<&mut RigidBody<f32>>)>()
+            .iter()
+        {
+            let position = na::Point3::from(global.iso.translation.vector);
+            let velocity = agent.steer(&position, navmesh);
+
+            match body {
+                Some(body) => {
+                    let mut linear = body.velocity().linear;
+                    let desired = velocity.unwrap_or_else(na::Vector3::zeros);
+                    linear.x = desired.x;
+                    linear.z = desired.z;
+                    body.set_linear_velocity(linear);
+                }
+                None => {
+                    if let Some(velocity) = velocity {
+                        global.iso.translation.vector += velocity * delta;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Surface in heightfield column.
+#[derive(Clone, Copy, Debug)]
+struct Surface {
+    height: f32,
+    walkable: bool,
+}
+
+/// Walkable surface with enough clearance.
+#[derive(Clone, Copy, Debug)]
+struct Node {
+    height: f32,
+    polygon: Option<usize>,
+}
+
+struct Heightfield {
+    origin: na::Point3<f32>,
+    cell_size: f32,
+    width: usize,
+    depth: usize,
+    columns: Vec<Range<usize>>,
+    nodes: Vec<Node>,
+}
+
+impl Heightfield {
+    fn voxelize(triangles: &[Triangle], config: &NavMeshConfig) -> Self {
+        let mut min = na::Point3::new(f32::MAX, f32::MAX, f32::MAX);
+        let mut max = na::Point3::new(f32::MIN, f32::MIN, f32::MIN);
+
+        for vertex in triangles.iter().flatten() {
+            min = min.inf(vertex);
+            max = max.sup(vertex);
+        }
+
+        if triangles.is_empty() {
+            min = na::Point3::origin();
+            max = na::Point3::origin();
+        }
+
+        let cs = config.cell_size;
+        let width = ((max.x - min.x) / cs).ceil() as usize + 1;
+        let depth = ((max.z - min.z) / cs).ceil() as usize + 1;
+
+        let mut surfaces = vec![Vec::new(); width * depth];
+        let min_normal_y = config.max_slope.cos();
+
+        for [a, b, c] in triangles {
+            let normal = (b - a).cross(&(c - a));
+            let normal = match normal.try_normalize(std::f32::EPSILON) {
+                Some(normal) => normal,
+                None => continue,
+            };
+
+            // Thin surfaces are walkable from both sides.
+            let walkable = normal.y.abs() >= min_normal_y;
+
+            let x0 = ((a.x.min(b.x).min(c.x) - min.x) / cs).floor() as usize;
+            let x1 = ((a.x.max(b.x).max(c.x) - min.x) / cs).ceil() as usize;
+            let z0 = ((a.z.min(b.z).min(c.z) - min.z) / cs).floor() as usize;
+            let z1 = ((a.z.max(b.z).max(c.z) - min.z) / cs).ceil() as usize;
+
+            for z in z0..z1.min(depth) {
+                for x in x0..x1.min(width) {
+                    let px = min.x + (x as f32 + 0.5) * cs;
+                    let pz = min.z + (z as f32 + 0.5) * cs;
+
+                    if let Some(height) = height_in_triangle(a, b, c, px, pz) {
+                        surfaces[z * width + x]
+                            .push(Surface { height, walkable });
+                    }
+                }
+            }
+        }
+
+        let mut columns = Vec::with_capacity(width * depth);
+        let mut nodes = Vec::new();
+
+        for column in &mut surfaces {
+            column.sort_by_key(|s| OrderedFloat(s.height));
+
+            // Merge surfaces closer than cell height.
+            let mut merged: Vec<Surface> = Vec::with_capacity(column.len());
+            for &surface in column.iter() {
+                match merged.last_mut() {
+                    Some(last)
+                        if surface.height - last.height
+                            < config.cell_height =>
+                    {
+                        *last = surface;
+                    }
+                    _ => merged.push(surface),
+                }
+            }
+
+            let start = nodes.len();
+            for (index, surface) in merged.iter().enumerate() {
+                let clearance = merged
+                    .get(index + 1)
+                    .map_or(f32::MAX, |above| above.height - surface.height);
+
+                if surface.walkable && clearance >= config.agent_height {
+                    nodes.push(Node {
+                        height: surface.height,
+                        polygon: None,
+                    });
+                }
+            }
+            columns.push(start..nodes.len());
+        }
+
+        let mut field = Heightfield {
+            origin: min,
+            cell_size: cs,
+            width,
+            depth,
+            columns,
+            nodes,
+        };
+
+        field.erode(config);
+        field
+    }
+
+    /// Finds node in neighbor cell reachable from specified node.
+    fn neighbor(
+        &self,
+        x: usize,
+        z: usize,
+        height: f32,
+        dx: isize,
+        dz: isize,
+        max_climb: f32,
+    ) -> Option<usize> {
+        let nx = usize::try_from(x as isize + dx).ok()?;
+        let nz = usize::try_from(z as isize + dz).ok()?;
+
+        if nx >= self.width || nz >= self.depth {
+            return None;
+        }
+
+        self.columns[nz * self.width + nx]
+            .clone()
+            .filter(|&n| (self.nodes[n].height - height).abs() <= max_climb)
+            .min_by_key(|&n| {
+                OrderedFloat((self.nodes[n].height - height).abs())
+            })
+    }
+
+    /// Removes nodes closer than agent radius to edges.
+    fn erode(&mut self, config: &NavMeshConfig) {
+        let steps = (config.agent_radius / self.cell_size).ceil() as usize;
+
+        for _ in 0..steps {
+            let mut columns = self.columns.clone();
+            let mut nodes = Vec::with_capacity(self.nodes.len());
+
+            for z in 0..self.depth {
+                for x in 0..self.width {
+                    let column = z * self.width + x;
+                    let start = nodes.len();
+
+                    for n in self.columns[column].clone() {
+                        let height = self.nodes[n].height;
+                        let inner = NEIGHBORS.iter().all(|&(dx, dz)| {
+                            self.neighbor(
+                                x,
+                                z,
+                                height,
+                                dx,
+                                dz,
+                                config.max_climb,
+                            )
+                            .is_some()
+                        });
+
+                        if inner {
+                            nodes.push(self.nodes[n]);
+                        }
+                    }
+
+                    columns[column] = start..nodes.len();
+                }
+            }
+
+            self.columns = columns;
+            self.nodes = nodes;
+        }
+    }
+
+    /// Merges nodes into rectangles and links adjacent rectangles.
+    fn build_polygons(mut self, config: &NavMeshConfig) -> Vec<NavPolygon> {
+        let mut rects = Vec::new();
+
+        for z in 0..self.depth {
+            for x in 0..self.width {
+                for n in self.columns[z * self.width + x].clone() {
+                    if self.nodes[n].polygon.is_none() {
+                        let rect = self.grow_rect(x, z, n, rects.len(), config);
+                        rects.push(rect);
+                    }
+                }
+            }
+        }
+
+        let mut polygons: Vec<_> = rects
+            .iter()
+            .map(|rect| NavPolygon {
+                vertices: self.rect_vertices(rect),
+                links: Vec::new(),
+            })
+            .collect();
+
+        self.link_rects(&rects, &mut polygons, config);
+        polygons
+    }
+
+    /// Grows rectangle of unassigned nodes starting from node `n`.
+    fn grow_rect(
+        &mut self,
+        x: usize,
+        z: usize,
+        n: usize,
+        polygon: usize,
+        config: &NavMeshConfig,
+    ) -> Rect {
+        let base = self.nodes[n].height;
+        let fits = |field: &Self, n: usize| {
+            field.nodes[n].polygon.is_none()
+                && (field.nodes[n].height - base).abs() <= config.max_climb
+        };
+
+        // Extend along X.
+        let mut row = vec![n];
+        let mut x1 = x + 1;
+        while let Some(next) = self.neighbor(
+            x1 - 1,
+            z,
+            self.nodes[row[row.len() - 1]].height,
+            1,
+            0,
+            config.max_climb,
+        ) {
+            if !fits(self, next) {
+                break;
+            }
+            row.push(next);
+            x1 += 1;
+        }
+
+        // Extend along Z while whole row fits.
+        let mut cells = row.clone();
+        let mut z1 = z + 1;
+        'rows: loop {
+            let mut next_row = Vec::with_capacity(row.len());
+            for (i, &prev) in row.iter().enumerate() {
+                let next = match self.neighbor(
+                    x + i,
+                    z1 - 1,
+                    self.nodes[prev].height,
+                    0,
+                    1,
+                    config.max_climb,
+                ) {
+                    Some(next) if fits(self, next) => next,
+                    _ => break 'rows,
+                };
+
+                if let Some(&left) = next_row.last() {
+                    let h: f32 = self.nodes[left].height;
+                    if (self.nodes[next].height - h).abs() > config.max_climb {
+                        break 'rows;
+                    }
+                }
+
+                next_row.push(next);
+            }
+
+            cells.extend_from_slice(&next_row);
+            row = next_row;
+            z1 += 1;
+        }
+
+        for &cell in &cells {
+            self.nodes[cell].polygon = Some(polygon);
+        }
+
+        Rect {
+            x: x..x1,
+            z: z..z1,
+            cells,
+        }
+    }
+
+    fn node_at(&self, rect: &Rect, x: usize, z: usize) -> &Node {
+        let width = rect.x.end - rect.x.start;
+        let index = (z - rect.z.start) * width + (x - rect.x.start);
+        &self.nodes[rect.cells[index]]
+    }
+
+    fn rect_vertices(&self, rect: &Rect) -> [na::Point3<f32>; 4] {
+        let (x0, x1) = (rect.x.start, rect.x.end - 1);
+        let (z0, z1) = (rect.z.start, rect.z.end - 1);
+
+        [
+            self.corner(rect.x.start, rect.z.start, self.node_at(rect, x0, z0)),
+            self.corner(rect.x.end, rect.z.start, self.node_at(rect, x1, z0)),
+            self.corner(rect.x.end, rect.z.end, self.node_at(rect, x1, z1)),
+            self.corner(rect.x.start, rect.z.end, self.node_at(rect, x0, z1)),
+        ]
+    }
+
+    fn corner(&self, x: usize, z: usize, node: &Node) -> na::Point3<f32> {
+        na::Point3::new(
+            self.origin.x + x as f32 * self.cell_size,
+            node.height,
+            self.origin.z + z as f32 * self.cell_size,
+        )
+    }
+
+    fn link_rects(
+        &self,
+        rects: &[Rect],
+        polygons: &mut [NavPolygon],
+        config: &NavMeshConfig,
+    ) {
+        for (index, rect) in rects.iter().enumerate() {
+            // Shared edges are collected per neighbor polygon.
+            let mut edges: HashMap<usize, Vec<na::Point3<f32>>> =
+                HashMap::new();
+
+            for z in rect.z.clone() {
+                for x in rect.x.clone() {
+                    let height = self.node_at(rect, x, z).height;
+
+                    for &(dx, dz) in &NEIGHBORS {
+                        let n = match self.neighbor(
+                            x,
+                            z,
+                            height,
+                            dx,
+                            dz,
+                            config.max_climb,
+                        ) {
+                            Some(n) => n,
+                            None => continue,
+                        };
+
+                        let other = match self.nodes[n].polygon {
+                            Some(other) if other != index => other,
+                            _ => continue,
+                        };
+
+                        let height = (height + self.nodes[n].height) / 2.0;
+                        let (a, b) = self.cell_edge(x, z, dx, dz);
+                        let edge = edges.entry(other).or_default();
+                        edge.push(na::Point3::new(a.0, height, a.1));
+                        edge.push(na::Point3::new(b.0, height, b.1));
+                    }
+                }
+            }
+
+            for (other, points) in edges {
+                // Edge points are colinear, portal spans extreme ones.
+                let first = points[0];
+                let direction = points[1] - first;
+                let project = |p: &na::Point3<f32>| (p - first).dot(&direction);
+
+                let min = points
+                    .iter()
+                    .min_by_key(|p| OrderedFloat(project(p)))
+                    .unwrap();
+                let max = points
+                    .iter()
+                    .max_by_key(|p| OrderedFloat(project(p)))
+                    .unwrap();
+
+                polygons[index].links.push(NavLink {
+                    polygon: other,
+                    portal: [*min, *max],
+                });
+            }
+        }
+    }
+
+    /// Returns XZ endpoints of cell edge facing specified direction.
+    fn cell_edge(
+        &self,
+        x: usize,
+        z: usize,
+        dx: isize,
+        dz: isize,
+    ) -> ((f32, f32), (f32, f32)) {
+        let cs = self.cell_size;
+        let x0 = self.origin.x + x as f32 * cs;
+        let z0 = self.origin.z + z as f32 * cs;
+        let (x1, z1) = (x0 + cs, z0 + cs);
+
+        match (dx, dz) {
+            (1, _) => ((x1, z0), (x1, z1)),
+            (-1, _) => ((x0, z0), (x0, z1)),
+            (_, 1) => ((x0, z1), (x1, z1)),
+            _ => ((x0, z0), (x1, z0)),
+        }
+    }
+}
+
+const NEIGHBORS: [(isize, isize); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+struct Rect {
+    x: Range<usize>,
+    z: Range<usize>,
+
+    /// Nodes in row-major order.
+    cells: Vec<usize>,
+}
+
+/// Returns height of triangle at XZ point if point is inside
+/// projection of the triangle.
+fn height_in_triangle(
+    a: &na::Point3<f32>,
+    b: &na::Point3<f32>,
+    c: &na::Point3<f32>,
+    x: f32,
+    z: f32,
+) -> Option<f32> {
+    let det = (b.z - c.z) * (a.x - c.x) + (c.x - b.x) * (a.z - c.z);
+    if det.abs() <= std::f32::EPSILON {
+        return None;
+    }
+
+    let u = ((b.z - c.z) * (x - c.x) + (c.x - b.x) * (z - c.z)) / det;
+    let v = ((c.z - a.z) * (x - c.x) + (a.x - c.x) * (z - c.z)) / det;
+    let w = 1.0 - u - v;
+
+    if u < 0.0 || v < 0.0 || w < 0.0 {
+        return None;
+    }
+
+    Some(a.y * u + b.y * v + c.y * w)
+}
+
+/// Twice signed area of triangle projected onto XZ plane.
+fn triarea2(
+    a: &na::Point3<f32>,
+    b: &na::Point3<f32>,
+    c: &na::Point3<f32>,
+) -> f32 {
+    let (ax, az) = (b.x - a.x, b.z - a.z);
+    let (bx, bz) = (c.x - a.x, c.z - a.z);
+    bx * az - ax * bz
+}
+
+fn same_xz(a: &na::Point3<f32>, b: &na::Point3<f32>) -> bool {
+    let dx = a.x - b.x;
+    let dz = a.z - b.z;
+    dx * dx + dz * dz < 1e-6
+}
+
+/// Simple stupid funnel algorithm.
+/// Portals are `(left, right)` pairs. First and last portals are
+/// degenerate and contain start and end points.
+fn string_pull(
+    portals: &[(na::Point3<f32>, na::Point3<f32>)],
+) -> Vec<na::Point3<f32>> {
+    let mut path = Vec::new();
+
+    let (mut apex, _) = portals[0];
+    let (mut left, mut right) = portals[0];
+    let (mut apex_index, mut left_index, mut right_index) = (0, 0, 0);
+
+    path.push(apex);
+
+    let mut i = 1;
+    while i < portals.len() {
+        let (next_left, next_right) = portals[i];
+
+        // Tighten right side.
+        if triarea2(&apex, &right, &next_right) <= 0.0 {
+            if same_xz(&apex, &right)
+                || triarea2(&apex, &left, &next_right) > 0.0
+            {
+                right = next_right;
+                right_index = i;
+            } else {
+                // Right crossed left, left becomes new apex.
+                path.push(left);
+                apex = left;
+                apex_index = left_index;
+                right = apex;
+                right_index = apex_index;
+                i = apex_index + 1;
+                continue;
+            }
+        }
+
+        // Tighten left side.
+        if triarea2(&apex, &left, &next_left) >= 0.0 {
+            if same_xz(&apex, &left)
+                || triarea2(&apex, &right, &next_left) < 0.0
+            {
+                left = next_left;
+                left_index = i;
+            } else {
+                // Left crossed right, right becomes new apex.
+                path.push(right);
+                apex = right;
+                apex_index = right_index;
+                left = apex;
+                left_index = apex_index;
+                i = apex_index + 1;
+                continue;
+            }
+        }
+
+        i += 1;
+    }
+
+    let (end, _) = portals[portals.len() - 1];
+    if !same_xz(&path[path.len() - 1], &end) {
+        path.push(end);
+    }
+
+    path
+}
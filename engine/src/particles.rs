@@ -0,0 +1,42 @@
+//! GPU particles.
+//!
+//! `ParticleEmitter` entities spawn particles that renderer simulates
+//! on GPU. Particles fall under gravity, bounce off visible geometry
+//! using depth and normals of the frame, and are splatted into
+//! emissive radiance as sparks.
+
+/// Spawns particles at entity's position.
+#[derive(Clone, Copy, Debug)]
+pub struct ParticleEmitter {
+    /// Particles spawned per second.
+    pub rate: f32,
+
+    /// Seconds each particle lives.
+    pub lifetime: f32,
+
+    /// Initial speed of particles.
+    pub speed: f32,
+
+    /// Half-angle in radians of the cone around entity's Y axis
+    /// particles are emitted into.
+    pub spread: f32,
+
+    /// Emitted radiance of particles. Fades over lifetime.
+    pub emission: [f32; 3],
+
+    /// Fraction of normal velocity kept after bounce.
+    pub restitution: f32,
+}
+
+impl Default for ParticleEmitter {
+    fn default() -> Self {
+        ParticleEmitter {
+            rate: 100.0,
+            lifetime: 2.0,
+            speed: 5.0,
+            spread: 0.5,
+            emission: [4.0, 2.0, 0.5],
+            restitution: 0.5,
+        }
+    }
+}
@@ -0,0 +1,572 @@
+//! Photo mode.
+//!
+//! F9 pauses simulation and detaches a free camera from the first camera
+//! in the world. Photo camera flies with WASD, Space and LControl,
+//! looks around with mouse and rolls with Q and E.
+//! Brackets change aperture, PageUp and PageDown move focus
+//! and Minus and Equals change exposure.
+//!
+//! Return takes a shot. Renderer draws the view in tiles,
+//! accumulating samples of each tile before reading it back,
+//! and stitches them into a PNG with `tiles` times resolution
+//! of the view along each axis.
+
+use {
+    crate::{
+        camera::{free::Direction, rig::CameraRig, Camera, CameraSettings},
+        clocks::TimeScale,
+        console::ConsoleState,
+        engine::{System, SystemContext},
+        renderer::{Extent2d, TextOverlay},
+        scene::Global3,
+    },
+    eyre::Report,
+    hecs::{Entity, World},
+    nalgebra as na,
+    std::{
+        f32::consts::SQRT_2,
+        path::{Path, PathBuf},
+        time::{SystemTime, UNIX_EPOCH},
+    },
+    type_map::TypeMap,
+    winit::event::{
+        DeviceEvent, ElementState, Event, KeyboardInput, VirtualKeyCode,
+    },
+};
+
+/// Directory where photos are written.
+const PHOTO_DIRECTORY: &str = "photos";
+
+/// Range of f-numbers available in photo mode.
+const MIN_APERTURE: f32 = 1.0;
+const MAX_APERTURE: f32 = 32.0;
+
+/// Closest focus distance.
+const MIN_FOCUS_DISTANCE: f32 = 0.1;
+
+/// Focus distance is multiplied or divided by this per key press.
+const FOCUS_STEP: f32 = 1.1;
+
+/// Exposure compensation per key press in EV.
+const EXPOSURE_STEP: f32 = 1.0 / 3.0;
+
+/// Photo camera marker component.
+pub struct PhotoCamera;
+
+/// Photo mode state shared with renderer.
+#[derive(Debug, Default)]
+pub struct PhotoMode {
+    /// Detached camera entity while photo mode is active.
+    /// Renderer draws first view split with it.
+    pub camera: Option<Entity>,
+
+    /// Shot being rendered.
+    /// Renderer drops it once photo is written.
+    pub shot: Option<PhotoShot>,
+}
+
+impl PhotoMode {
+    pub fn new() -> Self {
+        PhotoMode::default()
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.camera.is_some()
+    }
+}
+
+/// Tiled high resolution shot.
+#[derive(Debug)]
+pub struct PhotoShot {
+    /// File photo is written into.
+    pub path: PathBuf,
+
+    /// Number of tiles along each axis.
+    pub tiles: u32,
+
+    /// Samples accumulated per tile before it is read back.
+    pub samples: u32,
+
+    /// Index of tile being rendered.
+    pub(crate) tile: u32,
+
+    /// Frames drawn for current tile.
+    pub(crate) frames: u32,
+
+    /// Readback of current tile is requested.
+    pub(crate) requested: bool,
+
+    /// Extent of a tile, set by first tile.
+    tile_extent: Option<Extent2d>,
+
+    /// Exposed radiance of stitched tiles.
+    texels: Vec<[f32; 4]>,
+}
+
+impl PhotoShot {
+    /// Creates shot with 2x2 tiles, four times as many pixels as the view,
+    /// and 256 samples per pixel.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        PhotoShot {
+            path: path.into(),
+            tiles: 2,
+            samples: 256,
+            tile: 0,
+            frames: 0,
+            requested: false,
+            tile_extent: None,
+            texels: Vec::new(),
+        }
+    }
+
+    pub fn with_tiles(mut self, tiles: u32) -> Self {
+        self.tiles = tiles.max(1);
+        self
+    }
+
+    pub fn with_samples(mut self, samples: u32) -> Self {
+        self.samples = samples.max(1);
+        self
+    }
+
+    /// Returns index of tile being rendered and total number of tiles.
+    pub fn progress(&self) -> (u32, u32) {
+        (self.tile, self.tiles * self.tiles)
+    }
+
+    /// Returns projection that renders current tile
+    /// of the view drawn with specified projection.
+    /// Tiles are ordered in rows from top-left corner.
+    pub fn tile_projection(
+        &self,
+        projection: &na::Projective3<f32>,
+    ) -> na::Projective3<f32> {
+        let n = self.tiles as f32;
+        let col = (self.tile % self.tiles) as f32;
+        let row = (self.tile / self.tiles) as f32;
+
+        // Center of the tile in NDC. Top row of the image has largest y.
+        let x = (2.0 * col + 1.0) / n - 1.0;
+        let y = 1.0 - (2.0 * row + 1.0) / n;
+
+        let tile =
+            na::Matrix4::new_nonuniform_scaling(&na::Vector3::new(n, n, 1.0))
+                * na::Matrix4::new_translation(&na::Vector3::new(-x, -y, 0.0));
+
+        na::Projective3::from_matrix_unchecked(
+            tile * projection.to_homogeneous(),
+        )
+    }
+
+    /// Copies texels of current tile into the photo
+    /// and advances to next tile.
+    /// Returns `true` when all tiles are added.
+    pub(crate) fn add_tile(
+        &mut self,
+        extent: Extent2d,
+        texels: &[[f32; 4]],
+    ) -> Result<bool, Report> {
+        let tile_extent = *self.tile_extent.get_or_insert(extent);
+        if tile_extent != extent {
+            return Err(eyre::eyre!(
+                "Tile extent changed from {:?} to {:?}",
+                tile_extent,
+                extent
+            ));
+        }
+
+        let width = extent.width as usize;
+        let height = extent.height as usize;
+        let stride = width * self.tiles as usize;
+
+        if self.texels.is_empty() {
+            self.texels = vec![[0.0; 4]; stride * height * self.tiles as usize];
+        }
+
+        let col = (self.tile % self.tiles) as usize;
+        let row = (self.tile / self.tiles) as usize;
+
+        for (y, line) in texels.chunks_exact(width).enumerate() {
+            let start = (row * height + y) * stride + col * width;
+            self.texels[start..start + width].copy_from_slice(line);
+        }
+
+        self.tile += 1;
+        self.frames = 0;
+        self.requested = false;
+
+        Ok(self.tile >= self.tiles * self.tiles)
+    }
+
+    /// Writes tone mapped photo into PNG file.
+    /// Uses the same tone mapping as the view.
+    pub(crate) fn write(&self) -> Result<(), Report> {
+        let extent = match self.tile_extent {
+            Some(extent) => extent,
+            None => return Err(eyre::eyre!("Photo has no tiles")),
+        };
+
+        let width = extent.width * self.tiles;
+        let height = extent.height * self.tiles;
+
+        let mut bytes = Vec::with_capacity(self.texels.len() * 3);
+        for &[r, g, b, _] in &self.texels {
+            for &c in &[r, g, b] {
+                let c = c.max(0.0);
+                bytes.push((srgb_encode(c / (1.0 + c)) * 255.0).round() as u8);
+            }
+        }
+
+        let image = image::RgbImage::from_raw(width, height, bytes)
+            .ok_or_else(|| eyre::eyre!("Photo size mismatch"))?;
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        image.save_with_format(&self.path, image::ImageFormat::Png)?;
+        Ok(())
+    }
+}
+
+/// System that enters and leaves photo mode,
+/// controls photo camera and requests shots.
+pub struct PhotoModeSystem {
+    /// Time scale restored when photo mode is left.
+    time_scale: TimeScale,
+    direction: Direction,
+    roll: f32,
+    look_factor: f32,
+    roll_speed: f32,
+    speed: f32,
+    tiles: u32,
+    samples: u32,
+}
+
+impl PhotoModeSystem {
+    pub fn new() -> Self {
+        PhotoModeSystem {
+            time_scale: TimeScale::new(),
+            direction: Direction::empty(),
+            roll: 0.0,
+            look_factor: 0.002,
+            roll_speed: 1.0,
+            speed: 1.0,
+            tiles: 2,
+            samples: 256,
+        }
+    }
+
+    /// Sets radians photo camera turns per pixel of mouse motion.
+    pub fn with_factor(mut self, factor: f32) -> Self {
+        self.look_factor = factor;
+        self
+    }
+
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Sets number of tiles along each axis and samples per tile of shots.
+    pub fn with_shot(mut self, tiles: u32, samples: u32) -> Self {
+        self.tiles = tiles;
+        self.samples = samples;
+        self
+    }
+
+    fn enter(&mut self, world: &mut World, resources: &mut TypeMap) {
+        let source = world
+            .query::<(
+                &Camera,
+                &Global3,
+                Option<&CameraSettings>,
+                Option<&CameraRig>,
+            )>()
+            .iter()
+            .next()
+            .map(|(_, (camera, global, settings, rig))| {
+                // Rigged cameras are rendered from rig pose.
+                let global =
+                    rig.and_then(|rig| rig.pose().copied()).unwrap_or(*global);
+                (*camera, global, settings.copied().unwrap_or_default())
+            });
+
+        let (camera, global, settings) = match source {
+            Some(source) => source,
+            None => {
+                tracing::warn!("No camera to enter photo mode from");
+                return;
+            }
+        };
+
+        let entity = world.spawn((camera, global, settings, PhotoCamera));
+
+        self.time_scale =
+            resources.get::<TimeScale>().copied().unwrap_or_default();
+        resources.insert(TimeScale::paused());
+
+        let photo = resources.get_mut::<PhotoMode>().unwrap();
+        photo.camera = Some(entity);
+    }
+
+    fn leave(&mut self, world: &mut World, resources: &mut TypeMap) {
+        let photo = resources.get_mut::<PhotoMode>().unwrap();
+        if let Some(entity) = photo.camera.take() {
+            let _ = world.despawn(entity);
+        }
+        photo.shot = None;
+
+        resources.insert(self.time_scale);
+        self.direction = Direction::empty();
+        self.roll = 0.0;
+    }
+}
+
+impl System for PhotoModeSystem {
+    fn run(&mut self, ctx: SystemContext<'_>) {
+        let delta = ctx.clocks.delta.as_secs_f32();
+
+        if !ctx.resources.contains::<PhotoMode>() {
+            ctx.resources.insert(PhotoMode::new());
+        }
+
+        let console_open = ctx
+            .resources
+            .get::<ConsoleState>()
+            .map_or(false, |state| state.open);
+
+        let mut toggle = false;
+        let mut shoot = false;
+        let mut look = na::Vector2::zeros();
+        let mut aperture = 0i32;
+        let mut focus = 0i32;
+        let mut exposure = 0i32;
+
+        for event in ctx.input.read() {
+            let event = match event {
+                Event::DeviceEvent { event, .. } if !console_open => event,
+                _ => continue,
+            };
+
+            match *event {
+                DeviceEvent::MouseMotion { delta: (x, y) } => {
+                    look += na::Vector2::new(x as f32, y as f32);
+                }
+                DeviceEvent::Key(KeyboardInput {
+                    virtual_keycode: Some(key),
+                    state,
+                    ..
+                }) => {
+                    let pressed = state == ElementState::Pressed;
+
+                    let flag = match key {
+                        VirtualKeyCode::W => Direction::FORWARD,
+                        VirtualKeyCode::S => Direction::BACKWARD,
+                        VirtualKeyCode::A => Direction::LEFT,
+                        VirtualKeyCode::D => Direction::RIGHT,
+                        VirtualKeyCode::Space => Direction::UP,
+                        VirtualKeyCode::LControl => Direction::DOWN,
+                        VirtualKeyCode::Q | VirtualKeyCode::E => {
+                            let sign = if key == VirtualKeyCode::Q {
+                                1.0
+                            } else {
+                                -1.0
+                            };
+                            self.roll = if pressed { sign } else { 0.0 };
+                            continue;
+                        }
+                        _ if pressed => continue,
+                        VirtualKeyCode::F9 => {
+                            toggle = true;
+                            continue;
+                        }
+                        VirtualKeyCode::Return => {
+                            shoot = true;
+                            continue;
+                        }
+                        VirtualKeyCode::LBracket => {
+                            aperture -= 1;
+                            continue;
+                        }
+                        VirtualKeyCode::RBracket => {
+                            aperture += 1;
+                            continue;
+                        }
+                        VirtualKeyCode::PageUp => {
+                            focus += 1;
+                            continue;
+                        }
+                        VirtualKeyCode::PageDown => {
+                            focus -= 1;
+                            continue;
+                        }
+                        VirtualKeyCode::Equals => {
+                            exposure += 1;
+                            continue;
+                        }
+                        VirtualKeyCode::Minus => {
+                            exposure -= 1;
+                            continue;
+                        }
+                        _ => continue,
+                    };
+
+                    self.direction.set(flag, pressed);
+                }
+                _ => {}
+            }
+        }
+
+        let active = ctx.resources.get::<PhotoMode>().unwrap().is_active();
+
+        if toggle {
+            if active {
+                self.leave(ctx.world, ctx.resources);
+            } else {
+                self.enter(ctx.world, ctx.resources);
+            }
+            return;
+        }
+
+        let photo = ctx.resources.get_mut::<PhotoMode>().unwrap();
+        let entity = match photo.camera {
+            Some(entity) => entity,
+            None => return,
+        };
+
+        let mut query = match ctx
+            .world
+            .query_one::<(&mut Global3, &mut CameraSettings)>(entity)
+        {
+            Ok(query) => query,
+            Err(_) => {
+                // Photo camera was despawned by someone else.
+                photo.camera = None;
+                photo.shot = None;
+                ctx.resources.insert(self.time_scale);
+                return;
+            }
+        };
+
+        let (global, settings) = match query.get() {
+            Some(components) => components,
+            None => return,
+        };
+
+        // Any change of the camera would restart accumulation of the shot.
+        if let Some(shot) = &photo.shot {
+            let (tile, tiles) = shot.progress();
+            let status = format!("PHOTO tile {}/{}", tile + 1, tiles);
+            print_status(ctx.resources, &status);
+            return;
+        }
+
+        if shoot {
+            photo.shot = Some(
+                PhotoShot::new(photo_path())
+                    .with_tiles(self.tiles)
+                    .with_samples(self.samples),
+            );
+        }
+
+        let rotation = &mut global.iso.rotation;
+        if look != na::Vector2::zeros() {
+            *rotation = na::UnitQuaternion::from_axis_angle(
+                &na::Vector3::y_axis(),
+                -look.x * self.look_factor,
+            ) * *rotation
+                * na::UnitQuaternion::from_axis_angle(
+                    &na::Vector3::x_axis(),
+                    -look.y * self.look_factor,
+                );
+        }
+
+        if self.roll != 0.0 {
+            *rotation *= na::UnitQuaternion::from_axis_angle(
+                &na::Vector3::z_axis(),
+                self.roll * self.roll_speed * delta,
+            );
+        }
+
+        let mut moving = na::Vector3::new(0.0, 0.0, 0.0);
+        if self.direction.contains(Direction::FORWARD) {
+            moving[2] -= 1.0;
+        }
+        if self.direction.contains(Direction::BACKWARD) {
+            moving[2] += 1.0;
+        }
+        if self.direction.contains(Direction::LEFT) {
+            moving[0] -= 1.0;
+        }
+        if self.direction.contains(Direction::RIGHT) {
+            moving[0] += 1.0;
+        }
+        if self.direction.contains(Direction::UP) {
+            moving[1] += 1.0;
+        }
+        if self.direction.contains(Direction::DOWN) {
+            moving[1] -= 1.0;
+        }
+
+        if moving != na::Vector3::zeros() {
+            global.iso *= na::Translation::from(moving * self.speed * delta);
+        }
+
+        // Aperture changes depth of field only,
+        // sensitivity compensates it to keep exposure.
+        if aperture != 0 {
+            let old = settings.aperture;
+            settings.aperture = (old * SQRT_2.powi(aperture))
+                .max(MIN_APERTURE)
+                .min(MAX_APERTURE);
+            let ratio = settings.aperture / old;
+            settings.iso *= ratio * ratio;
+        }
+
+        if focus != 0 {
+            settings.focus_distance = (settings.focus_distance
+                * FOCUS_STEP.powi(focus))
+            .max(MIN_FOCUS_DISTANCE);
+        }
+
+        if exposure != 0 {
+            settings.iso *= (exposure as f32 * EXPOSURE_STEP).exp2();
+        }
+
+        let status = format!(
+            "PHOTO f/{:.1} focus {:.1} EV {:+.1}",
+            settings.aperture,
+            settings.focus_distance,
+            settings.exposure().log2(),
+        );
+
+        print_status(ctx.resources, &status);
+    }
+}
+
+/// Prints photo mode status at the top-left corner of `TextOverlay`.
+fn print_status(resources: &mut TypeMap, line: &str) {
+    if let Some(overlay) = resources.get_mut::<TextOverlay>() {
+        overlay.fill(0, 0, line.len() as u32, 1);
+        overlay.print(0, 0, line, [255, 255, 255]);
+    }
+}
+
+/// Returns path for new photo named after current time.
+fn photo_path() -> PathBuf {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+
+    Path::new(PHOTO_DIRECTORY).join(format!("photo-{}.png", secs))
+}
+
+/// Encodes linear value with sRGB transfer function.
+fn srgb_encode(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
@@ -1,11 +1,14 @@
 use {
     crate::{
         engine::{System, SystemContext},
-        scene::Global3,
+        scene::{is_changed, track_changes, Changed, Generation, Global3},
     },
     hecs::{Entity, World},
     nalgebra as na,
-    ncollide3d::shape::ShapeHandle,
+    ncollide3d::{
+        pipeline::CollisionGroups,
+        shape::{Ball, ShapeHandle},
+    },
     nphysics3d::{
         force_generator::DefaultForceGeneratorSet,
         joint::DefaultJointConstraintSet,
@@ -14,6 +17,11 @@ use {
     },
     parking_lot::Mutex,
     smallvec::{smallvec, SmallVec},
+    std::{
+        collections::HashSet,
+        sync::Arc,
+        time::{Duration, Instant},
+    },
 };
 
 pub use nphysics3d::object::{
@@ -43,13 +51,23 @@ impl Default for Constants {
     }
 }
 
+type Geometrical = GeometricalWorld<f32, Entity, DefaultColliderHandle>;
+
 pub struct Physics {
-    geometrical: GeometricalWorld<f32, Entity, DefaultColliderHandle>,
+    geometrical: Arc<Mutex<Geometrical>>,
     mechanical: MechanicalWorld<f32, Entity, DefaultColliderHandle>,
     // body_set: DefaultBodySet<f32>,
     // collider_set: DefaultColliderSet<f32>,
     joint_constraint_set: DefaultJointConstraintSet<f32, Entity>,
     force_generator_set: DefaultForceGeneratorSet<f32, Entity>,
+
+    /// Generation of `Global3` changes already synced with bodies.
+    synced: Generation,
+
+    /// Entities with bodies seen in last run.
+    /// Used to detect removed bodies.
+    bodies: HashSet<Entity>,
+    removed: Vec<Entity>,
 }
 
 pub struct Colliders {
@@ -68,6 +86,10 @@ impl Colliders {
             array: smallvec![(collider, part)],
         }
     }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ColliderDesc<f32>> + '_ {
+        self.array.iter().map(|(desc, _)| desc)
+    }
 }
 
 impl From<ColliderDesc<f32>> for Colliders {
@@ -82,6 +104,133 @@ impl From<ShapeHandle<f32>> for Colliders {
     }
 }
 
+/// Smooths rendering of rigid body between physics steps.
+///
+/// `Physics` stores two latest poses of the body in this component
+/// instead of writing them into `Global3`, and
+/// `PhysicsInterpolationSystem` writes pose interpolated between them
+/// at the time of the frame. Rendered pose lags one physics step
+/// behind simulation.
+///
+/// When frame gets ahead of latest step, pose is extrapolated
+/// by up to `max_extrapolation` seconds.
+#[derive(Clone, Copy, Debug)]
+pub struct PhysicsInterpolation {
+    /// Seconds pose may be extrapolated past latest physics step.
+    /// Zero disables extrapolation.
+    pub max_extrapolation: f32,
+
+    prev: na::Isometry3<f32>,
+    current: na::Isometry3<f32>,
+
+    /// Instant and delta of step that produced `current` pose.
+    step: Option<(Instant, Duration)>,
+
+    /// Pose last written into `Global3`.
+    /// Other changes of `Global3` are synced to the body.
+    rendered: na::Isometry3<f32>,
+}
+
+impl PhysicsInterpolation {
+    /// Interpolation without extrapolation.
+    pub fn new() -> Self {
+        PhysicsInterpolation {
+            max_extrapolation: 0.0,
+            prev: na::Isometry3::identity(),
+            current: na::Isometry3::identity(),
+            step: None,
+            rendered: na::Isometry3::identity(),
+        }
+    }
+
+    /// Interpolation with extrapolation capped by specified seconds.
+    pub fn with_extrapolation(max_extrapolation: f32) -> Self {
+        PhysicsInterpolation {
+            max_extrapolation,
+            ..PhysicsInterpolation::new()
+        }
+    }
+
+    /// Returns pose of the body at specified instant of simulation timeline.
+    /// `None` until body is simulated.
+    pub fn pose_at(&self, instant: Instant) -> Option<na::Isometry3<f32>> {
+        let (step, delta) = self.step?;
+        let delta = delta.as_secs_f32();
+        if delta <= 0.0 {
+            return Some(self.current);
+        }
+
+        // Factor is 1 at `step + delta` where current pose is shown.
+        let elapsed = instant.saturating_duration_since(step).as_secs_f32();
+        let max = 1.0 + self.max_extrapolation.max(0.0) / delta;
+        let t = (elapsed / delta).min(max);
+
+        let translation = self
+            .prev
+            .translation
+            .vector
+            .lerp(&self.current.translation.vector, t);
+        let rotation = self
+            .prev
+            .rotation
+            .rotation_to(&self.current.rotation)
+            .powf(t)
+            * self.prev.rotation;
+
+        Some(na::Isometry3::from_parts(translation.into(), rotation))
+    }
+
+    /// Records pose produced by physics step.
+    fn push(
+        &mut self,
+        pose: na::Isometry3<f32>,
+        step: Instant,
+        delta: Duration,
+    ) {
+        self.prev = match self.step {
+            Some(_) => self.current,
+            None => pose,
+        };
+        self.current = pose;
+        self.step = Some((step, delta));
+    }
+
+    /// Drops history after body is moved outside of physics.
+    fn reset(&mut self, pose: na::Isometry3<f32>) {
+        self.prev = pose;
+        self.current = pose;
+        self.rendered = pose;
+    }
+}
+
+impl Default for PhysicsInterpolation {
+    fn default() -> Self {
+        PhysicsInterpolation::new()
+    }
+}
+
+/// Writes interpolated poses of `PhysicsInterpolation` bodies
+/// into `Global3`.
+/// Should run every frame before rendering.
+pub struct PhysicsInterpolationSystem;
+
+impl System for PhysicsInterpolationSystem {
+    fn run(&mut self, ctx: SystemContext<'_>) {
+        for (_, (global, interpolation)) in ctx
+            .world
+            .query::<(&mut Global3, &mut PhysicsInterpolation)>()
+            .iter()
+        {
+            if let Some(pose) = interpolation.pose_at(ctx.clocks.sim_step) {
+                interpolation.rendered = pose;
+                if global.iso != pose {
+                    global.iso = pose;
+                }
+            }
+        }
+    }
+}
+
 struct AttachedColliders {
     array: SmallVec<[DefaultColliderHandle; 1]>,
 }
@@ -105,12 +254,15 @@ impl Physics {
         let force_generator_set = DefaultForceGeneratorSet::new();
 
         Physics {
-            geometrical,
+            geometrical: Arc::new(Mutex::new(geometrical)),
             mechanical,
             // body_set,
             // collider_set,
             joint_constraint_set,
             force_generator_set,
+            synced: Generation::ZERO,
+            bodies: HashSet::new(),
+            removed: Vec::new(),
         }
     }
 }
@@ -125,7 +277,15 @@ impl System for Physics {
             .get::<Constants>()
             .unwrap_or(&DEFAULT_CONSTANTS);
 
-        let delta = ctx.clocks.delta.as_secs_f32() * constants.time_factor;
+        let delta = ctx.clocks.sim_delta.as_secs_f32() * constants.time_factor;
+
+        if !ctx.resources.contains::<SceneQuery>() {
+            ctx.resources.insert(SceneQuery {
+                geometrical: self.geometrical.clone(),
+            });
+        }
+
+        let mut geometrical = self.geometrical.lock();
 
         let mut lock = None;
 
@@ -153,52 +313,142 @@ impl System for Physics {
             world.insert_one(entity, attached).unwrap();
         }
 
-        for (_, (global, body)) in
-            world.query::<(&Global3, &mut RigidBody<f32>)>().iter()
+        // Bodies of despawned entities and removed `RigidBody` components
+        // are reported to mechanical world so that joints and contacts
+        // referencing them are released.
+        let mut bodies = HashSet::with_capacity(self.bodies.len());
+        for (entity, _) in world.query::<&RigidBody<f32>>().iter() {
+            bodies.insert(entity);
+        }
+        self.removed
+            .extend(self.bodies.difference(&bodies).copied());
+        self.bodies = bodies;
+
+        // Only transforms modified outside of physics are synced to bodies.
+        // Interpolated poses are not.
+        track_changes::<Global3>(world);
+        for (_, (global, body, changed, interpolation)) in world
+            .query::<(
+                &Global3,
+                &mut RigidBody<f32>,
+                Option<&Changed<Global3>>,
+                Option<&mut PhysicsInterpolation>,
+            )>()
+            .iter()
         {
-            // FIXME: Update position only if changed.
+            if !is_changed(changed, self.synced) {
+                continue;
+            }
+
+            if let Some(interpolation) = interpolation {
+                if interpolation.rendered == global.iso {
+                    continue;
+                }
+                interpolation.reset(global.iso);
+            }
             body.set_position(global.iso);
         }
 
         let lock = lock.get_or_insert_with(|| COLLIDER_SET.lock());
 
         self.mechanical.maintain(
-            &mut self.geometrical,
-            WorldBodySet::cast(world),
+            &mut *geometrical,
+            &mut WorldBodySet {
+                world: &mut *world,
+                removed: &mut self.removed,
+            },
             &mut **lock,
             &mut self.joint_constraint_set,
         );
 
         self.mechanical.set_timestep(delta.min(0.01666666666666));
         self.mechanical.step(
-            &mut self.geometrical,
-            WorldBodySet::cast(world),
+            &mut *geometrical,
+            &mut WorldBodySet {
+                world: &mut *world,
+                removed: &mut self.removed,
+            },
             &mut **lock,
             &mut self.joint_constraint_set,
             &mut self.force_generator_set,
         );
 
-        for (_, (global, body)) in
-            world.query::<(&mut Global3, &RigidBody<f32>)>().iter()
+        for (_, (global, body, interpolation)) in world
+            .query::<(
+                &mut Global3,
+                &RigidBody<f32>,
+                Option<&mut PhysicsInterpolation>,
+            )>()
+            .iter()
         {
-            // FIXME: Update position only if changed.
-            global.iso = *body.position();
+            if let Some(interpolation) = interpolation {
+                interpolation.push(
+                    *body.position(),
+                    ctx.clocks.sim_step,
+                    ctx.clocks.sim_delta,
+                );
+            } else if global.iso != *body.position() {
+                global.iso = *body.position();
+            }
         }
+
+        // Changes made by physics are not synced back to bodies.
+        self.synced = track_changes::<Global3>(world);
     }
 }
 
-#[repr(transparent)]
-struct WorldBodySet {
-    world: World,
+/// Resource for querying physics scene from other systems.
+///
+/// Inserted by `Physics` system.
+/// Reflects state after last physics step.
+#[derive(Clone)]
+pub struct SceneQuery {
+    geometrical: Arc<Mutex<Geometrical>>,
 }
 
-impl WorldBodySet {
-    fn cast(world: &mut World) -> &mut Self {
-        unsafe { &mut *(world as *mut _ as *mut _) }
+impl SceneQuery {
+    /// Casts sphere from `origin` along `direction`.
+    /// Returns distance to first hit not greater than `max_distance`.
+    ///
+    /// Colliders attached to `ignore` entities are skipped.
+    pub fn cast_sphere(
+        &self,
+        origin: &na::Point3<f32>,
+        direction: &na::Unit<na::Vector3<f32>>,
+        radius: f32,
+        max_distance: f32,
+        ignore: &[Entity],
+    ) -> Option<f32> {
+        let geometrical = self.geometrical.lock();
+        let colliders = COLLIDER_SET.lock();
+
+        let ball = Ball::new(radius);
+        let isometry = na::Isometry3::translation(origin.x, origin.y, origin.z);
+        let groups = CollisionGroups::new();
+
+        geometrical
+            .sweep_test(
+                &*colliders,
+                &ball,
+                &isometry,
+                direction,
+                max_distance,
+                &groups,
+            )
+            .filter(|(_, collider, _)| !ignore.contains(&collider.body()))
+            .map(|(_, _, toi)| toi.toi)
+            .fold(None, |min: Option<f32>, toi| {
+                Some(min.map_or(toi, |min| min.min(toi)))
+            })
     }
 }
 
-impl BodySet<f32> for WorldBodySet {
+struct WorldBodySet<'a> {
+    world: &'a mut World,
+    removed: &'a mut Vec<Entity>,
+}
+
+impl BodySet<f32> for WorldBodySet<'_> {
     type Handle = Entity;
 
     fn get(&self, entity: Entity) -> Option<&dyn Body<f32>> {
@@ -233,6 +483,6 @@ impl BodySet<f32> for WorldBodySet {
     }
 
     fn pop_removal_event(&mut self) -> Option<Entity> {
-        None
+        self.removed.pop()
     }
 }
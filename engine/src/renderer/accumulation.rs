@@ -0,0 +1,146 @@
+//! Progressive accumulation for offline shots.
+//!
+//! While `r.accumulate` is set and neither camera nor scene change,
+//! path tracer averages samples across frames instead of denoising.
+//! `AccumulationOverlay` shows the sample count and F12 captures
+//! accumulated radiance into a Radiance HDR file.
+
+use {
+    super::TextOverlay,
+    crate::{
+        cvar::CVars,
+        engine::{System, SystemContext},
+    },
+    eyre::Report,
+    illume::Extent2d,
+    std::{
+        fs::File,
+        io::BufWriter,
+        path::{Path, PathBuf},
+        time::{SystemTime, UNIX_EPOCH},
+    },
+    winit::event::{
+        ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent,
+    },
+};
+
+/// Directory where captures are written.
+const CAPTURE_DIRECTORY: &str = "captures";
+
+/// Progressive accumulation state shared with renderer.
+#[derive(Clone, Debug, Default)]
+pub struct Accumulation {
+    /// Number of samples accumulated by last drawn frame.
+    /// `None` while accumulation is disabled.
+    pub samples: Option<u32>,
+
+    /// File to capture accumulated frame into.
+    /// Renderer takes it when drawing next frame.
+    pub capture: Option<PathBuf>,
+}
+
+impl Accumulation {
+    pub fn new() -> Self {
+        Accumulation::default()
+    }
+
+    /// Requests capture of accumulated frame into specified file.
+    pub fn capture(&mut self, path: impl Into<PathBuf>) {
+        self.capture = Some(path.into());
+    }
+}
+
+/// System that captures accumulated frame on F12
+/// and prints sample count into `TextOverlay`
+/// while `r.accumulate` is set.
+pub struct AccumulationOverlay;
+
+impl System for AccumulationOverlay {
+    fn run(&mut self, ctx: SystemContext<'_>) {
+        let enabled = ctx
+            .resources
+            .get::<CVars>()
+            .and_then(|cvars| cvars.get_bool("r.accumulate"))
+            .unwrap_or(false);
+
+        if !ctx.resources.contains::<Accumulation>() {
+            ctx.resources.insert(Accumulation::new());
+        }
+
+        let accumulation = ctx.resources.get_mut::<Accumulation>().unwrap();
+
+        if !enabled {
+            accumulation.capture = None;
+            return;
+        }
+
+        for event in ctx.input.read() {
+            if let Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(VirtualKeyCode::F12),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } = event
+            {
+                accumulation.capture(capture_path());
+            }
+        }
+
+        let samples = match accumulation.samples {
+            Some(samples) => samples,
+            None => return,
+        };
+
+        let line = format!("{} spp", samples);
+
+        let overlay = match ctx.resources.get_mut::<TextOverlay>() {
+            Some(overlay) => overlay,
+            None => return,
+        };
+
+        let col = overlay.cols().saturating_sub(line.len() as u32);
+        overlay.fill(col, 0, line.len() as u32, 1);
+        overlay.print(col, 0, &line, [255, 255, 255]);
+    }
+}
+
+/// Returns path for new capture named after current time.
+fn capture_path() -> PathBuf {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+
+    Path::new(CAPTURE_DIRECTORY).join(format!("beauty-{}.hdr", secs))
+}
+
+/// Writes linear radiance into Radiance HDR file.
+pub(crate) fn write_capture(
+    path: &Path,
+    extent: Extent2d,
+    texels: &[[f32; 4]],
+) -> Result<(), Report> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let pixels: Vec<_> = texels
+        .iter()
+        .map(|&[r, g, b, _]| image::Rgb([r, g, b]))
+        .collect();
+
+    let file = BufWriter::new(File::create(path)?);
+    image::codecs::hdr::HdrEncoder::new(file).encode(
+        &pixels,
+        extent.width as usize,
+        extent.height as usize,
+    )?;
+
+    Ok(())
+}
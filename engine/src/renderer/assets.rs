@@ -6,9 +6,9 @@ use crate::{
     device::{CreateRenderPassError, Device},
     fence::{Fence, FenceInfo},
     pipeline::{
-        Bounds, ColorBlend, Culling, DepthTest, FrontFace, GraphicsPipeline,
-        GraphicsPipelineInfo, PipelineLayoutInfo, PolygonMode,
-        PrimitiveTopology, Rasterizer, State, StencilTests,
+        Bounds, ColorBlend, Culling, DepthTest, DynamicStates, FrontFace,
+        GraphicsPipeline, GraphicsPipelineInfo, PipelineLayoutInfo,
+        PolygonMode, PrimitiveTopology, Rasterizer, State, StencilTests,
         VertexInputAttribute, VertexInputBinding, Viewport,
     },
     render_pass::{RenderPass, RenderPassInfo},
@@ -85,6 +85,7 @@ impl SyncAsset for GraphicsPipeline {
                         },
                         viewport: desc.viewport,
                         scissor: desc.scissor,
+                        viewport_count: desc.viewport_count,
                         depth_clamp: desc.depth_clamp,
                         front_face: desc.front_face,
                         culling: desc.culling,
@@ -100,6 +101,7 @@ impl SyncAsset for GraphicsPipeline {
                 layout,
                 render_pass: repr.render_pass,
                 subpass: repr.subpass,
+                dynamic_states: desc.dynamic_states,
             })?;
 
         Ok(pipeline)
@@ -151,6 +153,8 @@ pub struct RasterizerDesc<S> {
         default = "State::dynamic"
     )]
     pub scissor: State<Rect2d>,
+    #[serde(skip_serializing_if = "is_one", default = "one")]
+    pub viewport_count: u32,
     #[serde(skip_serializing_if = "std::ops::Not::not", default)]
     pub depth_clamp: bool,
     #[serde(skip_serializing_if = "is_default", default)]
@@ -194,6 +198,12 @@ pub struct GraphicsPipelineDesc<S> {
         flatten
     )]
     pub rasterizer: Option<RasterizerDesc<S>>,
+
+    #[serde(
+        skip_serializing_if = "DynamicStates::is_empty",
+        default = "DynamicStates::empty"
+    )]
+    pub dynamic_states: DynamicStates,
 }
 
 #[derive(Clone, Debug)]
@@ -296,6 +306,7 @@ where
                                 Some(RasterizerDesc {
                                     viewport: rasterizer.viewport,
                                     scissor: rasterizer.scissor,
+                                    viewport_count: rasterizer.viewport_count,
                                     depth_clamp: rasterizer.depth_clamp,
                                     front_face: rasterizer.front_face,
                                     culling: rasterizer.culling,
@@ -340,6 +351,7 @@ where
                                 None
                             },
                             layout: pipeline.layout,
+                            dynamic_states: pipeline.dynamic_states,
                         },
                         render_pass: self.render_pass,
                         subpass: self.subpass,
@@ -355,6 +367,14 @@ fn none<T>() -> Option<T> {
     None
 }
 
+fn one() -> u32 {
+    1
+}
+
+fn is_one(value: &u32) -> bool {
+    *value == 1
+}
+
 fn is_default<T: Default + Eq>(value: &T) -> bool {
     *value == T::default()
 }
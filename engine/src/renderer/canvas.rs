@@ -0,0 +1,82 @@
+use {
+    eyre::{eyre, Report},
+    illume::{Format, ImageExtent},
+    noise::Canvas,
+    std::convert::TryFrom as _,
+};
+
+/// Canvas value type that can be encoded into image texels.
+pub trait CanvasTexel: Sized {
+    /// Encodes canvas values into texels of specified format.
+    /// Returns `None` if values can't be encoded into that format.
+    fn encode(canvas: &Canvas<Self>, format: Format) -> Option<Vec<u8>>;
+}
+
+impl CanvasTexel for f32 {
+    fn encode(canvas: &Canvas<f32>, format: Format) -> Option<Vec<u8>> {
+        match format {
+            Format::R8Unorm => Some(canvas.to_r8()),
+            Format::R16Unorm => Some(canvas.to_r16()),
+            Format::R32Sfloat => Some(canvas.to_r32f()),
+            _ => None,
+        }
+    }
+}
+
+impl CanvasTexel for [f32; 4] {
+    fn encode(canvas: &Canvas<[f32; 4]>, format: Format) -> Option<Vec<u8>> {
+        match format {
+            Format::RGBA8Unorm => Some(canvas.to_rgba8()),
+            Format::RGBA32Sfloat => Some(canvas.to_rgba32f()),
+            _ => None,
+        }
+    }
+}
+
+/// Encodes canvases into texels of specified format, layer after layer.
+/// Returns extent of the layers stack,
+/// 2D for single canvas and 3D with one Z-layer per canvas otherwise.
+pub(super) fn encode_layers<T>(
+    layers: &[Canvas<T>],
+    format: Format,
+) -> Result<(ImageExtent, Vec<u8>), Report>
+where
+    T: CanvasTexel,
+{
+    let first = layers
+        .first()
+        .ok_or_else(|| eyre!("At least one canvas layer expected"))?;
+
+    let width = u32::try_from(first.width())?;
+    let height = u32::try_from(first.height())?;
+    let depth = u32::try_from(layers.len())?;
+
+    let mut bytes = Vec::new();
+    for layer in layers {
+        if layer.width() != first.width() || layer.height() != first.height() {
+            return Err(eyre!(
+                "All canvas layers must be {}x{}, found {}x{}",
+                width,
+                height,
+                layer.width(),
+                layer.height(),
+            ));
+        }
+
+        let encoded = T::encode(layer, format)
+            .ok_or_else(|| eyre!("Canvas can't be encoded as {:?}", format))?;
+        bytes.extend_from_slice(&encoded);
+    }
+
+    let extent = if depth == 1 {
+        ImageExtent::D2 { width, height }
+    } else {
+        ImageExtent::D3 {
+            width,
+            height,
+            depth,
+        }
+    };
+
+    Ok((extent, bytes))
+}
@@ -0,0 +1,202 @@
+use {
+    illume::{
+        ComputePipeline, ComputePipelineInfo, Device, GraphicsPipeline,
+        GraphicsPipelineInfo, OutOfMemory, RayTracingPipeline,
+        RayTracingPipelineInfo,
+    },
+    parking_lot::Mutex,
+    std::{sync::Arc, thread::JoinHandle},
+};
+
+/// Number of threads compiling pipelines in background.
+const COMPILER_THREADS: usize = 2;
+
+type Job = Box<dyn FnOnce(&Device) + Send>;
+
+type Callback<P> = Box<dyn FnOnce(&P) + Send>;
+
+/// Pool of threads that create pipelines in background.
+///
+/// Requests return `PipelineHandle` immediately.
+/// Pipeline is put into the handle once created.
+pub struct PipelineCompiler {
+    sender: Option<flume::Sender<Job>>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl PipelineCompiler {
+    pub fn new(device: Device) -> Self {
+        let (sender, receiver) = flume::unbounded::<Job>();
+
+        let threads = (0..COMPILER_THREADS)
+            .map(|index| {
+                let device = device.clone();
+                let receiver = receiver.clone();
+
+                std::thread::Builder::new()
+                    .name(format!("pipeline-compiler-{}", index))
+                    .spawn(move || {
+                        for job in receiver.iter() {
+                            job(&device);
+                        }
+                    })
+                    .expect("Failed to spawn pipeline compiler thread")
+            })
+            .collect();
+
+        PipelineCompiler {
+            sender: Some(sender),
+            threads,
+        }
+    }
+
+    pub fn compile_graphics_pipeline(
+        &self,
+        info: GraphicsPipelineInfo,
+    ) -> PipelineHandle<GraphicsPipeline> {
+        self.compile(move |device| device.create_graphics_pipeline(info))
+    }
+
+    pub fn compile_compute_pipeline(
+        &self,
+        info: ComputePipelineInfo,
+    ) -> PipelineHandle<ComputePipeline> {
+        self.compile(move |device| device.create_compute_pipeline(info))
+    }
+
+    pub fn compile_ray_tracing_pipeline(
+        &self,
+        info: RayTracingPipelineInfo,
+    ) -> PipelineHandle<RayTracingPipeline> {
+        self.compile(move |device| device.create_ray_tracing_pipeline(info))
+    }
+
+    fn compile<P>(
+        &self,
+        create: impl FnOnce(&Device) -> Result<P, OutOfMemory> + Send + 'static,
+    ) -> PipelineHandle<P>
+    where
+        P: Clone + Send + 'static,
+    {
+        let handle = PipelineHandle {
+            state: Arc::new(Mutex::new(State::Pending(Vec::new()))),
+        };
+
+        let shared = handle.clone();
+        let job: Job = Box::new(move |device| {
+            let result = create(device);
+            shared.complete(result);
+        });
+
+        self.sender
+            .as_ref()
+            .unwrap()
+            .send(job)
+            .expect("Pipeline compiler threads terminated");
+
+        handle
+    }
+}
+
+impl Drop for PipelineCompiler {
+    fn drop(&mut self) {
+        // Disconnecting channel stops threads after pending jobs.
+        self.sender = None;
+
+        for thread in self.threads.drain(..) {
+            if thread.join().is_err() {
+                tracing::error!("Pipeline compiler thread panicked");
+            }
+        }
+    }
+}
+
+enum State<P> {
+    Pending(Vec<Callback<P>>),
+    Ready(P),
+    Failed(OutOfMemory),
+}
+
+/// Handle to pipeline that is created in background.
+pub struct PipelineHandle<P> {
+    state: Arc<Mutex<State<P>>>,
+}
+
+impl<P> Clone for PipelineHandle<P> {
+    fn clone(&self) -> Self {
+        PipelineHandle {
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<P> PipelineHandle<P> {
+    /// Returns `true` if pipeline was created.
+    pub fn is_ready(&self) -> bool {
+        matches!(*self.state.lock(), State::Ready(_))
+    }
+
+    /// Returns created pipeline.
+    /// Returns `Ok(None)` if pipeline is not ready yet.
+    pub fn poll(&self) -> Result<Option<P>, OutOfMemory>
+    where
+        P: Clone,
+    {
+        match &*self.state.lock() {
+            State::Pending(_) => Ok(None),
+            State::Ready(pipeline) => Ok(Some(pipeline.clone())),
+            State::Failed(err) => Err(*err),
+        }
+    }
+
+    /// Registers callback to be called once pipeline is created.
+    /// If pipeline is ready callback is called immediately.
+    ///
+    /// Callback may be called from compiler thread.
+    /// It is not called if pipeline creation fails.
+    pub fn on_ready(&self, callback: impl FnOnce(&P) + Send + 'static)
+    where
+        P: Clone + 'static,
+    {
+        let mut state = self.state.lock();
+        let pipeline = match &mut *state {
+            State::Pending(callbacks) => {
+                callbacks.push(Box::new(callback));
+                return;
+            }
+            State::Ready(pipeline) => pipeline.clone(),
+            State::Failed(_) => return,
+        };
+
+        drop(state);
+        callback(&pipeline);
+    }
+
+    fn complete(&self, result: Result<P, OutOfMemory>)
+    where
+        P: Clone,
+    {
+        let mut state = self.state.lock();
+
+        let callbacks = match &mut *state {
+            State::Pending(callbacks) => std::mem::take(callbacks),
+            _ => unreachable!("Pipeline is completed twice"),
+        };
+
+        match result {
+            Ok(pipeline) => {
+                *state = State::Ready(pipeline.clone());
+
+                // Callbacks may access the handle.
+                drop(state);
+                for callback in callbacks {
+                    callback(&pipeline);
+                }
+            }
+            Err(err) => {
+                tracing::error!("Failed to create pipeline: {}", err);
+                *state = State::Failed(err);
+            }
+        }
+    }
+}
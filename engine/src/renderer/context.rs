@@ -1,32 +1,73 @@
 use {
+    super::{
+        canvas::{encode_layers, CanvasTexel},
+        compile::{PipelineCompiler, PipelineHandle},
+        object_table::{ObjectTable, ShaderObject},
+        profiler::{FrameGraphStats, PassProfiler},
+        staging::{StagingBelt, StagingRegion, STAGING_CHUNK_SIZE},
+        sync_pool::SyncPool,
+        validation::PassValidator,
+        vertex::VertexLayoutRegistry,
+        view_uniforms::{ViewGlobals, ViewUniform, ViewUniforms},
+    },
+    crate::logging::set_crash_context,
     bumpalo::{collections::Vec as BVec, Bump},
     bytemuck::Pod,
     eyre::Report,
+    hecs::World,
     illume::{
         Buffer, BufferCopy, BufferImageCopy, BufferInfo, BufferUsage,
-        CreateImageError, Device, Extent3d, Image, ImageInfo,
+        CommandBuffer, ComputePipeline, ComputePipelineInfo, CreateImageError,
+        DescriptorSetLayout, Device, Encoder, Extent3d, Feature, Fence, Format,
+        GraphicsPipeline, GraphicsPipelineInfo, Image, ImageInfo,
         ImageMemoryBarrier, ImageSubresourceLayers, ImageSubresourceRange,
         ImageUsage, Layout, MapError, Offset3d, OutOfMemory,
-        PipelineStageFlags, Queue,
+        PipelineStageFlags, Queue, RayTracingPipeline, RayTracingPipelineInfo,
+        Samples1, Semaphore, WaitError,
     },
-    std::{convert::TryFrom as _, mem::size_of_val, ops::Deref},
+    noise::Canvas,
+    std::{mem::size_of, ops::Deref},
 };
 
+/// Maximum size of data written with `update_buffer` command.
+/// Larger updates are staged.
+const UPDATE_BUFFER_MAX_SIZE: usize = 65_536;
+
+/// Required alignment of offset and size of `update_buffer` command.
+const UPDATE_BUFFER_ALIGN: u64 = 4;
+
 pub struct Context {
     pub device: Device,
     pub queue: Queue,
+
+    /// Vertex layouts for attribute sets declared by meshes.
+    pub vertex_layouts: VertexLayoutRegistry,
+
+    /// Features enabled on the device.
+    features: Vec<Feature>,
+    staging: StagingBelt,
+
+    /// Staging memory of copies recorded into pass encoders.
+    /// Recycled by fences of submissions made with `submit`.
+    encoder_staging: StagingBelt,
+    compiler: PipelineCompiler,
     buffer_uploads: Vec<BufferUpload>,
     image_uploads: Vec<ImageUpload>,
+    profiler: PassProfiler,
+    validator: PassValidator,
+    view_uniforms: ViewUniforms,
+    object_table: ObjectTable,
+    sync: SyncPool,
 }
 
 struct BufferUpload {
-    staging: Buffer,
+    staging: StagingRegion,
     buffer: Buffer,
     offset: u64,
 }
 
 struct ImageUpload {
-    staging: Buffer,
+    staging: StagingRegion,
     image: Image,
     layout: Option<Layout>,
     row_length: u32,
@@ -37,15 +78,224 @@ struct ImageUpload {
 }
 
 impl Context {
-    pub fn new(device: Device, queue: Queue) -> Self {
-        Context {
+    pub fn new(
+        device: Device,
+        queue: Queue,
+        features: Vec<Feature>,
+    ) -> Result<Self, Report> {
+        Ok(Context {
+            compiler: PipelineCompiler::new(device.clone()),
+            view_uniforms: ViewUniforms::new(&device)?,
+            object_table: ObjectTable::new(&device)?,
             device,
             queue,
+            vertex_layouts: VertexLayoutRegistry::new(),
+            features,
+            staging: StagingBelt::new(STAGING_CHUNK_SIZE),
+            encoder_staging: StagingBelt::new(STAGING_CHUNK_SIZE),
             buffer_uploads: Vec::new(),
             image_uploads: Vec::new(),
+            profiler: PassProfiler::new(),
+            validator: PassValidator::new(),
+            sync: SyncPool::new(),
+        })
+    }
+
+    /// Returns `true` if feature was enabled on device creation.
+    pub fn has_feature(&self, feature: Feature) -> bool {
+        self.features.contains(&feature)
+    }
+
+    /// Starts recording pass statistics for the frame.
+    /// Does nothing but finishing pending timestamps when disabled.
+    pub fn begin_profiled_frame(
+        &mut self,
+        enabled: bool,
+    ) -> Result<(), Report> {
+        self.profiler
+            .begin_frame(enabled, &self.device, &mut self.queue)
+    }
+
+    /// Ends recording pass statistics.
+    /// Returns statistics of a previous frame when its timestamps are ready.
+    pub fn end_profiled_frame(&mut self) -> Option<FrameGraphStats> {
+        self.profiler.end_frame()
+    }
+
+    /// Switches view globals to regions of next frame.
+    pub fn begin_views_frame(&mut self) {
+        self.view_uniforms.begin_frame()
+    }
+
+    /// Writes globals of a view for current frame.
+    /// Passes bind returned set with dynamic offset
+    /// instead of uploading camera data themselves.
+    pub fn write_view_globals(
+        &mut self,
+        globals: &ViewGlobals,
+    ) -> Result<ViewUniform, Report> {
+        self.view_uniforms.write(&self.device, globals)
+    }
+
+    /// Destroys context after work submitted in all frames is complete.
+    /// Resources dropped before and by this call are destroyed as well.
+    pub fn shutdown(self) -> Result<(), WaitError> {
+        let Context {
+            device,
+            mut queue,
+            vertex_layouts,
+            features: _,
+            staging,
+            encoder_staging,
+            compiler,
+            buffer_uploads,
+            image_uploads,
+            profiler,
+            validator,
+            view_uniforms,
+            object_table,
+            sync,
+        } = self;
+
+        // Pending uploads are discarded.
+        drop((
+            device,
+            vertex_layouts,
+            staging,
+            encoder_staging,
+            compiler,
+            buffer_uploads,
+            image_uploads,
+            profiler,
+            validator,
+            view_uniforms,
+            object_table,
+            sync,
+        ));
+
+        queue.finish_frames()
+    }
+
+    /// Recycles fences and semaphores of completed frames.
+    pub fn begin_sync_frame(&mut self) -> Result<(), WaitError> {
+        self.sync.begin_frame(&self.device)
+    }
+
+    /// Returns pooled fence.
+    /// It must be submitted and is reused after it is signalled.
+    pub fn pooled_fence(&mut self) -> Result<Fence, OutOfMemory> {
+        self.sync.fence(&self.device)
+    }
+
+    /// Returns pooled binary semaphore.
+    /// It must be signalled and waited on within current frame.
+    pub fn pooled_semaphore(&mut self) -> Result<Semaphore, OutOfMemory> {
+        self.sync.semaphore(&self.device)
+    }
+
+    /// Layout of the set with view globals.
+    pub fn view_globals_layout(&self) -> &DescriptorSetLayout {
+        self.view_uniforms.layout()
+    }
+
+    /// Returns table with per-renderable data.
+    pub fn object_table(&self) -> &ObjectTable {
+        &self.object_table
+    }
+
+    /// Brings object table in sync with renderables in the world.
+    /// Elements are uploaded with next `flush_uploads`.
+    pub fn update_object_table(
+        &mut self,
+        world: &mut World,
+    ) -> Result<(), Report> {
+        let (buffer, dirty) = self.object_table.update(world, &self.device)?;
+
+        // Neighbouring elements are uploaded together.
+        let mut start = 0;
+        while start < dirty.len() {
+            let mut end = start + 1;
+            while end < dirty.len() && dirty[end].0 == dirty[end - 1].0 + 1 {
+                end += 1;
+            }
+
+            let objects: Vec<_> = dirty[start..end]
+                .iter()
+                .map(|(_, object)| *object)
+                .collect();
+            self.upload_buffer(
+                &buffer,
+                u64::from(dirty[start].0) * size_of::<ShaderObject>() as u64,
+                &objects,
+            )?;
+
+            start = end;
+        }
+
+        Ok(())
+    }
+
+    /// Enables validation of commands submitted by each pass.
+    pub fn set_pass_validation(&mut self, enabled: bool) {
+        self.validator
+            .set_enabled(enabled, &self.device, &mut self.queue)
+    }
+
+    /// Begins named pass.
+    /// Passes may be nested.
+    pub fn begin_pass(&mut self, name: &'static str) -> Result<(), Report> {
+        set_crash_context("renderer.pass", name);
+        self.validator.begin_pass(name, &self.queue)?;
+        self.profiler.begin_pass(name, &mut self.queue)
+    }
+
+    /// Ends innermost pass.
+    /// Fails if pass validation is enabled and the pass
+    /// submitted invalid commands.
+    pub fn end_pass(&mut self) -> Result<(), Report> {
+        self.profiler.end_pass(&mut self.queue)?;
+        self.validator.end_pass(&self.queue)?;
+        Ok(())
+    }
+
+    /// Reports pipeline barriers recorded by current pass.
+    pub fn record_barriers(&mut self, count: usize) {
+        if let Some(pass) = self.profiler.current_pass() {
+            pass.barriers += count as u32;
+        }
+    }
+
+    /// Reports size of resources owned by current pass.
+    pub fn record_transient(&mut self, bytes: u64) {
+        if let Some(pass) = self.profiler.current_pass() {
+            pass.transient_bytes += bytes;
         }
     }
 
+    /// Starts creating graphics pipeline in background.
+    pub fn compile_graphics_pipeline(
+        &self,
+        info: GraphicsPipelineInfo,
+    ) -> PipelineHandle<GraphicsPipeline> {
+        self.compiler.compile_graphics_pipeline(info)
+    }
+
+    /// Starts creating compute pipeline in background.
+    pub fn compile_compute_pipeline(
+        &self,
+        info: ComputePipelineInfo,
+    ) -> PipelineHandle<ComputePipeline> {
+        self.compiler.compile_compute_pipeline(info)
+    }
+
+    /// Starts creating ray-tracing pipeline in background.
+    pub fn compile_ray_tracing_pipeline(
+        &self,
+        info: RayTracingPipelineInfo,
+    ) -> PipelineHandle<RayTracingPipeline> {
+        self.compiler.compile_ray_tracing_pipeline(info)
+    }
+
     pub fn upload_buffer<T>(
         &mut self,
         buffer: &Buffer,
@@ -55,14 +305,9 @@ impl Context {
     where
         T: Pod,
     {
-        let staging = self.device.create_buffer_static(
-            BufferInfo {
-                align: 15,
-                size: size_of_val(data) as u64,
-                usage: BufferUsage::TRANSFER_SRC,
-            },
-            data,
-        )?;
+        let staging = self
+            .staging
+            .upload(&self.device, bytemuck::cast_slice(data))?;
 
         self.buffer_uploads.push(BufferUpload {
             staging,
@@ -73,6 +318,84 @@ impl Context {
         Ok(())
     }
 
+    /// Records buffer update into the encoder.
+    ///
+    /// Small aligned updates are recorded inline as `update_buffer`.
+    /// Larger or misaligned ones are written into staging memory
+    /// and copied by the encoder, which then must be submitted
+    /// with `Context::submit`, so that staging memory is recycled
+    /// only after the copy is complete.
+    pub fn update_buffer<'a, T>(
+        &mut self,
+        encoder: &mut Encoder<'a>,
+        buffer: &'a Buffer,
+        offset: u64,
+        data: &'a [T],
+        bump: &'a Bump,
+    ) -> Result<(), Report>
+    where
+        T: Pod,
+    {
+        let data: &[u8] = bytemuck::cast_slice(data);
+
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        if data.len() < UPDATE_BUFFER_MAX_SIZE
+            && offset % UPDATE_BUFFER_ALIGN == 0
+            && data.len() as u64 % UPDATE_BUFFER_ALIGN == 0
+        {
+            encoder.update_buffer(buffer, offset, data);
+            return Ok(());
+        }
+
+        let staging = self.encoder_staging.upload(&self.device, data)?;
+
+        encoder.copy_buffer(
+            bump.alloc(staging.buffer),
+            buffer,
+            bump.alloc([BufferCopy {
+                src_offset: staging.offset,
+                dst_offset: offset,
+                size: staging.size,
+            }]),
+        );
+
+        Ok(())
+    }
+
+    /// Submits commands recorded by a pass.
+    ///
+    /// Staging memory written by `update_buffer` since last call
+    /// is recycled once the submission is complete.
+    /// When no fence is provided, a fence of staging belt is used.
+    pub fn submit(
+        &mut self,
+        wait: &[(PipelineStageFlags, Semaphore)],
+        cbuf: CommandBuffer,
+        signal: &[Semaphore],
+        fence: Option<&Fence>,
+    ) -> Result<(), Report> {
+        if self.encoder_staging.is_empty() {
+            self.queue.submit(wait, cbuf, signal, fence)?;
+            return Ok(());
+        }
+
+        match fence {
+            Some(fence) => {
+                self.encoder_staging.finish_with(fence);
+                self.queue.submit(wait, cbuf, signal, Some(fence))?;
+            }
+            None => {
+                let fence = self.encoder_staging.finish(&self.device)?;
+                self.queue.submit(wait, cbuf, signal, Some(&fence))?;
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn upload_image<T>(
         &mut self,
         image: &Image,
@@ -87,15 +410,9 @@ impl Context {
     where
         T: Pod,
     {
-        let staging = self.device.create_buffer_static(
-            BufferInfo {
-                align: 15,
-                size: u64::try_from(size_of_val(data))
-                    .map_err(|_| OutOfMemory)?,
-                usage: BufferUsage::TRANSFER_SRC,
-            },
-            data,
-        )?;
+        let staging = self
+            .staging
+            .upload(&self.device, bytemuck::cast_slice(data))?;
 
         self.image_uploads.push(ImageUpload {
             staging,
@@ -153,7 +470,71 @@ impl Context {
         Ok(image)
     }
 
+    /// Creates image with texels encoded from canvases.
+    /// Single canvas produces 2D image.
+    /// Multiple canvases produce 3D image with one Z-layer per canvas.
+    pub fn create_canvas_image<T>(
+        &mut self,
+        layers: &[Canvas<T>],
+        format: Format,
+        usage: ImageUsage,
+    ) -> Result<Image, Report>
+    where
+        T: CanvasTexel,
+    {
+        let (extent, bytes) = encode_layers(layers, format)?;
+
+        let image = self.create_image_static(
+            ImageInfo {
+                extent,
+                format,
+                levels: 1,
+                layers: 1,
+                samples: Samples1,
+                usage,
+            },
+            0,
+            0,
+            &bytes,
+        )?;
+
+        Ok(image)
+    }
+
+    /// Creates buffer with texels encoded from canvases, layer after layer.
+    /// `align` is alignment mask, as in `BufferInfo`.
+    pub fn create_canvas_buffer<T>(
+        &mut self,
+        layers: &[Canvas<T>],
+        format: Format,
+        align: u64,
+        usage: BufferUsage,
+    ) -> Result<Buffer, Report>
+    where
+        T: CanvasTexel,
+    {
+        let (_, bytes) = encode_layers(layers, format)?;
+
+        let buffer = self.device.create_buffer_static(
+            BufferInfo {
+                size: bytes.len() as _,
+                align,
+                usage,
+                capture_address: None,
+            },
+            &bytes,
+        )?;
+
+        Ok(buffer)
+    }
+
+    /// Records all pending uploads into single command buffer
+    /// and submits it.
+    /// Staging memory is recycled once the device finishes copying.
     pub fn flush_uploads(&mut self, bump: &Bump) -> Result<(), Report> {
+        self.staging.recall(&self.device)?;
+        self.encoder_staging.recall(&self.device)?;
+
         if self.buffer_uploads.is_empty() && self.image_uploads.is_empty() {
             return Ok(());
         }
@@ -165,12 +546,12 @@ impl Context {
 
             for upload in &self.buffer_uploads {
                 encoder.copy_buffer(
-                    &upload.staging,
+                    &upload.staging.buffer,
                     &upload.buffer,
                     bump.alloc([BufferCopy {
-                        src_offset: 0,
+                        src_offset: upload.staging.offset,
                         dst_offset: upload.offset,
-                        size: upload.staging.info().size,
+                        size: upload.staging.size,
                     }]),
                 )
             }
@@ -206,13 +587,13 @@ impl Context {
 
             encoder.image_barriers(
                 PipelineStageFlags::TOP_OF_PIPE,
-                PipelineStageFlags::TRANSFER,
+                PipelineStageFlags::COPY,
                 images.into_bump_slice(),
             );
 
             for upload in &self.image_uploads {
                 encoder.copy_buffer_to_image(
-                    &upload.staging,
+                    &upload.staging.buffer,
                     &upload.image,
                     if upload.layout == Some(Layout::General) {
                         Layout::General
@@ -220,7 +601,7 @@ impl Context {
                         Layout::TransferDstOptimal
                     },
                     bump.alloc([BufferImageCopy {
-                        buffer_offset: 0,
+                        buffer_offset: upload.staging.offset,
                         buffer_row_length: upload.row_length,
                         buffer_image_height: upload.image_height,
                         image_subresource: upload.subresource,
@@ -253,13 +634,15 @@ impl Context {
             }
 
             encoder.image_barriers(
-                PipelineStageFlags::TRANSFER,
+                PipelineStageFlags::COPY,
                 PipelineStageFlags::TOP_OF_PIPE,
                 images.into_bump_slice(),
             );
         }
 
-        self.queue.submit_no_semaphores(encoder.finish(), None);
+        let fence = self.staging.finish(&self.device)?;
+        self.queue
+            .submit_no_semaphores(encoder.finish()?, Some(&fence))?;
 
         self.buffer_uploads.clear();
         self.image_uploads.clear();
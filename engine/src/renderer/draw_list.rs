@@ -0,0 +1,114 @@
+use bumpalo::{collections::Vec as BVec, Bump};
+
+/// Bits of depth kept in sort key.
+const DEPTH_BITS: u32 = 24;
+
+/// Order of draws with equal state.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DepthOrder {
+    /// Nearest draws first, so early depth test rejects occluded fragments.
+    FrontToBack,
+
+    /// Farthest draws first, as blending requires.
+    BackToFront,
+}
+
+/// 64-bit key draws are sorted by before encoding.
+///
+/// Layer is always most significant, so layers are drawn one after
+/// another. Remaining bits either group draws by pipeline and material
+/// to minimize state changes or order them by depth first,
+/// when overdraw matters more.
+///
+/// Pipeline and material are indices of distinct ones in the frame.
+/// Only low 16 bits of each are kept, so with more of them
+/// draws are still sorted, but grouping becomes imperfect.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct SortKey(pub u64);
+
+impl SortKey {
+    /// Returns key that orders draws by
+    /// layer, pipeline, material and then depth.
+    pub fn new(
+        layer: u8,
+        pipeline: usize,
+        material: usize,
+        depth: f32,
+        order: DepthOrder,
+    ) -> Self {
+        SortKey(
+            u64::from(layer) << 56
+                | (pipeline as u64 & 0xFFFF) << 40
+                | (material as u64 & 0xFFFF) << 24
+                | u64::from(depth_bits(depth, order)),
+        )
+    }
+
+    /// Returns key that orders draws by
+    /// layer, depth and then pipeline.
+    pub fn depth_first(
+        layer: u8,
+        depth: f32,
+        order: DepthOrder,
+        pipeline: usize,
+    ) -> Self {
+        SortKey(
+            u64::from(layer) << 56
+                | u64::from(depth_bits(depth, order)) << 32
+                | (pipeline as u64 & 0xFFFF) << 16,
+        )
+    }
+
+    pub fn layer(&self) -> u8 {
+        (self.0 >> 56) as u8
+    }
+}
+
+/// Returns depth quantized to `DEPTH_BITS` in specified order.
+/// Negative depth is clamped to zero.
+fn depth_bits(depth: f32, order: DepthOrder) -> u32 {
+    // Bits of non-negative floats are ordered the same as their values.
+    let bits = depth.max(0.0).to_bits() >> (32 - DEPTH_BITS);
+
+    match order {
+        DepthOrder::FrontToBack => bits,
+        DepthOrder::BackToFront => !bits & ((1 << DEPTH_BITS) - 1),
+    }
+}
+
+/// Draws gathered for a frame into bump-allocated list.
+/// Sorting is stable, so draws with equal keys keep submission order.
+pub struct DrawList<'a, T> {
+    draws: BVec<'a, (SortKey, T)>,
+}
+
+impl<'a, T> DrawList<'a, T> {
+    pub fn new_in(bump: &'a Bump) -> Self {
+        DrawList {
+            draws: BVec::new_in(bump),
+        }
+    }
+
+    pub fn push(&mut self, key: SortKey, draw: T) {
+        self.draws.push((key, draw));
+    }
+
+    pub fn len(&self) -> usize {
+        self.draws.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.draws.is_empty()
+    }
+
+    /// Sorts draws by their keys.
+    pub fn sort(&mut self) {
+        self.draws.sort_by_key(|(key, _)| *key);
+    }
+
+    /// Iterates over draws in current order.
+    pub fn iter(&self) -> impl Iterator<Item = (SortKey, &T)> + '_ {
+        self.draws.iter().map(|(key, draw)| (*key, draw))
+    }
+}
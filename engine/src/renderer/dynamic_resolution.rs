@@ -0,0 +1,98 @@
+//! Dynamic resolution driven by GPU frame time.
+//!
+//! Cost of path tracing is roughly proportional to number of pixels,
+//! so controller scales both sides of internal extent by square root
+//! of ratio between target and measured GPU time.
+//! Timings come from pass profiler and lag a few frames behind,
+//! hence every correction is damped.
+
+use {super::FrameGraphStats, illume::Extent2d, std::time::Duration};
+
+/// Internal extent is rounded down to multiple of this.
+/// Matches sample map tiles.
+const RESOLUTION_STEP: u32 = 8;
+
+/// Fraction of correction applied per measurement.
+const RESPONSE: f32 = 0.25;
+
+/// Relative frame time error that is tolerated without correction.
+const DEADBAND: f32 = 0.05;
+
+/// Frame time feedback controller of view's internal extent.
+#[derive(Clone, Debug)]
+pub struct DynamicResolution {
+    max_extent: Extent2d,
+    scale: f32,
+
+    /// Profiled frame last measurement was taken from.
+    last_frame: Option<u64>,
+}
+
+impl DynamicResolution {
+    pub fn new(max_extent: Extent2d) -> Self {
+        DynamicResolution {
+            max_extent,
+            scale: 1.0,
+            last_frame: None,
+        }
+    }
+
+    /// Returns current scale of both sides of the extent.
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// Returns extent rendered at full scale.
+    pub fn max_extent(&self) -> Extent2d {
+        self.max_extent
+    }
+
+    /// Returns extent scaled by current scale.
+    pub fn extent(&self) -> Extent2d {
+        let scale = |size: u32| {
+            let scaled = (size as f32 * self.scale) as u32;
+            let stepped = scaled / RESOLUTION_STEP * RESOLUTION_STEP;
+            stepped.max(RESOLUTION_STEP).min(size)
+        };
+
+        Extent2d {
+            width: scale(self.max_extent.width),
+            height: scale(self.max_extent.height),
+        }
+    }
+
+    /// Restores full scale.
+    pub fn reset(&mut self) {
+        self.scale = 1.0;
+        self.last_frame = None;
+    }
+
+    /// Adjusts scale from GPU time of profiled frame
+    /// to approach target frame time.
+    /// Scale is kept within `[min_scale, 1]`.
+    pub fn update(
+        &mut self,
+        stats: &FrameGraphStats,
+        target: Duration,
+        min_scale: f32,
+    ) {
+        if self.last_frame.map_or(false, |frame| frame >= stats.frame) {
+            return;
+        }
+        self.last_frame = Some(stats.frame);
+
+        let gpu_time = stats.gpu_time().as_secs_f32();
+        if gpu_time <= 0.0 {
+            return;
+        }
+
+        let ratio = target.as_secs_f32() / gpu_time;
+        if (ratio - 1.0).abs() < DEADBAND {
+            return;
+        }
+
+        let wanted = self.scale * ratio.sqrt();
+        let scale = self.scale + (wanted - self.scale) * RESPONSE;
+        self.scale = scale.max(min_scale.min(1.0)).min(1.0);
+    }
+}
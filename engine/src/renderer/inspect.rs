@@ -0,0 +1,202 @@
+//! Debug pixel inspector.
+//!
+//! Middle click selects a pixel while `r.debug_overlay` is set.
+//! Renderer reads back intermediate image values at that pixel
+//! and `PixelInspectorOverlay` prints them into `TextOverlay`.
+
+use {
+    super::TextOverlay,
+    crate::{
+        cvar::CVars,
+        engine::{System, SystemContext},
+    },
+    winit::event::{ElementState, Event, MouseButton, WindowEvent},
+};
+
+/// Values of intermediate images at single pixel.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PixelSample {
+    pub pixel: [u32; 2],
+    pub albedo: [f32; 4],
+    pub normal: [f32; 3],
+
+    /// Distance along primary ray.
+    /// `None` if primary ray hit nothing.
+    pub depth: Option<f32>,
+    pub emissive: [f32; 3],
+    pub roughness: f32,
+
+    /// Unfiltered ray-traced radiance.
+    pub direct: [f32; 3],
+    pub diffuse: [f32; 3],
+
+    /// Index of hit material in the frame's material list.
+    pub material: Option<u32>,
+}
+
+impl PixelSample {
+    /// Returns radiance leaving the surface towards camera.
+    pub fn radiance(&self) -> [f32; 3] {
+        let mut radiance = [0.0; 3];
+        for (i, r) in radiance.iter_mut().enumerate() {
+            *r = self.albedo[i] * (self.direct[i] + self.diffuse[i])
+                + self.emissive[i];
+        }
+        radiance
+    }
+
+    /// Returns luminance of radiance leaving the surface towards camera.
+    pub fn luminance(&self) -> f32 {
+        let [r, g, b] = self.radiance();
+        0.2126 * r + 0.7152 * g + 0.0722 * b
+    }
+}
+
+/// Pixel selected for inspection and its last read back values.
+/// Renderer reads this resource when drawing views.
+#[derive(Clone, Debug, Default)]
+pub struct PixelInspector {
+    pub pixel: Option<[u32; 2]>,
+    pub sample: Option<PixelSample>,
+}
+
+impl PixelInspector {
+    pub fn new() -> Self {
+        PixelInspector::default()
+    }
+
+    /// Selects pixel to inspect.
+    pub fn inspect(&mut self, pixel: [u32; 2]) {
+        self.pixel = Some(pixel);
+    }
+
+    /// Stops inspection.
+    pub fn clear(&mut self) {
+        self.pixel = None;
+        self.sample = None;
+    }
+}
+
+/// System that selects pixel under cursor on middle click
+/// and prints `PixelInspector` values into `TextOverlay`
+/// while `r.debug_overlay` is set.
+pub struct PixelInspectorOverlay {
+    cursor: Option<[u32; 2]>,
+}
+
+impl PixelInspectorOverlay {
+    pub fn new() -> Self {
+        PixelInspectorOverlay { cursor: None }
+    }
+}
+
+impl Default for PixelInspectorOverlay {
+    fn default() -> Self {
+        PixelInspectorOverlay::new()
+    }
+}
+
+impl System for PixelInspectorOverlay {
+    fn run(&mut self, ctx: SystemContext<'_>) {
+        let enabled = ctx
+            .resources
+            .get::<CVars>()
+            .and_then(|cvars| cvars.get_bool("r.debug_overlay"))
+            .unwrap_or(false);
+
+        if !ctx.resources.contains::<PixelInspector>() {
+            ctx.resources.insert(PixelInspector::new());
+        }
+
+        let inspector = ctx.resources.get_mut::<PixelInspector>().unwrap();
+
+        if !enabled {
+            inspector.clear();
+            return;
+        }
+
+        for event in ctx.input.read() {
+            match event {
+                Event::WindowEvent {
+                    event: WindowEvent::CursorMoved { position, .. },
+                    ..
+                } => {
+                    self.cursor = Some([
+                        position.x.max(0.0) as u32,
+                        position.y.max(0.0) as u32,
+                    ]);
+                }
+                Event::WindowEvent {
+                    event: WindowEvent::CursorLeft { .. },
+                    ..
+                } => {
+                    self.cursor = None;
+                }
+                Event::WindowEvent {
+                    event:
+                        WindowEvent::MouseInput {
+                            button: MouseButton::Middle,
+                            state: ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                } => {
+                    if let Some(cursor) = self.cursor {
+                        inspector.inspect(cursor);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let sample = match inspector.sample {
+            Some(sample) => sample,
+            None => return,
+        };
+
+        let lines = sample_lines(&sample);
+
+        let overlay = match ctx.resources.get_mut::<TextOverlay>() {
+            Some(overlay) => overlay,
+            None => return,
+        };
+
+        let cols = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+        let row = overlay.rows().saturating_sub(lines.len() as u32);
+
+        overlay.fill(0, row, cols as u32, lines.len() as u32);
+        for (index, line) in lines.iter().enumerate() {
+            overlay.print(0, row + index as u32, line, [255, 255, 255]);
+        }
+    }
+}
+
+fn sample_lines(sample: &PixelSample) -> Vec<String> {
+    let rgb = |[r, g, b]: [f32; 3]| format!("{:9.3}{:9.3}{:9.3}", r, g, b);
+
+    let depth = match sample.depth {
+        Some(depth) => format!("{:.3}", depth),
+        None => "-".to_owned(),
+    };
+
+    let material = match sample.material {
+        Some(material) => material.to_string(),
+        None => "-".to_owned(),
+    };
+
+    let [r, g, b, a] = sample.albedo;
+
+    vec![
+        format!("pixel     {} {}", sample.pixel[0], sample.pixel[1]),
+        format!("luminance {:.3}", sample.luminance()),
+        format!("radiance  {}", rgb(sample.radiance())),
+        format!("albedo    {}{:9.3}", rgb([r, g, b]), a),
+        format!("normal    {}", rgb(sample.normal)),
+        format!("depth     {}", depth),
+        format!("material  {}", material),
+        format!("roughness {:.3}", sample.roughness),
+        format!("emissive  {}", rgb(sample.emissive)),
+        format!("direct    {}", rgb(sample.direct)),
+        format!("diffuse   {}", rgb(sample.diffuse)),
+    ]
+}
@@ -0,0 +1,207 @@
+use {
+    super::Mesh,
+    crate::{
+        camera::Camera,
+        engine::{System, SystemContext},
+        scene::Global3,
+    },
+};
+
+/// Default number of `RtLod` components re-evaluated per frame.
+pub const DEFAULT_RT_LOD_BUDGET: usize = 4096;
+
+/// Relative margin of projected size required to switch
+/// to finer level, preventing flickering at thresholds.
+const HYSTERESIS: f32 = 0.1;
+
+/// Level of detail selected for acceleration structure.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RtLodLevel {
+    /// Renderable's own mesh is used.
+    Full,
+
+    /// Proxy mesh is used.
+    Proxy,
+
+    /// Renderable is excluded from TLAS.
+    Culled,
+}
+
+/// Ray-tracing level of detail of renderable.
+///
+/// Projected size is bounding sphere diameter relative to view height.
+/// Level is updated by `RtLodSystem`.
+#[derive(Clone, Debug)]
+pub struct RtLod {
+    /// Radius of bounding sphere in renderable's local space.
+    pub radius: f32,
+
+    /// Low-poly mesh used instead of renderable's mesh at distance.
+    pub proxy: Option<Mesh>,
+
+    /// Projected size below which proxy is used.
+    pub proxy_size: f32,
+
+    /// Projected size below which renderable is excluded from TLAS.
+    pub cull_size: f32,
+
+    level: RtLodLevel,
+}
+
+impl RtLod {
+    /// Creates LOD that culls renderable when it is tiny.
+    pub fn new(radius: f32) -> Self {
+        RtLod {
+            radius,
+            proxy: None,
+            proxy_size: 0.0,
+            cull_size: 0.002,
+            level: RtLodLevel::Full,
+        }
+    }
+
+    /// Sets proxy mesh used below specified projected size.
+    pub fn with_proxy(mut self, proxy: Mesh, size: f32) -> Self {
+        self.proxy = Some(proxy);
+        self.proxy_size = size;
+        self
+    }
+
+    /// Sets projected size below which renderable is culled.
+    /// Zero disables culling.
+    pub fn with_cull_size(mut self, size: f32) -> Self {
+        self.cull_size = size;
+        self
+    }
+
+    pub fn level(&self) -> RtLodLevel {
+        self.level
+    }
+
+    /// Returns mesh to be placed into acceleration structure
+    /// given renderable's mesh.
+    pub fn select<'a>(&'a self, mesh: &'a Mesh) -> Option<&'a Mesh> {
+        match self.level {
+            RtLodLevel::Full => Some(mesh),
+            RtLodLevel::Proxy => Some(self.proxy.as_ref().unwrap_or(mesh)),
+            RtLodLevel::Culled => None,
+        }
+    }
+
+    fn update(&mut self, size: f32) {
+        let finer = 1.0 + HYSTERESIS;
+
+        let cull_size = match self.level {
+            RtLodLevel::Culled => self.cull_size * finer,
+            _ => self.cull_size,
+        };
+
+        let proxy_size = match self.level {
+            RtLodLevel::Full => self.proxy_size,
+            _ => self.proxy_size * finer,
+        };
+
+        self.level = if size < cull_size {
+            RtLodLevel::Culled
+        } else if self.proxy.is_some() && size < proxy_size {
+            RtLodLevel::Proxy
+        } else {
+            RtLodLevel::Full
+        };
+    }
+}
+
+/// System that selects `RtLod` levels from projected size
+/// relative to the first camera.
+///
+/// Only limited number of components is re-evaluated per frame,
+/// so TLAS instance list changes gradually.
+pub struct RtLodSystem {
+    budget: usize,
+    cursor: usize,
+}
+
+impl RtLodSystem {
+    pub fn new() -> Self {
+        RtLodSystem {
+            budget: DEFAULT_RT_LOD_BUDGET,
+            cursor: 0,
+        }
+    }
+
+    /// Sets maximum number of components re-evaluated per frame.
+    pub fn with_budget(mut self, budget: usize) -> Self {
+        self.budget = budget.max(1);
+        self
+    }
+}
+
+impl Default for RtLodSystem {
+    fn default() -> Self {
+        RtLodSystem::new()
+    }
+}
+
+impl System for RtLodSystem {
+    fn run(&mut self, ctx: SystemContext<'_>) {
+        let camera = ctx
+            .world
+            .query::<(&Camera, &Global3)>()
+            .iter()
+            .next()
+            .map(|(_, (camera, global))| (*camera, global.iso.translation));
+
+        let (camera, eye) = match camera {
+            Some(camera) => camera,
+            None => return,
+        };
+
+        // Projection scale maps radius to fraction of view height.
+        // Perspective size is additionally divided by distance.
+        let scale = camera.projection().matrix()[(1, 1)];
+        let perspective = !matches!(camera, Camera::Orthographic(_));
+
+        let update = |rt_lod: &mut RtLod, global: &Global3| {
+            let radius = rt_lod.radius
+                * (0..3)
+                    .map(|i| global.skew.column(i).norm())
+                    .fold(0.0, f32::max);
+
+            let mut size = radius * scale;
+            if perspective {
+                let distance =
+                    (global.iso.translation.vector - eye.vector).norm();
+                size /= distance.max(radius).max(f32::EPSILON);
+            }
+
+            rt_lod.update(size);
+        };
+
+        let mut query = ctx.world.query::<(&mut RtLod, &Global3)>();
+        let count = query.iter().count();
+        if count == 0 {
+            return;
+        }
+
+        if self.cursor >= count {
+            self.cursor = 0;
+        }
+
+        // Continue from where previous frame stopped and wrap around.
+        let mut updated = 0;
+        for (_, (rt_lod, global)) in
+            query.iter().skip(self.cursor).take(self.budget)
+        {
+            update(rt_lod, global);
+            updated += 1;
+        }
+
+        let wrapped = (self.budget - updated).min(self.cursor);
+        for (_, (rt_lod, global)) in query.iter().take(wrapped) {
+            update(rt_lod, global);
+            updated += 1;
+        }
+
+        self.cursor = (self.cursor + updated) % count;
+    }
+}
@@ -1,4 +1,5 @@
 use {
+    super::VirtualTexture,
     illume::{ImageView, Sampler},
     ordered_float::OrderedFloat,
 };
@@ -12,6 +13,62 @@ pub struct Texture {
     pub sampler: Sampler,
 }
 
+/// Number of custom scalar parameters in material parameter block.
+pub const MATERIAL_SCALARS: usize = 8;
+
+/// Number of custom textures in material parameter block.
+pub const MATERIAL_TEXTURES: usize = 4;
+
+/// Custom material parameters.
+/// Shaders access parameters by slot index.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ParameterBlock {
+    pub scalars: [OrderedFloat<f32>; MATERIAL_SCALARS],
+    pub textures: [Option<Texture>; MATERIAL_TEXTURES],
+}
+
+impl Default for ParameterBlock {
+    fn default() -> Self {
+        ParameterBlock::new()
+    }
+}
+
+impl ParameterBlock {
+    pub const fn new() -> Self {
+        ParameterBlock {
+            scalars: [OrderedFloat(0.0); MATERIAL_SCALARS],
+            textures: [None, None, None, None],
+        }
+    }
+}
+
+/// Specifies how alpha channel of albedo is interpreted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Deserialize)]
+pub enum AlphaMode {
+    /// Alpha is ignored and surface is fully opaque.
+    Opaque,
+
+    /// Surface is transparent where alpha is less than cutoff value
+    /// and opaque elsewhere.
+    Mask { cutoff: OrderedFloat<f32> },
+
+    /// Alpha is surface coverage.
+    /// Path tracer resolves it stochastically
+    /// and result converges to blended surface over frames.
+    Blend,
+
+    /// Surface refracts light passing through it.
+    /// Albedo tints transmitted light, alpha is strength of the tint.
+    /// Shadow rays pass through the surface.
+    Refract { ior: OrderedFloat<f32> },
+}
+
+impl Default for AlphaMode {
+    fn default() -> Self {
+        AlphaMode::Opaque
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Material {
     pub albedo: Option<Texture>,
@@ -26,6 +83,14 @@ pub struct Material {
                                            * vec3(sampled_normal.xy
                                            * * normal_factor,
                                            * sampled_normal.z) */
+    pub alpha_mode: AlphaMode,
+
+    /// Back faces are culled unless material is double-sided.
+    pub double_sided: bool,
+    pub params: ParameterBlock,
+
+    /// Streaming texture that replaces albedo and normal textures.
+    pub virtual_texture: Option<VirtualTexture>,
 }
 
 impl Default for Material {
@@ -46,6 +111,10 @@ impl Material {
             emissive_factor: [OrderedFloat(0.0); 3],
             normal: None,
             normal_factor: OrderedFloat(1.0),
+            alpha_mode: AlphaMode::Opaque,
+            double_sided: false,
+            params: ParameterBlock::new(),
+            virtual_texture: None,
         }
     }
 
@@ -56,4 +125,91 @@ impl Material {
             ..Material::new()
         }
     }
+
+    /// Checks if material references any device resources.
+    pub fn has_textures(&self) -> bool {
+        self.albedo.is_some()
+            || self.metallic_roughness.is_some()
+            || self.emissive.is_some()
+            || self.normal.is_some()
+            || self.params.textures.iter().any(Option::is_some)
+            || self.virtual_texture.is_some()
+    }
+}
+
+/// Component that overrides material parameters of renderable entity.
+///
+/// Allows changing parameters at runtime per entity
+/// without creating new material.
+/// For example to flash damaged entity or apply team color.
+#[derive(Clone, Debug, Default)]
+pub struct MaterialOverride {
+    /// Replaces albedo factor of the material.
+    pub albedo_factor: Option<[f32; 4]>,
+
+    /// Multiplies albedo factor of the material.
+    pub tint: Option<[f32; 4]>,
+
+    /// Replaces emissive factor of the material.
+    pub emissive_factor: Option<[f32; 3]>,
+
+    /// Multiplies emissive factor of the material.
+    pub emissive_multiplier: Option<f32>,
+
+    pub metallic_factor: Option<f32>,
+    pub roughness_factor: Option<f32>,
+
+    pub albedo: Option<Texture>,
+    pub normal: Option<Texture>,
+    pub emissive: Option<Texture>,
+
+    pub scalars: [Option<f32>; MATERIAL_SCALARS],
+    pub textures: [Option<Texture>; MATERIAL_TEXTURES],
+}
+
+impl MaterialOverride {
+    pub fn new() -> Self {
+        MaterialOverride::default()
+    }
+
+    /// Returns override that multiplies albedo by specified color.
+    pub fn tint(rgba: [f32; 4]) -> Self {
+        MaterialOverride {
+            tint: Some(rgba),
+            ..MaterialOverride::new()
+        }
+    }
+
+    /// Overrides custom scalar parameter in specified slot.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `slot` is not less than `MATERIAL_SCALARS`.
+    pub fn set_scalar(&mut self, slot: usize, value: f32) {
+        self.scalars[slot] = Some(value);
+    }
+
+    /// Overrides custom texture in specified slot.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `slot` is not less than `MATERIAL_TEXTURES`.
+    pub fn set_texture(&mut self, slot: usize, texture: Texture) {
+        self.textures[slot] = Some(texture);
+    }
+
+    /// Returns `true` if no parameters are overridden.
+    pub fn is_empty(&self) -> bool {
+        self.albedo_factor.is_none()
+            && self.tint.is_none()
+            && self.emissive_factor.is_none()
+            && self.emissive_multiplier.is_none()
+            && self.metallic_factor.is_none()
+            && self.roughness_factor.is_none()
+            && self.albedo.is_none()
+            && self.normal.is_none()
+            && self.emissive.is_none()
+            && self.scalars.iter().all(Option::is_none)
+            && self.textures.iter().all(Option::is_none)
+    }
 }
@@ -5,9 +5,14 @@ use {
     },
     bumpalo::{collections::Vec as BVec, Bump},
     bytemuck::cast_slice,
+    eyre::Report,
     illume::*,
     std::{
-        borrow::Cow, convert::TryFrom as _, mem::size_of_val, ops::Range,
+        borrow::Cow,
+        convert::TryFrom as _,
+        hash::{Hash, Hasher},
+        mem::size_of_val,
+        ops::Range,
         sync::Arc,
     },
 };
@@ -107,17 +112,64 @@ impl MeshBuilder {
             topology: self.topology,
             count,
             vertex_count,
+            data: None,
         }
     }
 }
 
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+/// Policy for CPU-side copy of mesh data after upload.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MeshRetention {
+    /// Drop CPU-side data after upload.
+    Drop,
+
+    /// Drop CPU-side data after upload but keep buffers readable
+    /// so data can be fetched with `Mesh::download`.
+    Download,
+
+    /// Keep CPU-side data in the mesh.
+    Retain,
+}
+
+impl Default for MeshRetention {
+    fn default() -> Self {
+        MeshRetention::Drop
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Mesh {
     bindings: Arc<[Binding]>,
     indices: Option<Indices>,
     count: u32,
     vertex_count: u32,
     topology: PrimitiveTopology,
+
+    /// CPU-side copy of mesh data.
+    /// Not considered in comparisons as it mirrors buffers content.
+    data: Option<Arc<MeshData<'static>>>,
+}
+
+impl PartialEq for Mesh {
+    fn eq(&self, other: &Self) -> bool {
+        self.bindings == other.bindings
+            && self.indices == other.indices
+            && self.count == other.count
+            && self.vertex_count == other.vertex_count
+            && self.topology == other.topology
+    }
+}
+
+impl Eq for Mesh {}
+
+impl Hash for Mesh {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.bindings.hash(state);
+        self.indices.hash(state);
+        self.count.hash(state);
+        self.vertex_count.hash(state);
+        self.topology.hash(state);
+    }
 }
 
 impl Mesh {
@@ -141,6 +193,155 @@ impl Mesh {
         self.indices.as_ref()
     }
 
+    pub fn topology(&self) -> PrimitiveTopology {
+        self.topology
+    }
+
+    /// Returns CPU-side copy of mesh data if it was retained.
+    pub fn data(&self) -> Option<&Arc<MeshData<'static>>> {
+        self.data.as_ref()
+    }
+
+    pub fn with_data(mut self, data: Arc<MeshData<'static>>) -> Self {
+        self.data = Some(data);
+        self
+    }
+
+    /// Drops CPU-side copy of mesh data.
+    pub fn take_data(&mut self) -> Option<Arc<MeshData<'static>>> {
+        self.data.take()
+    }
+
+    /// Uploads retained CPU-side data into new buffers
+    /// with the same usage as current ones.
+    /// Used to restore mesh on recreated device.
+    /// Returns `None` if mesh data was not retained.
+    pub fn rebuild(
+        &self,
+        ctx: &mut Context,
+    ) -> Result<Option<Mesh>, OutOfMemory> {
+        let data = match &self.data {
+            Some(data) => data,
+            None => return Ok(None),
+        };
+
+        let vertices_usage = self
+            .bindings
+            .iter()
+            .fold(BufferUsage::empty(), |usage, binding| {
+                usage | binding.buffer.info().usage
+            });
+
+        let indices_usage = self
+            .indices
+            .as_ref()
+            .map_or(BufferUsage::empty(), |indices| {
+                indices.buffer.info().usage
+            });
+
+        let mesh = data.build(ctx, vertices_usage, indices_usage)?;
+        Ok(Some(mesh.with_data(data.clone())))
+    }
+
+    /// Fetches mesh data from the device.
+    /// Buffers must be created with `TRANSFER_SRC` usage.
+    /// Pending uploads are flushed and the call blocks
+    /// until copy is complete.
+    pub fn download(
+        &self,
+        ctx: &mut Context,
+        bump: &Bump,
+    ) -> Result<MeshData<'static>, Report> {
+        ctx.flush_uploads(bump)?;
+
+        let mut regions = BVec::with_capacity_in(self.bindings.len() + 1, bump);
+        let mut size = 0;
+
+        for binding in &*self.bindings {
+            let len =
+                u64::from(binding.layout.stride) * u64::from(self.vertex_count);
+            regions.push((&binding.buffer, binding.offset, size, len));
+            size += len;
+        }
+
+        if let Some(indices) = &self.indices {
+            let len =
+                u64::from(indices.index_type.size()) * u64::from(self.count);
+            regions.push((&indices.buffer, indices.offset, size, len));
+            size += len;
+        }
+
+        let mut readback = ctx.create_mappable_buffer(
+            BufferInfo {
+                align: 15,
+                size: size.max(1),
+                usage: BufferUsage::TRANSFER_DST,
+                capture_address: None,
+            },
+            MemoryUsage::DOWNLOAD,
+        )?;
+
+        let dst_buffer = bump.alloc(readback.share());
+        let mut encoder = ctx.queue.create_encoder()?;
+        encoder.pipeline_barrier(
+            PipelineStageFlags::ALL_COMMANDS,
+            PipelineStageFlags::TRANSFER,
+        );
+
+        for &(buffer, src_offset, dst_offset, size) in &regions {
+            encoder.copy_buffer(
+                buffer,
+                dst_buffer,
+                bump.alloc([BufferCopy {
+                    src_offset,
+                    dst_offset,
+                    size,
+                }]),
+            );
+        }
+
+        encoder.pipeline_barrier(
+            PipelineStageFlags::TRANSFER,
+            PipelineStageFlags::HOST,
+        );
+
+        let fence = ctx.pooled_fence()?;
+        ctx.queue
+            .submit_no_semaphores(encoder.finish()?, Some(&fence))?;
+        ctx.wait_fences(&[&fence], true)?;
+
+        let mut bytes = vec![0u8; usize::try_from(size)?];
+        ctx.read_buffer(&mut readback, 0, &mut bytes)?;
+
+        let read = |offset: u64, len: u64| -> Result<Vec<u8>, Report> {
+            let start = usize::try_from(offset)?;
+            let end = usize::try_from(offset + len)?;
+            Ok(bytes[start..end].to_vec())
+        };
+
+        let mut data = MeshData::new(self.topology);
+        let mut regions = regions.iter();
+
+        for (binding, &(_, _, offset, len)) in
+            self.bindings.iter().zip(&mut regions)
+        {
+            data.bindings.push(BindingData {
+                data: Cow::Owned(read(offset, len)?),
+                layout: binding.layout.clone(),
+            });
+        }
+
+        if let Some(indices) = &self.indices {
+            let &(_, _, offset, len) = regions.next().unwrap();
+            data.indices = Some(IndicesData {
+                data: Cow::Owned(read(offset, len)?),
+                index_type: indices.index_type,
+            });
+        }
+
+        Ok(data)
+    }
+
     pub fn build_triangles_blas<'a>(
         &self,
         encoder: &mut Encoder<'a>,
@@ -314,6 +515,27 @@ impl MeshData<'_> {
     }
 }
 
+impl MeshData<'_> {
+    /// Converts into data that owns its bytes.
+    pub fn into_owned(self) -> MeshData<'static> {
+        MeshData {
+            bindings: self
+                .bindings
+                .into_iter()
+                .map(|binding| BindingData {
+                    data: Cow::Owned(binding.data.into_owned()),
+                    layout: binding.layout,
+                })
+                .collect(),
+            indices: self.indices.map(|indices| IndicesData {
+                data: Cow::Owned(indices.data.into_owned()),
+                index_type: indices.index_type,
+            }),
+            topology: self.topology,
+        }
+    }
+}
+
 impl<'a> MeshData<'a> {
     pub fn add_binding<V>(&mut self, vertices: &'a [V]) -> &mut Self
     where
@@ -381,6 +603,7 @@ impl MeshData<'_> {
                                 size: u64::try_from(binding.data.len())
                                     .map_err(|_| OutOfMemory)?,
                                 usage: vertices_usage,
+                                capture_address: None,
                             },
                             &binding.data,
                         )?
@@ -411,6 +634,7 @@ impl MeshData<'_> {
                                 size: u64::try_from(indices.data.len())
                                     .map_err(|_| OutOfMemory)?,
                                 usage: indices_usage,
+                                capture_address: None,
                             },
                             &indices.data,
                         )?
@@ -427,6 +651,7 @@ impl MeshData<'_> {
             topology: self.topology,
             count,
             vertex_count: min_vertex_count,
+            data: None,
         })
     }
 
@@ -660,6 +885,7 @@ mod gm {
                     align: 63,
                     size: u64::try_from(data.len()).map_err(|_| OutOfMemory)?,
                     usage,
+                    capture_address: None,
                 },
                 &data[..],
             )?);
@@ -682,6 +908,7 @@ mod gm {
                 count: index_count,
                 topology: PrimitiveTopology::TriangleList,
                 vertex_count,
+                data: None,
             })
         }
     }
@@ -725,6 +952,7 @@ impl PoseMesh {
             align: 255,
             size: offset,
             usage,
+            capture_address: None,
         })?;
 
         let bindings = prebindings
@@ -781,6 +1009,7 @@ fn build_triangles_blas<'a>(
         align: 255,
         size: sizes.acceleration_structure_size,
         usage: BufferUsage::ACCELERATION_STRUCTURE_STORAGE,
+        capture_address: None,
     })?;
 
     let blas =
@@ -797,6 +1026,7 @@ fn build_triangles_blas<'a>(
         align: 255,
         size: sizes.build_scratch_size,
         usage: BufferUsage::DEVICE_ADDRESS,
+        capture_address: None,
     })?;
 
     let blas_scratch_address =
@@ -1,26 +1,88 @@
+mod accumulation;
+mod canvas;
+mod compile;
 mod context;
+mod draw_list;
+mod dynamic_resolution;
+mod inspect;
+mod lod;
 mod material;
 mod mesh;
+mod object_table;
+mod overlay;
 mod pass;
 mod pipeline;
+mod profiler;
+mod staging;
+mod swapchain_frame;
+mod sync_pool;
+mod validation;
 mod vertex;
+mod view;
+mod view_uniforms;
+mod virtual_texture;
 
 pub use {
-    self::{context::Context, material::*, mesh::*, vertex::*},
+    self::{
+        accumulation::{Accumulation, AccumulationOverlay},
+        canvas::CanvasTexel,
+        compile::PipelineHandle,
+        context::Context,
+        draw_list::{DepthOrder, DrawList, SortKey},
+        dynamic_resolution::DynamicResolution,
+        inspect::{PixelInspector, PixelInspectorOverlay, PixelSample},
+        lod::{RtLod, RtLodLevel, RtLodSystem, DEFAULT_RT_LOD_BUDGET},
+        material::*,
+        mesh::*,
+        object_table::{ObjectTable, ShaderObject, MAX_OBJECTS},
+        overlay::{TextOverlay, GLYPH_SIZE, MAX_OVERLAY_CELLS},
+        pass::{
+            ATrousConfig, MAX_ADAPTIVE_SAMPLES, MAX_ATROUS_ITERATIONS,
+            WATER_FIELDS,
+        },
+        pipeline::{DenoiseImage, Denoiser, ExternalDenoiser},
+        profiler::{image_size, FrameGraphOverlay, FrameGraphStats, PassStats},
+        staging::{StagingBelt, StagingRegion, STAGING_CHUNK_SIZE},
+        swapchain_frame::{SwapchainAccess, SwapchainFrame},
+        validation::PassValidationError,
+        vertex::*,
+        view::{split_regions, ViewTarget, MAX_VIEW_SPLITS},
+        view_uniforms::{ViewGlobals, ViewUniform, MAX_VIEWS_PER_FRAME},
+        virtual_texture::*,
+    },
     illume::*,
 };
 
+#[cfg(feature = "oidn")]
+pub use self::pipeline::OidnDenoiser;
+
 use {
-    self::{pass::*, pipeline::*},
-    crate::{camera::Camera, clocks::ClockIndex, scene::Global3},
+    self::{
+        pass::*,
+        pipeline::*,
+        view::{ViewSplit, WindowHandle},
+    },
+    crate::{
+        assets::BlueNoise,
+        camera::{rig::CameraRig, Camera, CameraSettings},
+        clocks::ClockIndex,
+        cvar::{CVarValue, CVars},
+        logging::set_crash_context,
+        photo::{PhotoMode, PhotoShot},
+        scene::Global3,
+        water::Water,
+    },
     bumpalo::Bump,
     color_eyre::Report,
     eyre::eyre,
-    hecs::World,
+    hecs::{Entity, World},
     nalgebra as na,
+    smallvec::SmallVec,
     std::{
-        collections::hash_map::{Entry, HashMap},
+        collections::HashMap,
         ops::{Deref, DerefMut},
+        sync::Arc,
+        time::Duration,
     },
     type_map::TypeMap,
     winit::window::Window,
@@ -42,25 +104,574 @@ pub struct Renderable {
     // pub transform: Option<na::Matrix4<f32>>,
 }
 
+/// Marks renderables that are excluded from acceleration structures.
+/// Such renderables are drawn only by raster pipeline.
+#[derive(Clone, Copy, Debug)]
+pub struct RasterOnly;
+
+/// Marks renderables that are voxelized into global signed distance field.
+/// Such renderables are expected to rarely move or change,
+/// as any change causes their bricks to be voxelized again.
+#[derive(Clone, Copy, Debug)]
+pub struct StaticGeometry {
+    /// Radius of bounding sphere in renderable's local space.
+    pub radius: f32,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+#[serde(default)]
 pub struct RenderConstants {
     pub filter_enabled: bool,
+
+    /// Denoising preset.
+    /// Overrides per-channel filters unless custom.
+    pub denoise_preset: DenoisePreset,
+
+    /// Filter of direct radiance.
+    pub direct_filter: ATrousConfig,
+
+    /// Filter of indirect diffuse radiance.
+    pub diffuse_filter: ATrousConfig,
+
+    /// Filter of screen-space reflections.
+    pub specular_filter: ATrousConfig,
+
+    /// Denoises direct and diffuse radiance with `ExternalDenoiser`
+    /// instead of A-Trous filters when one is present in the world.
+    /// Stalls every frame, intended for offline captures.
+    pub external_denoise: bool,
+
+    /// Accumulates samples across frames instead of denoising
+    /// while camera and scene stay static.
+    pub accumulate: bool,
+
+    /// Upper limit for paths per pixel in a frame while accumulating.
+    /// Noisy tiles get more paths, converged ones get single path.
+    pub adaptive_samples: u32,
+
+    /// Relative error per tile that adaptive sampling aims for.
+    pub adaptive_error: f32,
+
+    /// Scales internal extent of views to hold target GPU frame time.
+    pub dynamic_resolution: bool,
+
+    /// GPU frame time in milliseconds dynamic resolution aims for.
+    pub target_frame_time: f32,
+
+    /// Lower bound for scale of internal extent sides.
+    pub min_resolution_scale: f32,
+
+    /// Traces half of the pixels per frame in checkerboard pattern
+    /// and reconstructs the rest from previous frame.
+    /// Ignored while accumulating.
+    pub checkerboard: bool,
+
+    /// Enables depth of field for cameras with `CameraSettings`.
+    pub depth_of_field: bool,
+
+    /// Enables camera motion blur.
+    pub motion_blur: bool,
+
+    /// Enables screen-space ambient occlusion.
+    pub ssao: bool,
+
+    /// Enables screen-space reflections.
+    pub ssr: bool,
+
+    /// Exposure compensation in stops.
+    pub exposure: f32,
+
+    /// Profiles passes and shows their statistics over the view.
+    pub debug_overlay: bool,
+
+    /// Validates commands submitted by each pass.
+    /// Invalid commands fail the frame with error naming the pass.
+    pub validate_passes: bool,
 }
 
 impl RenderConstants {
     pub const fn new() -> Self {
         RenderConstants {
             filter_enabled: true,
+            denoise_preset: DenoisePreset::Medium,
+            direct_filter: ATrousConfig::medium(),
+            diffuse_filter: ATrousConfig::medium(),
+            specular_filter: ATrousConfig::low(),
+            external_denoise: false,
+            accumulate: false,
+            adaptive_samples: 4,
+            adaptive_error: 0.02,
+            dynamic_resolution: false,
+            target_frame_time: 16.0,
+            min_resolution_scale: 0.5,
+            checkerboard: false,
+            depth_of_field: true,
+            motion_blur: false,
+            ssao: false,
+            ssr: false,
+            exposure: 0.0,
+            debug_overlay: false,
+            validate_passes: false,
+        }
+    }
+
+    /// Returns direct, diffuse and specular filters
+    /// selected by denoising preset.
+    pub fn denoise_filters(&self) -> [ATrousConfig; 3] {
+        self.denoise_preset.filters().unwrap_or([
+            self.direct_filter,
+            self.diffuse_filter,
+            self.specular_filter,
+        ])
+    }
+
+    /// Registers renderer console variables with these constants as defaults.
+    pub fn register_cvars(&self, cvars: &mut CVars) {
+        cvars.register_bool(
+            "r.filter_enabled",
+            self.filter_enabled,
+            "Enables denoising filter",
+        );
+        cvars.register_enum(
+            "r.denoise_preset",
+            DenoisePreset::NAMES,
+            self.denoise_preset.name(),
+            "Denoising quality, `custom` uses per-channel variables",
+        );
+        register_filter_cvars(cvars, &DIRECT_FILTER_CVARS, &self.direct_filter);
+        register_filter_cvars(
+            cvars,
+            &DIFFUSE_FILTER_CVARS,
+            &self.diffuse_filter,
+        );
+        register_filter_cvars(
+            cvars,
+            &SPECULAR_FILTER_CVARS,
+            &self.specular_filter,
+        );
+        cvars.register_bool(
+            "r.external_denoise",
+            self.external_denoise,
+            "Uses external denoiser instead of A-Trous filters if available",
+        );
+        cvars.register_bool(
+            "r.accumulate",
+            self.accumulate,
+            "Accumulates samples of static views instead of denoising",
+        );
+        cvars.register_int(
+            "r.adaptive_samples",
+            self.adaptive_samples.into(),
+            "Maximum paths per pixel in a frame while accumulating",
+        );
+        cvars.register_float(
+            "r.adaptive_error",
+            self.adaptive_error.into(),
+            "Relative error adaptive sampling aims for",
+        );
+        cvars.register_bool(
+            "r.dynamic_resolution",
+            self.dynamic_resolution,
+            "Scales internal resolution to hold target frame time",
+        );
+        cvars.register_float(
+            "r.target_frame_time",
+            self.target_frame_time.into(),
+            "GPU frame time in milliseconds dynamic resolution aims for",
+        );
+        cvars.register_float(
+            "r.min_resolution_scale",
+            self.min_resolution_scale.into(),
+            "Lower bound for dynamic resolution scale",
+        );
+        cvars.register_bool(
+            "r.checkerboard",
+            self.checkerboard,
+            "Traces half of the pixels per frame in checkerboard pattern",
+        );
+        cvars.register_bool(
+            "r.depth_of_field",
+            self.depth_of_field,
+            "Enables depth of field",
+        );
+        cvars.register_bool(
+            "r.motion_blur",
+            self.motion_blur,
+            "Enables camera motion blur",
+        );
+        cvars.register_bool(
+            "r.ssao",
+            self.ssao,
+            "Enables screen-space ambient occlusion",
+        );
+        cvars.register_bool(
+            "r.ssr",
+            self.ssr,
+            "Enables screen-space reflections",
+        );
+        cvars.register_float(
+            "r.exposure",
+            self.exposure.into(),
+            "Exposure compensation in stops",
+        );
+        cvars.register_bool(
+            "r.debug_overlay",
+            self.debug_overlay,
+            "Shows pass timings, barriers and transient resources",
+        );
+        cvars.register_bool(
+            "r.validate_passes",
+            self.validate_passes,
+            "Validates resources and layouts used by each pass",
+        );
+    }
+
+    /// Sets renderer console variables to these constants.
+    pub fn write_cvars(&self, cvars: &mut CVars) {
+        cvars.set_or_defer("r.filter_enabled", self.filter_enabled.into());
+        cvars.set_or_defer(
+            "r.denoise_preset",
+            CVarValue::Enum(self.denoise_preset.name().to_owned()),
+        );
+        write_filter_cvars(cvars, &DIRECT_FILTER_CVARS, &self.direct_filter);
+        write_filter_cvars(cvars, &DIFFUSE_FILTER_CVARS, &self.diffuse_filter);
+        write_filter_cvars(
+            cvars,
+            &SPECULAR_FILTER_CVARS,
+            &self.specular_filter,
+        );
+        cvars.set_or_defer("r.external_denoise", self.external_denoise.into());
+        cvars.set_or_defer("r.accumulate", self.accumulate.into());
+        cvars.set_or_defer(
+            "r.adaptive_samples",
+            i64::from(self.adaptive_samples).into(),
+        );
+        cvars.set_or_defer(
+            "r.adaptive_error",
+            f64::from(self.adaptive_error).into(),
+        );
+        cvars.set_or_defer(
+            "r.dynamic_resolution",
+            self.dynamic_resolution.into(),
+        );
+        cvars.set_or_defer(
+            "r.target_frame_time",
+            f64::from(self.target_frame_time).into(),
+        );
+        cvars.set_or_defer(
+            "r.min_resolution_scale",
+            f64::from(self.min_resolution_scale).into(),
+        );
+        cvars.set_or_defer("r.checkerboard", self.checkerboard.into());
+        cvars.set_or_defer("r.depth_of_field", self.depth_of_field.into());
+        cvars.set_or_defer("r.motion_blur", self.motion_blur.into());
+        cvars.set_or_defer("r.ssao", self.ssao.into());
+        cvars.set_or_defer("r.ssr", self.ssr.into());
+        cvars.set_or_defer("r.exposure", f64::from(self.exposure).into());
+        cvars.set_or_defer("r.debug_overlay", self.debug_overlay.into());
+        cvars.set_or_defer("r.validate_passes", self.validate_passes.into());
+    }
+
+    /// Overrides constants with registered renderer console variables.
+    pub fn read_cvars(&mut self, cvars: &CVars) {
+        if let Some(value) = cvars.get_bool("r.filter_enabled") {
+            self.filter_enabled = value;
+        }
+        if let Some(value) = cvars
+            .get_enum("r.denoise_preset")
+            .and_then(DenoisePreset::from_name)
+        {
+            self.denoise_preset = value;
+        }
+        read_filter_cvars(cvars, &DIRECT_FILTER_CVARS, &mut self.direct_filter);
+        read_filter_cvars(
+            cvars,
+            &DIFFUSE_FILTER_CVARS,
+            &mut self.diffuse_filter,
+        );
+        read_filter_cvars(
+            cvars,
+            &SPECULAR_FILTER_CVARS,
+            &mut self.specular_filter,
+        );
+        if let Some(value) = cvars.get_bool("r.external_denoise") {
+            self.external_denoise = value;
+        }
+        if let Some(value) = cvars.get_bool("r.accumulate") {
+            self.accumulate = value;
+        }
+        if let Some(value) = cvars.get_int("r.adaptive_samples") {
+            self.adaptive_samples =
+                value.max(1).min(MAX_ADAPTIVE_SAMPLES.into()) as u32;
+        }
+        if let Some(value) = cvars.get_float("r.adaptive_error") {
+            self.adaptive_error = value as f32;
+        }
+        if let Some(value) = cvars.get_bool("r.dynamic_resolution") {
+            self.dynamic_resolution = value;
+        }
+        if let Some(value) = cvars.get_float("r.target_frame_time") {
+            self.target_frame_time = value as f32;
+        }
+        if let Some(value) = cvars.get_float("r.min_resolution_scale") {
+            self.min_resolution_scale = value as f32;
+        }
+        if let Some(value) = cvars.get_bool("r.checkerboard") {
+            self.checkerboard = value;
+        }
+        if let Some(value) = cvars.get_bool("r.depth_of_field") {
+            self.depth_of_field = value;
+        }
+        if let Some(value) = cvars.get_bool("r.motion_blur") {
+            self.motion_blur = value;
+        }
+        if let Some(value) = cvars.get_bool("r.ssao") {
+            self.ssao = value;
+        }
+        if let Some(value) = cvars.get_bool("r.ssr") {
+            self.ssr = value;
+        }
+        if let Some(value) = cvars.get_float("r.exposure") {
+            self.exposure = value as f32;
+        }
+        if let Some(value) = cvars.get_bool("r.debug_overlay") {
+            self.debug_overlay = value;
+        }
+        if let Some(value) = cvars.get_bool("r.validate_passes") {
+            self.validate_passes = value;
+        }
+    }
+}
+
+impl Default for RenderConstants {
+    fn default() -> Self {
+        RenderConstants::new()
+    }
+}
+
+/// Quality preset of denoising filters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DenoisePreset {
+    Low,
+    Medium,
+    High,
+
+    /// Filters are configured per channel.
+    Custom,
+}
+
+impl DenoisePreset {
+    pub const NAMES: &'static [&'static str] =
+        &["low", "medium", "high", "custom"];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DenoisePreset::Low => "low",
+            DenoisePreset::Medium => "medium",
+            DenoisePreset::High => "high",
+            DenoisePreset::Custom => "custom",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "low" => Some(DenoisePreset::Low),
+            "medium" => Some(DenoisePreset::Medium),
+            "high" => Some(DenoisePreset::High),
+            "custom" => Some(DenoisePreset::Custom),
+            _ => None,
+        }
+    }
+
+    /// Returns direct, diffuse and specular filters of the preset.
+    /// Returns `None` for custom preset.
+    pub fn filters(&self) -> Option<[ATrousConfig; 3]> {
+        match self {
+            DenoisePreset::Low => Some([
+                ATrousConfig::low(),
+                ATrousConfig::low(),
+                ATrousConfig::disabled(),
+            ]),
+            DenoisePreset::Medium => Some([
+                ATrousConfig::medium(),
+                ATrousConfig::medium(),
+                ATrousConfig::low(),
+            ]),
+            DenoisePreset::High => Some([
+                ATrousConfig::high(),
+                ATrousConfig::high(),
+                ATrousConfig::medium(),
+            ]),
+            DenoisePreset::Custom => None,
         }
     }
 }
 
+/// Names of console variables configuring filter of one channel.
+struct FilterCVars {
+    enabled: &'static str,
+    enabled_description: &'static str,
+    iterations: &'static str,
+    sigma_normal: &'static str,
+    sigma_depth: &'static str,
+    sigma_luminance: &'static str,
+}
+
+const DIRECT_FILTER_CVARS: FilterCVars = FilterCVars {
+    enabled: "r.denoise_direct",
+    enabled_description: "Enables denoising of direct radiance",
+    iterations: "r.denoise_direct_iterations",
+    sigma_normal: "r.denoise_direct_sigma_normal",
+    sigma_depth: "r.denoise_direct_sigma_depth",
+    sigma_luminance: "r.denoise_direct_sigma_luminance",
+};
+
+const DIFFUSE_FILTER_CVARS: FilterCVars = FilterCVars {
+    enabled: "r.denoise_diffuse",
+    enabled_description: "Enables denoising of indirect diffuse radiance",
+    iterations: "r.denoise_diffuse_iterations",
+    sigma_normal: "r.denoise_diffuse_sigma_normal",
+    sigma_depth: "r.denoise_diffuse_sigma_depth",
+    sigma_luminance: "r.denoise_diffuse_sigma_luminance",
+};
+
+const SPECULAR_FILTER_CVARS: FilterCVars = FilterCVars {
+    enabled: "r.denoise_specular",
+    enabled_description: "Enables denoising of screen-space reflections",
+    iterations: "r.denoise_specular_iterations",
+    sigma_normal: "r.denoise_specular_sigma_normal",
+    sigma_depth: "r.denoise_specular_sigma_depth",
+    sigma_luminance: "r.denoise_specular_sigma_luminance",
+};
+
+fn register_filter_cvars(
+    cvars: &mut CVars,
+    names: &FilterCVars,
+    config: &ATrousConfig,
+) {
+    cvars.register_bool(
+        names.enabled,
+        config.enabled,
+        names.enabled_description,
+    );
+    cvars.register_int(
+        names.iterations,
+        config.iterations.into(),
+        "Number of A-Trous filter iterations",
+    );
+    cvars.register_float(
+        names.sigma_normal,
+        config.sigma_normal.into(),
+        "Normal edge-stopping exponent",
+    );
+    cvars.register_float(
+        names.sigma_depth,
+        config.sigma_depth.into(),
+        "Relative depth change allowed per pixel",
+    );
+    cvars.register_float(
+        names.sigma_luminance,
+        config.sigma_luminance.into(),
+        "Relative luminance difference allowed",
+    );
+}
+
+fn write_filter_cvars(
+    cvars: &mut CVars,
+    names: &FilterCVars,
+    config: &ATrousConfig,
+) {
+    cvars.set_or_defer(names.enabled, config.enabled.into());
+    cvars.set_or_defer(names.iterations, i64::from(config.iterations).into());
+    cvars.set_or_defer(
+        names.sigma_normal,
+        f64::from(config.sigma_normal).into(),
+    );
+    cvars.set_or_defer(names.sigma_depth, f64::from(config.sigma_depth).into());
+    cvars.set_or_defer(
+        names.sigma_luminance,
+        f64::from(config.sigma_luminance).into(),
+    );
+}
+
+fn read_filter_cvars(
+    cvars: &CVars,
+    names: &FilterCVars,
+    config: &mut ATrousConfig,
+) {
+    if let Some(value) = cvars.get_bool(names.enabled) {
+        config.enabled = value;
+    }
+    if let Some(value) = cvars.get_int(names.iterations) {
+        config.iterations =
+            value.max(1).min(MAX_ATROUS_ITERATIONS.into()) as u32;
+    }
+    if let Some(value) = cvars.get_float(names.sigma_normal) {
+        config.sigma_normal = value as f32;
+    }
+    if let Some(value) = cvars.get_float(names.sigma_depth) {
+        config.sigma_depth = value as f32;
+    }
+    if let Some(value) = cvars.get_float(names.sigma_luminance) {
+        config.sigma_luminance = value as f32;
+    }
+}
+
+/// Extent of images rendered by path tracing pipeline at full scale.
+/// Scaled down by dynamic resolution while `r.dynamic_resolution` is set.
+const VIEW_EXTENT: Extent2d = Extent2d {
+    width: 320,
+    height: 240,
+};
+
+/// Environment variable with directory to write recorded command frames to.
+/// Commands are not recorded if not set.
+const RECORD_COMMANDS_ENV: &str = "WILDS_RECORD_COMMANDS";
+
+/// Number of recorded frames kept for replay.
+const RECORDED_FRAMES: usize = 2;
+
+/// Usage swapchain images must support.
+/// Views are rendered into swapchain images as color attachments.
+const REQUIRED_SWAPCHAIN_USAGE: ImageUsage = ImageUsage::COLOR_ATTACHMENT;
+
+/// Usage enabled for swapchain images when surface supports it.
+const OPTIONAL_SWAPCHAIN_USAGE: ImageUsage = ImageUsage::TRANSFER_DST;
+
+/// Number of attempts to recreate lost surface in one frame.
+const MAX_SURFACE_RECREATIONS: u32 = 3;
+
+/// Number of frames mesh must stay unused before its BLAS may be evicted.
+/// Must exceed number of frames in flight.
+const BLAS_RELEASE_DELAY: u64 = 3;
+
+/// Number of frames unused BLAS is kept while memory is within budget.
+const BLAS_CACHE_FRAMES: u64 = 600;
+
+/// Default fraction of device-local memory renderer tries to stay within.
+pub const DEFAULT_VRAM_BUDGET: f32 = 0.8;
+
 pub struct Renderer {
     context: Context,
     blases: HashMap<Mesh, AccelerationStructure>,
-    swapchain: Swapchain,
+
+    /// Last frame in which each mesh with BLAS was rendered.
+    blases_used: HashMap<Mesh, u64>,
+    frame: u64,
+    vram_budget: f32,
     swapchain_format: Format,
+
+    /// Blue noise kept to re-upload it when device is recreated.
+    blue_noise: BlueNoise,
     blue_noise_buffer_256x256x128: Buffer,
-    pipeline: PathTracePipeline,
+
+    /// Simulates `Water` surfaces before BLASes are built.
+    water: WaterPass,
+
+    /// Incremented each time device is recreated.
+    /// Views created on previous devices are rebuilt when drawn.
+    device_generation: u64,
 }
 
 impl Deref for Renderer {
@@ -78,198 +689,937 @@ impl DerefMut for Renderer {
 }
 
 impl Renderer {
-    pub fn new(window: &Window) -> Result<Self, Report> {
+    /// Creates renderer with device capable of presenting to the window.
+    /// Returns view for that window as well.
+    pub fn new(
+        window: &Arc<Window>,
+        blue_noise: &BlueNoise,
+    ) -> Result<(Self, ViewTarget), Report> {
         let graphics = Graphics::get_or_init()?;
 
         tracing::debug!("{:?}", graphics);
 
         // Create surface for window.
-        let mut surface = graphics.create_surface(window)?;
+        let surface = graphics.create_surface(&**window)?;
 
-        let devices = graphics.devices()?;
+        let (mut context, swapchain_format) = create_context(&surface)?;
 
-        // Find suitable device.
-        let (physical, surface_caps) = devices
-            .into_iter()
-            .filter_map(|d| {
-                let caps = d.surface_capabilities(&surface).ok().flatten()?;
-                Some((d, caps))
-            })
-            .next()
-            .ok_or_else(|| eyre!("No devices found"))?;
-
-        tracing::debug!("{:?}", physical);
-        tracing::debug!("{:?}", surface_caps);
-
-        let device_info = physical.info();
-        tracing::debug!("{:?}", device_info);
-
-        // Initialize device.
-        let (device, queue) = physical.create_device(
-            &[
-                Feature::AccelerationStructure,
-                Feature::RayTracingPipeline,
-                Feature::BufferDeviceAddress,
-                Feature::SurfacePresentation,
-                Feature::RuntimeDescriptorArray,
-                Feature::ScalarBlockLayout,
-                Feature::DescriptorBindingUpdateUnusedWhilePending,
-                Feature::DescriptorBindingPartiallyBound,
-                Feature::ShaderSampledImageDynamicIndexing,
-                Feature::ShaderSampledImageNonUniformIndexing,
-                Feature::ShaderUniformBufferDynamicIndexing,
-                Feature::ShaderUniformBufferNonUniformIndexing,
-                Feature::ShaderStorageBufferDynamicIndexing,
-                Feature::ShaderStorageBufferNonUniformIndexing,
-            ],
-            SingleQueueQuery::GENERAL,
+        let blue_noise_buffer_256x256x128 =
+            create_blue_noise_buffer(&mut context, blue_noise)?;
+        let water = WaterPass::new(&mut context)?;
+
+        let mut renderer = Renderer {
+            blases: HashMap::new(),
+            blases_used: HashMap::new(),
+            frame: 0,
+            vram_budget: DEFAULT_VRAM_BUDGET,
+            swapchain_format,
+            context,
+            blue_noise: blue_noise.clone(),
+            blue_noise_buffer_256x256x128,
+            water,
+            device_generation: 0,
+        };
+
+        let view = renderer.create_view_for_surface(
+            surface,
+            WindowHandle::new(window),
+            VIEW_EXTENT,
         )?;
+        Ok((renderer, view))
+    }
 
-        tracing::debug!("{:?}", device);
+    /// Creates view that renders into the window.
+    /// Window must be presentable by the renderer's device
+    /// using the same surface format as the window renderer
+    /// was created with.
+    pub fn create_view(
+        &mut self,
+        window: &Arc<Window>,
+        extent: Extent2d,
+    ) -> Result<ViewTarget, Report> {
+        let surface = Graphics::get_or_init()?.create_surface(&**window)?;
+        self.create_view_for_surface(surface, WindowHandle::new(window), extent)
+    }
 
-        let swapchain_format = *surface_caps
-            .formats
-            .iter()
-            .filter(|format| {
-                use FormatDescription as FD;
+    fn create_view_for_surface(
+        &mut self,
+        mut surface: Surface,
+        window: WindowHandle,
+        extent: Extent2d,
+    ) -> Result<ViewTarget, Report> {
+        let mut swapchain = self.context.create_swapchain(&mut surface)?;
+        let usage = negotiate_swapchain_usage(&swapchain)?;
+        swapchain.configure(usage, self.swapchain_format, PresentMode::Fifo)?;
 
-                match format.description() {
-                    FD::RGB(_) | FD::RGBA(_) | FD::BGR(_) | FD::BGRA(_) => true,
-                    _ => false,
-                }
-            })
-            .max_by_key(|format| match format.color_type() {
-                Some(FormatType::Srgb) => 1,
-                _ => 0,
-            })
-            .ok_or_else(|| eyre!("No surface format found"))?;
+        let pipeline = PathTracePipeline::new(
+            &mut self.context,
+            self.blue_noise_buffer_256x256x128.clone(),
+            extent,
+        )?;
 
-        tracing::info!("Swapchain format: {:?}", swapchain_format);
+        Ok(ViewTarget::new(
+            window,
+            swapchain,
+            self.swapchain_format,
+            usage,
+            pipeline,
+            extent,
+            self.device_generation,
+        ))
+    }
 
-        let mut context = Context::new(device, queue);
+    /// Recreates swapchain and pipelines of the view
+    /// on current device. Cameras of the splits are kept.
+    fn rebuild_view(
+        &mut self,
+        view: &mut ViewTarget,
+        surface: Surface,
+    ) -> Result<(), Report> {
+        let cameras: SmallVec<[_; MAX_VIEW_SPLITS]> =
+            view.splits.iter().map(|split| split.camera).collect();
+        *view = self.create_view_for_surface(
+            surface,
+            view.window.clone(),
+            view.extent,
+        )?;
+        self.set_view_splits(view, &cameras)
+    }
 
-        let size = window.inner_size();
-        let window_extent = Extent2d {
-            width: size.width,
-            height: size.height,
-        };
+    /// Splits the view between cameras, one split per camera.
+    ///
+    /// Splits are drawn into separate regions of the same swapchain image,
+    /// each with own pipeline and view globals.
+    /// `None` camera means first camera found in the world.
+    pub fn set_view_splits(
+        &mut self,
+        view: &mut ViewTarget,
+        cameras: &[Option<Entity>],
+    ) -> Result<(), Report> {
+        assert!(
+            !cameras.is_empty() && cameras.len() <= MAX_VIEW_SPLITS,
+            "View may have from 1 to {} splits",
+            MAX_VIEW_SPLITS
+        );
 
-        let mut swapchain = context.create_swapchain(&mut surface)?;
-        swapchain.configure(
-            ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_DST,
-            swapchain_format,
+        let extent = split_regions(view.extent, cameras.len())
+            .next()
+            .unwrap()
+            .extent;
+
+        view.splits.truncate(cameras.len());
+        for split in &mut view.splits {
+            split.resolution = DynamicResolution::new(extent);
+        }
+
+        while view.splits.len() < cameras.len() {
+            let pipeline = PathTracePipeline::new(
+                &mut self.context,
+                self.blue_noise_buffer_256x256x128.clone(),
+                extent,
+            )?;
+
+            view.splits.push(ViewSplit {
+                pipeline,
+                resolution: DynamicResolution::new(extent),
+                camera: None,
+            });
+        }
+
+        for (split, &camera) in view.splits.iter_mut().zip(cameras) {
+            split.camera = camera;
+        }
+
+        Ok(())
+    }
+
+    /// Reconfigures swapchain of the view.
+    /// Recreates surface if it was lost.
+    fn reconfigure_view(
+        &mut self,
+        view: &mut ViewTarget,
+    ) -> Result<(), Report> {
+        set_crash_context("renderer.swapchain", "reconfiguring");
+        match view.swapchain.configure(
+            view.usage,
+            view.format,
             PresentMode::Fifo,
-        )?;
+        ) {
+            Ok(()) => Ok(()),
+            Err(SurfaceError::SurfaceLost) => self.recreate_surface(view),
+            Err(err) => Err(err.into()),
+        }
+    }
 
-        let blue_noise_buffer_256x256x128 = load_blue_noise(&mut context)?;
+    /// Recreates lost surface of the view from its window
+    /// and configures new swapchain.
+    /// Surface may be lost when e.g. presenting GPU is switched.
+    fn recreate_surface(
+        &mut self,
+        view: &mut ViewTarget,
+    ) -> Result<(), Report> {
+        tracing::warn!("Surface lost. Recreating");
+        set_crash_context("renderer.swapchain", "recreating surface");
 
-        let pipeline = PathTracePipeline::new(
-            &mut context,
-            blue_noise_buffer_256x256x128.clone(),
-            Extent2d {
-                width: 320,
-                height: 240,
-            },
-        )?;
+        // Old swapchain images may still be in use.
+        self.context.queue.wait_for_idle()?;
 
-        Ok(Renderer {
-            blases: HashMap::new(),
-            swapchain,
-            swapchain_format,
-            context,
-            blue_noise_buffer_256x256x128,
-            pipeline,
-        })
+        let mut surface =
+            Graphics::get_or_init()?.create_surface(&view.window)?;
+        let mut swapchain = self.context.create_swapchain(&mut surface)?;
+        let usage = negotiate_swapchain_usage(&swapchain)?;
+        swapchain.configure(usage, view.format, PresentMode::Fifo)?;
+        view.swapchain = swapchain;
+        view.usage = usage;
+        Ok(())
     }
 
-    pub fn draw(
+    /// Recreates device after it was lost.
+    ///
+    /// Context with all its resources is replaced
+    /// and the view gets new swapchain and pipelines
+    /// with all their passes.
+    /// Other views of the renderer are rebuilt the same way
+    /// when they are drawn next.
+    /// Components that hold device resources are rebuilt:
+    /// `Water` surfaces create their resources again,
+    /// meshes of renderables and `RtLod` proxies are uploaded again
+    /// from retained CPU-side data and `PoseMesh`es are recreated.
+    /// Renderables that can't be restored, because their mesh data
+    /// was not retained or their material has textures,
+    /// are removed from entities. Those entities are returned
+    /// so their prefabs may be reloaded.
+    pub fn recover(
+        &mut self,
+        view: &mut ViewTarget,
+        world: &mut World,
+    ) -> Result<Vec<Entity>, Report> {
+        tracing::warn!("Device lost. Recreating");
+        set_crash_context("renderer.device", "recovering");
+
+        self.blases.clear();
+        self.blases_used.clear();
+
+        let surface = Graphics::get_or_init()?.create_surface(&view.window)?;
+        let (mut context, swapchain_format) = create_context(&surface)?;
+
+        self.blue_noise_buffer_256x256x128 =
+            create_blue_noise_buffer(&mut context, &self.blue_noise)?;
+        self.water = WaterPass::new(&mut context)?;
+        self.context = context;
+        self.swapchain_format = swapchain_format;
+        self.device_generation += 1;
+
+        self.rebuild_view(view, surface)?;
+
+        for (_, (water, renderable)) in
+            world.query::<(&Water, Option<&mut Renderable>)>().iter()
+        {
+            water.recreate(&mut self.context)?;
+
+            if let Some(renderable) = renderable {
+                renderable.mesh = water.mesh();
+                renderable.material = water.material();
+            }
+        }
+
+        let mut rebuilt = HashMap::new();
+        let mut lost = Vec::new();
+
+        for (entity, renderable) in
+            world.query::<&mut Renderable>().without::<Water>().iter()
+        {
+            if renderable.material.has_textures() {
+                lost.push(entity);
+                continue;
+            }
+
+            match rebuild_mesh(
+                &mut rebuilt,
+                &renderable.mesh,
+                &mut self.context,
+            )? {
+                Some(mesh) => renderable.mesh = mesh,
+                None => lost.push(entity),
+            }
+        }
+
+        for &entity in &lost {
+            let _ = world.remove_one::<Renderable>(entity);
+        }
+
+        // Proxies that can't be rebuilt are dropped,
+        // full meshes are traced instead.
+        for (_, rt_lod) in world.query::<&mut RtLod>().iter() {
+            if let Some(proxy) = &rt_lod.proxy {
+                rt_lod.proxy =
+                    rebuild_mesh(&mut rebuilt, proxy, &mut self.context)?;
+            }
+        }
+
+        let bump = Bump::new();
+        for (_, (pose_mesh, renderable)) in
+            world.query::<(&mut PoseMesh, &Renderable)>().iter()
+        {
+            *pose_mesh = PoseMesh::new(&renderable.mesh, &self.context, &bump)?;
+        }
+
+        let lost_poses: Vec<_> = world
+            .query::<&PoseMesh>()
+            .without::<Renderable>()
+            .iter()
+            .map(|(entity, _)| entity)
+            .collect();
+
+        for entity in lost_poses {
+            let _ = world.remove_one::<PoseMesh>(entity);
+        }
+
+        if !lost.is_empty() {
+            tracing::warn!(
+                "{} renderables can't be restored on new device",
+                lost.len()
+            );
+        }
+
+        Ok(lost)
+    }
+
+    /// Sets fraction of device-local memory renderer tries to stay within.
+    /// Least recently used cached BLASes are evicted when it is exceeded.
+    pub fn set_vram_budget(&mut self, budget: f32) {
+        self.vram_budget = budget;
+    }
+
+    /// Prepares frame shared by all views.
+    /// Must be called once per frame before views are drawn.
+    pub fn begin_frame(
         &mut self,
         world: &mut World,
-        resources: &TypeMap,
-        _clock: &ClockIndex,
         bump: &Bump,
     ) -> Result<(), Report> {
-        const DEFAULT_CONSTANTS: RenderConstants = RenderConstants::new();
-
-        let constants = resources
-            .get::<RenderConstants>()
-            .unwrap_or(&DEFAULT_CONSTANTS);
+        // Recycles transient command buffers of the frame
+        // that used the same pool.
+        set_crash_context("renderer.pass", "begin_frame");
+        self.context.queue.begin_frame()?;
+        self.context.begin_views_frame();
+        self.context.begin_sync_frame()?;
 
+        self.context.update_object_table(world)?;
         self.context.flush_uploads(bump)?;
 
+        // Water meshes are displaced before their BLASes are rebuilt.
+        set_crash_context("renderer.pass", "water");
+        self.water.draw(
+            (),
+            self.frame,
+            &[],
+            &[],
+            None,
+            &mut self.context,
+            world,
+            bump,
+        )?;
+
         tracing::debug!("Rendering next frame");
 
         let mut encoder = None;
+        self.frame += 1;
 
         // Create BLASes for new meshes.
-        for (_, renderable) in
-            world.query::<&Renderable>().with::<Global3>().iter()
+        // Only meshes selected by `RtLod` need BLAS.
+        // Displaced water meshes are rebuilt every frame.
+        for (_, (renderable, rt_lod, water)) in world
+            .query::<(&Renderable, Option<&RtLod>, Option<&Water>)>()
+            .with::<Global3>()
+            .without::<RasterOnly>()
+            .iter()
         {
-            match self.blases.entry(renderable.mesh.clone()) {
-                Entry::Vacant(entry) => {
-                    let blas = renderable.mesh.build_triangles_blas(
-                        match &mut encoder {
-                            Some(encoder) => encoder,
-                            slot => {
-                                *slot =
-                                    Some(self.context.queue.create_encoder()?);
-                                slot.as_mut().unwrap()
-                            }
-                        },
-                        &self.context.device,
-                        bump,
-                    )?;
-
-                    entry.insert(blas);
-                }
-                Entry::Occupied(_entry) => {}
+            let mesh = match rt_lod {
+                Some(rt_lod) => match rt_lod.select(&renderable.mesh) {
+                    Some(mesh) => mesh,
+                    None => continue,
+                },
+                None => &renderable.mesh,
             };
+
+            self.blases_used.insert(mesh.clone(), self.frame);
+
+            let displaced = water.is_some() && *mesh == renderable.mesh;
+
+            if displaced || !self.blases.contains_key(mesh) {
+                let blas = mesh.build_triangles_blas(
+                    match &mut encoder {
+                        Some(encoder) => encoder,
+                        slot => {
+                            *slot = Some(self.context.queue.create_encoder()?);
+                            slot.as_mut().unwrap()
+                        }
+                    },
+                    &self.context.device,
+                    bump,
+                )?;
+
+                // Replaced BLAS is destroyed once frames using it
+                // are complete.
+                self.blases.insert(mesh.clone(), blas);
+            }
         }
 
         tracing::trace!("BLASes created");
 
+        self.evict_blases();
+
         if let Some(encoder) = encoder {
             self.context
                 .queue
-                .submit_no_semaphores(encoder.finish(), None);
+                .submit_no_semaphores(encoder.finish()?, None)?;
         }
 
+        Ok(())
+    }
+
+    /// Drops BLASes of meshes that are no longer rendered.
+    ///
+    /// Unused BLASes are cached for a while in case their meshes are
+    /// rendered again. Least recently used ones are evicted first when
+    /// memory usage exceeds the budget.
+    /// Dropped BLASes and their buffers are destroyed by device
+    /// once frames that could use them are complete.
+    fn evict_blases(&mut self) {
+        let frame = self.frame;
+        let stats = self.context.device.memory_stats();
+        let budget =
+            (stats.device_local as f64 * f64::from(self.vram_budget)) as u64;
+        let mut excess = stats.allocated.saturating_sub(budget);
+
+        let mut unused: Vec<_> = self
+            .blases_used
+            .iter()
+            .filter(|(_, &last)| frame - last > BLAS_RELEASE_DELAY)
+            .map(|(mesh, &last)| (last, mesh.clone()))
+            .collect();
+
+        unused.sort_by_key(|(last, _)| *last);
+
+        for (last, mesh) in unused {
+            if excess == 0 && frame - last <= BLAS_CACHE_FRAMES {
+                break;
+            }
+
+            self.blases_used.remove(&mesh);
+            if let Some(blas) = self.blases.remove(&mesh) {
+                excess = excess.saturating_sub(blas.info().region.size);
+            }
+        }
+    }
+
+    /// Renders world into the view and presents result.
+    ///
+    /// `TextOverlay` resource is resized to the view
+    /// and cleared after drawing.
+    /// `FrameGraphStats` resource is updated while `r.debug_overlay` is set.
+    /// `PixelInspector` resource receives values of selected pixel
+    /// if pipeline supports inspection.
+    /// `Accumulation` resource receives sample count and its capture
+    /// request is passed to pipeline.
+    /// Camera of `PhotoMode` replaces camera of the first split
+    /// and its shot is drawn tile by tile with accumulation.
+    /// View created on device lost since is rebuilt first.
+    pub fn draw_view(
+        &mut self,
+        view: &mut ViewTarget,
+        world: &mut World,
+        resources: &mut TypeMap,
+        clock: &ClockIndex,
+        bump: &Bump,
+    ) -> Result<(), Report> {
+        if view.device_generation != self.device_generation {
+            tracing::warn!("View was created on lost device. Recreating");
+            let surface =
+                Graphics::get_or_init()?.create_surface(&view.window)?;
+            self.rebuild_view(view, surface)?;
+        }
+
+        let mut constants = resources
+            .get::<RenderConstants>()
+            .cloned()
+            .unwrap_or_default();
+
+        if let Some(cvars) = resources.get::<CVars>() {
+            constants.read_cvars(cvars);
+        }
+
+        let (photo_camera, shooting) = match resources.get::<PhotoMode>() {
+            Some(photo) => (photo.camera, photo.shot.is_some()),
+            None => (None, false),
+        };
+
+        if photo_camera.is_some() {
+            constants.depth_of_field = true;
+        }
+
+        // Tiles must have the same extent and converge before readback.
+        if shooting {
+            constants.accumulate = true;
+            constants.dynamic_resolution = false;
+        }
+
+        let mut cameras = SmallVec::<[_; MAX_VIEW_SPLITS]>::new();
+        for (index, split) in view.splits.iter_mut().enumerate() {
+            let camera = match photo_camera {
+                Some(camera) if index == 0 => Some(camera),
+                _ => split.camera,
+            };
+
+            match find_camera(world, camera) {
+                Some(camera) => cameras.push(camera),
+                None => {
+                    tracing::warn!("No camera found");
+                    return Ok(());
+                }
+            }
+
+            if !split.pipeline.is_ready(&mut self.context)? {
+                tracing::trace!("View pipeline is not ready yet");
+                return Ok(());
+            }
+        }
+
+        // Swapchain is not configured while window is minimized.
+        // Capabilities are queried again until surface is restored.
+        if !view.swapchain.is_configured() {
+            self.reconfigure_view(view)?;
+            if !view.swapchain.is_configured() {
+                tracing::trace!("View surface has zero extent");
+                return Ok(());
+            }
+        }
+
+        set_crash_context("renderer.swapchain", "acquiring");
+        let mut surface_recreations = 0;
         let frame = loop {
-            if let Some(frame) = self.swapchain.acquire_image()? {
-                break frame;
+            match view.swapchain.acquire_image() {
+                Ok(Some(frame)) => break frame,
+                Ok(None) => {
+                    self.reconfigure_view(view)?;
+                    if !view.swapchain.is_configured() {
+                        tracing::trace!("View surface has zero extent");
+                        return Ok(());
+                    }
+                }
+                Err(SurfaceError::SurfaceLost)
+                    if surface_recreations < MAX_SURFACE_RECREATIONS =>
+                {
+                    surface_recreations += 1;
+                    self.recreate_surface(view)?;
+                }
+                Err(err) => return Err(err.into()),
             }
-            self.swapchain.configure(
-                ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_DST,
-                self.swapchain_format,
-                PresentMode::Fifo,
-            )?;
         };
 
-        self.pipeline.draw(
-            frame.info().image.clone(),
-            &frame.info().wait,
-            &frame.info().signal,
-            &self.blases,
-            &mut self.context,
-            world,
-            bump,
+        if let Some(overlay) = resources.get_mut::<TextOverlay>() {
+            overlay.resize(frame.info().image.info().extent.into_2d());
+        }
+
+        self.context.set_pass_validation(constants.validate_passes);
+
+        // Dynamic resolution is driven by profiled GPU time.
+        self.context.begin_profiled_frame(
+            constants.debug_overlay || constants.dynamic_resolution,
         )?;
 
+        // Splits draw into own regions of the image one after another.
+        let swapchain_frame = SwapchainFrame::new(frame.info());
+        let regions: SmallVec<[_; MAX_VIEW_SPLITS]> =
+            split_regions(swapchain_frame.extent(), view.splits.len())
+                .collect();
+        let semaphores = (1..regions.len())
+            .map(|_| self.context.pooled_semaphore())
+            .collect::<Result<SmallVec<[_; MAX_VIEW_SPLITS]>, _>>()?;
+        let targets = swapchain_frame.split(&regions, &semaphores);
+
+        for (index, (split, target)) in
+            view.splits.iter_mut().zip(&targets).enumerate()
+        {
+            if !constants.dynamic_resolution {
+                split.resolution.reset();
+            }
+            split.pipeline.set_extent(split.resolution.extent());
+
+            // Inspection and accumulation follow the first split.
+            if index == 0 {
+                if let Some(inspector) = resources.get_mut::<PixelInspector>() {
+                    inspector.sample =
+                        split.pipeline.inspect_pixel(inspector.pixel);
+                }
+
+                if let Some(accumulation) = resources.get_mut::<Accumulation>()
+                {
+                    accumulation.samples = split
+                        .pipeline
+                        .accumulation(accumulation.capture.take());
+                }
+
+                if let Some(photo) = resources.get_mut::<PhotoMode>() {
+                    if let Some(shot) = &mut photo.shot {
+                        let projection = cameras[0].0.projection();
+                        match advance_shot(
+                            shot,
+                            &mut split.pipeline,
+                            &projection,
+                        ) {
+                            Ok(Some(projection)) => {
+                                cameras[0].0 = Camera::Matrix(projection);
+                            }
+                            Ok(None) => photo.shot = None,
+                            Err(err) => {
+                                tracing::error!("Photo shot failed: {:#}", err);
+                                photo.shot = None;
+                            }
+                        }
+                    }
+                }
+            }
+
+            let (camera, camera_global, camera_settings) = &cameras[index];
+
+            split.pipeline.draw(
+                target,
+                camera,
+                camera_global,
+                camera_settings.as_ref(),
+                &constants,
+                clock,
+                resources.get::<TextOverlay>(),
+                &self.blases,
+                &mut self.context,
+                world,
+                bump,
+            )?;
+        }
+
+        if let Some(overlay) = resources.get_mut::<TextOverlay>() {
+            overlay.clear();
+        }
+
+        if let Some(stats) = self.context.end_profiled_frame() {
+            if constants.dynamic_resolution {
+                let target = Duration::from_secs_f32(
+                    constants.target_frame_time.max(0.0) / 1000.0,
+                );
+                for split in &mut view.splits {
+                    split.resolution.update(
+                        &stats,
+                        target,
+                        constants.min_resolution_scale,
+                    );
+                }
+            }
+            resources.insert(stats);
+        }
+
         tracing::trace!("Presenting");
+        set_crash_context("renderer.pass", "present");
         match self.queue.present(frame) {
             Ok(PresentOk::Suboptimal) | Err(PresentError::OutOfDate) => {
-                self.swapchain.configure(
-                    ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_DST,
-                    self.swapchain_format,
-                    PresentMode::Fifo,
-                )?;
+                set_crash_context("renderer.swapchain", "out of date");
+                self.reconfigure_view(view)?;
             }
-            Ok(_) => {}
+            Err(PresentError::SurfaceLost) => self.recreate_surface(view)?,
+            Ok(_) => set_crash_context("renderer.swapchain", "presented"),
             Err(err) => return Err(err.into()),
         };
 
         Ok(())
     }
+
+    /// Destroys renderer and its views.
+    ///
+    /// Waits for frames in flight, destroys swapchains and surfaces
+    /// of the views and then all resources dropped by the renderer,
+    /// so it must be called before exit instead of dropping the renderer.
+    pub fn shutdown(
+        self,
+        views: impl IntoIterator<Item = ViewTarget>,
+    ) -> Result<(), Report> {
+        tracing::info!("Shutting down renderer");
+        set_crash_context("renderer.device", "shutting down");
+
+        let Renderer {
+            mut context,
+            blases,
+            blases_used,
+            blue_noise_buffer_256x256x128,
+            water,
+            ..
+        } = self;
+
+        context.queue.finish_frames()?;
+
+        let graphics = Graphics::get_or_init()?;
+        for view in views {
+            let ViewTarget {
+                swapchain, splits, ..
+            } = view;
+
+            drop(splits);
+            graphics.destroy_surface(swapchain.destroy())?;
+        }
+
+        drop((blases, blases_used, blue_noise_buffer_256x256x128, water));
+
+        context.shutdown()?;
+        Ok(())
+    }
+
+    /// Begins frame and draws single view.
+    pub fn draw(
+        &mut self,
+        view: &mut ViewTarget,
+        world: &mut World,
+        resources: &mut TypeMap,
+        clock: &ClockIndex,
+        bump: &Bump,
+    ) -> Result<(), Report> {
+        self.begin_frame(world, bump)?;
+        self.draw_view(view, world, resources, clock, bump)
+    }
+}
+
+/// Returns `true` if error returned by renderer is caused by lost device.
+/// Renderer can't continue after that until `Renderer::recover` is called.
+pub fn is_device_lost(err: &Report) -> bool {
+    err.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref::<WaitError>(),
+            Some(WaitError::DeviceLost)
+        ) || matches!(
+            cause.downcast_ref::<PresentError>(),
+            Some(PresentError::DeviceLost)
+        ) || matches!(
+            cause.downcast_ref::<EncodeError>(),
+            Some(EncodeError::DeviceLost)
+        )
+    })
+}
+
+/// Creates device capable of presenting to the surface
+/// and context over it.
+/// Returns swapchain format picked for the surface as well.
+fn create_context(surface: &Surface) -> Result<(Context, Format), Report> {
+    let devices = Graphics::get_or_init()?.devices()?;
+
+    // Find suitable device.
+    let (physical, surface_caps) = devices
+        .into_iter()
+        .filter_map(|d| {
+            let caps = d.surface_capabilities(surface).ok().flatten()?;
+            Some((d, caps))
+        })
+        .next()
+        .ok_or_else(|| eyre!("No devices found"))?;
+
+    tracing::debug!("{:?}", physical);
+    tracing::debug!("{:?}", surface_caps);
+
+    let device_info = physical.info();
+    tracing::debug!("{:?}", device_info);
+    tracing::info!(
+        "Device '{}' with API version {}, capabilities: {:?}",
+        device_info.name,
+        device_info.api_version,
+        device_info.capabilities,
+    );
+
+    let mut features = vec![
+        Feature::AccelerationStructure,
+        Feature::RayTracingPipeline,
+        Feature::BufferDeviceAddress,
+        Feature::SurfacePresentation,
+        Feature::RuntimeDescriptorArray,
+        Feature::ScalarBlockLayout,
+        Feature::DescriptorBindingUpdateUnusedWhilePending,
+        Feature::DescriptorBindingPartiallyBound,
+        Feature::DescriptorBindingStorageBufferUpdateAfterBind,
+        Feature::ShaderSampledImageDynamicIndexing,
+        Feature::ShaderSampledImageNonUniformIndexing,
+        Feature::ShaderUniformBufferDynamicIndexing,
+        Feature::ShaderUniformBufferNonUniformIndexing,
+        Feature::ShaderStorageBufferDynamicIndexing,
+        Feature::ShaderStorageBufferNonUniformIndexing,
+    ];
+
+    // Lets capture tools replay ray-tracing buffers at captured addresses.
+    if device_info
+        .features
+        .contains(&Feature::BufferDeviceAddressCaptureReplay)
+    {
+        features.push(Feature::BufferDeviceAddressCaptureReplay);
+    }
+
+    // Lets hit groups be compiled separately and linked.
+    if device_info
+        .features
+        .contains(&Feature::RayTracingPipelineLibrary)
+    {
+        features.push(Feature::RayTracingPipelineLibrary);
+    }
+
+    // Lets raster pipelines switch culling and depth test per draw.
+    if device_info
+        .features
+        .contains(&Feature::ExtendedDynamicState)
+    {
+        features.push(Feature::ExtendedDynamicState);
+    }
+
+    // Lets barriers synchronize copies apart from other transfers.
+    if device_info.features.contains(&Feature::Synchronization2) {
+        features.push(Feature::Synchronization2);
+    }
+
+    // Initialize device.
+    let (device, mut queue) =
+        physical.create_device(&features, SingleQueueQuery::GENERAL)?;
+
+    tracing::debug!("{:?}", device);
+
+    let swapchain_format = *surface_caps
+        .formats
+        .iter()
+        .filter(|format| {
+            use FormatDescription as FD;
+
+            match format.description() {
+                FD::RGB(_) | FD::RGBA(_) | FD::BGR(_) | FD::BGRA(_) => true,
+                _ => false,
+            }
+        })
+        .max_by_key(|format| match format.color_type() {
+            Some(FormatType::Srgb) => 1,
+            _ => 0,
+        })
+        .ok_or_else(|| eyre!("No surface format found"))?;
+
+    tracing::info!("Swapchain format: {:?}", swapchain_format);
+
+    if let Ok(directory) = std::env::var(RECORD_COMMANDS_ENV) {
+        tracing::info!("Recording commands into '{}'", directory);
+        queue.set_recorder(Some(
+            CommandRecorder::new(RECORDED_FRAMES)
+                .with_directory(directory)
+                .with_validation(),
+        ));
+    }
+
+    Ok((Context::new(device, queue, features)?, swapchain_format))
+}
+
+/// Returns usage for swapchain images supported by the surface.
+/// Optional usage is dropped when unsupported.
+fn negotiate_swapchain_usage(
+    swapchain: &Swapchain,
+) -> Result<ImageUsage, SurfaceError> {
+    let supported = swapchain.supported_usage()?;
+
+    if !supported.contains(REQUIRED_SWAPCHAIN_USAGE) {
+        return Err(SurfaceError::UsageNotSupported {
+            usage: REQUIRED_SWAPCHAIN_USAGE,
+        });
+    }
+
+    let usage =
+        REQUIRED_SWAPCHAIN_USAGE | (OPTIONAL_SWAPCHAIN_USAGE & supported);
+
+    if usage != REQUIRED_SWAPCHAIN_USAGE | OPTIONAL_SWAPCHAIN_USAGE {
+        tracing::warn!("Swapchain images usage is reduced to {:?}", usage);
+    }
+
+    Ok(usage)
+}
+
+/// Uploads retained data of the mesh into new buffers
+/// once for all components that share it.
+fn rebuild_mesh(
+    rebuilt: &mut HashMap<Mesh, Mesh>,
+    mesh: &Mesh,
+    ctx: &mut Context,
+) -> Result<Option<Mesh>, OutOfMemory> {
+    if let Some(mesh) = rebuilt.get(mesh) {
+        return Ok(Some(mesh.clone()));
+    }
+
+    let new = mesh.rebuild(ctx)?;
+    if let Some(new) = &new {
+        rebuilt.insert(mesh.clone(), new.clone());
+    }
+    Ok(new)
+}
+
+/// Advances tiled photo shot drawn by the pipeline.
+/// Returns projection of the tile to draw next
+/// or `None` once photo is written.
+fn advance_shot(
+    shot: &mut PhotoShot,
+    pipeline: &mut dyn Pipeline,
+    projection: &na::Projective3<f32>,
+) -> Result<Option<na::Projective3<f32>>, Report> {
+    if let Some((extent, texels)) = pipeline.take_readback() {
+        if shot.add_tile(extent, &texels)? {
+            shot.write()?;
+            tracing::info!("Photo written into {}", shot.path.display());
+            return Ok(None);
+        }
+    }
+
+    // Samples reported by pipeline belong to previous tile
+    // until current one is drawn at least once.
+    if shot.frames > 0 && !shot.requested {
+        match pipeline.accumulation(None) {
+            Some(samples) if samples >= shot.samples => {
+                pipeline.request_readback();
+                shot.requested = true;
+            }
+            Some(_) => {}
+            None => return Err(eyre!("Pipeline doesn't accumulate samples")),
+        }
+    }
+
+    shot.frames += 1;
+    Ok(Some(shot.tile_projection(projection)))
+}
+
+/// Returns camera of the view.
+/// Falls back to first camera in the world if entity is not specified.
+fn find_camera(
+    world: &World,
+    entity: Option<Entity>,
+) -> Option<(Camera, Global3, Option<CameraSettings>)> {
+    type Query<'a> = (
+        &'a Camera,
+        &'a Global3,
+        Option<&'a CameraSettings>,
+        Option<&'a CameraRig>,
+    );
+
+    let (camera, global, settings, rig) = match entity {
+        Some(entity) => {
+            let mut query = world.query_one::<Query<'_>>(entity).ok()?;
+            let (camera, global, settings, rig) = query.get()?;
+            (*camera, *global, settings.copied(), rig.copied())
+        }
+        None => {
+            let mut query = world.query::<Query<'_>>();
+            let (_, (camera, global, settings, rig)) = query.iter().next()?;
+            (*camera, *global, settings.copied(), rig.copied())
+        }
+    };
+
+    // Rigged cameras are rendered from rig pose.
+    let global = rig.and_then(|rig| rig.pose().copied()).unwrap_or(global);
+
+    Some((camera, global, settings))
 }
 
 fn ray_tracing_transform_matrix_from_nalgebra(
@@ -293,107 +1643,28 @@ fn ray_tracing_transform_matrix_from_nalgebra(
     }
 }
 
-fn load_blue_noise(ctx: &mut Context) -> Result<Buffer, OutOfMemory> {
-    let blue_noise = include_bytes!("../../blue_noise/RGBAF32_256x256x128");
+/// Uploads blue noise into storage buffer.
+/// Shaders expect RGBA noise of 256x256 size with 128 layers.
+fn create_blue_noise_buffer(
+    ctx: &mut Context,
+    blue_noise: &BlueNoise,
+) -> Result<Buffer, Report> {
+    if blue_noise.width() != 256
+        || blue_noise.height() != 256
+        || blue_noise.layers() != 128
+    {
+        return Err(eyre!(
+            "Blue noise of 256x256x128 size expected, found {}x{}x{}",
+            blue_noise.width(),
+            blue_noise.height(),
+            blue_noise.layers(),
+        ));
+    }
 
-    ctx.create_buffer_static(
-        BufferInfo {
-            size: blue_noise.len() as _,
-            align: 255,
-            usage: BufferUsage::STORAGE,
-        },
-        &blue_noise[..],
+    ctx.create_canvas_buffer(
+        &blue_noise.to_canvases(),
+        Format::RGBA32Sfloat,
+        255,
+        BufferUsage::STORAGE,
     )
-    .map(Into::into)
 }
-
-// fn load_blue_noise(ctx: &mut Context) -> Result<Buffer, OutOfMemory> {
-//     use std::{convert::TryFrom as _, mem::size_of_val};
-
-//     const KERNAL_DIM: usize = 7;
-
-//     const KERNEL: [[f32; KERNAL_DIM]; KERNAL_DIM] = [
-//         [
-//             0.000036, 0.000363, 0.001446, 0.002291, 0.001446, 0.000363,
-//             0.000036,
-//         ],
-//         [
-//             0.000363, 0.003676, 0.014662, 0.023226, 0.014662, 0.003676,
-//             0.000363,
-//         ],
-//         [
-//             0.001446, 0.014662, 0.058488, 0.092651, 0.058488, 0.014662,
-//             0.001446,
-//         ],
-//         [
-//             0.002291, 0.023226, 0.092651, 0.146768, 0.092651, 0.023226,
-//             0.002291,
-//         ],
-//         [
-//             0.001446, 0.014662, 0.058488, 0.092651, 0.058488, 0.014662,
-//             0.001446,
-//         ],
-//         [
-//             0.000363, 0.003676, 0.014662, 0.023226, 0.014662, 0.003676,
-//             0.000363,
-//         ],
-//         [
-//             0.000036, 0.000363, 0.001446, 0.002291, 0.001446, 0.000363,
-//             0.000036,
-//         ],
-//     ];
-
-//     const DIM: usize = 256;
-//     const COUNT: usize = DIM * DIM;
-//     const STEP: f32 = 1.0 / COUNT as f32;
-
-//     let mut data = [[[0f32; 4]; DIM]; DIM];
-
-//     for c in 0..4 {
-//         for s in 1..COUNT {
-//             // Next value to put.
-//             let v = s as f32 * STEP;
-
-//             let mut lx = 0;
-//             let mut ly = 0;
-//             let mut lb = 1.0;
-
-//             // Search over all pixels.
-//             for x in 0..DIM {
-//                 for y in 0..DIM {
-//                     if data[x][y][c] > 0.0 {
-//                         continue;
-//                     }
-
-//                     let mut b = 0.0;
-
-//                     for wx in 0..=6 {
-//                         for wy in 0..=6 {
-//                             b += data[(x + wx - 3) % DIM][(y + wy - 3) % DIM]
-//                                 [c]
-//                                 * KERNEL[wx][wy];
-//                         }
-//                     }
-
-//                     if lb > b {
-//                         lb = b;
-//                         lx = x;
-//                         ly = y;
-//                     }
-//                 }
-//             }
-
-//             data[lx][ly][c] = v;
-//         }
-//     }
-
-//     ctx.create_buffer_static(
-//         BufferInfo {
-//             size: u64::try_from(size_of_val(&data)).unwrap(),
-//             align: 255,
-//             usage: BufferUsage::STORAGE,
-//
-//         },
-//         &data,
-//     )
-// }
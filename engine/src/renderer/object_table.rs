@@ -0,0 +1,345 @@
+use {
+    super::{Material, Mesh, Renderable},
+    crate::scene::{is_changed, track_changes, Changed, Generation, Global3},
+    bytemuck::{Pod, Zeroable},
+    eyre::{ensure, Report},
+    hecs::{Entity, World},
+    illume::{
+        Buffer, BufferInfo, BufferUsage, DescriptorBindingFlags, DescriptorSet,
+        DescriptorSetInfo, DescriptorSetLayout, DescriptorSetLayoutBinding,
+        DescriptorSetLayoutFlags, DescriptorSetLayoutInfo, DescriptorType,
+        Descriptors, Device, ShaderStageFlags, WriteDescriptorSet,
+    },
+    nalgebra as na,
+    std::{collections::HashMap, mem::size_of},
+};
+
+/// Maximum number of renderables in object table.
+pub const MAX_OBJECTS: u32 = 32768;
+
+/// Per-renderable data as seen by shaders.
+/// Matches `Object` struct in `pass/common/objects.glsl`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct ShaderObject {
+    pub transform: na::Matrix4<f32>,
+
+    /// Transform at previous frame.
+    pub prev_transform: na::Matrix4<f32>,
+
+    /// Device addresses of first vertex binding and indices of the mesh.
+    /// Zero if buffer has no device address.
+    pub vertices: u64,
+    pub indices: u64,
+
+    /// Persistent index of the material.
+    pub material: u32,
+    pub _pad: [u32; 3],
+}
+
+unsafe impl Zeroable for ShaderObject {}
+unsafe impl Pod for ShaderObject {}
+
+/// Element of the table occupied by a renderable.
+struct Slot {
+    index: u32,
+    mesh: Mesh,
+    material: Material,
+    transform: na::Matrix4<f32>,
+
+    /// Previous transform differs from current one,
+    /// so element must be written again next frame.
+    moving: bool,
+    frame: u64,
+}
+
+/// Registered material with number of slots referencing it.
+struct MaterialEntry {
+    index: u32,
+    refs: u32,
+}
+
+/// GPU table with one element per renderable.
+///
+/// Each renderable keeps its element while it exists,
+/// so shaders may address it with persistent index
+/// instead of passes writing per-draw data.
+/// Only elements of new, moved or modified renderables
+/// are uploaded each frame.
+pub struct ObjectTable {
+    buffer: Buffer,
+    layout: DescriptorSetLayout,
+    set: DescriptorSet,
+    slots: HashMap<Entity, Slot>,
+    free: Vec<u32>,
+    next: u32,
+    materials: HashMap<Material, MaterialEntry>,
+    free_materials: Vec<u32>,
+    next_material: u32,
+    generation: Generation,
+    frame: u64,
+}
+
+impl ObjectTable {
+    pub fn new(device: &Device) -> Result<Self, Report> {
+        let layout =
+            device.create_descriptor_set_layout(DescriptorSetLayoutInfo {
+                flags: DescriptorSetLayoutFlags::empty(),
+                bindings: vec![DescriptorSetLayoutBinding {
+                    binding: 0,
+                    ty: DescriptorType::StorageBuffer,
+                    count: 1,
+                    stages: ShaderStageFlags::VERTEX
+                        | ShaderStageFlags::FRAGMENT
+                        | ShaderStageFlags::COMPUTE
+                        | ShaderStageFlags::CLOSEST_HIT
+                        | ShaderStageFlags::ANY_HIT,
+                    flags: DescriptorBindingFlags::empty(),
+                }],
+            })?;
+
+        let size = size_of::<ShaderObject>() as u64 * u64::from(MAX_OBJECTS);
+        let buffer = device.create_buffer(BufferInfo {
+            align: 255,
+            size,
+            usage: BufferUsage::STORAGE | BufferUsage::TRANSFER_DST,
+            capture_address: None,
+        })?;
+
+        let set = device.create_descriptor_set(DescriptorSetInfo {
+            layout: layout.clone(),
+        })?;
+
+        device.update_descriptor_sets(
+            &[WriteDescriptorSet {
+                set: &set,
+                binding: 0,
+                element: 0,
+                descriptors: Descriptors::StorageBuffer(&[(
+                    buffer.clone(),
+                    0,
+                    size,
+                )]),
+            }],
+            &[],
+        );
+
+        Ok(ObjectTable {
+            buffer,
+            layout,
+            set,
+            slots: HashMap::new(),
+            free: Vec::new(),
+            next: 0,
+            materials: HashMap::new(),
+            free_materials: Vec::new(),
+            next_material: 0,
+            generation: Generation::ZERO,
+            frame: 0,
+        })
+    }
+
+    /// Layout of the set with object table.
+    /// Passes include it into their pipeline layouts.
+    pub fn layout(&self) -> &DescriptorSetLayout {
+        &self.layout
+    }
+
+    /// Set with object table bound as storage buffer.
+    pub fn set(&self) -> &DescriptorSet {
+        &self.set
+    }
+
+    /// Returns index of element of the renderable entity.
+    /// Index stays the same while entity has `Renderable` component.
+    pub fn index(&self, entity: Entity) -> Option<u32> {
+        self.slots.get(&entity).map(|slot| slot.index)
+    }
+
+    /// Returns persistent index of the material
+    /// used by at least one renderable.
+    pub fn material_index(&self, material: &Material) -> Option<u32> {
+        self.materials.get(material).map(|entry| entry.index)
+    }
+
+    /// Assigns elements to new renderables and releases elements
+    /// of removed ones.
+    /// Returns buffer with elements that must be uploaded
+    /// sorted by index.
+    pub(super) fn update(
+        &mut self,
+        world: &mut World,
+        device: &Device,
+    ) -> Result<(Buffer, Vec<(u32, ShaderObject)>), Report> {
+        self.frame += 1;
+        let generation = track_changes::<Global3>(world);
+        let seen = std::mem::replace(&mut self.generation, generation);
+
+        let mut dirty = Vec::new();
+
+        for (entity, (renderable, global, changed)) in world
+            .query::<(&Renderable, &Global3, Option<&Changed<Global3>>)>()
+            .iter()
+        {
+            let transform = global.to_homogeneous();
+
+            let slot = match self.slots.get_mut(&entity) {
+                Some(slot) => slot,
+                None => {
+                    let index = match self.free.pop() {
+                        Some(index) => index,
+                        None => {
+                            ensure!(
+                                self.next < MAX_OBJECTS,
+                                "At most {} renderables are supported",
+                                MAX_OBJECTS,
+                            );
+                            self.next += 1;
+                            self.next - 1
+                        }
+                    };
+
+                    acquire_material(
+                        &mut self.materials,
+                        &mut self.free_materials,
+                        &mut self.next_material,
+                        &renderable.material,
+                    );
+
+                    // New renderable has no previous transform.
+                    let slot = Slot {
+                        index,
+                        mesh: renderable.mesh.clone(),
+                        material: renderable.material.clone(),
+                        transform,
+                        moving: false,
+                        frame: self.frame,
+                    };
+
+                    dirty.push((index, object(&slot, &self.materials, device)));
+                    self.slots.insert(entity, slot);
+                    continue;
+                }
+            };
+
+            slot.frame = self.frame;
+
+            let mut modified = false;
+            if slot.mesh != renderable.mesh {
+                slot.mesh = renderable.mesh.clone();
+                modified = true;
+            }
+
+            if slot.material != renderable.material {
+                acquire_material(
+                    &mut self.materials,
+                    &mut self.free_materials,
+                    &mut self.next_material,
+                    &renderable.material,
+                );
+                release_material(
+                    &mut self.materials,
+                    &mut self.free_materials,
+                    &slot.material,
+                );
+                slot.material = renderable.material.clone();
+                modified = true;
+            }
+
+            let mut object = object(slot, &self.materials, device);
+            if is_changed(changed, seen) {
+                object.transform = transform;
+                slot.transform = transform;
+                slot.moving = true;
+                modified = true;
+            } else if slot.moving {
+                // Stopped this frame, previous transform catches up.
+                slot.moving = false;
+                modified = true;
+            }
+
+            if modified {
+                dirty.push((slot.index, object));
+            }
+        }
+
+        let frame = self.frame;
+        let free = &mut self.free;
+        let materials = &mut self.materials;
+        let free_materials = &mut self.free_materials;
+        self.slots.retain(|_, slot| {
+            if slot.frame == frame {
+                true
+            } else {
+                free.push(slot.index);
+                release_material(materials, free_materials, &slot.material);
+                false
+            }
+        });
+
+        dirty.sort_by_key(|(index, _)| *index);
+        Ok((self.buffer.clone(), dirty))
+    }
+}
+
+/// Returns element for the slot.
+/// Previous transform equals current one.
+fn object(
+    slot: &Slot,
+    materials: &HashMap<Material, MaterialEntry>,
+    device: &Device,
+) -> ShaderObject {
+    let address = |buffer: &Buffer, offset: u64| {
+        device
+            .get_buffer_device_address(buffer)
+            .map_or(0, |address| address.0.get() + offset)
+    };
+
+    ShaderObject {
+        transform: slot.transform,
+        prev_transform: slot.transform,
+        vertices: slot
+            .mesh
+            .bindings()
+            .first()
+            .map_or(0, |binding| address(&binding.buffer, binding.offset)),
+        indices: slot
+            .mesh
+            .indices()
+            .map_or(0, |indices| address(&indices.buffer, indices.offset)),
+        material: materials[&slot.material].index,
+        _pad: [0; 3],
+    }
+}
+
+fn acquire_material(
+    materials: &mut HashMap<Material, MaterialEntry>,
+    free: &mut Vec<u32>,
+    next: &mut u32,
+    material: &Material,
+) {
+    match materials.get_mut(material) {
+        Some(entry) => entry.refs += 1,
+        None => {
+            let index = free.pop().unwrap_or_else(|| {
+                *next += 1;
+                *next - 1
+            });
+            materials
+                .insert(material.clone(), MaterialEntry { index, refs: 1 });
+        }
+    }
+}
+
+fn release_material(
+    materials: &mut HashMap<Material, MaterialEntry>,
+    free: &mut Vec<u32>,
+    material: &Material,
+) {
+    let entry = materials.get_mut(material).unwrap();
+    entry.refs -= 1;
+    if entry.refs == 0 {
+        free.push(entry.index);
+        materials.remove(material);
+    }
+}
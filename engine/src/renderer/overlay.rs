@@ -0,0 +1,146 @@
+use {
+    super::Extent2d,
+    font8x8::{UnicodeFonts as _, BASIC_FONTS},
+};
+
+/// Size of glyph in font texels.
+pub const GLYPH_SIZE: u32 = 8;
+
+/// Maximum number of cells in overlay grid.
+pub const MAX_OVERLAY_CELLS: usize = 65536;
+
+/// First glyph in overlay font.
+const FIRST_GLYPH: u8 = 32;
+
+/// Number of glyphs in overlay font.
+const GLYPH_COUNT: usize = 96;
+
+/// Text grid drawn on top of the rendered image.
+///
+/// Overlay is immediate-mode, systems print into it every frame
+/// and renderer clears it after frame is drawn.
+/// Non-empty cells are drawn over darkened background.
+#[derive(Clone, Debug)]
+pub struct TextOverlay {
+    cols: u32,
+    rows: u32,
+
+    /// Size of font texel in pixels.
+    scale: u32,
+
+    /// Encoded cells.
+    /// Low byte is ASCII code, upper three bytes are RGB color.
+    /// Zero is empty cell.
+    cells: Vec<u32>,
+}
+
+impl Default for TextOverlay {
+    fn default() -> Self {
+        TextOverlay::new()
+    }
+}
+
+impl TextOverlay {
+    /// Creates empty overlay.
+    /// Grid is sized by renderer to fit the view.
+    pub fn new() -> Self {
+        TextOverlay {
+            cols: 0,
+            rows: 0,
+            scale: 2,
+            cells: Vec::new(),
+        }
+    }
+
+    pub fn with_scale(mut self, scale: u32) -> Self {
+        self.scale = scale.max(1);
+        self
+    }
+
+    pub fn cols(&self) -> u32 {
+        self.cols
+    }
+
+    pub fn rows(&self) -> u32 {
+        self.rows
+    }
+
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    pub fn cells(&self) -> &[u32] {
+        &self.cells
+    }
+
+    /// Resizes grid to cover view of specified extent.
+    /// Content is cleared if size changes.
+    pub fn resize(&mut self, extent: Extent2d) {
+        let cell = GLYPH_SIZE * self.scale;
+        let cols = extent.width / cell;
+        let rows =
+            (extent.height / cell).min(MAX_OVERLAY_CELLS as u32 / cols.max(1));
+
+        if cols != self.cols || rows != self.rows {
+            self.cols = cols;
+            self.rows = rows;
+            self.cells.clear();
+            self.cells.resize((cols * rows) as usize, 0);
+        }
+    }
+
+    /// Clears all cells.
+    pub fn clear(&mut self) {
+        for cell in &mut self.cells {
+            *cell = 0;
+        }
+    }
+
+    /// Fills rectangle with background.
+    pub fn fill(&mut self, col: u32, row: u32, cols: u32, rows: u32) {
+        for y in row..(row + rows).min(self.rows) {
+            for x in col..(col + cols).min(self.cols) {
+                self.cells[(y * self.cols + x) as usize] = encode(' ', [0; 3]);
+            }
+        }
+    }
+
+    /// Prints text starting at specified cell.
+    /// Text does not wrap, characters outside the grid are dropped.
+    /// Characters absent in the font are printed as `?`.
+    pub fn print(&mut self, col: u32, row: u32, text: &str, color: [u8; 3]) {
+        if row >= self.rows {
+            return;
+        }
+
+        for (x, c) in (col..self.cols).zip(text.chars()) {
+            self.cells[(row * self.cols + x) as usize] = encode(c, color);
+        }
+    }
+}
+
+fn encode(c: char, color: [u8; 3]) -> u32 {
+    let code = match c as u32 {
+        code @ 32..=126 => code,
+        _ => '?' as u32,
+    };
+
+    code | (color[0] as u32) << 8
+        | (color[1] as u32) << 16
+        | (color[2] as u32) << 24
+}
+
+/// Returns font bitmap for overlay shader.
+/// Each glyph is 8 bytes, one per row, least significant bit is leftmost.
+pub(super) fn overlay_font() -> [u8; GLYPH_COUNT * 8] {
+    let mut font = [0; GLYPH_COUNT * 8];
+
+    for (index, glyph) in font.chunks_mut(8).enumerate() {
+        let c = (FIRST_GLYPH + index as u8) as char;
+        if let Some(bitmap) = BASIC_FONTS.get(c) {
+            glyph.copy_from_slice(&bitmap);
+        }
+    }
+
+    font
+}
@@ -0,0 +1,451 @@
+use {
+    super::Pass,
+    crate::renderer::Context,
+    bumpalo::{collections::Vec as BVec, Bump},
+    bytemuck::{Pod, Zeroable},
+    color_eyre::Report,
+    hecs::World,
+    illume::*,
+    std::mem::size_of,
+};
+
+pub struct Input {
+    pub albedo: Image,
+
+    /// Emitted radiance with surface roughness in alpha channel.
+    pub emissive: Image,
+
+    pub direct: Image,
+    pub diffuse: Image,
+
+    /// Per-tile path counts read by ray-tracing prepass.
+    /// Rewritten from accumulated variance when `max_samples > 1`.
+    pub sample_map: Image,
+    pub max_samples: u32,
+
+    /// Relative error that single path per pixel is expected to reach.
+    pub error_threshold: f32,
+
+    /// Discards accumulated samples.
+    pub reset: bool,
+
+    /// Writes exposed radiance of accumulated samples
+    /// into capture buffer.
+    pub capture: bool,
+    pub exposure: f32,
+}
+
+/// Running means of accumulated input images.
+pub struct Output {
+    pub albedo: Image,
+    pub emissive: Image,
+    pub direct: Image,
+    pub diffuse: Image,
+
+    /// Number of samples accumulated including this one.
+    pub samples: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct Params {
+    extent: [u32; 2],
+    samples: u32,
+    capture: u32,
+    exposure: f32,
+    max_samples: u32,
+    error_threshold: f32,
+}
+
+unsafe impl Zeroable for Params {}
+unsafe impl Pod for Params {}
+
+/// Upper limit for paths traced per pixel in a frame.
+pub const MAX_ADAPTIVE_SAMPLES: u32 = 16;
+
+/// Number of sampled input images.
+const INPUTS: usize = 4;
+
+/// Number of accumulated images.
+/// Inputs plus luminance moments.
+const IMAGES: usize = INPUTS + 1;
+
+const CAPTURE_BINDING: u32 = (INPUTS + IMAGES) as u32;
+const SAMPLE_MAP_BINDING: u32 = CAPTURE_BINDING + 1;
+
+/// Accumulates samples of ray-traced images across frames.
+pub struct AccumulatePass {
+    sampler: Sampler,
+
+    /// Albedo, emissive, direct and diffuse views.
+    inputs: [[Option<ImageView>; INPUTS]; 2],
+    sample_map: [Option<ImageView>; 2],
+
+    pipeline: ComputePipeline,
+    pipeline_layout: PipelineLayout,
+    per_frame_sets: [DescriptorSet; 2],
+
+    targets: Option<Targets>,
+    samples: u32,
+}
+
+struct Targets {
+    extent: Extent2d,
+    accumulated: [Image; IMAGES],
+    capture: MappableBuffer,
+}
+
+impl AccumulatePass {
+    pub fn new(ctx: &mut Context) -> Result<Self, Report> {
+        let set_layout =
+            ctx.create_descriptor_set_layout(DescriptorSetLayoutInfo {
+                flags: DescriptorSetLayoutFlags::UPDATE_AFTER_BIND_POOL,
+                bindings: (0..=SAMPLE_MAP_BINDING)
+                    .map(|binding| DescriptorSetLayoutBinding {
+                        binding,
+                        ty: match binding {
+                            CAPTURE_BINDING => DescriptorType::StorageBuffer,
+                            SAMPLE_MAP_BINDING => DescriptorType::StorageImage,
+                            _ if binding < INPUTS as u32 => {
+                                DescriptorType::CombinedImageSampler
+                            }
+                            _ => DescriptorType::StorageImage,
+                        },
+                        count: 1,
+                        stages: ShaderStageFlags::COMPUTE,
+                        flags: DescriptorBindingFlags::empty(),
+                    })
+                    .collect(),
+            })?;
+
+        let pipeline_layout =
+            ctx.create_pipeline_layout(PipelineLayoutInfo {
+                sets: vec![set_layout.clone()],
+                push_constants: vec![PushConstant {
+                    stages: ShaderStageFlags::COMPUTE,
+                    offset: 0,
+                    size: size_of::<Params>() as u32,
+                }],
+            })?;
+
+        let shader = ComputeShader::with_main(
+            ctx.create_shader_module(
+                Spirv::new(
+                    include_bytes!("accumulate/accumulate.comp.spv").to_vec(),
+                )
+                .into(),
+            )?,
+        );
+
+        let pipeline = ctx.create_compute_pipeline(ComputePipelineInfo {
+            shader,
+            layout: pipeline_layout.clone(),
+        })?;
+
+        let set0 = ctx.create_descriptor_set(DescriptorSetInfo {
+            layout: set_layout.clone(),
+        })?;
+
+        let set1 = ctx.create_descriptor_set(DescriptorSetInfo {
+            layout: set_layout.clone(),
+        })?;
+
+        let sampler = ctx.create_sampler(SamplerInfo::new())?;
+
+        Ok(AccumulatePass {
+            sampler,
+            inputs: Default::default(),
+            sample_map: Default::default(),
+            pipeline,
+            pipeline_layout,
+            per_frame_sets: [set0, set1],
+            targets: None,
+            samples: 0,
+        })
+    }
+
+    /// Returns number of samples accumulated so far.
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    /// Reads exposed radiance written by last draw with `capture` set.
+    /// Frame that wrote it must be complete.
+    pub fn read_capture(
+        &mut self,
+        ctx: &mut Context,
+    ) -> Result<Option<(Extent2d, Vec<[f32; 4]>)>, Report> {
+        let targets = match &mut self.targets {
+            Some(targets) => targets,
+            None => return Ok(None),
+        };
+
+        let extent = targets.extent;
+        let mut texels =
+            vec![[0.0; 4]; extent.width as usize * extent.height as usize];
+        ctx.read_buffer(&mut targets.capture, 0, &mut texels)?;
+        Ok(Some((extent, texels)))
+    }
+}
+
+impl<'a> Pass<'a> for AccumulatePass {
+    type Input = Input;
+    type Output = Output;
+
+    fn draw(
+        &mut self,
+        input: Input,
+        frame: u64,
+        wait: &[(PipelineStageFlags, Semaphore)],
+        signal: &[Semaphore],
+        fence: Option<&Fence>,
+        ctx: &mut Context,
+        _world: &mut World,
+        bump: &Bump,
+    ) -> Result<Output, Report> {
+        let fid = (frame % 2) as usize;
+        let set = &self.per_frame_sets[fid];
+
+        let extent = input.direct.info().extent.into_2d();
+
+        let mut writes = BVec::new_in(bump);
+
+        let mut reset = input.reset;
+        match &self.targets {
+            Some(targets) if targets.extent == extent => {}
+            _ => {
+                self.targets = None;
+                reset = true;
+
+                let mut create_target = || {
+                    ctx.create_image(ImageInfo {
+                        extent: extent.into(),
+                        format: Format::RGBA32Sfloat,
+                        levels: 1,
+                        layers: 1,
+                        samples: Samples1,
+                        usage: ImageUsage::STORAGE | ImageUsage::SAMPLED,
+                    })
+                };
+
+                let accumulated = [
+                    create_target()?,
+                    create_target()?,
+                    create_target()?,
+                    create_target()?,
+                    create_target()?,
+                ];
+
+                let capture = ctx.create_mappable_buffer(
+                    BufferInfo {
+                        align: 255,
+                        size: size_of::<[f32; 4]>() as u64
+                            * u64::from(extent.width)
+                            * u64::from(extent.height),
+                        usage: BufferUsage::STORAGE,
+                        capture_address: None,
+                    },
+                    MemoryUsage::DOWNLOAD,
+                )?;
+
+                for set in &self.per_frame_sets {
+                    for (index, image) in accumulated.iter().enumerate() {
+                        let view = ctx.create_image_view(
+                            ImageViewInfo::new(image.clone()),
+                        )?;
+
+                        writes.push(WriteDescriptorSet {
+                            set,
+                            binding: (INPUTS + index) as u32,
+                            element: 0,
+                            descriptors: Descriptors::StorageImage(
+                                bump.alloc([(view, Layout::General)]),
+                            ),
+                        });
+                    }
+
+                    writes.push(WriteDescriptorSet {
+                        set,
+                        binding: CAPTURE_BINDING,
+                        element: 0,
+                        descriptors: Descriptors::StorageBuffer(bump.alloc([
+                            (capture.share(), 0, capture.info().size),
+                        ])),
+                    });
+                }
+
+                self.targets = Some(Targets {
+                    extent,
+                    accumulated,
+                    capture,
+                });
+            }
+        }
+
+        let targets = self.targets.as_ref().unwrap();
+
+        if reset {
+            self.samples = 0;
+        }
+
+        let images = [
+            &input.albedo,
+            &input.emissive,
+            &input.direct,
+            &input.diffuse,
+        ];
+
+        for (binding, (slot, image)) in
+            self.inputs[fid].iter_mut().zip(images.iter()).enumerate()
+        {
+            match slot {
+                Some(view) if view.info().image == **image => continue,
+                _ => {}
+            }
+
+            *slot = None;
+            let view =
+                ctx.create_image_view(ImageViewInfo::new((*image).clone()))?;
+            let view = slot.get_or_insert(view);
+
+            writes.push(WriteDescriptorSet {
+                set,
+                binding: binding as u32,
+                element: 0,
+                descriptors: Descriptors::CombinedImageSampler(bump.alloc([(
+                    view.clone(),
+                    Layout::ShaderReadOnlyOptimal,
+                    self.sampler.clone(),
+                )])),
+            });
+        }
+
+        match &self.sample_map[fid] {
+            Some(view) if view.info().image == input.sample_map => {}
+            _ => {
+                self.sample_map[fid] = None;
+                let view = ctx.create_image_view(ImageViewInfo::new(
+                    input.sample_map.clone(),
+                ))?;
+                let view = self.sample_map[fid].get_or_insert(view);
+
+                writes.push(WriteDescriptorSet {
+                    set,
+                    binding: SAMPLE_MAP_BINDING,
+                    element: 0,
+                    descriptors: Descriptors::StorageImage(
+                        bump.alloc([(view.clone(), Layout::General)]),
+                    ),
+                });
+            }
+        }
+
+        ctx.update_descriptor_sets(&writes, &[]);
+
+        let mut encoder = ctx.queue.create_encoder()?;
+
+        // Inputs are written by ray-tracing prepass.
+        // Sample map is read by it.
+        encoder.pipeline_barrier(
+            PipelineStageFlags::RAY_TRACING_SHADER,
+            PipelineStageFlags::COMPUTE_SHADER,
+        );
+
+        // Accumulated images are sampled by previous frame.
+        // Their content is discarded on reset.
+        let images: &[ImageMemoryBarrier<'_>] = bump.alloc_slice_fill_iter(
+            targets.accumulated.iter().map(|image| {
+                if self.samples == 0 {
+                    ImageLayoutTransition::initialize_whole(
+                        image,
+                        Layout::General,
+                    )
+                    .into()
+                } else {
+                    ImageLayoutTransition::transition_whole(
+                        image,
+                        Layout::ShaderReadOnlyOptimal..Layout::General,
+                    )
+                    .into()
+                }
+            }),
+        );
+
+        encoder.image_barriers(
+            PipelineStageFlags::FRAGMENT_SHADER
+                | PipelineStageFlags::COMPUTE_SHADER,
+            PipelineStageFlags::COMPUTE_SHADER,
+            images,
+        );
+
+        encoder.bind_compute_pipeline(&self.pipeline);
+        encoder.bind_compute_descriptor_sets(
+            &self.pipeline_layout,
+            0,
+            std::slice::from_ref(set),
+            &[],
+        );
+        encoder.push_constants(
+            &self.pipeline_layout,
+            ShaderStageFlags::COMPUTE,
+            0,
+            bump.alloc([Params {
+                extent: [extent.width, extent.height],
+                samples: self.samples,
+                capture: input.capture as u32,
+                exposure: input.exposure,
+                max_samples: input.max_samples,
+                error_threshold: input.error_threshold,
+            }]),
+        );
+
+        // One workgroup per sample map tile.
+        encoder.dispatch((extent.width + 7) / 8, (extent.height + 7) / 8, 1);
+
+        // Sample map is read by next frame's ray-tracing prepass.
+        encoder.pipeline_barrier(
+            PipelineStageFlags::COMPUTE_SHADER,
+            PipelineStageFlags::RAY_TRACING_SHADER,
+        );
+
+        let images: &[ImageMemoryBarrier<'_>] = bump.alloc_slice_fill_iter(
+            targets.accumulated.iter().map(|image| {
+                ImageLayoutTransition::transition_whole(
+                    image,
+                    Layout::General..Layout::ShaderReadOnlyOptimal,
+                )
+                .into()
+            }),
+        );
+
+        encoder.image_barriers(
+            PipelineStageFlags::COMPUTE_SHADER,
+            PipelineStageFlags::FRAGMENT_SHADER
+                | PipelineStageFlags::COMPUTE_SHADER,
+            images,
+        );
+
+        if input.capture {
+            // Make capture visible to host once frame is complete.
+            encoder.pipeline_barrier(
+                PipelineStageFlags::COMPUTE_SHADER,
+                PipelineStageFlags::HOST,
+            );
+        }
+
+        ctx.record_barriers(encoder.barrier_count());
+        ctx.queue.submit(wait, encoder.finish()?, signal, fence)?;
+
+        self.samples = self.samples.saturating_add(1);
+
+        let [albedo, emissive, direct, diffuse, _] = &targets.accumulated;
+
+        Ok(Output {
+            albedo: albedo.clone(),
+            emissive: emissive.clone(),
+            direct: direct.clone(),
+            diffuse: diffuse.clone(),
+            samples: self.samples,
+        })
+    }
+}
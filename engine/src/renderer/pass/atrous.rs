@@ -2,22 +2,113 @@ use {
     super::Pass,
     crate::renderer::Context,
     bumpalo::{collections::Vec as BVec, Bump},
+    bytemuck::{Pod, Zeroable},
     color_eyre::Report,
     hecs::World,
     illume::*,
     smallvec::smallvec,
+    std::mem::size_of,
 };
 
+/// Maximum number of filter iterations.
+/// Kernel of last iteration spans `4 << (MAX_ATROUS_ITERATIONS - 1)` pixels.
+pub const MAX_ATROUS_ITERATIONS: u32 = 6;
+
+/// Parameters of A-Trous filter.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Deserialize)]
+#[serde(default)]
+pub struct ATrousConfig {
+    pub enabled: bool,
+
+    /// Number of iterations.
+    /// Each iteration doubles the kernel spread.
+    /// Clamped to `1..=MAX_ATROUS_ITERATIONS`.
+    pub iterations: u32,
+
+    /// Exponent applied to cosine between normals.
+    /// Larger values preserve more geometric edges.
+    pub sigma_normal: f32,
+
+    /// Allowed relative depth change per pixel of distance.
+    pub sigma_depth: f32,
+
+    /// Allowed luminance difference relative to filtered pixel.
+    pub sigma_luminance: f32,
+}
+
+impl ATrousConfig {
+    pub const fn low() -> Self {
+        ATrousConfig {
+            enabled: true,
+            iterations: 2,
+            sigma_normal: 32.0,
+            sigma_depth: 0.02,
+            sigma_luminance: 8.0,
+        }
+    }
+
+    pub const fn medium() -> Self {
+        ATrousConfig {
+            enabled: true,
+            iterations: 4,
+            sigma_normal: 64.0,
+            sigma_depth: 0.01,
+            sigma_luminance: 4.0,
+        }
+    }
+
+    pub const fn high() -> Self {
+        ATrousConfig {
+            enabled: true,
+            iterations: 5,
+            sigma_normal: 128.0,
+            sigma_depth: 0.01,
+            sigma_luminance: 2.0,
+        }
+    }
+
+    pub const fn disabled() -> Self {
+        ATrousConfig {
+            enabled: false,
+            ..ATrousConfig::medium()
+        }
+    }
+}
+
+impl Default for ATrousConfig {
+    fn default() -> Self {
+        ATrousConfig::medium()
+    }
+}
+
 pub struct Input {
     pub normal_depth: Image,
+
+    /// Image to filter.
+    /// Alpha channel is preserved.
     pub unfiltered: Image,
+    pub config: ATrousConfig,
 }
 
 pub struct Output {
     pub filtered: Image,
 }
 
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct Params {
+    step: u32,
+    sigma_normal: f32,
+    sigma_depth: f32,
+    sigma_luminance: f32,
+}
+
+unsafe impl Zeroable for Params {}
+unsafe impl Pod for Params {}
+
 pub struct ATrousFilter {
+    /// Format of filtered images.
+    format: Format,
     sampler: Sampler,
     normal_depth: Option<ImageView>,
     unfiltered: Option<ImageView>,
@@ -26,7 +117,7 @@ pub struct ATrousFilter {
     framebuffers: Option<[Framebuffer; 2]>,
 
     render_pass: RenderPass,
-    pipelines: [GraphicsPipeline; 6],
+    pipeline: GraphicsPipeline,
 
     pipeline_layout: PipelineLayout,
     sets: [DescriptorSet; 3],
@@ -34,6 +125,8 @@ pub struct ATrousFilter {
 
 impl ATrousFilter {
     pub fn new(ctx: &mut Context) -> Result<Self, Report> {
+        let format = filtered_format(ctx)?;
+
         let set_layout =
             ctx.create_descriptor_set_layout(DescriptorSetLayoutInfo {
                 flags: DescriptorSetLayoutFlags::UPDATE_AFTER_BIND_POOL,
@@ -60,7 +153,11 @@ impl ATrousFilter {
         let pipeline_layout =
             ctx.create_pipeline_layout(PipelineLayoutInfo {
                 sets: vec![set_layout.clone()],
-                push_constants: Vec::new(),
+                push_constants: vec![PushConstant {
+                    stages: ShaderStageFlags::FRAGMENT,
+                    offset: 0,
+                    size: size_of::<Params>() as u32,
+                }],
             })?;
 
         let vert = VertexShader::with_main(
@@ -70,44 +167,9 @@ impl ATrousFilter {
             )?,
         );
 
-        let frag0h = FragmentShader::with_main(
-            ctx.create_shader_module(
-                Spirv::new(include_bytes!("atrous/atrous0h.frag.spv").to_vec())
-                    .into(),
-            )?,
-        );
-
-        let frag1h = FragmentShader::with_main(
+        let frag = FragmentShader::with_main(
             ctx.create_shader_module(
-                Spirv::new(include_bytes!("atrous/atrous1h.frag.spv").to_vec())
-                    .into(),
-            )?,
-        );
-
-        let frag2h = FragmentShader::with_main(
-            ctx.create_shader_module(
-                Spirv::new(include_bytes!("atrous/atrous2h.frag.spv").to_vec())
-                    .into(),
-            )?,
-        );
-
-        let frag0v = FragmentShader::with_main(
-            ctx.create_shader_module(
-                Spirv::new(include_bytes!("atrous/atrous0v.frag.spv").to_vec())
-                    .into(),
-            )?,
-        );
-
-        let frag1v = FragmentShader::with_main(
-            ctx.create_shader_module(
-                Spirv::new(include_bytes!("atrous/atrous1v.frag.spv").to_vec())
-                    .into(),
-            )?,
-        );
-
-        let frag2v = FragmentShader::with_main(
-            ctx.create_shader_module(
-                Spirv::new(include_bytes!("atrous/atrous2v.frag.spv").to_vec())
+                Spirv::new(include_bytes!("atrous/atrous.frag.spv").to_vec())
                     .into(),
             )?,
         );
@@ -136,7 +198,7 @@ impl ATrousFilter {
 
         let render_pass = ctx.create_render_pass(RenderPassInfo {
             attachments: smallvec![AttachmentInfo {
-                format: Format::RGBA32Sfloat,
+                format,
                 samples: Samples::Samples1,
                 load_op: AttachmentLoadOp::Clear,
                 store_op: AttachmentStoreOp::Store,
@@ -146,6 +208,7 @@ impl ATrousFilter {
             subpasses: smallvec![Subpass {
                 colors: smallvec![0],
                 depth: None,
+                resolves: smallvec![],
             }],
             dependencies: smallvec![
                 SubpassDependency {
@@ -163,58 +226,18 @@ impl ATrousFilter {
             ],
         })?;
 
-        let pipelines = [
-            ctx.create_graphics_pipeline(graphics_pipeline_info! {
-                vertex_shader: vert.clone(),
-                layout: pipeline_layout.clone(),
-                render_pass: render_pass.clone(),
-                rasterizer: rasterizer!{
-                    fragment_shader: frag0h,
-                }
-            })?,
-            ctx.create_graphics_pipeline(graphics_pipeline_info! {
-                vertex_shader: vert.clone(),
-                layout: pipeline_layout.clone(),
-                render_pass: render_pass.clone(),
-                rasterizer: rasterizer!{
-                    fragment_shader: frag1h,
-                }
-            })?,
-            ctx.create_graphics_pipeline(graphics_pipeline_info! {
-                vertex_shader: vert.clone(),
-                layout: pipeline_layout.clone(),
-                render_pass: render_pass.clone(),
-                rasterizer: rasterizer!{
-                    fragment_shader: frag2h,
-                }
-            })?,
-            ctx.create_graphics_pipeline(graphics_pipeline_info! {
-                vertex_shader: vert.clone(),
-                layout: pipeline_layout.clone(),
-                render_pass: render_pass.clone(),
-                rasterizer: rasterizer!{
-                    fragment_shader: frag0v,
-                }
-            })?,
-            ctx.create_graphics_pipeline(graphics_pipeline_info! {
-                vertex_shader: vert.clone(),
-                layout: pipeline_layout.clone(),
-                render_pass: render_pass.clone(),
-                rasterizer: rasterizer!{
-                    fragment_shader: frag1v,
-                }
-            })?,
+        let pipeline =
             ctx.create_graphics_pipeline(graphics_pipeline_info! {
                 vertex_shader: vert,
                 layout: pipeline_layout.clone(),
                 render_pass: render_pass.clone(),
                 rasterizer: rasterizer!{
-                    fragment_shader: frag2v,
+                    fragment_shader: frag,
                 }
-            })?,
-        ];
+            })?;
 
         Ok(ATrousFilter {
+            format,
             sampler,
             normal_depth: None,
             unfiltered: None,
@@ -224,7 +247,7 @@ impl ATrousFilter {
             sets: [set0, set1, set2],
             pipeline_layout,
             render_pass,
-            pipelines,
+            pipeline,
         })
     }
 }
@@ -262,7 +285,7 @@ impl<'a> Pass<'a> for ATrousFilter {
 
                 let filtered0 = ctx.create_image(ImageInfo {
                     extent: extent.into(),
-                    format: Format::RGBA32Sfloat,
+                    format: self.format,
                     levels: 1,
                     layers: 1,
                     samples: Samples1,
@@ -271,7 +294,7 @@ impl<'a> Pass<'a> for ATrousFilter {
 
                 let filtered1 = ctx.create_image(ImageInfo {
                     extent: extent.into(),
-                    format: Format::RGBA32Sfloat,
+                    format: self.format,
                     levels: 1,
                     layers: 1,
                     samples: Samples1,
@@ -420,22 +443,43 @@ impl<'a> Pass<'a> for ATrousFilter {
 
         let mut encoder = ctx.queue.create_encoder()?;
 
-        const SET_INDICES: [usize; 6] = [0, 1, 2, 1, 2, 1];
+        let config = input.config;
+        let iterations = config.iterations.max(1).min(MAX_ATROUS_ITERATIONS);
+        let params =
+            bump.alloc_slice_fill_iter((0..iterations).map(|i| Params {
+                step: 1 << i,
+                sigma_normal: config.sigma_normal,
+                sigma_depth: config.sigma_depth,
+                sigma_luminance: config.sigma_luminance,
+            }));
+
+        // First iteration reads unfiltered image.
+        // Following iterations ping-pong between filtered images.
+        for (i, params) in params.iter().enumerate() {
+            let set = match i {
+                0 => &self.sets[0],
+                _ => &self.sets[1 + (i + 1) % 2],
+            };
 
-        for i in 0..6 {
             let mut render_pass_encoder = encoder.with_render_pass(
                 &self.render_pass,
                 &framebuffers[i % 2],
                 &[ClearValue::Color(0.3, 0.4, 0.5, 1.0)],
             );
 
-            render_pass_encoder.bind_graphics_pipeline(&self.pipelines[i]);
+            render_pass_encoder.bind_graphics_pipeline(&self.pipeline);
             render_pass_encoder.bind_graphics_descriptor_sets(
                 &self.pipeline_layout,
                 0,
-                std::slice::from_ref(&self.sets[SET_INDICES[i]]),
+                std::slice::from_ref(set),
                 &[],
             );
+            render_pass_encoder.push_constants(
+                &self.pipeline_layout,
+                ShaderStageFlags::FRAGMENT,
+                0,
+                std::slice::from_ref(params),
+            );
             render_pass_encoder.set_viewport(Viewport {
                 x: Bounds {
                     offset: 0.0.into(),
@@ -455,10 +499,32 @@ impl<'a> Pass<'a> for ATrousFilter {
             render_pass_encoder.draw(0..3, 0..1);
         }
 
-        ctx.queue.submit(wait, encoder.finish(), signal, fence);
+        ctx.queue.submit(wait, encoder.finish()?, signal, fence)?;
 
         Ok(Output {
-            filtered: filtered[1].info().image.clone(),
+            filtered: filtered[(iterations as usize - 1) % 2]
+                .info()
+                .image
+                .clone(),
         })
     }
 }
+
+/// Picks format for filtered images.
+/// Full precision is preferred when device supports it.
+fn filtered_format(device: &Device) -> Result<Format, Report> {
+    let required =
+        FormatFeatures::COLOR_ATTACHMENT | FormatFeatures::SAMPLED_IMAGE;
+
+    for &format in &[Format::RGBA32Sfloat, Format::RGBA16Sfloat] {
+        if device
+            .format_properties(format)
+            .optimal_tiling
+            .contains(required)
+        {
+            return Ok(format);
+        }
+    }
+
+    Err(eyre::eyre!("No supported format for filtered images"))
+}
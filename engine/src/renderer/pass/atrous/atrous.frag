@@ -0,0 +1,70 @@
+#version 460
+
+// One iteration of edge-avoiding A-Trous wavelet filter.
+// 5x5 B3-spline kernel is spread by `step` pixels.
+// Samples are weighted by similarity of normal, depth and luminance
+// to the filtered pixel.
+
+layout(binding = 0, set = 0) uniform sampler2D normals_depth;
+layout(binding = 1, set = 0) uniform sampler2D unfiltered;
+
+layout(push_constant) uniform Params {
+    uint step;
+    float sigma_normal;
+    float sigma_depth;
+    float sigma_luminance;
+};
+
+layout(location = 0) out vec4 output_image;
+
+const float KERNEL[3] = float[](3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0);
+const float EPSILON = 0.0001;
+
+float luminance(vec3 color) {
+    return dot(color, vec3(0.2126, 0.7152, 0.0722));
+}
+
+void main() {
+    vec4 center = texture(unfiltered, gl_FragCoord.xy);
+    vec4 normal_depth = texture(normals_depth, gl_FragCoord.xy);
+
+    // Nothing to preserve edges of.
+    if (normal_depth.w < 0.0) {
+        output_image = center;
+        return;
+    }
+
+    vec3 normal = normal_depth.xyz;
+    float depth = normal_depth.w;
+    float center_luminance = luminance(center.rgb);
+
+    vec3 sum = vec3(0.0);
+    float weights = 0.0;
+
+    for (int y = -2; y <= 2; ++y) {
+        for (int x = -2; x <= 2; ++x) {
+            vec2 offset = vec2(x, y) * float(step);
+            vec4 sample_normal_depth = texture(normals_depth, gl_FragCoord.xy + offset);
+            if (sample_normal_depth.w < 0.0) {
+                continue;
+            }
+
+            vec3 color = texture(unfiltered, gl_FragCoord.xy + offset).rgb;
+
+            // Normal similarity is sharpened by exponent.
+            float wn = pow(max(dot(normal, sample_normal_depth.xyz), 0.0), sigma_normal);
+
+            // Depth may change proportionally to distance in pixels.
+            float wd = exp(-abs(depth - sample_normal_depth.w) / (sigma_depth * depth * length(offset) + EPSILON));
+
+            // Luminance difference is relative to filtered pixel.
+            float wl = exp(-abs(center_luminance - luminance(color)) / (sigma_luminance * center_luminance + EPSILON));
+
+            float w = KERNEL[abs(x)] * KERNEL[abs(y)] * wn * wd * wl;
+            sum += color * w;
+            weights += w;
+        }
+    }
+
+    output_image = vec4(sum / max(weights, EPSILON), center.a);
+}
@@ -0,0 +1,369 @@
+use {
+    super::Pass,
+    crate::{
+        audio::{AudioOcclusion, AudioSource},
+        renderer::Context,
+        scene::Global3,
+    },
+    bumpalo::{collections::Vec as BVec, Bump},
+    bytemuck::{Pod, Zeroable},
+    color_eyre::Report,
+    hecs::{Entity, World},
+    illume::*,
+    nalgebra as na,
+    std::mem::size_of,
+};
+
+/// Maximum number of sources queried per frame.
+/// Farther sources are left with their last occlusion.
+pub const MAX_AUDIO_SOURCES: u32 = 256;
+
+/// Rays traced from listener to each source.
+pub const AUDIO_OCCLUSION_RAYS: u32 = 16;
+
+pub struct Input {
+    pub tlas: AccelerationStructure,
+    pub listener: na::Point3<f32>,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct Query {
+    listener: [f32; 3],
+    sources_count: u32,
+}
+
+unsafe impl Zeroable for Query {}
+unsafe impl Pod for Query {}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct ShaderSource {
+    pos: [f32; 3],
+    radius: f32,
+}
+
+unsafe impl Zeroable for ShaderSource {}
+unsafe impl Pod for ShaderSource {}
+
+/// Traces occlusion rays from listener to audio sources
+/// against ray-tracing prepass TLAS.
+///
+/// Visibility of each ray is copied into host-visible buffer
+/// and turned into `AudioOcclusion` of the source two frames later,
+/// once the frame that traced them is complete.
+pub struct AudioOcclusionPass {
+    pipeline: RayTracingPipeline,
+    pipeline_layout: PipelineLayout,
+    shader_binding_table: ShaderBindingTable,
+    per_frame_sets: [DescriptorSet; 2],
+    sources: [MappableBuffer; 2],
+    readback: [MappableBuffer; 2],
+
+    /// TLAS bound to per-frame sets.
+    tlas: [Option<AccelerationStructure>; 2],
+
+    /// Sources whose rays are written into readback buffers.
+    pending: [Vec<Entity>; 2],
+}
+
+impl AudioOcclusionPass {
+    pub fn new(ctx: &mut Context) -> Result<Self, Report> {
+        let set_layout =
+            ctx.create_descriptor_set_layout(DescriptorSetLayoutInfo {
+                flags: DescriptorSetLayoutFlags::empty(),
+                bindings: vec![
+                    DescriptorSetLayoutBinding {
+                        binding: 0,
+                        ty: DescriptorType::AccelerationStructure,
+                        count: 1,
+                        stages: ShaderStageFlags::RAYGEN,
+                        flags: DescriptorBindingFlags::empty(),
+                    },
+                    DescriptorSetLayoutBinding {
+                        binding: 1,
+                        ty: DescriptorType::StorageBuffer,
+                        count: 1,
+                        stages: ShaderStageFlags::RAYGEN,
+                        flags: DescriptorBindingFlags::empty(),
+                    },
+                    DescriptorSetLayoutBinding {
+                        binding: 2,
+                        ty: DescriptorType::StorageBuffer,
+                        count: 1,
+                        stages: ShaderStageFlags::RAYGEN,
+                        flags: DescriptorBindingFlags::empty(),
+                    },
+                ],
+            })?;
+
+        let pipeline_layout =
+            ctx.create_pipeline_layout(PipelineLayoutInfo {
+                sets: vec![set_layout.clone()],
+                push_constants: vec![PushConstant {
+                    stages: ShaderStageFlags::RAYGEN,
+                    offset: 0,
+                    size: size_of::<Query>() as u32,
+                }],
+            })?;
+
+        let occlusion_rgen = RaygenShader::with_main(
+            ctx.create_shader_module(
+                Spirv::new(
+                    include_bytes!("audio_occlusion/occlusion.rgen.spv")
+                        .to_vec(),
+                )
+                .into(),
+            )?,
+        );
+
+        let shadow_rmiss = MissShader::with_main(
+            ctx.create_shader_module(
+                Spirv::new(include_bytes!("common/shadow.rmiss.spv").to_vec())
+                    .into(),
+            )?,
+        );
+
+        // Rays are opaque and skip closest hit,
+        // so hit group has no shaders.
+        let pipeline =
+            ctx.create_ray_tracing_pipeline(RayTracingPipelineInfo {
+                shaders: vec![occlusion_rgen.into(), shadow_rmiss.into()],
+                groups: vec![
+                    RayTracingShaderGroupInfo::Raygen { raygen: 0 },
+                    RayTracingShaderGroupInfo::Miss { miss: 1 },
+                    RayTracingShaderGroupInfo::Triangles {
+                        any_hit: None,
+                        closest_hit: None,
+                    },
+                ],
+                max_recursion_depth: 1,
+                layout: pipeline_layout.clone(),
+                libraries: Vec::new(),
+                interface: None,
+                library: false,
+            })?;
+
+        let shader_binding_table = ctx.create_shader_binding_table(
+            &pipeline,
+            ShaderBindingTableInfo {
+                raygen: Some(0),
+                miss: &[1],
+                hit: &[2],
+                callable: &[],
+            },
+        )?;
+
+        let sources_size =
+            size_of::<ShaderSource>() as u64 * u64::from(MAX_AUDIO_SOURCES);
+        let readback_size = size_of::<u32>() as u64
+            * u64::from(MAX_AUDIO_SOURCES * AUDIO_OCCLUSION_RAYS);
+
+        let mut create_buffer = |size, usage| {
+            ctx.create_mappable_buffer(
+                BufferInfo {
+                    align: 255,
+                    size,
+                    usage: BufferUsage::STORAGE,
+                    capture_address: None,
+                },
+                usage,
+            )
+        };
+
+        let sources = [
+            create_buffer(sources_size, MemoryUsage::UPLOAD)?,
+            create_buffer(sources_size, MemoryUsage::UPLOAD)?,
+        ];
+        let readback = [
+            create_buffer(readback_size, MemoryUsage::DOWNLOAD)?,
+            create_buffer(readback_size, MemoryUsage::DOWNLOAD)?,
+        ];
+
+        let set0 = ctx.create_descriptor_set(DescriptorSetInfo {
+            layout: set_layout.clone(),
+        })?;
+
+        let set1 = ctx
+            .create_descriptor_set(DescriptorSetInfo { layout: set_layout })?;
+
+        let per_frame_sets = [set0, set1];
+
+        for (set, (sources, readback)) in per_frame_sets
+            .iter()
+            .zip(sources.iter().zip(readback.iter()))
+        {
+            ctx.update_descriptor_sets(
+                &[
+                    WriteDescriptorSet {
+                        set,
+                        binding: 1,
+                        element: 0,
+                        descriptors: Descriptors::StorageBuffer(&[(
+                            sources.share(),
+                            0,
+                            sources_size,
+                        )]),
+                    },
+                    WriteDescriptorSet {
+                        set,
+                        binding: 2,
+                        element: 0,
+                        descriptors: Descriptors::StorageBuffer(&[(
+                            readback.share(),
+                            0,
+                            readback_size,
+                        )]),
+                    },
+                ],
+                &[],
+            );
+        }
+
+        Ok(AudioOcclusionPass {
+            pipeline,
+            pipeline_layout,
+            shader_binding_table,
+            per_frame_sets,
+            sources,
+            readback,
+            tlas: [None, None],
+            pending: [Vec::new(), Vec::new()],
+        })
+    }
+}
+
+impl<'a> Pass<'a> for AudioOcclusionPass {
+    type Input = Input;
+    type Output = ();
+
+    fn draw(
+        &mut self,
+        input: Input,
+        frame: u64,
+        wait: &[(PipelineStageFlags, Semaphore)],
+        signal: &[Semaphore],
+        fence: Option<&Fence>,
+        ctx: &mut Context,
+        world: &mut World,
+        bump: &Bump,
+    ) -> Result<(), Report> {
+        let fid = (frame % 2) as usize;
+
+        // Frame that traced rays into the buffer is complete by now.
+        let pending = std::mem::take(&mut self.pending[fid]);
+        if !pending.is_empty() {
+            let visible = bump.alloc_slice_fill_copy(
+                pending.len() * AUDIO_OCCLUSION_RAYS as usize,
+                0u32,
+            );
+            ctx.read_buffer(&mut self.readback[fid], 0, visible)?;
+
+            for (entity, rays) in pending
+                .iter()
+                .zip(visible.chunks(AUDIO_OCCLUSION_RAYS as usize))
+            {
+                let reached = rays.iter().filter(|&&ray| ray != 0).count();
+                let occlusion = AudioOcclusion::from_visibility(
+                    reached as f32 / AUDIO_OCCLUSION_RAYS as f32,
+                );
+
+                // Source may be despawned since.
+                let _ = world.insert_one(*entity, occlusion);
+            }
+        }
+
+        // Nearest sources are queried when there are too many.
+        let mut sources = BVec::new_in(bump);
+        for (entity, (source, global)) in
+            world.query::<(&AudioSource, &Global3)>().iter()
+        {
+            let pos = global.iso.translation.vector;
+            let dist = (pos - input.listener.coords).norm_squared();
+            sources.push((dist, entity, source.radius, pos));
+        }
+
+        if sources.len() > MAX_AUDIO_SOURCES as usize {
+            sources.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+            sources.truncate(MAX_AUDIO_SOURCES as usize);
+        }
+
+        if sources.is_empty() {
+            return Ok(());
+        }
+
+        let mut shader_sources = BVec::with_capacity_in(sources.len(), bump);
+        shader_sources.extend(sources.iter().map(|&(_, _, radius, pos)| {
+            ShaderSource {
+                pos: pos.into(),
+                radius,
+            }
+        }));
+        ctx.write_buffer(&mut self.sources[fid], 0, &shader_sources)?;
+
+        let set = &self.per_frame_sets[fid];
+        if self.tlas[fid].as_ref() != Some(&input.tlas) {
+            ctx.update_descriptor_sets(
+                &[WriteDescriptorSet {
+                    set,
+                    binding: 0,
+                    element: 0,
+                    descriptors: Descriptors::AccelerationStructure(
+                        std::slice::from_ref(&input.tlas),
+                    ),
+                }],
+                &[],
+            );
+            self.tlas[fid] = Some(input.tlas);
+        }
+
+        let query = Query {
+            listener: input.listener.coords.into(),
+            sources_count: sources.len() as u32,
+        };
+
+        let mut encoder = ctx.queue.create_encoder()?;
+
+        // TLAS is built by ray-tracing prepass.
+        encoder.pipeline_barrier(
+            PipelineStageFlags::ACCELERATION_STRUCTURE_BUILD,
+            PipelineStageFlags::RAY_TRACING_SHADER,
+        );
+
+        encoder.bind_ray_tracing_pipeline(&self.pipeline);
+        encoder.bind_ray_tracing_descriptor_sets(
+            &self.pipeline_layout,
+            0,
+            std::slice::from_ref(set),
+            &[],
+        );
+        encoder.push_constants(
+            &self.pipeline_layout,
+            ShaderStageFlags::RAYGEN,
+            0,
+            bump.alloc([query]),
+        );
+        encoder.trace_rays(
+            &self.shader_binding_table,
+            Extent3d {
+                width: sources.len() as u32,
+                height: AUDIO_OCCLUSION_RAYS,
+                depth: 1,
+            },
+        );
+
+        // Make readback visible to host once frame is complete.
+        encoder.pipeline_barrier(
+            PipelineStageFlags::RAY_TRACING_SHADER,
+            PipelineStageFlags::HOST,
+        );
+
+        ctx.record_barriers(encoder.barrier_count());
+        ctx.queue.submit(wait, encoder.finish()?, signal, fence)?;
+
+        self.pending[fid] =
+            sources.iter().map(|&(_, entity, _, _)| entity).collect();
+
+        Ok(())
+    }
+}
@@ -0,0 +1,219 @@
+use {
+    super::Pass,
+    crate::renderer::Context,
+    bumpalo::{collections::Vec as BVec, Bump},
+    bytemuck::{Pod, Zeroable},
+    color_eyre::Report,
+    hecs::World,
+    illume::*,
+    std::mem::size_of,
+};
+
+/// Outputs of ray-tracing prepass drawn in checkerboard mode.
+/// Resolved in place.
+pub struct Input {
+    pub albedo: Image,
+    pub normal_depth: Image,
+    pub emissive: Image,
+    pub direct: Image,
+    pub diffuse: Image,
+
+    /// Pixels with even `x + y + checkerboard` were traced.
+    pub checkerboard: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct Params {
+    extent: [u32; 2],
+    checkerboard: u32,
+}
+
+unsafe impl Zeroable for Params {}
+unsafe impl Pod for Params {}
+
+/// Number of resolved images.
+const IMAGES: usize = 5;
+
+/// Reconstructs pixels skipped by checkerboard rendering
+/// from previous frame and neighbouring pixels.
+pub struct CheckerboardPass {
+    /// Albedo, normal-depth, emissive, direct and diffuse views.
+    images: [[Option<ImageView>; IMAGES]; 2],
+
+    pipeline: ComputePipeline,
+    pipeline_layout: PipelineLayout,
+    per_frame_sets: [DescriptorSet; 2],
+}
+
+impl CheckerboardPass {
+    pub fn new(ctx: &mut Context) -> Result<Self, Report> {
+        let set_layout =
+            ctx.create_descriptor_set_layout(DescriptorSetLayoutInfo {
+                flags: DescriptorSetLayoutFlags::UPDATE_AFTER_BIND_POOL,
+                bindings: (0..IMAGES as u32)
+                    .map(|binding| DescriptorSetLayoutBinding {
+                        binding,
+                        ty: DescriptorType::StorageImage,
+                        count: 1,
+                        stages: ShaderStageFlags::COMPUTE,
+                        flags: DescriptorBindingFlags::empty(),
+                    })
+                    .collect(),
+            })?;
+
+        let pipeline_layout =
+            ctx.create_pipeline_layout(PipelineLayoutInfo {
+                sets: vec![set_layout.clone()],
+                push_constants: vec![PushConstant {
+                    stages: ShaderStageFlags::COMPUTE,
+                    offset: 0,
+                    size: size_of::<Params>() as u32,
+                }],
+            })?;
+
+        let shader = ComputeShader::with_main(
+            ctx.create_shader_module(
+                Spirv::new(
+                    include_bytes!("checkerboard/resolve.comp.spv").to_vec(),
+                )
+                .into(),
+            )?,
+        );
+
+        let pipeline = ctx.create_compute_pipeline(ComputePipelineInfo {
+            shader,
+            layout: pipeline_layout.clone(),
+        })?;
+
+        let set0 = ctx.create_descriptor_set(DescriptorSetInfo {
+            layout: set_layout.clone(),
+        })?;
+
+        let set1 = ctx.create_descriptor_set(DescriptorSetInfo {
+            layout: set_layout.clone(),
+        })?;
+
+        Ok(CheckerboardPass {
+            images: Default::default(),
+            pipeline,
+            pipeline_layout,
+            per_frame_sets: [set0, set1],
+        })
+    }
+}
+
+impl<'a> Pass<'a> for CheckerboardPass {
+    type Input = Input;
+    type Output = ();
+
+    fn draw(
+        &mut self,
+        input: Input,
+        frame: u64,
+        wait: &[(PipelineStageFlags, Semaphore)],
+        signal: &[Semaphore],
+        fence: Option<&Fence>,
+        ctx: &mut Context,
+        _world: &mut World,
+        bump: &Bump,
+    ) -> Result<(), Report> {
+        let fid = (frame % 2) as usize;
+        let set = &self.per_frame_sets[fid];
+
+        let extent = input.normal_depth.info().extent.into_2d();
+
+        let images = [
+            &input.albedo,
+            &input.normal_depth,
+            &input.emissive,
+            &input.direct,
+            &input.diffuse,
+        ];
+
+        let mut writes = BVec::new_in(bump);
+
+        for (binding, (slot, image)) in
+            self.images[fid].iter_mut().zip(images.iter()).enumerate()
+        {
+            match slot {
+                Some(view) if view.info().image == **image => continue,
+                _ => {}
+            }
+
+            *slot = None;
+            let view =
+                ctx.create_image_view(ImageViewInfo::new((*image).clone()))?;
+            let view = slot.get_or_insert(view);
+
+            writes.push(WriteDescriptorSet {
+                set,
+                binding: binding as u32,
+                element: 0,
+                descriptors: Descriptors::StorageImage(
+                    bump.alloc([(view.clone(), Layout::General)]),
+                ),
+            });
+        }
+
+        ctx.update_descriptor_sets(&writes, &[]);
+
+        let mut encoder = ctx.queue.create_encoder()?;
+
+        // Images are written by ray-tracing prepass.
+        let barriers: &[ImageMemoryBarrier<'_>] =
+            bump.alloc_slice_fill_iter(images.iter().map(|image| {
+                ImageLayoutTransition::transition_whole(
+                    image,
+                    Layout::ShaderReadOnlyOptimal..Layout::General,
+                )
+                .into()
+            }));
+
+        encoder.image_barriers(
+            PipelineStageFlags::RAY_TRACING_SHADER,
+            PipelineStageFlags::COMPUTE_SHADER,
+            barriers,
+        );
+
+        encoder.bind_compute_pipeline(&self.pipeline);
+        encoder.bind_compute_descriptor_sets(
+            &self.pipeline_layout,
+            0,
+            std::slice::from_ref(set),
+            &[],
+        );
+        encoder.push_constants(
+            &self.pipeline_layout,
+            ShaderStageFlags::COMPUTE,
+            0,
+            bump.alloc([Params {
+                extent: [extent.width, extent.height],
+                checkerboard: input.checkerboard,
+            }]),
+        );
+        encoder.dispatch((extent.width + 7) / 8, (extent.height + 7) / 8, 1);
+
+        let barriers: &[ImageMemoryBarrier<'_>] =
+            bump.alloc_slice_fill_iter(images.iter().map(|image| {
+                ImageLayoutTransition::transition_whole(
+                    image,
+                    Layout::General..Layout::ShaderReadOnlyOptimal,
+                )
+                .into()
+            }));
+
+        encoder.image_barriers(
+            PipelineStageFlags::COMPUTE_SHADER,
+            PipelineStageFlags::FRAGMENT_SHADER
+                | PipelineStageFlags::COMPUTE_SHADER
+                | PipelineStageFlags::RAY_TRACING_SHADER,
+            barriers,
+        );
+
+        ctx.record_barriers(encoder.barrier_count());
+        ctx.queue.submit(wait, encoder.finish()?, signal, fence)?;
+
+        Ok(())
+    }
+}
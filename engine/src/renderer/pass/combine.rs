@@ -1,21 +1,79 @@
 use {
     super::Pass,
-    crate::renderer::Context,
+    crate::{
+        light::Fog,
+        renderer::{
+            overlay::{overlay_font, TextOverlay, MAX_OVERLAY_CELLS},
+            Context, SwapchainFrame,
+        },
+    },
     bumpalo::{collections::Vec as BVec, Bump},
+    bytemuck::{Pod, Zeroable},
     color_eyre::Report,
     hecs::World,
     illume::*,
     lru::LruCache,
     smallvec::smallvec,
+    std::mem::size_of,
 };
 
-pub struct Input {
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct PushConstants {
+    screen_size: [u32; 2],
+    exposure: f32,
+    fog_density: f32,
+    fog_color: [f32; 3],
+    _pad: f32,
+    screen_offset: [i32; 2],
+}
+
+unsafe impl Zeroable for PushConstants {}
+unsafe impl Pod for PushConstants {}
+
+/// Size of overlay grid at the beginning of overlay buffer region.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct OverlayHeader {
+    cols: u32,
+    rows: u32,
+    scale: u32,
+    _pad: u32,
+}
+
+unsafe impl Zeroable for OverlayHeader {}
+unsafe impl Pod for OverlayHeader {}
+
+pub struct Input<'a> {
     pub albedo: Image,
     pub normal_depth: Image,
     pub emissive: Image,
     pub direct: Image,
     pub diffuse: Image,
     pub combined: Image,
+
+    /// Region of combined image to draw into.
+    pub region: Rect2d,
+
+    /// Layout of combined image before the pass.
+    /// Image is cleared if `None`, otherwise content outside of
+    /// the region is preserved.
+    pub initial_layout: Option<Layout>,
+
+    /// Ambient occlusion applied to diffuse lighting.
+    pub ao: Option<Image>,
+
+    /// Reflected radiance blended by weight in alpha channel.
+    pub reflection: Option<Image>,
+
+    /// Multiplier for combined radiance before tonemapping.
+    pub exposure: f32,
+
+    /// Distance fog applied before tonemapping.
+    pub fog: Option<Fog>,
+
+    /// Text drawn over combined image.
+    pub overlay: Option<&'a TextOverlay>,
 }
 
 pub struct Output;
@@ -27,6 +85,14 @@ pub struct CombinePass {
     emissive: [Option<ImageView>; 2],
     direct: [Option<ImageView>; 2],
     diffuse: [Option<ImageView>; 2],
+    ao: [Option<ImageView>; 2],
+    reflection: [Option<ImageView>; 2],
+
+    /// Bound in place of missing ambient occlusion.
+    white: ImageView,
+
+    /// Bound in place of missing reflections.
+    black: ImageView,
 
     framebuffer: LruCache<Image, Framebuffer>,
 
@@ -38,6 +104,9 @@ pub struct CombinePass {
 
     pipeline_layout: PipelineLayout,
     per_frame_sets: [DescriptorSet; 2],
+
+    /// Overlay header, font and cells for each frame.
+    overlay_buffer: MappableBuffer,
 }
 
 impl CombinePass {
@@ -87,6 +156,30 @@ impl CombinePass {
                         stages: ShaderStageFlags::FRAGMENT,
                         flags: DescriptorBindingFlags::empty(),
                     },
+                    // overlay
+                    DescriptorSetLayoutBinding {
+                        binding: 5,
+                        ty: DescriptorType::StorageBuffer,
+                        count: 1,
+                        stages: ShaderStageFlags::FRAGMENT,
+                        flags: DescriptorBindingFlags::empty(),
+                    },
+                    // ambient occlusion
+                    DescriptorSetLayoutBinding {
+                        binding: 6,
+                        ty: DescriptorType::CombinedImageSampler,
+                        count: 1,
+                        stages: ShaderStageFlags::FRAGMENT,
+                        flags: DescriptorBindingFlags::empty(),
+                    },
+                    // reflection
+                    DescriptorSetLayoutBinding {
+                        binding: 7,
+                        ty: DescriptorType::CombinedImageSampler,
+                        count: 1,
+                        stages: ShaderStageFlags::FRAGMENT,
+                        flags: DescriptorBindingFlags::empty(),
+                    },
                 ],
             })?;
 
@@ -96,7 +189,7 @@ impl CombinePass {
                 push_constants: vec![PushConstant {
                     stages: ShaderStageFlags::FRAGMENT,
                     offset: 0,
-                    size: 8,
+                    size: size_of::<PushConstants>() as u32,
                 }],
             })?;
 
@@ -122,6 +215,57 @@ impl CombinePass {
             layout: set_layout.clone(),
         })?;
 
+        let mut overlay_buffer = ctx.create_mappable_buffer(
+            BufferInfo {
+                align: 255,
+                size: overlay_region_stride() * 2,
+                usage: BufferUsage::STORAGE,
+                capture_address: None,
+            },
+            MemoryUsage::UPLOAD | MemoryUsage::FAST_DEVICE_ACCESS,
+        )?;
+
+        // Font never changes.
+        let font = overlay_font();
+        for region in 0..2 {
+            ctx.write_buffer(
+                &mut overlay_buffer,
+                overlay_region_offset(region) + overlay_font_offset(),
+                &font[..],
+            )?;
+            ctx.write_buffer(
+                &mut overlay_buffer,
+                overlay_region_offset(region),
+                &[OverlayHeader::zeroed()],
+            )?;
+        }
+
+        ctx.update_descriptor_sets(
+            &[
+                WriteDescriptorSet {
+                    set: &set0,
+                    binding: 5,
+                    element: 0,
+                    descriptors: Descriptors::StorageBuffer(&[(
+                        overlay_buffer.clone(),
+                        overlay_region_offset(0),
+                        overlay_region_stride(),
+                    )]),
+                },
+                WriteDescriptorSet {
+                    set: &set1,
+                    binding: 5,
+                    element: 0,
+                    descriptors: Descriptors::StorageBuffer(&[(
+                        overlay_buffer.clone(),
+                        overlay_region_offset(1),
+                        overlay_region_stride(),
+                    )]),
+                },
+            ],
+            &[],
+        );
+
         let sampler = ctx.create_sampler(SamplerInfo {
             unnormalized_coordinates: false,
             min_lod: 0.0.into(),
@@ -132,6 +276,90 @@ impl CombinePass {
             ..Default::default()
         })?;
 
+        let white = ctx.create_image_static(
+            ImageInfo {
+                extent: Extent2d {
+                    width: 1,
+                    height: 1,
+                }
+                .into(),
+                format: Format::R8Unorm,
+                levels: 1,
+                layers: 1,
+                samples: Samples1,
+                usage: ImageUsage::SAMPLED,
+            },
+            0,
+            0,
+            &[255u8],
+        )?;
+        let white = ctx.create_image_view(ImageViewInfo::new(white))?;
+
+        let black = ctx.create_image_static(
+            ImageInfo {
+                extent: Extent2d {
+                    width: 1,
+                    height: 1,
+                }
+                .into(),
+                format: Format::RGBA8Unorm,
+                levels: 1,
+                layers: 1,
+                samples: Samples1,
+                usage: ImageUsage::SAMPLED,
+            },
+            0,
+            0,
+            &[0u8; 4],
+        )?;
+        let black = ctx.create_image_view(ImageViewInfo::new(black))?;
+
+        ctx.update_descriptor_sets(
+            &[
+                WriteDescriptorSet {
+                    set: &set0,
+                    binding: 6,
+                    element: 0,
+                    descriptors: Descriptors::CombinedImageSampler(&[(
+                        white.clone(),
+                        Layout::General,
+                        sampler.clone(),
+                    )]),
+                },
+                WriteDescriptorSet {
+                    set: &set1,
+                    binding: 6,
+                    element: 0,
+                    descriptors: Descriptors::CombinedImageSampler(&[(
+                        white.clone(),
+                        Layout::General,
+                        sampler.clone(),
+                    )]),
+                },
+                WriteDescriptorSet {
+                    set: &set0,
+                    binding: 7,
+                    element: 0,
+                    descriptors: Descriptors::CombinedImageSampler(&[(
+                        black.clone(),
+                        Layout::General,
+                        sampler.clone(),
+                    )]),
+                },
+                WriteDescriptorSet {
+                    set: &set1,
+                    binding: 7,
+                    element: 0,
+                    descriptors: Descriptors::CombinedImageSampler(&[(
+                        black.clone(),
+                        Layout::General,
+                        sampler.clone(),
+                    )]),
+                },
+            ],
+            &[],
+        );
+
         Ok(CombinePass {
             sampler,
             albedo: [None, None],
@@ -139,6 +367,10 @@ impl CombinePass {
             emissive: [None, None],
             direct: [None, None],
             diffuse: [None, None],
+            ao: [None, None],
+            reflection: [None, None],
+            white,
+            black,
 
             framebuffer: LruCache::new(3),
 
@@ -150,17 +382,19 @@ impl CombinePass {
 
             vert,
             frag,
+
+            overlay_buffer,
         })
     }
 }
 
 impl<'a> Pass<'a> for CombinePass {
-    type Input = Input;
+    type Input = Input<'a>;
     type Output = Output;
 
     fn draw(
         &mut self,
-        input: Input,
+        input: Input<'a>,
         frame: u64,
         wait: &[(PipelineStageFlags, Semaphore)],
         signal: &[Semaphore],
@@ -171,12 +405,14 @@ impl<'a> Pass<'a> for CombinePass {
     ) -> Result<Output, Report> {
         tracing::trace!("CombinePass::draw");
         let combined_info = input.combined.info();
-        let extent = combined_info.extent.into_2d();
+        let extent = input.region.extent;
         let format = combined_info.format;
 
         let render_pass = match &self.render_pass {
             Some(render_pass)
-                if render_pass.info().attachments[0].format == format =>
+                if render_pass.info().attachments[0].format == format
+                    && render_pass.info().attachments[0].initial_layout
+                        == input.initial_layout =>
             {
                 render_pass
             }
@@ -188,14 +424,18 @@ impl<'a> Pass<'a> for CombinePass {
                     attachments: smallvec![AttachmentInfo {
                         format,
                         samples: Samples::Samples1,
-                        load_op: AttachmentLoadOp::Clear,
+                        load_op: match input.initial_layout {
+                            None => AttachmentLoadOp::Clear,
+                            Some(_) => AttachmentLoadOp::Load,
+                        },
                         store_op: AttachmentStoreOp::Store,
-                        initial_layout: None,
-                        final_layout: Layout::Present,
+                        initial_layout: input.initial_layout,
+                        final_layout: SwapchainFrame::FINAL_LAYOUT,
                     }],
                     subpasses: smallvec![Subpass {
                         colors: smallvec![0],
                         depth: None,
+                        resolves: smallvec![],
                     }],
                     dependencies: smallvec![
                         SubpassDependency {
@@ -252,7 +492,7 @@ impl<'a> Pass<'a> for CombinePass {
                 let framebuffer = ctx.create_framebuffer(FramebufferInfo {
                     render_pass: render_pass.clone(),
                     views: smallvec![combined],
-                    extent,
+                    extent: combined_info.extent.into_2d(),
                 })?;
 
                 self.framebuffer
@@ -385,8 +625,114 @@ impl<'a> Pass<'a> for CombinePass {
             }
         }
 
+        match (&self.ao[fid as usize], &input.ao) {
+            (Some(ao), Some(image)) if ao.info().image == *image => {}
+            (None, None) => {}
+            (_, Some(image)) => {
+                self.ao[fid as usize] = None;
+                let ao =
+                    ctx.create_image_view(ImageViewInfo::new(image.clone()))?;
+                let ao = self.ao[fid as usize].get_or_insert(ao);
+                writes.push(WriteDescriptorSet {
+                    set,
+                    binding: 6,
+                    element: 0,
+                    descriptors: Descriptors::CombinedImageSampler(bump.alloc(
+                        [(
+                            ao.clone(),
+                            Layout::ShaderReadOnlyOptimal,
+                            self.sampler.clone(),
+                        )],
+                    )),
+                });
+            }
+            (Some(_), None) => {
+                self.ao[fid as usize] = None;
+                writes.push(WriteDescriptorSet {
+                    set,
+                    binding: 6,
+                    element: 0,
+                    descriptors: Descriptors::CombinedImageSampler(bump.alloc(
+                        [(
+                            self.white.clone(),
+                            Layout::General,
+                            self.sampler.clone(),
+                        )],
+                    )),
+                });
+            }
+        }
+
+        match (&self.reflection[fid as usize], &input.reflection) {
+            (Some(reflection), Some(image))
+                if reflection.info().image == *image => {}
+            (None, None) => {}
+            (_, Some(image)) => {
+                self.reflection[fid as usize] = None;
+                let reflection =
+                    ctx.create_image_view(ImageViewInfo::new(image.clone()))?;
+                let reflection =
+                    self.reflection[fid as usize].get_or_insert(reflection);
+                writes.push(WriteDescriptorSet {
+                    set,
+                    binding: 7,
+                    element: 0,
+                    descriptors: Descriptors::CombinedImageSampler(bump.alloc(
+                        [(
+                            reflection.clone(),
+                            Layout::ShaderReadOnlyOptimal,
+                            self.sampler.clone(),
+                        )],
+                    )),
+                });
+            }
+            (Some(_), None) => {
+                self.reflection[fid as usize] = None;
+                writes.push(WriteDescriptorSet {
+                    set,
+                    binding: 7,
+                    element: 0,
+                    descriptors: Descriptors::CombinedImageSampler(bump.alloc(
+                        [(
+                            self.black.clone(),
+                            Layout::General,
+                            self.sampler.clone(),
+                        )],
+                    )),
+                });
+            }
+        }
+
         ctx.update_descriptor_sets(&writes, &[]);
 
+        let region = overlay_region_offset(fid as u64);
+        match input.overlay {
+            Some(overlay) if !overlay.cells().is_empty() => {
+                ctx.write_buffer(
+                    &mut self.overlay_buffer,
+                    region,
+                    &[OverlayHeader {
+                        cols: overlay.cols(),
+                        rows: overlay.rows(),
+                        scale: overlay.scale(),
+                        _pad: 0,
+                    }],
+                )?;
+                ctx.write_buffer(
+                    &mut self.overlay_buffer,
+                    region + overlay_cells_offset(),
+                    overlay.cells(),
+                )?;
+            }
+            _ => {
+                ctx.write_buffer(
+                    &mut self.overlay_buffer,
+                    region,
+                    &[OverlayHeader::zeroed()],
+                )?;
+            }
+        }
+
         let mut encoder = ctx.queue.create_encoder()?;
 
         let mut render_pass_encoder = encoder.with_render_pass(
@@ -403,20 +749,28 @@ impl<'a> Pass<'a> for CombinePass {
             &[],
         );
 
-        let extent_push_constant = [extent.width, extent.height];
+        let push_constants = PushConstants {
+            screen_size: [extent.width, extent.height],
+            exposure: input.exposure,
+            fog_density: input.fog.map_or(0.0, |fog| fog.density),
+            fog_color: input.fog.map_or([0.0; 3], |fog| fog.color),
+            _pad: 0.0,
+            screen_offset: [input.region.offset.x, input.region.offset.y],
+        };
+
         render_pass_encoder.push_constants(
             &self.pipeline_layout,
-            ShaderStageFlags::VERTEX,
+            ShaderStageFlags::FRAGMENT,
             0,
-            &extent_push_constant,
+            std::slice::from_ref(&push_constants),
         );
         render_pass_encoder.set_viewport(Viewport {
             x: Bounds {
-                offset: 0.0.into(),
+                offset: (input.region.offset.x as f32).into(),
                 size: (extent.width as f32).into(),
             },
             y: Bounds {
-                offset: 0.0.into(),
+                offset: (input.region.offset.y as f32).into(),
                 size: (extent.height as f32).into(),
             },
             z: Bounds {
@@ -425,11 +779,29 @@ impl<'a> Pass<'a> for CombinePass {
             },
         });
 
-        render_pass_encoder.set_scissor(extent.into());
+        render_pass_encoder.set_scissor(input.region);
         render_pass_encoder.draw(0..3, 0..1);
         drop(render_pass_encoder);
-        ctx.queue.submit(wait, encoder.finish(), signal, fence);
+        ctx.record_barriers(encoder.barrier_count());
+        ctx.queue.submit(wait, encoder.finish()?, signal, fence)?;
 
         Ok(Output)
     }
 }
+
+const fn overlay_font_offset() -> u64 {
+    size_of::<OverlayHeader>() as u64
+}
+
+const fn overlay_cells_offset() -> u64 {
+    overlay_font_offset() + 768
+}
+
+const fn overlay_region_stride() -> u64 {
+    let size = overlay_cells_offset() + (MAX_OVERLAY_CELLS * 4) as u64;
+    (size + 255) & !255
+}
+
+const fn overlay_region_offset(region: u64) -> u64 {
+    overlay_region_stride() * region
+}
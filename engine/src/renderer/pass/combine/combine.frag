@@ -5,18 +5,71 @@ layout(binding = 1, set = 0) uniform sampler2D normals_depth;
 layout(binding = 2, set = 0) uniform sampler2D emissive;
 layout(binding = 3, set = 0) uniform sampler2D direct;
 layout(binding = 4, set = 0) uniform sampler2D diffuse;
+layout(binding = 6, set = 0) uniform sampler2D ambient_occlusion;
+layout(binding = 7, set = 0) uniform sampler2D reflection;
+
+// Text overlay.
+// Each cell has ASCII code in low byte and RGB color in upper bytes.
+layout(binding = 5, set = 0, std430) readonly buffer Overlay {
+    uvec4 overlay_size; // cols, rows, scale
+    uint overlay_font[192]; // 96 glyphs starting at ' ', 8 rows each
+    uint overlay_cells[];
+};
 
 layout(location = 0) out vec4 output_color;
 
-layout(push_constant) uniform push_constants { uvec2 screen_size; };
+layout(push_constant) uniform push_constants {
+    uvec2 screen_size;
+    float exposure;
+    float fog_density;
+    vec3 fog_color;
+    ivec2 screen_offset;
+};
+
+// Distance assumed for rays that hit nothing.
+const float FOG_FAR = 1000.0;
 
 void main() {
-    vec3 albedo = texture(albedo, gl_FragCoord.xy / screen_size).rgb;
-    vec3 emissive = texture(emissive, gl_FragCoord.xy / screen_size).rgb;
-    vec3 direct = texture(direct, gl_FragCoord.xy / screen_size).rgb;
-    vec4 normals_depth = texture(normals_depth, gl_FragCoord.xy / screen_size);
-    vec3 diffuse = texture(diffuse, gl_FragCoord.xy / screen_size).xyz;
+    vec2 uv = (gl_FragCoord.xy - screen_offset) / screen_size;
+    vec3 albedo = texture(albedo, uv).rgb;
+    vec3 emissive = texture(emissive, uv).rgb;
+    vec3 direct = texture(direct, uv).rgb;
+    vec4 normals_depth = texture(normals_depth, uv);
+    vec3 diffuse = texture(diffuse, uv).xyz;
+    float ao = texture(ambient_occlusion, uv).r;
+    vec4 reflection = texture(reflection, uv);
     // direct *= dot(normals_depth.xyz, vec3(0, 1, 0));
-    vec3 combined = albedo * (direct + diffuse) + emissive;
-    output_color = vec4(combined / (vec3(1, 1, 1) + combined), 1);
+    vec3 combined = albedo * (direct + diffuse * ao) + emissive;
+    combined = mix(combined, reflection.rgb, reflection.a) * exposure;
+    if (fog_density > 0.0) {
+        float distance = normals_depth.w < 0.0 ? FOG_FAR : normals_depth.w;
+        float transmittance = exp(-fog_density * distance);
+        combined = mix(fog_color * exposure, combined, transmittance);
+    }
+    vec3 color = combined / (vec3(1, 1, 1) + combined);
+
+    uvec2 texel = uvec2(gl_FragCoord.xy) / max(overlay_size.z, 1);
+    uvec2 cell = texel / 8;
+    if (cell.x < overlay_size.x && cell.y < overlay_size.y) {
+        uint code = overlay_cells[cell.y * overlay_size.x + cell.x];
+        if (code != 0) {
+            color *= 0.4;
+
+            uint glyph = code & 0xff;
+            if (glyph > 32 && glyph < 128) {
+                uvec2 px = texel % 8;
+                uint byte_index = (glyph - 32) * 8 + px.y;
+                uint bits = overlay_font[byte_index / 4] >> ((byte_index % 4) * 8);
+                if (((bits >> px.x) & 1) != 0) {
+                    color = vec3(
+                        (code >> 8) & 0xff,
+                        (code >> 16) & 0xff,
+                        (code >> 24) & 0xff
+                    ) / 255.0;
+                }
+            }
+        }
+    }
+
+    output_color = vec4(color, 1);
 }
@@ -0,0 +1,25 @@
+#extension GL_EXT_scalar_block_layout : enable
+#extension GL_ARB_gpu_shader_int64 : enable
+
+// Element of object table, one per renderable.
+// Matches `ShaderObject`.
+struct Object {
+    mat4 transform;
+    mat4 prev_transform;
+
+    // Device addresses of vertices and indices of the mesh.
+    // Zero if buffer has no device address.
+    uint64_t vertices;
+    uint64_t indices;
+
+    // Persistent index of the material.
+    uint material;
+};
+
+#ifndef OBJECTS_SET
+#define OBJECTS_SET 0
+#endif
+
+layout(binding = 0, set = OBJECTS_SET, scalar) readonly buffer Objects {
+    Object objects[];
+};
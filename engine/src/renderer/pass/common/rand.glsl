@@ -114,8 +114,11 @@ vec3 nice_rand(uvec4 co) {
 // }
 
 vec3 blue_rand(uvec4 co) {
-    uint x = co.x & 255;
-    uint y = co.y & 255;
+    // Texture is shifted every 128 frames,
+    // so accumulated frames keep getting new samples.
+    uint cycle = co.z >> 7;
+    uint x = (co.x + cycle * 97) & 255;
+    uint y = (co.y + cycle * 157) & 255;
     uint z = (co.z + co.w) & 127;
 
     uint index = x + y * 256 + z * 65536;
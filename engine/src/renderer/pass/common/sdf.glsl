@@ -0,0 +1,98 @@
+// Sparse global signed distance field of static geometry.
+// Written by global SDF pass. Matches constants in `global_sdf.rs`.
+//
+// Space is divided into bricks, those near static geometry
+// have 8x8x8 distance samples in atlas, spanning brick including
+// its far faces so samples are interpolated within brick only.
+// Bricks are addressed toroidally within window around the camera.
+
+#ifndef SDF_SET
+#define SDF_SET 2
+#endif
+
+const int SDF_GRID = 64;
+const int SDF_ATLAS = 32;
+const int SDF_BRICK_TEXELS = 8;
+const float SDF_BRICK_SIZE = 2.0;
+
+// Distance reported outside of bricks near geometry.
+const float SDF_FAR = 1.0;
+
+layout(binding = 0, set = SDF_SET, std430) readonly buffer SdfBricks {
+    // Window origin in bricks.
+    ivec4 sdf_origin;
+
+    // Atlas slot plus one for each brick of window, zero for empty bricks.
+    uint sdf_bricks[];
+};
+
+layout(binding = 1, set = SDF_SET) uniform sampler3D sdf_atlas;
+
+uint sdf_cell(ivec3 brick) {
+    ivec3 cell = ((brick % SDF_GRID) + SDF_GRID) % SDF_GRID;
+    return uint(cell.x + cell.y * SDF_GRID + cell.z * SDF_GRID * SDF_GRID);
+}
+
+ivec3 sdf_slot_texel(uint slot) {
+    ivec3 coord = ivec3(slot % SDF_ATLAS, (slot / SDF_ATLAS) % SDF_ATLAS, slot / (SDF_ATLAS * SDF_ATLAS));
+    return coord * SDF_BRICK_TEXELS;
+}
+
+// Signed distance to static geometry at world space point.
+// Negative behind the surface.
+float global_sdf(vec3 p) {
+    ivec3 brick = ivec3(floor(p / SDF_BRICK_SIZE));
+    ivec3 local = brick - sdf_origin.xyz;
+    if (any(lessThan(local, ivec3(0))) || any(greaterThanEqual(local, ivec3(SDF_GRID)))) {
+        return SDF_FAR;
+    }
+
+    uint entry = sdf_bricks[sdf_cell(brick)];
+    if (entry == 0) {
+        return SDF_FAR;
+    }
+
+    vec3 f = p / SDF_BRICK_SIZE - vec3(brick);
+    vec3 texel = vec3(sdf_slot_texel(entry - 1)) + 0.5 + f * float(SDF_BRICK_TEXELS - 1);
+    return textureLod(sdf_atlas, texel / vec3(textureSize(sdf_atlas, 0)), 0.0).r;
+}
+
+// Gradient of the field, pointing away from the surface.
+vec3 global_sdf_normal(vec3 p) {
+    const float h = SDF_BRICK_SIZE / float(SDF_BRICK_TEXELS - 1) * 0.5;
+    vec3 n = vec3(
+        global_sdf(p + vec3(h, 0, 0)) - global_sdf(p - vec3(h, 0, 0)),
+        global_sdf(p + vec3(0, h, 0)) - global_sdf(p - vec3(0, h, 0)),
+        global_sdf(p + vec3(0, 0, h)) - global_sdf(p - vec3(0, 0, h))
+    );
+    float len = length(n);
+    return len > 0.0 ? n / len : vec3(0, 1, 0);
+}
+
+// Soft shadow factor along the ray towards the light.
+// Penumbra widens with smaller `k`.
+float global_sdf_shadow(vec3 origin, vec3 dir, float max_dist, float k) {
+    float shadow = 1.0;
+    float t = SDF_BRICK_SIZE / float(SDF_BRICK_TEXELS - 1);
+    for (int i = 0; i < 32 && t < max_dist; ++i) {
+        float d = global_sdf(origin + dir * t);
+        if (d <= 0.001) {
+            return 0.0;
+        }
+        shadow = min(shadow, k * d / t);
+        t += max(d, 0.05);
+    }
+    return clamp(shadow, 0.0, 1.0);
+}
+
+// Approximate ambient occlusion from few samples along the normal.
+float global_sdf_ao(vec3 p, vec3 n) {
+    float occlusion = 0.0;
+    float weight = 1.0;
+    for (int i = 1; i <= 5; ++i) {
+        float h = 0.2 * float(i);
+        occlusion += weight * (h - global_sdf(p + n * h));
+        weight *= 0.5;
+    }
+    return clamp(1.0 - occlusion, 0.0, 1.0);
+}
@@ -0,0 +1,15 @@
+// Globals of the view shared by all passes.
+// Written once per frame by renderer and bound as set 1
+// with dynamic offset. Matches `ViewGlobals`.
+layout(binding = 0, set = 1, std140) uniform ViewGlobals {
+    mat4 view;
+    mat4 iview;
+    mat4 proj;
+    mat4 iproj;
+    mat4 prev_view;
+    mat4 prev_iview;
+    uvec2 extent;
+    uint frame;
+    float time;
+    vec2 jitter;
+} view_globals;
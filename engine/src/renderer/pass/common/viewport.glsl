@@ -1,16 +1,66 @@
 
 
+// Ray flags for primary rays. Including shader may override them.
+#ifndef VIEWPORT_RAY_FLAGS
+#define VIEWPORT_RAY_FLAGS 0
+#endif
+
+// Index of path traced for the pixel. Including shader may override it.
+#ifndef VIEWPORT_SAMPLE
+#define VIEWPORT_SAMPLE 0
+#endif
+
+// Pixel and extent of the viewport. Including shader may override them
+// when launch doesn't map to pixels one to one.
+#ifndef VIEWPORT_PIXEL
+#define VIEWPORT_PIXEL gl_LaunchIDEXT.xy
+#endif
+
+#ifndef VIEWPORT_EXTENT
+#define VIEWPORT_EXTENT gl_LaunchSizeEXT.xy
+#endif
+
 void traceViewportPixelRay()
 {
     const uint shadow_ray_flags = gl_RayFlagsOpaqueEXT | gl_RayFlagsTerminateOnFirstHitEXT | gl_RayFlagsSkipClosestHitShaderEXT;
-    const uvec3 co = uvec3(gl_LaunchIDEXT.xy, globals.frame);
-    const vec2 pixelCenter = vec2(gl_LaunchIDEXT.xy);
-    const vec2 inUV = pixelCenter/vec2(gl_LaunchSizeEXT.xy);
+    const uvec3 co = uvec3(VIEWPORT_PIXEL, globals.frame + VIEWPORT_SAMPLE);
+    const vec2 pixelCenter = vec2(VIEWPORT_PIXEL);
+    const vec2 inUV = pixelCenter/vec2(VIEWPORT_EXTENT);
     vec2 d = inUV * 2.0 - 1.0;
-    vec4 proj  = globals.cam.iproj * vec4(d.x, -d.y, -1, 1);
-    vec4 target = globals.cam.view * vec4(normalize(proj.xyz), 1.0);
 
-    vec4 origin = globals.cam.view[3];
+    // Unproject points on near plane and in the middle of depth range.
+    // This works for perspective, orthographic and oblique projections alike.
+    vec4 near = globals.cam.iproj * vec4(d.x, -d.y, -1, 1);
+    vec4 far = globals.cam.iproj * vec4(d.x, -d.y, 0, 1);
+
+    // Ray in camera space.
+    vec3 origin = near.xyz / near.w;
+    vec3 direction = normalize(far.xyz / far.w - origin);
+
+#ifdef VIEWPORT_DEPTH_OF_FIELD
+    // Thin lens model.
+    // Rays through any point of the lens converge on the plane in focus.
+    if (globals.lens_radius > 0.0)
+    {
+        vec3 focus = origin + direction * ((globals.focus_distance + origin.z) / -direction.z);
+        origin.xy += rand_circle(blue_rand(uvec4(co, 2048))) * globals.lens_radius;
+        direction = normalize(focus - origin);
+    }
+#endif
+
+    mat4 view = globals.cam.view;
+
+#ifdef VIEWPORT_MOTION_BLUR
+    // Camera moves from previous frame transform while shutter is open.
+    if (globals.motion_blur > 0.0)
+    {
+        float t = blue_rand(uvec4(co, 2049)).x * globals.motion_blur;
+        view = view * (1.0 - t) + globals.prev_view * t;
+    }
+#endif
+
+    origin = (view * vec4(origin, 1.0)).xyz;
+    direction = normalize((view * vec4(direction, 0.0)).xyz);
 
-    traceRayEXT(tlas, 0, 0xff, 0, 0, 0, origin.xyz, 0.0, normalize((target - origin).xyz), 1000.0, 0);
+    traceRayEXT(tlas, VIEWPORT_RAY_FLAGS, 0xff, 0, 0, 0, origin, 0.0, direction, 1000.0, 0);
 }
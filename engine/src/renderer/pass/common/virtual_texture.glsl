@@ -0,0 +1,44 @@
+// Virtual texture addressing.
+// Including file must declare `VirtualTexture`.
+//
+// Page table level `l` has an entry for each page of virtual level `l`:
+// atlas slot x and y, level of resident page and residency flag.
+// Missing pages point to nearest resident ancestor.
+
+// Returns texels of finest level per world unit on triangle.
+float virtual_texture_density(VirtualTexture vt, vec3 p0, vec3 p1, vec3 p2, vec2 uv0, vec2 uv1, vec2 uv2) {
+    vec3 w0 = gl_ObjectToWorldEXT * vec4(p0, 1.0);
+    vec3 w1 = gl_ObjectToWorldEXT * vec4(p1, 1.0);
+    vec3 w2 = gl_ObjectToWorldEXT * vec4(p2, 1.0);
+    float world_area = length(cross(w1 - w0, w2 - w0));
+
+    vec2 e1 = (uv1 - uv0) * vt.uv_scale;
+    vec2 e2 = (uv2 - uv0) * vt.uv_scale;
+    float uv_area = abs(e1.x * e2.y - e2.x * e1.y);
+
+    float texels = float(vt.pages * (vt.page_size - 2 * vt.border));
+    return sqrt(uv_area / max(world_area, 1e-12)) * texels;
+}
+
+// Returns level at which texels of the triangle match ray cone
+// with specified spread angle at hit distance.
+float virtual_texture_level(VirtualTexture vt, float density, float spread) {
+    float footprint = gl_HitTEXT * spread * density;
+    return clamp(log2(max(footprint, 1.0)), 0.0, float(vt.levels - 1));
+}
+
+ivec2 virtual_page(VirtualTexture vt, vec2 uv, uint level) {
+    vec2 vuv = clamp(uv * vt.uv_scale, 0.0, 0.99999);
+    return ivec2(vuv * float(max(vt.pages >> level, 1)));
+}
+
+// Returns atlas coordinates of `uv` in page pointed by page table entry.
+vec2 virtual_atlas_uv(VirtualTexture vt, vec4 entry, vec2 uv) {
+    uvec3 slot = uvec3(round(entry.rgb * 255.0));
+    float resident_pages = float(max(vt.pages >> slot.z, 1));
+    vec2 in_page = fract(clamp(uv * vt.uv_scale, 0.0, 0.99999) * resident_pages);
+
+    float inner = float(vt.page_size - 2 * vt.border);
+    vec2 texel = vec2(slot.xy) * float(vt.page_size) + float(vt.border) + in_page * inner;
+    return texel / float(vt.atlas_pages * vt.page_size);
+}
@@ -0,0 +1,362 @@
+use {
+    super::Pass,
+    crate::renderer::{Context, DenoiseImage, ExternalDenoiser},
+    bumpalo::{collections::Vec as BVec, Bump},
+    bytemuck::{Pod, Zeroable},
+    color_eyre::Report,
+    eyre::eyre,
+    hecs::World,
+    illume::*,
+    std::mem::size_of,
+};
+
+pub struct Input {
+    pub albedo: Image,
+
+    /// Normals and depth in ray-tracing prepass layout.
+    pub normal_depth: Image,
+
+    pub direct: Image,
+    pub diffuse: Image,
+}
+
+pub struct Output {
+    /// Denoised direct radiance.
+    /// Alpha channel is copied from input.
+    pub direct: Image,
+
+    /// Denoised indirect diffuse radiance.
+    pub diffuse: Image,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct Extent {
+    extent: [u32; 2],
+}
+
+unsafe impl Zeroable for Extent {}
+unsafe impl Pod for Extent {}
+
+/// Number of planes written by export shader.
+const PLANES: usize = 4;
+
+/// Exports noisy radiance, albedo and normals to the host,
+/// denoises them with first `ExternalDenoiser` in the world
+/// and uploads result into images.
+///
+/// Unlike other passes this one blocks until
+/// the export is complete.
+pub struct ExternalDenoisePass {
+    sampler: Sampler,
+
+    /// Albedo, normal-depth, direct and diffuse views.
+    inputs: [[Option<ImageView>; PLANES]; 2],
+
+    pipeline: ComputePipeline,
+    pipeline_layout: PipelineLayout,
+    per_frame_sets: [DescriptorSet; 2],
+
+    export: Option<Export>,
+    fence: Fence,
+}
+
+struct Export {
+    extent: Extent2d,
+    buffer: MappableBuffer,
+    texels: Vec<[f32; 4]>,
+    direct: Image,
+    diffuse: Image,
+}
+
+impl ExternalDenoisePass {
+    pub fn new(ctx: &mut Context) -> Result<Self, Report> {
+        let set_layout =
+            ctx.create_descriptor_set_layout(DescriptorSetLayoutInfo {
+                flags: DescriptorSetLayoutFlags::UPDATE_AFTER_BIND_POOL,
+                bindings: (0..PLANES as u32 + 1)
+                    .map(|binding| DescriptorSetLayoutBinding {
+                        binding,
+                        ty: if binding == PLANES as u32 {
+                            DescriptorType::StorageBuffer
+                        } else {
+                            DescriptorType::CombinedImageSampler
+                        },
+                        count: 1,
+                        stages: ShaderStageFlags::COMPUTE,
+                        flags: DescriptorBindingFlags::empty(),
+                    })
+                    .collect(),
+            })?;
+
+        let pipeline_layout =
+            ctx.create_pipeline_layout(PipelineLayoutInfo {
+                sets: vec![set_layout.clone()],
+                push_constants: vec![PushConstant {
+                    stages: ShaderStageFlags::COMPUTE,
+                    offset: 0,
+                    size: size_of::<Extent>() as u32,
+                }],
+            })?;
+
+        let shader = ComputeShader::with_main(
+            ctx.create_shader_module(
+                Spirv::new(
+                    include_bytes!("external_denoise/export.comp.spv").to_vec(),
+                )
+                .into(),
+            )?,
+        );
+
+        let pipeline = ctx.create_compute_pipeline(ComputePipelineInfo {
+            shader,
+            layout: pipeline_layout.clone(),
+        })?;
+
+        let set0 = ctx.create_descriptor_set(DescriptorSetInfo {
+            layout: set_layout.clone(),
+        })?;
+
+        let set1 = ctx.create_descriptor_set(DescriptorSetInfo {
+            layout: set_layout.clone(),
+        })?;
+
+        let sampler = ctx.create_sampler(SamplerInfo::new())?;
+
+        Ok(ExternalDenoisePass {
+            sampler,
+            inputs: Default::default(),
+            pipeline,
+            pipeline_layout,
+            per_frame_sets: [set0, set1],
+            export: None,
+            fence: ctx.create_fence()?,
+        })
+    }
+}
+
+impl<'a> Pass<'a> for ExternalDenoisePass {
+    type Input = Input;
+    type Output = Output;
+
+    fn draw(
+        &mut self,
+        input: Input,
+        frame: u64,
+        wait: &[(PipelineStageFlags, Semaphore)],
+        signal: &[Semaphore],
+        fence: Option<&Fence>,
+        ctx: &mut Context,
+        world: &mut World,
+        bump: &Bump,
+    ) -> Result<Output, Report> {
+        let fid = (frame % 2) as usize;
+        let set = &self.per_frame_sets[fid];
+
+        let extent = input.normal_depth.info().extent.into_2d();
+        let texels = extent.width as usize * extent.height as usize;
+
+        let mut writes = BVec::new_in(bump);
+
+        match &self.export {
+            Some(export) if export.extent == extent => {}
+            _ => {
+                self.export = None;
+
+                let buffer = ctx.create_mappable_buffer(
+                    BufferInfo {
+                        align: 255,
+                        size: (size_of::<[f32; 4]>() * texels * PLANES) as u64,
+                        usage: BufferUsage::STORAGE,
+                        capture_address: None,
+                    },
+                    MemoryUsage::DOWNLOAD,
+                )?;
+
+                let mut create_output = || {
+                    ctx.create_image(ImageInfo {
+                        extent: extent.into(),
+                        format: Format::RGBA32Sfloat,
+                        levels: 1,
+                        layers: 1,
+                        samples: Samples1,
+                        usage: ImageUsage::TRANSFER_DST | ImageUsage::SAMPLED,
+                    })
+                };
+
+                let direct = create_output()?;
+                let diffuse = create_output()?;
+
+                for set in &self.per_frame_sets {
+                    writes.push(WriteDescriptorSet {
+                        set,
+                        binding: PLANES as u32,
+                        element: 0,
+                        descriptors: Descriptors::StorageBuffer(
+                            bump.alloc([(
+                                buffer.share(),
+                                0,
+                                buffer.info().size,
+                            )]),
+                        ),
+                    });
+                }
+
+                self.export = Some(Export {
+                    extent,
+                    buffer,
+                    texels: vec![[0.0; 4]; texels * PLANES],
+                    direct,
+                    diffuse,
+                });
+            }
+        }
+
+        let export = self.export.as_mut().unwrap();
+
+        let images = [
+            &input.albedo,
+            &input.normal_depth,
+            &input.direct,
+            &input.diffuse,
+        ];
+
+        for (binding, (slot, image)) in
+            self.inputs[fid].iter_mut().zip(images.iter()).enumerate()
+        {
+            match slot {
+                Some(view) if view.info().image == **image => continue,
+                _ => {}
+            }
+
+            *slot = None;
+            let view =
+                ctx.create_image_view(ImageViewInfo::new((*image).clone()))?;
+            let view = slot.get_or_insert(view);
+
+            writes.push(WriteDescriptorSet {
+                set,
+                binding: binding as u32,
+                element: 0,
+                descriptors: Descriptors::CombinedImageSampler(bump.alloc([(
+                    view.clone(),
+                    Layout::ShaderReadOnlyOptimal,
+                    self.sampler.clone(),
+                )])),
+            });
+        }
+
+        ctx.update_descriptor_sets(&writes, &[]);
+
+        let mut encoder = ctx.queue.create_encoder()?;
+
+        // Inputs are written by ray-tracing prepass.
+        encoder.pipeline_barrier(
+            PipelineStageFlags::RAY_TRACING_SHADER,
+            PipelineStageFlags::COMPUTE_SHADER,
+        );
+
+        encoder.bind_compute_pipeline(&self.pipeline);
+        encoder.bind_compute_descriptor_sets(
+            &self.pipeline_layout,
+            0,
+            std::slice::from_ref(set),
+            &[],
+        );
+        encoder.push_constants(
+            &self.pipeline_layout,
+            ShaderStageFlags::COMPUTE,
+            0,
+            bump.alloc([Extent {
+                extent: [extent.width, extent.height],
+            }]),
+        );
+        encoder.dispatch((extent.width + 7) / 8, (extent.height + 7) / 8, 1);
+
+        encoder.pipeline_barrier(
+            PipelineStageFlags::COMPUTE_SHADER,
+            PipelineStageFlags::HOST,
+        );
+
+        ctx.record_barriers(encoder.barrier_count());
+        ctx.queue
+            .submit(wait, encoder.finish()?, &[], Some(&self.fence))?;
+
+        // Fence also covers all earlier submissions,
+        // so previous frame no longer reads output images.
+        ctx.wait_fences(&[&self.fence], true)?;
+        ctx.reset_fences(&[&self.fence])?;
+
+        ctx.read_buffer(&mut export.buffer, 0, &mut export.texels)?;
+
+        let mut denoisers = world.query::<&mut ExternalDenoiser>();
+        let (_, denoiser) = denoisers
+            .iter()
+            .next()
+            .ok_or_else(|| eyre!("No `ExternalDenoiser` in the world"))?;
+
+        let (albedo, rest) = export.texels.split_at(texels);
+        let (normal, rest) = rest.split_at(texels);
+        let (direct, diffuse) = rest.split_at(texels);
+
+        let rgb = |texels: &[[f32; 4]]| {
+            texels
+                .iter()
+                .map(|&[r, g, b, _]| [r, g, b])
+                .collect::<Vec<_>>()
+        };
+
+        let albedo = rgb(albedo);
+        let normal = rgb(normal);
+        let mut output = vec![[0.0; 3]; texels];
+
+        for (color, image) in
+            [(direct, &export.direct), (diffuse, &export.diffuse)].iter()
+        {
+            denoiser.denoise(DenoiseImage {
+                width: extent.width as usize,
+                height: extent.height as usize,
+                color: &rgb(color),
+                albedo: &albedo,
+                normal: &normal,
+                output: &mut output,
+            })?;
+
+            let denoised = output
+                .iter()
+                .zip(color.iter())
+                .map(|(&[r, g, b], &[_, _, _, a])| [r, g, b, a])
+                .collect::<Vec<_>>();
+
+            ctx.upload_image(
+                image,
+                Some(Layout::ShaderReadOnlyOptimal),
+                0,
+                0,
+                ImageSubresourceLayers::color(0, 0..1),
+                Offset3d::ZERO,
+                extent.into_3d(),
+                &denoised,
+            )?;
+        }
+
+        ctx.flush_uploads(bump)?;
+
+        // Uploads are only ordered with following commands,
+        // make them visible to passes that sample outputs.
+        let mut encoder = ctx.queue.create_encoder()?;
+        encoder.pipeline_barrier(
+            PipelineStageFlags::TRANSFER,
+            PipelineStageFlags::COMPUTE_SHADER
+                | PipelineStageFlags::FRAGMENT_SHADER,
+        );
+
+        ctx.record_barriers(encoder.barrier_count());
+        ctx.queue.submit(&[], encoder.finish()?, signal, fence)?;
+
+        Ok(Output {
+            direct: export.direct.clone(),
+            diffuse: export.diffuse.clone(),
+        })
+    }
+}
@@ -108,6 +108,7 @@ impl GaussFilter {
             subpasses: smallvec![Subpass {
                 colors: smallvec![0],
                 depth: None,
+                resolves: smallvec![],
             }],
             dependencies: smallvec![
                 SubpassDependency {
@@ -299,7 +300,7 @@ impl<'a> Pass<'a> for GaussFilter {
         render_pass_encoder.set_scissor(extent.into());
         render_pass_encoder.draw(0..3, 0..1);
         drop(render_pass_encoder);
-        ctx.queue.submit(wait, encoder.finish(), signal, fence);
+        ctx.queue.submit(wait, encoder.finish()?, signal, fence)?;
 
         Ok(Output {
             filtered: filtered.info().image.clone(),
@@ -0,0 +1,781 @@
+use {
+    super::Pass,
+    crate::{
+        renderer::{Context, Mesh, Renderable, StaticGeometry},
+        scene::Global3,
+    },
+    bumpalo::{collections::Vec as BVec, Bump},
+    bytemuck::{Pod, Zeroable},
+    color_eyre::Report,
+    hecs::{Entity, World},
+    illume::*,
+    nalgebra as na,
+    std::{
+        collections::{HashMap, HashSet},
+        mem::size_of,
+    },
+};
+
+/// Side of the window of bricks around the camera.
+/// Matches `SDF_GRID` in `common/sdf.glsl`.
+pub const SDF_GRID: i32 = 64;
+
+/// Side of atlas in bricks.
+/// Matches `SDF_ATLAS` in `common/sdf.glsl`.
+const SDF_ATLAS: u32 = 32;
+
+/// Samples along each side of a brick.
+const BRICK_TEXELS: u32 = 8;
+
+/// World-space side of a brick.
+/// Matches `SDF_BRICK_SIZE` in `common/sdf.glsl`.
+pub const SDF_BRICK_SIZE: f32 = 2.0;
+
+/// Distance band around geometry.
+/// Bricks within it from static geometry bounds are voxelized.
+const BAND: f32 = 1.0;
+
+/// Maximum number of bricks voxelized per frame.
+/// Remaining ones are voxelized in following frames.
+const MAX_BRICK_UPDATES: usize = 256;
+
+/// Distance camera may move from window center, in bricks,
+/// before window is moved.
+const WINDOW_SLACK: i32 = SDF_GRID / 8;
+
+const FLAG_INDEXED: u32 = 1;
+const FLAG_INDEX_U16: u32 = 2;
+
+type Brick = [i32; 3];
+
+pub struct Input {
+    /// Window of bricks is kept around this point.
+    pub camera: na::Point3<f32>,
+}
+
+pub struct Output {
+    /// Set with bricks and atlas as expected by `common/sdf.glsl`.
+    pub set: DescriptorSet,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct Params {
+    brick_min: [f32; 3],
+    scratch: u32,
+    object: u32,
+    triangles: u32,
+    stride: u32,
+    flags: u32,
+    slot: u32,
+}
+
+unsafe impl Zeroable for Params {}
+unsafe impl Pod for Params {}
+
+/// Static renderable voxelized into the field.
+struct StaticEntry {
+    global: Global3,
+    mesh: Mesh,
+
+    /// Inclusive range of bricks renderable's bounds touch.
+    min: Brick,
+    max: Brick,
+    frame: u64,
+}
+
+impl StaticEntry {
+    fn overlaps(&self, brick: &Brick) -> bool {
+        (0..3).all(|i| self.min[i] <= brick[i] && brick[i] <= self.max[i])
+    }
+}
+
+/// Sparse signed distance field of `StaticGeometry` renderables.
+///
+/// Space is divided into bricks, each brick near static geometry
+/// gets slot in 3D atlas with distance samples.
+/// Only bricks within window around the camera are kept.
+/// Bricks are voxelized on GPU when static renderables are added,
+/// removed or changed, e.g. when chunks stream in and out,
+/// and when they enter the window, limited to `MAX_BRICK_UPDATES`
+/// per frame.
+pub struct GlobalSdfPass {
+    /// Window origin followed by atlas slot of each brick of the window.
+    bricks_buffer: Buffer,
+    atlas: Image,
+    scratch: Buffer,
+    initialized: bool,
+
+    statics: HashMap<Entity, StaticEntry>,
+    bricks: HashMap<Brick, u32>,
+    dirty: HashSet<Brick>,
+    free_slots: Vec<u32>,
+    next_slot: u32,
+
+    /// Origin of the window in bricks.
+    origin: Option<Brick>,
+    frame: u64,
+
+    voxelize: ComputePipeline,
+    resolve: ComputePipeline,
+    pipeline_layout: PipelineLayout,
+    build_set: DescriptorSet,
+
+    layout: DescriptorSetLayout,
+    set: DescriptorSet,
+}
+
+impl GlobalSdfPass {
+    pub fn new(ctx: &mut Context) -> Result<Self, Report> {
+        let layout =
+            ctx.create_descriptor_set_layout(DescriptorSetLayoutInfo {
+                flags: DescriptorSetLayoutFlags::empty(),
+                bindings: vec![
+                    // Bricks
+                    DescriptorSetLayoutBinding {
+                        binding: 0,
+                        ty: DescriptorType::StorageBuffer,
+                        count: 1,
+                        stages: ShaderStageFlags::COMPUTE
+                            | ShaderStageFlags::FRAGMENT
+                            | ShaderStageFlags::RAYGEN,
+                        flags: DescriptorBindingFlags::empty(),
+                    },
+                    // Atlas
+                    DescriptorSetLayoutBinding {
+                        binding: 1,
+                        ty: DescriptorType::CombinedImageSampler,
+                        count: 1,
+                        stages: ShaderStageFlags::COMPUTE
+                            | ShaderStageFlags::FRAGMENT
+                            | ShaderStageFlags::RAYGEN,
+                        flags: DescriptorBindingFlags::empty(),
+                    },
+                ],
+            })?;
+
+        let build_layout =
+            ctx.create_descriptor_set_layout(DescriptorSetLayoutInfo {
+                flags: DescriptorSetLayoutFlags::empty(),
+                bindings: vec![
+                    // Scratch
+                    DescriptorSetLayoutBinding {
+                        binding: 0,
+                        ty: DescriptorType::StorageBuffer,
+                        count: 1,
+                        stages: ShaderStageFlags::COMPUTE,
+                        flags: DescriptorBindingFlags::empty(),
+                    },
+                    // Atlas
+                    DescriptorSetLayoutBinding {
+                        binding: 1,
+                        ty: DescriptorType::StorageImage,
+                        count: 1,
+                        stages: ShaderStageFlags::COMPUTE,
+                        flags: DescriptorBindingFlags::empty(),
+                    },
+                ],
+            })?;
+
+        let pipeline_layout =
+            ctx.create_pipeline_layout(PipelineLayoutInfo {
+                sets: vec![
+                    ctx.object_table().layout().clone(),
+                    build_layout.clone(),
+                ],
+                push_constants: vec![PushConstant {
+                    stages: ShaderStageFlags::COMPUTE,
+                    offset: 0,
+                    size: size_of::<Params>() as u32,
+                }],
+            })?;
+
+        let voxelize = ComputeShader::with_main(
+            ctx.create_shader_module(
+                Spirv::new(
+                    include_bytes!("global_sdf/voxelize.comp.spv").to_vec(),
+                )
+                .into(),
+            )?,
+        );
+
+        let resolve = ComputeShader::with_main(
+            ctx.create_shader_module(
+                Spirv::new(
+                    include_bytes!("global_sdf/resolve.comp.spv").to_vec(),
+                )
+                .into(),
+            )?,
+        );
+
+        let voxelize = ctx.create_compute_pipeline(ComputePipelineInfo {
+            shader: voxelize,
+            layout: pipeline_layout.clone(),
+        })?;
+
+        let resolve = ctx.create_compute_pipeline(ComputePipelineInfo {
+            shader: resolve,
+            layout: pipeline_layout.clone(),
+        })?;
+
+        let bricks_size = 16 + 4 * (SDF_GRID as u64).pow(3);
+        let bricks_buffer = ctx.create_buffer(BufferInfo {
+            align: 255,
+            size: bricks_size,
+            usage: BufferUsage::STORAGE | BufferUsage::TRANSFER_DST,
+            capture_address: None,
+        })?;
+
+        let scratch_size =
+            4 * u64::from(BRICK_TEXELS).pow(3) * MAX_BRICK_UPDATES as u64;
+        let scratch = ctx.create_buffer(BufferInfo {
+            align: 255,
+            size: scratch_size,
+            usage: BufferUsage::STORAGE | BufferUsage::TRANSFER_DST,
+            capture_address: None,
+        })?;
+
+        let atlas_side = SDF_ATLAS * BRICK_TEXELS;
+        let atlas = ctx.create_image(ImageInfo {
+            extent: Extent3d {
+                width: atlas_side,
+                height: atlas_side,
+                depth: atlas_side,
+            }
+            .into(),
+            format: Format::R16Sfloat,
+            levels: 1,
+            layers: 1,
+            samples: Samples1,
+            usage: ImageUsage::STORAGE | ImageUsage::SAMPLED,
+        })?;
+        let atlas_view =
+            ctx.create_image_view(ImageViewInfo::new(atlas.clone()))?;
+
+        let sampler = ctx.create_sampler(SamplerInfo {
+            mag_filter: Filter::Linear,
+            min_filter: Filter::Linear,
+            min_lod: 0.0.into(),
+            max_lod: 0.0.into(),
+            address_mode_u: SamplerAddressMode::ClampToEdge,
+            address_mode_v: SamplerAddressMode::ClampToEdge,
+            address_mode_w: SamplerAddressMode::ClampToEdge,
+            ..Default::default()
+        })?;
+
+        let set = ctx.create_descriptor_set(DescriptorSetInfo {
+            layout: layout.clone(),
+        })?;
+
+        let build_set = ctx.create_descriptor_set(DescriptorSetInfo {
+            layout: build_layout,
+        })?;
+
+        // Atlas stays in general layout as it is updated between draws.
+        ctx.update_descriptor_sets(
+            &[
+                WriteDescriptorSet {
+                    set: &set,
+                    binding: 0,
+                    element: 0,
+                    descriptors: Descriptors::StorageBuffer(&[(
+                        bricks_buffer.clone(),
+                        0,
+                        bricks_size,
+                    )]),
+                },
+                WriteDescriptorSet {
+                    set: &set,
+                    binding: 1,
+                    element: 0,
+                    descriptors: Descriptors::CombinedImageSampler(&[(
+                        atlas_view.clone(),
+                        Layout::General,
+                        sampler,
+                    )]),
+                },
+                WriteDescriptorSet {
+                    set: &build_set,
+                    binding: 0,
+                    element: 0,
+                    descriptors: Descriptors::StorageBuffer(&[(
+                        scratch.clone(),
+                        0,
+                        scratch_size,
+                    )]),
+                },
+                WriteDescriptorSet {
+                    set: &build_set,
+                    binding: 1,
+                    element: 0,
+                    descriptors: Descriptors::StorageImage(&[(
+                        atlas_view,
+                        Layout::General,
+                    )]),
+                },
+            ],
+            &[],
+        );
+
+        Ok(GlobalSdfPass {
+            bricks_buffer,
+            atlas,
+            scratch,
+            initialized: false,
+            statics: HashMap::new(),
+            bricks: HashMap::new(),
+            dirty: HashSet::new(),
+            free_slots: Vec::new(),
+            next_slot: 0,
+            origin: None,
+            frame: 0,
+            voxelize,
+            resolve,
+            pipeline_layout,
+            build_set,
+            layout,
+            set,
+        })
+    }
+
+    /// Layout of the set with the field.
+    /// Passes sampling the field include it into their pipeline layouts.
+    pub fn layout(&self) -> &DescriptorSetLayout {
+        &self.layout
+    }
+
+    /// Marks bricks within window in inclusive range as dirty.
+    /// Bricks within window at `skip` origin are left intact.
+    fn mark_dirty(&mut self, min: Brick, max: Brick, skip: Option<Brick>) {
+        let origin = match self.origin {
+            Some(origin) => origin,
+            None => return,
+        };
+
+        let mut lo = min;
+        let mut hi = max;
+        for i in 0..3 {
+            lo[i] = lo[i].max(origin[i]);
+            hi[i] = hi[i].min(origin[i] + SDF_GRID - 1);
+        }
+
+        for z in lo[2]..=hi[2] {
+            for y in lo[1]..=hi[1] {
+                for x in lo[0]..=hi[0] {
+                    let brick = [x, y, z];
+                    match skip {
+                        Some(skip) if in_window(&brick, skip) => {}
+                        _ => {
+                            self.dirty.insert(brick);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<'a> Pass<'a> for GlobalSdfPass {
+    type Input = Input;
+    type Output = Output;
+
+    fn draw(
+        &mut self,
+        input: Input,
+        _frame: u64,
+        wait: &[(PipelineStageFlags, Semaphore)],
+        signal: &[Semaphore],
+        fence: Option<&Fence>,
+        ctx: &mut Context,
+        world: &mut World,
+        bump: &Bump,
+    ) -> Result<Output, Report> {
+        self.frame += 1;
+
+        let camera = brick_of(&input.camera.coords);
+        let center = |origin: Brick| {
+            let mut center = origin;
+            for c in &mut center {
+                *c += SDF_GRID / 2;
+            }
+            center
+        };
+
+        let mut indirection = BVec::new_in(bump);
+
+        // Window is moved once camera gets far enough from its center.
+        let moved = match self.origin {
+            Some(origin) => (0..3)
+                .any(|i| (camera[i] - center(origin)[i]).abs() > WINDOW_SLACK),
+            None => true,
+        };
+
+        if moved {
+            let old = self.origin;
+            let mut origin = camera;
+            for c in &mut origin {
+                *c -= SDF_GRID / 2;
+            }
+            self.origin = Some(origin);
+
+            // Bricks that left the window are released.
+            let free_slots = &mut self.free_slots;
+            self.bricks.retain(|brick, slot| {
+                if in_window(brick, origin) {
+                    true
+                } else {
+                    free_slots.push(*slot);
+                    indirection.push((cell(brick), 0));
+                    false
+                }
+            });
+            self.dirty.retain(|brick| in_window(brick, origin));
+
+            // Bricks that entered the window are voxelized.
+            let ranges: Vec<_> = self
+                .statics
+                .values()
+                .map(|entry| (entry.min, entry.max))
+                .collect();
+
+            for (min, max) in ranges {
+                self.mark_dirty(min, max, old);
+            }
+        }
+
+        // Find added, changed and removed static renderables.
+        let mut changed = Vec::new();
+        for (entity, (renderable, global, geometry)) in world
+            .query::<(&Renderable, &Global3, &StaticGeometry)>()
+            .iter()
+        {
+            let frame = self.frame;
+            match self.statics.get_mut(&entity) {
+                Some(entry)
+                    if entry.global == *global
+                        && entry.mesh == renderable.mesh =>
+                {
+                    entry.frame = frame;
+                }
+                _ => {
+                    let (min, max) = brick_bounds(global, geometry.radius);
+                    let old = self.statics.insert(
+                        entity,
+                        StaticEntry {
+                            global: *global,
+                            mesh: renderable.mesh.clone(),
+                            min,
+                            max,
+                            frame,
+                        },
+                    );
+
+                    if let Some(old) = old {
+                        changed.push((old.min, old.max));
+                    }
+                    changed.push((min, max));
+                }
+            }
+        }
+
+        let frame = self.frame;
+        self.statics.retain(|_, entry| {
+            if entry.frame == frame {
+                true
+            } else {
+                changed.push((entry.min, entry.max));
+                false
+            }
+        });
+
+        for (min, max) in changed {
+            self.mark_dirty(min, max, None);
+        }
+
+        // Nearest dirty bricks are voxelized first.
+        let mut dirty: Vec<Brick> = self.dirty.iter().copied().collect();
+        dirty.sort_by_key(|brick| {
+            (0..3).map(|i| (brick[i] - camera[i]).abs()).max()
+        });
+
+        let mut jobs = BVec::new_in(bump);
+        let mut dispatches = BVec::new_in(bump);
+
+        for brick in dirty {
+            if jobs.len() >= MAX_BRICK_UPDATES {
+                break;
+            }
+
+            let brick_min = [
+                brick[0] as f32 * SDF_BRICK_SIZE,
+                brick[1] as f32 * SDF_BRICK_SIZE,
+                brick[2] as f32 * SDF_BRICK_SIZE,
+            ];
+
+            let first_dispatch = dispatches.len();
+            for (entity, entry) in &self.statics {
+                if !entry.overlaps(&brick) {
+                    continue;
+                }
+
+                if let Some(params) = voxelize_params(ctx, *entity, &entry.mesh)
+                {
+                    dispatches.push(Params {
+                        brick_min,
+                        scratch: jobs.len() as u32,
+                        ..params
+                    });
+                }
+            }
+
+            if dispatches.len() == first_dispatch {
+                // Brick has no geometry anymore.
+                if let Some(slot) = self.bricks.remove(&brick) {
+                    self.free_slots.push(slot);
+                    indirection.push((cell(&brick), 0));
+                }
+                self.dirty.remove(&brick);
+                continue;
+            }
+
+            let slot = match self.bricks.get(&brick) {
+                Some(&slot) => slot,
+                None => {
+                    let slot = match self.free_slots.pop() {
+                        Some(slot) => slot,
+                        None if self.next_slot < SDF_ATLAS.pow(3) => {
+                            self.next_slot += 1;
+                            self.next_slot - 1
+                        }
+                        None => {
+                            // Atlas is full, brick stays dirty.
+                            dispatches.truncate(first_dispatch);
+                            continue;
+                        }
+                    };
+                    self.bricks.insert(brick, slot);
+                    indirection.push((cell(&brick), slot + 1));
+                    slot
+                }
+            };
+
+            for params in &mut dispatches[first_dispatch..] {
+                params.slot = slot;
+            }
+
+            jobs.push(Params {
+                brick_min,
+                scratch: jobs.len() as u32,
+                object: 0,
+                triangles: 0,
+                stride: 0,
+                flags: 0,
+                slot,
+            });
+            self.dirty.remove(&brick);
+        }
+
+        let mut encoder = ctx.queue.create_encoder()?;
+
+        // Field is read and built by previous frame.
+        encoder.pipeline_barrier(
+            PipelineStageFlags::COMPUTE_SHADER
+                | PipelineStageFlags::FRAGMENT_SHADER
+                | PipelineStageFlags::RAY_TRACING_SHADER,
+            PipelineStageFlags::TRANSFER | PipelineStageFlags::COMPUTE_SHADER,
+        );
+
+        if !self.initialized {
+            encoder.fill_buffer(
+                &self.bricks_buffer,
+                0,
+                self.bricks_buffer.info().size,
+                0,
+            );
+
+            let atlas_barrier =
+                bump.alloc([ImageLayoutTransition::initialize_whole(
+                    &self.atlas,
+                    Layout::General,
+                )
+                .into()]);
+
+            encoder.image_barriers(
+                PipelineStageFlags::TOP_OF_PIPE,
+                PipelineStageFlags::COMPUTE_SHADER,
+                atlas_barrier,
+            );
+
+            encoder.pipeline_barrier(
+                PipelineStageFlags::TRANSFER,
+                PipelineStageFlags::TRANSFER,
+            );
+            self.initialized = true;
+        }
+
+        if moved {
+            let origin = self.origin.unwrap();
+            ctx.update_buffer(
+                &mut encoder,
+                &self.bricks_buffer,
+                0,
+                bump.alloc([origin[0], origin[1], origin[2], 0]),
+                bump,
+            )?;
+        }
+
+        // Entries are written in order, so later ones win.
+        for &(cell, entry) in indirection.iter() {
+            ctx.update_buffer(
+                &mut encoder,
+                &self.bricks_buffer,
+                16 + 4 * u64::from(cell),
+                bump.alloc([entry]),
+                bump,
+            )?;
+        }
+
+        if !jobs.is_empty() {
+            encoder.fill_buffer(
+                &self.scratch,
+                0,
+                4 * u64::from(BRICK_TEXELS).pow(3) * jobs.len() as u64,
+                !0,
+            );
+        }
+
+        encoder.pipeline_barrier(
+            PipelineStageFlags::TRANSFER,
+            PipelineStageFlags::COMPUTE_SHADER
+                | PipelineStageFlags::FRAGMENT_SHADER
+                | PipelineStageFlags::RAY_TRACING_SHADER,
+        );
+
+        if !jobs.is_empty() {
+            let sets = bump.alloc([
+                ctx.object_table().set().clone(),
+                self.build_set.clone(),
+            ]);
+
+            encoder.bind_compute_pipeline(&self.voxelize);
+            encoder.bind_compute_descriptor_sets(
+                &self.pipeline_layout,
+                0,
+                &*sets,
+                &[],
+            );
+
+            for params in dispatches.iter() {
+                encoder.push_constants(
+                    &self.pipeline_layout,
+                    ShaderStageFlags::COMPUTE,
+                    0,
+                    std::slice::from_ref(params),
+                );
+                encoder.dispatch((params.triangles + 63) / 64, 1, 1);
+            }
+
+            encoder.pipeline_barrier(
+                PipelineStageFlags::COMPUTE_SHADER,
+                PipelineStageFlags::COMPUTE_SHADER,
+            );
+
+            encoder.bind_compute_pipeline(&self.resolve);
+            for params in jobs.iter() {
+                encoder.push_constants(
+                    &self.pipeline_layout,
+                    ShaderStageFlags::COMPUTE,
+                    0,
+                    std::slice::from_ref(params),
+                );
+                encoder.dispatch(1, 1, 1);
+            }
+
+            encoder.pipeline_barrier(
+                PipelineStageFlags::COMPUTE_SHADER,
+                PipelineStageFlags::COMPUTE_SHADER
+                    | PipelineStageFlags::FRAGMENT_SHADER
+                    | PipelineStageFlags::RAY_TRACING_SHADER,
+            );
+        }
+
+        ctx.record_barriers(encoder.barrier_count());
+        ctx.submit(wait, encoder.finish()?, signal, fence)?;
+
+        Ok(Output {
+            set: self.set.clone(),
+        })
+    }
+}
+
+/// Returns brick containing the point.
+fn brick_of(p: &na::Vector3<f32>) -> Brick {
+    [
+        (p.x / SDF_BRICK_SIZE).floor() as i32,
+        (p.y / SDF_BRICK_SIZE).floor() as i32,
+        (p.z / SDF_BRICK_SIZE).floor() as i32,
+    ]
+}
+
+/// Returns inclusive range of bricks within band
+/// from renderable's bounding sphere.
+fn brick_bounds(global: &Global3, radius: f32) -> (Brick, Brick) {
+    let scale = (0..3)
+        .map(|i| global.skew.column(i).norm())
+        .fold(0.0, f32::max);
+
+    let center = global.iso.translation.vector;
+    let extent = na::Vector3::repeat(radius * scale + BAND);
+
+    (brick_of(&(center - extent)), brick_of(&(center + extent)))
+}
+
+fn in_window(brick: &Brick, origin: Brick) -> bool {
+    (0..3).all(|i| origin[i] <= brick[i] && brick[i] < origin[i] + SDF_GRID)
+}
+
+/// Returns element of bricks buffer for brick within window.
+fn cell(brick: &Brick) -> u32 {
+    let c = |i: usize| brick[i].rem_euclid(SDF_GRID) as u32;
+    let grid = SDF_GRID as u32;
+    c(0) + c(1) * grid + c(2) * grid * grid
+}
+
+/// Returns parameters for voxelization of renderable's triangles.
+/// `None` if mesh has no triangles or renderable has no
+/// object table element yet.
+fn voxelize_params(
+    ctx: &Context,
+    entity: Entity,
+    mesh: &Mesh,
+) -> Option<Params> {
+    if mesh.topology() != PrimitiveTopology::TriangleList {
+        return None;
+    }
+
+    let object = ctx.object_table().index(entity)?;
+    let binding = mesh.bindings().first()?;
+
+    let flags = match mesh.indices() {
+        None => 0,
+        Some(indices) => match indices.index_type {
+            IndexType::U16 => FLAG_INDEXED | FLAG_INDEX_U16,
+            IndexType::U32 => FLAG_INDEXED,
+        },
+    };
+
+    let triangles = mesh.count() / 3;
+    if triangles == 0 {
+        return None;
+    }
+
+    Some(Params {
+        brick_min: [0.0; 3],
+        scratch: 0,
+        object,
+        triangles,
+        stride: binding.layout.stride,
+        flags,
+        slot: 0,
+    })
+}
@@ -0,0 +1,33 @@
+// Parameters of brick voxelization. Matches `Params` in `global_sdf.rs`.
+layout(push_constant) uniform Params {
+    // World space position of brick's first sample.
+    vec3 brick_min;
+
+    // Index of brick's region in scratch buffer.
+    uint scratch;
+
+    // Object table element of voxelized renderable.
+    uint object;
+    uint triangles;
+
+    // Stride of vertices with position at offset zero.
+    uint stride;
+    uint flags;
+
+    // Atlas slot brick is resolved into.
+    uint slot;
+} params;
+
+const uint FLAG_INDEXED = 1;
+const uint FLAG_INDEX_U16 = 2;
+
+const uint BRICK_TEXELS = 8;
+const uint BRICK_SAMPLES = BRICK_TEXELS * BRICK_TEXELS * BRICK_TEXELS;
+const float BRICK_SIZE = 2.0;
+const float VOXEL_SIZE = BRICK_SIZE / float(BRICK_TEXELS - 1);
+
+// Distance to triangles farther than this is not written.
+// Matches `SDF_FAR` in `common/sdf.glsl`.
+const float BAND = 1.0;
+
+layout(binding = 0, set = 1, std430) buffer Scratch { uint scratch[]; };
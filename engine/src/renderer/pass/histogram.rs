@@ -0,0 +1,238 @@
+//!
+//! Compute pass that builds histogram of `f32` values.
+
+use {
+    super::Pass,
+    crate::renderer::Context,
+    bumpalo::Bump,
+    bytemuck::{Pod, Zeroable},
+    eyre::Report,
+    hecs::World,
+    illume::{
+        BufferRegion, ComputePipeline, ComputePipelineInfo, ComputeShader,
+        DescriptorBindingFlags, DescriptorSet, DescriptorSetInfo,
+        DescriptorSetLayoutBinding, DescriptorSetLayoutFlags,
+        DescriptorSetLayoutInfo, DescriptorType, Descriptors, Fence,
+        PipelineLayout, PipelineLayoutInfo, PipelineStageFlags, PushConstant,
+        Semaphore, ShaderStageFlags, Spirv, WriteDescriptorSet,
+    },
+    std::ops::Range,
+};
+
+/// Maximum number of bins supported by histogram pass.
+pub const MAX_HISTOGRAM_BINS: u32 = 256;
+
+/// Upper bound for number of workgroups dispatched.
+/// Each invocation processes multiple values when there are more.
+const MAX_GROUPS: u32 = 1024;
+
+/// Mapping of values onto bins range.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HistogramScale {
+    /// Bins are distributed uniformly over values range.
+    Linear,
+
+    /// Bins are distributed uniformly over `log2` of values.
+    /// Useful for luminance histograms.
+    Log2,
+}
+
+pub struct Input {
+    /// Region of the buffer with values.
+    pub values: BufferRegion,
+
+    /// Number of values in the region.
+    pub count: u32,
+
+    /// Range of values covered by bins.
+    /// With `HistogramScale::Log2` range is specified in `log2` space.
+    /// Values outside range are clamped to first and last bins.
+    pub range: Range<f32>,
+
+    pub scale: HistogramScale,
+
+    /// Region of the buffer with `u32` counter for each bin.
+    /// Counters are cleared before values are accumulated.
+    pub bins: BufferRegion,
+
+    /// Number of bins. Must not exceed `MAX_HISTOGRAM_BINS`.
+    pub bin_count: u32,
+}
+
+pub struct Output;
+
+pub struct HistogramPass {
+    layout: PipelineLayout,
+    pipeline: ComputePipeline,
+    per_frame_sets: [DescriptorSet; 2],
+}
+
+impl HistogramPass {
+    pub fn new(ctx: &mut Context) -> Result<Self, Report> {
+        let set_layout =
+            ctx.create_descriptor_set_layout(DescriptorSetLayoutInfo {
+                flags: DescriptorSetLayoutFlags::empty(),
+                bindings: (0..2)
+                    .map(|binding| DescriptorSetLayoutBinding {
+                        binding,
+                        ty: DescriptorType::StorageBuffer,
+                        count: 1,
+                        stages: ShaderStageFlags::COMPUTE,
+                        flags: DescriptorBindingFlags::empty(),
+                    })
+                    .collect(),
+            })?;
+
+        let layout = ctx.create_pipeline_layout(PipelineLayoutInfo {
+            sets: vec![set_layout.clone()],
+            push_constants: vec![PushConstant {
+                stages: ShaderStageFlags::COMPUTE,
+                offset: 0,
+                size: std::mem::size_of::<Params>() as u32,
+            }],
+        })?;
+
+        let shader = ComputeShader::with_main(
+            ctx.create_shader_module(
+                Spirv::new(
+                    include_bytes!("histogram/histogram.comp.spv").to_vec(),
+                )
+                .into(),
+            )?,
+        );
+
+        let pipeline = ctx.create_compute_pipeline(ComputePipelineInfo {
+            shader,
+            layout: layout.clone(),
+        })?;
+
+        let per_frame_set0 = ctx.create_descriptor_set(DescriptorSetInfo {
+            layout: set_layout.clone(),
+        })?;
+
+        let per_frame_set1 = ctx
+            .create_descriptor_set(DescriptorSetInfo { layout: set_layout })?;
+
+        Ok(HistogramPass {
+            layout,
+            pipeline,
+            per_frame_sets: [per_frame_set0, per_frame_set1],
+        })
+    }
+}
+
+impl Pass<'_> for HistogramPass {
+    type Input = Input;
+    type Output = Output;
+
+    fn draw(
+        &mut self,
+        input: Input,
+        frame: u64,
+        wait: &[(PipelineStageFlags, Semaphore)],
+        signal: &[Semaphore],
+        fence: Option<&Fence>,
+        ctx: &mut Context,
+        _world: &mut World,
+        bump: &Bump,
+    ) -> Result<Output, Report> {
+        assert!(u64::from(input.count) * 4 <= input.values.size);
+        assert!(input.bin_count > 0 && input.bin_count <= MAX_HISTOGRAM_BINS);
+        assert!(u64::from(input.bin_count) * 4 <= input.bins.size);
+        assert!(input.range.start < input.range.end);
+
+        let findex = (frame & 1) as usize;
+
+        let descriptors = bump.alloc([
+            (
+                input.values.buffer.clone(),
+                input.values.offset,
+                input.values.size,
+            ),
+            (
+                input.bins.buffer.clone(),
+                input.bins.offset,
+                input.bins.size,
+            ),
+        ]);
+
+        let set = &self.per_frame_sets[findex];
+        let writes = [
+            WriteDescriptorSet {
+                set,
+                binding: 0,
+                element: 0,
+                descriptors: Descriptors::StorageBuffer(&descriptors[0..1]),
+            },
+            WriteDescriptorSet {
+                set,
+                binding: 1,
+                element: 0,
+                descriptors: Descriptors::StorageBuffer(&descriptors[1..2]),
+            },
+        ];
+        ctx.update_descriptor_sets(&writes, &[]);
+
+        let params = bump.alloc([Params {
+            count: input.count,
+            bin_count: input.bin_count,
+            min_value: input.range.start,
+            inv_range: 1.0 / (input.range.end - input.range.start),
+            flags: match input.scale {
+                HistogramScale::Linear => 0,
+                HistogramScale::Log2 => Params::LOG2_BIT,
+            },
+        }]);
+
+        let mut encoder = ctx.queue.create_encoder()?;
+        encoder.fill_buffer(
+            &input.bins.buffer,
+            input.bins.offset,
+            u64::from(input.bin_count) * 4,
+            0,
+        );
+        encoder.pipeline_barrier(
+            PipelineStageFlags::TRANSFER,
+            PipelineStageFlags::COMPUTE_SHADER,
+        );
+
+        encoder.bind_compute_pipeline(&self.pipeline);
+        encoder.bind_compute_descriptor_sets(
+            &self.layout,
+            0,
+            std::slice::from_ref(set),
+            &[],
+        );
+        encoder.push_constants(
+            &self.layout,
+            ShaderStageFlags::COMPUTE,
+            0,
+            params,
+        );
+
+        let groups = ((input.count + 255) / 256).max(1).min(MAX_GROUPS);
+        encoder.dispatch(groups, 1, 1);
+
+        let cbuf = encoder.finish()?;
+        ctx.queue.submit(wait, cbuf, signal, fence)?;
+
+        Ok(Output)
+    }
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct Params {
+    count: u32,
+    bin_count: u32,
+    min_value: f32,
+    inv_range: f32,
+    flags: u32,
+}
+
+impl Params {
+    const LOG2_BIT: u32 = 1;
+}
+
+unsafe impl Zeroable for Params {}
+unsafe impl Pod for Params {}
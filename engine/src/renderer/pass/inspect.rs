@@ -0,0 +1,322 @@
+use {
+    super::Pass,
+    crate::renderer::{Context, PixelSample},
+    bumpalo::{collections::Vec as BVec, Bump},
+    bytemuck::{Pod, Zeroable},
+    color_eyre::Report,
+    hecs::World,
+    illume::*,
+    std::mem::size_of,
+};
+
+pub struct Input {
+    /// Pixel to read.
+    /// Clamped to extent of input images.
+    pub pixel: [u32; 2],
+
+    pub albedo: Image,
+
+    /// Normals and depth in ray-tracing prepass layout.
+    pub normal_depth: Image,
+
+    /// Emissive radiance with roughness in alpha channel.
+    pub emissive: Image,
+
+    /// Direct radiance with material index in alpha channel.
+    pub direct: Image,
+    pub diffuse: Image,
+}
+
+pub struct Output {
+    /// Values read by the last completed frame that used
+    /// same readback buffer.
+    pub sample: Option<PixelSample>,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct Pixel {
+    pixel: [u32; 2],
+}
+
+unsafe impl Zeroable for Pixel {}
+unsafe impl Pod for Pixel {}
+
+/// Values written by inspect shader.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct Readback {
+    albedo: [f32; 4],
+    normal_depth: [f32; 4],
+    emissive: [f32; 4],
+    direct: [f32; 4],
+    diffuse: [f32; 4],
+}
+
+unsafe impl Zeroable for Readback {}
+unsafe impl Pod for Readback {}
+
+/// Reads back values of intermediate images at single pixel.
+///
+/// Values are copied by compute shader into host-visible buffer
+/// and returned two frames later, once the frame that wrote them
+/// is complete.
+pub struct InspectPass {
+    sampler: Sampler,
+
+    /// Albedo, normal-depth, emissive, direct and diffuse views.
+    inputs: [[Option<ImageView>; 5]; 2],
+
+    pipeline: ComputePipeline,
+    pipeline_layout: PipelineLayout,
+    per_frame_sets: [DescriptorSet; 2],
+
+    readback: [MappableBuffer; 2],
+
+    /// Pixels written into readback buffers.
+    pending: [Option<[u32; 2]>; 2],
+}
+
+impl InspectPass {
+    pub fn new(ctx: &mut Context) -> Result<Self, Report> {
+        let set_layout =
+            ctx.create_descriptor_set_layout(DescriptorSetLayoutInfo {
+                flags: DescriptorSetLayoutFlags::UPDATE_AFTER_BIND_POOL,
+                bindings: (0..6)
+                    .map(|binding| DescriptorSetLayoutBinding {
+                        binding,
+                        ty: if binding == 5 {
+                            DescriptorType::StorageBuffer
+                        } else {
+                            DescriptorType::CombinedImageSampler
+                        },
+                        count: 1,
+                        stages: ShaderStageFlags::COMPUTE,
+                        flags: DescriptorBindingFlags::empty(),
+                    })
+                    .collect(),
+            })?;
+
+        let pipeline_layout =
+            ctx.create_pipeline_layout(PipelineLayoutInfo {
+                sets: vec![set_layout.clone()],
+                push_constants: vec![PushConstant {
+                    stages: ShaderStageFlags::COMPUTE,
+                    offset: 0,
+                    size: size_of::<Pixel>() as u32,
+                }],
+            })?;
+
+        let shader = ComputeShader::with_main(
+            ctx.create_shader_module(
+                Spirv::new(include_bytes!("inspect/inspect.comp.spv").to_vec())
+                    .into(),
+            )?,
+        );
+
+        let pipeline = ctx.create_compute_pipeline(ComputePipelineInfo {
+            shader,
+            layout: pipeline_layout.clone(),
+        })?;
+
+        let set0 = ctx.create_descriptor_set(DescriptorSetInfo {
+            layout: set_layout.clone(),
+        })?;
+
+        let set1 = ctx.create_descriptor_set(DescriptorSetInfo {
+            layout: set_layout.clone(),
+        })?;
+
+        let mut create_readback = || {
+            ctx.create_mappable_buffer(
+                BufferInfo {
+                    align: 255,
+                    size: size_of::<Readback>() as u64,
+                    usage: BufferUsage::STORAGE,
+                    capture_address: None,
+                },
+                MemoryUsage::DOWNLOAD,
+            )
+        };
+
+        let readback = [create_readback()?, create_readback()?];
+
+        ctx.update_descriptor_sets(
+            &[
+                WriteDescriptorSet {
+                    set: &set0,
+                    binding: 5,
+                    element: 0,
+                    descriptors: Descriptors::StorageBuffer(&[(
+                        readback[0].share(),
+                        0,
+                        size_of::<Readback>() as u64,
+                    )]),
+                },
+                WriteDescriptorSet {
+                    set: &set1,
+                    binding: 5,
+                    element: 0,
+                    descriptors: Descriptors::StorageBuffer(&[(
+                        readback[1].share(),
+                        0,
+                        size_of::<Readback>() as u64,
+                    )]),
+                },
+            ],
+            &[],
+        );
+
+        let sampler = ctx.create_sampler(SamplerInfo::new())?;
+
+        Ok(InspectPass {
+            sampler,
+            inputs: Default::default(),
+            pipeline,
+            pipeline_layout,
+            per_frame_sets: [set0, set1],
+            readback,
+            pending: [None; 2],
+        })
+    }
+}
+
+impl<'a> Pass<'a> for InspectPass {
+    type Input = Input;
+    type Output = Output;
+
+    fn draw(
+        &mut self,
+        input: Input,
+        frame: u64,
+        wait: &[(PipelineStageFlags, Semaphore)],
+        signal: &[Semaphore],
+        fence: Option<&Fence>,
+        ctx: &mut Context,
+        _world: &mut World,
+        bump: &Bump,
+    ) -> Result<Output, Report> {
+        let fid = (frame % 2) as usize;
+        let set = &self.per_frame_sets[fid];
+
+        // Frame that wrote the buffer is complete by now.
+        let sample = match self.pending[fid].take() {
+            Some(pixel) => {
+                let mut readback = Readback::zeroed();
+                ctx.read_buffer(
+                    &mut self.readback[fid],
+                    0,
+                    std::slice::from_mut(&mut readback),
+                )?;
+                Some(decode_readback(pixel, &readback))
+            }
+            None => None,
+        };
+
+        let mut writes = BVec::new_in(bump);
+
+        let images = [
+            &input.albedo,
+            &input.normal_depth,
+            &input.emissive,
+            &input.direct,
+            &input.diffuse,
+        ];
+
+        for (binding, (slot, image)) in
+            self.inputs[fid].iter_mut().zip(images.iter()).enumerate()
+        {
+            match slot {
+                Some(view) if view.info().image == **image => continue,
+                _ => {}
+            }
+
+            *slot = None;
+            let view =
+                ctx.create_image_view(ImageViewInfo::new((*image).clone()))?;
+            let view = slot.get_or_insert(view);
+
+            writes.push(WriteDescriptorSet {
+                set,
+                binding: binding as u32,
+                element: 0,
+                descriptors: Descriptors::CombinedImageSampler(bump.alloc([(
+                    view.clone(),
+                    Layout::ShaderReadOnlyOptimal,
+                    self.sampler.clone(),
+                )])),
+            });
+        }
+
+        ctx.update_descriptor_sets(&writes, &[]);
+
+        let extent = input.normal_depth.info().extent.into_2d();
+        let pixel = Pixel {
+            pixel: [
+                input.pixel[0].min(extent.width.saturating_sub(1)),
+                input.pixel[1].min(extent.height.saturating_sub(1)),
+            ],
+        };
+
+        let mut encoder = ctx.queue.create_encoder()?;
+
+        // Inputs are written by ray-tracing prepass.
+        encoder.pipeline_barrier(
+            PipelineStageFlags::RAY_TRACING_SHADER,
+            PipelineStageFlags::COMPUTE_SHADER,
+        );
+
+        encoder.bind_compute_pipeline(&self.pipeline);
+        encoder.bind_compute_descriptor_sets(
+            &self.pipeline_layout,
+            0,
+            std::slice::from_ref(set),
+            &[],
+        );
+        encoder.push_constants(
+            &self.pipeline_layout,
+            ShaderStageFlags::COMPUTE,
+            0,
+            bump.alloc([pixel]),
+        );
+        encoder.dispatch(1, 1, 1);
+
+        // Make readback visible to host once frame is complete.
+        encoder.pipeline_barrier(
+            PipelineStageFlags::COMPUTE_SHADER,
+            PipelineStageFlags::HOST,
+        );
+
+        ctx.record_barriers(encoder.barrier_count());
+        ctx.queue.submit(wait, encoder.finish()?, signal, fence)?;
+
+        self.pending[fid] = Some(pixel.pixel);
+
+        Ok(Output { sample })
+    }
+}
+
+fn decode_readback(pixel: [u32; 2], readback: &Readback) -> PixelSample {
+    let [nx, ny, nz, depth] = readback.normal_depth;
+    let [er, eg, eb, roughness] = readback.emissive;
+    let [dr, dg, db, material] = readback.direct;
+    let [fr, fg, fb, _] = readback.diffuse;
+
+    PixelSample {
+        pixel,
+        albedo: readback.albedo,
+        normal: [nx, ny, nz],
+        depth: if depth < 0.0 { None } else { Some(depth) },
+        emissive: [er, eg, eb],
+        roughness,
+        direct: [dr, dg, db],
+        diffuse: [fr, fg, fb],
+
+        // Index is stored off by one so that zero marks a miss.
+        material: if material >= 1.0 {
+            Some(material as u32 - 1)
+        } else {
+            None
+        },
+    }
+}
@@ -1,15 +1,47 @@
+pub mod accumulate;
 pub mod atrous;
+pub mod audio_occlusion;
+pub mod checkerboard;
 pub mod combine;
+pub mod external_denoise;
 pub mod gauss_filter;
+pub mod global_sdf;
+pub mod histogram;
+pub mod inspect;
+pub mod particles;
 pub mod pose;
+pub mod prefix_sum;
+pub mod radix_sort;
 pub mod raster;
 pub mod ray_probe;
+pub mod reduce;
 pub mod rt_prepass;
+pub mod ssao;
+pub mod ssr;
+pub mod water;
 
 pub use self::{
-    atrous::ATrousFilter, combine::CombinePass, gauss_filter::GaussFilter,
-    pose::PosePass, raster::RasterPass, ray_probe::RayProbe,
+    accumulate::{AccumulatePass, MAX_ADAPTIVE_SAMPLES},
+    atrous::{ATrousConfig, ATrousFilter, MAX_ATROUS_ITERATIONS},
+    audio_occlusion::AudioOcclusionPass,
+    checkerboard::CheckerboardPass,
+    combine::CombinePass,
+    external_denoise::ExternalDenoisePass,
+    gauss_filter::GaussFilter,
+    global_sdf::GlobalSdfPass,
+    histogram::HistogramPass,
+    inspect::InspectPass,
+    particles::ParticlesPass,
+    pose::PosePass,
+    prefix_sum::PrefixSumPass,
+    radix_sort::RadixSortPass,
+    raster::RasterPass,
+    ray_probe::RayProbe,
+    reduce::ReducePass,
     rt_prepass::RtPrepass,
+    ssao::SsaoPass,
+    ssr::SsrPass,
+    water::{WaterPass, WATER_FIELDS},
 };
 
 use {
@@ -18,7 +50,10 @@ use {
     color_eyre::Report,
     fastbitset::BoxedBitSet,
     hecs::World,
-    illume::{Fence, PipelineStageFlags, Semaphore},
+    illume::{
+        Buffer, BufferInfo, BufferUsage, Fence, OutOfMemory,
+        PipelineStageFlags, Semaphore,
+    },
     std::{
         collections::hash_map::{Entry, HashMap},
         hash::Hash,
@@ -42,10 +77,15 @@ pub trait Pass<'a> {
     ) -> Result<Self::Output, Report>;
 }
 
+/// Number of frames resource must stay unused before its descriptor index
+/// is released. Must exceed number of frames in flight.
+const RELEASE_DELAY: u64 = 3;
+
 struct SparseDescriptors<T> {
-    resources: HashMap<T, u32>,
+    resources: HashMap<T, (u32, u64)>,
     bitset: BoxedBitSet,
     next: u32,
+    frame: u64,
 }
 
 impl<T> SparseDescriptors<T>
@@ -57,20 +97,79 @@ where
             resources: HashMap::new(),
             bitset: BoxedBitSet::new(),
             next: 0,
+            frame: 0,
         }
     }
 
     fn index(&mut self, resource: T) -> (u32, bool) {
+        let frame = self.frame;
         match self.resources.entry(resource) {
-            Entry::Occupied(entry) => (*entry.get(), false),
+            Entry::Occupied(mut entry) => {
+                let (index, used) = entry.get_mut();
+                *used = frame;
+                (*index, false)
+            }
             Entry::Vacant(entry) => {
-                if let Some(index) = self.bitset.find_set() {
+                let index = if let Some(index) = self.bitset.find_set() {
                     self.bitset.unset(index);
-                    (*entry.insert(index as u32), true)
+                    index
                 } else {
                     self.next += 1;
-                    (*entry.insert(self.next - 1), true)
-                }
+                    self.next - 1
+                };
+                entry.insert((index, frame));
+                (index, true)
+            }
+        }
+    }
+
+    /// Advances frame counter and releases indices of resources
+    /// not used for `RELEASE_DELAY` frames, dropping references to them.
+    fn next_frame(&mut self) {
+        self.frame += 1;
+
+        let frame = self.frame;
+        let bitset = &mut self.bitset;
+        self.resources.retain(|_, &mut (index, used)| {
+            if frame - used > RELEASE_DELAY {
+                bitset.set(index);
+                false
+            } else {
+                true
+            }
+        });
+    }
+}
+
+/// Device-local buffer reused by passes for intermediate data.
+/// Grows when larger buffer is requested and never shrinks.
+struct ScratchBuffer {
+    usage: BufferUsage,
+    buffer: Option<Buffer>,
+}
+
+impl ScratchBuffer {
+    const fn new(usage: BufferUsage) -> Self {
+        ScratchBuffer {
+            usage,
+            buffer: None,
+        }
+    }
+
+    /// Returns buffer at least `size` bytes large.
+    fn get(&mut self, ctx: &Context, size: u64) -> Result<Buffer, OutOfMemory> {
+        match &self.buffer {
+            Some(buffer) if buffer.info().size >= size => Ok(buffer.clone()),
+            _ => {
+                let size = (size.max(1) + 4095) & !4095;
+                let buffer = ctx.create_buffer(BufferInfo {
+                    align: 255,
+                    size,
+                    usage: self.usage,
+                    capture_address: None,
+                })?;
+                self.buffer = Some(buffer.clone());
+                Ok(buffer)
             }
         }
     }
@@ -0,0 +1,445 @@
+use {
+    super::Pass,
+    crate::{
+        particles::ParticleEmitter,
+        renderer::{Context, ViewUniform},
+        scene::Global3,
+    },
+    bumpalo::{collections::Vec as BVec, Bump},
+    bytemuck::{Pod, Zeroable},
+    color_eyre::Report,
+    hecs::World,
+    illume::*,
+    nalgebra as na,
+    rand::{rngs::StdRng, Rng as _, SeedableRng as _},
+    std::{f32::consts::PI, mem::size_of},
+};
+
+/// Maximum number of simulated particles.
+/// Oldest particles are replaced by new ones when exceeded.
+pub const MAX_PARTICLES: u32 = 65536;
+
+/// Maximum number of particles spawned per frame.
+const MAX_SPAWNED: u32 = 4096;
+
+/// Longest step particles are advanced by.
+/// Longer frames slow simulation down instead of letting particles
+/// tunnel through surfaces.
+const MAX_DELTA: f32 = 0.05;
+
+/// Depth behind visible surface within which particles collide with it.
+/// Farther particles are assumed to be behind the geometry.
+const COLLISION_THICKNESS: f32 = 0.5;
+
+const GRAVITY: [f32; 3] = [0.0, -9.81, 0.0];
+
+pub struct Input {
+    /// Normals and depth in ray-tracing prepass layout.
+    /// Particles collide with surfaces they describe.
+    pub normal_depth: Image,
+
+    /// Emissive radiance particles are splatted into.
+    pub emissive: Image,
+
+    /// Globals of the rendered view.
+    pub view: ViewUniform,
+
+    /// Set with global SDF of static geometry.
+    pub sdf: DescriptorSet,
+
+    /// Seconds since previous frame.
+    pub delta: f32,
+}
+
+pub struct Output {
+    /// Buffer with `MAX_PARTICLES` particles.
+    /// Dead particles have non-positive life.
+    pub particles: Buffer,
+}
+
+/// Particle as seen by shader.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct Particle {
+    pos: [f32; 3],
+    life: f32,
+    vel: [f32; 3],
+    lifetime: f32,
+    emission: [f32; 3],
+    restitution: f32,
+}
+
+unsafe impl Zeroable for Particle {}
+unsafe impl Pod for Particle {}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct Params {
+    gravity: [f32; 3],
+    delta: f32,
+    count: u32,
+    thickness: f32,
+}
+
+unsafe impl Zeroable for Params {}
+unsafe impl Pod for Params {}
+
+/// Simulates particles spawned by `ParticleEmitter`s.
+///
+/// Collisions are resolved against global SDF of static geometry
+/// and in screen-space against depth and normals of ray-tracing
+/// prepass, so particles also bounce off visible dynamic surfaces.
+pub struct ParticlesPass {
+    particles: Buffer,
+    initialized: bool,
+
+    /// Spawned particles copied into ring of particles.
+    spawn: [MappableBuffer; 2],
+
+    /// Index in ring where next particle is spawned.
+    next: u32,
+    rng: StdRng,
+
+    /// Normal-depth and emissive views.
+    images: [[Option<ImageView>; 2]; 2],
+
+    pipeline: ComputePipeline,
+    pipeline_layout: PipelineLayout,
+    per_frame_sets: [DescriptorSet; 2],
+}
+
+impl ParticlesPass {
+    pub fn new(
+        ctx: &mut Context,
+        sdf_layout: &DescriptorSetLayout,
+    ) -> Result<Self, Report> {
+        let set_layout =
+            ctx.create_descriptor_set_layout(DescriptorSetLayoutInfo {
+                flags: DescriptorSetLayoutFlags::UPDATE_AFTER_BIND_POOL,
+                bindings: vec![
+                    // Normal-Depth
+                    DescriptorSetLayoutBinding {
+                        binding: 0,
+                        ty: DescriptorType::StorageImage,
+                        count: 1,
+                        stages: ShaderStageFlags::COMPUTE,
+                        flags: DescriptorBindingFlags::empty(),
+                    },
+                    // Emissive
+                    DescriptorSetLayoutBinding {
+                        binding: 1,
+                        ty: DescriptorType::StorageImage,
+                        count: 1,
+                        stages: ShaderStageFlags::COMPUTE,
+                        flags: DescriptorBindingFlags::empty(),
+                    },
+                    // Particles
+                    DescriptorSetLayoutBinding {
+                        binding: 2,
+                        ty: DescriptorType::StorageBuffer,
+                        count: 1,
+                        stages: ShaderStageFlags::COMPUTE,
+                        flags: DescriptorBindingFlags::empty(),
+                    },
+                ],
+            })?;
+
+        let pipeline_layout =
+            ctx.create_pipeline_layout(PipelineLayoutInfo {
+                sets: vec![
+                    set_layout.clone(),
+                    ctx.view_globals_layout().clone(),
+                    sdf_layout.clone(),
+                ],
+                push_constants: vec![PushConstant {
+                    stages: ShaderStageFlags::COMPUTE,
+                    offset: 0,
+                    size: size_of::<Params>() as u32,
+                }],
+            })?;
+
+        let shader = ComputeShader::with_main(
+            ctx.create_shader_module(
+                Spirv::new(
+                    include_bytes!("particles/simulate.comp.spv").to_vec(),
+                )
+                .into(),
+            )?,
+        );
+
+        let pipeline = ctx.create_compute_pipeline(ComputePipelineInfo {
+            shader,
+            layout: pipeline_layout.clone(),
+        })?;
+
+        let particles_size =
+            size_of::<Particle>() as u64 * u64::from(MAX_PARTICLES);
+        let particles = ctx.create_buffer(BufferInfo {
+            align: 255,
+            size: particles_size,
+            usage: BufferUsage::STORAGE | BufferUsage::TRANSFER_DST,
+            capture_address: None,
+        })?;
+
+        let spawn_size = size_of::<Particle>() as u64 * u64::from(MAX_SPAWNED);
+        let mut create_spawn = || {
+            ctx.create_mappable_buffer(
+                BufferInfo {
+                    align: 255,
+                    size: spawn_size,
+                    usage: BufferUsage::TRANSFER_SRC,
+                    capture_address: None,
+                },
+                MemoryUsage::UPLOAD,
+            )
+        };
+        let spawn = [create_spawn()?, create_spawn()?];
+
+        let set0 = ctx.create_descriptor_set(DescriptorSetInfo {
+            layout: set_layout.clone(),
+        })?;
+
+        let set1 = ctx
+            .create_descriptor_set(DescriptorSetInfo { layout: set_layout })?;
+
+        let per_frame_sets = [set0, set1];
+
+        for set in &per_frame_sets {
+            ctx.update_descriptor_sets(
+                &[WriteDescriptorSet {
+                    set,
+                    binding: 2,
+                    element: 0,
+                    descriptors: Descriptors::StorageBuffer(&[(
+                        particles.clone(),
+                        0,
+                        particles_size,
+                    )]),
+                }],
+                &[],
+            );
+        }
+
+        Ok(ParticlesPass {
+            particles,
+            initialized: false,
+            spawn,
+            next: 0,
+            rng: StdRng::seed_from_u64(0),
+            images: Default::default(),
+            pipeline,
+            pipeline_layout,
+            per_frame_sets,
+        })
+    }
+}
+
+impl<'a> Pass<'a> for ParticlesPass {
+    type Input = Input;
+    type Output = Output;
+
+    fn draw(
+        &mut self,
+        input: Input,
+        frame: u64,
+        wait: &[(PipelineStageFlags, Semaphore)],
+        signal: &[Semaphore],
+        fence: Option<&Fence>,
+        ctx: &mut Context,
+        world: &mut World,
+        bump: &Bump,
+    ) -> Result<Output, Report> {
+        let fid = (frame % 2) as usize;
+        let set = &self.per_frame_sets[fid];
+        let delta = input.delta.max(0.0).min(MAX_DELTA);
+
+        let mut spawned = BVec::new_in(bump);
+        for (_, (emitter, global)) in
+            world.query::<(&ParticleEmitter, &Global3)>().iter()
+        {
+            // Fractional part is spawned with matching probability.
+            let count = (emitter.rate * delta + self.rng.gen::<f32>()) as u32;
+            let origin = global.iso.translation.vector;
+
+            for _ in 0..count {
+                if spawned.len() >= MAX_SPAWNED as usize {
+                    break;
+                }
+
+                // Uniform over spherical cap around Y axis.
+                let cos_theta =
+                    1.0 - self.rng.gen::<f32>() * (1.0 - emitter.spread.cos());
+                let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+                let phi = self.rng.gen::<f32>() * 2.0 * PI;
+                let dir = global.iso.rotation
+                    * na::Vector3::new(
+                        sin_theta * phi.cos(),
+                        cos_theta,
+                        sin_theta * phi.sin(),
+                    );
+
+                spawned.push(Particle {
+                    pos: origin.into(),
+                    life: emitter.lifetime,
+                    vel: (dir * emitter.speed).into(),
+                    lifetime: emitter.lifetime,
+                    emission: emitter.emission,
+                    restitution: emitter.restitution,
+                });
+            }
+        }
+
+        let images = [&input.normal_depth, &input.emissive];
+
+        let mut writes = BVec::new_in(bump);
+        for (binding, (slot, image)) in
+            self.images[fid].iter_mut().zip(images.iter()).enumerate()
+        {
+            match slot {
+                Some(view) if view.info().image == **image => continue,
+                _ => {}
+            }
+
+            *slot = None;
+            let view =
+                ctx.create_image_view(ImageViewInfo::new((*image).clone()))?;
+            let view = slot.get_or_insert(view);
+
+            writes.push(WriteDescriptorSet {
+                set,
+                binding: binding as u32,
+                element: 0,
+                descriptors: Descriptors::StorageImage(
+                    bump.alloc([(view.clone(), Layout::General)]),
+                ),
+            });
+        }
+        ctx.update_descriptor_sets(&writes, &[]);
+
+        // Spawned particles replace oldest ones, wrapping around the ring.
+        let mut copies = BVec::new_in(bump);
+        if !spawned.is_empty() {
+            ctx.write_buffer(&mut self.spawn[fid], 0, &spawned)?;
+
+            let count = spawned.len() as u32;
+            let first = count.min(MAX_PARTICLES - self.next);
+            let stride = size_of::<Particle>() as u64;
+
+            copies.push(BufferCopy {
+                src_offset: 0,
+                dst_offset: u64::from(self.next) * stride,
+                size: u64::from(first) * stride,
+            });
+
+            if first < count {
+                copies.push(BufferCopy {
+                    src_offset: u64::from(first) * stride,
+                    dst_offset: 0,
+                    size: u64::from(count - first) * stride,
+                });
+            }
+
+            self.next = (self.next + count) % MAX_PARTICLES;
+        }
+
+        let mut encoder = ctx.queue.create_encoder()?;
+
+        // Particles are simulated by previous frame.
+        encoder.pipeline_barrier(
+            PipelineStageFlags::COMPUTE_SHADER,
+            PipelineStageFlags::TRANSFER,
+        );
+
+        // All particles are dead initially.
+        if !self.initialized {
+            encoder.fill_buffer(
+                &self.particles,
+                0,
+                self.particles.info().size,
+                0,
+            );
+            self.initialized = true;
+        }
+
+        if !copies.is_empty() {
+            encoder.copy_buffer(&self.spawn[fid], &self.particles, &copies);
+        }
+
+        encoder.pipeline_barrier(
+            PipelineStageFlags::TRANSFER,
+            PipelineStageFlags::COMPUTE_SHADER,
+        );
+
+        // Images are written by ray-tracing prepass.
+        let barriers: &[ImageMemoryBarrier<'_>] =
+            bump.alloc_slice_fill_iter(images.iter().map(|image| {
+                ImageLayoutTransition::transition_whole(
+                    image,
+                    Layout::ShaderReadOnlyOptimal..Layout::General,
+                )
+                .into()
+            }));
+
+        encoder.image_barriers(
+            PipelineStageFlags::RAY_TRACING_SHADER
+                | PipelineStageFlags::COMPUTE_SHADER,
+            PipelineStageFlags::COMPUTE_SHADER,
+            barriers,
+        );
+
+        encoder.bind_compute_pipeline(&self.pipeline);
+        encoder.bind_compute_descriptor_sets(
+            &self.pipeline_layout,
+            0,
+            std::slice::from_ref(set),
+            &[],
+        );
+        encoder.bind_compute_descriptor_sets(
+            &self.pipeline_layout,
+            1,
+            std::slice::from_ref(&input.view.set),
+            bump.alloc([input.view.offset]),
+        );
+        encoder.bind_compute_descriptor_sets(
+            &self.pipeline_layout,
+            2,
+            std::slice::from_ref(&input.sdf),
+            &[],
+        );
+        encoder.push_constants(
+            &self.pipeline_layout,
+            ShaderStageFlags::COMPUTE,
+            0,
+            bump.alloc([Params {
+                gravity: GRAVITY,
+                delta,
+                count: MAX_PARTICLES,
+                thickness: COLLISION_THICKNESS,
+            }]),
+        );
+        encoder.dispatch((MAX_PARTICLES + 63) / 64, 1, 1);
+
+        let barriers: &[ImageMemoryBarrier<'_>] =
+            bump.alloc_slice_fill_iter(images.iter().map(|image| {
+                ImageLayoutTransition::transition_whole(
+                    image,
+                    Layout::General..Layout::ShaderReadOnlyOptimal,
+                )
+                .into()
+            }));
+
+        encoder.image_barriers(
+            PipelineStageFlags::COMPUTE_SHADER,
+            PipelineStageFlags::FRAGMENT_SHADER
+                | PipelineStageFlags::COMPUTE_SHADER,
+            barriers,
+        );
+
+        ctx.record_barriers(encoder.barrier_count());
+        ctx.queue.submit(wait, encoder.finish()?, signal, fence)?;
+
+        Ok(Output {
+            particles: self.particles.clone(),
+        })
+    }
+}
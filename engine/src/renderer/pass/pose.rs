@@ -138,6 +138,7 @@ impl Pass<'_> for PosePass {
         bump: &Bump,
     ) -> Result<(), Report> {
         let findex = (frame & 1) as usize;
+        self.meshes.next_frame();
         let joints_descriptor;
         let mut pose_mesh_descriptors = BVec::new_in(bump);
         let mut writes = BVec::new_in(bump);
@@ -283,6 +284,7 @@ impl Pass<'_> for PosePass {
                         size,
                         align: 255,
                         usage: BufferUsage::STORAGE,
+                        capture_address: None,
                     },
                     MemoryUsage::UPLOAD | MemoryUsage::FAST_DEVICE_ACCESS,
                 )?;
@@ -329,8 +331,8 @@ impl Pass<'_> for PosePass {
             encoder.dispatch(vertex_count, 1, 1);
         }
 
-        let cbuf = encoder.finish();
-        ctx.queue.submit(wait, cbuf, signal, fence);
+        let cbuf = encoder.finish()?;
+        ctx.queue.submit(wait, cbuf, signal, fence)?;
 
         Ok(())
     }
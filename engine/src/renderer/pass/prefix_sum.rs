@@ -0,0 +1,268 @@
+//!
+//! Compute pass that replaces array of `u32` values with exclusive prefix
+//! sums.
+
+use {
+    super::{Pass, ScratchBuffer},
+    crate::renderer::Context,
+    bumpalo::{collections::Vec as BVec, Bump},
+    bytemuck::{Pod, Zeroable},
+    eyre::Report,
+    hecs::World,
+    illume::{
+        BufferRegion, BufferUsage, ComputePipeline, ComputePipelineInfo,
+        ComputeShader, DescriptorBindingFlags, DescriptorSet,
+        DescriptorSetInfo, DescriptorSetLayoutBinding,
+        DescriptorSetLayoutFlags, DescriptorSetLayoutInfo, DescriptorType,
+        Descriptors, Fence, PipelineLayout, PipelineLayoutInfo,
+        PipelineStageFlags, PushConstant, Semaphore, ShaderStageFlags, Spirv,
+        WriteDescriptorSet,
+    },
+};
+
+/// Number of values scanned by single workgroup.
+pub const SCAN_GROUP_SIZE: u32 = 512;
+
+pub struct Input {
+    /// Region of the buffer with values to scan.
+    /// Values are replaced with prefix sums in place.
+    pub values: BufferRegion,
+
+    /// Number of values in the region.
+    pub count: u32,
+}
+
+pub struct Output;
+
+pub struct PrefixSumPass {
+    layout: PipelineLayout,
+    scan_pipeline: ComputePipeline,
+    add_pipeline: ComputePipeline,
+    per_frame_sets: [DescriptorSet; 2],
+    scratch: ScratchBuffer,
+}
+
+impl PrefixSumPass {
+    pub fn new(ctx: &mut Context) -> Result<Self, Report> {
+        let set_layout =
+            ctx.create_descriptor_set_layout(DescriptorSetLayoutInfo {
+                flags: DescriptorSetLayoutFlags::empty(),
+                bindings: (0..2)
+                    .map(|binding| DescriptorSetLayoutBinding {
+                        binding,
+                        ty: DescriptorType::StorageBuffer,
+                        count: 1,
+                        stages: ShaderStageFlags::COMPUTE,
+                        flags: DescriptorBindingFlags::empty(),
+                    })
+                    .collect(),
+            })?;
+
+        let layout = ctx.create_pipeline_layout(PipelineLayoutInfo {
+            sets: vec![set_layout.clone()],
+            push_constants: vec![PushConstant {
+                stages: ShaderStageFlags::COMPUTE,
+                offset: 0,
+                size: std::mem::size_of::<Level>() as u32,
+            }],
+        })?;
+
+        let scan_shader = ComputeShader::with_main(
+            ctx.create_shader_module(
+                Spirv::new(include_bytes!("prefix_sum/scan.comp.spv").to_vec())
+                    .into(),
+            )?,
+        );
+
+        let add_shader = ComputeShader::with_main(
+            ctx.create_shader_module(
+                Spirv::new(include_bytes!("prefix_sum/add.comp.spv").to_vec())
+                    .into(),
+            )?,
+        );
+
+        let scan_pipeline =
+            ctx.create_compute_pipeline(ComputePipelineInfo {
+                shader: scan_shader,
+                layout: layout.clone(),
+            })?;
+
+        let add_pipeline =
+            ctx.create_compute_pipeline(ComputePipelineInfo {
+                shader: add_shader,
+                layout: layout.clone(),
+            })?;
+
+        let per_frame_set0 = ctx.create_descriptor_set(DescriptorSetInfo {
+            layout: set_layout.clone(),
+        })?;
+
+        let per_frame_set1 = ctx
+            .create_descriptor_set(DescriptorSetInfo { layout: set_layout })?;
+
+        Ok(PrefixSumPass {
+            layout,
+            scan_pipeline,
+            add_pipeline,
+            per_frame_sets: [per_frame_set0, per_frame_set1],
+            scratch: ScratchBuffer::new(BufferUsage::STORAGE),
+        })
+    }
+}
+
+impl Pass<'_> for PrefixSumPass {
+    type Input = Input;
+    type Output = Output;
+
+    fn draw(
+        &mut self,
+        input: Input,
+        frame: u64,
+        wait: &[(PipelineStageFlags, Semaphore)],
+        signal: &[Semaphore],
+        fence: Option<&Fence>,
+        ctx: &mut Context,
+        _world: &mut World,
+        bump: &Bump,
+    ) -> Result<Output, Report> {
+        assert!(u64::from(input.count) * 4 <= input.values.size);
+
+        let findex = (frame & 1) as usize;
+
+        // First level scans values in place.
+        // Each following level scans block sums of previous level
+        // which are stored in scratch buffer.
+        let mut levels = BVec::new_in(bump);
+        let mut count = input.count;
+        let mut data_offset = 0;
+        let mut sums_offset = 0;
+
+        loop {
+            let groups = group_count(count);
+            let mut flags = 0;
+
+            if !levels.is_empty() {
+                flags |= Level::DATA_SCRATCH_BIT;
+            }
+
+            if groups > 1 {
+                flags |= Level::WRITE_SUMS_BIT;
+            }
+
+            levels.push(Level {
+                count,
+                data_offset,
+                sums_offset,
+                flags,
+            });
+
+            if groups == 1 {
+                break;
+            }
+
+            data_offset = sums_offset;
+            sums_offset += groups;
+            count = groups;
+        }
+
+        let scratch_size = u64::from(sums_offset).max(1) * 4;
+
+        let scratch = self.scratch.get(ctx, scratch_size)?;
+
+        let descriptors = bump.alloc([
+            (
+                input.values.buffer.clone(),
+                input.values.offset,
+                input.values.size,
+            ),
+            (scratch.clone(), 0, scratch.info().size),
+        ]);
+
+        let set = &self.per_frame_sets[findex];
+        let writes = [
+            WriteDescriptorSet {
+                set,
+                binding: 0,
+                element: 0,
+                descriptors: Descriptors::StorageBuffer(&descriptors[0..1]),
+            },
+            WriteDescriptorSet {
+                set,
+                binding: 1,
+                element: 0,
+                descriptors: Descriptors::StorageBuffer(&descriptors[1..2]),
+            },
+        ];
+        ctx.update_descriptor_sets(&writes, &[]);
+
+        let mut encoder = ctx.queue.create_encoder()?;
+        encoder.bind_compute_descriptor_sets(
+            &self.layout,
+            0,
+            std::slice::from_ref(set),
+            &[],
+        );
+
+        encoder.bind_compute_pipeline(&self.scan_pipeline);
+        for (index, level) in levels.iter().enumerate() {
+            if index > 0 {
+                encoder.pipeline_barrier(
+                    PipelineStageFlags::COMPUTE_SHADER,
+                    PipelineStageFlags::COMPUTE_SHADER,
+                );
+            }
+
+            encoder.push_constants(
+                &self.layout,
+                ShaderStageFlags::COMPUTE,
+                0,
+                std::slice::from_ref(level),
+            );
+            encoder.dispatch(group_count(level.count), 1, 1);
+        }
+
+        // Propagate scanned block sums back to lower levels.
+        // Last level consists of single block and needs no propagation.
+        encoder.bind_compute_pipeline(&self.add_pipeline);
+        for level in levels.iter().rev().skip(1) {
+            encoder.pipeline_barrier(
+                PipelineStageFlags::COMPUTE_SHADER,
+                PipelineStageFlags::COMPUTE_SHADER,
+            );
+
+            encoder.push_constants(
+                &self.layout,
+                ShaderStageFlags::COMPUTE,
+                0,
+                std::slice::from_ref(level),
+            );
+            encoder.dispatch(group_count(level.count), 1, 1);
+        }
+
+        let cbuf = encoder.finish()?;
+        ctx.queue.submit(wait, cbuf, signal, fence)?;
+
+        Ok(Output)
+    }
+}
+
+fn group_count(count: u32) -> u32 {
+    ((count + SCAN_GROUP_SIZE - 1) / SCAN_GROUP_SIZE).max(1)
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct Level {
+    count: u32,
+    data_offset: u32,
+    sums_offset: u32,
+    flags: u32,
+}
+
+impl Level {
+    const DATA_SCRATCH_BIT: u32 = 1;
+    const WRITE_SUMS_BIT: u32 = 2;
+}
+
+unsafe impl Zeroable for Level {}
+unsafe impl Pod for Level {}
@@ -0,0 +1,328 @@
+//!
+//! Compute pass that sorts keys with optional values using radix sort.
+//! Sort is stable.
+
+use {
+    super::{Pass, ScratchBuffer},
+    crate::renderer::Context,
+    bumpalo::Bump,
+    bytemuck::{Pod, Zeroable},
+    eyre::Report,
+    hecs::World,
+    illume::{
+        BufferRegion, BufferUsage, ComputePipeline, ComputePipelineInfo,
+        ComputeShader, DescriptorBindingFlags, DescriptorSet,
+        DescriptorSetInfo, DescriptorSetLayoutBinding,
+        DescriptorSetLayoutFlags, DescriptorSetLayoutInfo, DescriptorType,
+        Descriptors, Fence, PipelineLayout, PipelineLayoutInfo,
+        PipelineStageFlags, PushConstant, Semaphore, ShaderStageFlags, Spirv,
+        WriteDescriptorSet,
+    },
+};
+
+/// Number of bits sorted in one pass.
+const RADIX_BITS: u32 = 4;
+
+/// Number of elements processed by single workgroup.
+const BLOCK_SIZE: u32 = 1024;
+
+/// Type of the sorted keys.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RadixKey {
+    U32,
+    U64,
+}
+
+impl RadixKey {
+    fn size(&self) -> u64 {
+        match self {
+            RadixKey::U32 => 4,
+            RadixKey::U64 => 8,
+        }
+    }
+
+    fn bits(&self) -> u32 {
+        match self {
+            RadixKey::U32 => 32,
+            RadixKey::U64 => 64,
+        }
+    }
+}
+
+pub struct Input {
+    /// Region of the buffer with keys.
+    /// Keys are sorted in place.
+    pub keys: BufferRegion,
+
+    /// Region of the buffer with `u32` values associated with keys.
+    /// Values are reordered along with keys.
+    pub values: Option<BufferRegion>,
+
+    /// Number of keys.
+    pub count: u32,
+
+    pub key: RadixKey,
+
+    /// Number of low key bits to sort by.
+    /// Higher bits are ignored.
+    /// `None` means all bits of the key.
+    pub significant_bits: Option<u32>,
+}
+
+pub struct Output;
+
+pub struct RadixSortPass {
+    layout: PipelineLayout,
+    count_pipeline: ComputePipeline,
+    scan_pipeline: ComputePipeline,
+    scatter_pipeline: ComputePipeline,
+    per_frame_sets: [DescriptorSet; 2],
+    keys_scratch: ScratchBuffer,
+    values_scratch: ScratchBuffer,
+    counts: ScratchBuffer,
+}
+
+impl RadixSortPass {
+    pub fn new(ctx: &mut Context) -> Result<Self, Report> {
+        let set_layout =
+            ctx.create_descriptor_set_layout(DescriptorSetLayoutInfo {
+                flags: DescriptorSetLayoutFlags::empty(),
+                bindings: (0..5)
+                    .map(|binding| DescriptorSetLayoutBinding {
+                        binding,
+                        ty: DescriptorType::StorageBuffer,
+                        count: 1,
+                        stages: ShaderStageFlags::COMPUTE,
+                        flags: DescriptorBindingFlags::empty(),
+                    })
+                    .collect(),
+            })?;
+
+        let layout = ctx.create_pipeline_layout(PipelineLayoutInfo {
+            sets: vec![set_layout.clone()],
+            push_constants: vec![PushConstant {
+                stages: ShaderStageFlags::COMPUTE,
+                offset: 0,
+                size: std::mem::size_of::<Params>() as u32,
+            }],
+        })?;
+
+        let count_shader = ComputeShader::with_main(
+            ctx.create_shader_module(
+                Spirv::new(
+                    include_bytes!("radix_sort/count.comp.spv").to_vec(),
+                )
+                .into(),
+            )?,
+        );
+
+        let scan_shader = ComputeShader::with_main(
+            ctx.create_shader_module(
+                Spirv::new(include_bytes!("radix_sort/scan.comp.spv").to_vec())
+                    .into(),
+            )?,
+        );
+
+        let scatter_shader = ComputeShader::with_main(
+            ctx.create_shader_module(
+                Spirv::new(
+                    include_bytes!("radix_sort/scatter.comp.spv").to_vec(),
+                )
+                .into(),
+            )?,
+        );
+
+        let count_pipeline =
+            ctx.create_compute_pipeline(ComputePipelineInfo {
+                shader: count_shader,
+                layout: layout.clone(),
+            })?;
+
+        let scan_pipeline =
+            ctx.create_compute_pipeline(ComputePipelineInfo {
+                shader: scan_shader,
+                layout: layout.clone(),
+            })?;
+
+        let scatter_pipeline =
+            ctx.create_compute_pipeline(ComputePipelineInfo {
+                shader: scatter_shader,
+                layout: layout.clone(),
+            })?;
+
+        let per_frame_set0 = ctx.create_descriptor_set(DescriptorSetInfo {
+            layout: set_layout.clone(),
+        })?;
+
+        let per_frame_set1 = ctx
+            .create_descriptor_set(DescriptorSetInfo { layout: set_layout })?;
+
+        Ok(RadixSortPass {
+            layout,
+            count_pipeline,
+            scan_pipeline,
+            scatter_pipeline,
+            per_frame_sets: [per_frame_set0, per_frame_set1],
+            keys_scratch: ScratchBuffer::new(BufferUsage::STORAGE),
+            values_scratch: ScratchBuffer::new(BufferUsage::STORAGE),
+            counts: ScratchBuffer::new(BufferUsage::STORAGE),
+        })
+    }
+}
+
+impl Pass<'_> for RadixSortPass {
+    type Input = Input;
+    type Output = Output;
+
+    fn draw(
+        &mut self,
+        input: Input,
+        frame: u64,
+        wait: &[(PipelineStageFlags, Semaphore)],
+        signal: &[Semaphore],
+        fence: Option<&Fence>,
+        ctx: &mut Context,
+        _world: &mut World,
+        bump: &Bump,
+    ) -> Result<Output, Report> {
+        let keys_size = u64::from(input.count) * input.key.size();
+        assert!(keys_size <= input.keys.size);
+
+        if let Some(values) = &input.values {
+            assert!(u64::from(input.count) * 4 <= values.size);
+        }
+
+        let bits = input
+            .significant_bits
+            .unwrap_or(input.key.bits())
+            .min(input.key.bits());
+
+        // Round up to even number of passes so that result ends up
+        // in the input buffers.
+        let mut passes = (bits + RADIX_BITS - 1) / RADIX_BITS;
+        passes += passes & 1;
+
+        let findex = (frame & 1) as usize;
+        let group_count = ((input.count + BLOCK_SIZE - 1) / BLOCK_SIZE).max(1);
+
+        let keys_scratch = self.keys_scratch.get(ctx, keys_size)?;
+        let values_scratch =
+            self.values_scratch.get(ctx, u64::from(input.count) * 4)?;
+        let counts = self
+            .counts
+            .get(ctx, u64::from(group_count) * (1 << RADIX_BITS) * 4)?;
+
+        let values = input.values.as_ref().unwrap_or(&input.keys);
+
+        let descriptors = bump.alloc([
+            (
+                input.keys.buffer.clone(),
+                input.keys.offset,
+                input.keys.size,
+            ),
+            (values.buffer.clone(), values.offset, values.size),
+            (keys_scratch.clone(), 0, keys_scratch.info().size),
+            (values_scratch.clone(), 0, values_scratch.info().size),
+            (counts.clone(), 0, counts.info().size),
+        ]);
+
+        let set = &self.per_frame_sets[findex];
+        let writes = bump.alloc_slice_fill_iter((0..5).map(|binding| {
+            WriteDescriptorSet {
+                set,
+                binding,
+                element: 0,
+                descriptors: Descriptors::StorageBuffer(
+                    &descriptors[binding as usize..][..1],
+                ),
+            }
+        }));
+        ctx.update_descriptor_sets(writes, &[]);
+
+        let mut flags = 0;
+        if input.key == RadixKey::U64 {
+            flags |= Params::KEY64_BIT;
+        }
+        if input.values.is_some() {
+            flags |= Params::HAS_VALUES_BIT;
+        }
+
+        let params =
+            bump.alloc_slice_fill_iter((0..passes).map(|pass| Params {
+                count: input.count,
+                group_count,
+                shift: pass * RADIX_BITS,
+                flags: if pass & 1 == 1 {
+                    flags | Params::SWAPPED_BIT
+                } else {
+                    flags
+                },
+            }));
+
+        let mut encoder = ctx.queue.create_encoder()?;
+        encoder.bind_compute_descriptor_sets(
+            &self.layout,
+            0,
+            std::slice::from_ref(set),
+            &[],
+        );
+
+        for (index, params) in params.iter().enumerate() {
+            if index > 0 {
+                encoder.pipeline_barrier(
+                    PipelineStageFlags::COMPUTE_SHADER,
+                    PipelineStageFlags::COMPUTE_SHADER,
+                );
+            }
+
+            encoder.push_constants(
+                &self.layout,
+                ShaderStageFlags::COMPUTE,
+                0,
+                std::slice::from_ref(params),
+            );
+
+            encoder.bind_compute_pipeline(&self.count_pipeline);
+            encoder.dispatch(group_count, 1, 1);
+
+            encoder.pipeline_barrier(
+                PipelineStageFlags::COMPUTE_SHADER,
+                PipelineStageFlags::COMPUTE_SHADER,
+            );
+
+            encoder.bind_compute_pipeline(&self.scan_pipeline);
+            encoder.dispatch(1, 1, 1);
+
+            encoder.pipeline_barrier(
+                PipelineStageFlags::COMPUTE_SHADER,
+                PipelineStageFlags::COMPUTE_SHADER,
+            );
+
+            encoder.bind_compute_pipeline(&self.scatter_pipeline);
+            encoder.dispatch(group_count, 1, 1);
+        }
+
+        let cbuf = encoder.finish()?;
+        ctx.queue.submit(wait, cbuf, signal, fence)?;
+
+        Ok(Output)
+    }
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct Params {
+    count: u32,
+    group_count: u32,
+    shift: u32,
+    flags: u32,
+}
+
+impl Params {
+    const KEY64_BIT: u32 = 1;
+    const HAS_VALUES_BIT: u32 = 2;
+    const SWAPPED_BIT: u32 = 4;
+}
+
+unsafe impl Zeroable for Params {}
+unsafe impl Pod for Params {}
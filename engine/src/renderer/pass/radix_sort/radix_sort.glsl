@@ -0,0 +1,37 @@
+layout(binding = 0, set = 0, std430) buffer KeysA { uint keys_a[]; };
+layout(binding = 1, set = 0, std430) buffer ValuesA { uint values_a[]; };
+layout(binding = 2, set = 0, std430) buffer KeysB { uint keys_b[]; };
+layout(binding = 3, set = 0, std430) buffer ValuesB { uint values_b[]; };
+layout(binding = 4, set = 0, std430) buffer Counts { uint counts[]; };
+
+layout(push_constant) uniform Params {
+    uint count;
+    uint group_count;
+    uint shift;
+    uint flags;
+};
+
+const uint KEY64_BIT = 1;
+const uint HAS_VALUES_BIT = 2;
+const uint SWAPPED_BIT = 4;
+
+const uint RADIX = 16;
+const uint RADIX_MASK = 15;
+const uint ITEMS_PER_INVOCATION = 4;
+const uint BLOCK_SIZE = 256 * ITEMS_PER_INVOCATION;
+
+uint key_words() {
+    return (flags & KEY64_BIT) != 0 ? 2 : 1;
+}
+
+// Returns radix digit of the key at specified index in source buffer.
+uint load_digit(uint index) {
+    uint word = index * key_words() + shift / 32;
+    uint key;
+    if ((flags & SWAPPED_BIT) != 0) {
+        key = keys_b[word];
+    } else {
+        key = keys_a[word];
+    }
+    return (key >> (shift % 32)) & RADIX_MASK;
+}
@@ -2,30 +2,122 @@ use crate::renderer::PositionNormalTangent3d;
 
 use {
     super::Pass,
-    crate::renderer::{
-        vertex::{
-            vertex_layouts_for_pipeline, PositionNormalTangent3dUV,
-            VertexType as _,
+    crate::{
+        renderer::{
+            vertex::{vertex_input_for_layout, VertexAttributes, VertexLayout},
+            AlphaMode, Context, DepthOrder, DrawList, Material, Mesh,
+            Renderable, SortKey,
         },
-        Context,
+        scene::Global3,
     },
-    bumpalo::{collections::Vec as BVec, Bump},
+    bumpalo::Bump,
+    bytemuck::{Pod, Zeroable},
     color_eyre::Report,
     hecs::World,
     illume::*,
+    nalgebra as na,
     smallvec::smallvec,
+    std::{collections::HashMap, mem::size_of},
 };
 
+/// Attributes consumed by raster vertex shader.
+const RASTER_ATTRIBUTES: VertexAttributes =
+    VertexAttributes::from_bits_truncate(
+        VertexAttributes::POSITION.bits()
+            | VertexAttributes::NORMAL.bits()
+            | VertexAttributes::UV0.bits(),
+    );
+
+/// Attributes consumed by depth pre-pass vertex shader.
+const DEPTH_ATTRIBUTES: VertexAttributes = VertexAttributes::POSITION;
+
 pub struct Input {
-    target: Image,
+    pub target: Image,
+
+    /// Projection multiplied by view transform of the camera.
+    pub view_proj: na::Matrix4<f32>,
 }
 
 pub struct Output;
 
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct DrawConstants {
+    view_proj: [[f32; 4]; 4],
+
+    /// Index of renderable in object table.
+    object: u32,
+    _pad: [u32; 3],
+}
+
+unsafe impl Zeroable for DrawConstants {}
+unsafe impl Pod for DrawConstants {}
+
+/// Pipelines of both subpasses specialized for the same vertex layout
+/// and culling mode, unless culling is dynamic.
+#[derive(Clone)]
+struct LayoutPipelines {
+    /// Writes depth in pre-pass.
+    depth: GraphicsPipeline,
+
+    /// Shades fragments that pass depth test against pre-pass.
+    color: GraphicsPipeline,
+
+    /// Blends fragments over shaded ones without writing depth.
+    blend: GraphicsPipeline,
+}
+
+/// Layer of opaque draws.
+const OPAQUE_LAYER: u8 = 0;
+
+/// Layer of draws with masked alpha.
+/// They don't write depth in pre-pass.
+const MASK_LAYER: u8 = 1;
+
+/// Layer of blended draws.
+/// They are drawn last, back to front, and don't write depth.
+const BLEND_LAYER: u8 = 2;
+
+/// Renderable gathered for drawing.
+struct Draw<'a> {
+    /// Index of pipelines in per-frame list.
+    pipeline: usize,
+
+    /// Index of distinct mesh in the frame.
+    mesh_index: usize,
+    mesh: &'a Mesh,
+
+    /// Culling required by material.
+    culling: Option<Culling>,
+
+    /// Distance from camera plane.
+    depth: f32,
+    constants: DrawConstants,
+}
+
+/// Raster fallback renderer.
+///
+/// Opaque draws are rendered into depth buffer first, front to back,
+/// with depth-only pipelines. Second subpass then shades them
+/// with depth test against pre-pass, so every pixel is shaded once,
+/// and draws are ordered by `SortKey` to minimize state changes.
+/// Blended draws follow in the same subpass, sorted back to front.
+/// Refraction is not supported here, so refractive materials are blended.
+///
+/// With `ExtendedDynamicState` feature culling is dynamic state,
+/// so single-sided and double-sided materials share pipelines.
 pub struct RasterPass {
     render_pass: RenderPass,
     pipeline_layout: PipelineLayout,
-    graphics_pipeline: GraphicsPipeline,
+    vert: VertexShader,
+    depth_vert: VertexShader,
+    frag: FragmentShader,
+
+    /// Culling is set per draw instead of baked into pipelines.
+    dynamic_culling: bool,
+
+    /// Pipelines specialized for vertex layouts and culling.
+    pipelines: HashMap<(VertexLayout, bool), LayoutPipelines>,
     framebuffers: lru::LruCache<Image, Framebuffer>,
 }
 
@@ -38,6 +130,13 @@ impl RasterPass {
             "main",
         );
 
+        let depth_vert = VertexShader::new(
+            ctx.create_shader_module(ShaderModuleInfo::spirv(
+                include_bytes!("raster/depth.vert.spv").to_vec(),
+            ))?,
+            "main",
+        );
+
         let frag = FragmentShader::new(
             ctx.create_shader_module(ShaderModuleInfo::spirv(
                 include_bytes!("raster/main.frag.spv").to_vec(),
@@ -64,45 +163,163 @@ impl RasterPass {
                     final_layout: Layout::Present,
                 },
             ],
-            subpasses: smallvec![Subpass {
-                colors: smallvec![1],
-                depth: Some(0),
+            subpasses: smallvec![
+                Subpass {
+                    colors: smallvec![],
+                    depth: Some(0),
+                    resolves: smallvec![],
+                },
+                Subpass {
+                    colors: smallvec![1],
+                    depth: Some(0),
+                    resolves: smallvec![],
+                }
+            ],
+            dependencies: smallvec![SubpassDependency {
+                src: Some(0),
+                dst: Some(1),
+                src_stages: PipelineStageFlags::LATE_FRAGMENT_TESTS,
+                dst_stages: PipelineStageFlags::EARLY_FRAGMENT_TESTS,
             }],
-            dependencies: smallvec![],
         })?;
 
         let pipeline_layout =
             ctx.create_pipeline_layout(PipelineLayoutInfo {
-                sets: vec![],
+                sets: vec![ctx.object_table().layout().clone()],
                 push_constants: vec![PushConstant {
                     stages: ShaderStageFlags::VERTEX,
                     offset: 0,
-                    size: 64,
+                    size: size_of::<DrawConstants>() as u32,
                 }],
             })?;
 
-        let (vertex_bindings, vertex_attributes) =
-            vertex_layouts_for_pipeline(&[PositionNormalTangent3dUV::layout()]);
-
-        let graphics_pipeline =
-            ctx.create_graphics_pipeline(graphics_pipeline_info! {
-                vertex_bindings: vertex_bindings,
-                vertex_attributes: vertex_attributes,
-                vertex_shader: vert,
-                layout: pipeline_layout.clone(),
-                render_pass: render_pass.clone(),
-                rasterizer: rasterizer!{
-                    fragment_shader: frag,
-                }
-            })?;
-
         Ok(RasterPass {
             render_pass,
             pipeline_layout,
-            graphics_pipeline,
+            vert,
+            depth_vert,
+            frag,
+            dynamic_culling: ctx.has_feature(Feature::ExtendedDynamicState),
+            pipelines: HashMap::new(),
             framebuffers: lru::LruCache::new(4),
         })
     }
+
+    /// Returns key of pipelines for vertex layout and material.
+    /// Culling is not a part of the key when it is dynamic.
+    fn pipeline_key(
+        &self,
+        layout: &VertexLayout,
+        material: &Material,
+    ) -> (VertexLayout, bool) {
+        (
+            layout.clone(),
+            !self.dynamic_culling && material.double_sided,
+        )
+    }
+
+    /// Returns pipelines specialized for vertex layout
+    /// with culling mode required by material.
+    /// Returns `None` if layout lacks attributes shader consumes.
+    fn pipelines(
+        &mut self,
+        layout: &VertexLayout,
+        material: &Material,
+        ctx: &Context,
+    ) -> Result<Option<LayoutPipelines>, Report> {
+        let key = self.pipeline_key(layout, material);
+        if let Some(pipelines) = self.pipelines.get(&key) {
+            return Ok(Some(pipelines.clone()));
+        }
+
+        let (vertex_bindings, vertex_attributes) = match vertex_input_for_layout(
+            layout,
+            RASTER_ATTRIBUTES,
+        ) {
+            Some(input) => input,
+            None => {
+                tracing::warn!(
+                        "Vertex layout with {:?} lacks attributes for raster pipeline",
+                        layout.attributes(),
+                    );
+                return Ok(None);
+            }
+        };
+
+        // Layout with all raster attributes has position as well.
+        let (depth_bindings, depth_attributes) =
+            vertex_input_for_layout(layout, DEPTH_ATTRIBUTES).unwrap();
+
+        let culling = material_culling(material);
+
+        let dynamic_states = if self.dynamic_culling {
+            DynamicStates::CULL_MODE
+        } else {
+            DynamicStates::empty()
+        };
+
+        let depth = ctx.create_graphics_pipeline(graphics_pipeline_info! {
+            vertex_bindings: depth_bindings,
+            vertex_attributes: depth_attributes,
+            vertex_shader: self.depth_vert.clone(),
+            layout: self.pipeline_layout.clone(),
+            render_pass: self.render_pass.clone(),
+            subpass: 0u32,
+            dynamic_states: dynamic_states,
+            rasterizer: rasterizer!{
+                culling: culling,
+                depth: true,
+            }
+        })?;
+
+        // Opaque fragments have equal depth after pre-pass
+        // and masked ones are not in pre-pass, so they still write depth.
+        let color = ctx.create_graphics_pipeline(graphics_pipeline_info! {
+            vertex_bindings: vertex_bindings.clone(),
+            vertex_attributes: vertex_attributes.clone(),
+            vertex_shader: self.vert.clone(),
+            layout: self.pipeline_layout.clone(),
+            render_pass: self.render_pass.clone(),
+            subpass: 1u32,
+            dynamic_states: dynamic_states,
+            rasterizer: rasterizer!{
+                culling: culling,
+                depth_test: DepthTest {
+                    compare: CompareOp::LessOrEqual,
+                    write: true,
+                },
+                fragment_shader: self.frag.clone(),
+            }
+        })?;
+
+        // Blending relies on draw order, so depth is tested but not written.
+        let blend = ctx.create_graphics_pipeline(graphics_pipeline_info! {
+            vertex_bindings: vertex_bindings,
+            vertex_attributes: vertex_attributes,
+            vertex_shader: self.vert.clone(),
+            layout: self.pipeline_layout.clone(),
+            render_pass: self.render_pass.clone(),
+            subpass: 1u32,
+            dynamic_states: dynamic_states,
+            rasterizer: rasterizer!{
+                culling: culling,
+                depth_test: DepthTest {
+                    compare: CompareOp::LessOrEqual,
+                    write: false,
+                },
+                fragment_shader: self.frag.clone(),
+                color_blend: ColorBlend::default(),
+            }
+        })?;
+
+        let pipelines = LayoutPipelines {
+            depth,
+            color,
+            blend,
+        };
+        self.pipelines.insert(key, pipelines.clone());
+        Ok(Some(pipelines))
+    }
 }
 
 impl Pass<'_> for RasterPass {
@@ -121,33 +338,269 @@ impl Pass<'_> for RasterPass {
         bump: &Bump,
     ) -> Result<Output, Report> {
         let target = input.target;
+        let extent = target.info().extent.into_2d();
 
-        let framebuffer;
-        let fb = match self.framebuffers.get(&target) {
-            Some(fb) => fb,
+        let framebuffer = match self.framebuffers.get(&target) {
+            Some(fb) => fb.clone(),
             None => {
-                let extent = target.info().extent.into_2d();
+                // Depth is never stored, so it may live in
+                // lazily allocated memory on tile-based devices.
+                let depth = ctx.create_image_with_memory_usage(
+                    ImageInfo {
+                        extent: extent.into(),
+                        format: Format::D32Sfloat,
+                        levels: 1,
+                        layers: 1,
+                        samples: Samples1,
+                        usage: ImageUsage::DEPTH_STENCIL_ATTACHMENT
+                            | ImageUsage::TRANSIENT_ATTACHMENT,
+                    },
+                    MemoryUsage::LAZILY_ALLOCATED,
+                )?;
+                let depth = ctx.create_image_view(ImageViewInfo::new(depth))?;
                 let view =
                     ctx.create_image_view(ImageViewInfo::new(target.clone()))?;
-                framebuffer = ctx.create_framebuffer(FramebufferInfo {
+                let framebuffer = ctx.create_framebuffer(FramebufferInfo {
                     render_pass: self.render_pass.clone(),
-                    views: smallvec![view],
+                    views: smallvec![depth, view],
                     extent,
                 })?;
 
                 self.framebuffers.put(target, framebuffer.clone());
-                &framebuffer
+                framebuffer
             }
         };
 
+        let mut query = world.query::<(&Renderable, &Global3)>();
+
+        let mut pipelines = Vec::new();
+        let mut pipeline_indices = HashMap::new();
+        let mut material_indices = HashMap::new();
+        let mut mesh_indices = HashMap::new();
+        let mut draws = DrawList::new_in(bump);
+
+        for (entity, (renderable, global)) in query.iter() {
+            let layer = match renderable.material.alpha_mode {
+                AlphaMode::Opaque => OPAQUE_LAYER,
+                AlphaMode::Mask { .. } => MASK_LAYER,
+                AlphaMode::Blend | AlphaMode::Refract { .. } => BLEND_LAYER,
+            };
+
+            // Renderables added after frame began are not in the table yet.
+            let object = match ctx.object_table().index(entity) {
+                Some(object) => object,
+                None => continue,
+            };
+
+            let layout = match renderable.mesh.bindings().first() {
+                Some(binding) => &binding.layout,
+                None => continue,
+            };
+
+            let key = self.pipeline_key(layout, &renderable.material);
+            let pipeline = match pipeline_indices.get(&key) {
+                Some(&index) => index,
+                None => {
+                    match self.pipelines(layout, &renderable.material, ctx)? {
+                        Some(layout_pipelines) => {
+                            pipelines.push(layout_pipelines);
+                            pipeline_indices.insert(key, pipelines.len() - 1);
+                            pipelines.len() - 1
+                        }
+                        None => continue,
+                    }
+                }
+            };
+
+            let next = material_indices.len();
+            let material =
+                *material_indices.entry(&renderable.material).or_insert(next);
+
+            let next = mesh_indices.len();
+            let mesh_index =
+                *mesh_indices.entry(&renderable.mesh).or_insert(next);
+
+            let origin =
+                input.view_proj * global.iso.translation.vector.push(1.0);
+
+            // Shading order doesn't affect overdraw after depth pre-pass,
+            // so draws are grouped to minimize state changes.
+            // Blending is order dependent, so those draws are sorted by depth.
+            let key = if layer == BLEND_LAYER {
+                SortKey::depth_first(
+                    layer,
+                    origin.w,
+                    DepthOrder::BackToFront,
+                    pipeline,
+                )
+            } else {
+                SortKey::new(
+                    layer,
+                    pipeline,
+                    material,
+                    origin.w,
+                    DepthOrder::FrontToBack,
+                )
+            };
+
+            draws.push(
+                key,
+                Draw {
+                    pipeline,
+                    mesh_index,
+                    mesh: &renderable.mesh,
+                    culling: material_culling(&renderable.material),
+                    depth: origin.w,
+                    constants: DrawConstants {
+                        view_proj: input.view_proj.into(),
+                        object,
+                        _pad: [0; 3],
+                    },
+                },
+            );
+        }
+
+        draws.sort();
+
+        // Pre-pass goes front to back so that
+        // early depth test rejects occluded fragments.
+        let mut prepass = DrawList::new_in(bump);
+        for (key, draw) in draws.iter() {
+            if key.layer() == OPAQUE_LAYER {
+                prepass.push(
+                    SortKey::depth_first(
+                        OPAQUE_LAYER,
+                        draw.depth,
+                        DepthOrder::FrontToBack,
+                        draw.pipeline,
+                    ),
+                    draw,
+                );
+            }
+        }
+        prepass.sort();
+
+        let objects = ctx.object_table().set().clone();
         let mut encoder = ctx.queue.create_encoder()?;
 
-        let encoder = encoder.with_render_pass(
+        let mut render_pass_encoder = encoder.with_render_pass(
             &self.render_pass,
-            fb,
+            &framebuffer,
             &[ClearValue::DepthStencil(1.0, 0)],
         );
 
+        // Layouts of all pipelines are the same,
+        // so the set stays bound through both subpasses.
+        render_pass_encoder.bind_graphics_descriptor_sets(
+            &self.pipeline_layout,
+            0,
+            std::slice::from_ref(&objects),
+            &[],
+        );
+
+        render_pass_encoder.set_viewport(Viewport {
+            x: Bounds {
+                offset: 0.0.into(),
+                size: (extent.width as f32).into(),
+            },
+            y: Bounds {
+                offset: 0.0.into(),
+                size: (extent.height as f32).into(),
+            },
+            z: Bounds {
+                offset: 0.0.into(),
+                size: 1.0.into(),
+            },
+        });
+        render_pass_encoder.set_scissor(extent.into());
+
+        let (mut pipeline, mut mesh) = (None, None);
+        for (_, &draw) in prepass.iter() {
+            if pipeline != Some(draw.pipeline) {
+                render_pass_encoder
+                    .bind_graphics_pipeline(&pipelines[draw.pipeline].depth);
+                pipeline = Some(draw.pipeline);
+            }
+            self.encode_draw(&mut render_pass_encoder, draw, &mut mesh, bump);
+        }
+
+        render_pass_encoder.next_subpass();
+
+        let mut pipeline = None;
+        for (key, draw) in draws.iter() {
+            let blend = key.layer() == BLEND_LAYER;
+            if pipeline != Some((draw.pipeline, blend)) {
+                let layout_pipelines = &pipelines[draw.pipeline];
+                render_pass_encoder.bind_graphics_pipeline(if blend {
+                    &layout_pipelines.blend
+                } else {
+                    &layout_pipelines.color
+                });
+                pipeline = Some((draw.pipeline, blend));
+            }
+            self.encode_draw(&mut render_pass_encoder, draw, &mut mesh, bump);
+        }
+
+        drop(render_pass_encoder);
+        ctx.record_barriers(encoder.barrier_count());
+        ctx.queue.submit(wait, encoder.finish()?, signal, fence)?;
+
         Ok(Output)
     }
 }
+
+impl RasterPass {
+    /// Encodes draw after its pipeline is bound.
+    /// Buffers of the mesh are bound unless it is already bound.
+    fn encode_draw<'a>(
+        &'a self,
+        encoder: &mut RenderPassEncoder<'_, 'a>,
+        draw: &'a Draw<'a>,
+        bound: &mut Option<usize>,
+        bump: &'a Bump,
+    ) {
+        if self.dynamic_culling {
+            encoder.set_cull_mode(draw.culling);
+        }
+
+        if *bound != Some(draw.mesh_index) {
+            *bound = Some(draw.mesh_index);
+
+            let binding = &draw.mesh.bindings()[0];
+            encoder.bind_vertex_buffers(
+                0,
+                bump.alloc([(binding.buffer.clone(), binding.offset)]),
+            );
+
+            if let Some(indices) = draw.mesh.indices() {
+                encoder.bind_index_buffer(
+                    &indices.buffer,
+                    indices.offset,
+                    indices.index_type,
+                );
+            }
+        }
+
+        encoder.push_constants(
+            &self.pipeline_layout,
+            ShaderStageFlags::VERTEX,
+            0,
+            std::slice::from_ref(&draw.constants),
+        );
+
+        if draw.mesh.indices().is_some() {
+            encoder.draw_indexed(0..draw.mesh.count(), 0, 0..1);
+        } else {
+            encoder.draw(0..draw.mesh.count(), 0..1);
+        }
+    }
+}
+
+/// Returns culling mode required by material.
+fn material_culling(material: &Material) -> Option<Culling> {
+    if material.double_sided {
+        None
+    } else {
+        Some(Culling::Back)
+    }
+}
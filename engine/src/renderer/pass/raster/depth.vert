@@ -0,0 +1,21 @@
+#version 460
+#extension GL_GOOGLE_include_directive : enable
+
+#include "../common/objects.glsl"
+
+// Locations match `Semantics::location`.
+layout(location = 0) in vec3 position;
+
+layout(push_constant) uniform Draw {
+    mat4 view_proj;
+    uint object;
+} draw;
+
+// Must match depth tested by `main.vert`.
+invariant gl_Position;
+
+void main() {
+    mat4 model = objects[draw.object].transform;
+
+    gl_Position = draw.view_proj * (model * vec4(position, 1.));
+}
@@ -4,8 +4,8 @@ use {
         animate::Pose,
         light::{DirectionalLight, PointLight, SkyLight},
         renderer::{
-            ray_tracing_transform_matrix_from_nalgebra, Context, Mesh,
-            PoseMesh, PositionNormalTangent3dUV, Renderable, Texture,
+            image_size, ray_tracing_transform_matrix_from_nalgebra, Context,
+            Mesh, PoseMesh, PositionNormalTangent3dUV, Renderable, Texture,
             VertexType as _,
         },
         scene::Global3,
@@ -99,7 +99,9 @@ impl RayProbe {
     ) -> Result<Self, Report> {
         // Create pipeline.
         let set_layout = ctx.create_descriptor_set_layout(DescriptorSetLayoutInfo {
-                flags: DescriptorSetLayoutFlags::empty(),
+                // Mesh descriptors are written when meshes are loaded
+                // while the set may be bound.
+                flags: DescriptorSetLayoutFlags::UPDATE_AFTER_BIND_POOL,
                 bindings: vec![
                     // TLAS.
                     DescriptorSetLayoutBinding {
@@ -126,7 +128,7 @@ impl RayProbe {
                         ty: DescriptorType::StorageBuffer,
                         count: MAX_INSTANCE_COUNT.into(),
                         stages: ShaderStageFlags::CLOSEST_HIT,
-                        flags: DescriptorBindingFlags::PARTIALLY_BOUND | DescriptorBindingFlags::UPDATE_UNUSED_WHILE_PENDING,
+                        flags: DescriptorBindingFlags::PARTIALLY_BOUND | DescriptorBindingFlags::UPDATE_UNUSED_WHILE_PENDING | DescriptorBindingFlags::UPDATE_AFTER_BIND,
                     },
                     // Vertex input.
                     DescriptorSetLayoutBinding {
@@ -134,7 +136,7 @@ impl RayProbe {
                         ty: DescriptorType::StorageBuffer,
                         count: MAX_INSTANCE_COUNT.into(),
                         stages: ShaderStageFlags::CLOSEST_HIT,
-                        flags: DescriptorBindingFlags::PARTIALLY_BOUND | DescriptorBindingFlags::UPDATE_UNUSED_WHILE_PENDING,
+                        flags: DescriptorBindingFlags::PARTIALLY_BOUND | DescriptorBindingFlags::UPDATE_UNUSED_WHILE_PENDING | DescriptorBindingFlags::UPDATE_AFTER_BIND,
                     },
                     // Textures
                     DescriptorSetLayoutBinding {
@@ -313,6 +315,9 @@ impl RayProbe {
                 ],
                 max_recursion_depth: 2,
                 layout: pipeline_layout.clone(),
+                libraries: Vec::new(),
+                interface: None,
+                library: false,
             })?;
 
         let probes_binding_table = ctx.create_shader_binding_table(
@@ -355,6 +360,7 @@ impl RayProbe {
             align: 255,
             size: tlas_sizes.acceleration_structure_size,
             usage: BufferUsage::ACCELERATION_STRUCTURE_STORAGE,
+            capture_address: None,
         })?;
 
         let tlas =
@@ -369,6 +375,7 @@ impl RayProbe {
             align: 255,
             size: tlas_sizes.build_scratch_size,
             usage: BufferUsage::DEVICE_ADDRESS,
+            capture_address: None,
         })?;
 
         tracing::trace!("TLAS scratch allocated");
@@ -381,6 +388,7 @@ impl RayProbe {
                     | BufferUsage::STORAGE
                     | BufferUsage::ACCELERATION_STRUCTURE_BUILD_INPUT
                     | BufferUsage::DEVICE_ADDRESS,
+                capture_address: None,
             },
             MemoryUsage::FAST_DEVICE_ACCESS,
         )?;
@@ -531,6 +539,10 @@ impl<'a> Pass<'a> for RayProbe {
 
         let findex = (frame & 1) as u32;
 
+        self.meshes.next_frame();
+        self.albedo.next_frame();
+        self.normal.next_frame();
+
         let config = world
             .query::<&Config>()
             .iter()
@@ -577,7 +589,7 @@ impl<'a> Pass<'a> for RayProbe {
 
                 let m = global.to_homogeneous();
 
-                let (mut mesh_index, new) =
+                let (mesh_index, new) =
                     self.meshes.index(renderable.mesh.clone());
                 if new {
                     let vectors = renderable
@@ -644,7 +656,7 @@ impl<'a> Pass<'a> for RayProbe {
                     let vectors_size: u64 = vectors.layout.stride as u64
                         * renderable.mesh.vertex_count() as u64;
 
-                    mesh_index = anim_vertices_descriptors.len() as u32;
+                    let anim_index = anim_vertices_descriptors.len() as u32;
 
                     anim_vertices_descriptors.push((
                         vectors_buffer,
@@ -668,73 +680,91 @@ impl<'a> Pass<'a> for RayProbe {
                         AccelerationStructureInstance::new(blas_address)
                             .with_transform(
                                 ray_tracing_transform_matrix_from_nalgebra(&m),
-                            ),
+                            )
+                            .with_custom_index(mesh_index),
                     );
 
-                    true
+                    // Zero means not animated.
+                    anim_index + 1
                 } else {
                     acc_instances.push(
                         AccelerationStructureInstance::new(blas_address)
                             .with_transform(
                                 ray_tracing_transform_matrix_from_nalgebra(&m),
-                            ),
+                            )
+                            .with_custom_index(mesh_index),
                     );
-                    false
+                    0
                 };
 
-                let albedo_index = if let Some(albedo) =
-                    &renderable.material.albedo
-                {
-                    let (albedo_index, new) = self.albedo.index(albedo.clone());
-
-                    if new {
-                        let descriptors =
-                            Descriptors::CombinedImageSampler(bump.alloc([(
-                                albedo.image.clone(),
-                                Layout::General,
-                                albedo.sampler.clone(),
-                            )]));
-                        writes.push(WriteDescriptorSet {
-                            set: &self.set,
-                            binding: 4,
-                            element: albedo_index,
-                            descriptors,
-                        });
-                    }
-
-                    albedo_index + 1
-                } else {
-                    0
+                let albedo_index = match &renderable.material.albedo {
+                    Some(albedo) => texture_index(
+                        &mut self.albedo,
+                        albedo,
+                        &self.set,
+                        4,
+                        &mut writes,
+                        bump,
+                    ),
+                    None => 0,
+                };
+
+                let normal_index = match &renderable.material.normal {
+                    Some(normal) => texture_index(
+                        &mut self.normal,
+                        normal,
+                        &self.set,
+                        5,
+                        &mut writes,
+                        bump,
+                    ),
+                    None => 0,
                 };
 
-                let normal_index = if let Some(normal) =
-                    &renderable.material.normal
+                // Page table and albedo atlas share albedo array.
+                let virtual_texture = match &renderable.material.virtual_texture
                 {
-                    let (normal_index, new) = self.normal.index(normal.clone());
-
-                    if new {
-                        let descriptors =
-                            Descriptors::CombinedImageSampler(bump.alloc([(
-                                normal.image.clone(),
-                                Layout::General,
-                                normal.sampler.clone(),
-                            )]));
-                        writes.push(WriteDescriptorSet {
-                            set: &self.set,
-                            binding: 5,
-                            element: normal_index,
-                            descriptors,
-                        });
-                    }
-
-                    normal_index + 1
-                } else {
-                    0
+                    Some(vt) => ShaderVirtualTexture {
+                        page_table: texture_index(
+                            &mut self.albedo,
+                            vt.page_table(),
+                            &self.set,
+                            4,
+                            &mut writes,
+                            bump,
+                        ),
+                        albedo: texture_index(
+                            &mut self.albedo,
+                            vt.albedo(),
+                            &self.set,
+                            4,
+                            &mut writes,
+                            bump,
+                        ),
+                        normal: match vt.normal() {
+                            Some(normal) => texture_index(
+                                &mut self.normal,
+                                normal,
+                                &self.set,
+                                5,
+                                &mut writes,
+                                bump,
+                            ),
+                            None => 0,
+                        },
+                        id: vt.id(),
+                        pages: vt.info().pages,
+                        levels: vt.levels(),
+                        atlas_pages: vt.info().atlas_pages,
+                        page_size: vt.info().page_size,
+                        border: vt.info().border,
+                        uv_scale: vt.uv_scale(),
+                    },
+                    None => ShaderVirtualTexture::zeroed(),
                 };
 
                 instances.push(ShaderInstance {
                     transform: m,
-                    mesh: mesh_index,
                     albedo_sampler: albedo_index,
                     normal_sampler: normal_index,
                     albedo_factor: {
@@ -750,7 +780,8 @@ impl<'a> Pass<'a> for RayProbe {
                         .material
                         .normal_factor
                         .into_inner(),
-                    anim: anim as u32,
+                    anim,
+                    virtual_texture,
                 });
             } else {
                 tracing::error!("Missing BLAS for mesh @ {:?}", entity);
@@ -1131,11 +1162,21 @@ impl<'a> Pass<'a> for RayProbe {
         encoder
             .trace_rays(&self.viewport_binding_table, input.extent.into_3d());
 
-        let cbuf = encoder.finish();
+        ctx.record_barriers(encoder.barrier_count());
+        ctx.record_transient(
+            self.tlas.info().region.size
+                + self.scratch.info().size
+                + [&probes_data, &probes_compiled, &output_image]
+                    .iter()
+                    .map(|view| image_size(view.info().image.info()))
+                    .sum::<u64>(),
+        );
+
+        let cbuf = encoder.finish()?;
 
         tracing::trace!("Submitting");
 
-        ctx.queue.submit(wait, cbuf, signal, fence);
+        ctx.queue.submit(wait, cbuf, signal, fence)?;
 
         Ok(Output {
             output_image: output_image.info().image.clone(),
@@ -1194,17 +1235,64 @@ unsafe impl Pod for Globals {}
 #[derive(Clone, Copy, Debug)]
 struct ShaderInstance {
     transform: na::Matrix4<f32>,
-    mesh: u32,
     albedo_sampler: u32,
     albedo_factor: [f32; 4],
     normal_sampler: u32,
     normal_factor: f32,
     anim: u32,
+    virtual_texture: ShaderVirtualTexture,
 }
 
 unsafe impl Zeroable for ShaderInstance {}
 unsafe impl Pod for ShaderInstance {}
 
+/// Virtual texture parameters as seen by hit shader.
+/// Zero page table index means no virtual texture.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct ShaderVirtualTexture {
+    page_table: u32,
+    albedo: u32,
+    normal: u32,
+    id: u32,
+    pages: u32,
+    levels: u32,
+    atlas_pages: u32,
+    page_size: u32,
+    border: u32,
+    uv_scale: f32,
+}
+
+unsafe impl Zeroable for ShaderVirtualTexture {}
+unsafe impl Pod for ShaderVirtualTexture {}
+
+/// Returns index of the texture in sampler array plus one.
+/// Descriptor is written when texture is seen first time.
+fn texture_index<'a>(
+    textures: &mut SparseDescriptors<Texture>,
+    texture: &Texture,
+    set: &'a DescriptorSet,
+    binding: u32,
+    writes: &mut BVec<'_, WriteDescriptorSet<'a>>,
+    bump: &'a Bump,
+) -> u32 {
+    let (index, new) = textures.index(texture.clone());
+    if new {
+        writes.push(WriteDescriptorSet {
+            set,
+            binding,
+            element: index,
+            descriptors: Descriptors::CombinedImageSampler(bump.alloc([(
+                texture.image.clone(),
+                Layout::General,
+                texture.sampler.clone(),
+            )])),
+        });
+    }
+
+    index + 1
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug)]
 struct ShaderPointLight {
@@ -13,15 +13,33 @@ struct Vertex {
     vec2 uv;
 };
 
+// Zero page table index means no virtual texture.
+// Page table and albedo atlas are in `albedo` array,
+// normal atlas is in `normal` array.
+struct VirtualTexture {
+    uint page_table;
+    uint albedo;
+    uint normal;
+    uint id;
+    uint pages;
+    uint levels;
+    uint atlas_pages;
+    uint page_size;
+    uint border;
+    float uv_scale;
+};
+
+// Mesh index is instance custom index.
 struct Instance {
     mat4 transform;
-    uint mesh;
     uint albedo_sampler;
     vec4 albedo_factor;
     uint normals_sampler;
     float normals_factor;
+
+    // Index of animated vertices plus one. Zero if not animated.
     uint anim;
-    
+    VirtualTexture virtual_texture;
 };
 
 struct DirLight {
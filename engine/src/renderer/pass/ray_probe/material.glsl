@@ -0,0 +1,81 @@
+#include "../common/virtual_texture.glsl"
+
+// Virtual texture level sampled by current hit.
+// Set with `select_virtual_texture_lod`.
+float virtual_texture_lod = 0.0;
+
+// Estimates level of detail from texel density of hit triangle
+// and angle between probe rays.
+// Probes don't request pages, resident ones requested by viewport are used.
+void select_virtual_texture_lod(vec3 p0, vec3 p1, vec3 p2, vec2 uv0, vec2 uv1, vec2 uv2) {
+    VirtualTexture vt = instances[gl_InstanceID].virtual_texture;
+    if (vt.page_table == 0)
+    {
+        return;
+    }
+
+    float density = virtual_texture_density(vt, p0, p1, p2, uv0, uv1, uv2);
+    float ray_angle = sqrt(4.0 * M_PI / float(globals.diffuse_rays));
+    virtual_texture_lod = virtual_texture_level(vt, density, ray_angle);
+}
+
+// Reads page table entry for `uv`.
+// Alpha is zero if no ancestor of the page is resident.
+vec4 virtual_texture_entry(VirtualTexture vt, vec2 uv) {
+    uint level = uint(virtual_texture_lod);
+    ivec2 page = virtual_page(vt, uv, level);
+    return texelFetch(albedo[nonuniformEXT(vt.page_table - 1)], page, int(level));
+}
+
+vec4 sample_albedo(vec2 uv) {
+    VirtualTexture vt = instances[gl_InstanceID].virtual_texture;
+    uint sampler_index = instances[gl_InstanceID].albedo_sampler;
+    vec4 raw = vec4(1, 1, 1, 1);
+    if (vt.page_table > 0)
+    {
+        vec4 entry = virtual_texture_entry(vt, uv);
+        if (entry.a >= 0.5)
+        {
+            raw = textureLod(albedo[nonuniformEXT(vt.albedo - 1)], virtual_atlas_uv(vt, entry, uv), 0.0);
+        }
+    }
+    else if (sampler_index > 0)
+    {
+        raw = texture(albedo[sampler_index-1], uv);
+    }
+    return raw * instances[gl_InstanceID].albedo_factor;
+}
+
+// Returns tangent space normal.
+vec3 sample_normal(vec2 uv) {
+    VirtualTexture vt = instances[gl_InstanceID].virtual_texture;
+    uint sampler_index = instances[gl_InstanceID].normals_sampler;
+    vec3 raw = vec3(0, 0, 1);
+    if (vt.normal > 0)
+    {
+        vec4 entry = virtual_texture_entry(vt, uv);
+        if (entry.a >= 0.5)
+        {
+            raw = textureLod(normal[nonuniformEXT(vt.normal - 1)], virtual_atlas_uv(vt, entry, uv), 0.0).xyz * 2.0 - 1.0;
+        }
+    }
+    else if (sampler_index > 0)
+    {
+        raw = texture(normal[sampler_index-1], uv).xyz * 2.0 - 1.0;
+    }
+    return normalize(vec3(raw.xy * instances[gl_InstanceID].normals_factor, raw.z));
+}
+
+vec3 local_normal(vec3 vertex_normal, vec4 tangh, vec2 uv) {
+    // Tangent is zero if mesh has neither tangents nor texture coordinates.
+    bool has_normal = instances[gl_InstanceID].normals_sampler != 0 || instances[gl_InstanceID].virtual_texture.normal != 0;
+    if (!has_normal || dot(tangh.xyz, tangh.xyz) < 0.000001)
+    {
+        return vertex_normal;
+    }
+
+    vec3 tangent = normalize(tangh.xyz - vertex_normal * dot(vertex_normal, tangh.xyz));
+    vec3 bitangent = cross(vertex_normal, tangent) * (tangh.w < 0.0 ? -1.0 : 1.0);
+    mat3 tangent_space = mat3(tangent, bitangent, vertex_normal);
+    return normalize(tangent_space * sample_normal(uv));
+}
@@ -0,0 +1,282 @@
+//!
+//! Compute pass that reduces array of values into single value.
+
+use {
+    super::{Pass, ScratchBuffer},
+    crate::renderer::Context,
+    bumpalo::{collections::Vec as BVec, Bump},
+    bytemuck::{Pod, Zeroable},
+    eyre::Report,
+    hecs::World,
+    illume::{
+        BufferRegion, BufferUsage, ComputePipeline, ComputePipelineInfo,
+        ComputeShader, DescriptorBindingFlags, DescriptorSet,
+        DescriptorSetInfo, DescriptorSetLayoutBinding,
+        DescriptorSetLayoutFlags, DescriptorSetLayoutInfo, DescriptorType,
+        Descriptors, Fence, PipelineLayout, PipelineLayoutInfo,
+        PipelineStageFlags, PushConstant, Semaphore, ShaderStageFlags, Spirv,
+        WriteDescriptorSet,
+    },
+};
+
+/// Number of values reduced by single workgroup.
+pub const REDUCE_GROUP_SIZE: u32 = 512;
+
+/// Binary operation used to reduce values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ReduceOp {
+    Sum,
+    Min,
+    Max,
+}
+
+/// Interpretation of 32 bit values in reduced buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ReduceValue {
+    U32,
+    F32,
+}
+
+pub struct Input {
+    /// Region of the buffer with values to reduce.
+    pub values: BufferRegion,
+
+    /// Number of values in the region.
+    pub count: u32,
+
+    pub op: ReduceOp,
+    pub value: ReduceValue,
+
+    /// Region of the buffer that receives reduced value.
+    /// Must be at least 4 bytes large.
+    pub output: BufferRegion,
+}
+
+pub struct Output;
+
+pub struct ReducePass {
+    layout: PipelineLayout,
+    pipeline: ComputePipeline,
+    per_frame_sets: [DescriptorSet; 2],
+    scratch: ScratchBuffer,
+}
+
+impl ReducePass {
+    pub fn new(ctx: &mut Context) -> Result<Self, Report> {
+        let set_layout =
+            ctx.create_descriptor_set_layout(DescriptorSetLayoutInfo {
+                flags: DescriptorSetLayoutFlags::empty(),
+                bindings: (0..3)
+                    .map(|binding| DescriptorSetLayoutBinding {
+                        binding,
+                        ty: DescriptorType::StorageBuffer,
+                        count: 1,
+                        stages: ShaderStageFlags::COMPUTE,
+                        flags: DescriptorBindingFlags::empty(),
+                    })
+                    .collect(),
+            })?;
+
+        let layout = ctx.create_pipeline_layout(PipelineLayoutInfo {
+            sets: vec![set_layout.clone()],
+            push_constants: vec![PushConstant {
+                stages: ShaderStageFlags::COMPUTE,
+                offset: 0,
+                size: std::mem::size_of::<Level>() as u32,
+            }],
+        })?;
+
+        let shader = ComputeShader::with_main(
+            ctx.create_shader_module(
+                Spirv::new(include_bytes!("reduce/reduce.comp.spv").to_vec())
+                    .into(),
+            )?,
+        );
+
+        let pipeline = ctx.create_compute_pipeline(ComputePipelineInfo {
+            shader,
+            layout: layout.clone(),
+        })?;
+
+        let per_frame_set0 = ctx.create_descriptor_set(DescriptorSetInfo {
+            layout: set_layout.clone(),
+        })?;
+
+        let per_frame_set1 = ctx
+            .create_descriptor_set(DescriptorSetInfo { layout: set_layout })?;
+
+        Ok(ReducePass {
+            layout,
+            pipeline,
+            per_frame_sets: [per_frame_set0, per_frame_set1],
+            scratch: ScratchBuffer::new(BufferUsage::STORAGE),
+        })
+    }
+}
+
+impl Pass<'_> for ReducePass {
+    type Input = Input;
+    type Output = Output;
+
+    fn draw(
+        &mut self,
+        input: Input,
+        frame: u64,
+        wait: &[(PipelineStageFlags, Semaphore)],
+        signal: &[Semaphore],
+        fence: Option<&Fence>,
+        ctx: &mut Context,
+        _world: &mut World,
+        bump: &Bump,
+    ) -> Result<Output, Report> {
+        assert!(u64::from(input.count) * 4 <= input.values.size);
+        assert!(input.output.size >= 4);
+
+        let findex = (frame & 1) as usize;
+
+        // Every level except last one writes partial results into scratch.
+        let mut levels = BVec::new_in(bump);
+        let mut scratch_size = 0;
+        let mut count = input.count;
+
+        loop {
+            let groups = group_count(count);
+
+            levels.push(Level {
+                count,
+                src_offset: 0,
+                dst_offset: 0,
+                flags: 0,
+            });
+
+            if groups == 1 {
+                break;
+            }
+
+            scratch_size += u64::from(groups) * 4;
+            count = groups;
+        }
+
+        let scratch = self.scratch.get(ctx, scratch_size)?;
+
+        let op_flags = match input.op {
+            ReduceOp::Sum => Level::OP_SUM,
+            ReduceOp::Min => Level::OP_MIN,
+            ReduceOp::Max => Level::OP_MAX,
+        } | match input.value {
+            ReduceValue::U32 => 0,
+            ReduceValue::F32 => Level::FLOAT_BIT,
+        };
+
+        let last = levels.len() - 1;
+        let mut offset = 0;
+        for (index, level) in levels.iter_mut().enumerate() {
+            level.flags = op_flags;
+
+            if index > 0 {
+                level.flags |= Level::SRC_SCRATCH_BIT;
+                level.src_offset = offset;
+                offset += level.count;
+            }
+
+            if index == last {
+                level.flags |= Level::DST_OUTPUT_BIT;
+            } else {
+                level.dst_offset = offset;
+            }
+        }
+
+        let descriptors = bump.alloc([
+            (
+                input.values.buffer.clone(),
+                input.values.offset,
+                input.values.size,
+            ),
+            (scratch.clone(), 0, scratch.info().size),
+            (
+                input.output.buffer.clone(),
+                input.output.offset,
+                input.output.size,
+            ),
+        ]);
+
+        let set = &self.per_frame_sets[findex];
+        let writes = [
+            WriteDescriptorSet {
+                set,
+                binding: 0,
+                element: 0,
+                descriptors: Descriptors::StorageBuffer(&descriptors[0..1]),
+            },
+            WriteDescriptorSet {
+                set,
+                binding: 1,
+                element: 0,
+                descriptors: Descriptors::StorageBuffer(&descriptors[1..2]),
+            },
+            WriteDescriptorSet {
+                set,
+                binding: 2,
+                element: 0,
+                descriptors: Descriptors::StorageBuffer(&descriptors[2..3]),
+            },
+        ];
+        ctx.update_descriptor_sets(&writes, &[]);
+
+        let mut encoder = ctx.queue.create_encoder()?;
+        encoder.bind_compute_pipeline(&self.pipeline);
+        encoder.bind_compute_descriptor_sets(
+            &self.layout,
+            0,
+            std::slice::from_ref(set),
+            &[],
+        );
+
+        for (index, level) in levels.iter().enumerate() {
+            if index > 0 {
+                encoder.pipeline_barrier(
+                    PipelineStageFlags::COMPUTE_SHADER,
+                    PipelineStageFlags::COMPUTE_SHADER,
+                );
+            }
+
+            encoder.push_constants(
+                &self.layout,
+                ShaderStageFlags::COMPUTE,
+                0,
+                std::slice::from_ref(level),
+            );
+            encoder.dispatch(group_count(level.count), 1, 1);
+        }
+
+        let cbuf = encoder.finish()?;
+        ctx.queue.submit(wait, cbuf, signal, fence)?;
+
+        Ok(Output)
+    }
+}
+
+fn group_count(count: u32) -> u32 {
+    ((count + REDUCE_GROUP_SIZE - 1) / REDUCE_GROUP_SIZE).max(1)
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct Level {
+    count: u32,
+    src_offset: u32,
+    dst_offset: u32,
+    flags: u32,
+}
+
+impl Level {
+    const OP_SUM: u32 = 0;
+    const OP_MIN: u32 = 1;
+    const OP_MAX: u32 = 2;
+    const FLOAT_BIT: u32 = 4;
+    const SRC_SCRATCH_BIT: u32 = 8;
+    const DST_OUTPUT_BIT: u32 = 16;
+}
+
+unsafe impl Zeroable for Level {}
+unsafe impl Pod for Level {}
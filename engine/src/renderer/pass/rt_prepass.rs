@@ -4,18 +4,21 @@ use {
         animate::Pose,
         light::{DirectionalLight, PointLight, SkyLight},
         renderer::{
-            ray_tracing_transform_matrix_from_nalgebra, Context, Mesh,
-            PoseMesh, PositionNormalTangent3dUV, Renderable, Texture,
-            VertexType,
+            collect_virtual_textures, decode_vt_feedback, image_size,
+            ray_tracing_transform_matrix_from_nalgebra, AlphaMode, Context,
+            Material, MaterialOverride, Mesh, PipelineHandle, PoseMesh,
+            PositionNormalTangent3dUV, RasterOnly, Renderable, RtLod, Texture,
+            VertexType, VirtualTexture, MATERIAL_SCALARS, MATERIAL_TEXTURES,
+            VT_FEEDBACK_CELL,
         },
-        scene::Global3,
+        scene::{is_changed, track_changes, Changed, Generation, Global3},
         util::BumpaloCellList,
     },
     bumpalo::{collections::Vec as BVec, Bump},
     bytemuck::{Pod, Zeroable},
     color_eyre::Report,
     eyre::ensure,
-    hecs::World,
+    hecs::{Entity, World},
     illume::*,
     nalgebra as na,
     std::{collections::HashMap, convert::TryFrom as _, mem::size_of},
@@ -23,59 +26,193 @@ use {
 
 const MAX_INSTANCE_COUNT: u16 = 1024 * 32;
 
+/// Number of consecutive TLAS refits before full rebuild.
+/// Refits degrade TLAS quality as instances move.
+const MAX_TLAS_REFITS: u32 = 60;
+
+/// Number of unchanged instances that may be uploaded together
+/// with changed neighbours to reduce number of copies.
+const DIRTY_INSTANCES_GAP: usize = 16;
+
+/// Size of square pixel tiles that share path count in sample map.
+pub const SAMPLE_TILE: u32 = 8;
+
 pub struct Input<'a> {
     pub camera_global: Global3,
     pub camera_projection: na::Projective3<f32>,
+
+    /// Lens radius for depth of field.
+    /// Zero radius disables depth of field.
+    pub lens_radius: f32,
+    pub focus_distance: f32,
+
+    /// Camera transform at previous frame.
+    pub prev_camera_global: Global3,
+
+    /// Fraction of the interval between frames during which
+    /// camera moves from previous transform.
+    /// Zero disables motion blur.
+    pub motion_blur: f32,
     pub blases: &'a HashMap<Mesh, AccelerationStructure>,
+
+    /// Index of sample that selects blue noise layer.
+    /// Kept constant unless samples are accumulated
+    /// as changing noise pattern flickers.
+    pub sample: u32,
+
+    /// Maximum number of paths traced per pixel.
+    /// Actual number is read from sample map when greater than one.
+    pub max_samples: u32,
+
+    /// Traces half of the pixels in alternating checkerboard pattern.
+    /// Other pixels keep values of previous draw.
+    pub checkerboard: bool,
 }
 
 pub struct Output {
     pub tlas: AccelerationStructure,
     pub albedo: Image,
     pub normal_depth: Image,
+
+    /// Emitted radiance with surface roughness in alpha channel.
     pub emissive: Image,
+
+    /// Direct radiance with index of hit material plus one
+    /// in alpha channel. Zero marks pixels without geometry.
     pub direct: Image,
     pub diffuse: Image,
+
+    /// `true` if instances, materials or lights changed
+    /// since previous draw.
+    pub scene_changed: bool,
+
+    /// Number of paths per pixel for each `SAMPLE_TILE` tile.
+    /// Kept in general layout, initialized with ones.
+    pub sample_map: Image,
+
+    /// Pixels with even `x + y + checkerboard` were traced.
+    /// Zero if all pixels were traced.
+    pub checkerboard: u32,
 }
 
 pub struct RtPrepass {
     pipeline_layout: PipelineLayout,
-    pipeline: RayTracingPipeline,
-    shader_binding_table: ShaderBindingTable,
+    pipeline: PipelineHandle<RayTracingPipeline>,
+
+    /// Compiled pipeline with its shader binding table.
+    compiled: Option<(RayTracingPipeline, ShaderBindingTable)>,
 
     tlas: AccelerationStructure,
     scratch: Buffer,
     globals_and_instances: MappableBuffer,
 
+    /// Device-local TLAS instances, one buffer per frame in flight.
+    tlas_instances: [Buffer; 2],
+
+    /// Instances last uploaded into `tlas_instances`.
+    tlas_instances_uploaded: [UploadedInstances; 2],
+
+    /// BLAS references and flags of instances in TLAS.
+    /// TLAS is refitted instead of rebuilt while these are unchanged.
+    tlas_structure: Vec<(DeviceAddress, u32)>,
+
+    /// Number of refits since last full TLAS build.
+    tlas_refits: u32,
+
+    /// Generation of instance transforms seen by previous draw.
+    scene_generation: Generation,
+
+    /// Materials and lights uploaded by previous draw.
+    scene_shading: Vec<u8>,
+
     set: DescriptorSet,
     per_frame_sets: [DescriptorSet; 2],
 
+    /// Virtual texture page requests written by hit shaders,
+    /// one buffer per frame in flight.
+    feedback: [MappableBuffer; 2],
+    feedback_size: u64,
+
     meshes: SparseDescriptors<Mesh>,
-    albedo: SparseDescriptors<Texture>,
-    normal: SparseDescriptors<Texture>,
-
-    output_albedo_image: Image,
-    output_normal_depth_image: Image,
-    output_emissive_image: Image,
-    output_direct_image: Image,
-    output_diffuse_image: Image,
+    textures: SparseDescriptors<Texture>,
+
+    /// Maximum extent of outputs.
+    /// Feedback buffers are sized for it.
+    max_extent: Extent2d,
+    outputs: Outputs,
+}
+
+/// Images written by ray-tracing pipeline.
+struct Outputs {
+    extent: Extent2d,
+    albedo: Image,
+    normal_depth: Image,
+    emissive: Image,
+    direct: Image,
+    diffuse: Image,
+    sample_map: Image,
+
+    /// All pixels were traced at least once.
+    history: bool,
 }
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug)]
 struct ShaderInstance {
-    transform: na::Matrix4<f32>,
-    mesh: u32,
-    albedo_sampler: u32,
-    albedo_factor: [f32; 4],
-    normal_sampler: u32,
-    normal_factor: f32,
+    /// Index of renderable in object table.
+    object: u32,
+    material: u32,
     anim: u32,
 }
 
 unsafe impl Zeroable for ShaderInstance {}
 unsafe impl Pod for ShaderInstance {}
 
+/// Material parameters as seen by hit shaders.
+/// Texture indices are offset by one. Zero means no texture.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct ShaderMaterial {
+    albedo_factor: [f32; 4],
+    emissive_factor: [f32; 3],
+    metallic_factor: f32,
+    roughness_factor: f32,
+    normal_factor: f32,
+    albedo_texture: u32,
+    normal_texture: u32,
+    metallic_roughness_texture: u32,
+    emissive_texture: u32,
+    scalars: [f32; MATERIAL_SCALARS],
+    textures: [u32; MATERIAL_TEXTURES],
+    alpha_mode: u32,
+    alpha_cutoff: f32,
+    ior: f32,
+    virtual_texture: ShaderVirtualTexture,
+}
+
+unsafe impl Zeroable for ShaderMaterial {}
+unsafe impl Pod for ShaderMaterial {}
+
+/// Virtual texture parameters as seen by hit shaders.
+/// Zero page table index means no virtual texture.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct ShaderVirtualTexture {
+    page_table: u32,
+    albedo: u32,
+    normal: u32,
+    id: u32,
+    pages: u32,
+    levels: u32,
+    atlas_pages: u32,
+    page_size: u32,
+    border: u32,
+    uv_scale: f32,
+}
+
+unsafe impl Zeroable for ShaderVirtualTexture {}
+unsafe impl Pod for ShaderVirtualTexture {}
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug)]
 struct ShaderPointLight {
@@ -96,7 +233,9 @@ impl RtPrepass {
     ) -> Result<Self, Report> {
         // Create pipeline.
         let set_layout = ctx.create_descriptor_set_layout(DescriptorSetLayoutInfo {
-                flags: DescriptorSetLayoutFlags::empty(),
+                // Mesh descriptors are written when meshes are loaded
+                // while the set may be bound.
+                flags: DescriptorSetLayoutFlags::UPDATE_AFTER_BIND_POOL,
                 bindings: vec![
                     // TLAS.
                     DescriptorSetLayoutBinding {
@@ -113,7 +252,8 @@ impl RtPrepass {
                         ty: DescriptorType::StorageBuffer,
                         count: 1,
                         stages: ShaderStageFlags::RAYGEN
-                            | ShaderStageFlags::CLOSEST_HIT,
+                            | ShaderStageFlags::CLOSEST_HIT
+                            | ShaderStageFlags::ANY_HIT,
                         flags: DescriptorBindingFlags::empty(),
                     },
                     // Indices
@@ -121,30 +261,26 @@ impl RtPrepass {
                         binding: 2,
                         ty: DescriptorType::StorageBuffer,
                         count: MAX_INSTANCE_COUNT.into(),
-                        stages: ShaderStageFlags::CLOSEST_HIT,
-                        flags: DescriptorBindingFlags::PARTIALLY_BOUND | DescriptorBindingFlags::UPDATE_UNUSED_WHILE_PENDING,
+                        stages: ShaderStageFlags::CLOSEST_HIT
+                            | ShaderStageFlags::ANY_HIT,
+                        flags: DescriptorBindingFlags::PARTIALLY_BOUND | DescriptorBindingFlags::UPDATE_UNUSED_WHILE_PENDING | DescriptorBindingFlags::UPDATE_AFTER_BIND,
                     },
                     // Vertex input.
                     DescriptorSetLayoutBinding {
                         binding: 3,
                         ty: DescriptorType::StorageBuffer,
                         count: MAX_INSTANCE_COUNT.into(),
-                        stages: ShaderStageFlags::CLOSEST_HIT,
-                        flags: DescriptorBindingFlags::PARTIALLY_BOUND | DescriptorBindingFlags::UPDATE_UNUSED_WHILE_PENDING,
+                        stages: ShaderStageFlags::CLOSEST_HIT
+                            | ShaderStageFlags::ANY_HIT,
+                        flags: DescriptorBindingFlags::PARTIALLY_BOUND | DescriptorBindingFlags::UPDATE_UNUSED_WHILE_PENDING | DescriptorBindingFlags::UPDATE_AFTER_BIND,
                     },
-                    // Textures
+                    // Material textures
                     DescriptorSetLayoutBinding {
                         binding: 4,
                         ty: DescriptorType::CombinedImageSampler,
                         count: MAX_INSTANCE_COUNT.into(),
-                        stages: ShaderStageFlags::CLOSEST_HIT,
-                        flags: DescriptorBindingFlags::PARTIALLY_BOUND | DescriptorBindingFlags::UPDATE_UNUSED_WHILE_PENDING,
-                    },
-                    DescriptorSetLayoutBinding {
-                        binding: 5,
-                        ty: DescriptorType::CombinedImageSampler,
-                        count: MAX_INSTANCE_COUNT.into(),
-                        stages: ShaderStageFlags::CLOSEST_HIT,
+                        stages: ShaderStageFlags::CLOSEST_HIT
+                            | ShaderStageFlags::ANY_HIT,
                         flags: DescriptorBindingFlags::PARTIALLY_BOUND | DescriptorBindingFlags::UPDATE_UNUSED_WHILE_PENDING,
                     },
                     // G-Buffer
@@ -188,6 +324,14 @@ impl RtPrepass {
                         stages: ShaderStageFlags::RAYGEN,
                         flags: DescriptorBindingFlags::empty(),
                     },
+                    // sample map
+                    DescriptorSetLayoutBinding {
+                        binding: 11,
+                        ty: DescriptorType::StorageImage,
+                        count: 1,
+                        stages: ShaderStageFlags::RAYGEN,
+                        flags: DescriptorBindingFlags::empty(),
+                    },
                 ],
             })?;
 
@@ -202,7 +346,8 @@ impl RtPrepass {
                         count: 1,
                         stages: ShaderStageFlags::RAYGEN
                             | ShaderStageFlags::CLOSEST_HIT
-                            | ShaderStageFlags::MISS,
+                            | ShaderStageFlags::MISS
+                            | ShaderStageFlags::ANY_HIT,
                         flags: DescriptorBindingFlags::empty(),
                     },
                     // Scene
@@ -210,7 +355,8 @@ impl RtPrepass {
                         binding: 1,
                         ty: DescriptorType::StorageBuffer,
                         count: 1,
-                        stages: ShaderStageFlags::CLOSEST_HIT,
+                        stages: ShaderStageFlags::CLOSEST_HIT
+                            | ShaderStageFlags::ANY_HIT,
                         flags: DescriptorBindingFlags::empty(),
                     },
                     // Lights
@@ -226,16 +372,38 @@ impl RtPrepass {
                         binding: 3,
                         ty: DescriptorType::StorageBuffer,
                         count: 1024,
-                        stages: ShaderStageFlags::CLOSEST_HIT,
+                        stages: ShaderStageFlags::CLOSEST_HIT
+                            | ShaderStageFlags::ANY_HIT,
                         flags: DescriptorBindingFlags::PARTIALLY_BOUND,
                     },
+                    // Materials
+                    DescriptorSetLayoutBinding {
+                        binding: 4,
+                        ty: DescriptorType::StorageBuffer,
+                        count: 1,
+                        stages: ShaderStageFlags::CLOSEST_HIT
+                            | ShaderStageFlags::ANY_HIT,
+                        flags: DescriptorBindingFlags::empty(),
+                    },
+                    // Virtual texture feedback
+                    DescriptorSetLayoutBinding {
+                        binding: 5,
+                        ty: DescriptorType::StorageBuffer,
+                        count: 1,
+                        stages: ShaderStageFlags::CLOSEST_HIT,
+                        flags: DescriptorBindingFlags::empty(),
+                    },
                 ],
             },
         )?;
 
         let pipeline_layout =
             ctx.create_pipeline_layout(PipelineLayoutInfo {
-                sets: vec![set_layout.clone(), per_frame_set_layout.clone()],
+                sets: vec![
+                    set_layout.clone(),
+                    per_frame_set_layout.clone(),
+                    ctx.object_table().layout().clone(),
+                ],
                 push_constants: Vec::new(),
             })?;
 
@@ -291,8 +459,19 @@ impl RtPrepass {
             )?,
         );
 
+        let alpha_rahit = AnyHitShader::with_main(
+            ctx.create_shader_module(
+                Spirv::new(
+                    include_bytes!("rt_prepass/alpha.rahit.spv").to_vec(),
+                )
+                .into(),
+            )?,
+        );
+
+        // Pipeline is compiled in background.
+        // Shader binding table is created once it is ready.
         let pipeline =
-            ctx.create_ray_tracing_pipeline(RayTracingPipelineInfo {
+            ctx.compile_ray_tracing_pipeline(RayTracingPipelineInfo {
                 shaders: vec![
                     viewport_rgen.into(),
                     primary_rmiss.into(),
@@ -300,6 +479,7 @@ impl RtPrepass {
                     diffuse_rmiss.into(),
                     diffuse_rchit.into(),
                     shadow_rmiss.into(),
+                    alpha_rahit.into(),
                 ],
                 groups: vec![
                     RayTracingShaderGroupInfo::Raygen { raygen: 0 },
@@ -307,34 +487,28 @@ impl RtPrepass {
                     RayTracingShaderGroupInfo::Miss { miss: 3 },
                     RayTracingShaderGroupInfo::Miss { miss: 5 },
                     RayTracingShaderGroupInfo::Triangles {
-                        any_hit: None,
+                        any_hit: Some(6),
                         closest_hit: Some(2),
                     },
                     RayTracingShaderGroupInfo::Triangles {
-                        any_hit: None,
+                        any_hit: Some(6),
                         closest_hit: Some(4),
                     },
                 ],
                 max_recursion_depth: 10,
                 layout: pipeline_layout.clone(),
-            })?;
-
-        let shader_binding_table = ctx.create_shader_binding_table(
-            &pipeline,
-            ShaderBindingTableInfo {
-                raygen: Some(0),
-                miss: &[1, 2, 3],
-                hit: &[4, 5],
-                callable: &[],
-            },
-        )?;
+                libraries: Vec::new(),
+                interface: None,
+                library: false,
+            });
 
-        tracing::trace!("RT pipeline created");
+        tracing::trace!("RT pipeline requested");
 
         // Creating TLAS.
         let tlas_sizes = ctx.get_acceleration_structure_build_sizes(
             AccelerationStructureLevel::Top,
-            AccelerationStructureBuildFlags::PREFER_FAST_BUILD,
+            AccelerationStructureBuildFlags::PREFER_FAST_BUILD
+                | AccelerationStructureBuildFlags::ALLOW_UPDATE,
             &[AccelerationStructureGeometryInfo::Instances {
                 max_primitive_count: MAX_INSTANCE_COUNT.into(),
             }],
@@ -344,6 +518,7 @@ impl RtPrepass {
             align: 255,
             size: tlas_sizes.acceleration_structure_size,
             usage: BufferUsage::ACCELERATION_STRUCTURE_STORAGE,
+            capture_address: None,
         })?;
 
         let tlas =
@@ -356,12 +531,29 @@ impl RtPrepass {
         // Allocate scratch memory for TLAS building.
         let scratch = ctx.create_buffer(BufferInfo {
             align: 255,
-            size: tlas_sizes.build_scratch_size,
+            size: tlas_sizes
+                .build_scratch_size
+                .max(tlas_sizes.update_scratch_size),
             usage: BufferUsage::DEVICE_ADDRESS,
+            capture_address: None,
         })?;
 
         tracing::trace!("TLAS scratch allocated");
 
+        let create_tlas_instances = |ctx: &mut Context| {
+            ctx.create_buffer(BufferInfo {
+                align: 255,
+                size: acc_instances_size(),
+                usage: BufferUsage::ACCELERATION_STRUCTURE_BUILD_INPUT
+                    | BufferUsage::DEVICE_ADDRESS
+                    | BufferUsage::TRANSFER_DST,
+                capture_address: None,
+            })
+        };
+
+        let tlas_instances =
+            [create_tlas_instances(ctx)?, create_tlas_instances(ctx)?];
+
         let globals_and_instances = ctx.create_mappable_buffer(
             BufferInfo {
                 align: globals_and_instances_align(),
@@ -370,82 +562,33 @@ impl RtPrepass {
                     | BufferUsage::STORAGE
                     | BufferUsage::ACCELERATION_STRUCTURE_BUILD_INPUT
                     | BufferUsage::DEVICE_ADDRESS,
+                capture_address: None,
             },
             MemoryUsage::FAST_DEVICE_ACCESS,
         )?;
 
         tracing::trace!("Globals and instances buffer created");
 
-        // Image matching surface extent.
-        let output_albedo_image = ctx.create_image(ImageInfo {
-            extent: extent.into(),
-            format: Format::RGBA8Unorm,
-            levels: 1,
-            layers: 1,
-            samples: Samples::Samples1,
-            usage: ImageUsage::STORAGE | ImageUsage::SAMPLED,
-        })?;
-
-        // View for whole image
-        let output_albedo_view = ctx.create_image_view(ImageViewInfo::new(
-            output_albedo_image.clone(),
-        ))?;
-
-        let output_normal_depth_image = ctx.create_image(ImageInfo {
-            extent: extent.into(),
-            format: Format::RGBA32Sfloat,
-            levels: 1,
-            layers: 1,
-            samples: Samples::Samples1,
-            usage: ImageUsage::STORAGE | ImageUsage::SAMPLED,
-        })?;
-
-        // View for whole image
-        let output_normal_depth_view = ctx.create_image_view(
-            ImageViewInfo::new(output_normal_depth_image.clone()),
-        )?;
-
-        let output_emissive_image = ctx.create_image(ImageInfo {
-            extent: extent.into(),
-            format: Format::RGBA32Sfloat,
-            levels: 1,
-            layers: 1,
-            samples: Samples::Samples1,
-            usage: ImageUsage::STORAGE | ImageUsage::SAMPLED,
-        })?;
-
-        // View for whole image
-        let output_emissive_view = ctx.create_image_view(
-            ImageViewInfo::new(output_emissive_image.clone()),
-        )?;
+        let feedback_size = feedback_size(extent);
+        let mut create_feedback = || -> Result<_, Report> {
+            let mut buffer = ctx.create_mappable_buffer(
+                BufferInfo {
+                    align: 255,
+                    size: feedback_size,
+                    usage: BufferUsage::STORAGE,
+                    capture_address: None,
+                },
+                MemoryUsage::DOWNLOAD,
+            )?;
 
-        let output_direct_image = ctx.create_image(ImageInfo {
-            extent: extent.into(),
-            format: Format::RGBA32Sfloat,
-            levels: 1,
-            layers: 1,
-            samples: Samples::Samples1,
-            usage: ImageUsage::STORAGE | ImageUsage::SAMPLED,
-        })?;
+            let zeros = vec![0u32; (feedback_size / 4) as usize];
+            ctx.write_buffer(&mut buffer, 0, &zeros)?;
+            Ok(buffer)
+        };
 
-        // View for whole image
-        let output_direct_view = ctx.create_image_view(ImageViewInfo::new(
-            output_direct_image.clone(),
-        ))?;
-
-        let output_diffuse_image = ctx.create_image(ImageInfo {
-            extent: extent.into(),
-            format: Format::RGBA32Sfloat,
-            levels: 1,
-            layers: 1,
-            samples: Samples::Samples1,
-            usage: ImageUsage::STORAGE | ImageUsage::SAMPLED,
-        })?;
+        let feedback = [create_feedback()?, create_feedback()?];
 
-        // View for whole image
-        let output_diffuse_view = ctx.create_image_view(ImageViewInfo::new(
-            output_diffuse_image.clone(),
-        ))?;
+        let outputs = Outputs::new(extent, ctx)?;
 
         tracing::trace!("Feature images created");
 
@@ -483,18 +626,6 @@ impl RtPrepass {
                         blue_noise_buffer_256x256x128.info().size,
                     )]),
                 },
-                WriteDescriptorSet {
-                    set: &set,
-                    binding: 6,
-                    element: 0,
-                    descriptors: Descriptors::StorageImage(&[
-                        (output_albedo_view.clone(), Layout::General),
-                        (output_normal_depth_view.clone(), Layout::General),
-                        (output_emissive_view.clone(), Layout::General),
-                        (output_direct_view.clone(), Layout::General),
-                        (output_diffuse_view.clone(), Layout::General),
-                    ]),
-                },
                 WriteDescriptorSet {
                     set: &per_frame_set0,
                     binding: 0,
@@ -555,29 +686,167 @@ impl RtPrepass {
                         pointlight_size(),
                     )]),
                 },
+                WriteDescriptorSet {
+                    set: &per_frame_set0,
+                    binding: 4,
+                    element: 0,
+                    descriptors: Descriptors::StorageBuffer(&[(
+                        globals_and_instances.clone(),
+                        materials_offset(0),
+                        materials_size(),
+                    )]),
+                },
+                WriteDescriptorSet {
+                    set: &per_frame_set1,
+                    binding: 4,
+                    element: 0,
+                    descriptors: Descriptors::StorageBuffer(&[(
+                        globals_and_instances.clone(),
+                        materials_offset(1),
+                        materials_size(),
+                    )]),
+                },
+                WriteDescriptorSet {
+                    set: &per_frame_set0,
+                    binding: 5,
+                    element: 0,
+                    descriptors: Descriptors::StorageBuffer(&[(
+                        feedback[0].clone(),
+                        0,
+                        feedback_size,
+                    )]),
+                },
+                WriteDescriptorSet {
+                    set: &per_frame_set1,
+                    binding: 5,
+                    element: 0,
+                    descriptors: Descriptors::StorageBuffer(&[(
+                        feedback[1].clone(),
+                        0,
+                        feedback_size,
+                    )]),
+                },
             ],
             &[],
         );
 
+        outputs.write_descriptors(&set, ctx)?;
+
         Ok(RtPrepass {
             pipeline_layout,
             pipeline,
-            shader_binding_table,
+            compiled: None,
             tlas,
             scratch,
             globals_and_instances,
+            tlas_instances,
+            tlas_instances_uploaded: [
+                UploadedInstances::new(),
+                UploadedInstances::new(),
+            ],
+            tlas_structure: Vec::new(),
+            // Forces full build on first frame.
+            tlas_refits: MAX_TLAS_REFITS,
+            scene_generation: Generation::ZERO,
+            scene_shading: Vec::new(),
             set,
             per_frame_sets: [per_frame_set0, per_frame_set1],
-            output_albedo_image,
-            output_normal_depth_image,
-            output_emissive_image,
-            output_direct_image,
-            output_diffuse_image,
+            feedback,
+            feedback_size,
+            max_extent: extent,
+            outputs,
             meshes: SparseDescriptors::new(),
-            albedo: SparseDescriptors::new(),
-            normal: SparseDescriptors::new(),
+            textures: SparseDescriptors::new(),
         })
     }
+
+    /// Returns `true` once ray-tracing pipeline is compiled.
+    /// Pass must not be drawn before that.
+    pub fn is_ready(&mut self, ctx: &mut Context) -> Result<bool, Report> {
+        if self.compiled.is_some() {
+            return Ok(true);
+        }
+
+        let pipeline = match self.pipeline.poll()? {
+            Some(pipeline) => pipeline,
+            None => return Ok(false),
+        };
+
+        let shader_binding_table = ctx.create_shader_binding_table(
+            &pipeline,
+            ShaderBindingTableInfo {
+                raygen: Some(0),
+                miss: &[1, 2, 3],
+                hit: &[4, 5],
+                callable: &[],
+            },
+        )?;
+
+        tracing::trace!("RT pipeline created");
+
+        self.compiled = Some((pipeline, shader_binding_table));
+        Ok(true)
+    }
+
+    /// Recreates output images with new extent.
+    /// Extent is clamped to the one pass was created with.
+    ///
+    /// Waits for device to become idle when extent changes,
+    /// as descriptor set with outputs may be in use.
+    pub fn resize(
+        &mut self,
+        extent: Extent2d,
+        ctx: &mut Context,
+    ) -> Result<(), Report> {
+        let extent = Extent2d {
+            width: extent.width.max(1).min(self.max_extent.width),
+            height: extent.height.max(1).min(self.max_extent.height),
+        };
+
+        if self.outputs.extent == extent {
+            return Ok(());
+        }
+
+        tracing::debug!("Resizing ray-tracing outputs to {:?}", extent);
+
+        ctx.queue.wait_for_idle()?;
+        let outputs = Outputs::new(extent, ctx)?;
+        outputs.write_descriptors(&self.set, ctx)?;
+        self.outputs = outputs;
+        Ok(())
+    }
+
+    /// Dispatches virtual texture page requests from feedback buffer
+    /// and clears it for reuse.
+    fn read_feedback(
+        &mut self,
+        findex: u32,
+        virtual_textures: &HashMap<u32, VirtualTexture>,
+        ctx: &mut Context,
+    ) -> Result<(), Report> {
+        if virtual_textures.is_empty() {
+            return Ok(());
+        }
+
+        let buffer = &mut self.feedback[findex as usize];
+        let mut requests = vec![0u32; (self.feedback_size / 4) as usize];
+
+        ctx.read_buffer(buffer, 0, &mut requests)?;
+
+        requests.sort_unstable();
+        requests.dedup();
+
+        for (id, page) in requests.iter().filter_map(|&r| decode_vt_feedback(r))
+        {
+            if let Some(vt) = virtual_textures.get(&id) {
+                vt.request(page);
+            }
+        }
+
+        let zeros = vec![0u32; (self.feedback_size / 4) as usize];
+        ctx.write_buffer(buffer, 0, &zeros)?;
+        Ok(())
+    }
 }
 
 impl<'a> Pass<'a> for RtPrepass {
@@ -602,37 +871,88 @@ impl<'a> Pass<'a> for RtPrepass {
 
         let findex = (frame & 1) as u32;
 
+        // Release descriptors of resources no longer rendered.
+        self.meshes.next_frame();
+        self.textures.next_frame();
+
+        // Feedback buffer was written by the frame that used it last,
+        // which is complete by now.
+        let virtual_textures = collect_virtual_textures(world);
+        self.read_feedback(findex, &virtual_textures, ctx)?;
+
         let storage_buffers = BumpaloCellList::new();
         let combined_image_samples = BumpaloCellList::new();
         let bind_ray_tracing_descriptor_sets_array;
 
-        // https://microsoft.github.io/DirectX-Specs/d3d/Raytracing.html#general-tips-for-building-acceleration-structures
-        //
-        // > Rebuild top-level acceleration structure every frame
-        //   Only updating instead of rebuilding is rarely the right thing to
-        // do.   Rebuilds for a few thousand instances are very fast,
-        //   and having a good quality top-level acceleration structure can have
-        // a significant payoff   (bad quality has a higher cost further
-        // up in the tree).
+        // Instance list is collected every frame, but only changed
+        // instances are uploaded and TLAS is refitted while set of
+        // instances stays the same. Periodic rebuilds keep TLAS quality.
         let mut instances = BVec::new_in(bump);
+        let mut materials = BVec::new_in(bump);
+        let mut material_indices = HashMap::new();
         let mut acc_instances = BVec::new_in(bump);
+        let mut acc_moved = BVec::new_in(bump);
         let mut anim_vertices_descriptors = BVec::new_in(bump);
 
         let mut writes = BVec::new_in(bump);
 
         let mut encoder = ctx.queue.create_encoder()?;
 
-        let mut query = world.query::<(
-            &Renderable,
-            &Global3,
-            Option<&Pose>,
-            Option<&PoseMesh>,
-        )>();
+        // Instances that didn't move since this frame's buffer
+        // was uploaded need no upload.
+        let generation = track_changes::<Global3>(world);
+        let uploaded_generation =
+            self.tlas_instances_uploaded[findex as usize].generation;
+        let scene_generation =
+            std::mem::replace(&mut self.scene_generation, generation);
+        let mut scene_changed = false;
+
+        let mut query = world
+            .query::<(
+                &Renderable,
+                &Global3,
+                Option<&Pose>,
+                Option<&PoseMesh>,
+                Option<&MaterialOverride>,
+                Option<&RtLod>,
+                Option<&Changed<Global3>>,
+            )>()
+            .without::<RasterOnly>();
 
         tracing::trace!("Query all renderable");
 
-        for (entity, (renderable, global, pose, pose_mesh)) in query.iter() {
-            if let Some(blas) = input.blases.get(&renderable.mesh) {
+        for (
+            entity,
+            (
+                renderable,
+                global,
+                pose,
+                pose_mesh,
+                material_override,
+                rt_lod,
+                changed,
+            ),
+        ) in query.iter()
+        {
+            // Distant renderables may use proxy mesh or be dropped.
+            let mesh = match rt_lod {
+                Some(rt_lod) => match rt_lod.select(&renderable.mesh) {
+                    Some(mesh) => mesh,
+                    None => continue,
+                },
+                None => &renderable.mesh,
+            };
+
+            // Pose can't be applied to proxy mesh.
+            let pose = pose.filter(|_| *mesh == renderable.mesh);
+
+            // Renderables added after frame began are not in the table yet.
+            let object = match ctx.object_table().index(entity) {
+                Some(object) => object,
+                None => continue,
+            };
+
+            if let Some(blas) = input.blases.get(mesh) {
                 let blas_address =
                     ctx.get_acceleration_structure_device_address(blas);
 
@@ -643,11 +963,20 @@ impl<'a> Pass<'a> for RtPrepass {
 
                 let m = global.to_homogeneous();
 
-                let (mut mesh_index, new) =
-                    self.meshes.index(renderable.mesh.clone());
+                // Any-hit shader is invoked only for non-opaque materials.
+                let mut instance_flags = match renderable.material.alpha_mode {
+                    AlphaMode::Opaque => GeometryInstanceFlags::FORCE_OPAQUE,
+                    _ => GeometryInstanceFlags::FORCE_NO_OPAQUE,
+                };
+
+                if renderable.material.double_sided {
+                    instance_flags |=
+                        GeometryInstanceFlags::TRIANGLE_FACING_CULL_DISABLE;
+                }
+
+                let (mesh_index, new) = self.meshes.index(mesh.clone());
                 if new {
-                    let vectors = renderable
-                        .mesh
+                    let vectors = mesh
                         .bindings()
                         .iter()
                         .find(|binding| {
@@ -659,13 +988,13 @@ impl<'a> Pass<'a> for RtPrepass {
                     let vectors_buffer = vectors.buffer.clone();
                     let vectors_offset = vectors.offset;
                     let vectors_size: u64 = vectors.layout.stride as u64
-                        * renderable.mesh.vertex_count() as u64;
+                        * mesh.vertex_count() as u64;
 
-                    let indices = renderable.mesh.indices().unwrap();
+                    let indices = mesh.indices().unwrap();
                     let indices_buffer = indices.buffer.clone();
                     let indices_offset = indices.offset;
-                    let indices_size: u64 = indices.index_type.size() as u64
-                        * renderable.mesh.count() as u64;
+                    let indices_size: u64 =
+                        indices.index_type.size() as u64 * mesh.count() as u64;
 
                     assert_eq!(vectors_offset & 15, 0);
                     assert_eq!(indices_offset & 15, 0);
@@ -705,6 +1034,10 @@ impl<'a> Pass<'a> for RtPrepass {
                     });
                 }
 
+                let moved = is_changed(changed, uploaded_generation);
+                acc_moved.push((entity, moved));
+                scene_changed |= is_changed(changed, scene_generation);
+
                 let anim = if let (Some(_), Some(pose_mesh)) = (pose, pose_mesh)
                 {
                     let vectors = pose_mesh
@@ -719,9 +1052,9 @@ impl<'a> Pass<'a> for RtPrepass {
                     let vectors_buffer = vectors.buffer.clone();
                     let vectors_offset = vectors.offset;
                     let vectors_size: u64 = vectors.layout.stride as u64
-                        * renderable.mesh.vertex_count() as u64;
+                        * mesh.vertex_count() as u64;
 
-                    mesh_index = anim_vertices_descriptors.len() as u32;
+                    let anim_index = anim_vertices_descriptors.len() as u32;
 
                     anim_vertices_descriptors.push((
                         vectors_buffer,
@@ -729,7 +1062,7 @@ impl<'a> Pass<'a> for RtPrepass {
                         vectors_size,
                     ));
 
-                    let blas = renderable.mesh.build_pose_triangles_blas(
+                    let blas = mesh.build_pose_triangles_blas(
                         pose_mesh,
                         &mut encoder,
                         &ctx.device,
@@ -745,101 +1078,57 @@ impl<'a> Pass<'a> for RtPrepass {
                         AccelerationStructureInstance::new(blas_address)
                             .with_transform(
                                 ray_tracing_transform_matrix_from_nalgebra(&m),
-                            ),
+                            )
+                            .with_flags(instance_flags)
+                            .with_custom_index(mesh_index),
                     );
 
-                    true
+                    // Zero means not animated.
+                    anim_index + 1
                 } else {
                     acc_instances.push(
                         AccelerationStructureInstance::new(blas_address)
                             .with_transform(
                                 ray_tracing_transform_matrix_from_nalgebra(&m),
-                            ),
+                            )
+                            .with_flags(instance_flags)
+                            .with_custom_index(mesh_index),
                     );
-                    false
-                };
-
-                let albedo_index = if let Some(albedo) =
-                    &renderable.material.albedo
-                {
-                    let (albedo_index, new) = self.albedo.index(albedo.clone());
-
-                    if new {
-                        let descriptors = Descriptors::CombinedImageSampler(
-                            std::slice::from_ref(
-                                combined_image_samples.push_in(
-                                    (
-                                        albedo.image.clone(),
-                                        Layout::General,
-                                        albedo.sampler.clone(),
-                                    ),
-                                    bump,
-                                ),
-                            ),
-                        );
-                        writes.push(WriteDescriptorSet {
-                            set: &self.set,
-                            binding: 4,
-                            element: albedo_index,
-                            descriptors,
-                        });
-                    }
-
-                    albedo_index + 1
-                } else {
                     0
                 };
 
-                let normal_index = if let Some(normal) =
-                    &renderable.material.normal
-                {
-                    let (normal_index, new) = self.normal.index(normal.clone());
-
-                    if new {
-                        let descriptors = Descriptors::CombinedImageSampler(
-                            std::slice::from_ref(
-                                combined_image_samples.push_in(
-                                    (
-                                        normal.image.clone(),
-                                        Layout::General,
-                                        normal.sampler.clone(),
-                                    ),
-                                    bump,
-                                ),
-                            ),
-                        );
-                        writes.push(WriteDescriptorSet {
-                            set: &self.set,
-                            binding: 5,
-                            element: normal_index,
-                            descriptors,
-                        });
-                    }
-
-                    normal_index + 1
-                } else {
-                    0
-                };
+                // Entities with overridden parameters get own material.
+                let material_override =
+                    material_override.filter(|o| !o.is_empty());
+                let material_index =
+                    match material_indices.get(&renderable.material) {
+                        Some(&index) if material_override.is_none() => index,
+                        _ => {
+                            let index = materials.len() as u32;
+                            materials.push(pack_material(
+                                &renderable.material,
+                                material_override,
+                                &mut self.textures,
+                                &self.set,
+                                &combined_image_samples,
+                                &mut writes,
+                                bump,
+                            ));
+
+                            if material_override.is_none() {
+                                material_indices
+                                    .insert(&renderable.material, index);
+                            }
+                            index
+                        }
+                    };
+
+                scene_changed |= anim > 0;
 
                 instances.push(ShaderInstance {
-                    transform: m,
-                    mesh: mesh_index,
-                    albedo_sampler: albedo_index,
-                    normal_sampler: normal_index,
-                    albedo_factor: {
-                        let [r, g, b, a] = renderable.material.albedo_factor;
-                        [
-                            r.into_inner(),
-                            g.into_inner(),
-                            b.into_inner(),
-                            a.into_inner(),
-                        ]
-                    },
-                    normal_factor: renderable
-                        .material
-                        .normal_factor
-                        .into_inner(),
-                    anim: anim as u32,
+                    object,
+                    material: material_index,
+                    anim,
                 });
             } else {
                 tracing::error!("Missing BLAS for mesh @ {:?}", entity);
@@ -875,25 +1164,74 @@ impl<'a> Pass<'a> for RtPrepass {
 
         ensure!(u32::try_from(instances.len()).is_ok(), "Too many instances");
 
+        ensure!(
+            materials.len() <= MAX_INSTANCE_COUNT.into(),
+            "Too many materials"
+        );
+
+        tracing::trace!("Upload TLAS instances");
+
+        let tlas_instances = &self.tlas_instances[findex as usize];
+        upload_dirty_instances(
+            ctx,
+            tlas_instances,
+            &mut self.tlas_instances_uploaded[findex as usize],
+            &acc_instances,
+            &acc_moved,
+            generation,
+        )?;
+
+        for vt in virtual_textures.values() {
+            vt.flush(ctx)?;
+        }
+
+        ctx.flush_uploads(bump)?;
+
         tracing::trace!("Build TLAS");
 
-        // Sync BLAS and TLAS builds.
+        let same_structure = acc_instances.len() == self.tlas_structure.len()
+            && acc_instances.iter().zip(&self.tlas_structure).all(
+                |(instance, &(blas, flags))| {
+                    instance.acceleration_structure_reference == blas
+                        && instance.shader_binding_offset_flags.0 == flags
+                },
+            );
+
+        scene_changed |= !same_structure;
+
+        let refit = same_structure && self.tlas_refits < MAX_TLAS_REFITS;
+        if refit {
+            self.tlas_refits += 1;
+        } else {
+            self.tlas_refits = 0;
+            self.tlas_structure.clear();
+            self.tlas_structure
+                .extend(acc_instances.iter().map(|instance| {
+                    (
+                        instance.acceleration_structure_reference,
+                        instance.shader_binding_offset_flags.0,
+                    )
+                }));
+        }
+
+        // Sync instance uploads, BLAS and TLAS builds.
         encoder.pipeline_barrier(
-            PipelineStageFlags::ACCELERATION_STRUCTURE_BUILD,
+            PipelineStageFlags::TRANSFER
+                | PipelineStageFlags::ACCELERATION_STRUCTURE_BUILD,
             PipelineStageFlags::ACCELERATION_STRUCTURE_BUILD,
         );
 
         let infos = bump.alloc([AccelerationStructureBuildGeometryInfo {
-            src: None,
+            src: if refit { Some(self.tlas.clone()) } else { None },
             dst: self.tlas.clone(),
-            flags: AccelerationStructureBuildFlags::PREFER_FAST_BUILD,
+            flags: AccelerationStructureBuildFlags::PREFER_FAST_BUILD
+                | AccelerationStructureBuildFlags::ALLOW_UPDATE,
             geometries: bump.alloc([
                 AccelerationStructureGeometry::Instances {
                     flags: GeometryFlags::OPAQUE,
                     data: ctx
-                        .get_buffer_device_address(&self.globals_and_instances)
-                        .unwrap()
-                        .offset(acc_instances_offset(findex)),
+                        .get_buffer_device_address(tlas_instances)
+                        .unwrap(),
                     primitive_count: instances.len() as u32,
                 },
             ]),
@@ -906,16 +1244,16 @@ impl<'a> Pass<'a> for RtPrepass {
 
         ctx.write_buffer(
             &mut self.globals_and_instances,
-            acc_instances_offset(findex),
-            &acc_instances,
+            instances_offset(findex),
+            &instances,
         )?;
 
-        tracing::trace!("Update Globals");
+        tracing::trace!("Update Materials");
 
         ctx.write_buffer(
             &mut self.globals_and_instances,
-            instances_offset(findex),
-            &instances,
+            materials_offset(findex),
+            &materials,
         )?;
 
         let mut pointlights: BVec<ShaderPointLight> =
@@ -965,6 +1303,27 @@ impl<'a> Pass<'a> for RtPrepass {
             .map(|(_, sl)| sl.radiance)
             .unwrap_or_default();
 
+        // Materials and lights are few, so they are compared bytewise.
+        let mut shading = BVec::new_in(bump);
+        shading.extend_from_slice(bytemuck::cast_slice(&materials));
+        shading.extend_from_slice(bytemuck::cast_slice(&pointlights));
+        shading.extend_from_slice(bytemuck::bytes_of(&dirlight));
+        shading.extend_from_slice(bytemuck::bytes_of(&skylight));
+
+        if self.scene_shading[..] != shading[..] {
+            scene_changed = true;
+            self.scene_shading.clear();
+            self.scene_shading.extend_from_slice(&shading);
+        }
+
+        // Untraced pixels keep values of previous draw,
+        // so new outputs are traced whole first.
+        let checkerboard = if input.checkerboard && self.outputs.history {
+            (frame % 2) as u32 + 1
+        } else {
+            0
+        };
+
         let globals = Globals {
             camera: GlobalsCamera {
                 view: input.camera_global.to_homogeneous(),
@@ -976,11 +1335,19 @@ impl<'a> Pass<'a> for RtPrepass {
             dirlight,
             skylight,
             plights: pointlights.len() as u32,
-            // frame: frame as u32,
-            frame: 0,
+            frame: input.sample * input.max_samples.max(1),
             shadow_rays: 8,
             diffuse_rays: 16,
-            pad: 0.0,
+            lens_radius: input.lens_radius,
+            focus_distance: input.focus_distance,
+            motion_blur: input.motion_blur,
+            feedback_phase: (frame
+                % u64::from(VT_FEEDBACK_CELL * VT_FEEDBACK_CELL))
+                as u32,
+            prev_view: input.prev_camera_global.to_homogeneous(),
+            max_samples: input.max_samples,
+            checkerboard,
+            pad: [0; 3],
         };
 
         tracing::trace!("Update Globals");
@@ -993,11 +1360,17 @@ impl<'a> Pass<'a> for RtPrepass {
 
         tracing::trace!("Trace rays");
 
-        encoder.bind_ray_tracing_pipeline(&self.pipeline);
+        let (pipeline, shader_binding_table) = self
+            .compiled
+            .as_ref()
+            .expect("RT pipeline must be compiled before drawing");
+
+        encoder.bind_ray_tracing_pipeline(pipeline);
 
         bind_ray_tracing_descriptor_sets_array = [
             self.set.clone(),
             self.per_frame_sets[findex as usize].clone(),
+            ctx.object_table().set().clone(),
         ];
 
         encoder.bind_ray_tracing_descriptor_sets(
@@ -1008,32 +1381,14 @@ impl<'a> Pass<'a> for RtPrepass {
         );
 
         // Sync storage image access from last frame.
+        // Untraced pixels of checkerboard keep their values.
+        let keep = checkerboard != 0;
         let images = [
-            ImageLayoutTransition::initialize_whole(
-                &self.output_albedo_image,
-                Layout::General,
-            )
-            .into(),
-            ImageLayoutTransition::initialize_whole(
-                &self.output_normal_depth_image,
-                Layout::General,
-            )
-            .into(),
-            ImageLayoutTransition::initialize_whole(
-                &self.output_emissive_image,
-                Layout::General,
-            )
-            .into(),
-            ImageLayoutTransition::initialize_whole(
-                &self.output_direct_image,
-                Layout::General,
-            )
-            .into(),
-            ImageLayoutTransition::initialize_whole(
-                &self.output_diffuse_image,
-                Layout::General,
-            )
-            .into(),
+            output_barrier(&self.outputs.albedo, keep),
+            output_barrier(&self.outputs.normal_depth, keep),
+            output_barrier(&self.outputs.emissive, keep),
+            output_barrier(&self.outputs.direct, keep),
+            output_barrier(&self.outputs.diffuse, keep),
         ];
 
         encoder.image_barriers(
@@ -1049,35 +1404,37 @@ impl<'a> Pass<'a> for RtPrepass {
         );
 
         // Perform ray-trace operation.
-        encoder.trace_rays(
-            &self.shader_binding_table,
-            self.output_albedo_image.info().extent.into_3d(),
-        );
+        // Checkerboard launch covers every other pixel of each row.
+        let mut launch = self.outputs.extent;
+        if checkerboard != 0 {
+            launch.width = (launch.width + 1) / 2;
+        }
+        encoder.trace_rays(shader_binding_table, launch.into_3d());
 
         // Sync storage image access from last frame.
         let images = [
             ImageLayoutTransition::transition_whole(
-                &self.output_albedo_image,
+                &self.outputs.albedo,
                 Layout::General..Layout::ShaderReadOnlyOptimal,
             )
             .into(),
             ImageLayoutTransition::transition_whole(
-                &self.output_normal_depth_image,
+                &self.outputs.normal_depth,
                 Layout::General..Layout::ShaderReadOnlyOptimal,
             )
             .into(),
             ImageLayoutTransition::transition_whole(
-                &self.output_emissive_image,
+                &self.outputs.emissive,
                 Layout::General..Layout::ShaderReadOnlyOptimal,
             )
             .into(),
             ImageLayoutTransition::transition_whole(
-                &self.output_direct_image,
+                &self.outputs.direct,
                 Layout::General..Layout::ShaderReadOnlyOptimal,
             )
             .into(),
             ImageLayoutTransition::transition_whole(
-                &self.output_diffuse_image,
+                &self.outputs.diffuse,
                 Layout::General..Layout::ShaderReadOnlyOptimal,
             )
             .into(),
@@ -1089,23 +1446,138 @@ impl<'a> Pass<'a> for RtPrepass {
             &images,
         );
 
-        let cbuf = encoder.finish();
+        // Make feedback visible to host once frame is complete.
+        encoder.pipeline_barrier(
+            PipelineStageFlags::RAY_TRACING_SHADER,
+            PipelineStageFlags::HOST,
+        );
+
+        ctx.record_barriers(encoder.barrier_count());
+        ctx.record_transient(
+            self.tlas.info().region.size
+                + self.scratch.info().size
+                + [
+                    &self.outputs.albedo,
+                    &self.outputs.normal_depth,
+                    &self.outputs.emissive,
+                    &self.outputs.direct,
+                    &self.outputs.diffuse,
+                    &self.outputs.sample_map,
+                ]
+                .iter()
+                .map(|image| image_size(image.info()))
+                .sum::<u64>(),
+        );
+
+        let cbuf = encoder.finish()?;
 
         tracing::trace!("Submitting");
 
-        ctx.queue.submit(wait, cbuf, signal, fence);
+        ctx.queue.submit(wait, cbuf, signal, fence)?;
+
+        self.outputs.history = true;
 
         Ok(Output {
-            albedo: self.output_albedo_image.clone(),
-            normal_depth: self.output_normal_depth_image.clone(),
-            emissive: self.output_emissive_image.clone(),
-            direct: self.output_direct_image.clone(),
-            diffuse: self.output_diffuse_image.clone(),
+            albedo: self.outputs.albedo.clone(),
+            normal_depth: self.outputs.normal_depth.clone(),
+            emissive: self.outputs.emissive.clone(),
+            direct: self.outputs.direct.clone(),
+            diffuse: self.outputs.diffuse.clone(),
             tlas: self.tlas.clone(),
+            scene_changed,
+            sample_map: self.outputs.sample_map.clone(),
+            checkerboard,
         })
     }
 }
 
+impl Outputs {
+    fn new(extent: Extent2d, ctx: &mut Context) -> Result<Self, Report> {
+        let mut create_output = |format| {
+            ctx.create_image(ImageInfo {
+                extent: extent.into(),
+                format,
+                levels: 1,
+                layers: 1,
+                samples: Samples::Samples1,
+                usage: ImageUsage::STORAGE | ImageUsage::SAMPLED,
+            })
+        };
+
+        let albedo = create_output(Format::RGBA8Unorm)?;
+        let normal_depth = create_output(Format::RGBA32Sfloat)?;
+        let emissive = create_output(Format::RGBA32Sfloat)?;
+        let direct = create_output(Format::RGBA32Sfloat)?;
+        let diffuse = create_output(Format::RGBA32Sfloat)?;
+
+        let sample_map_extent = Extent2d {
+            width: (extent.width + SAMPLE_TILE - 1) / SAMPLE_TILE,
+            height: (extent.height + SAMPLE_TILE - 1) / SAMPLE_TILE,
+        };
+
+        let tiles = sample_map_extent.width as usize
+            * sample_map_extent.height as usize;
+
+        // Single path per pixel until adaptive sampling writes the map.
+        let sample_map = ctx.create_image_static(
+            ImageInfo {
+                extent: sample_map_extent.into(),
+                format: Format::R32Uint,
+                levels: 1,
+                layers: 1,
+                samples: Samples::Samples1,
+                usage: ImageUsage::STORAGE,
+            },
+            0,
+            0,
+            &vec![1u32; tiles],
+        )?;
+
+        Ok(Outputs {
+            extent,
+            albedo,
+            normal_depth,
+            emissive,
+            direct,
+            diffuse,
+            sample_map,
+            history: false,
+        })
+    }
+
+    /// Writes views of outputs into storage image array.
+    fn write_descriptors(
+        &self,
+        set: &DescriptorSet,
+        ctx: &mut Context,
+    ) -> Result<(), Report> {
+        let mut create_view = |image: &Image| {
+            ctx.create_image_view(ImageViewInfo::new(image.clone()))
+        };
+
+        let views = [
+            (create_view(&self.albedo)?, Layout::General),
+            (create_view(&self.normal_depth)?, Layout::General),
+            (create_view(&self.emissive)?, Layout::General),
+            (create_view(&self.direct)?, Layout::General),
+            (create_view(&self.diffuse)?, Layout::General),
+            (create_view(&self.sample_map)?, Layout::General),
+        ];
+
+        ctx.update_descriptor_sets(
+            &[WriteDescriptorSet {
+                set,
+                binding: 6,
+                element: 0,
+                descriptors: Descriptors::StorageImage(&views),
+            }],
+            &[],
+        );
+
+        Ok(())
+    }
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
 struct GlobalsCamera {
@@ -1136,11 +1608,18 @@ struct Globals {
     camera: GlobalsCamera,
     dirlight: GlobalsDirLight,
     skylight: [f32; 3],
-    pad: f32,
+    max_samples: u32,
     plights: u32,
     frame: u32,
     shadow_rays: u32,
     diffuse_rays: u32,
+    lens_radius: f32,
+    focus_distance: f32,
+    motion_blur: f32,
+    feedback_phase: u32,
+    prev_view: na::Matrix4<f32>,
+    checkerboard: u32,
+    pad: [u32; 3],
 }
 
 unsafe impl Zeroable for Globals {}
@@ -1158,6 +1637,27 @@ fn globals_end(frame: u32) -> u64 {
     globals_offset(frame) + globals_size()
 }
 
+/// Returns barrier that makes output image writable by ray-tracing shaders.
+/// Content of the image is discarded unless `keep` is set.
+fn output_barrier(image: &Image, keep: bool) -> ImageMemoryBarrier<'_> {
+    if keep {
+        ImageLayoutTransition::transition_whole(
+            image,
+            Layout::ShaderReadOnlyOptimal..Layout::General,
+        )
+        .into()
+    } else {
+        ImageLayoutTransition::initialize_whole(image, Layout::General).into()
+    }
+}
+
+/// Size of feedback buffer with one entry per feedback cell.
+fn feedback_size(extent: Extent2d) -> u64 {
+    let cells =
+        |size: u32| u64::from((size + VT_FEEDBACK_CELL - 1) / VT_FEEDBACK_CELL);
+    cells(extent.width) * cells(extent.height) * size_of::<u32>() as u64
+}
+
 const fn instances_size() -> u64 {
     size_of::<[ShaderInstance; MAX_INSTANCE_COUNT as usize]>() as u64
 }
@@ -1189,19 +1689,250 @@ const fn acc_instances_size() -> u64 {
         as u64
 }
 
-fn acc_instances_offset(frame: u32) -> u64 {
-    align_up(255u8, pointlight_end(1)).unwrap()
-        + u64::from(frame) * align_up(255u8, acc_instances_size()).unwrap()
+const fn globals_and_instances_align() -> u64 {
+    255
 }
 
-fn acc_instances_end(frame: u32) -> u64 {
-    acc_instances_offset(frame) + acc_instances_size()
+const fn materials_size() -> u64 {
+    size_of::<[ShaderMaterial; MAX_INSTANCE_COUNT as usize]>() as u64
 }
 
-const fn globals_and_instances_align() -> u64 {
-    255
+fn materials_offset(frame: u32) -> u64 {
+    align_up(255u8, pointlight_end(1)).unwrap()
+        + u64::from(frame) * align_up(255u8, materials_size()).unwrap()
+}
+
+fn materials_end(frame: u32) -> u64 {
+    materials_offset(frame) + materials_size()
 }
 
 fn globals_and_instances_size() -> u64 {
-    acc_instances_end(1)
+    materials_end(1)
+}
+
+/// Packs material parameters for hit shaders.
+/// Writes descriptors for textures not seen before.
+fn pack_material<'a, 'b>(
+    material: &Material,
+    material_override: Option<&MaterialOverride>,
+    textures: &mut SparseDescriptors<Texture>,
+    set: &'a DescriptorSet,
+    combined_image_samples: &'a BumpaloCellList<
+        'b,
+        (ImageView, Layout, Sampler),
+    >,
+    writes: &mut BVec<'b, WriteDescriptorSet<'a>>,
+    bump: &'b Bump,
+) -> ShaderMaterial {
+    let mut texture_index = |texture: Option<&Texture>| -> u32 {
+        let texture = match texture {
+            Some(texture) => texture,
+            None => return 0,
+        };
+
+        let (index, new) = textures.index(texture.clone());
+        if new {
+            let descriptors = Descriptors::CombinedImageSampler(
+                std::slice::from_ref(combined_image_samples.push_in(
+                    (
+                        texture.image.clone(),
+                        Layout::General,
+                        texture.sampler.clone(),
+                    ),
+                    bump,
+                )),
+            );
+
+            writes.push(WriteDescriptorSet {
+                set,
+                binding: 4,
+                element: index,
+                descriptors,
+            });
+        }
+
+        index + 1
+    };
+
+    let default_override = MaterialOverride::new();
+    let over = material_override.unwrap_or(&default_override);
+
+    let mut scalars = [0.0; MATERIAL_SCALARS];
+    for (slot, (value, value_override)) in scalars
+        .iter_mut()
+        .zip(material.params.scalars.iter().zip(&over.scalars))
+    {
+        *slot = value_override.unwrap_or(value.into_inner());
+    }
+
+    let mut param_textures = [0; MATERIAL_TEXTURES];
+    for (slot, (texture, texture_override)) in param_textures
+        .iter_mut()
+        .zip(material.params.textures.iter().zip(&over.textures))
+    {
+        *slot = texture_index(
+            texture_override.as_ref().or_else(|| texture.as_ref()),
+        );
+    }
+
+    let mut albedo_factor = over.albedo_factor.unwrap_or_else(|| {
+        let [r, g, b, a] = material.albedo_factor;
+        [
+            r.into_inner(),
+            g.into_inner(),
+            b.into_inner(),
+            a.into_inner(),
+        ]
+    });
+
+    if let Some(tint) = over.tint {
+        for (factor, tint) in albedo_factor.iter_mut().zip(&tint) {
+            *factor *= tint;
+        }
+    }
+
+    let mut emissive_factor = over.emissive_factor.unwrap_or_else(|| {
+        let [r, g, b] = material.emissive_factor;
+        [r.into_inner(), g.into_inner(), b.into_inner()]
+    });
+
+    if let Some(multiplier) = over.emissive_multiplier {
+        for factor in &mut emissive_factor {
+            *factor *= multiplier;
+        }
+    }
+
+    ShaderMaterial {
+        albedo_factor,
+        emissive_factor,
+        metallic_factor: over
+            .metallic_factor
+            .unwrap_or(material.metallic_factor.into_inner()),
+        roughness_factor: over
+            .roughness_factor
+            .unwrap_or(material.roughness_factor.into_inner()),
+        normal_factor: material.normal_factor.into_inner(),
+        albedo_texture: texture_index(
+            over.albedo.as_ref().or_else(|| material.albedo.as_ref()),
+        ),
+        normal_texture: texture_index(
+            over.normal.as_ref().or_else(|| material.normal.as_ref()),
+        ),
+        metallic_roughness_texture: texture_index(
+            material.metallic_roughness.as_ref(),
+        ),
+        emissive_texture: texture_index(
+            over.emissive
+                .as_ref()
+                .or_else(|| material.emissive.as_ref()),
+        ),
+        scalars,
+        textures: param_textures,
+        alpha_mode: match material.alpha_mode {
+            AlphaMode::Opaque => 0,
+            AlphaMode::Mask { .. } => 1,
+            AlphaMode::Blend => 2,
+            AlphaMode::Refract { .. } => 3,
+        },
+        alpha_cutoff: match material.alpha_mode {
+            AlphaMode::Mask { cutoff } => cutoff.into_inner(),
+            _ => 0.0,
+        },
+        ior: match material.alpha_mode {
+            AlphaMode::Refract { ior } => ior.into_inner(),
+            _ => 1.0,
+        },
+        virtual_texture: match &material.virtual_texture {
+            Some(vt) => ShaderVirtualTexture {
+                page_table: texture_index(Some(vt.page_table())),
+                albedo: texture_index(Some(vt.albedo())),
+                normal: texture_index(vt.normal()),
+                id: vt.id(),
+                pages: vt.info().pages,
+                levels: vt.levels(),
+                atlas_pages: vt.info().atlas_pages,
+                page_size: vt.info().page_size,
+                border: vt.info().border,
+                uv_scale: vt.uv_scale(),
+            },
+            None => ShaderVirtualTexture::zeroed(),
+        },
+    }
+}
+
+/// Instances uploaded into TLAS instance buffer.
+struct UploadedInstances {
+    /// Generation of `Global3` changes reflected in the buffer.
+    generation: Generation,
+
+    /// Entity, BLAS reference and flags of each uploaded instance.
+    keys: Vec<(Entity, DeviceAddress, u32)>,
+}
+
+impl UploadedInstances {
+    fn new() -> Self {
+        UploadedInstances {
+            generation: Generation::ZERO,
+            keys: Vec::new(),
+        }
+    }
+}
+
+/// Uploads instances that moved or don't match `uploaded` instances
+/// at the same index.
+/// Nearby dirty instances are merged into single upload.
+fn upload_dirty_instances(
+    ctx: &mut Context,
+    buffer: &Buffer,
+    uploaded: &mut UploadedInstances,
+    instances: &[AccelerationStructureInstance],
+    moved: &[(Entity, bool)],
+    generation: Generation,
+) -> Result<(), MapError> {
+    let key = |index: usize| {
+        let instance = &instances[index];
+        (
+            moved[index].0,
+            instance.acceleration_structure_reference,
+            instance.shader_binding_offset_flags.0,
+        )
+    };
+
+    let dirty = |index: usize| {
+        moved[index].1 || uploaded.keys.get(index) != Some(&key(index))
+    };
+
+    let mut index = 0;
+    while index < instances.len() {
+        if !dirty(index) {
+            index += 1;
+            continue;
+        }
+
+        let start = index;
+        let mut end = index + 1;
+        let mut clean = 0;
+        while end < instances.len() && clean < DIRTY_INSTANCES_GAP {
+            if dirty(end) {
+                clean = 0;
+            } else {
+                clean += 1;
+            }
+            end += 1;
+        }
+        end -= clean;
+
+        ctx.upload_buffer(
+            buffer,
+            (start * size_of::<AccelerationStructureInstance>()) as u64,
+            &instances[start..end],
+        )?;
+
+        index = end;
+    }
+
+    uploaded.keys.clear();
+    uploaded.keys.extend((0..instances.len()).map(key));
+    uploaded.generation = generation;
+    Ok(())
 }
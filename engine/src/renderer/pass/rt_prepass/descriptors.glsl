@@ -11,8 +11,18 @@ struct PrimaryHitPayload {
     float depth;
     vec4 albedo;
     vec3 emissive;
+    float roughness;
     vec3 direct;
     vec3 diffuse;
+
+    // Index of hit material plus one. Zero if nothing was hit.
+    uint material;
+
+    // Index of path traced for the pixel in this frame.
+    uint sample;
+
+    // Number of refracting surfaces the ray passed through.
+    uint refractions;
 };
 
 struct DiffuseHitPayload {
@@ -27,16 +37,49 @@ struct Vertex {
     vec2 uv;
 };
 
+// Mesh index is instance custom index.
 struct Instance {
-    mat4 transform;
-    uint mesh;
-    uint albedo_sampler;
-    vec4 albedo_factor;
-    uint normals_sampler;
-    float normals_factor;
+    // Index of renderable in object table.
+    uint object;
+    uint material;
+
+    // Index of animated vertices plus one. Zero if not animated.
     uint anim;
 };
 
+// Zero page table index means no virtual texture.
+struct VirtualTexture {
+    uint page_table;
+    uint albedo;
+    uint normal;
+    uint id;
+    uint pages;
+    uint levels;
+    uint atlas_pages;
+    uint page_size;
+    uint border;
+    float uv_scale;
+};
+
+// Texture indices are offset by one. Zero means no texture.
+struct Material {
+    vec4 albedo_factor;
+    vec3 emissive_factor;
+    float metallic_factor;
+    float roughness_factor;
+    float normal_factor;
+    uint albedo_texture;
+    uint normal_texture;
+    uint metallic_roughness_texture;
+    uint emissive_texture;
+    float scalars[8];
+    uint textures[4];
+    uint alpha_mode;
+    float alpha_cutoff;
+    float ior;
+    VirtualTexture virtual_texture;
+};
+
 struct Camera {
     mat4 view;
     mat4 proj;
@@ -60,20 +103,34 @@ layout(binding = 0, set = 0) uniform accelerationStructureEXT tlas;
 layout(binding = 1, set = 0) buffer BlueNoise { vec4 blue_noise[]; };
 layout(binding = 2, set = 0, scalar) buffer Indices { uint i[]; } indices[];
 layout(binding = 3, set = 0, scalar) buffer Vertices { Vertex v[]; } vertices[];
-layout(binding = 4, set = 0) uniform sampler2D albedo[];
-layout(binding = 5, set = 0) uniform sampler2D normal[];
+layout(binding = 4, set = 0) uniform sampler2D textures[];
 
 layout(binding = 0, set = 1, std140) uniform Globals {
     Camera cam;
     DirLight dirlight;
     vec3 skylight;
-    float pad0;
+    uint max_samples;
     uint plights;
     uint frame;
     uint shadow_rays;
     uint diffuse_rays;
+    float lens_radius;
+    float focus_distance;
+    float motion_blur;
+    uint feedback_phase;
+    mat4 prev_view;
+
+    // Zero unless checkerboard rendering is enabled.
+    // Otherwise pixels with even `x + y + checkerboard` are traced.
+    uint checkerboard;
 } globals;
 
 layout(binding = 1, set = 1, scalar) buffer Scene { Instance instances[]; };
 layout(binding = 2, set = 1, std140) buffer PointLights { PointLight plight[]; };
-layout(binding = 3, set = 1, scalar) buffer AnimVertices { Vertex v[]; } anim_vertices[];
\ No newline at end of file
+layout(binding = 3, set = 1, scalar) buffer AnimVertices { Vertex v[]; } anim_vertices[];
+layout(binding = 4, set = 1, scalar) buffer Materials { Material materials[]; };
+layout(binding = 5, set = 1) buffer Feedback { uint feedback[]; };
+
+#define OBJECTS_SET 2
+#include "../common/objects.glsl"
+
@@ -0,0 +1,87 @@
+const uint ALPHA_MODE_OPAQUE = 0;
+const uint ALPHA_MODE_MASK = 1;
+const uint ALPHA_MODE_BLEND = 2;
+const uint ALPHA_MODE_REFRACT = 3;
+
+Material instance_material() {
+    return materials[instances[gl_InstanceID].material];
+}
+
+#include "virtual_texture.glsl"
+
+vec4 sample_texture(uint texture_index, vec2 uv, vec4 fallback) {
+    if (texture_index > 0)
+    {
+        return texture(textures[nonuniformEXT(texture_index-1)], uv);
+    }
+    return fallback;
+}
+
+vec4 sample_albedo(vec2 uv) {
+    Material material = instance_material();
+    vec4 raw;
+    if (material.virtual_texture.page_table > 0)
+    {
+        VirtualTexture vt = material.virtual_texture;
+        raw = sample_virtual_texture(vt, vt.albedo, uv, vec4(1, 1, 1, 1));
+    }
+    else
+    {
+        raw = sample_texture(material.albedo_texture, uv, vec4(1, 1, 1, 1));
+    }
+    return raw * material.albedo_factor;
+}
+
+// Returns tangent space normal.
+vec3 sample_normal(vec2 uv) {
+    Material material = instance_material();
+    vec3 raw;
+    if (material.virtual_texture.normal > 0)
+    {
+        VirtualTexture vt = material.virtual_texture;
+        raw = sample_virtual_texture(vt, vt.normal, uv, vec4(.5, .5, 1, 0)).xyz * 2.0 - 1.0;
+    }
+    else
+    {
+        raw = sample_texture(material.normal_texture, uv, vec4(.5, .5, 1, 0)).xyz * 2.0 - 1.0;
+    }
+    return normalize(vec3(raw.xy * material.normal_factor, raw.z));
+}
+
+// Perceptual roughness stored in green channel, as in glTF.
+float sample_roughness(vec2 uv) {
+    Material material = instance_material();
+    float raw = sample_texture(material.metallic_roughness_texture, uv, vec4(1, 1, 1, 1)).g;
+    return clamp(raw * material.roughness_factor, 0.0, 1.0);
+}
+
+vec3 sample_emissive(vec2 uv) {
+    Material material = instance_material();
+    vec3 raw = sample_texture(material.emissive_texture, uv, vec4(1, 1, 1, 1)).rgb;
+    return raw * material.emissive_factor;
+}
+
+// Custom scalar parameter of the material.
+float material_scalar(uint slot) {
+    return instance_material().scalars[slot];
+}
+
+// Samples custom texture of the material.
+vec4 sample_material_texture(uint slot, vec2 uv, vec4 fallback) {
+    return sample_texture(instance_material().textures[slot], uv, fallback);
+}
+
+vec3 local_normal(vec3 vertex_normal, vec4 tangh, vec2 uv) {
+    // Tangent is zero if mesh has neither tangents nor texture coordinates.
+    Material material = instance_material();
+    bool has_normal = material.normal_texture != 0 || material.virtual_texture.normal != 0;
+    if (!has_normal || dot(tangh.xyz, tangh.xyz) < 0.000001)
+    {
+        return vertex_normal;
+    }
+
+    vec3 tangent = normalize(tangh.xyz - vertex_normal * dot(vertex_normal, tangh.xyz));
+    vec3 bitangent = cross(vertex_normal, tangent) * (tangh.w < 0.0 ? -1.0 : 1.0);
+    mat3 tangent_space = mat3(tangent, bitangent, vertex_normal);
+    return normalize(tangent_space * sample_normal(uv));
+}
@@ -0,0 +1,66 @@
+// Virtual texture sampling and feedback.
+
+#include "../common/virtual_texture.glsl"
+
+// Must match `VT_FEEDBACK_CELL`.
+const uint VT_FEEDBACK_CELL = 8;
+
+// Virtual texture level sampled by current hit.
+// Set with `select_virtual_texture_lod`.
+float virtual_texture_lod = 0.0;
+
+// Estimates level of detail from ray cone footprint
+// and texel density of hit triangle.
+void select_virtual_texture_lod(VirtualTexture vt, vec3 p0, vec3 p1, vec3 p2, vec2 uv0, vec2 uv1, vec2 uv2) {
+    if (vt.page_table == 0)
+    {
+        return;
+    }
+
+    float density = virtual_texture_density(vt, p0, p1, p2, uv0, uv1, uv2);
+    float pixel_angle = 2.0 * globals.cam.iproj[1][1] / float(gl_LaunchSizeEXT.y);
+    virtual_texture_lod = virtual_texture_level(vt, density, pixel_angle);
+}
+
+// Samples atlas through page table.
+// Returns fallback if no ancestor of the page is resident.
+vec4 sample_virtual_texture(VirtualTexture vt, uint atlas, vec2 uv, vec4 fallback) {
+    uint level = uint(virtual_texture_lod);
+    ivec2 page = virtual_page(vt, uv, level);
+
+    vec4 entry = texelFetch(textures[nonuniformEXT(vt.page_table - 1)], page, int(level));
+    if (entry.a < 0.5)
+    {
+        return fallback;
+    }
+
+    vec2 atlas_uv = virtual_atlas_uv(vt, entry, uv);
+    return textureLod(textures[nonuniformEXT(atlas - 1)], atlas_uv, 0.0);
+}
+
+// Packs request of page sampled at current level.
+// Must match `decode_vt_feedback`.
+uint virtual_texture_request(VirtualTexture vt, vec2 uv) {
+    uint level = uint(virtual_texture_lod);
+    uvec2 page = uvec2(virtual_page(vt, uv, level));
+    return (vt.id << 24) | ((level & 0xf) << 20) | ((page.y & 0x3ff) << 10) | (page.x & 0x3ff);
+}
+
+// Writes page request for one pixel of each feedback cell.
+// Pixel is selected by frame so all pixels are covered over time.
+void write_virtual_texture_feedback(VirtualTexture vt, vec2 uv) {
+    if (vt.page_table == 0)
+    {
+        return;
+    }
+
+    uvec2 cell = gl_LaunchIDEXT.xy / VT_FEEDBACK_CELL;
+    uvec2 pixel = gl_LaunchIDEXT.xy % VT_FEEDBACK_CELL;
+    if (pixel.x + pixel.y * VT_FEEDBACK_CELL != globals.feedback_phase)
+    {
+        return;
+    }
+
+    uint cells = (gl_LaunchSizeEXT.x + VT_FEEDBACK_CELL - 1) / VT_FEEDBACK_CELL;
+    feedback[cell.y * cells + cell.x] = virtual_texture_request(vt, uv);
+}
@@ -0,0 +1,386 @@
+use {
+    super::Pass,
+    crate::renderer::{Context, ViewUniform},
+    bumpalo::Bump,
+    bytemuck::{Pod, Zeroable},
+    color_eyre::Report,
+    hecs::World,
+    illume::*,
+    smallvec::smallvec,
+    std::mem::size_of,
+};
+
+/// Weight of current frame when blending with reprojected history.
+const HISTORY_BLEND: f32 = 0.1;
+
+pub struct Input {
+    /// Normals and depth in ray-tracing prepass layout.
+    /// Negative depth marks pixels without geometry.
+    pub normal_depth: Image,
+
+    /// Globals of the rendered view.
+    pub view: ViewUniform,
+
+    /// World-space radius of occlusion sampling.
+    pub radius: f32,
+
+    /// Strength of occlusion.
+    pub intensity: f32,
+}
+
+pub struct Output {
+    /// Ambient occlusion factor in red channel.
+    pub ao: Image,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct Params {
+    radius: f32,
+    intensity: f32,
+    history_blend: f32,
+}
+
+unsafe impl Zeroable for Params {}
+unsafe impl Pod for Params {}
+
+/// Screen-space ambient occlusion with temporal accumulation.
+///
+/// Occlusion of two consecutive frames is rendered into alternating
+/// images, previous one is reprojected and blended with current.
+pub struct SsaoPass {
+    nearest: Sampler,
+    linear: Sampler,
+    normal_depth: [Option<ImageView>; 2],
+
+    /// Occlusion images, one written per frame.
+    ao: Option<[(ImageView, Framebuffer); 2]>,
+    history_valid: bool,
+
+    render_pass: RenderPass,
+    pipeline: GraphicsPipeline,
+
+    pipeline_layout: PipelineLayout,
+    per_frame_sets: [DescriptorSet; 2],
+}
+
+impl SsaoPass {
+    pub fn new(ctx: &mut Context) -> Result<Self, Report> {
+        let set_layout =
+            ctx.create_descriptor_set_layout(DescriptorSetLayoutInfo {
+                flags: DescriptorSetLayoutFlags::UPDATE_AFTER_BIND_POOL,
+                bindings: vec![
+                    // Normal-Depth
+                    DescriptorSetLayoutBinding {
+                        binding: 0,
+                        ty: DescriptorType::CombinedImageSampler,
+                        count: 1,
+                        stages: ShaderStageFlags::FRAGMENT,
+                        flags: DescriptorBindingFlags::empty(),
+                    },
+                    // History
+                    DescriptorSetLayoutBinding {
+                        binding: 1,
+                        ty: DescriptorType::CombinedImageSampler,
+                        count: 1,
+                        stages: ShaderStageFlags::FRAGMENT,
+                        flags: DescriptorBindingFlags::empty(),
+                    },
+                ],
+            })?;
+
+        let pipeline_layout =
+            ctx.create_pipeline_layout(PipelineLayoutInfo {
+                sets: vec![
+                    set_layout.clone(),
+                    ctx.view_globals_layout().clone(),
+                ],
+                push_constants: vec![PushConstant {
+                    stages: ShaderStageFlags::FRAGMENT,
+                    offset: 0,
+                    size: size_of::<Params>() as u32,
+                }],
+            })?;
+
+        let vert = VertexShader::with_main(ctx.create_shader_module(
+            Spirv::new(include_bytes!("ssao/ssao.vert.spv").to_vec()).into(),
+        )?);
+
+        let frag = FragmentShader::with_main(ctx.create_shader_module(
+            Spirv::new(include_bytes!("ssao/ssao.frag.spv").to_vec()).into(),
+        )?);
+
+        let set0 = ctx.create_descriptor_set(DescriptorSetInfo {
+            layout: set_layout.clone(),
+        })?;
+
+        let set1 = ctx.create_descriptor_set(DescriptorSetInfo {
+            layout: set_layout.clone(),
+        })?;
+
+        let nearest = ctx.create_sampler(SamplerInfo {
+            min_lod: 0.0.into(),
+            max_lod: 0.0.into(),
+            address_mode_u: SamplerAddressMode::ClampToEdge,
+            address_mode_v: SamplerAddressMode::ClampToEdge,
+            address_mode_w: SamplerAddressMode::ClampToEdge,
+            ..Default::default()
+        })?;
+
+        let linear = ctx.create_sampler(SamplerInfo {
+            mag_filter: Filter::Linear,
+            min_filter: Filter::Linear,
+            min_lod: 0.0.into(),
+            max_lod: 0.0.into(),
+            address_mode_u: SamplerAddressMode::ClampToEdge,
+            address_mode_v: SamplerAddressMode::ClampToEdge,
+            address_mode_w: SamplerAddressMode::ClampToEdge,
+            ..Default::default()
+        })?;
+
+        let render_pass = ctx.create_render_pass(RenderPassInfo {
+            attachments: smallvec![AttachmentInfo {
+                format: Format::R16Sfloat,
+                samples: Samples::Samples1,
+                load_op: AttachmentLoadOp::DontCare,
+                store_op: AttachmentStoreOp::Store,
+                initial_layout: None,
+                final_layout: Layout::ShaderReadOnlyOptimal,
+            }],
+            subpasses: smallvec![Subpass {
+                colors: smallvec![0],
+                depth: None,
+                resolves: smallvec![],
+            }],
+            dependencies: smallvec![
+                SubpassDependency {
+                    src: None,
+                    dst: Some(0),
+                    src_stages: PipelineStageFlags::FRAGMENT_SHADER,
+                    dst_stages: PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                },
+                SubpassDependency {
+                    src: Some(0),
+                    dst: None,
+                    src_stages: PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                    dst_stages: PipelineStageFlags::FRAGMENT_SHADER,
+                },
+            ],
+        })?;
+
+        let pipeline =
+            ctx.create_graphics_pipeline(graphics_pipeline_info! {
+                vertex_shader: vert,
+                layout: pipeline_layout.clone(),
+                render_pass: render_pass.clone(),
+                rasterizer: rasterizer!{
+                    fragment_shader: frag,
+                }
+            })?;
+
+        Ok(SsaoPass {
+            nearest,
+            linear,
+            normal_depth: [None, None],
+            ao: None,
+            history_valid: false,
+
+            per_frame_sets: [set0, set1],
+            pipeline_layout,
+            render_pass,
+            pipeline,
+        })
+    }
+
+    fn create_target(
+        &self,
+        extent: Extent2d,
+        ctx: &mut Context,
+    ) -> Result<(ImageView, Framebuffer), Report> {
+        let image = ctx.create_image(ImageInfo {
+            extent: extent.into(),
+            format: Format::R16Sfloat,
+            levels: 1,
+            layers: 1,
+            samples: Samples1,
+            usage: ImageUsage::COLOR_ATTACHMENT | ImageUsage::SAMPLED,
+        })?;
+
+        let view = ctx.create_image_view(ImageViewInfo::new(image))?;
+        let framebuffer = ctx.create_framebuffer(FramebufferInfo {
+            render_pass: self.render_pass.clone(),
+            views: smallvec![view.clone()],
+            extent,
+        })?;
+
+        Ok((view, framebuffer))
+    }
+}
+
+impl<'a> Pass<'a> for SsaoPass {
+    type Input = Input;
+    type Output = Output;
+
+    fn draw(
+        &mut self,
+        input: Input,
+        frame: u64,
+        wait: &[(PipelineStageFlags, Semaphore)],
+        signal: &[Semaphore],
+        fence: Option<&Fence>,
+        ctx: &mut Context,
+        _world: &mut World,
+        bump: &Bump,
+    ) -> Result<Output, Report> {
+        let extent = input.normal_depth.info().extent.into_2d();
+        let fid = (frame % 2) as usize;
+        let set = &self.per_frame_sets[fid];
+
+        let mut update_history = false;
+        match &self.ao {
+            Some([(view, _), _])
+                if view.info().image.info().extent.into_2d() == extent => {}
+            _ => {
+                self.ao = None;
+                let ao = [
+                    self.create_target(extent, ctx)?,
+                    self.create_target(extent, ctx)?,
+                ];
+                self.ao = Some(ao);
+                self.history_valid = false;
+                update_history = true;
+            }
+        }
+
+        let ao = self.ao.as_ref().unwrap();
+        let (target, framebuffer) = &ao[fid];
+        let (history, _) = &ao[1 - fid];
+
+        let mut writes = bumpalo::collections::Vec::new_in(bump);
+
+        match &self.normal_depth[fid] {
+            Some(normal_depth)
+                if normal_depth.info().image == input.normal_depth => {}
+            _ => {
+                self.normal_depth[fid] = None;
+                let normal_depth = ctx.create_image_view(
+                    ImageViewInfo::new(input.normal_depth.clone()),
+                )?;
+                let normal_depth =
+                    self.normal_depth[fid].get_or_insert(normal_depth);
+                writes.push(WriteDescriptorSet {
+                    set,
+                    binding: 0,
+                    element: 0,
+                    descriptors: Descriptors::CombinedImageSampler(bump.alloc(
+                        [(
+                            normal_depth.clone(),
+                            Layout::ShaderReadOnlyOptimal,
+                            self.nearest.clone(),
+                        )],
+                    )),
+                });
+            }
+        }
+
+        if update_history {
+            for (index, set) in self.per_frame_sets.iter().enumerate() {
+                writes.push(WriteDescriptorSet {
+                    set,
+                    binding: 1,
+                    element: 0,
+                    descriptors: Descriptors::CombinedImageSampler(bump.alloc(
+                        [(
+                            ao[1 - index].0.clone(),
+                            Layout::ShaderReadOnlyOptimal,
+                            self.linear.clone(),
+                        )],
+                    )),
+                });
+            }
+        }
+
+        ctx.update_descriptor_sets(&writes, &[]);
+
+        let params = Params {
+            radius: input.radius,
+            intensity: input.intensity,
+            history_blend: if self.history_valid {
+                HISTORY_BLEND
+            } else {
+                1.0
+            },
+        };
+
+        let history_barrier;
+        let mut encoder = ctx.queue.create_encoder()?;
+
+        // History has no content yet and is ignored by the shader,
+        // but still must be in expected layout.
+        if !self.history_valid {
+            history_barrier = [ImageLayoutTransition::initialize_whole(
+                &history.info().image,
+                Layout::ShaderReadOnlyOptimal,
+            )
+            .into()];
+
+            encoder.image_barriers(
+                PipelineStageFlags::TOP_OF_PIPE,
+                PipelineStageFlags::FRAGMENT_SHADER,
+                &history_barrier,
+            );
+        }
+
+        let mut render_pass_encoder = encoder.with_render_pass(
+            &self.render_pass,
+            framebuffer,
+            &[ClearValue::Color(1.0, 1.0, 1.0, 1.0)],
+        );
+
+        render_pass_encoder.bind_graphics_pipeline(&self.pipeline);
+        render_pass_encoder.bind_graphics_descriptor_sets(
+            &self.pipeline_layout,
+            0,
+            std::slice::from_ref(set),
+            &[],
+        );
+        render_pass_encoder.bind_graphics_descriptor_sets(
+            &self.pipeline_layout,
+            1,
+            std::slice::from_ref(&input.view.set),
+            bump.alloc([input.view.offset]),
+        );
+        render_pass_encoder.push_constants(
+            &self.pipeline_layout,
+            ShaderStageFlags::FRAGMENT,
+            0,
+            std::slice::from_ref(&params),
+        );
+        render_pass_encoder.set_viewport(Viewport {
+            x: Bounds {
+                offset: 0.0.into(),
+                size: (extent.width as f32).into(),
+            },
+            y: Bounds {
+                offset: 0.0.into(),
+                size: (extent.height as f32).into(),
+            },
+            z: Bounds {
+                offset: 0.0.into(),
+                size: 1.0.into(),
+            },
+        });
+
+        render_pass_encoder.set_scissor(extent.into());
+        render_pass_encoder.draw(0..3, 0..1);
+        drop(render_pass_encoder);
+        ctx.record_barriers(encoder.barrier_count());
+        ctx.queue.submit(wait, encoder.finish()?, signal, fence)?;
+
+        self.history_valid = true;
+
+        Ok(Output {
+            ao: target.info().image.clone(),
+        })
+    }
+}
@@ -0,0 +1,113 @@
+#version 460
+#extension GL_GOOGLE_include_directive : enable
+
+// Screen-space ambient occlusion.
+// Horizon based estimation over few rotated directions
+// accumulated over frames with reprojected history.
+
+layout(binding = 0, set = 0) uniform sampler2D normals_depth;
+layout(binding = 1, set = 0) uniform sampler2D history;
+
+#include "../common/view.glsl"
+
+layout(push_constant) uniform Params {
+    float radius;
+    float intensity;
+    float history_blend;
+} params;
+
+layout(location = 0) out float output_ao;
+
+const uint DIRECTIONS = 4;
+const uint STEPS = 4;
+const float BIAS = 0.1;
+const float PI = 3.14159265;
+
+// Camera space position of pixel at normalized coordinates.
+// Depth is distance along primary ray, same as in ray-tracing prepass.
+vec3 view_position(vec2 uv, float depth) {
+    vec2 d = uv * 2.0 - 1.0;
+    vec4 near = view_globals.iproj * vec4(d.x, -d.y, -1, 1);
+    vec4 far = view_globals.iproj * vec4(d.x, -d.y, 0, 1);
+    vec3 origin = near.xyz / near.w;
+    vec3 direction = normalize(far.xyz / far.w - origin);
+    return origin + direction * depth;
+}
+
+float interleaved_gradient_noise(vec2 pixel) {
+    pixel += float(view_globals.frame % 64) * 5.588238;
+    return fract(52.9829189 * fract(dot(pixel, vec2(0.06711056, 0.00583715))));
+}
+
+void main() {
+    vec2 screen_size = vec2(view_globals.extent);
+    vec2 uv = (gl_FragCoord.xy - 0.5) / screen_size;
+
+    vec4 normal_depth = texture(normals_depth, uv);
+    if (normal_depth.w < 0.0)
+    {
+        output_ao = 1.0;
+        return;
+    }
+
+    vec3 p = view_position(uv, normal_depth.w);
+    vec3 n = normalize((view_globals.iview * vec4(normal_depth.xyz, 0.0)).xyz);
+
+    // Radius projected to pixels.
+    float pixels = params.radius * view_globals.proj[1][1] * 0.5 * screen_size.y / max(-p.z, 0.0001);
+    float step_pixels = max(pixels / float(STEPS), 1.0);
+
+    float noise = interleaved_gradient_noise(gl_FragCoord.xy);
+    float radius2 = params.radius * params.radius;
+
+    float occlusion = 0.0;
+    for (uint i = 0; i < DIRECTIONS; ++i)
+    {
+        float angle = (float(i) + noise) * (PI / float(DIRECTIONS));
+        vec2 dir = vec2(cos(angle), sin(angle));
+
+        // Opposite directions share angle.
+        for (int side = -1; side <= 1; side += 2)
+        {
+            float horizon = 0.0;
+            for (uint s = 0; s < STEPS; ++s)
+            {
+                vec2 offset = dir * float(side) * step_pixels * (float(s) + fract(noise * 7.0) + 0.5);
+                vec2 suv = uv + offset / screen_size;
+                if (any(lessThan(suv, vec2(0.0))) || any(greaterThanEqual(suv, vec2(1.0))))
+                {
+                    break;
+                }
+
+                float sdepth = texture(normals_depth, suv).w;
+                if (sdepth < 0.0)
+                {
+                    continue;
+                }
+
+                vec3 v = view_position(suv, sdepth) - p;
+                float d2 = dot(v, v);
+                float falloff = clamp(1.0 - d2 / radius2, 0.0, 1.0);
+                float h = (dot(v, n) * inversesqrt(max(d2, 0.000001)) - BIAS) * falloff;
+                horizon = max(horizon, h);
+            }
+            occlusion += horizon;
+        }
+    }
+
+    float ao = clamp(1.0 - params.intensity * occlusion / float(DIRECTIONS * 2), 0.0, 1.0);
+
+    // Reproject into previous frame.
+    vec3 world = (view_globals.view * vec4(p, 1.0)).xyz;
+    vec4 clip = view_globals.proj * view_globals.prev_iview * vec4(world, 1.0);
+    vec2 prev_uv = vec2(clip.x, -clip.y) / clip.w * 0.5 + 0.5;
+
+    float blend = params.history_blend;
+    if (clip.w <= 0.0 || any(lessThan(prev_uv, vec2(0.0))) || any(greaterThan(prev_uv, vec2(1.0))))
+    {
+        blend = 1.0;
+    }
+
+    float prev_ao = texture(history, prev_uv + 0.5 / screen_size).r;
+    output_ao = mix(prev_ao, ao, blend);
+}
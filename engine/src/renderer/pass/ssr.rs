@@ -0,0 +1,598 @@
+use {
+    super::Pass,
+    crate::renderer::{Context, ViewUniform},
+    bumpalo::{collections::Vec as BVec, Bump},
+    bytemuck::{Pod, Zeroable},
+    color_eyre::Report,
+    hecs::World,
+    illume::*,
+    smallvec::smallvec,
+    std::mem::size_of,
+};
+
+/// Maximum number of hierarchical depth levels.
+const HIZ_MAX_LEVELS: u32 = 6;
+
+/// Surfaces rougher than this reflect nothing.
+const MAX_ROUGHNESS: f32 = 0.5;
+
+/// Assumed thickness of geometry behind depth buffer.
+const THICKNESS: f32 = 0.5;
+
+/// Maximum length of reflected ray.
+const MAX_DISTANCE: f32 = 100.0;
+
+pub struct Input {
+    /// Normals and depth in ray-tracing prepass layout.
+    /// Negative depth marks pixels without geometry.
+    pub normal_depth: Image,
+    pub albedo: Image,
+
+    /// Emissive radiance with roughness in alpha channel.
+    pub emissive: Image,
+    pub direct: Image,
+    pub diffuse: Image,
+
+    /// Globals of the rendered view.
+    pub view: ViewUniform,
+
+    /// Radiance of rays that don't hit anything on screen.
+    pub sky: [f32; 3],
+}
+
+pub struct Output {
+    /// Reflected radiance with its weight in alpha channel.
+    pub reflection: Image,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct Params {
+    hiz_levels: u32,
+    max_roughness: f32,
+    thickness: f32,
+    max_distance: f32,
+    sky: [f32; 3],
+}
+
+unsafe impl Zeroable for Params {}
+unsafe impl Pod for Params {}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct HizLevel {
+    level: u32,
+}
+
+unsafe impl Zeroable for HizLevel {}
+unsafe impl Pod for HizLevel {}
+
+struct Targets {
+    /// Nearest depth of 2x2 cells, halved in each level.
+    hiz: ImageView,
+    hiz_levels: Vec<ImageView>,
+    reflection: ImageView,
+    framebuffer: Framebuffer,
+}
+
+/// Screen-space reflections.
+///
+/// Builds hierarchical depth from normal-depth image and marches
+/// reflected rays through it. Rays that leave the screen fall back
+/// to sky radiance.
+pub struct SsrPass {
+    sampler: Sampler,
+
+    /// Normal-depth, albedo, emissive, direct and diffuse views.
+    inputs: [[Option<ImageView>; 5]; 2],
+    targets: Option<Targets>,
+
+    render_pass: RenderPass,
+    pipeline: GraphicsPipeline,
+    pipeline_layout: PipelineLayout,
+    per_frame_sets: [DescriptorSet; 2],
+
+    hiz_pipeline: ComputePipeline,
+    hiz_pipeline_layout: PipelineLayout,
+
+    /// Set for each hierarchical depth level for each frame.
+    hiz_sets: [Vec<DescriptorSet>; 2],
+}
+
+impl SsrPass {
+    pub fn new(ctx: &mut Context) -> Result<Self, Report> {
+        let set_layout =
+            ctx.create_descriptor_set_layout(DescriptorSetLayoutInfo {
+                flags: DescriptorSetLayoutFlags::UPDATE_AFTER_BIND_POOL,
+                bindings: (0..6)
+                    .map(|binding| DescriptorSetLayoutBinding {
+                        binding,
+                        ty: DescriptorType::CombinedImageSampler,
+                        count: 1,
+                        stages: ShaderStageFlags::FRAGMENT,
+                        flags: DescriptorBindingFlags::empty(),
+                    })
+                    .collect(),
+            })?;
+
+        let hiz_set_layout =
+            ctx.create_descriptor_set_layout(DescriptorSetLayoutInfo {
+                flags: DescriptorSetLayoutFlags::UPDATE_AFTER_BIND_POOL,
+                bindings: vec![
+                    // Normal-Depth
+                    DescriptorSetLayoutBinding {
+                        binding: 0,
+                        ty: DescriptorType::CombinedImageSampler,
+                        count: 1,
+                        stages: ShaderStageFlags::COMPUTE,
+                        flags: DescriptorBindingFlags::empty(),
+                    },
+                    // Previous levels
+                    DescriptorSetLayoutBinding {
+                        binding: 1,
+                        ty: DescriptorType::CombinedImageSampler,
+                        count: 1,
+                        stages: ShaderStageFlags::COMPUTE,
+                        flags: DescriptorBindingFlags::empty(),
+                    },
+                    // Output level
+                    DescriptorSetLayoutBinding {
+                        binding: 2,
+                        ty: DescriptorType::StorageImage,
+                        count: 1,
+                        stages: ShaderStageFlags::COMPUTE,
+                        flags: DescriptorBindingFlags::empty(),
+                    },
+                ],
+            })?;
+
+        let pipeline_layout =
+            ctx.create_pipeline_layout(PipelineLayoutInfo {
+                sets: vec![
+                    set_layout.clone(),
+                    ctx.view_globals_layout().clone(),
+                ],
+                push_constants: vec![PushConstant {
+                    stages: ShaderStageFlags::FRAGMENT,
+                    offset: 0,
+                    size: size_of::<Params>() as u32,
+                }],
+            })?;
+
+        let hiz_pipeline_layout =
+            ctx.create_pipeline_layout(PipelineLayoutInfo {
+                sets: vec![hiz_set_layout.clone()],
+                push_constants: vec![PushConstant {
+                    stages: ShaderStageFlags::COMPUTE,
+                    offset: 0,
+                    size: size_of::<HizLevel>() as u32,
+                }],
+            })?;
+
+        let vert = VertexShader::with_main(ctx.create_shader_module(
+            Spirv::new(include_bytes!("ssr/ssr.vert.spv").to_vec()).into(),
+        )?);
+
+        let frag = FragmentShader::with_main(ctx.create_shader_module(
+            Spirv::new(include_bytes!("ssr/ssr.frag.spv").to_vec()).into(),
+        )?);
+
+        let hiz = ComputeShader::with_main(ctx.create_shader_module(
+            Spirv::new(include_bytes!("ssr/hiz.comp.spv").to_vec()).into(),
+        )?);
+
+        let hiz_pipeline =
+            ctx.create_compute_pipeline(ComputePipelineInfo {
+                shader: hiz,
+                layout: hiz_pipeline_layout.clone(),
+            })?;
+
+        let set0 = ctx.create_descriptor_set(DescriptorSetInfo {
+            layout: set_layout.clone(),
+        })?;
+
+        let set1 = ctx.create_descriptor_set(DescriptorSetInfo {
+            layout: set_layout.clone(),
+        })?;
+
+        let mut hiz_sets = [Vec::new(), Vec::new()];
+        for sets in &mut hiz_sets {
+            for _ in 0..HIZ_MAX_LEVELS {
+                sets.push(ctx.create_descriptor_set(DescriptorSetInfo {
+                    layout: hiz_set_layout.clone(),
+                })?);
+            }
+        }
+
+        let sampler = ctx.create_sampler(SamplerInfo {
+            min_lod: 0.0.into(),
+            max_lod: (HIZ_MAX_LEVELS as f32).into(),
+            address_mode_u: SamplerAddressMode::ClampToEdge,
+            address_mode_v: SamplerAddressMode::ClampToEdge,
+            address_mode_w: SamplerAddressMode::ClampToEdge,
+            ..Default::default()
+        })?;
+
+        let render_pass = ctx.create_render_pass(RenderPassInfo {
+            attachments: smallvec![AttachmentInfo {
+                format: Format::RGBA16Sfloat,
+                samples: Samples::Samples1,
+                load_op: AttachmentLoadOp::DontCare,
+                store_op: AttachmentStoreOp::Store,
+                initial_layout: None,
+                final_layout: Layout::ShaderReadOnlyOptimal,
+            }],
+            subpasses: smallvec![Subpass {
+                colors: smallvec![0],
+                depth: None,
+                resolves: smallvec![],
+            }],
+            dependencies: smallvec![
+                SubpassDependency {
+                    src: None,
+                    dst: Some(0),
+                    src_stages: PipelineStageFlags::FRAGMENT_SHADER,
+                    dst_stages: PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                },
+                SubpassDependency {
+                    src: Some(0),
+                    dst: None,
+                    src_stages: PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                    dst_stages: PipelineStageFlags::FRAGMENT_SHADER,
+                },
+            ],
+        })?;
+
+        let pipeline =
+            ctx.create_graphics_pipeline(graphics_pipeline_info! {
+                vertex_shader: vert,
+                layout: pipeline_layout.clone(),
+                render_pass: render_pass.clone(),
+                rasterizer: rasterizer!{
+                    fragment_shader: frag,
+                }
+            })?;
+
+        Ok(SsrPass {
+            sampler,
+            inputs: Default::default(),
+            targets: None,
+
+            render_pass,
+            pipeline,
+            pipeline_layout,
+            per_frame_sets: [set0, set1],
+
+            hiz_pipeline,
+            hiz_pipeline_layout,
+            hiz_sets,
+        })
+    }
+
+    fn create_targets(
+        &self,
+        extent: Extent2d,
+        ctx: &mut Context,
+    ) -> Result<Targets, Report> {
+        let levels = hiz_level_count(extent);
+        let hiz = ctx.create_image(ImageInfo {
+            extent: hiz_level_extent(extent, 0).into(),
+            format: Format::R32Sfloat,
+            levels,
+            layers: 1,
+            samples: Samples1,
+            usage: ImageUsage::STORAGE | ImageUsage::SAMPLED,
+        })?;
+
+        let hiz_levels = (0..levels)
+            .map(|level| {
+                ctx.create_image_view(ImageViewInfo {
+                    view_kind: ImageViewKind::D2,
+                    subresource: ImageSubresourceRange::new(
+                        AspectFlags::COLOR,
+                        level..level + 1,
+                        0..1,
+                    ),
+                    image: hiz.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let hiz = ctx.create_image_view(ImageViewInfo::new(hiz))?;
+
+        let reflection = ctx.create_image(ImageInfo {
+            extent: extent.into(),
+            format: Format::RGBA16Sfloat,
+            levels: 1,
+            layers: 1,
+            samples: Samples1,
+            usage: ImageUsage::COLOR_ATTACHMENT | ImageUsage::SAMPLED,
+        })?;
+
+        let reflection =
+            ctx.create_image_view(ImageViewInfo::new(reflection))?;
+        let framebuffer = ctx.create_framebuffer(FramebufferInfo {
+            render_pass: self.render_pass.clone(),
+            views: smallvec![reflection.clone()],
+            extent,
+        })?;
+
+        Ok(Targets {
+            hiz,
+            hiz_levels,
+            reflection,
+            framebuffer,
+        })
+    }
+}
+
+impl<'a> Pass<'a> for SsrPass {
+    type Input = Input;
+    type Output = Output;
+
+    fn draw(
+        &mut self,
+        input: Input,
+        frame: u64,
+        wait: &[(PipelineStageFlags, Semaphore)],
+        signal: &[Semaphore],
+        fence: Option<&Fence>,
+        ctx: &mut Context,
+        _world: &mut World,
+        bump: &Bump,
+    ) -> Result<Output, Report> {
+        let extent = input.normal_depth.info().extent.into_2d();
+        let fid = (frame % 2) as usize;
+        let set = &self.per_frame_sets[fid];
+
+        let mut writes = BVec::new_in(bump);
+
+        let mut new_targets = false;
+        match &self.targets {
+            Some(targets)
+                if targets.reflection.info().image.info().extent.into_2d()
+                    == extent => {}
+            _ => {
+                self.targets = None;
+                let targets = self.create_targets(extent, ctx)?;
+                self.targets = Some(targets);
+                new_targets = true;
+            }
+        }
+
+        let targets = self.targets.as_ref().unwrap();
+
+        if new_targets {
+            for (sets, frame_set) in
+                self.hiz_sets.iter().zip(&self.per_frame_sets)
+            {
+                writes.push(WriteDescriptorSet {
+                    set: frame_set,
+                    binding: 1,
+                    element: 0,
+                    descriptors: Descriptors::CombinedImageSampler(bump.alloc(
+                        [(
+                            targets.hiz.clone(),
+                            Layout::General,
+                            self.sampler.clone(),
+                        )],
+                    )),
+                });
+
+                for (level, set) in sets.iter().enumerate() {
+                    // Sets of missing levels are never dispatched
+                    // but must be valid.
+                    let output = level.min(targets.hiz_levels.len() - 1);
+                    writes.push(WriteDescriptorSet {
+                        set,
+                        binding: 1,
+                        element: 0,
+                        descriptors: Descriptors::CombinedImageSampler(
+                            bump.alloc([(
+                                targets.hiz.clone(),
+                                Layout::General,
+                                self.sampler.clone(),
+                            )]),
+                        ),
+                    });
+                    writes.push(WriteDescriptorSet {
+                        set,
+                        binding: 2,
+                        element: 0,
+                        descriptors: Descriptors::StorageImage(bump.alloc([(
+                            targets.hiz_levels[output].clone(),
+                            Layout::General,
+                        )])),
+                    });
+                }
+            }
+        }
+
+        let images = [
+            &input.normal_depth,
+            &input.albedo,
+            &input.emissive,
+            &input.direct,
+            &input.diffuse,
+        ];
+
+        for (index, (slot, image)) in
+            self.inputs[fid].iter_mut().zip(images.iter()).enumerate()
+        {
+            match slot {
+                Some(view) if view.info().image == **image => continue,
+                _ => {}
+            }
+
+            *slot = None;
+            let view =
+                ctx.create_image_view(ImageViewInfo::new((*image).clone()))?;
+            let view = slot.get_or_insert(view);
+
+            let binding = if index == 0 { 0 } else { index as u32 + 1 };
+            let descriptors: &[_] = bump.alloc([(
+                view.clone(),
+                Layout::ShaderReadOnlyOptimal,
+                self.sampler.clone(),
+            )]);
+
+            writes.push(WriteDescriptorSet {
+                set,
+                binding,
+                element: 0,
+                descriptors: Descriptors::CombinedImageSampler(descriptors),
+            });
+
+            if index == 0 {
+                for set in &self.hiz_sets[fid] {
+                    writes.push(WriteDescriptorSet {
+                        set,
+                        binding: 0,
+                        element: 0,
+                        descriptors: Descriptors::CombinedImageSampler(
+                            descriptors,
+                        ),
+                    });
+                }
+            }
+        }
+
+        ctx.update_descriptor_sets(&writes, &[]);
+
+        let params = Params {
+            hiz_levels: targets.hiz_levels.len() as u32,
+            max_roughness: MAX_ROUGHNESS,
+            thickness: THICKNESS,
+            max_distance: MAX_DISTANCE,
+            sky: input.sky,
+        };
+
+        let hiz_barrier;
+        let mut encoder = ctx.queue.create_encoder()?;
+
+        if new_targets {
+            hiz_barrier = [ImageLayoutTransition::initialize_whole(
+                &targets.hiz.info().image,
+                Layout::General,
+            )
+            .into()];
+
+            encoder.image_barriers(
+                PipelineStageFlags::TOP_OF_PIPE,
+                PipelineStageFlags::COMPUTE_SHADER,
+                &hiz_barrier,
+            );
+        } else {
+            // Previous frame may still read hierarchical depth.
+            encoder.pipeline_barrier(
+                PipelineStageFlags::FRAGMENT_SHADER,
+                PipelineStageFlags::COMPUTE_SHADER,
+            );
+        }
+
+        let levels = bump.alloc_slice_fill_iter(
+            (0..targets.hiz_levels.len() as u32)
+                .map(|level| HizLevel { level }),
+        );
+
+        encoder.bind_compute_pipeline(&self.hiz_pipeline);
+        for (level, set) in levels.iter().zip(&self.hiz_sets[fid]) {
+            if level.level > 0 {
+                encoder.pipeline_barrier(
+                    PipelineStageFlags::COMPUTE_SHADER,
+                    PipelineStageFlags::COMPUTE_SHADER,
+                );
+            }
+
+            encoder.bind_compute_descriptor_sets(
+                &self.hiz_pipeline_layout,
+                0,
+                std::slice::from_ref(set),
+                &[],
+            );
+            encoder.push_constants(
+                &self.hiz_pipeline_layout,
+                ShaderStageFlags::COMPUTE,
+                0,
+                std::slice::from_ref(level),
+            );
+
+            let level_extent = hiz_level_extent(extent, level.level);
+            encoder.dispatch(
+                (level_extent.width + 7) / 8,
+                (level_extent.height + 7) / 8,
+                1,
+            );
+        }
+
+        encoder.pipeline_barrier(
+            PipelineStageFlags::COMPUTE_SHADER,
+            PipelineStageFlags::FRAGMENT_SHADER,
+        );
+
+        let mut render_pass_encoder = encoder.with_render_pass(
+            &self.render_pass,
+            &targets.framebuffer,
+            &[ClearValue::Color(0.0, 0.0, 0.0, 0.0)],
+        );
+
+        render_pass_encoder.bind_graphics_pipeline(&self.pipeline);
+        render_pass_encoder.bind_graphics_descriptor_sets(
+            &self.pipeline_layout,
+            0,
+            std::slice::from_ref(set),
+            &[],
+        );
+        render_pass_encoder.bind_graphics_descriptor_sets(
+            &self.pipeline_layout,
+            1,
+            std::slice::from_ref(&input.view.set),
+            bump.alloc([input.view.offset]),
+        );
+        render_pass_encoder.push_constants(
+            &self.pipeline_layout,
+            ShaderStageFlags::FRAGMENT,
+            0,
+            std::slice::from_ref(&params),
+        );
+        render_pass_encoder.set_viewport(Viewport {
+            x: Bounds {
+                offset: 0.0.into(),
+                size: (extent.width as f32).into(),
+            },
+            y: Bounds {
+                offset: 0.0.into(),
+                size: (extent.height as f32).into(),
+            },
+            z: Bounds {
+                offset: 0.0.into(),
+                size: 1.0.into(),
+            },
+        });
+
+        render_pass_encoder.set_scissor(extent.into());
+        render_pass_encoder.draw(0..3, 0..1);
+        drop(render_pass_encoder);
+        ctx.record_barriers(encoder.barrier_count());
+        ctx.queue.submit(wait, encoder.finish()?, signal, fence)?;
+
+        Ok(Output {
+            reflection: targets.reflection.info().image.clone(),
+        })
+    }
+}
+
+/// Number of hierarchical depth levels for viewport extent.
+fn hiz_level_count(extent: Extent2d) -> u32 {
+    let max = extent.width.max(extent.height).max(2);
+    (31 - max.leading_zeros()).min(HIZ_MAX_LEVELS)
+}
+
+/// Extent of hierarchical depth level.
+/// Level 0 is half of viewport extent.
+fn hiz_level_extent(extent: Extent2d, level: u32) -> Extent2d {
+    Extent2d {
+        width: (extent.width >> (level + 1)).max(1),
+        height: (extent.height >> (level + 1)).max(1),
+    }
+}
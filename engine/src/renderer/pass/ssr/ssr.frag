@@ -0,0 +1,161 @@
+#version 460
+#extension GL_GOOGLE_include_directive : enable
+
+// Screen-space reflections.
+// Reflected rays are marched through hierarchical depth.
+// Rays that leave the screen or pass behind geometry fall back
+// to sky radiance.
+
+layout(binding = 0, set = 0) uniform sampler2D normals_depth;
+layout(binding = 1, set = 0) uniform sampler2D hiz;
+layout(binding = 2, set = 0) uniform sampler2D albedo;
+layout(binding = 3, set = 0) uniform sampler2D emissive;
+layout(binding = 4, set = 0) uniform sampler2D direct;
+layout(binding = 5, set = 0) uniform sampler2D diffuse;
+
+#include "../common/view.glsl"
+
+layout(push_constant) uniform Params {
+    uint hiz_levels;
+    float max_roughness;
+    float thickness;
+    float max_distance;
+    vec3 sky;
+} params;
+
+// Reflected radiance and its weight.
+layout(location = 0) out vec4 output_reflection;
+
+const uint MAX_STEPS = 64;
+const float FAR = 1e30;
+const float F0 = 0.04;
+
+vec3 near_origin(vec2 uv) {
+    vec2 d = uv * 2.0 - 1.0;
+    vec4 near = view_globals.iproj * vec4(d.x, -d.y, -1, 1);
+    return near.xyz / near.w;
+}
+
+// Camera space position of pixel at normalized coordinates.
+// Depth is distance along primary ray, same as in ray-tracing prepass.
+vec3 view_position(vec2 uv, float depth) {
+    vec2 d = uv * 2.0 - 1.0;
+    vec4 far = view_globals.iproj * vec4(d.x, -d.y, 0, 1);
+    vec3 origin = near_origin(uv);
+    vec3 direction = normalize(far.xyz / far.w - origin);
+    return origin + direction * depth;
+}
+
+vec2 project(vec3 p) {
+    vec4 clip = view_globals.proj * vec4(p, 1.0);
+    return vec2(clip.x, -clip.y) / clip.w * 0.5 + 0.5;
+}
+
+ivec2 pixel(vec2 uv) {
+    return ivec2(floor(uv * vec2(view_globals.extent) + 0.5));
+}
+
+// Nearest depth in the cell of given level that covers the pixel.
+// Level 0 is full resolution depth.
+float scene_depth(ivec2 pixel, uint level) {
+    if (level == 0)
+    {
+        float depth = texelFetch(normals_depth, pixel, 0).w;
+        return depth < 0.0 ? FAR : depth;
+    }
+
+    ivec2 size = textureSize(hiz, int(level - 1));
+    return texelFetch(hiz, min(pixel >> level, size - 1), int(level - 1)).r;
+}
+
+// Number of pixels ray crosses per unit of length at point `p`.
+float pixels_per_unit(vec3 p, vec3 dir) {
+    vec2 dxy = (dir.xy * -p.z + p.xy * dir.z) / (p.z * p.z);
+    return length(dxy) * view_globals.proj[1][1] * 0.5 * float(view_globals.extent.y);
+}
+
+vec3 radiance(ivec2 pixel) {
+    vec3 albedo = texelFetch(albedo, pixel, 0).rgb;
+    vec3 emissive = texelFetch(emissive, pixel, 0).rgb;
+    vec3 direct = texelFetch(direct, pixel, 0).rgb;
+    vec3 diffuse = texelFetch(diffuse, pixel, 0).rgb;
+    return albedo * (direct + diffuse) + emissive;
+}
+
+// Fades hits close to screen borders into fallback.
+float edge_fade(vec2 uv) {
+    vec2 edge = min(uv, 1.0 - uv);
+    return clamp(min(edge.x, edge.y) * 10.0, 0.0, 1.0);
+}
+
+void main() {
+    ivec2 origin_pixel = ivec2(gl_FragCoord.xy);
+    vec2 uv = vec2(origin_pixel) / vec2(view_globals.extent);
+
+    vec4 normal_depth = texelFetch(normals_depth, origin_pixel, 0);
+    float roughness = texelFetch(emissive, origin_pixel, 0).a;
+    if (normal_depth.w < 0.0 || roughness >= params.max_roughness)
+    {
+        output_reflection = vec4(0.0);
+        return;
+    }
+
+    vec3 p = view_position(uv, normal_depth.w);
+    vec3 n = normalize((view_globals.iview * vec4(normal_depth.xyz, 0.0)).xyz);
+    vec3 v = normalize(p);
+    vec3 dir = reflect(v, n);
+
+    float cos_theta = clamp(dot(-v, n), 0.0, 1.0);
+    float fresnel = F0 + (1.0 - F0) * pow(1.0 - cos_theta, 5.0);
+    float weight = fresnel * (1.0 - roughness / params.max_roughness);
+
+    // Start slightly off the surface to avoid self intersection.
+    vec3 start = p + n * params.thickness * 0.1;
+
+    vec3 reflection = params.sky;
+    float t = 0.0;
+    uint level = 0;
+    for (uint i = 0; i < MAX_STEPS && t < params.max_distance; ++i)
+    {
+        vec3 q = start + dir * t;
+        if (q.z >= near_origin(vec2(0.5)).z)
+        {
+            break;
+        }
+
+        vec2 quv = project(q);
+        if (any(lessThan(quv, vec2(0.0))) || any(greaterThanEqual(quv, vec2(1.0))))
+        {
+            break;
+        }
+
+        ivec2 qpixel = pixel(quv);
+        float ray_depth = length(q - near_origin(quv));
+        float step_length = float(1u << level) / max(pixels_per_unit(q, dir), 0.0001);
+
+        if (ray_depth < scene_depth(qpixel, level))
+        {
+            // Whole cell is behind the ray. Skip it and try coarser level.
+            t += step_length;
+            level = min(level + 1, params.hiz_levels);
+        }
+        else if (level > 0)
+        {
+            // Ray may be occluded inside the cell. Refine.
+            level -= 1;
+        }
+        else
+        {
+            if (ray_depth - scene_depth(qpixel, 0) < params.thickness)
+            {
+                reflection = mix(params.sky, radiance(qpixel), edge_fade(quv));
+                break;
+            }
+
+            // Ray passes behind thin geometry.
+            t += step_length;
+        }
+    }
+
+    output_reflection = vec4(reflection, weight);
+}
@@ -96,10 +96,10 @@ impl Pass<'_> for SwapchainBlitPresentPass {
         // Submit execution.
         ctx.queue.submit(
             &[(PipelineStageFlags::all(), input.frame.info().wait.clone())],
-            encoder.finish(),
+            encoder.finish()?,
             &[input.frame.info().signal.clone()],
             fence,
-        );
+        )?;
 
         // Present the frame.
         ctx.queue.present(input.frame);
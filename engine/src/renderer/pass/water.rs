@@ -0,0 +1,342 @@
+//!
+//! Compute pass that simulates `Water` surfaces on the device.
+//!
+//! Spectrum of each surface is advanced to the time of its last
+//! simulation step and transformed into heights, horizontal displacements
+//! and slopes with radix-2 FFT.
+//! Displaced positions are written into vertices of the surface mesh,
+//! so rasterization and BLAS built from the mesh follow the waves.
+//! Slopes are encoded into the normal map of the surface.
+
+use {
+    super::{Pass, SparseDescriptors},
+    crate::{
+        renderer::{Context, PositionNormalTangent3dUV, VertexType as _},
+        water::Water,
+    },
+    bumpalo::{collections::Vec as BVec, Bump},
+    bytemuck::{Pod, Zeroable},
+    eyre::Report,
+    hecs::World,
+    illume::{
+        Buffer, ComputePipeline, ComputePipelineInfo, ComputeShader,
+        DescriptorBindingFlags, DescriptorSet, DescriptorSetInfo,
+        DescriptorSetLayoutBinding, DescriptorSetLayoutFlags,
+        DescriptorSetLayoutInfo, DescriptorType, Descriptors, Fence, Layout,
+        PipelineLayout, PipelineLayoutInfo, PipelineStageFlags, PushConstant,
+        Semaphore, ShaderStageFlags, Spirv, WriteDescriptorSet,
+    },
+    std::mem::size_of,
+};
+
+/// Maximum number of water surfaces simulated at once.
+pub const MAX_WATERS: u32 = 16;
+
+/// Number of fields evaluated for each texel of simulated grid.
+/// Must match `FIELDS` in shaders.
+pub const WATER_FIELDS: u64 = 5;
+
+pub struct WaterPass {
+    layout: PipelineLayout,
+    spectrum: ComputePipeline,
+    fft: ComputePipeline,
+    displace: ComputePipeline,
+    normals: ComputePipeline,
+    set: DescriptorSet,
+    waters: SparseDescriptors<Buffer>,
+}
+
+impl WaterPass {
+    pub fn new(ctx: &mut Context) -> Result<Self, Report> {
+        let binding = |binding, ty, count| DescriptorSetLayoutBinding {
+            binding,
+            ty,
+            count,
+            stages: ShaderStageFlags::COMPUTE,
+            flags: DescriptorBindingFlags::PARTIALLY_BOUND
+                | DescriptorBindingFlags::UPDATE_UNUSED_WHILE_PENDING,
+        };
+
+        let set_layout =
+            ctx.create_descriptor_set_layout(DescriptorSetLayoutInfo {
+                flags: DescriptorSetLayoutFlags::empty(),
+                bindings: vec![
+                    // Waves
+                    binding(0, DescriptorType::StorageBuffer, MAX_WATERS),
+                    // Fields
+                    binding(1, DescriptorType::StorageBuffer, MAX_WATERS * 2),
+                    // Vertices
+                    binding(2, DescriptorType::StorageBuffer, MAX_WATERS),
+                    // Normal maps
+                    binding(3, DescriptorType::StorageImage, MAX_WATERS),
+                ],
+            })?;
+
+        let layout = ctx.create_pipeline_layout(PipelineLayoutInfo {
+            sets: vec![set_layout.clone()],
+            push_constants: vec![PushConstant {
+                stages: ShaderStageFlags::COMPUTE,
+                offset: 0,
+                size: size_of::<Params>() as u32,
+            }],
+        })?;
+
+        let mut create_pipeline = |code: &[u8]| -> Result<_, Report> {
+            let shader = ComputeShader::with_main(
+                ctx.create_shader_module(Spirv::new(code.to_vec()).into())?,
+            );
+
+            let pipeline =
+                ctx.create_compute_pipeline(ComputePipelineInfo {
+                    shader,
+                    layout: layout.clone(),
+                })?;
+
+            Ok(pipeline)
+        };
+
+        let spectrum =
+            create_pipeline(include_bytes!("water/spectrum.comp.spv"))?;
+        let fft = create_pipeline(include_bytes!("water/fft.comp.spv"))?;
+        let displace =
+            create_pipeline(include_bytes!("water/displace.comp.spv"))?;
+        let normals =
+            create_pipeline(include_bytes!("water/normals.comp.spv"))?;
+
+        let set = ctx
+            .create_descriptor_set(DescriptorSetInfo { layout: set_layout })?;
+
+        Ok(WaterPass {
+            layout,
+            spectrum,
+            fft,
+            displace,
+            normals,
+            set,
+            waters: SparseDescriptors::new(),
+        })
+    }
+}
+
+impl Pass<'_> for WaterPass {
+    type Input = ();
+    type Output = ();
+
+    fn draw(
+        &mut self,
+        _: (),
+        _frame: u64,
+        wait: &[(PipelineStageFlags, Semaphore)],
+        signal: &[Semaphore],
+        fence: Option<&Fence>,
+        ctx: &mut Context,
+        world: &mut World,
+        bump: &Bump,
+    ) -> Result<(), Report> {
+        self.waters.next_frame();
+
+        let mut writes = BVec::new_in(bump);
+        let mut to_dispatch = BVec::new_in(bump);
+
+        for (_, water) in world.query::<&Water>().iter() {
+            let resources = water.resources();
+            let info = water.info();
+
+            let (index, new) = self.waters.index(resources.waves.clone());
+            if index >= MAX_WATERS {
+                tracing::error!(
+                    "Only {} water surfaces are simulated",
+                    MAX_WATERS
+                );
+                continue;
+            }
+
+            let vectors = resources
+                .mesh
+                .bindings()
+                .iter()
+                .find(|binding| {
+                    binding.layout == PositionNormalTangent3dUV::layout()
+                })
+                .unwrap();
+            let vertex_count = resources.mesh.vertex_count();
+            let vectors_size =
+                u64::from(vectors.layout.stride) * u64::from(vertex_count);
+
+            if new {
+                let fields_size = resources.fields[0].info().size;
+
+                writes.push(WriteDescriptorSet {
+                    set: &self.set,
+                    binding: 0,
+                    element: index,
+                    descriptors: Descriptors::StorageBuffer(bump.alloc([(
+                        resources.waves.clone(),
+                        0,
+                        resources.waves.info().size,
+                    )])),
+                });
+
+                writes.push(WriteDescriptorSet {
+                    set: &self.set,
+                    binding: 1,
+                    element: index * 2,
+                    descriptors: Descriptors::StorageBuffer(bump.alloc([
+                        (resources.fields[0].clone(), 0, fields_size),
+                        (resources.fields[1].clone(), 0, fields_size),
+                    ])),
+                });
+
+                writes.push(WriteDescriptorSet {
+                    set: &self.set,
+                    binding: 2,
+                    element: index,
+                    descriptors: Descriptors::StorageBuffer(bump.alloc([(
+                        vectors.buffer.clone(),
+                        vectors.offset,
+                        vectors_size,
+                    )])),
+                });
+
+                writes.push(WriteDescriptorSet {
+                    set: &self.set,
+                    binding: 3,
+                    element: index,
+                    descriptors: Descriptors::StorageImage(bump.alloc([(
+                        resources.normal_map.image.clone(),
+                        Layout::General,
+                    )])),
+                });
+            }
+
+            to_dispatch.push(Params {
+                water: index,
+                size: info.size,
+                patch_size: info.patch,
+                time: water.time(),
+                choppiness: info.choppiness,
+                span: 1,
+                axis: 0,
+                src: 0,
+                vertex_count,
+            });
+        }
+
+        if to_dispatch.is_empty() {
+            return Ok(());
+        }
+
+        ctx.update_descriptor_sets(&writes, &[]);
+
+        let mut encoder = ctx.queue.create_encoder()?;
+
+        // Vertices and normal maps are read by previous frame.
+        encoder.pipeline_barrier(
+            PipelineStageFlags::VERTEX_INPUT
+                | PipelineStageFlags::FRAGMENT_SHADER
+                | PipelineStageFlags::RAY_TRACING_SHADER
+                | PipelineStageFlags::ACCELERATION_STRUCTURE_BUILD,
+            PipelineStageFlags::COMPUTE_SHADER,
+        );
+
+        encoder.bind_compute_descriptor_sets(
+            &self.layout,
+            0,
+            std::slice::from_ref(&self.set),
+            &[],
+        );
+
+        for params in to_dispatch.iter() {
+            let n = params.size;
+            let groups = (n + 7) / 8;
+
+            encoder.bind_compute_pipeline(&self.spectrum);
+            encoder.push_constants(
+                &self.layout,
+                ShaderStageFlags::COMPUTE,
+                0,
+                bump.alloc([*params]),
+            );
+            encoder.dispatch(groups, groups, 1);
+
+            // Rows are transformed first, then columns.
+            // Each stage reads fields written by previous one.
+            encoder.bind_compute_pipeline(&self.fft);
+            let mut src = 0;
+            for axis in 0..2 {
+                let mut span = 1;
+                while span < n {
+                    encoder.pipeline_barrier(
+                        PipelineStageFlags::COMPUTE_SHADER,
+                        PipelineStageFlags::COMPUTE_SHADER,
+                    );
+                    encoder.push_constants(
+                        &self.layout,
+                        ShaderStageFlags::COMPUTE,
+                        0,
+                        bump.alloc([Params {
+                            span,
+                            axis,
+                            src,
+                            ..*params
+                        }]),
+                    );
+                    encoder.dispatch((n / 2 + 63) / 64, n, WATER_FIELDS as u32);
+                    span *= 2;
+                    src = 1 - src;
+                }
+            }
+
+            // Even number of stages leaves results in the first element.
+            debug_assert_eq!(src, 0);
+
+            encoder.pipeline_barrier(
+                PipelineStageFlags::COMPUTE_SHADER,
+                PipelineStageFlags::COMPUTE_SHADER,
+            );
+
+            encoder.bind_compute_pipeline(&self.displace);
+            encoder.push_constants(
+                &self.layout,
+                ShaderStageFlags::COMPUTE,
+                0,
+                bump.alloc([*params]),
+            );
+            encoder.dispatch((params.vertex_count + 63) / 64, 1, 1);
+
+            encoder.bind_compute_pipeline(&self.normals);
+            encoder.dispatch(groups, groups, 1);
+        }
+
+        // Meshes are rasterized, traced and built into BLASes.
+        encoder.pipeline_barrier(
+            PipelineStageFlags::COMPUTE_SHADER,
+            PipelineStageFlags::VERTEX_INPUT
+                | PipelineStageFlags::FRAGMENT_SHADER
+                | PipelineStageFlags::RAY_TRACING_SHADER
+                | PipelineStageFlags::ACCELERATION_STRUCTURE_BUILD,
+        );
+
+        ctx.record_barriers(encoder.barrier_count());
+        ctx.queue.submit(wait, encoder.finish()?, signal, fence)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct Params {
+    water: u32,
+    size: u32,
+    patch_size: f32,
+    time: f32,
+    choppiness: f32,
+    span: u32,
+    axis: u32,
+    src: u32,
+    vertex_count: u32,
+}
+
+unsafe impl Zeroable for Params {}
+unsafe impl Pod for Params {}
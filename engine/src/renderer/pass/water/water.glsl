@@ -0,0 +1,76 @@
+#extension GL_EXT_scalar_block_layout : enable
+#extension GL_EXT_nonuniform_qualifier : enable
+
+#ifndef M_PI
+#define M_PI 3.1415926535897932384626433832795
+#endif
+
+// Number of fields evaluated for each texel.
+// Height, horizontal displacement along X and Z, slope along X and Z.
+const uint FIELDS = 5;
+
+const uint FIELD_HEIGHT = 0;
+const uint FIELD_DISPLACEMENT_X = 1;
+const uint FIELD_DISPLACEMENT_Z = 2;
+const uint FIELD_SLOPE_X = 3;
+const uint FIELD_SLOPE_Z = 4;
+
+struct Wave {
+    vec2 h0;
+    float omega;
+    float pad;
+};
+
+struct Vertex {
+    vec3 pos;
+    vec3 norm;
+    vec4 tangh;
+    vec2 uv;
+};
+
+// Each water surface has own element in every array.
+layout(binding = 0, set = 0, std430) readonly buffer Waves { Wave w[]; } waves[];
+
+// Two elements per water surface. FFT stages ping-pong between them.
+layout(binding = 1, set = 0, std430) buffer Fields { vec2 f[]; } fields[];
+
+layout(binding = 2, set = 0, scalar) buffer Vertices { Vertex v[]; } vertices[];
+layout(binding = 3, set = 0, rgba8) uniform writeonly image2D normal_maps[];
+
+layout(push_constant) uniform Params {
+    uint water;
+    uint size;
+    float patch_size;
+    float time;
+    float choppiness;
+
+    // Half-size of sub-transforms merged by FFT stage.
+    uint span;
+
+    // Zero for FFT over rows, one for FFT over columns.
+    uint axis;
+
+    // Element of `fields` that FFT stage reads.
+    uint src;
+
+    uint vertex_count;
+};
+
+vec2 cmul(vec2 a, vec2 b) {
+    return vec2(a.x * b.x - a.y * b.y, a.x * b.y + a.y * b.x);
+}
+
+vec2 conj(vec2 a) {
+    return vec2(a.x, -a.y);
+}
+
+// Multiplies by imaginary unit.
+vec2 imul(vec2 a) {
+    return vec2(-a.y, a.x);
+}
+
+// Fields of the surface after inverse FFT.
+// Even number of FFT stages ends in the first element.
+vec2 field(uint f, uint texel) {
+    return fields[water * 2].f[f * size * size + texel];
+}
@@ -0,0 +1,101 @@
+//! Hook points for external denoisers.
+//!
+//! Path tracing pipeline exports noisy radiance together with
+//! albedo and normals to the host, runs `ExternalDenoiser`
+//! found in the world and imports the result in place of
+//! built-in A-Trous filters.
+//! Export stalls until the frame's ray-tracing is complete,
+//! so this is intended for offline and high-quality captures.
+
+use eyre::Report;
+
+/// Host-side images of single denoised channel.
+/// All slices contain `width * height` texels in row-major order.
+pub struct DenoiseImage<'a> {
+    pub width: usize,
+    pub height: usize,
+
+    /// Noisy radiance in linear HDR.
+    pub color: &'a [[f32; 3]],
+
+    /// Surface albedo in range `[0, 1]`.
+    pub albedo: &'a [[f32; 3]],
+
+    /// World-space surface normals.
+    /// Zero where primary ray hit nothing.
+    pub normal: &'a [[f32; 3]],
+
+    /// Denoised radiance.
+    pub output: &'a mut [[f32; 3]],
+}
+
+/// Denoiser running on the host.
+pub trait Denoiser: Send + Sync + 'static {
+    /// Denoises single channel.
+    fn denoise(&mut self, image: DenoiseImage<'_>) -> Result<(), Report>;
+}
+
+/// Component that provides external denoiser to path tracing pipeline.
+/// Renderer uses first one found in the world
+/// while `r.external_denoise` is set.
+pub struct ExternalDenoiser {
+    denoiser: Box<dyn Denoiser>,
+}
+
+impl ExternalDenoiser {
+    pub fn new(denoiser: impl Denoiser) -> Self {
+        ExternalDenoiser {
+            denoiser: Box::new(denoiser),
+        }
+    }
+
+    pub fn denoise(&mut self, image: DenoiseImage<'_>) -> Result<(), Report> {
+        self.denoiser.denoise(image)
+    }
+}
+
+/// Intel Open Image Denoise running on CPU.
+#[cfg(feature = "oidn")]
+pub struct OidnDenoiser {
+    device: oidn::Device,
+}
+
+#[cfg(feature = "oidn")]
+impl OidnDenoiser {
+    pub fn new() -> Self {
+        OidnDenoiser {
+            device: oidn::Device::new(),
+        }
+    }
+}
+
+#[cfg(feature = "oidn")]
+impl Default for OidnDenoiser {
+    fn default() -> Self {
+        OidnDenoiser::new()
+    }
+}
+
+#[cfg(feature = "oidn")]
+impl Denoiser for OidnDenoiser {
+    fn denoise(&mut self, image: DenoiseImage<'_>) -> Result<(), Report> {
+        oidn::RayTracing::new(&self.device)
+            .hdr(true)
+            .srgb(false)
+            .image_dimensions(image.width, image.height)
+            .albedo_normal(
+                bytemuck::cast_slice(image.albedo),
+                bytemuck::cast_slice(image.normal),
+            )
+            .filter(
+                bytemuck::cast_slice(image.color),
+                bytemuck::cast_slice_mut(image.output),
+            )
+            .map_err(|err| eyre::eyre!("OIDN filter error: {:?}", err))?;
+
+        match self.device.get_error() {
+            Ok(()) => Ok(()),
+            Err((_, msg)) => Err(eyre::eyre!("OIDN error: {}", msg)),
+        }
+    }
+}
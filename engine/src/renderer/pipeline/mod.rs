@@ -1,24 +1,80 @@
+mod denoise;
 mod path_trace;
 mod ray_probe;
 
 use {
-    super::{AccelerationStructure, Context, Image, Mesh, Semaphore},
+    super::{
+        AccelerationStructure, Context, Extent2d, Mesh, PixelSample,
+        RenderConstants, SwapchainFrame, TextOverlay,
+    },
+    crate::{
+        camera::{Camera, CameraSettings},
+        clocks::ClockIndex,
+        scene::Global3,
+    },
     bumpalo::Bump,
     eyre::Report,
     hecs::World,
-    std::collections::HashMap,
+    std::{collections::HashMap, path::PathBuf},
 };
 
-pub use self::{path_trace::*, ray_probe::*};
+pub use self::{denoise::*, path_trace::*, ray_probe::*};
 
 /// Pipeline represents particular rendering strategy.
 /// For example path-tracing pipeline uses path tracing and denoising to render final image.
 pub trait Pipeline {
+    /// Returns `true` if pipeline can be drawn.
+    /// Views are skipped while their pipeline is not ready,
+    /// for example while its shaders are compiled in background.
+    fn is_ready(&mut self, _ctx: &mut Context) -> Result<bool, Report> {
+        Ok(true)
+    }
+
+    /// Selects pixel to read back in following frames
+    /// and returns last values read back.
+    /// Pipelines without intermediate images return `None`.
+    fn inspect_pixel(
+        &mut self,
+        _pixel: Option<[u32; 2]>,
+    ) -> Option<PixelSample> {
+        None
+    }
+
+    /// Requests capture of accumulated frame into file
+    /// during next draw and returns number of samples
+    /// accumulated by last draw.
+    /// Pipelines that don't accumulate samples return `None`.
+    fn accumulation(&mut self, _capture: Option<PathBuf>) -> Option<u32> {
+        None
+    }
+
+    /// Requests capture of accumulated frame during next draw
+    /// to be read back with `take_readback` instead of written into file.
+    /// Pipelines that don't accumulate samples ignore it.
+    fn request_readback(&mut self) {}
+
+    /// Returns exposed radiance captured after `request_readback`.
+    /// `None` until capture is complete.
+    fn take_readback(&mut self) -> Option<(Extent2d, Vec<[f32; 4]>)> {
+        None
+    }
+
+    /// Sets extent of internal images for following draws.
+    /// Pipelines that render at target extent ignore it.
+    fn set_extent(&mut self, _extent: Extent2d) {}
+
+    /// Draws into acquired swapchain image.
+    /// Last submission must wait for and signal semaphores
+    /// provided by the frame and leave image in its final layout.
     fn draw(
         &mut self,
-        target: Image,
-        target_wait: &Semaphore,
-        target_signal: &Semaphore,
+        target: &SwapchainFrame,
+        camera: &Camera,
+        camera_global: &Global3,
+        camera_settings: Option<&CameraSettings>,
+        constants: &RenderConstants,
+        clock: &ClockIndex,
+        overlay: Option<&TextOverlay>,
         blases: &HashMap<Mesh, AccelerationStructure>,
         ctx: &mut Context,
         world: &mut World,
@@ -1,30 +1,87 @@
 use {
     super::Pipeline,
     crate::{
-        camera::Camera,
+        audio::AudioListener,
+        camera::{Camera, CameraSettings},
+        clocks::ClockIndex,
+        light::{Fog, SkyLight},
         renderer::{
+            accumulation::write_capture,
             pass::{
+                accumulate::{self, AccumulatePass},
                 atrous::{self, ATrousFilter},
+                audio_occlusion::{self, AudioOcclusionPass},
+                checkerboard::{self, CheckerboardPass},
                 combine::{self, CombinePass},
+                external_denoise::{self, ExternalDenoisePass},
+                global_sdf::{self, GlobalSdfPass},
+                inspect::{self, InspectPass},
+                particles::{self, ParticlesPass},
                 rt_prepass::{self, RtPrepass},
+                ssao::{self, SsaoPass},
+                ssr::{self, SsrPass},
                 Pass as _,
             },
-            AccelerationStructure, Buffer, Context, Extent2d, Fence, Image,
-            Mesh, PipelineStageFlags, Semaphore,
+            AccelerationStructure, Buffer, Context, Extent2d, ExternalDenoiser,
+            Fence, Mesh, PixelSample, RenderConstants, SwapchainAccess,
+            SwapchainFrame, TextOverlay, ViewGlobals,
         },
         scene::Global3,
     },
     bumpalo::Bump,
     eyre::Report,
     hecs::World,
-    std::collections::HashMap,
+    nalgebra as na,
+    std::{collections::HashMap, path::PathBuf},
 };
 
+/// World-space radius of ambient occlusion sampling.
+const SSAO_RADIUS: f32 = 1.0;
+
+/// Strength of ambient occlusion.
+const SSAO_INTENSITY: f32 = 1.0;
+
 pub struct PathTracePipeline {
     rt_prepass: RtPrepass,
     diffuse_filter: ATrousFilter,
     direct_filter: ATrousFilter,
+    specular_filter: ATrousFilter,
+    external_denoise: Option<ExternalDenoisePass>,
+    checkerboard: CheckerboardPass,
     combine: CombinePass,
+    ssao: SsaoPass,
+    ssr: SsrPass,
+    inspect: InspectPass,
+    accumulate: AccumulatePass,
+    audio_occlusion: AudioOcclusionPass,
+    global_sdf: GlobalSdfPass,
+    particles: ParticlesPass,
+
+    /// Pixel selected for inspection and its last read back values.
+    inspected_pixel: Option<[u32; 2]>,
+    pixel_sample: Option<PixelSample>,
+
+    /// Samples accumulated by last draw.
+    /// `None` while accumulation is disabled.
+    accumulated_samples: Option<u32>,
+
+    /// Camera that accumulated samples were taken with.
+    accumulation_view: Option<AccumulationView>,
+
+    /// File to capture accumulated frame into.
+    capture: Option<PathBuf>,
+
+    /// Capture is requested to be read back.
+    readback: bool,
+
+    /// Accumulated frame read back after request.
+    readback_texels: Option<(Extent2d, Vec<[f32; 4]>)>,
+
+    /// Camera transform used in previous frame.
+    prev_camera_global: Option<Global3>,
+
+    /// Extent of ray-traced images in following draws.
+    extent: Extent2d,
 
     frame: u64,
     fences: [Fence; 2],
@@ -40,14 +97,45 @@ impl PathTracePipeline {
             RtPrepass::new(extent, ctx, blue_noise_buffer_256x256x128)?;
 
         let combine = CombinePass::new(ctx)?;
+        let checkerboard = CheckerboardPass::new(ctx)?;
         let diffuse_filter = ATrousFilter::new(ctx)?;
         let direct_filter = ATrousFilter::new(ctx)?;
+        let specular_filter = ATrousFilter::new(ctx)?;
+        let ssao = SsaoPass::new(ctx)?;
+        let ssr = SsrPass::new(ctx)?;
+        let inspect = InspectPass::new(ctx)?;
+        let accumulate = AccumulatePass::new(ctx)?;
+        let audio_occlusion = AudioOcclusionPass::new(ctx)?;
+        let global_sdf = GlobalSdfPass::new(ctx)?;
+        let particles = ParticlesPass::new(ctx, global_sdf.layout())?;
 
         Ok(PathTracePipeline {
             rt_prepass,
             diffuse_filter,
             direct_filter,
+            specular_filter,
+            external_denoise: None,
+            checkerboard,
             combine,
+            ssao,
+            ssr,
+            inspect,
+            accumulate,
+            audio_occlusion,
+            global_sdf,
+            particles,
+
+            inspected_pixel: None,
+            pixel_sample: None,
+
+            accumulated_samples: None,
+            accumulation_view: None,
+            capture: None,
+            readback: false,
+            readback_texels: None,
+
+            prev_camera_global: None,
+            extent,
 
             frame: 0,
             fences: [ctx.create_fence()?, ctx.create_fence()?],
@@ -56,38 +144,206 @@ impl PathTracePipeline {
 }
 
 impl Pipeline for PathTracePipeline {
+    fn is_ready(&mut self, ctx: &mut Context) -> Result<bool, Report> {
+        self.rt_prepass.is_ready(ctx)
+    }
+
+    fn inspect_pixel(
+        &mut self,
+        pixel: Option<[u32; 2]>,
+    ) -> Option<PixelSample> {
+        if pixel.is_none() {
+            self.pixel_sample = None;
+        }
+        self.inspected_pixel = pixel;
+        self.pixel_sample
+    }
+
+    fn accumulation(&mut self, capture: Option<PathBuf>) -> Option<u32> {
+        if capture.is_some() {
+            self.capture = capture;
+        }
+        self.accumulated_samples
+    }
+
+    fn request_readback(&mut self) {
+        self.readback = true;
+    }
+
+    fn take_readback(&mut self) -> Option<(Extent2d, Vec<[f32; 4]>)> {
+        self.readback_texels.take()
+    }
+
+    fn set_extent(&mut self, extent: Extent2d) {
+        self.extent = extent;
+    }
+
     fn draw(
         &mut self,
-        target: Image,
-        target_wait: &Semaphore,
-        target_signal: &Semaphore,
+        target: &SwapchainFrame,
+        camera: &Camera,
+        camera_global: &Global3,
+        camera_settings: Option<&CameraSettings>,
+        constants: &RenderConstants,
+        clock: &ClockIndex,
+        overlay: Option<&TextOverlay>,
         blases: &HashMap<Mesh, AccelerationStructure>,
         ctx: &mut Context,
         world: &mut World,
         bump: &Bump,
     ) -> Result<(), Report> {
-        let mut cameras = world.query::<(&Camera, &Global3)>();
-        let camera = if let Some((_, camera)) = cameras.iter().next() {
-            camera
+        let camera_global = *camera_global;
+        let camera_projection = camera.projection();
+
+        let lens_radius = match camera_settings {
+            Some(settings) if constants.depth_of_field => {
+                settings.lens_radius()
+            }
+            _ => 0.0,
+        };
+
+        // Camera moves during the part of frame interval
+        // when shutter is open.
+        let motion_blur = match camera_settings {
+            Some(settings) if constants.motion_blur => {
+                let delta = clock.delta.as_secs_f32();
+                if delta > 0.0 {
+                    (settings.shutter / delta).min(1.0)
+                } else {
+                    0.0
+                }
+            }
+            _ => 0.0,
+        };
+
+        let prev_camera_global = self.prev_camera_global.replace(camera_global);
+
+        let focus_distance =
+            camera_settings.map_or(1.0, |settings| settings.focus_distance);
+
+        let exposure = camera_settings.map_or(1.0, CameraSettings::exposure)
+            * constants.exposure.exp2();
+
+        // Any change of camera restarts accumulation.
+        let view = AccumulationView {
+            camera_global,
+            camera_projection: camera_projection.to_homogeneous(),
+            lens_radius,
+            focus_distance,
+        };
+        let view_changed = self.accumulation_view.replace(view) != Some(view);
+
+        // Noise pattern changes only while accumulating.
+        // Adaptive sampling traces more paths in noisy tiles.
+        let (sample, max_samples) = if constants.accumulate {
+            (self.accumulate.samples(), constants.adaptive_samples)
         } else {
-            tracing::warn!("No camera found");
-            return Ok(());
+            (0, 1)
         };
-        let camera_global = *camera.1;
-        let camera_projection = camera.0.projection();
-        drop(cameras);
 
         if self.frame > 1 {
             let fence = &self.fences[(self.frame % 2) as usize];
-            ctx.wait_fences(&[fence], true);
-            ctx.reset_fences(&[fence])
+            ctx.wait_fences(&[fence], true)?;
+            ctx.reset_fences(&[fence])?;
         }
 
+        let view_matrix = camera_global.to_homogeneous();
+        let prev_view_matrix =
+            prev_camera_global.unwrap_or(camera_global).to_homogeneous();
+        let view_uniform = ctx.write_view_globals(&ViewGlobals {
+            view: view_matrix,
+            iview: view_matrix
+                .try_inverse()
+                .unwrap_or_else(na::Matrix4::identity),
+            proj: camera_projection.to_homogeneous(),
+            iproj: camera_projection.inverse().to_homogeneous(),
+            prev_view: prev_view_matrix,
+            prev_iview: prev_view_matrix
+                .try_inverse()
+                .unwrap_or_else(na::Matrix4::identity),
+            extent: [self.extent.width, self.extent.height],
+            frame: self.frame as u32,
+            time: (clock.step - clock.start).as_secs_f32(),
+            jitter: [0.0; 2],
+            _pad: [0.0; 2],
+        })?;
+
+        ctx.begin_pass("path_trace")?;
+
+        ctx.begin_pass("rt_prepass")?;
+        self.rt_prepass.resize(self.extent, ctx)?;
         let rt_prepass_output = self.rt_prepass.draw(
             rt_prepass::Input {
                 camera_global,
                 camera_projection,
+                lens_radius,
+                focus_distance,
+                prev_camera_global: prev_camera_global.unwrap_or(camera_global),
+                motion_blur,
                 blases,
+                sample,
+                max_samples,
+                checkerboard: constants.checkerboard && !constants.accumulate,
+            },
+            self.frame,
+            &[],
+            &[],
+            None,
+            ctx,
+            world,
+            bump,
+        )?;
+        ctx.end_pass()?;
+
+        // Sounds are heard by listener or by the camera without one.
+        let listener = world
+            .query::<(&AudioListener, &Global3)>()
+            .iter()
+            .next()
+            .map_or(camera_global, |(_, (_, global))| *global);
+
+        ctx.begin_pass("audio_occlusion")?;
+        self.audio_occlusion.draw(
+            audio_occlusion::Input {
+                tlas: rt_prepass_output.tlas.clone(),
+                listener: listener.iso.translation.vector.into(),
+            },
+            self.frame,
+            &[],
+            &[],
+            None,
+            ctx,
+            world,
+            bump,
+        )?;
+        ctx.end_pass()?;
+
+        if rt_prepass_output.checkerboard != 0 {
+            ctx.begin_pass("checkerboard")?;
+            self.checkerboard.draw(
+                checkerboard::Input {
+                    albedo: rt_prepass_output.albedo.clone(),
+                    normal_depth: rt_prepass_output.normal_depth.clone(),
+                    emissive: rt_prepass_output.emissive.clone(),
+                    direct: rt_prepass_output.direct.clone(),
+                    diffuse: rt_prepass_output.diffuse.clone(),
+                    checkerboard: rt_prepass_output.checkerboard,
+                },
+                self.frame,
+                &[],
+                &[],
+                None,
+                ctx,
+                world,
+                bump,
+            )?;
+            ctx.end_pass()?;
+        }
+
+        ctx.begin_pass("global_sdf")?;
+        let sdf_output = self.global_sdf.draw(
+            global_sdf::Input {
+                camera: camera_global.iso.translation.vector.into(),
             },
             self.frame,
             &[],
@@ -97,61 +353,335 @@ impl Pipeline for PathTracePipeline {
             world,
             bump,
         )?;
+        ctx.end_pass()?;
 
-        // let diffuse_filter_output = self.diffuse_filter.draw(
-        //     atrous::Input {
-        //         normal_depth: rt_prepass_output.normal_depth.clone(),
-        //         unfiltered: rt_prepass_output.diffuse,
-        //     },
-        //     self.frame,
-        //     &[],
-        //     &[],
-        //     None,
-        //     ctx,
-        //     world,
-        //     bump,
-        // )?;
-
-        // let direct_filter_output = self.direct_filter.draw(
-        //     atrous::Input {
-        //         normal_depth: rt_prepass_output.normal_depth.clone(),
-        //         unfiltered: rt_prepass_output.direct,
-        //     },
-        //     self.frame,
-        //     &[],
-        //     &[],
-        //     None,
-        //     ctx,
-        //     world,
-        //     bump,
-        // )?;
+        ctx.begin_pass("particles")?;
+        self.particles.draw(
+            particles::Input {
+                normal_depth: rt_prepass_output.normal_depth.clone(),
+                emissive: rt_prepass_output.emissive.clone(),
+                view: view_uniform.clone(),
+                sdf: sdf_output.set,
+                delta: clock.sim_delta.as_secs_f32(),
+            },
+            self.frame,
+            &[],
+            &[],
+            None,
+            ctx,
+            world,
+            bump,
+        )?;
+        ctx.end_pass()?;
+
+        if let Some(pixel) = self.inspected_pixel {
+            ctx.begin_pass("inspect")?;
+            let inspect_output = self.inspect.draw(
+                inspect::Input {
+                    pixel,
+                    albedo: rt_prepass_output.albedo.clone(),
+                    normal_depth: rt_prepass_output.normal_depth.clone(),
+                    emissive: rt_prepass_output.emissive.clone(),
+                    direct: rt_prepass_output.direct.clone(),
+                    diffuse: rt_prepass_output.diffuse.clone(),
+                },
+                self.frame,
+                &[],
+                &[],
+                None,
+                ctx,
+                world,
+                bump,
+            )?;
+            ctx.end_pass()?;
+
+            if inspect_output.sample.is_some() {
+                self.pixel_sample = inspect_output.sample;
+            }
+        }
+
+        let accumulated = if constants.accumulate {
+            let capture = self.capture.is_some() || self.readback;
+
+            ctx.begin_pass("accumulate")?;
+            let output = self.accumulate.draw(
+                accumulate::Input {
+                    albedo: rt_prepass_output.albedo.clone(),
+                    emissive: rt_prepass_output.emissive.clone(),
+                    direct: rt_prepass_output.direct.clone(),
+                    diffuse: rt_prepass_output.diffuse.clone(),
+                    sample_map: rt_prepass_output.sample_map.clone(),
+                    max_samples,
+                    error_threshold: constants.adaptive_error,
+                    reset: view_changed
+                        || rt_prepass_output.scene_changed
+                        || self.accumulated_samples.is_none(),
+                    capture,
+                    exposure,
+                },
+                self.frame,
+                &[],
+                &[],
+                None,
+                ctx,
+                world,
+                bump,
+            )?;
+            ctx.end_pass()?;
+
+            self.accumulated_samples = Some(output.samples);
+            Some(output)
+        } else {
+            self.accumulated_samples = None;
+            self.capture = None;
+            self.readback = false;
+            None
+        };
+
+        let (albedo, emissive) = match &accumulated {
+            Some(accumulated) => {
+                (accumulated.albedo.clone(), accumulated.emissive.clone())
+            }
+            None => (
+                rt_prepass_output.albedo.clone(),
+                rt_prepass_output.emissive.clone(),
+            ),
+        };
+
+        let [direct_config, diffuse_config, specular_config] =
+            constants.denoise_filters();
+
+        let external_denoise = constants.filter_enabled
+            && constants.external_denoise
+            && world.query::<&ExternalDenoiser>().iter().next().is_some();
+
+        let (direct, diffuse) = if let Some(accumulated) = &accumulated {
+            // Accumulated samples converge without denoising.
+            (accumulated.direct.clone(), accumulated.diffuse.clone())
+        } else if external_denoise {
+            // Created on first use as most sessions never need it.
+            if self.external_denoise.is_none() {
+                self.external_denoise = Some(ExternalDenoisePass::new(ctx)?);
+            }
+            let pass = self.external_denoise.as_mut().unwrap();
+
+            ctx.begin_pass("external_denoise")?;
+            let output = pass.draw(
+                external_denoise::Input {
+                    albedo: rt_prepass_output.albedo.clone(),
+                    normal_depth: rt_prepass_output.normal_depth.clone(),
+                    direct: rt_prepass_output.direct.clone(),
+                    diffuse: rt_prepass_output.diffuse.clone(),
+                },
+                self.frame,
+                &[],
+                &[],
+                None,
+                ctx,
+                world,
+                bump,
+            )?;
+            ctx.end_pass()?;
+            (output.direct, output.diffuse)
+        } else {
+            let direct = if constants.filter_enabled && direct_config.enabled {
+                ctx.begin_pass("direct_filter")?;
+                let output = self.direct_filter.draw(
+                    atrous::Input {
+                        normal_depth: rt_prepass_output.normal_depth.clone(),
+                        unfiltered: rt_prepass_output.direct.clone(),
+                        config: direct_config,
+                    },
+                    self.frame,
+                    &[],
+                    &[],
+                    None,
+                    ctx,
+                    world,
+                    bump,
+                )?;
+                ctx.end_pass()?;
+                output.filtered
+            } else {
+                rt_prepass_output.direct.clone()
+            };
+
+            let diffuse = if constants.filter_enabled && diffuse_config.enabled
+            {
+                ctx.begin_pass("diffuse_filter")?;
+                let output = self.diffuse_filter.draw(
+                    atrous::Input {
+                        normal_depth: rt_prepass_output.normal_depth.clone(),
+                        unfiltered: rt_prepass_output.diffuse.clone(),
+                        config: diffuse_config,
+                    },
+                    self.frame,
+                    &[],
+                    &[],
+                    None,
+                    ctx,
+                    world,
+                    bump,
+                )?;
+                ctx.end_pass()?;
+                output.filtered
+            } else {
+                rt_prepass_output.diffuse.clone()
+            };
+
+            (direct, diffuse)
+        };
+
+        let ao = if constants.ssao {
+            ctx.begin_pass("ssao")?;
+            let ssao_output = self.ssao.draw(
+                ssao::Input {
+                    normal_depth: rt_prepass_output.normal_depth.clone(),
+                    view: view_uniform.clone(),
+                    radius: SSAO_RADIUS,
+                    intensity: SSAO_INTENSITY,
+                },
+                self.frame,
+                &[],
+                &[],
+                None,
+                ctx,
+                world,
+                bump,
+            )?;
+            ctx.end_pass()?;
+            Some(ssao_output.ao)
+        } else {
+            None
+        };
+
+        let reflection = if constants.ssr {
+            let sky = world
+                .query::<&SkyLight>()
+                .iter()
+                .next()
+                .map(|(_, sky)| sky.radiance)
+                .unwrap_or_default();
+
+            ctx.begin_pass("ssr")?;
+            let ssr_output = self.ssr.draw(
+                ssr::Input {
+                    normal_depth: rt_prepass_output.normal_depth.clone(),
+                    albedo: albedo.clone(),
+                    emissive: emissive.clone(),
+                    direct: direct.clone(),
+                    diffuse: diffuse.clone(),
+                    view: view_uniform.clone(),
+                    sky,
+                },
+                self.frame,
+                &[],
+                &[],
+                None,
+                ctx,
+                world,
+                bump,
+            )?;
+            ctx.end_pass()?;
+
+            if constants.filter_enabled
+                && specular_config.enabled
+                && accumulated.is_none()
+            {
+                ctx.begin_pass("specular_filter")?;
+                let output = self.specular_filter.draw(
+                    atrous::Input {
+                        normal_depth: rt_prepass_output.normal_depth.clone(),
+                        unfiltered: ssr_output.reflection,
+                        config: specular_config,
+                    },
+                    self.frame,
+                    &[],
+                    &[],
+                    None,
+                    ctx,
+                    world,
+                    bump,
+                )?;
+                ctx.end_pass()?;
+                Some(output.filtered)
+            } else {
+                Some(ssr_output.reflection)
+            }
+        } else {
+            None
+        };
+
+        let fog = world.query::<&Fog>().iter().next().map(|(_, fog)| *fog);
 
         let fence = &self.fences[(self.frame % 2) as usize];
+        ctx.begin_pass("combine")?;
         self.combine.draw(
             combine::Input {
-                albedo: rt_prepass_output.albedo,
+                albedo,
                 normal_depth: rt_prepass_output.normal_depth,
-                emissive: rt_prepass_output.emissive,
-                // direct: direct_filter_output.filtered,
-                // diffuse: diffuse_filter_output.filtered,
-                direct: rt_prepass_output.direct,
-                diffuse: rt_prepass_output.diffuse,
-                combined: target.clone(),
+                emissive,
+                direct,
+                diffuse,
+                combined: target.image().clone(),
+                region: target.region(),
+                initial_layout: target.initial_layout(),
+                ao,
+                reflection,
+                exposure,
+                fog,
+                overlay,
             },
             self.frame,
-            &[(
-                PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
-                target_wait.clone(),
-            )],
-            std::slice::from_ref(target_signal),
+            &[target.wait(SwapchainAccess::ColorAttachment)],
+            std::slice::from_ref(target.signal()),
             Some(fence),
             ctx,
             world,
             bump,
         )?;
+        ctx.end_pass()?;
+
+        ctx.end_pass()?;
+
+        if accumulated.is_some() && (self.capture.is_some() || self.readback) {
+            // Capture is written by this frame.
+            ctx.wait_fences(&[fence], true)?;
+
+            if let Some((extent, texels)) = self.accumulate.read_capture(ctx)? {
+                if let Some(path) = self.capture.take() {
+                    match write_capture(&path, extent, &texels) {
+                        Ok(()) => tracing::info!(
+                            "Captured {} samples into {}",
+                            self.accumulate.samples(),
+                            path.display()
+                        ),
+                        Err(err) => tracing::error!(
+                            "Failed to write capture {}: {:#}",
+                            path.display(),
+                            err
+                        ),
+                    }
+                }
+
+                if self.readback {
+                    self.readback = false;
+                    self.readback_texels = Some((extent, texels));
+                }
+            }
+        }
 
         self.frame += 1;
 
         Ok(())
     }
 }
+
+/// Camera parameters that accumulated samples depend on.
+#[derive(Clone, Copy, PartialEq)]
+struct AccumulationView {
+    camera_global: Global3,
+    camera_projection: na::Matrix4<f32>,
+    lens_radius: f32,
+    focus_distance: f32,
+}
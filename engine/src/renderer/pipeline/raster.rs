@@ -7,7 +7,7 @@ use {
                 Pass as _,
             },
             AccelerationStructure, Buffer, Context, Extent2d, Fence, Image,
-            Mesh, PipelineStageFlags, Semaphore,
+            Mesh, PipelineStageFlags, SwapchainFrame,
         },
         scene::Global3,
     },
@@ -30,9 +30,7 @@ pub struct RasterPipeline {
 impl Pipeline for RasterPipeline {
     fn draw(
         &mut self,
-        target: Image,
-        target_wait: &Semaphore,
-        target_signal: &Semaphore,
+        target: &SwapchainFrame,
         blases: &HashMap<Mesh, AccelerationStructure>,
         ctx: &mut Context,
         world: &mut World,
@@ -2,14 +2,16 @@
 use {
     super::Pipeline,
     crate::{
-        camera::Camera,
+        camera::{Camera, CameraSettings},
+        clocks::ClockIndex,
         renderer::{
             pass::{
                 ray_probe::{self, RayProbe},
                 Pass as _,
             },
-            AccelerationStructure, Buffer, Context, Extent2d, Fence, Image,
-            Mesh, PipelineStageFlags, Semaphore,
+            AccelerationStructure, Buffer, Context, Extent2d, Fence, Mesh,
+            PipelineStageFlags, RenderConstants, SwapchainAccess,
+            SwapchainFrame, TextOverlay,
         },
         scene::Global3,
     },
@@ -17,7 +19,7 @@ use {
     eyre::Report,
     hecs::World,
     illume::*,
-    std::collections::HashMap,
+    std::{collections::HashMap, convert::TryFrom as _},
 };
 
 pub struct RayProbePipeline {
@@ -46,34 +48,31 @@ impl RayProbePipeline {
 impl Pipeline for RayProbePipeline {
     fn draw(
         &mut self,
-        target: Image,
-        target_wait: &Semaphore,
-        target_signal: &Semaphore,
+        target: &SwapchainFrame,
+        camera: &Camera,
+        camera_global: &Global3,
+        _camera_settings: Option<&CameraSettings>,
+        _constants: &RenderConstants,
+        _clock: &ClockIndex,
+        _overlay: Option<&TextOverlay>,
         blases: &HashMap<Mesh, AccelerationStructure>,
         ctx: &mut Context,
         world: &mut World,
         bump: &Bump,
     ) -> Result<(), Report> {
-        let mut cameras = world.query::<(&Camera, &Global3)>();
-        let camera = if let Some((_, camera)) = cameras.iter().next() {
-            camera
-        } else {
-            tracing::warn!("No camera found");
-            return Ok(());
-        };
-        let camera_global = *camera.1;
-        let camera_projection = camera.0.projection();
-        drop(cameras);
+        let camera_global = *camera_global;
+        let camera_projection = camera.projection();
 
         if self.frame > 1 {
             let fence = &self.fences[(self.frame % 2) as usize];
-            ctx.wait_fences(&[fence], true);
-            ctx.reset_fences(&[fence])
+            ctx.wait_fences(&[fence], true)?;
+            ctx.reset_fences(&[fence])?;
         }
 
+        ctx.begin_pass("ray_probe")?;
         let ray_probe_output = self.ray_probe.draw(
             ray_probe::Input {
-                extent: target.info().extent.into_2d(),
+                extent: target.extent(),
                 camera_global,
                 camera_projection,
                 blases,
@@ -86,8 +85,10 @@ impl Pipeline for RayProbePipeline {
             world,
             bump,
         )?;
+        ctx.end_pass()?;
 
         let rendered = ray_probe_output.output_image;
+        let region = target.region();
         let blit = ImageBlit {
             src_subresource: ImageSubresourceLayers::all_layers(
                 rendered.info(),
@@ -98,15 +99,24 @@ impl Pipeline for RayProbePipeline {
                 Offset3d::from_extent(rendered.info().extent.into_3d())?,
             ],
             dst_subresource: ImageSubresourceLayers::all_layers(
-                target.info(),
+                target.image().info(),
                 0,
             ),
             dst_offsets: [
-                Offset3d::ZERO,
-                Offset3d::from_extent(target.info().extent.into_3d())?,
+                Offset3d {
+                    x: region.offset.x,
+                    y: region.offset.y,
+                    z: 0,
+                },
+                Offset3d {
+                    x: region.offset.x + i32::try_from(region.extent.width)?,
+                    y: region.offset.y + i32::try_from(region.extent.height)?,
+                    z: 1,
+                },
             ],
         };
 
+        ctx.begin_pass("blit")?;
         let mut encoder = ctx.queue.create_encoder()?;
 
         let images = [
@@ -115,11 +125,7 @@ impl Pipeline for RayProbePipeline {
                 Layout::General..Layout::TransferSrcOptimal,
             )
             .into(),
-            ImageLayoutTransition::initialize_whole(
-                &target,
-                Layout::TransferDstOptimal,
-            )
-            .into(),
+            target.initialize(Layout::TransferDstOptimal).into(),
         ];
 
         encoder.image_barriers(
@@ -132,17 +138,13 @@ impl Pipeline for RayProbePipeline {
         encoder.blit_image(
             &rendered,
             Layout::TransferSrcOptimal,
-            &target,
+            target.image(),
             Layout::TransferDstOptimal,
             std::slice::from_ref(&blit),
             Filter::Nearest,
         );
 
-        let images = [ImageLayoutTransition::transition_whole(
-            &target,
-            Layout::TransferDstOptimal..Layout::Present,
-        )
-        .into()];
+        let images = [target.finalize(Layout::TransferDstOptimal).into()];
 
         encoder.image_barriers(
             PipelineStageFlags::TRANSFER,
@@ -150,13 +152,16 @@ impl Pipeline for RayProbePipeline {
             &images,
         );
 
+        ctx.record_barriers(encoder.barrier_count());
+
         let fence = &self.fences[(self.frame % 2) as usize];
         ctx.queue.submit(
-            &[(PipelineStageFlags::TRANSFER, target_wait.clone())],
-            encoder.finish(),
-            std::slice::from_ref(target_signal),
+            &[target.wait(SwapchainAccess::TransferDst)],
+            encoder.finish()?,
+            std::slice::from_ref(target.signal()),
             Some(fence),
-        );
+        )?;
+        ctx.end_pass()?;
 
         self.frame += 1;
 
@@ -0,0 +1,426 @@
+//! Per-pass frame statistics.
+//!
+//! Passes report barriers and transient resources to `Context`
+//! and are timed on GPU with timestamp queries.
+//! Statistics are shown by `FrameGraphOverlay` when `r.debug_overlay` is set.
+
+use {
+    super::TextOverlay,
+    crate::{
+        cvar::CVars,
+        engine::{System, SystemContext},
+    },
+    eyre::Report,
+    illume::{
+        Device, FormatDescription, ImageInfo, PipelineStageFlags, QueryPool,
+        QueryPoolInfo, Queue,
+    },
+    std::time::{Duration, Instant},
+};
+
+/// Maximum number of passes profiled in one frame.
+const MAX_PROFILED_PASSES: u32 = 32;
+
+/// Number of frames with GPU timings in flight.
+const PROFILED_FRAMES: usize = 3;
+
+/// Statistics of single pass.
+#[derive(Clone, Debug)]
+pub struct PassStats {
+    pub name: &'static str,
+
+    /// Nesting level of the pass.
+    pub depth: u32,
+
+    /// Time spent encoding the pass.
+    pub cpu_time: Duration,
+
+    /// Time between completion of previous work and this pass on GPU.
+    /// `None` if timestamps were not available.
+    pub gpu_time: Option<Duration>,
+
+    /// Number of pipeline barriers recorded by the pass.
+    pub barriers: u32,
+
+    /// Size of images and buffers owned by the pass.
+    pub transient_bytes: u64,
+}
+
+/// Statistics of last profiled frame.
+/// Published as resource while profiling is enabled.
+#[derive(Clone, Debug, Default)]
+pub struct FrameGraphStats {
+    pub frame: u64,
+
+    /// Passes in order of execution.
+    /// Nested passes follow their parent.
+    pub passes: Vec<PassStats>,
+}
+
+impl FrameGraphStats {
+    /// Returns total GPU time of top-level passes.
+    pub fn gpu_time(&self) -> Duration {
+        self.passes
+            .iter()
+            .filter(|pass| pass.depth == 0)
+            .filter_map(|pass| pass.gpu_time)
+            .sum()
+    }
+
+    /// Returns total size of transient resources.
+    pub fn transient_bytes(&self) -> u64 {
+        self.passes.iter().map(|pass| pass.transient_bytes).sum()
+    }
+}
+
+#[derive(Default)]
+struct ProfiledFrame {
+    frame: u64,
+    passes: Vec<PassStats>,
+
+    /// Timestamps are not read yet.
+    pending: bool,
+}
+
+/// Records pass statistics and GPU timestamps.
+pub(super) struct PassProfiler {
+    enabled: bool,
+    pool: Option<QueryPool>,
+    frames: Vec<ProfiledFrame>,
+
+    /// Index of frame being recorded.
+    current: Option<usize>,
+    frame: u64,
+
+    /// Open passes with their start time.
+    /// Passes above query limit are not recorded.
+    open: Vec<Option<(usize, Instant)>>,
+
+    completed: Option<FrameGraphStats>,
+}
+
+impl PassProfiler {
+    pub(super) fn new() -> Self {
+        PassProfiler {
+            enabled: false,
+            pool: None,
+            frames: (0..PROFILED_FRAMES).map(|_| Default::default()).collect(),
+            current: None,
+            frame: 0,
+            open: Vec::new(),
+            completed: None,
+        }
+    }
+
+    pub(super) fn begin_frame(
+        &mut self,
+        enabled: bool,
+        device: &Device,
+        queue: &mut Queue,
+    ) -> Result<(), Report> {
+        if self.enabled != enabled {
+            self.enabled = enabled;
+            for frame in &mut self.frames {
+                frame.pending = false;
+            }
+        }
+
+        if !enabled {
+            return Ok(());
+        }
+
+        let pool = match &self.pool {
+            Some(pool) => pool.clone(),
+            None => {
+                let pool = device.create_query_pool(QueryPoolInfo {
+                    count: PROFILED_FRAMES as u32 * MAX_PROFILED_PASSES * 2,
+                })?;
+                self.pool.get_or_insert(pool).clone()
+            }
+        };
+
+        self.collect(device, &pool)?;
+
+        let index = (self.frame % PROFILED_FRAMES as u64) as usize;
+        let slot = &mut self.frames[index];
+        if slot.pending {
+            tracing::debug!("Timestamps of frame {} were dropped", slot.frame);
+        }
+        slot.frame = self.frame;
+        slot.passes.clear();
+        slot.pending = true;
+
+        let first = index as u32 * MAX_PROFILED_PASSES * 2;
+        let mut encoder = queue.create_encoder()?;
+        encoder.reset_query_pool(&pool, first..first + MAX_PROFILED_PASSES * 2);
+        queue.submit_no_semaphores(encoder.finish()?, None)?;
+
+        self.current = Some(index);
+        self.frame += 1;
+        Ok(())
+    }
+
+    pub(super) fn end_frame(&mut self) -> Option<FrameGraphStats> {
+        if !self.open.is_empty() {
+            tracing::warn!(
+                "{} profiled passes were not ended",
+                self.open.len()
+            );
+            self.open.clear();
+        }
+
+        self.current = None;
+        self.completed.take()
+    }
+
+    pub(super) fn begin_pass(
+        &mut self,
+        name: &'static str,
+        queue: &mut Queue,
+    ) -> Result<(), Report> {
+        let index = match self.current {
+            Some(index) => index,
+            None => return Ok(()),
+        };
+
+        let slot = &mut self.frames[index];
+        if slot.passes.len() >= MAX_PROFILED_PASSES as usize {
+            self.open.push(None);
+            return Ok(());
+        }
+
+        let pass = slot.passes.len();
+        slot.passes.push(PassStats {
+            name,
+            depth: self.open.len() as u32,
+            cpu_time: Duration::default(),
+            gpu_time: None,
+            barriers: 0,
+            transient_bytes: 0,
+        });
+
+        self.write_timestamp(queue, index, pass as u32 * 2)?;
+        self.open.push(Some((pass, Instant::now())));
+        Ok(())
+    }
+
+    pub(super) fn end_pass(&mut self, queue: &mut Queue) -> Result<(), Report> {
+        let index = match self.current {
+            Some(index) => index,
+            None => return Ok(()),
+        };
+
+        match self.open.pop() {
+            Some(Some((pass, start))) => {
+                self.frames[index].passes[pass].cpu_time = start.elapsed();
+                self.write_timestamp(queue, index, pass as u32 * 2 + 1)
+            }
+            Some(None) => Ok(()),
+            None => {
+                tracing::warn!("Profiled pass ended without beginning");
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns innermost open pass.
+    pub(super) fn current_pass(&mut self) -> Option<&mut PassStats> {
+        let index = self.current?;
+        let (pass, _) = (*self.open.last()?)?;
+        Some(&mut self.frames[index].passes[pass])
+    }
+
+    fn write_timestamp(
+        &self,
+        queue: &mut Queue,
+        index: usize,
+        query: u32,
+    ) -> Result<(), Report> {
+        let pool = self.pool.as_ref().expect("Profiling is enabled");
+
+        // Timestamps are submitted separately, so passes don't need
+        // to be aware of profiling. Submission order is preserved.
+        let mut encoder = queue.create_encoder()?;
+        encoder.write_timestamp(
+            pool,
+            index as u32 * MAX_PROFILED_PASSES * 2 + query,
+            PipelineStageFlags::BOTTOM_OF_PIPE,
+        );
+        queue.submit_no_semaphores(encoder.finish()?, None)?;
+        Ok(())
+    }
+
+    /// Reads timestamps of finished frames.
+    fn collect(
+        &mut self,
+        device: &Device,
+        pool: &QueryPool,
+    ) -> Result<(), Report> {
+        let period = f64::from(device.timestamp_period());
+        let mut timestamps = [0; MAX_PROFILED_PASSES as usize * 2];
+
+        for (index, slot) in self.frames.iter_mut().enumerate() {
+            if !slot.pending {
+                continue;
+            }
+
+            let count = slot.passes.len() * 2;
+            let first = index as u32 * MAX_PROFILED_PASSES * 2;
+            if count > 0
+                && !device.get_timestamps(
+                    pool,
+                    first,
+                    &mut timestamps[..count],
+                )?
+            {
+                continue;
+            }
+
+            slot.pending = false;
+
+            for (pass, range) in
+                slot.passes.iter_mut().zip(timestamps.chunks(2))
+            {
+                let ticks = range[1].saturating_sub(range[0]);
+                pass.gpu_time =
+                    Some(Duration::from_nanos((ticks as f64 * period) as u64));
+            }
+
+            let newer = self
+                .completed
+                .as_ref()
+                .map_or(true, |stats| stats.frame < slot.frame);
+
+            if newer {
+                self.completed = Some(FrameGraphStats {
+                    frame: slot.frame,
+                    passes: slot.passes.clone(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns size of image memory.
+pub fn image_size(info: &ImageInfo) -> u64 {
+    let bits = match info.format.description() {
+        FormatDescription::R(repr)
+        | FormatDescription::Depth(repr)
+        | FormatDescription::Stencil(repr) => repr.bits as u64,
+        FormatDescription::RG(repr) => repr.bits as u64 * 2,
+        FormatDescription::RGB(repr) | FormatDescription::BGR(repr) => {
+            repr.bits as u64 * 3
+        }
+        FormatDescription::RGBA(repr) | FormatDescription::BGRA(repr) => {
+            repr.bits as u64 * 4
+        }
+        FormatDescription::DepthStencil { depth, stencil } => {
+            depth.bits as u64 + stencil.bits as u64
+        }
+    };
+
+    let extent = info.extent.into_3d();
+    let mut texels = 0;
+    for level in 0..info.levels {
+        texels += u64::from((extent.width >> level).max(1))
+            * u64::from((extent.height >> level).max(1))
+            * u64::from((extent.depth >> level).max(1));
+    }
+
+    texels * u64::from(info.layers) * bits / 8
+}
+
+/// System that prints `FrameGraphStats` into `TextOverlay`
+/// while `r.debug_overlay` is set.
+pub struct FrameGraphOverlay;
+
+impl System for FrameGraphOverlay {
+    fn run(&mut self, ctx: SystemContext<'_>) {
+        let enabled = ctx
+            .resources
+            .get::<CVars>()
+            .and_then(|cvars| cvars.get_bool("r.debug_overlay"))
+            .unwrap_or(false);
+
+        if !enabled {
+            return;
+        }
+
+        let stats = match ctx.resources.get::<FrameGraphStats>() {
+            Some(stats) => stats,
+            None => return,
+        };
+
+        let mut lines = Vec::with_capacity(stats.passes.len() + 2);
+        lines.push((
+            format!(
+                "{:<24}{:>9}{:>9}{:>5}{:>10}",
+                "pass", "gpu ms", "cpu ms", "bar", "transient"
+            ),
+            [255, 255, 128],
+        ));
+
+        for pass in &stats.passes {
+            let name = format!(
+                "{:indent$}{}",
+                "",
+                pass.name,
+                indent = pass.depth as usize * 2
+            );
+
+            let gpu = match pass.gpu_time {
+                Some(time) => format!("{:.3}", time.as_secs_f64() * 1000.0),
+                None => "-".to_owned(),
+            };
+
+            lines.push((
+                format!(
+                    "{:<24}{:>9}{:>9.3}{:>5}{:>10}",
+                    name,
+                    gpu,
+                    pass.cpu_time.as_secs_f64() * 1000.0,
+                    pass.barriers,
+                    format_bytes(pass.transient_bytes),
+                ),
+                [255, 255, 255],
+            ));
+        }
+
+        lines.push((
+            format!(
+                "frame {:<18}{:>9.3}{:>9}{:>5}{:>10}",
+                stats.frame,
+                stats.gpu_time().as_secs_f64() * 1000.0,
+                "",
+                "",
+                format_bytes(stats.transient_bytes()),
+            ),
+            [255, 255, 128],
+        ));
+
+        let overlay = ctx
+            .resources
+            .entry::<TextOverlay>()
+            .or_insert_with(TextOverlay::new);
+
+        // Drawn at the bottom, below the console.
+        let rows = lines.len() as u32;
+        let top = overlay.rows().saturating_sub(rows);
+        overlay.fill(0, top, 57, rows);
+        for (row, (line, color)) in (top..).zip(&lines) {
+            overlay.print(0, row, line, *color);
+        }
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    if bytes >= 1 << 20 {
+        format!("{:.1}M", bytes as f64 / (1 << 20) as f64)
+    } else if bytes >= 1 << 10 {
+        format!("{:.1}K", bytes as f64 / (1 << 10) as f64)
+    } else {
+        format!("{}B", bytes)
+    }
+}
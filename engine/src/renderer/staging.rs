@@ -0,0 +1,212 @@
+use illume::{
+    Buffer, BufferInfo, BufferUsage, Device, Fence, MapError, MappableBuffer,
+    MemoryUsage, OutOfMemory, WaitError,
+};
+
+/// Default size of staging chunk.
+/// Larger uploads get dedicated chunks that are not reused.
+pub const STAGING_CHUNK_SIZE: u64 = 4 << 20;
+
+/// Alignment of uploaded data within chunk.
+/// Satisfies buffer-to-image copy requirements for all texel formats.
+const STAGING_ALIGN: u64 = 16;
+
+/// Region of staging buffer that holds uploaded data
+/// until it is copied by the device.
+#[derive(Clone, Debug)]
+pub struct StagingRegion {
+    pub buffer: Buffer,
+    pub offset: u64,
+    pub size: u64,
+}
+
+/// Set of host-visible buffers reused for data uploads across frames.
+///
+/// Data is written into chunks sequentially.
+/// After copy commands are submitted chunks are kept
+/// until associated fence is signalled and then recycled.
+pub struct StagingBelt {
+    chunk_size: u64,
+    active: Vec<Chunk>,
+    in_flight: Vec<InFlight>,
+    free: Vec<Chunk>,
+    fences: Vec<Fence>,
+}
+
+/// Chunks used by submitted copies.
+struct InFlight {
+    fence: Fence,
+    chunks: Vec<Chunk>,
+
+    /// Fence was taken from the belt and is returned to it when signalled.
+    owned: bool,
+}
+
+struct Chunk {
+    buffer: MappableBuffer,
+    offset: u64,
+}
+
+impl Chunk {
+    fn size(&self) -> u64 {
+        self.buffer.info().size
+    }
+
+    fn aligned_offset(&self) -> u64 {
+        (self.offset + STAGING_ALIGN - 1) & !(STAGING_ALIGN - 1)
+    }
+}
+
+impl StagingBelt {
+    pub fn new(chunk_size: u64) -> Self {
+        StagingBelt {
+            chunk_size,
+            active: Vec::new(),
+            in_flight: Vec::new(),
+            free: Vec::new(),
+            fences: Vec::new(),
+        }
+    }
+
+    /// Writes data into staging memory.
+    /// Returned region stays valid until fence returned from
+    /// next `finish` call is signalled.
+    pub fn upload(
+        &mut self,
+        device: &Device,
+        data: &[u8],
+    ) -> Result<StagingRegion, OutOfMemory> {
+        let size = data.len() as u64;
+
+        let index = match self
+            .active
+            .iter()
+            .position(|chunk| chunk.aligned_offset() + size <= chunk.size())
+        {
+            Some(index) => index,
+            None => {
+                let chunk = self.allocate(device, size)?;
+                self.active.push(chunk);
+                self.active.len() - 1
+            }
+        };
+
+        let chunk = &mut self.active[index];
+        let offset = chunk.aligned_offset();
+
+        // Chunks are allocated host-visible and never left mapped,
+        // so mapping may only fail when host address space is exhausted.
+        match device.write_buffer(&mut chunk.buffer, offset, data) {
+            Ok(()) => {}
+            Err(MapError::OutOfMemory { source }) => return Err(source),
+            Err(err) => {
+                tracing::error!("Failed to write staging buffer: {}", err);
+                return Err(OutOfMemory);
+            }
+        }
+
+        chunk.offset = offset + size;
+
+        Ok(StagingRegion {
+            buffer: chunk.buffer.share(),
+            offset,
+            size,
+        })
+    }
+
+    /// Returns `true` if nothing was uploaded since last `finish` call.
+    pub fn is_empty(&self) -> bool {
+        self.active.is_empty()
+    }
+
+    /// Marks all chunks written since last call as in use by the device.
+    /// Returned fence must be passed to the submission
+    /// that performs copies from those chunks.
+    pub fn finish(&mut self, device: &Device) -> Result<Fence, OutOfMemory> {
+        let fence = match self.fences.pop() {
+            Some(fence) => fence,
+            None => device.create_fence()?,
+        };
+
+        let chunks = std::mem::take(&mut self.active);
+        self.in_flight.push(InFlight {
+            fence: fence.clone(),
+            chunks,
+            owned: true,
+        });
+        Ok(fence)
+    }
+
+    /// Marks all chunks written since last call as in use by the device
+    /// until submission signals the fence.
+    /// Fence stays owned by caller, belt never resets it.
+    pub fn finish_with(&mut self, fence: &Fence) {
+        let chunks = std::mem::take(&mut self.active);
+        self.in_flight.push(InFlight {
+            fence: fence.clone(),
+            chunks,
+            owned: false,
+        });
+    }
+
+    /// Recycles chunks used by finished submissions.
+    pub fn recall(&mut self, device: &Device) -> Result<(), WaitError> {
+        let chunk_size = self.chunk_size;
+        let mut index = 0;
+
+        while index < self.in_flight.len() {
+            if !device.is_fence_signalled(&self.in_flight[index].fence)? {
+                index += 1;
+                continue;
+            }
+
+            let InFlight {
+                fence,
+                chunks,
+                owned,
+            } = self.in_flight.swap_remove(index);
+
+            if owned {
+                device.reset_fences(&[&fence])?;
+                self.fences.push(fence);
+            }
+
+            // Dedicated chunks for large uploads are released.
+            self.free.extend(
+                chunks
+                    .into_iter()
+                    .filter(|chunk| chunk.size() == chunk_size)
+                    .map(|chunk| Chunk {
+                        buffer: chunk.buffer,
+                        offset: 0,
+                    }),
+            );
+        }
+
+        Ok(())
+    }
+
+    fn allocate(
+        &mut self,
+        device: &Device,
+        size: u64,
+    ) -> Result<Chunk, OutOfMemory> {
+        if size <= self.chunk_size {
+            if let Some(chunk) = self.free.pop() {
+                return Ok(chunk);
+            }
+        }
+
+        let buffer = device.create_mappable_buffer(
+            BufferInfo {
+                align: STAGING_ALIGN - 1,
+                size: size.max(self.chunk_size),
+                usage: BufferUsage::TRANSFER_SRC,
+                capture_address: None,
+            },
+            MemoryUsage::UPLOAD,
+        )?;
+
+        Ok(Chunk { buffer, offset: 0 })
+    }
+}
@@ -0,0 +1,165 @@
+use illume::{
+    Extent2d, Image, ImageLayoutTransition, ImageUsage, Layout,
+    PipelineStageFlags, Rect2d, Semaphore, SwapchainImageInfo,
+};
+
+/// Way pipeline first accesses swapchain image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SwapchainAccess {
+    /// Image is rendered to as color attachment.
+    ColorAttachment,
+
+    /// Image is written by transfer commands, e.g. blits.
+    TransferDst,
+}
+
+impl SwapchainAccess {
+    /// Returns stage that must wait for image acquisition.
+    pub fn stage(&self) -> PipelineStageFlags {
+        match self {
+            SwapchainAccess::ColorAttachment => {
+                PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+            }
+            SwapchainAccess::TransferDst => PipelineStageFlags::TRANSFER,
+        }
+    }
+
+    /// Returns usage swapchain image must support.
+    pub fn usage(&self) -> ImageUsage {
+        match self {
+            SwapchainAccess::ColorAttachment => ImageUsage::COLOR_ATTACHMENT,
+            SwapchainAccess::TransferDst => ImageUsage::TRANSFER_DST,
+        }
+    }
+}
+
+/// Swapchain image acquired for a frame
+/// together with semaphores guarding it.
+///
+/// Image is acquired with undefined content and layout
+/// and must be in `FINAL_LAYOUT` when last submission
+/// that accesses it signals present semaphore.
+///
+/// Frame may cover only a region of the image
+/// when image is split between several views.
+#[derive(Clone, Debug)]
+pub struct SwapchainFrame {
+    image: Image,
+    acquire: Semaphore,
+    present: Semaphore,
+    region: Rect2d,
+    initial_layout: Option<Layout>,
+}
+
+impl SwapchainFrame {
+    /// Layout of acquired image.
+    pub const INITIAL_LAYOUT: Option<Layout> = None;
+
+    /// Layout image must be in when presented.
+    pub const FINAL_LAYOUT: Layout = Layout::Present;
+
+    pub fn new(info: &SwapchainImageInfo) -> Self {
+        SwapchainFrame {
+            image: info.image.clone(),
+            acquire: info.wait.clone(),
+            present: info.signal.clone(),
+            region: info.image.info().extent.into_2d().into(),
+            initial_layout: Self::INITIAL_LAYOUT,
+        }
+    }
+
+    /// Returns frames that draw into regions of the image one after another.
+    ///
+    /// First frame waits for image acquisition and last one
+    /// signals presentation. Frames in between are chained
+    /// with `semaphores`, which must be one less than regions.
+    /// Only first frame gets image with undefined content,
+    /// following frames must preserve what previous ones have drawn.
+    pub fn split(
+        &self,
+        regions: &[Rect2d],
+        semaphores: &[Semaphore],
+    ) -> Vec<SwapchainFrame> {
+        assert!(!regions.is_empty(), "At least one region is required");
+        assert_eq!(regions.len(), semaphores.len() + 1);
+
+        regions
+            .iter()
+            .enumerate()
+            .map(|(index, &region)| SwapchainFrame {
+                image: self.image.clone(),
+                acquire: match index {
+                    0 => self.acquire.clone(),
+                    _ => semaphores[index - 1].clone(),
+                },
+                present: semaphores.get(index).unwrap_or(&self.present).clone(),
+                region,
+                initial_layout: match index {
+                    0 => self.initial_layout,
+                    _ => Some(Self::FINAL_LAYOUT),
+                },
+            })
+            .collect()
+    }
+
+    pub fn image(&self) -> &Image {
+        &self.image
+    }
+
+    /// Returns extent of the region frame draws into.
+    pub fn extent(&self) -> Extent2d {
+        self.region.extent
+    }
+
+    /// Returns region of the image frame draws into.
+    /// Content outside of the region must be preserved.
+    pub fn region(&self) -> Rect2d {
+        self.region
+    }
+
+    /// Returns layout of the image when frame begins.
+    /// `None` means content of the image is undefined.
+    pub fn initial_layout(&self) -> Option<Layout> {
+        self.initial_layout
+    }
+
+    /// Returns acquire semaphore with stage that must wait for it
+    /// when image is first accessed in specified way.
+    pub fn wait(
+        &self,
+        access: SwapchainAccess,
+    ) -> (PipelineStageFlags, Semaphore) {
+        debug_assert!(
+            self.image.info().usage.contains(access.usage()),
+            "Swapchain image usage {:?} does not allow {:?} access",
+            self.image.info().usage,
+            access,
+        );
+
+        (access.stage(), self.acquire.clone())
+    }
+
+    /// Returns semaphore that must be signaled by last submission
+    /// accessing the image.
+    pub fn signal(&self) -> &Semaphore {
+        &self.present
+    }
+
+    /// Returns transition of the image from initial layout
+    /// into specified layout.
+    pub fn initialize(&self, layout: Layout) -> ImageLayoutTransition<'_> {
+        ImageLayoutTransition {
+            old_layout: self.initial_layout,
+            ..ImageLayoutTransition::initialize_whole(&self.image, layout)
+        }
+    }
+
+    /// Returns transition of the image from specified layout
+    /// into layout required for presentation.
+    pub fn finalize(&self, layout: Layout) -> ImageLayoutTransition<'_> {
+        ImageLayoutTransition::transition_whole(
+            &self.image,
+            layout..Self::FINAL_LAYOUT,
+        )
+    }
+}
@@ -0,0 +1,81 @@
+use illume::{Device, Fence, OutOfMemory, Semaphore, WaitError};
+
+/// Number of frames semaphores are kept for before reuse.
+/// Matches number of frames in flight of the queue.
+const SYNC_POOL_FRAMES: usize = 2;
+
+/// Pools of fences and binary semaphores reused across frames.
+///
+/// Device keeps every created fence and semaphore in a slab,
+/// so creating new ones for each submission grows it without bound.
+pub struct SyncPool {
+    free_fences: Vec<Fence>,
+    in_flight_fences: Vec<Fence>,
+    free_semaphores: Vec<Semaphore>,
+
+    /// Semaphores handed out in recent frames.
+    used_semaphores: [Vec<Semaphore>; SYNC_POOL_FRAMES],
+    frame: usize,
+}
+
+impl SyncPool {
+    pub fn new() -> Self {
+        SyncPool {
+            free_fences: Vec::new(),
+            in_flight_fences: Vec::new(),
+            free_semaphores: Vec::new(),
+            used_semaphores: Default::default(),
+            frame: 0,
+        }
+    }
+
+    /// Returns unsignalled fence.
+    /// Fence is recycled once it is signalled.
+    pub fn fence(&mut self, device: &Device) -> Result<Fence, OutOfMemory> {
+        let fence = match self.free_fences.pop() {
+            Some(fence) => fence,
+            None => device.create_fence()?,
+        };
+
+        self.in_flight_fences.push(fence.clone());
+        Ok(fence)
+    }
+
+    /// Returns unsignalled binary semaphore.
+    /// Semaphore is recycled when frames that could use it are complete,
+    /// so it must be waited on in the same frame it is signalled.
+    pub fn semaphore(
+        &mut self,
+        device: &Device,
+    ) -> Result<Semaphore, OutOfMemory> {
+        let semaphore = match self.free_semaphores.pop() {
+            Some(semaphore) => semaphore,
+            None => device.create_semaphore()?,
+        };
+
+        self.used_semaphores[self.frame].push(semaphore.clone());
+        Ok(semaphore)
+    }
+
+    /// Recycles signalled fences and semaphores of completed frames.
+    /// Must be called after queue begins next frame.
+    pub fn begin_frame(&mut self, device: &Device) -> Result<(), WaitError> {
+        self.frame = (self.frame + 1) % SYNC_POOL_FRAMES;
+        self.free_semaphores
+            .extend(self.used_semaphores[self.frame].drain(..));
+
+        let mut index = 0;
+        while index < self.in_flight_fences.len() {
+            if !device.is_fence_signalled(&self.in_flight_fences[index])? {
+                index += 1;
+                continue;
+            }
+
+            let fence = self.in_flight_fences.swap_remove(index);
+            device.reset_fences(&[&fence])?;
+            self.free_fences.push(fence);
+        }
+
+        Ok(())
+    }
+}
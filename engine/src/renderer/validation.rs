@@ -0,0 +1,173 @@
+//! Per-pass validation of submitted commands.
+//!
+//! While `r.validate_passes` is set, commands submitted to the queue
+//! are recorded and validated whenever a pass begins or ends.
+//! Referenced images and buffers must be owned by the renderer's device
+//! and images must be in layouts commands expect.
+//! Errors name the pass that submitted offending commands
+//! and are returned from `Context::end_pass`.
+
+use illume::{
+    CommandRecorder, CommandValidator, Device, Queue, RecordedSubmission,
+    ValidationError,
+};
+
+/// Name used for submissions made outside of any pass.
+const NO_PASS: &str = "<frame>";
+
+/// Commands submitted by a pass failed validation.
+#[derive(Debug, thiserror::Error)]
+#[error(
+    "Pass `{pass}` submitted {} invalid command(s), first: {}",
+    .errors.len(),
+    .errors[0]
+)]
+pub struct PassValidationError {
+    pub pass: &'static str,
+    pub errors: Vec<ValidationError>,
+}
+
+/// Validates commands submitted by each pass.
+pub(super) struct PassValidator {
+    /// `None` while validation is disabled.
+    validator: Option<CommandValidator>,
+
+    /// Recorder was installed by validator and is removed with it.
+    installed: bool,
+
+    /// Recorded frame and number of its submissions validated so far.
+    frame: u64,
+    validated: usize,
+
+    /// Names of open passes.
+    open: Vec<&'static str>,
+}
+
+impl PassValidator {
+    pub(super) fn new() -> Self {
+        PassValidator {
+            validator: None,
+            installed: false,
+            frame: 0,
+            validated: 0,
+            open: Vec::new(),
+        }
+    }
+
+    pub(super) fn set_enabled(
+        &mut self,
+        enabled: bool,
+        device: &Device,
+        queue: &mut Queue,
+    ) {
+        if enabled == self.validator.is_some() {
+            return;
+        }
+
+        if !enabled {
+            if self.installed {
+                queue.set_recorder(None);
+                self.installed = false;
+            }
+            self.validator = None;
+            return;
+        }
+
+        // Last finished frame is kept to validate its trailing submissions.
+        if queue.recorder().is_none() {
+            queue.set_recorder(Some(CommandRecorder::new(1)));
+            self.installed = true;
+        }
+
+        // Commands submitted before are not validated,
+        // so layouts of images they used are unknown.
+        let frame = queue.recorder().unwrap().current_frame();
+        self.frame = frame.index;
+        self.validated = frame.submissions.len();
+        self.validator = Some(CommandValidator::new().with_device(device));
+    }
+
+    pub(super) fn begin_pass(
+        &mut self,
+        name: &'static str,
+        queue: &Queue,
+    ) -> Result<(), PassValidationError> {
+        let result = self.flush(queue);
+        self.open.push(name);
+        result
+    }
+
+    pub(super) fn end_pass(
+        &mut self,
+        queue: &Queue,
+    ) -> Result<(), PassValidationError> {
+        let result = self.flush(queue);
+        self.open.pop();
+        result
+    }
+
+    /// Validates submissions made since last call
+    /// and attributes errors to innermost open pass.
+    fn flush(&mut self, queue: &Queue) -> Result<(), PassValidationError> {
+        let validator = match &mut self.validator {
+            Some(validator) => validator,
+            None => return Ok(()),
+        };
+
+        let recorder = match queue.recorder() {
+            Some(recorder) => recorder,
+            None => return Ok(()),
+        };
+
+        let mut errors = Vec::new();
+        let frame = recorder.current_frame();
+
+        if frame.index != self.frame {
+            // Submissions made after last pass of previous frame.
+            if let Some(last) = recorder.last_frame() {
+                if last.index == self.frame {
+                    errors.extend(validate_pending(
+                        validator,
+                        self.frame,
+                        self.validated,
+                        &last.submissions,
+                    ));
+                }
+            }
+
+            self.frame = frame.index;
+            self.validated = 0;
+        }
+
+        errors.extend(validate_pending(
+            validator,
+            frame.index,
+            self.validated,
+            &frame.submissions,
+        ));
+        self.validated = frame.submissions.len();
+
+        if errors.is_empty() {
+            return Ok(());
+        }
+
+        let pass = self.open.last().copied().unwrap_or(NO_PASS);
+        for error in &errors {
+            tracing::error!("Pass `{}`: {}", pass, error);
+        }
+
+        Err(PassValidationError { pass, errors })
+    }
+}
+
+fn validate_pending(
+    validator: &mut CommandValidator,
+    frame: u64,
+    first: usize,
+    submissions: &[RecordedSubmission],
+) -> Vec<ValidationError> {
+    match submissions.get(first..) {
+        Some(pending) => validator.validate_submissions(frame, first, pending),
+        None => Vec::new(),
+    }
+}
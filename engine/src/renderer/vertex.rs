@@ -5,6 +5,7 @@ use illume::{
 };
 use std::{
     borrow::Cow,
+    collections::HashMap,
     marker::PhantomData,
     mem::{size_of, size_of_val},
 };
@@ -24,6 +25,7 @@ pub enum Semantics {
     Normal3d,
     Tangent3d,
     UV,
+    UV1,
     Color,
     Joints,
     Weights,
@@ -36,6 +38,91 @@ impl Semantics {
             Semantics::Position3d | Semantics::Normal3d | Semantics::Tangent3d
         )
     }
+
+    /// Format of attribute with this semantics in registered layouts.
+    pub fn format(&self) -> Format {
+        match self {
+            Semantics::Position3d | Semantics::Normal3d => Format::RGB32Sfloat,
+            Semantics::UV | Semantics::UV1 => Format::RG32Sfloat,
+            Semantics::Tangent3d | Semantics::Color | Semantics::Weights => {
+                Format::RGBA32Sfloat
+            }
+            Semantics::Joints => Format::RGBA32Uint,
+        }
+    }
+
+    /// Shader location of attribute with this semantics
+    /// in pipelines specialized for vertex layout.
+    pub fn location(&self) -> u32 {
+        match self {
+            Semantics::Position3d => 0,
+            Semantics::Normal3d => 1,
+            Semantics::Tangent3d => 2,
+            Semantics::UV => 3,
+            Semantics::UV1 => 4,
+            Semantics::Color => 5,
+            Semantics::Joints => 6,
+            Semantics::Weights => 7,
+        }
+    }
+
+    /// Size of attribute in registered layouts.
+    fn size(&self) -> u32 {
+        match self {
+            Semantics::Position3d | Semantics::Normal3d => 12,
+            Semantics::UV | Semantics::UV1 => 8,
+            Semantics::Tangent3d
+            | Semantics::Color
+            | Semantics::Joints
+            | Semantics::Weights => 16,
+        }
+    }
+
+    fn attribute(&self) -> VertexAttributes {
+        match self {
+            Semantics::Position3d => VertexAttributes::POSITION,
+            Semantics::Normal3d => VertexAttributes::NORMAL,
+            Semantics::Tangent3d => VertexAttributes::TANGENT,
+            Semantics::UV => VertexAttributes::UV0,
+            Semantics::UV1 => VertexAttributes::UV1,
+            Semantics::Color => VertexAttributes::COLOR,
+            Semantics::Joints => VertexAttributes::JOINTS,
+            Semantics::Weights => VertexAttributes::WEIGHTS,
+        }
+    }
+}
+
+bitflags::bitflags! {
+    /// Set of vertex attributes mesh declares.
+    pub struct VertexAttributes: u32 {
+        const POSITION = 0x01;
+        const NORMAL = 0x02;
+        const TANGENT = 0x04;
+        const UV0 = 0x08;
+        const UV1 = 0x10;
+        const COLOR = 0x20;
+        const JOINTS = 0x40;
+        const WEIGHTS = 0x80;
+    }
+}
+
+impl VertexAttributes {
+    /// Returns semantics of attributes in layout order.
+    pub fn semantics(self) -> impl Iterator<Item = Semantics> {
+        [
+            Semantics::Position3d,
+            Semantics::Normal3d,
+            Semantics::Tangent3d,
+            Semantics::UV,
+            Semantics::UV1,
+            Semantics::Color,
+            Semantics::Joints,
+            Semantics::Weights,
+        ]
+        .iter()
+        .copied()
+        .filter(move |semantics| self.contains(semantics.attribute()))
+    }
 }
 
 /// Describes single vertex location.
@@ -72,6 +159,62 @@ pub struct VertexLayout {
     pub rate: VertexInputRate,
 }
 
+impl VertexLayout {
+    /// Returns set of attributes with known semantics in this layout.
+    pub fn attributes(&self) -> VertexAttributes {
+        self.locations
+            .iter()
+            .filter_map(|location| location.semantics)
+            .fold(VertexAttributes::empty(), |attributes, semantics| {
+                attributes | semantics.attribute()
+            })
+    }
+}
+
+/// Registry of interleaved vertex layouts for attribute sets.
+/// Layouts for the same set are equal, so meshes with matching
+/// attributes share specialized pipelines.
+#[derive(Debug, Default)]
+pub struct VertexLayoutRegistry {
+    layouts: HashMap<VertexAttributes, VertexLayout>,
+}
+
+impl VertexLayoutRegistry {
+    pub fn new() -> Self {
+        VertexLayoutRegistry::default()
+    }
+
+    /// Returns layout with attributes interleaved in fixed order.
+    /// Full set of position, normal, tangent and UV0
+    /// matches `PositionNormalTangent3dUV::layout()`.
+    pub fn layout(&mut self, attributes: VertexAttributes) -> VertexLayout {
+        self.layouts
+            .entry(attributes)
+            .or_insert_with(|| {
+                let mut stride = 0;
+                let locations = attributes
+                    .semantics()
+                    .map(|semantics| {
+                        let offset = stride;
+                        stride += semantics.size();
+                        VertexLocation {
+                            format: semantics.format(),
+                            offset,
+                            semantics: Some(semantics),
+                        }
+                    })
+                    .collect::<Vec<_>>();
+
+                VertexLayout {
+                    locations: Cow::Owned(locations),
+                    stride,
+                    rate: VertexInputRate::Vertex,
+                }
+            })
+            .clone()
+    }
+}
+
 pub trait FromBytes {
     /// Loads value from raw bytes slice.
     /// This function may expect that bytes len equals size of the type.
@@ -855,6 +998,43 @@ pub fn vertex_layouts_for_pipeline(
     (bindings, locations)
 }
 
+/// Returns vertex input for pipeline specialized for vertex layout.
+/// Only `required` attributes are bound to locations of their semantics.
+/// Returns `None` if layout lacks any of `required` attributes.
+pub fn vertex_input_for_layout(
+    layout: &VertexLayout,
+    required: VertexAttributes,
+) -> Option<(Vec<VertexInputBinding>, Vec<VertexInputAttribute>)> {
+    if !layout.attributes().contains(required) {
+        return None;
+    }
+
+    let attributes = layout
+        .locations
+        .iter()
+        .filter_map(|location| {
+            let semantics = location.semantics?;
+            if !required.contains(semantics.attribute()) {
+                return None;
+            }
+
+            Some(VertexInputAttribute {
+                location: semantics.location(),
+                format: location.format,
+                offset: location.offset,
+                binding: 0,
+            })
+        })
+        .collect();
+
+    let bindings = vec![VertexInputBinding {
+        stride: layout.stride,
+        rate: layout.rate,
+    }];
+
+    Some((bindings, attributes))
+}
+
 #[cfg(feature = "genmesh")]
 mod gm {
     use super::*;
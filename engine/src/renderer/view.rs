@@ -0,0 +1,164 @@
+use {
+    super::{
+        pipeline::PathTracePipeline, DynamicResolution, Extent2d, Format,
+        ImageUsage, Offset2d, Rect2d, Swapchain,
+    },
+    hecs::Entity,
+    raw_window_handle::{HasRawWindowHandle, RawWindowHandle},
+    std::sync::Arc,
+    winit::window::Window,
+};
+
+/// Window view presents to.
+/// Keeps window alive so surface can be recreated when it is lost.
+#[derive(Clone)]
+pub(super) struct WindowHandle(Arc<Window>);
+
+impl WindowHandle {
+    pub(super) fn new(window: &Arc<Window>) -> Self {
+        WindowHandle(window.clone())
+    }
+}
+
+// Handle is valid as long as the window is alive,
+// and the window is owned.
+unsafe impl HasRawWindowHandle for WindowHandle {
+    fn raw_window_handle(&self) -> RawWindowHandle {
+        self.0.raw_window_handle()
+    }
+}
+
+/// Maximum number of splits of one view.
+pub const MAX_VIEW_SPLITS: usize = 4;
+
+/// Region of a view rendered from own camera.
+pub(super) struct ViewSplit {
+    pub(super) pipeline: PathTracePipeline,
+
+    /// Controller of pipeline's internal extent.
+    pub(super) resolution: DynamicResolution,
+    pub(super) camera: Option<Entity>,
+}
+
+/// Render target driven by the `Renderer`.
+///
+/// Owns swapchain of a window or editor viewport
+/// together with pipeline state that renders into it.
+/// Multiple views can be drawn with single renderer.
+/// View keeps its window alive.
+///
+/// View may be split between several cameras for split-screen.
+/// Each split has own pipeline and is drawn into own region
+/// of the same swapchain image.
+pub struct ViewTarget {
+    pub(super) window: WindowHandle,
+    pub(super) swapchain: Swapchain,
+    pub(super) format: Format,
+
+    /// Usage of swapchain images negotiated with the surface.
+    pub(super) usage: ImageUsage,
+
+    /// Internal extent of unsplit view.
+    pub(super) extent: Extent2d,
+
+    /// Splits of the view. There is always at least one.
+    pub(super) splits: Vec<ViewSplit>,
+
+    /// Generation of renderer's device the view was created on.
+    pub(super) device_generation: u64,
+}
+
+impl ViewTarget {
+    pub(super) fn new(
+        window: WindowHandle,
+        swapchain: Swapchain,
+        format: Format,
+        usage: ImageUsage,
+        pipeline: PathTracePipeline,
+        extent: Extent2d,
+        device_generation: u64,
+    ) -> Self {
+        ViewTarget {
+            window,
+            swapchain,
+            format,
+            usage,
+            extent,
+            splits: vec![ViewSplit {
+                pipeline,
+                resolution: DynamicResolution::new(extent),
+                camera: None,
+            }],
+            device_generation,
+        }
+    }
+
+    /// Returns camera entity this view renders from.
+    /// `None` means first camera found in the world.
+    pub fn camera(&self) -> Option<Entity> {
+        self.splits[0].camera
+    }
+
+    /// Sets camera entity this view renders from.
+    /// `None` means first camera found in the world.
+    pub fn set_camera(&mut self, camera: Option<Entity>) {
+        self.splits[0].camera = camera;
+    }
+
+    /// Returns number of splits of the view.
+    pub fn split_count(&self) -> usize {
+        self.splits.len()
+    }
+
+    /// Returns camera entity of the split.
+    pub fn split_camera(&self, split: usize) -> Option<Entity> {
+        self.splits[split].camera
+    }
+
+    /// Sets camera entity of the split.
+    /// Number of splits is changed with `Renderer::set_view_splits`.
+    pub fn set_split_camera(&mut self, split: usize, camera: Option<Entity>) {
+        self.splits[split].camera = camera;
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Returns scale of internal extent chosen by dynamic resolution.
+    pub fn resolution_scale(&self) -> f32 {
+        self.splits[0].resolution.scale()
+    }
+}
+
+/// Returns regions of the extent occupied by each of `count` splits.
+///
+/// Two splits are stacked vertically, three and four are laid out
+/// in two by two grid, leaving last cell empty for three splits.
+pub fn split_regions(
+    extent: Extent2d,
+    count: usize,
+) -> impl Iterator<Item = Rect2d> {
+    assert!(
+        count > 0 && count <= MAX_VIEW_SPLITS,
+        "View may have from 1 to {} splits",
+        MAX_VIEW_SPLITS
+    );
+
+    let (cols, rows) = match count {
+        1 => (1, 1),
+        2 => (1, 2),
+        _ => (2, 2),
+    };
+
+    let width = extent.width / cols;
+    let height = extent.height / rows;
+
+    (0..count as u32).map(move |index| Rect2d {
+        offset: Offset2d {
+            x: ((index % cols) * width) as i32,
+            y: ((index / cols) * height) as i32,
+        },
+        extent: Extent2d { width, height },
+    })
+}
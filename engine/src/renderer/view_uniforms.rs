@@ -0,0 +1,175 @@
+use {
+    bytemuck::{Pod, Zeroable},
+    eyre::{ensure, Report},
+    illume::{
+        BufferInfo, BufferUsage, DescriptorBindingFlags, DescriptorSet,
+        DescriptorSetInfo, DescriptorSetLayout, DescriptorSetLayoutBinding,
+        DescriptorSetLayoutFlags, DescriptorSetLayoutInfo, DescriptorType,
+        Descriptors, Device, MappableBuffer, MemoryUsage, ShaderStageFlags,
+        WriteDescriptorSet,
+    },
+    nalgebra as na,
+    std::mem::size_of,
+};
+
+/// Maximum number of views drawn in one frame.
+pub const MAX_VIEWS_PER_FRAME: u32 = 8;
+
+/// Number of frames in flight view globals are kept for.
+const VIEW_UNIFORM_FRAMES: u32 = 2;
+
+/// Constants of a view shared by all passes.
+/// Matches `ViewGlobals` block in `pass/common/view.glsl`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct ViewGlobals {
+    /// Camera transform.
+    pub view: na::Matrix4<f32>,
+    pub iview: na::Matrix4<f32>,
+    pub proj: na::Matrix4<f32>,
+    pub iproj: na::Matrix4<f32>,
+
+    /// Camera transform at previous frame.
+    pub prev_view: na::Matrix4<f32>,
+    pub prev_iview: na::Matrix4<f32>,
+
+    /// Internal extent of the view.
+    pub extent: [u32; 2],
+    pub frame: u32,
+
+    /// Seconds since clocks were started.
+    pub time: f32,
+
+    /// Sub-pixel offset of the projection in pixels.
+    pub jitter: [f32; 2],
+    pub _pad: [f32; 2],
+}
+
+unsafe impl Zeroable for ViewGlobals {}
+unsafe impl Pod for ViewGlobals {}
+
+/// View globals written for current frame.
+/// Bound as single set with dynamic offset.
+#[derive(Clone, Debug)]
+pub struct ViewUniform {
+    pub set: DescriptorSet,
+    pub offset: u32,
+}
+
+/// Suballocated uniform buffer for view globals.
+///
+/// Each view writes its globals once per frame into own region
+/// instead of passes updating ad-hoc buffers.
+/// Regions of a frame are reused two frames later.
+pub struct ViewUniforms {
+    buffer: MappableBuffer,
+    layout: DescriptorSetLayout,
+    set: DescriptorSet,
+    frame: u32,
+    views: u32,
+}
+
+impl ViewUniforms {
+    pub fn new(device: &Device) -> Result<Self, Report> {
+        let layout =
+            device.create_descriptor_set_layout(DescriptorSetLayoutInfo {
+                flags: DescriptorSetLayoutFlags::empty(),
+                bindings: vec![DescriptorSetLayoutBinding {
+                    binding: 0,
+                    ty: DescriptorType::UniformBufferDynamic,
+                    count: 1,
+                    stages: ShaderStageFlags::VERTEX
+                        | ShaderStageFlags::FRAGMENT
+                        | ShaderStageFlags::COMPUTE
+                        | ShaderStageFlags::RAYGEN
+                        | ShaderStageFlags::CLOSEST_HIT
+                        | ShaderStageFlags::MISS
+                        | ShaderStageFlags::ANY_HIT,
+                    flags: DescriptorBindingFlags::empty(),
+                }],
+            })?;
+
+        let buffer = device.create_mappable_buffer(
+            BufferInfo {
+                align: 255,
+                size: view_globals_stride()
+                    * u64::from(MAX_VIEWS_PER_FRAME * VIEW_UNIFORM_FRAMES),
+                usage: BufferUsage::UNIFORM,
+                capture_address: None,
+            },
+            MemoryUsage::UPLOAD | MemoryUsage::FAST_DEVICE_ACCESS,
+        )?;
+
+        let set = device.create_descriptor_set(DescriptorSetInfo {
+            layout: layout.clone(),
+        })?;
+
+        device.update_descriptor_sets(
+            &[WriteDescriptorSet {
+                set: &set,
+                binding: 0,
+                element: 0,
+                descriptors: Descriptors::UniformBufferDynamic(&[(
+                    buffer.share(),
+                    0,
+                    size_of::<ViewGlobals>() as u64,
+                )]),
+            }],
+            &[],
+        );
+
+        Ok(ViewUniforms {
+            buffer,
+            layout,
+            set,
+            frame: 0,
+            views: 0,
+        })
+    }
+
+    /// Layout of the set with view globals.
+    /// Passes include it into their pipeline layouts.
+    pub fn layout(&self) -> &DescriptorSetLayout {
+        &self.layout
+    }
+
+    /// Switches to regions of next frame.
+    pub fn begin_frame(&mut self) {
+        self.frame = (self.frame + 1) % VIEW_UNIFORM_FRAMES;
+        self.views = 0;
+    }
+
+    /// Writes globals of a view for current frame.
+    pub fn write(
+        &mut self,
+        device: &Device,
+        globals: &ViewGlobals,
+    ) -> Result<ViewUniform, Report> {
+        ensure!(
+            self.views < MAX_VIEWS_PER_FRAME,
+            "At most {} views may be drawn in a frame",
+            MAX_VIEWS_PER_FRAME,
+        );
+
+        let region = self.frame * MAX_VIEWS_PER_FRAME + self.views;
+        let offset = view_globals_stride() * u64::from(region);
+        self.views += 1;
+
+        device.write_buffer(
+            &mut self.buffer,
+            offset,
+            std::slice::from_ref(globals),
+        )?;
+
+        Ok(ViewUniform {
+            set: self.set.clone(),
+            offset: offset as u32,
+        })
+    }
+}
+
+/// Stride of view globals regions.
+/// 256 is maximum allowed alignment of dynamic uniform buffer offsets.
+const fn view_globals_stride() -> u64 {
+    (size_of::<ViewGlobals>() as u64 + 255) & !255
+}
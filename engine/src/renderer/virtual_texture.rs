@@ -0,0 +1,828 @@
+//! Streaming virtual texturing.
+//!
+//! Virtual texture is a mip chain split into square pages stored as
+//! separate image assets. Ray-tracing pass writes feedback with pages
+//! hit shaders would like to sample and `VirtualTextureSystem` loads
+//! them in background into physical page atlas.
+//!
+//! Page table texture maps each virtual page to atlas slot of the page
+//! or of its nearest resident ancestor, so sampling never misses and
+//! detail is refined as pages arrive.
+
+use {
+    super::{Context, Renderable, Texture},
+    crate::{
+        assets::{AssetKey, Assets},
+        engine::{Engine, System, SystemContext},
+    },
+    illume::{
+        CreateImageError, Extent2d, Extent3d, Filter, Format, ImageInfo,
+        ImageSubresourceLayers, ImageUsage, ImageViewInfo, Layout, Offset3d,
+        OutOfMemory, SamplerAddressMode, SamplerInfo, Samples,
+    },
+    parking_lot::Mutex,
+    std::{
+        collections::{HashMap, HashSet},
+        hash::{Hash, Hasher},
+        sync::{
+            atomic::{AtomicU32, Ordering},
+            Arc,
+        },
+    },
+};
+
+/// Maximum number of pages along each side at finest level.
+pub const MAX_VIRTUAL_PAGES: u32 = 1024;
+
+/// Maximum number of atlas slots along each side.
+pub const MAX_ATLAS_PAGES: u32 = 256;
+
+/// Default number of page loads in flight.
+pub const DEFAULT_VT_LOAD_BUDGET: usize = 16;
+
+/// Feedback is written by one pixel of each square cell of this size.
+/// Pixel changes every frame so whole view is covered over time.
+pub const VT_FEEDBACK_CELL: u32 = 8;
+
+/// Virtual texture ids packed into feedback are limited to 8 bits.
+/// Zero marks empty feedback entry.
+const MAX_VIRTUAL_TEXTURE_ID: u32 = 255;
+
+static NEXT_ID: AtomicU32 = AtomicU32::new(0);
+
+/// Address of page in virtual texture mip chain.
+/// Level zero is the finest one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct VirtualPage {
+    pub level: u32,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl VirtualPage {
+    fn parent(&self) -> Self {
+        VirtualPage {
+            level: self.level + 1,
+            x: self.x / 2,
+            y: self.y / 2,
+        }
+    }
+}
+
+/// Decodes page request written into feedback buffer by hit shaders.
+/// Returns virtual texture id and page.
+pub(crate) fn decode_vt_feedback(request: u32) -> Option<(u32, VirtualPage)> {
+    if request == 0 {
+        return None;
+    }
+
+    let page = VirtualPage {
+        level: (request >> 20) & 0xf,
+        x: request & 0x3ff,
+        y: (request >> 10) & 0x3ff,
+    };
+
+    Some((request >> 24, page))
+}
+
+fn default_border() -> u32 {
+    4
+}
+
+fn default_atlas_pages() -> u32 {
+    32
+}
+
+/// Layout and sources of virtual texture pages.
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct VirtualTextureInfo {
+    /// Number of pages along each side at finest level.
+    /// Must be power of two.
+    pub pages: u32,
+
+    /// Size of page side in texels, including borders.
+    pub page_size: u32,
+
+    /// Texels on each side of the page duplicated from neighbour pages,
+    /// so filtering never reads unrelated atlas slot.
+    #[serde(default = "default_border")]
+    pub border: u32,
+
+    /// Number of atlas slots along each side.
+    #[serde(default = "default_atlas_pages")]
+    pub atlas_pages: u32,
+
+    /// Key pattern of albedo pages.
+    /// `{level}`, `{x}` and `{y}` are replaced with page address.
+    pub albedo: String,
+
+    /// Key pattern of tangent space normal pages.
+    #[serde(default)]
+    pub normal: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum VirtualTextureError {
+    #[error("Virtual texture page count {pages} is not supported")]
+    InvalidPages { pages: u32 },
+
+    #[error("Virtual texture page size {page_size} is too small")]
+    InvalidPageSize { page_size: u32, border: u32 },
+
+    #[error("Virtual texture atlas size {atlas_pages} is out of range")]
+    InvalidAtlasPages { atlas_pages: u32 },
+
+    #[error(transparent)]
+    CreateImage {
+        #[from]
+        source: CreateImageError,
+    },
+}
+
+impl From<OutOfMemory> for VirtualTextureError {
+    fn from(source: OutOfMemory) -> Self {
+        VirtualTextureError::CreateImage {
+            source: source.into(),
+        }
+    }
+}
+
+/// Streaming virtual texture.
+///
+/// Created with empty atlas. Pages are loaded by `VirtualTextureSystem`
+/// and uploaded by renderer.
+#[derive(Clone)]
+pub struct VirtualTexture {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    id: u32,
+    info: VirtualTextureInfo,
+    levels: u32,
+    uv_scale: f32,
+    page_table: Texture,
+    albedo: Texture,
+    normal: Option<Texture>,
+    state: Mutex<State>,
+}
+
+struct Resident {
+    slot: (u32, u32),
+    last_used: u64,
+}
+
+struct LoadedPage {
+    page: VirtualPage,
+    albedo: Vec<u8>,
+    normal: Option<Vec<u8>>,
+}
+
+/// Inclusive-exclusive rectangle of page table texels.
+#[derive(Clone, Copy)]
+struct DirtyRect {
+    x0: u32,
+    y0: u32,
+    x1: u32,
+    y1: u32,
+}
+
+struct State {
+    initialized: bool,
+    pages: u32,
+    frame: u64,
+
+    /// Pages requested since last `VirtualTextureSystem` run.
+    requested: HashSet<VirtualPage>,
+    loading: HashSet<VirtualPage>,
+    failed: HashSet<VirtualPage>,
+    loaded: Vec<LoadedPage>,
+    resident: HashMap<VirtualPage, Resident>,
+    free: Vec<(u32, u32)>,
+
+    /// CPU copy of page table levels.
+    table: Vec<Vec<[u8; 4]>>,
+    dirty: Vec<Option<DirtyRect>>,
+}
+
+impl PartialEq for VirtualTexture {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.shared, &other.shared)
+    }
+}
+
+impl Eq for VirtualTexture {}
+
+impl Hash for VirtualTexture {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (&*self.shared as *const Shared).hash(state)
+    }
+}
+
+impl std::fmt::Debug for VirtualTexture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VirtualTexture")
+            .field("id", &self.shared.id)
+            .field("info", &self.shared.info)
+            .finish()
+    }
+}
+
+impl VirtualTexture {
+    /// Creates virtual texture with empty page atlas.
+    ///
+    /// `uv_scale` maps mesh texture coordinates to `[0, 1]` range
+    /// of virtual texture.
+    /// Key patterns in `info` must be already resolved.
+    pub fn new(
+        info: VirtualTextureInfo,
+        uv_scale: f32,
+        ctx: &mut Context,
+    ) -> Result<Self, VirtualTextureError> {
+        if !info.pages.is_power_of_two() || info.pages > MAX_VIRTUAL_PAGES {
+            return Err(VirtualTextureError::InvalidPages {
+                pages: info.pages,
+            });
+        }
+
+        if info.page_size <= info.border * 2 {
+            return Err(VirtualTextureError::InvalidPageSize {
+                page_size: info.page_size,
+                border: info.border,
+            });
+        }
+
+        if info.atlas_pages < 2 || info.atlas_pages > MAX_ATLAS_PAGES {
+            return Err(VirtualTextureError::InvalidAtlasPages {
+                atlas_pages: info.atlas_pages,
+            });
+        }
+
+        let levels = info.pages.trailing_zeros() + 1;
+
+        let page_table_image = ctx.create_image(ImageInfo {
+            extent: Extent2d {
+                width: info.pages,
+                height: info.pages,
+            }
+            .into(),
+            format: Format::RGBA8Unorm,
+            levels,
+            layers: 1,
+            samples: Samples::Samples1,
+            usage: ImageUsage::SAMPLED | ImageUsage::TRANSFER_DST,
+        })?;
+
+        // Page table is read with `texelFetch`.
+        let page_table = Texture {
+            image: ctx
+                .create_image_view(ImageViewInfo::new(page_table_image))?,
+            sampler: ctx.create_sampler(SamplerInfo::new())?,
+        };
+
+        let atlas_sampler = ctx.create_sampler(SamplerInfo {
+            mag_filter: Filter::Linear,
+            min_filter: Filter::Linear,
+            address_mode_u: SamplerAddressMode::ClampToEdge,
+            address_mode_v: SamplerAddressMode::ClampToEdge,
+            address_mode_w: SamplerAddressMode::ClampToEdge,
+            min_lod: 0.0.into(),
+            max_lod: 0.0.into(),
+            ..SamplerInfo::new()
+        })?;
+
+        let atlas_size = info.atlas_pages * info.page_size;
+        let mut create_atlas = |format| -> Result<_, VirtualTextureError> {
+            let image = ctx.create_image(ImageInfo {
+                extent: Extent2d {
+                    width: atlas_size,
+                    height: atlas_size,
+                }
+                .into(),
+                format,
+                levels: 1,
+                layers: 1,
+                samples: Samples::Samples1,
+                usage: ImageUsage::SAMPLED | ImageUsage::TRANSFER_DST,
+            })?;
+
+            Ok(Texture {
+                image: ctx.create_image_view(ImageViewInfo::new(image))?,
+                sampler: atlas_sampler.clone(),
+            })
+        };
+
+        let albedo = create_atlas(Format::RGBA8Srgb)?;
+        let normal = match info.normal {
+            Some(_) => Some(create_atlas(Format::RGBA8Unorm)?),
+            None => None,
+        };
+
+        let table = (0..levels)
+            .map(|level| {
+                let side = (info.pages >> level) as usize;
+                vec![[0; 4]; side * side]
+            })
+            .collect();
+
+        // Slots are popped from the end.
+        let free = (0..info.atlas_pages)
+            .flat_map(|y| (0..info.atlas_pages).map(move |x| (x, y)))
+            .rev()
+            .collect();
+
+        // Root page is always requested first and never evicted.
+        let mut requested = HashSet::new();
+        requested.insert(VirtualPage {
+            level: levels - 1,
+            x: 0,
+            y: 0,
+        });
+
+        let pages = info.pages;
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed)
+            % MAX_VIRTUAL_TEXTURE_ID
+            + 1;
+
+        Ok(VirtualTexture {
+            shared: Arc::new(Shared {
+                id,
+                info,
+                levels,
+                uv_scale,
+                page_table,
+                albedo,
+                normal,
+                state: Mutex::new(State {
+                    initialized: false,
+                    pages,
+                    frame: 0,
+                    requested,
+                    loading: HashSet::new(),
+                    failed: HashSet::new(),
+                    loaded: Vec::new(),
+                    resident: HashMap::new(),
+                    free,
+                    table,
+                    dirty: vec![None; levels as usize],
+                }),
+            }),
+        })
+    }
+
+    /// Id written into feedback buffer.
+    /// Ids are reused after 255 virtual textures are created.
+    pub fn id(&self) -> u32 {
+        self.shared.id
+    }
+
+    pub fn info(&self) -> &VirtualTextureInfo {
+        &self.shared.info
+    }
+
+    /// Number of levels in the mip chain.
+    pub fn levels(&self) -> u32 {
+        self.shared.levels
+    }
+
+    pub fn uv_scale(&self) -> f32 {
+        self.shared.uv_scale
+    }
+
+    pub fn page_table(&self) -> &Texture {
+        &self.shared.page_table
+    }
+
+    pub fn albedo(&self) -> &Texture {
+        &self.shared.albedo
+    }
+
+    pub fn normal(&self) -> Option<&Texture> {
+        self.shared.normal.as_ref()
+    }
+
+    /// Checks if page is loaded into atlas.
+    pub fn is_resident(&self, page: VirtualPage) -> bool {
+        self.shared.state.lock().resident.contains_key(&page)
+    }
+
+    /// Requests page to be streamed in.
+    ///
+    /// Missing ancestors are requested as well, so coarse pages
+    /// arrive first. Resident pages on the chain are kept from
+    /// eviction.
+    pub fn request(&self, mut page: VirtualPage) {
+        let side = self.shared.info.pages >> page.level.min(31);
+        if page.level >= self.shared.levels || page.x >= side || page.y >= side
+        {
+            return;
+        }
+
+        let mut state = self.shared.state.lock();
+        let state = &mut *state;
+
+        loop {
+            match state.resident.get_mut(&page) {
+                Some(resident) => resident.last_used = state.frame,
+                None => {
+                    if !state.loading.contains(&page)
+                        && !state.failed.contains(&page)
+                    {
+                        state.requested.insert(page);
+                    }
+                }
+            }
+
+            if page.level + 1 >= self.shared.levels {
+                break;
+            }
+            page = page.parent();
+        }
+    }
+
+    /// Uploads loaded pages into atlas and updated page table regions.
+    ///
+    /// Called by renderer once per frame before uploads are flushed.
+    pub fn flush(&self, ctx: &mut Context) -> Result<(), OutOfMemory> {
+        let shared = &*self.shared;
+        let mut state = shared.state.lock();
+        let state = &mut *state;
+
+        if !state.initialized {
+            // Undefined layout is discarded before first upload,
+            // so images are initialized in separate flush.
+            self.initialize(state, ctx)?;
+            state.initialized = true;
+            state.frame += 1;
+            return Ok(());
+        }
+
+        let page_size = shared.info.page_size;
+        let root = VirtualPage {
+            level: shared.levels - 1,
+            x: 0,
+            y: 0,
+        };
+
+        for loaded in std::mem::take(&mut state.loaded) {
+            state.loading.remove(&loaded.page);
+
+            let slot = match state.free.pop() {
+                Some(slot) => slot,
+                None => match state.evict(root) {
+                    Some(slot) => slot,
+                    // Every slot is in use. Page will be requested again.
+                    None => continue,
+                },
+            };
+
+            let offset = Offset3d {
+                x: (slot.0 * page_size) as i32,
+                y: (slot.1 * page_size) as i32,
+                z: 0,
+            };
+
+            let extent = Extent3d {
+                width: page_size,
+                height: page_size,
+                depth: 1,
+            };
+
+            ctx.upload_image(
+                &shared.albedo.image.info().image,
+                Some(Layout::General),
+                0,
+                0,
+                ImageSubresourceLayers::color(0, 0..1),
+                offset,
+                extent,
+                &loaded.albedo,
+            )?;
+
+            if let (Some(normal), Some(data)) = (&shared.normal, &loaded.normal)
+            {
+                ctx.upload_image(
+                    &normal.image.info().image,
+                    Some(Layout::General),
+                    0,
+                    0,
+                    ImageSubresourceLayers::color(0, 0..1),
+                    offset,
+                    extent,
+                    data,
+                )?;
+            }
+
+            state.resident.insert(
+                loaded.page,
+                Resident {
+                    slot,
+                    last_used: state.frame,
+                },
+            );
+            state.update_table(loaded.page);
+        }
+
+        let image = &shared.page_table.image.info().image;
+        for (level, dirty) in state.dirty.iter_mut().enumerate() {
+            let rect = match dirty.take() {
+                Some(rect) => rect,
+                None => continue,
+            };
+
+            let side = shared.info.pages >> level;
+            let start = (rect.y0 * side + rect.x0) as usize;
+            let end = ((rect.y1 - 1) * side + rect.x1) as usize;
+
+            ctx.upload_image(
+                image,
+                Some(Layout::General),
+                side,
+                0,
+                ImageSubresourceLayers::color(level as u32, 0..1),
+                Offset3d {
+                    x: rect.x0 as i32,
+                    y: rect.y0 as i32,
+                    z: 0,
+                },
+                Extent3d {
+                    width: rect.x1 - rect.x0,
+                    height: rect.y1 - rect.y0,
+                    depth: 1,
+                },
+                &state.table[level][start..end],
+            )?;
+        }
+
+        state.frame += 1;
+        Ok(())
+    }
+
+    fn initialize(
+        &self,
+        state: &mut State,
+        ctx: &mut Context,
+    ) -> Result<(), OutOfMemory> {
+        let shared = &*self.shared;
+
+        let image = &shared.page_table.image.info().image;
+        for (level, table) in state.table.iter().enumerate() {
+            let side = shared.info.pages >> level;
+            ctx.upload_image(
+                image,
+                None,
+                0,
+                0,
+                ImageSubresourceLayers::color(level as u32, 0..1),
+                Offset3d::ZERO,
+                Extent3d {
+                    width: side,
+                    height: side,
+                    depth: 1,
+                },
+                table,
+            )?;
+        }
+
+        let page_size = shared.info.page_size;
+        let blank = vec![0u8; (page_size * page_size * 4) as usize];
+        for atlas in std::iter::once(&shared.albedo).chain(&shared.normal) {
+            ctx.upload_image(
+                &atlas.image.info().image,
+                None,
+                0,
+                0,
+                ImageSubresourceLayers::color(0, 0..1),
+                Offset3d::ZERO,
+                Extent3d {
+                    width: page_size,
+                    height: page_size,
+                    depth: 1,
+                },
+                &blank,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Takes requested pages, coarsest first.
+    fn take_requests(&self, budget: usize) -> Vec<VirtualPage> {
+        let mut state = self.shared.state.lock();
+        let state = &mut *state;
+
+        let budget = budget.saturating_sub(state.loading.len());
+        let mut pages: Vec<_> = state.requested.drain().collect();
+        pages.sort_unstable_by(|a, b| b.cmp(a));
+        pages.truncate(budget);
+
+        state.loading.extend(pages.iter().copied());
+        pages
+    }
+
+    fn page_loaded(&self, loaded: LoadedPage) {
+        self.shared.state.lock().loaded.push(loaded);
+    }
+
+    fn page_failed(&self, page: VirtualPage) {
+        let mut state = self.shared.state.lock();
+        state.loading.remove(&page);
+        state.failed.insert(page);
+    }
+}
+
+impl State {
+    /// Evicts least recently used page not used this frame.
+    fn evict(&mut self, root: VirtualPage) -> Option<(u32, u32)> {
+        let frame = self.frame;
+        let (&page, _) = self
+            .resident
+            .iter()
+            .filter(|&(&page, resident)| {
+                page != root && resident.last_used < frame
+            })
+            .min_by_key(|(_, resident)| resident.last_used)?;
+
+        let resident = self.resident.remove(&page)?;
+        self.update_table(page);
+        Some(resident.slot)
+    }
+
+    /// Recomputes page table entries covered by page.
+    /// Entries of missing pages are copied from parent level.
+    fn update_table(&mut self, page: VirtualPage) {
+        let levels = self.table.len() as u32;
+
+        for level in (0..=page.level).rev() {
+            let shift = page.level - level;
+            let side = self.pages >> level;
+            let x0 = page.x << shift;
+            let y0 = page.y << shift;
+            let size = 1 << shift;
+
+            for y in y0..y0 + size {
+                for x in x0..x0 + size {
+                    let entry =
+                        match self.resident.get(&VirtualPage { level, x, y }) {
+                            Some(resident) => [
+                                resident.slot.0 as u8,
+                                resident.slot.1 as u8,
+                                level as u8,
+                                255,
+                            ],
+                            None if level + 1 < levels => {
+                                let parent_side = (side / 2) as usize;
+                                self.table[level as usize + 1][(y / 2) as usize
+                                    * parent_side
+                                    + (x / 2) as usize]
+                            }
+                            None => [0; 4],
+                        };
+
+                    self.table[level as usize][(y * side + x) as usize] = entry;
+                }
+            }
+
+            let dirty = &mut self.dirty[level as usize];
+            *dirty = Some(match *dirty {
+                Some(rect) => DirtyRect {
+                    x0: rect.x0.min(x0),
+                    y0: rect.y0.min(y0),
+                    x1: rect.x1.max(x0 + size),
+                    y1: rect.y1.max(y0 + size),
+                },
+                None => DirtyRect {
+                    x0,
+                    y0,
+                    x1: x0 + size,
+                    y1: y0 + size,
+                },
+            });
+        }
+    }
+}
+
+/// Collects virtual textures of renderables by feedback id.
+pub(crate) fn collect_virtual_textures(
+    world: &hecs::World,
+) -> HashMap<u32, VirtualTexture> {
+    world
+        .query::<&Renderable>()
+        .iter()
+        .filter_map(|(_, renderable)| {
+            renderable.material.virtual_texture.as_ref()
+        })
+        .map(|vt| (vt.id(), vt.clone()))
+        .collect()
+}
+
+fn page_key(pattern: &str, page: VirtualPage) -> AssetKey {
+    pattern
+        .replace("{level}", &page.level.to_string())
+        .replace("{x}", &page.x.to_string())
+        .replace("{y}", &page.y.to_string())
+        .into()
+}
+
+async fn load_page(
+    assets: Assets,
+    pattern: String,
+    page: VirtualPage,
+    page_size: u32,
+) -> Result<Vec<u8>, eyre::Report> {
+    let key = page_key(&pattern, page);
+    let bytes = assets.load::<Box<[u8]>>(key.clone()).await?;
+
+    let image = smol::unblock(move || image::load_from_memory(&bytes)).await?;
+    let image = image.to_rgba8();
+
+    eyre::ensure!(
+        image.dimensions() == (page_size, page_size),
+        "Virtual texture page '{}' is {:?}, expected {}x{}",
+        key,
+        image.dimensions(),
+        page_size,
+        page_size,
+    );
+
+    Ok(image.into_raw())
+}
+
+/// System that loads pages requested by virtual textures
+/// of renderables.
+pub struct VirtualTextureSystem {
+    assets: Assets,
+    budget: usize,
+}
+
+impl VirtualTextureSystem {
+    pub fn new(engine: &Engine) -> Self {
+        VirtualTextureSystem {
+            assets: engine.assets.clone(),
+            budget: DEFAULT_VT_LOAD_BUDGET,
+        }
+    }
+
+    /// Sets maximum number of page loads in flight
+    /// per virtual texture.
+    pub fn with_budget(mut self, budget: usize) -> Self {
+        self.budget = budget.max(1);
+        self
+    }
+}
+
+impl System for VirtualTextureSystem {
+    fn run(&mut self, ctx: SystemContext<'_>) {
+        for (_, vt) in collect_virtual_textures(ctx.world) {
+            for page in vt.take_requests(self.budget) {
+                let assets = self.assets.clone();
+                let info = vt.info().clone();
+                let vt = vt.clone();
+
+                smol::spawn(async move {
+                    let result = async {
+                        let albedo = load_page(
+                            assets.clone(),
+                            info.albedo,
+                            page,
+                            info.page_size,
+                        )
+                        .await?;
+
+                        let normal = match info.normal {
+                            Some(pattern) => Some(
+                                load_page(
+                                    assets,
+                                    pattern,
+                                    page,
+                                    info.page_size,
+                                )
+                                .await?,
+                            ),
+                            None => None,
+                        };
+
+                        Ok::<_, eyre::Report>(LoadedPage {
+                            page,
+                            albedo,
+                            normal,
+                        })
+                    }
+                    .await;
+
+                    match result {
+                        Ok(loaded) => vt.page_loaded(loaded),
+                        Err(err) => {
+                            tracing::error!(
+                                "Failed to load virtual texture page: {:#}",
+                                err
+                            );
+                            vt.page_failed(page);
+                        }
+                    }
+                })
+                .detach();
+            }
+        }
+    }
+}
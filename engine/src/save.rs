@@ -0,0 +1,355 @@
+//! Save games.
+//!
+//! Unlike scene files saves contain only state that must persist
+//! between sessions.
+//! Components of entities marked with `Persistent` and resources
+//! registered in `SaveManager` are saved.
+
+use {
+    hecs::{Component, Entity, World},
+    serde::{de::DeserializeOwned, Deserialize, Serialize},
+    std::{collections::HashMap, future::Future, path::PathBuf, sync::Arc},
+    type_map::TypeMap,
+};
+
+/// Magic bytes at the beginning of save files.
+const MAGIC: [u8; 4] = *b"WSAV";
+
+/// Marks entity to be saved.
+///
+/// Id identifies entity across sessions
+/// and must be unique among persistent entities.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Persistent(pub u64);
+
+/// Saved state of single entity.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SavedEntity {
+    pub id: u64,
+
+    /// Encoded components by registered name.
+    pub components: HashMap<String, Vec<u8>>,
+}
+
+/// Contents of save file.
+///
+/// Components and resources are encoded separately,
+/// so migrations can rename, drop or re-encode them
+/// and unknown ones are skipped on load.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SaveData {
+    pub version: u32,
+    pub entities: Vec<SavedEntity>,
+
+    /// Encoded resources by registered name.
+    pub resources: HashMap<String, Vec<u8>>,
+}
+
+impl SaveData {
+    /// Decodes value previously encoded for save file.
+    pub fn decode<T>(bytes: &[u8]) -> Result<T, SaveError>
+    where
+        T: DeserializeOwned,
+    {
+        Ok(bincode::deserialize(bytes)?)
+    }
+
+    /// Encodes value for save file.
+    pub fn encode<T>(value: &T) -> Result<Vec<u8>, SaveError>
+    where
+        T: Serialize,
+    {
+        Ok(bincode::serialize(value)?)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SaveError {
+    #[error("Failed to access save file: `{source}`")]
+    Io {
+        #[from]
+        source: std::io::Error,
+    },
+
+    #[error("Failed to encode or decode save data: `{source}`")]
+    Bincode {
+        #[from]
+        source: bincode::Error,
+    },
+
+    #[error("File is not a save file")]
+    BadMagic,
+
+    #[error("Save version {version} is newer than supported {supported}")]
+    UnsupportedVersion { version: u32, supported: u32 },
+
+    #[error("Failed to migrate save from version {version}: {reason}")]
+    Migration { version: u32, reason: String },
+}
+
+type Migration =
+    Arc<dyn Fn(&mut SaveData) -> Result<(), SaveError> + Send + Sync>;
+
+struct Entry<T> {
+    name: &'static str,
+    save: fn(&T, Option<Entity>) -> Option<Result<Vec<u8>, SaveError>>,
+    load: fn(&mut T, Option<Entity>, &[u8]) -> Result<(), SaveError>,
+}
+
+/// Saves and loads persistent state.
+///
+/// Encoding of components happens on the calling thread.
+/// File access, decoding of the file and migrations
+/// are performed in background.
+pub struct SaveManager {
+    version: u32,
+    components: Vec<Entry<World>>,
+    resources: Vec<Entry<TypeMap>>,
+    migrations: HashMap<u32, Migration>,
+}
+
+impl SaveManager {
+    /// Creates manager for current save schema version.
+    pub fn new(version: u32) -> Self {
+        SaveManager {
+            version,
+            components: Vec::new(),
+            resources: Vec::new(),
+            migrations: HashMap::new(),
+        }
+    }
+
+    /// Returns current save schema version.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Registers component to be saved for persistent entities.
+    /// Name must stay the same across versions unless migration renames it.
+    pub fn register_component<T>(&mut self, name: &'static str)
+    where
+        T: Component + Serialize + DeserializeOwned,
+    {
+        self.components.push(Entry {
+            name,
+            save: save_component::<T>,
+            load: load_component::<T>,
+        });
+    }
+
+    /// Registers resource to be saved.
+    /// Name must stay the same across versions unless migration renames it.
+    pub fn register_resource<T>(&mut self, name: &'static str)
+    where
+        T: Serialize + DeserializeOwned + 'static,
+    {
+        self.resources.push(Entry {
+            name,
+            save: save_resource::<T>,
+            load: load_resource::<T>,
+        });
+    }
+
+    /// Registers migration from `version` to `version + 1`.
+    ///
+    /// Versions without migration are considered compatible
+    /// with the next one.
+    pub fn add_migration<F>(&mut self, version: u32, migration: F)
+    where
+        F: Fn(&mut SaveData) -> Result<(), SaveError> + Send + Sync + 'static,
+    {
+        self.migrations.insert(version, Arc::new(migration));
+    }
+
+    /// Collects persistent state.
+    pub fn snapshot(
+        &self,
+        world: &World,
+        resources: &TypeMap,
+    ) -> Result<SaveData, SaveError> {
+        let mut entities = Vec::new();
+
+        for (entity, persistent) in world.query::<&Persistent>().iter() {
+            let mut saved = SavedEntity {
+                id: persistent.0,
+                components: HashMap::new(),
+            };
+
+            for entry in &self.components {
+                if let Some(bytes) = (entry.save)(world, Some(entity)) {
+                    saved.components.insert(entry.name.to_owned(), bytes?);
+                }
+            }
+
+            entities.push(saved);
+        }
+
+        let mut saved_resources = HashMap::new();
+        for entry in &self.resources {
+            if let Some(bytes) = (entry.save)(resources, None) {
+                saved_resources.insert(entry.name.to_owned(), bytes?);
+            }
+        }
+
+        Ok(SaveData {
+            version: self.version,
+            entities,
+            resources: saved_resources,
+        })
+    }
+
+    /// Saves persistent state into file.
+    ///
+    /// State is collected immediately.
+    /// File is written in background, returned future resolves when done.
+    pub fn save(
+        &self,
+        world: &World,
+        resources: &TypeMap,
+        path: PathBuf,
+    ) -> Result<impl Future<Output = Result<(), SaveError>>, SaveError> {
+        let data = self.snapshot(world, resources)?;
+
+        Ok(smol::unblock(move || {
+            let mut bytes = MAGIC.to_vec();
+            bincode::serialize_into(&mut bytes, &data)?;
+
+            // Write into temporary file first,
+            // so crash during saving does not corrupt previous save.
+            let tmp = path.with_extension("tmp");
+            std::fs::write(&tmp, &bytes)?;
+            std::fs::rename(&tmp, &path)?;
+            Ok(())
+        }))
+    }
+
+    /// Reads save file and migrates it to current version in background.
+    /// Use `apply` to restore state from loaded data.
+    pub fn load(
+        &self,
+        path: PathBuf,
+    ) -> impl Future<Output = Result<SaveData, SaveError>> {
+        let current = self.version;
+        let migrations = self.migrations.clone();
+
+        smol::unblock(move || {
+            let bytes = std::fs::read(&path)?;
+
+            if bytes.len() < MAGIC.len() || bytes[..MAGIC.len()] != MAGIC {
+                return Err(SaveError::BadMagic);
+            }
+
+            let mut data: SaveData =
+                bincode::deserialize(&bytes[MAGIC.len()..])?;
+
+            if data.version > current {
+                return Err(SaveError::UnsupportedVersion {
+                    version: data.version,
+                    supported: current,
+                });
+            }
+
+            while data.version < current {
+                if let Some(migration) = migrations.get(&data.version) {
+                    tracing::info!(
+                        "Migrating save from version {}",
+                        data.version
+                    );
+                    migration(&mut data)?;
+                }
+                data.version += 1;
+            }
+
+            Ok(data)
+        })
+    }
+
+    /// Restores state from loaded data.
+    ///
+    /// Saved components are inserted into entities with matching
+    /// `Persistent` id. Entities are spawned if not found.
+    /// Components and resources unknown to this manager are skipped.
+    pub fn apply(
+        &self,
+        data: &SaveData,
+        world: &mut World,
+        resources: &mut TypeMap,
+    ) -> Result<(), SaveError> {
+        let mut existing: HashMap<u64, Entity> = world
+            .query::<&Persistent>()
+            .iter()
+            .map(|(entity, persistent)| (persistent.0, entity))
+            .collect();
+
+        for saved in &data.entities {
+            let entity = *existing
+                .entry(saved.id)
+                .or_insert_with(|| world.spawn((Persistent(saved.id),)));
+
+            for entry in &self.components {
+                if let Some(bytes) = saved.components.get(entry.name) {
+                    (entry.load)(world, Some(entity), bytes)?;
+                }
+            }
+        }
+
+        for entry in &self.resources {
+            if let Some(bytes) = data.resources.get(entry.name) {
+                (entry.load)(resources, None, bytes)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn save_component<T>(
+    world: &World,
+    entity: Option<Entity>,
+) -> Option<Result<Vec<u8>, SaveError>>
+where
+    T: Component + Serialize,
+{
+    let component = world.get::<T>(entity?).ok()?;
+    Some(SaveData::encode(&*component))
+}
+
+fn load_component<T>(
+    world: &mut World,
+    entity: Option<Entity>,
+    bytes: &[u8],
+) -> Result<(), SaveError>
+where
+    T: Component + DeserializeOwned,
+{
+    let component = SaveData::decode::<T>(bytes)?;
+    if let Some(entity) = entity {
+        // Entity was found or spawned just now.
+        world.insert_one(entity, component).unwrap();
+    }
+    Ok(())
+}
+
+fn save_resource<T>(
+    resources: &TypeMap,
+    _: Option<Entity>,
+) -> Option<Result<Vec<u8>, SaveError>>
+where
+    T: Serialize + 'static,
+{
+    let resource = resources.get::<T>()?;
+    Some(SaveData::encode(resource))
+}
+
+fn load_resource<T>(
+    resources: &mut TypeMap,
+    _: Option<Entity>,
+    bytes: &[u8],
+) -> Result<(), SaveError>
+where
+    T: DeserializeOwned + 'static,
+{
+    let resource = SaveData::decode::<T>(bytes)?;
+    resources.insert(resource);
+    Ok(())
+}
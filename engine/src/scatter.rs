@@ -0,0 +1,496 @@
+//! Scattering of instanced props over terrain.
+//!
+//! `ScatterSystem` splits the world into square chunks around the camera
+//! and fills chunks with instances of `ScatterLayer` props.
+//! Placement probability is controlled by `DensityMap`
+//! and instances are placed on top of `Terrain` colliders.
+//!
+//! Each instance is an entity sharing `Renderable` with other instances
+//! of the same LOD, so ray-tracing pipeline instantiates single BLAS
+//! for all of them. Chunks switch LOD as a whole and are despawned
+//! once they leave view distance.
+
+use {
+    crate::{
+        assets::Terrain,
+        camera::Camera,
+        engine::{System, SystemContext},
+        physics::Colliders,
+        renderer::{RasterOnly, Renderable},
+        scene::Global3,
+    },
+    hecs::{Entity, World},
+    nalgebra as na,
+    ncollide3d::{query::Ray, shape::ShapeHandle},
+    noise::{Noise2, NoiseTexture},
+    rand::{rngs::StdRng, Rng as _, SeedableRng as _},
+    std::{collections::HashMap, sync::Arc},
+};
+
+/// Default size of scatter chunk side.
+pub const DEFAULT_CHUNK_SIZE: f32 = 32.0;
+
+/// Default number of chunks generated per frame.
+pub const DEFAULT_CHUNK_BUDGET: usize = 4;
+
+/// Height above which rays are cast down onto terrain.
+const RAY_HEIGHT: f32 = 10000.0;
+
+/// Map of placement probability.
+#[derive(Clone)]
+pub enum DensityMap {
+    /// Same probability everywhere.
+    Uniform(f32),
+
+    /// Noise function sampled at `position * frequency`.
+    /// Values in `[-1, 1]` are remapped to `[0, 1]`.
+    Noise {
+        noise: Arc<dyn Noise2 + Send + Sync>,
+        frequency: f32,
+    },
+
+    /// Noise texture stretched so that each texel covers
+    /// `texel_size` units.
+    Texture {
+        texture: Arc<NoiseTexture>,
+        texel_size: f32,
+    },
+}
+
+impl DensityMap {
+    /// Returns probability of placement at specified point on XZ plane.
+    pub fn sample(&self, x: f32, z: f32) -> f32 {
+        let value = match self {
+            DensityMap::Uniform(value) => *value,
+            DensityMap::Noise { noise, frequency } => {
+                noise.noise2(x * frequency, z * frequency) * 0.5 + 0.5
+            }
+            DensityMap::Texture {
+                texture,
+                texel_size,
+            } => texture.sample(
+                (x / texel_size).floor() as isize,
+                (z / texel_size).floor() as isize,
+            ),
+        };
+
+        value.max(0.0).min(1.0)
+    }
+}
+
+/// Level of detail of scattered prop.
+#[derive(Clone, Debug)]
+pub struct ScatterLod {
+    pub renderable: Renderable,
+
+    /// Maximum distance from camera to chunk center
+    /// at which this LOD is used.
+    pub distance: f32,
+}
+
+/// Layer of props scattered with same rules.
+#[derive(Clone)]
+pub struct ScatterLayer {
+    lods: Vec<ScatterLod>,
+    density: f32,
+    density_map: DensityMap,
+    scale: (f32, f32),
+    max_slope: f32,
+    align_to_normal: bool,
+    ray_traced: bool,
+    seed: u64,
+}
+
+impl ScatterLayer {
+    /// Creates layer with LODs sorted from closest to farthest.
+    /// Last LOD distance is the view distance of the layer.
+    ///
+    /// Density is number of candidate instances per square unit.
+    pub fn new(mut lods: Vec<ScatterLod>, density: f32) -> Self {
+        lods.sort_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap());
+
+        ScatterLayer {
+            lods,
+            density,
+            density_map: DensityMap::Uniform(1.0),
+            scale: (1.0, 1.0),
+            max_slope: 1.0,
+            align_to_normal: false,
+            ray_traced: true,
+            seed: 0,
+        }
+    }
+
+    pub fn with_density_map(mut self, density_map: DensityMap) -> Self {
+        self.density_map = density_map;
+        self
+    }
+
+    /// Sets range of random uniform scale of instances.
+    pub fn with_scale(mut self, min: f32, max: f32) -> Self {
+        self.scale = (min, max.max(min));
+        self
+    }
+
+    /// Sets maximum slope as sine of surface inclination.
+    pub fn with_max_slope(mut self, max_slope: f32) -> Self {
+        self.max_slope = max_slope;
+        self
+    }
+
+    /// Rotates instances to match terrain normal instead of standing
+    /// upright.
+    pub fn with_align_to_normal(mut self, align: bool) -> Self {
+        self.align_to_normal = align;
+        self
+    }
+
+    /// Sets whether instances are included into acceleration structures.
+    /// Small props like grass may be excluded to keep TLAS small.
+    pub fn with_ray_traced(mut self, ray_traced: bool) -> Self {
+        self.ray_traced = ray_traced;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    fn view_distance(&self) -> f32 {
+        self.lods.last().map_or(0.0, |lod| lod.distance)
+    }
+
+    fn lod(&self, distance: f32) -> Option<usize> {
+        self.lods.iter().position(|lod| distance <= lod.distance)
+    }
+}
+
+/// Marks entities spawned by `ScatterSystem`.
+#[derive(Clone, Copy, Debug)]
+pub struct Scattered;
+
+type ChunkCoord = (i32, i32);
+
+struct Chunk {
+    lod: usize,
+    instances: Vec<Entity>,
+}
+
+struct LayerState {
+    layer: ScatterLayer,
+    chunks: HashMap<ChunkCoord, Chunk>,
+}
+
+/// Terrain collider prepared for ray casting.
+struct TerrainShape {
+    shape: ShapeHandle<f32>,
+    transform: na::Matrix4<f32>,
+    inverse: na::Matrix4<f32>,
+}
+
+/// System that streams scattered props around camera.
+pub struct ScatterSystem {
+    layers: Vec<LayerState>,
+    chunk_size: f32,
+    budget: usize,
+}
+
+impl ScatterSystem {
+    pub fn new() -> Self {
+        ScatterSystem {
+            layers: Vec::new(),
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            budget: DEFAULT_CHUNK_BUDGET,
+        }
+    }
+
+    pub fn with_chunk_size(mut self, chunk_size: f32) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Sets maximum number of chunks generated per frame.
+    pub fn with_budget(mut self, budget: usize) -> Self {
+        self.budget = budget.max(1);
+        self
+    }
+
+    pub fn with_layer(mut self, layer: ScatterLayer) -> Self {
+        self.add_layer(layer);
+        self
+    }
+
+    pub fn add_layer(&mut self, layer: ScatterLayer) {
+        self.layers.push(LayerState {
+            layer,
+            chunks: HashMap::new(),
+        });
+    }
+
+    /// Despawns all scattered instances.
+    /// They will be generated again when in view distance.
+    pub fn clear(&mut self, world: &mut World) {
+        for state in &mut self.layers {
+            for (_, chunk) in state.chunks.drain() {
+                despawn_chunk(world, chunk);
+            }
+        }
+    }
+}
+
+impl Default for ScatterSystem {
+    fn default() -> Self {
+        ScatterSystem::new()
+    }
+}
+
+impl System for ScatterSystem {
+    fn run(&mut self, ctx: SystemContext<'_>) {
+        let camera = ctx
+            .world
+            .query::<&Global3>()
+            .with::<Camera>()
+            .iter()
+            .next()
+            .map(|(_, global)| global.iso.translation.vector);
+
+        let camera = match camera {
+            Some(camera) => na::Vector2::new(camera.x, camera.z),
+            None => return,
+        };
+
+        let terrains = terrain_shapes(ctx.world);
+        let chunk_size = self.chunk_size;
+        let mut budget = self.budget;
+
+        for (index, state) in self.layers.iter_mut().enumerate() {
+            let view_distance = state.layer.view_distance();
+
+            // Update LODs and unload chunks that are too far.
+            // Chunks are unloaded a bit farther than loaded
+            // to avoid thrashing on the boundary.
+            let mut unload = Vec::new();
+            for (&coord, chunk) in &mut state.chunks {
+                let distance = chunk_distance(coord, chunk_size, camera);
+                if distance > view_distance + chunk_size {
+                    unload.push(coord);
+                    continue;
+                }
+
+                let lod = state.layer.lod(distance).unwrap_or(chunk.lod);
+                if lod != chunk.lod {
+                    chunk.lod = lod;
+                    let renderable = &state.layer.lods[lod].renderable;
+                    for &entity in &chunk.instances {
+                        let _ =
+                            ctx.world.insert_one(entity, renderable.clone());
+                    }
+                }
+            }
+
+            for coord in unload {
+                let chunk = state.chunks.remove(&coord).unwrap();
+                despawn_chunk(ctx.world, chunk);
+            }
+
+            if terrains.is_empty() || budget == 0 {
+                continue;
+            }
+
+            // Generate missing chunks from closest to farthest.
+            let radius = (view_distance / chunk_size).ceil() as i32;
+            let center = (
+                (camera.x / chunk_size).floor() as i32,
+                (camera.y / chunk_size).floor() as i32,
+            );
+
+            let mut missing = Vec::new();
+            for x in center.0 - radius..=center.0 + radius {
+                for z in center.1 - radius..=center.1 + radius {
+                    let coord = (x, z);
+                    if state.chunks.contains_key(&coord) {
+                        continue;
+                    }
+
+                    let distance = chunk_distance(coord, chunk_size, camera);
+                    if let Some(lod) = state.layer.lod(distance) {
+                        missing.push((distance, coord, lod));
+                    }
+                }
+            }
+
+            missing.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+            for (_, coord, lod) in missing.into_iter().take(budget) {
+                budget -= 1;
+
+                let instances = generate_chunk(
+                    &state.layer,
+                    index,
+                    coord,
+                    chunk_size,
+                    lod,
+                    &terrains,
+                    ctx.world,
+                );
+
+                state.chunks.insert(coord, Chunk { lod, instances });
+            }
+        }
+    }
+}
+
+fn chunk_distance(
+    coord: ChunkCoord,
+    chunk_size: f32,
+    camera: na::Vector2<f32>,
+) -> f32 {
+    let center = na::Vector2::new(
+        (coord.0 as f32 + 0.5) * chunk_size,
+        (coord.1 as f32 + 0.5) * chunk_size,
+    );
+    (center - camera).magnitude()
+}
+
+fn despawn_chunk(world: &mut World, chunk: Chunk) {
+    for entity in chunk.instances {
+        let _ = world.despawn(entity);
+    }
+}
+
+fn terrain_shapes(world: &World) -> Vec<TerrainShape> {
+    world
+        .query::<(&Colliders, &Global3)>()
+        .with::<Terrain>()
+        .iter()
+        .flat_map(|(_, (colliders, global))| {
+            let transform = global.to_homogeneous();
+            let inverse = transform.try_inverse();
+            colliders.iter().filter_map(move |desc| {
+                Some(TerrainShape {
+                    shape: desc.get_shape().clone(),
+                    transform,
+                    inverse: inverse?,
+                })
+            })
+        })
+        .collect()
+}
+
+/// Casts ray down onto terrain.
+/// Returns highest hit point and surface normal.
+fn cast_down(
+    terrains: &[TerrainShape],
+    x: f32,
+    z: f32,
+) -> Option<(na::Point3<f32>, na::Vector3<f32>)> {
+    let origin = na::Point3::new(x, RAY_HEIGHT, z);
+    let dir = -na::Vector3::y();
+
+    let mut closest: Option<(f32, na::Point3<f32>, na::Vector3<f32>)> = None;
+
+    for terrain in terrains {
+        // Ray parameter is preserved by affine transformation.
+        let ray = Ray::new(
+            terrain.inverse.transform_point(&origin),
+            terrain.inverse.transform_vector(&dir),
+        );
+
+        let ray_cast = match terrain.shape.as_ray_cast() {
+            Some(ray_cast) => ray_cast,
+            None => continue,
+        };
+
+        let hit = ray_cast.toi_and_normal_with_ray(
+            &na::Isometry3::identity(),
+            &ray,
+            f32::MAX,
+            true,
+        );
+
+        if let Some(hit) = hit {
+            if closest.map_or(true, |(toi, _, _)| hit.toi < toi) {
+                let normal = terrain
+                    .inverse
+                    .transpose()
+                    .transform_vector(&hit.normal)
+                    .normalize();
+                let point =
+                    terrain.transform.transform_point(&ray.point_at(hit.toi));
+                closest = Some((hit.toi, point, normal));
+            }
+        }
+    }
+
+    closest.map(|(_, point, normal)| (point, normal))
+}
+
+fn generate_chunk(
+    layer: &ScatterLayer,
+    layer_index: usize,
+    coord: ChunkCoord,
+    chunk_size: f32,
+    lod: usize,
+    terrains: &[TerrainShape],
+    world: &mut World,
+) -> Vec<Entity> {
+    // Same chunk always gets same instances.
+    let seed = layer.seed
+        ^ (layer_index as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (coord.0 as u32 as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+        ^ (coord.1 as u32 as u64).wrapping_mul(0x1656_67B1_9E37_79F9);
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let candidates = (layer.density * chunk_size * chunk_size).round() as usize;
+    let renderable = &layer.lods[lod].renderable;
+    let mut instances = Vec::new();
+
+    for _ in 0..candidates {
+        let x = (coord.0 as f32 + rng.gen::<f32>()) * chunk_size;
+        let z = (coord.1 as f32 + rng.gen::<f32>()) * chunk_size;
+        let accept = rng.gen::<f32>();
+        let yaw = rng.gen::<f32>() * std::f32::consts::TAU;
+        let scale =
+            layer.scale.0 + (layer.scale.1 - layer.scale.0) * rng.gen::<f32>();
+
+        if accept >= layer.density_map.sample(x, z) {
+            continue;
+        }
+
+        let (point, normal) = match cast_down(terrains, x, z) {
+            Some(hit) => hit,
+            None => continue,
+        };
+
+        let slope = normal.cross(&na::Vector3::y()).magnitude();
+        if slope > layer.max_slope {
+            continue;
+        }
+
+        let mut rotation =
+            na::UnitQuaternion::from_axis_angle(&na::Vector3::y_axis(), yaw);
+
+        if layer.align_to_normal {
+            if let Some(tilt) =
+                na::UnitQuaternion::rotation_between(&na::Vector3::y(), &normal)
+            {
+                rotation = tilt * rotation;
+            }
+        }
+
+        let global = Global3 {
+            iso: na::Isometry3::from_parts(point.coords.into(), rotation),
+            skew: na::Matrix3::from_diagonal_element(scale),
+        };
+
+        let entity = world.spawn((global, renderable.clone(), Scattered));
+        if !layer.ray_traced {
+            let _ = world.insert_one(entity, RasterOnly);
+        }
+
+        instances.push(entity);
+    }
+
+    instances
+}
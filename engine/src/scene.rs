@@ -5,8 +5,9 @@ use {
     },
     bumpalo::{collections::Vec as BVec, Bump},
     fastbitset::BumpBitSet,
-    hecs::{Entity, EntityRef, World},
+    hecs::{Component, Entity, EntityRef, World},
     nalgebra as na,
+    std::sync::atomic::{AtomicU64, Ordering},
 };
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -143,6 +144,89 @@ impl Global3 {
     }
 }
 
+/// Next generation assigned by `track_changes`.
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(1);
+
+/// Generation of tracked component modifications.
+/// Generations increase monotonically with every `track_changes` call.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Generation(u64);
+
+impl Generation {
+    /// Generation that precedes all tracked modifications.
+    pub const ZERO: Generation = Generation(0);
+}
+
+/// Last seen value of component `T` of the same entity
+/// and generation at which it was modified.
+///
+/// Attached and updated by `track_changes::<T>`.
+#[derive(Clone, Debug)]
+pub struct Changed<T> {
+    value: T,
+    generation: Generation,
+}
+
+impl<T> Changed<T> {
+    pub fn generation(&self) -> Generation {
+        self.generation
+    }
+
+    /// Checks if component was modified after specified generation.
+    pub fn since(&self, generation: Generation) -> bool {
+        self.generation > generation
+    }
+}
+
+/// Checks if component was modified after specified generation.
+/// Untracked components are always considered modified.
+pub fn is_changed<T>(
+    changed: Option<&Changed<T>>,
+    generation: Generation,
+) -> bool {
+    changed.map_or(true, |changed| changed.since(generation))
+}
+
+/// Detects modifications of component `T` since previous call
+/// and stamps them with new generation which is returned.
+///
+/// Components are compared with values seen by previous call,
+/// so modifications are detected regardless of how they were made.
+/// Newly attached components are considered modified.
+pub fn track_changes<T>(world: &mut World) -> Generation
+where
+    T: Component + Clone + PartialEq,
+{
+    let generation =
+        Generation(NEXT_GENERATION.fetch_add(1, Ordering::Relaxed));
+
+    for (_, (value, changed)) in world.query::<(&T, &mut Changed<T>)>().iter() {
+        if changed.value != *value {
+            changed.value = value.clone();
+            changed.generation = generation;
+        }
+    }
+
+    let untracked: Vec<_> = world
+        .query::<&T>()
+        .without::<Changed<T>>()
+        .iter()
+        .map(|(entity, value)| {
+            let changed = Changed {
+                value: value.clone(),
+                generation,
+            };
+            (entity, changed)
+        })
+        .collect();
+
+    for (entity, changed) in untracked {
+        let _ = world.insert_one(entity, changed);
+    }
+
+    generation
+}
+
 pub struct SceneSystem;
 
 impl System for SceneSystem {
@@ -168,6 +252,8 @@ impl System for SceneSystem {
         for entity in despawn {
             let _ = ctx.world.despawn(entity);
         }
+
+        track_changes::<Global3>(ctx.world);
     }
 }
 
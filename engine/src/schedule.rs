@@ -0,0 +1,278 @@
+//! Stage-based system scheduler.
+//!
+//! Exclusive systems run one after another with mutable access to everything.
+//! Parallel systems added between exclusive ones form a stage.
+//! Systems of a stage are split into batches of systems with
+//! non-conflicting access, each batch is run on rayon thread pool.
+
+use {
+    crate::{
+        clocks::ClockIndex,
+        engine::{InputEvents, System, SystemContext},
+    },
+    bumpalo::Bump,
+    hecs::{Component, World},
+    parking_lot::{
+        MappedRwLockReadGuard, MappedRwLockWriteGuard, RwLock, RwLockReadGuard,
+        RwLockWriteGuard,
+    },
+    smallvec::SmallVec,
+    std::{
+        any::{Any, TypeId},
+        collections::HashMap,
+    },
+    type_map::TypeMap,
+};
+
+/// Components and resources accessed by parallel system.
+#[derive(Clone, Debug, Default)]
+pub struct Access {
+    reads: SmallVec<[TypeId; 8]>,
+    writes: SmallVec<[TypeId; 8]>,
+}
+
+impl Access {
+    pub fn new() -> Self {
+        Access::default()
+    }
+
+    /// Declares shared access to component.
+    pub fn read<T: Component>(mut self) -> Self {
+        self.reads.push(TypeId::of::<T>());
+        self
+    }
+
+    /// Declares unique access to component.
+    pub fn write<T: Component>(mut self) -> Self {
+        self.writes.push(TypeId::of::<T>());
+        self
+    }
+
+    /// Declares shared access to resource in `SharedResources`.
+    pub fn read_resource<T: Send + Sync + 'static>(mut self) -> Self {
+        self.reads.push(resource_id::<T>());
+        self
+    }
+
+    /// Declares unique access to resource in `SharedResources`.
+    pub fn write_resource<T: Send + Sync + 'static>(mut self) -> Self {
+        self.writes.push(resource_id::<T>());
+        self
+    }
+
+    /// Returns `true` if systems with these accesses
+    /// can't run concurrently.
+    pub fn conflicts(&self, other: &Access) -> bool {
+        self.writes
+            .iter()
+            .any(|id| other.reads.contains(id) || other.writes.contains(id))
+            || other.writes.iter().any(|id| self.reads.contains(id))
+    }
+}
+
+/// Returns id of resource access.
+/// Distinguishes resources from components of the same type.
+fn resource_id<T: 'static>() -> TypeId {
+    TypeId::of::<fn() -> T>()
+}
+
+/// Resources that can be accessed from parallel systems.
+///
+/// Access must be declared with `Access::read_resource` and
+/// `Access::write_resource`, otherwise systems may block on each other.
+#[derive(Default)]
+pub struct SharedResources {
+    map: HashMap<TypeId, RwLock<Box<dyn Any + Send + Sync>>>,
+}
+
+impl SharedResources {
+    pub fn new() -> Self {
+        SharedResources::default()
+    }
+
+    pub fn insert<T: Send + Sync + 'static>(&mut self, resource: T) {
+        self.map
+            .insert(TypeId::of::<T>(), RwLock::new(Box::new(resource)));
+    }
+
+    pub fn remove<T: Send + Sync + 'static>(&mut self) -> Option<T> {
+        let resource = self.map.remove(&TypeId::of::<T>())?;
+        Some(*resource.into_inner().downcast().unwrap())
+    }
+
+    pub fn get<T: Send + Sync + 'static>(
+        &self,
+    ) -> Option<MappedRwLockReadGuard<'_, T>> {
+        let lock = self.map.get(&TypeId::of::<T>())?;
+        Some(RwLockReadGuard::map(lock.read(), |resource| {
+            resource.downcast_ref().unwrap()
+        }))
+    }
+
+    pub fn get_mut<T: Send + Sync + 'static>(
+        &self,
+    ) -> Option<MappedRwLockWriteGuard<'_, T>> {
+        let lock = self.map.get(&TypeId::of::<T>())?;
+        Some(RwLockWriteGuard::map(lock.write(), |resource| {
+            resource.downcast_mut().unwrap()
+        }))
+    }
+}
+
+pub struct ParallelContext<'a> {
+    pub world: &'a World,
+    pub resources: &'a SharedResources,
+    pub bump: &'a Bump,
+    pub clocks: ClockIndex,
+}
+
+/// System that may run concurrently with other systems.
+pub trait ParallelSystem: Send {
+    /// Returns components and resources this system accesses.
+    /// Accessing anything not declared may panic.
+    fn access(&self) -> Access;
+
+    fn run(&mut self, ctx: ParallelContext<'_>);
+}
+
+struct ParallelEntry {
+    system: Box<dyn ParallelSystem>,
+    access: Access,
+    bump: Bump,
+}
+
+#[derive(Default)]
+struct Stage {
+    systems: Vec<ParallelEntry>,
+
+    /// Indices of systems in each batch.
+    batches: Vec<Vec<usize>>,
+}
+
+impl Stage {
+    fn push(&mut self, system: Box<dyn ParallelSystem>) {
+        let access = system.access();
+        let index = self.systems.len();
+
+        // System must run after all conflicting systems added before it.
+        let after = self
+            .batches
+            .iter()
+            .rposition(|batch| {
+                batch
+                    .iter()
+                    .any(|&i| self.systems[i].access.conflicts(&access))
+            })
+            .map_or(0, |batch| batch + 1);
+
+        match self.batches.get_mut(after) {
+            Some(batch) => batch.push(index),
+            None => self.batches.push(vec![index]),
+        }
+
+        self.systems.push(ParallelEntry {
+            system,
+            access,
+            bump: Bump::new(),
+        });
+    }
+
+    fn run(
+        &mut self,
+        world: &World,
+        resources: &SharedResources,
+        clocks: ClockIndex,
+    ) {
+        let Stage { systems, batches } = self;
+
+        for batch in batches.iter() {
+            rayon::scope(|scope| {
+                for (_, entry) in systems
+                    .iter_mut()
+                    .enumerate()
+                    .filter(|(index, _)| batch.contains(index))
+                {
+                    scope.spawn(move |_| {
+                        entry.bump.reset();
+                        entry.system.run(ParallelContext {
+                            world,
+                            resources,
+                            bump: &entry.bump,
+                            clocks,
+                        });
+                    });
+                }
+            });
+        }
+    }
+}
+
+enum Scheduled {
+    Exclusive(Box<dyn System>, Bump),
+    Parallel(Stage),
+}
+
+/// Ordered list of systems.
+#[derive(Default)]
+pub struct Schedule {
+    scheduled: Vec<Scheduled>,
+}
+
+impl Schedule {
+    pub fn new() -> Self {
+        Schedule::default()
+    }
+
+    /// Adds system that runs exclusively.
+    /// It also ends current stage.
+    pub fn add_system(&mut self, system: Box<dyn System>) {
+        self.scheduled
+            .push(Scheduled::Exclusive(system, Bump::new()));
+    }
+
+    /// Adds system to current stage.
+    pub fn add_parallel_system(&mut self, system: Box<dyn ParallelSystem>) {
+        match self.scheduled.last_mut() {
+            Some(Scheduled::Parallel(stage)) => stage.push(system),
+            _ => {
+                let mut stage = Stage::default();
+                stage.push(system);
+                self.scheduled.push(Scheduled::Parallel(stage));
+            }
+        }
+    }
+
+    /// Ends current stage.
+    /// Parallel systems added after this call run after systems
+    /// added before.
+    pub fn add_barrier(&mut self) {
+        self.scheduled.push(Scheduled::Parallel(Stage::default()));
+    }
+
+    pub fn run(
+        &mut self,
+        world: &mut World,
+        resources: &mut TypeMap,
+        shared: &SharedResources,
+        input: &InputEvents,
+        clocks: ClockIndex,
+    ) {
+        for scheduled in &mut self.scheduled {
+            match scheduled {
+                Scheduled::Exclusive(system, bump) => {
+                    bump.reset();
+                    system.run(SystemContext {
+                        world: &mut *world,
+                        resources: &mut *resources,
+                        input,
+                        clocks,
+                        bump,
+                    });
+                }
+                Scheduled::Parallel(stage) => {
+                    stage.run(world, shared, clocks);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use {
+    super::ScriptComponent,
+    crate::{
+        light::{DirectionalLight, PointLight, SkyLight},
+        scene::Global3,
+    },
+    hecs::{Entity, World},
+    mlua::{Error as LuaError, Lua, Result as LuaResult, Table, Value},
+    nalgebra as na,
+    std::marker::PhantomData,
+};
+
+/// Type-erased access to component of particular type.
+pub(super) trait ComponentAccess {
+    fn get<'lua>(
+        &self,
+        lua: &'lua Lua,
+        world: &World,
+        entity: Entity,
+    ) -> LuaResult<Value<'lua>>;
+
+    fn set<'lua>(
+        &self,
+        lua: &'lua Lua,
+        world: &mut World,
+        entity: Entity,
+        value: Value<'lua>,
+    ) -> LuaResult<()>;
+
+    fn remove(&self, world: &mut World, entity: Entity) -> LuaResult<()>;
+}
+
+pub(super) struct Access<T>(PhantomData<fn() -> T>);
+
+impl<T> Access<T> {
+    pub fn new() -> Self {
+        Access(PhantomData)
+    }
+}
+
+impl<T> ComponentAccess for Access<T>
+where
+    T: ScriptComponent,
+{
+    fn get<'lua>(
+        &self,
+        lua: &'lua Lua,
+        world: &World,
+        entity: Entity,
+    ) -> LuaResult<Value<'lua>> {
+        match world.get::<T>(entity) {
+            Ok(component) => component.to_lua(lua),
+            Err(_) => Ok(Value::Nil),
+        }
+    }
+
+    fn set<'lua>(
+        &self,
+        lua: &'lua Lua,
+        world: &mut World,
+        entity: Entity,
+        value: Value<'lua>,
+    ) -> LuaResult<()> {
+        let component = T::from_lua(value, lua)?;
+        world
+            .insert_one(entity, component)
+            .map_err(LuaError::external)
+    }
+
+    fn remove(&self, world: &mut World, entity: Entity) -> LuaResult<()> {
+        match world.remove_one::<T>(entity) {
+            Ok(_) | Err(hecs::ComponentError::MissingComponent(_)) => Ok(()),
+            Err(err) => Err(LuaError::external(err)),
+        }
+    }
+}
+
+pub(super) fn entity_to_lua(entity: Entity) -> i64 {
+    entity.to_bits() as i64
+}
+
+pub(super) fn entity_from_lua(entity: i64) -> Entity {
+    Entity::from_bits(entity as u64)
+}
+
+/// Converts vector into `{ x, y, z }` table.
+pub fn vector_to_lua<'lua>(
+    lua: &'lua Lua,
+    v: &na::Vector3<f32>,
+) -> LuaResult<Table<'lua>> {
+    let table = lua.create_table()?;
+    table.set("x", v.x)?;
+    table.set("y", v.y)?;
+    table.set("z", v.z)?;
+    Ok(table)
+}
+
+/// Converts `{ x, y, z }` table into vector.
+/// Missing components are zero.
+pub fn vector_from_lua(table: &Table<'_>) -> LuaResult<na::Vector3<f32>> {
+    Ok(na::Vector3::new(
+        table.get::<_, Option<f32>>("x")?.unwrap_or(0.0),
+        table.get::<_, Option<f32>>("y")?.unwrap_or(0.0),
+        table.get::<_, Option<f32>>("z")?.unwrap_or(0.0),
+    ))
+}
+
+fn color_to_lua<'lua>(
+    lua: &'lua Lua,
+    color: &[f32; 3],
+) -> LuaResult<Table<'lua>> {
+    lua.create_sequence_from(color.iter().copied())
+}
+
+fn color_from_lua(table: &Table<'_>) -> LuaResult<[f32; 3]> {
+    Ok([table.get(1)?, table.get(2)?, table.get(3)?])
+}
+
+fn table<'lua>(value: Value<'lua>, lua: &'lua Lua) -> LuaResult<Table<'lua>> {
+    lua.unpack(value)
+}
+
+/// Represented as `{ position, rotation, scale }` table.
+/// Rotation is quaternion `{ x, y, z, w }`.
+impl ScriptComponent for Global3 {
+    const NAME: &'static str = "Global3";
+
+    fn to_lua<'lua>(&self, lua: &'lua Lua) -> LuaResult<Value<'lua>> {
+        let table = lua.create_table()?;
+        table.set(
+            "position",
+            vector_to_lua(lua, &self.iso.translation.vector)?,
+        )?;
+
+        let rotation = lua.create_table()?;
+        rotation.set("x", self.iso.rotation.i)?;
+        rotation.set("y", self.iso.rotation.j)?;
+        rotation.set("z", self.iso.rotation.k)?;
+        rotation.set("w", self.iso.rotation.w)?;
+        table.set("rotation", rotation)?;
+
+        table.set("scale", vector_to_lua(lua, &self.skew.diagonal())?)?;
+        Ok(Value::Table(table))
+    }
+
+    fn from_lua<'lua>(value: Value<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        let table = table(value, lua)?;
+
+        let translation = match table.get::<_, Option<Table>>("position")? {
+            Some(position) => vector_from_lua(&position)?,
+            None => na::Vector3::zeros(),
+        };
+
+        let rotation = match table.get::<_, Option<Table>>("rotation")? {
+            Some(rotation) => {
+                na::UnitQuaternion::from_quaternion(na::Quaternion::new(
+                    rotation.get("w")?,
+                    rotation.get("x")?,
+                    rotation.get("y")?,
+                    rotation.get("z")?,
+                ))
+            }
+            None => na::UnitQuaternion::identity(),
+        };
+
+        let scale = match table.get::<_, Option<Table>>("scale")? {
+            Some(scale) => vector_from_lua(&scale)?,
+            None => na::Vector3::new(1.0, 1.0, 1.0),
+        };
+
+        Ok(Global3 {
+            iso: na::Isometry3::from_parts(translation.into(), rotation),
+            skew: na::Matrix3::from_diagonal(&scale),
+        })
+    }
+}
+
+/// Represented as `{ radiance }` table.
+impl ScriptComponent for PointLight {
+    const NAME: &'static str = "PointLight";
+
+    fn to_lua<'lua>(&self, lua: &'lua Lua) -> LuaResult<Value<'lua>> {
+        let table = lua.create_table()?;
+        table.set("radiance", color_to_lua(lua, &self.radiance)?)?;
+        Ok(Value::Table(table))
+    }
+
+    fn from_lua<'lua>(value: Value<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        let table = table(value, lua)?;
+        Ok(PointLight {
+            radiance: color_from_lua(&table.get("radiance")?)?,
+        })
+    }
+}
+
+/// Represented as `{ direction, radiance }` table.
+impl ScriptComponent for DirectionalLight {
+    const NAME: &'static str = "DirectionalLight";
+
+    fn to_lua<'lua>(&self, lua: &'lua Lua) -> LuaResult<Value<'lua>> {
+        let table = lua.create_table()?;
+        table.set("direction", vector_to_lua(lua, &self.direction)?)?;
+        table.set("radiance", color_to_lua(lua, &self.radiance)?)?;
+        Ok(Value::Table(table))
+    }
+
+    fn from_lua<'lua>(value: Value<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        let table = table(value, lua)?;
+        Ok(DirectionalLight {
+            direction: vector_from_lua(&table.get("direction")?)?,
+            radiance: color_from_lua(&table.get("radiance")?)?,
+        })
+    }
+}
+
+/// Represented as `{ radiance }` table.
+impl ScriptComponent for SkyLight {
+    const NAME: &'static str = "SkyLight";
+
+    fn to_lua<'lua>(&self, lua: &'lua Lua) -> LuaResult<Value<'lua>> {
+        let table = lua.create_table()?;
+        table.set("radiance", color_to_lua(lua, &self.radiance)?)?;
+        Ok(Value::Table(table))
+    }
+
+    fn from_lua<'lua>(value: Value<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        let table = table(value, lua)?;
+        Ok(SkyLight {
+            radiance: color_from_lua(&table.get("radiance")?)?,
+        })
+    }
+}
@@ -0,0 +1,545 @@
+//! Lua scripting for gameplay logic.
+//!
+//! Entities with `Script` component are driven by Lua modules
+//! loaded through assets cache.
+//! Module is a table that may define following functions:
+//!
+//! * `start(world, entity)` - called once for each entity.
+//! * `update(world, entity, delta)` - called every frame.
+//! * `events[name](world, entity, source, value)` - called for each
+//!   `ScriptEvent` with matching name.
+//!
+//! `world` table exposes `get`, `set`, `remove`, `spawn`, `despawn`,
+//! `spawn_prefab` and `emit` functions.
+//! Only components registered with `ScriptSystem::register_component`
+//! are accessible.
+
+mod api;
+
+pub use self::api::{vector_from_lua, vector_to_lua};
+
+use {
+    self::api::{entity_from_lua, entity_to_lua, Access, ComponentAccess},
+    crate::{
+        assets::{Asset, AssetKey, Assets, Format, Prefab},
+        broker::EventBroker,
+        engine::{Engine, PrefabLoader, System, SystemContext},
+        light::{DirectionalLight, PointLight, SkyLight},
+        scene::Global3,
+    },
+    flume::{Receiver, Sender},
+    hecs::{Entity, World},
+    mlua::{
+        Error as LuaError, Function, Lua, RegistryKey, Result as LuaResult,
+        Table, Value,
+    },
+    std::{
+        cell::RefCell,
+        collections::{HashMap, HashSet},
+        path::{Path, PathBuf},
+        time::{Duration, Instant, SystemTime},
+    },
+    winit::event::{DeviceEvent, ElementState, Event},
+};
+
+/// Interval between checks for modified script files.
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Component that attaches Lua module to an entity.
+#[derive(Clone, Debug)]
+pub struct Script {
+    pub key: AssetKey,
+}
+
+impl Script {
+    pub fn new(key: AssetKey) -> Self {
+        Script { key }
+    }
+}
+
+/// Event delivered to scripts.
+///
+/// Gameplay systems send events to scripts through `ScriptEvents` resource.
+/// Scripts emit events with `world.emit`.
+#[derive(Clone, Debug)]
+pub struct ScriptEvent {
+    pub name: String,
+    pub entity: Option<Entity>,
+    pub value: f64,
+}
+
+/// Resource with events for scripts.
+/// Cleared by `ScriptSystem` after events are delivered.
+pub type ScriptEvents = EventBroker<ScriptEvent>;
+
+/// Component that can be accessed from scripts.
+pub trait ScriptComponent: hecs::Component + Sized {
+    /// Name of the component in scripts.
+    const NAME: &'static str;
+
+    fn to_lua<'lua>(&self, lua: &'lua Lua) -> LuaResult<Value<'lua>>;
+
+    fn from_lua<'lua>(value: Value<'lua>, lua: &'lua Lua) -> LuaResult<Self>;
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ScriptError {
+    #[error("Failed to load script: `{source}`")]
+    Load {
+        #[from]
+        source: goods::Error,
+    },
+
+    #[error("Script is not valid UTF-8: `{source}`")]
+    Utf8 {
+        #[from]
+        source: std::string::FromUtf8Error,
+    },
+
+    #[error("Failed to read script file: `{source}`")]
+    Io {
+        #[from]
+        source: std::io::Error,
+    },
+}
+
+type SpawnPrefab =
+    Box<dyn Fn(&PrefabLoader, &World, AssetKey, Global3) -> Entity>;
+
+struct Module {
+    /// Module table. `None` while loading or if module failed.
+    table: Option<RegistryKey>,
+    started: HashSet<Entity>,
+    path: Option<PathBuf>,
+    modified: Option<SystemTime>,
+}
+
+/// System that runs Lua scripts.
+///
+/// Scripts are reloaded when their files are modified.
+pub struct ScriptSystem {
+    lua: Lua,
+    assets: Assets,
+    loader: PrefabLoader,
+    roots: Vec<PathBuf>,
+    components: HashMap<&'static str, Box<dyn ComponentAccess>>,
+    prefabs: HashMap<String, SpawnPrefab>,
+    modules: HashMap<AssetKey, Module>,
+    sender: Sender<(AssetKey, Result<String, ScriptError>)>,
+    receiver: Receiver<(AssetKey, Result<String, ScriptError>)>,
+    last_reload_check: Instant,
+}
+
+impl ScriptSystem {
+    pub fn new(engine: &Engine) -> Self {
+        let (sender, receiver) = flume::unbounded();
+
+        let mut system = ScriptSystem {
+            lua: Lua::new(),
+            assets: engine.assets.clone(),
+            loader: engine.prefab_loader(),
+            roots: engine.asset_roots().to_vec(),
+            components: HashMap::new(),
+            prefabs: HashMap::new(),
+            modules: HashMap::new(),
+            sender,
+            receiver,
+            last_reload_check: Instant::now(),
+        };
+
+        system.register_component::<Global3>();
+        system.register_component::<PointLight>();
+        system.register_component::<DirectionalLight>();
+        system.register_component::<SkyLight>();
+        system
+    }
+
+    /// Makes component accessible from scripts.
+    pub fn register_component<T>(&mut self)
+    where
+        T: ScriptComponent,
+    {
+        self.components
+            .insert(T::NAME, Box::new(Access::<T>::new()));
+    }
+
+    /// Allows scripts to spawn prefabs of specified kind
+    /// with `world.spawn_prefab(kind, key, transform)`.
+    pub fn register_prefab<P, F>(&mut self, kind: &str, format: F)
+    where
+        P: Prefab<Info = Global3> + Asset + Clone,
+        F: Format<P, AssetKey> + Clone + Send + 'static,
+    {
+        self.prefabs.insert(
+            kind.to_owned(),
+            Box::new(move |loader, world, key, global| {
+                loader.load_prefab_with_format::<P, F>(
+                    world,
+                    key,
+                    global,
+                    format.clone(),
+                )
+            }),
+        );
+    }
+
+    fn request_load(&mut self, key: AssetKey) {
+        tracing::info!("Loading script '{}'", key);
+
+        let path = self
+            .roots
+            .iter()
+            .map(|root| root.join(&*key))
+            .find(|path| path.is_file());
+
+        self.modules.insert(
+            key.clone(),
+            Module {
+                table: None,
+                started: HashSet::new(),
+                modified: path.as_ref().and_then(|path| modified(path)),
+                path,
+            },
+        );
+
+        let assets = self.assets.clone();
+        let sender = self.sender.clone();
+
+        smol::spawn(async move {
+            let result = async {
+                let bytes = assets.load::<Box<[u8]>>(key.clone()).await?;
+                Ok::<_, ScriptError>(String::from_utf8(bytes.into_vec())?)
+            }
+            .await;
+
+            let _ = sender.send((key, result));
+        })
+        .detach();
+    }
+
+    /// Checks script files modification time and reloads modified ones.
+    fn check_reload(&mut self) {
+        if self.last_reload_check.elapsed() < RELOAD_CHECK_INTERVAL {
+            return;
+        }
+        self.last_reload_check = Instant::now();
+
+        for (key, module) in &mut self.modules {
+            let path = match &module.path {
+                Some(path) => path,
+                None => continue,
+            };
+
+            let time = modified(path);
+            if time.is_none() || time == module.modified {
+                continue;
+            }
+            module.modified = time;
+
+            tracing::info!("Reloading script '{}'", key);
+            let result = std::fs::read_to_string(path).map_err(Into::into);
+            let _ = self.sender.send((key.clone(), result));
+        }
+    }
+
+    /// Compiles loaded modules.
+    fn compile_loaded(&mut self) {
+        for (key, result) in self.receiver.try_iter() {
+            let module = match self.modules.get_mut(&key) {
+                Some(module) => module,
+                None => continue,
+            };
+
+            let source = match result {
+                Ok(source) => source,
+                Err(err) => {
+                    tracing::error!("Failed to load script '{}': {}", key, err);
+                    continue;
+                }
+            };
+
+            let table = self
+                .lua
+                .load(&source)
+                .set_name(&*key)
+                .and_then(|chunk| chunk.eval::<Table>())
+                .and_then(|table| self.lua.create_registry_value(table));
+
+            match table {
+                Ok(table) => {
+                    if let Some(old) = module.table.replace(table) {
+                        let _ = self.lua.remove_registry_value(old);
+                    }
+                }
+                Err(err) => {
+                    tracing::error!(
+                        "Failed to compile script '{}': {}",
+                        key,
+                        err
+                    );
+                }
+            }
+        }
+    }
+}
+
+impl System for ScriptSystem {
+    fn run(&mut self, ctx: SystemContext<'_>) {
+        self.check_reload();
+        self.compile_loaded();
+
+        let scripted: Vec<(Entity, AssetKey)> = ctx
+            .world
+            .query::<&Script>()
+            .iter()
+            .map(|(entity, script)| (entity, script.key.clone()))
+            .collect();
+
+        for (_, key) in &scripted {
+            if !self.modules.contains_key(key) {
+                self.request_load(key.clone());
+            }
+        }
+
+        let mut events = Vec::new();
+        if let Some(script_events) = ctx.resources.get_mut::<ScriptEvents>() {
+            events.extend(script_events.read().cloned());
+            script_events.clear();
+        }
+
+        for event in ctx.input.read() {
+            if let Event::DeviceEvent {
+                event: DeviceEvent::Key(input),
+                ..
+            } = event
+            {
+                if let Some(code) = input.virtual_keycode {
+                    let name = match input.state {
+                        ElementState::Pressed => "key_pressed",
+                        ElementState::Released => "key_released",
+                    };
+
+                    events.push(ScriptEvent {
+                        name: format!("{}:{:?}", name, code),
+                        entity: None,
+                        value: 0.0,
+                    });
+                }
+            }
+        }
+
+        let delta = ctx.clocks.delta.as_secs_f64();
+        let emitted = RefCell::new(Vec::new());
+
+        let ScriptSystem {
+            lua,
+            loader,
+            components,
+            prefabs,
+            modules,
+            ..
+        } = self;
+
+        let loader = &*loader;
+        let components = &*components;
+        let prefabs = &*prefabs;
+        let world = RefCell::new(ctx.world);
+
+        let result = lua.scope(|scope| {
+            let api = lua.create_table()?;
+
+            api.set(
+                "get",
+                scope.create_function(
+                    |lua, (entity, name): (i64, String)| {
+                        let access = component_access(components, &name)?;
+                        let world = world.borrow();
+                        access.get(lua, &world, entity_from_lua(entity))
+                    },
+                )?,
+            )?;
+
+            api.set(
+                "set",
+                scope.create_function(
+                    |lua, (entity, name, value): (i64, String, Value)| {
+                        let access = component_access(components, &name)?;
+                        let mut world = world.borrow_mut();
+                        access.set(
+                            lua,
+                            &mut world,
+                            entity_from_lua(entity),
+                            value,
+                        )
+                    },
+                )?,
+            )?;
+
+            api.set(
+                "remove",
+                scope.create_function(|_, (entity, name): (i64, String)| {
+                    let access = component_access(components, &name)?;
+                    let mut world = world.borrow_mut();
+                    access.remove(&mut world, entity_from_lua(entity))
+                })?,
+            )?;
+
+            api.set(
+                "spawn",
+                scope.create_function(|_, ()| {
+                    let entity = world.borrow_mut().spawn(());
+                    Ok(entity_to_lua(entity))
+                })?,
+            )?;
+
+            api.set(
+                "despawn",
+                scope.create_function(|_, entity: i64| {
+                    let _ = world.borrow_mut().despawn(entity_from_lua(entity));
+                    Ok(())
+                })?,
+            )?;
+
+            api.set(
+                "spawn_prefab",
+                scope.create_function(
+                    |lua, (kind, key, global): (String, String, Value)| {
+                        let spawn = prefabs.get(&kind).ok_or_else(|| {
+                            LuaError::RuntimeError(format!(
+                                "Unknown prefab kind '{}'",
+                                kind
+                            ))
+                        })?;
+
+                        let global = match global {
+                            Value::Nil => Global3::identity(),
+                            global => Global3::from_lua(global, lua)?,
+                        };
+
+                        let world = world.borrow();
+                        let entity = spawn(loader, &world, key.into(), global);
+                        Ok(entity_to_lua(entity))
+                    },
+                )?,
+            )?;
+
+            api.set(
+                "emit",
+                scope.create_function(
+                    |_,
+                     (name, entity, value): (
+                        String,
+                        Option<i64>,
+                        Option<f64>,
+                    )| {
+                        emitted.borrow_mut().push(ScriptEvent {
+                            name,
+                            entity: entity.map(entity_from_lua),
+                            value: value.unwrap_or(0.0),
+                        });
+                        Ok(())
+                    },
+                )?,
+            )?;
+
+            for (entity, key) in &scripted {
+                let module = match modules.get_mut(key) {
+                    Some(module) => module,
+                    None => continue,
+                };
+
+                let table = match &module.table {
+                    Some(table) => lua.registry_value::<Table>(table)?,
+                    None => continue,
+                };
+
+                let lua_entity = entity_to_lua(*entity);
+
+                let result = (|| {
+                    if module.started.insert(*entity) {
+                        if let Some(start) =
+                            table.get::<_, Option<Function>>("start")?
+                        {
+                            start.call::<_, ()>((api.clone(), lua_entity))?;
+                        }
+                    }
+
+                    if let Some(handlers) =
+                        table.get::<_, Option<Table>>("events")?
+                    {
+                        for event in &events {
+                            if let Some(handler) = handlers
+                                .get::<_, Option<Function>>(
+                                    event.name.as_str(),
+                                )?
+                            {
+                                handler.call::<_, ()>((
+                                    api.clone(),
+                                    lua_entity,
+                                    event.entity.map(entity_to_lua),
+                                    event.value,
+                                ))?;
+                            }
+                        }
+                    }
+
+                    if let Some(update) =
+                        table.get::<_, Option<Function>>("update")?
+                    {
+                        update.call::<_, ()>((
+                            api.clone(),
+                            lua_entity,
+                            delta,
+                        ))?;
+                    }
+
+                    Ok::<_, LuaError>(())
+                })();
+
+                if let Err(err) = result {
+                    tracing::error!("Script '{}' failed: {}", key, err);
+                }
+            }
+
+            Ok(())
+        });
+
+        if let Err(err) = result {
+            tracing::error!("Failed to run scripts: {}", err);
+        }
+
+        // Forget entities that are no longer scripted.
+        let world = world.into_inner();
+        for module in modules.values_mut() {
+            module
+                .started
+                .retain(|&entity| world.get::<Script>(entity).is_ok());
+        }
+
+        let emitted = emitted.into_inner();
+        if !emitted.is_empty() {
+            if !ctx.resources.contains::<ScriptEvents>() {
+                ctx.resources.insert(ScriptEvents::new());
+            }
+
+            let script_events =
+                ctx.resources.get_mut::<ScriptEvents>().unwrap();
+            for event in emitted {
+                script_events.add(event);
+            }
+        }
+    }
+}
+
+fn component_access<'a>(
+    components: &'a HashMap<&'static str, Box<dyn ComponentAccess>>,
+    name: &str,
+) -> LuaResult<&'a dyn ComponentAccess> {
+    components.get(name).map(|access| &**access).ok_or_else(|| {
+        LuaError::RuntimeError(format!("Unknown component '{}'", name))
+    })
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).ok()?.modified().ok()
+}
@@ -0,0 +1,236 @@
+//! Day/night cycle.
+//!
+//! `TimeOfDay` resource is advanced by `DayNightSystem`
+//! which moves the sun and adjusts sun and sky radiance.
+//! Gameplay systems can read `TimeOfDay` directly or react to
+//! `TimeOfDayEvent`s emitted when hours, sunrise or sunset pass.
+
+use {
+    crate::{
+        broker::EventBroker,
+        engine::{System, SystemContext},
+        light::{DirectionalLight, SkyLight},
+    },
+    nalgebra as na,
+    std::f32::consts::PI,
+};
+
+const HOURS_PER_DAY: f32 = 24.0;
+
+/// Current time of day.
+#[derive(
+    Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize,
+)]
+pub struct TimeOfDay {
+    /// Days passed since start.
+    pub day: u32,
+
+    /// Hours since midnight in range `[0, 24)`.
+    pub hours: f32,
+
+    /// Game hours per real second.
+    /// Zero stops the cycle.
+    pub speed: f32,
+}
+
+impl TimeOfDay {
+    /// Creates time at specified hour with real-time speed.
+    pub fn new(hours: f32) -> Self {
+        TimeOfDay {
+            day: 0,
+            hours: hours.rem_euclid(HOURS_PER_DAY),
+            speed: 1.0 / 3600.0,
+        }
+    }
+
+    /// Sets speed so that full day takes specified number of seconds.
+    pub fn with_day_length(mut self, seconds: f32) -> Self {
+        self.speed = HOURS_PER_DAY / seconds;
+        self
+    }
+
+    /// Checks if current hour is within `[from, to)` range.
+    /// Range may wrap around midnight.
+    pub fn is_between(&self, from: f32, to: f32) -> bool {
+        if from <= to {
+            self.hours >= from && self.hours < to
+        } else {
+            self.hours >= from || self.hours < to
+        }
+    }
+
+    /// Sine of sun elevation angle.
+    /// Positive during day and negative during night.
+    pub fn sun_elevation(&self) -> f32 {
+        self.sun_angle().sin()
+    }
+
+    /// Angle of the sun from eastern horizon.
+    fn sun_angle(&self) -> f32 {
+        (self.hours - 6.0) / HOURS_PER_DAY * 2.0 * PI
+    }
+
+    /// Advances time and reports passed events.
+    fn advance(
+        &mut self,
+        hours: f32,
+        events: &mut EventBroker<TimeOfDayEvent>,
+    ) {
+        let was_day = self.sun_elevation() > 0.0;
+        let last_hour = self.hours.floor() as u32;
+
+        let mut total = self.hours + hours;
+        while total >= HOURS_PER_DAY {
+            total -= HOURS_PER_DAY;
+            self.day += 1;
+            events.add(TimeOfDayEvent::NewDay(self.day));
+        }
+        self.hours = total;
+
+        let hour = self.hours.floor() as u32;
+        if hour != last_hour {
+            events.add(TimeOfDayEvent::Hour(hour));
+        }
+
+        let is_day = self.sun_elevation() > 0.0;
+        match (was_day, is_day) {
+            (false, true) => events.add(TimeOfDayEvent::Sunrise),
+            (true, false) => events.add(TimeOfDayEvent::Sunset),
+            _ => {}
+        }
+    }
+}
+
+impl Default for TimeOfDay {
+    fn default() -> Self {
+        TimeOfDay::new(12.0)
+    }
+}
+
+/// Event emitted by `DayNightSystem`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeOfDayEvent {
+    /// New day started.
+    NewDay(u32),
+
+    /// New hour started.
+    Hour(u32),
+
+    /// Sun rose above horizon.
+    Sunrise,
+
+    /// Sun set below horizon.
+    Sunset,
+}
+
+/// Resource with time of day events.
+/// Cleared by `DayNightSystem` before new events are added.
+pub type TimeOfDayEvents = EventBroker<TimeOfDayEvent>;
+
+/// Marks directional and sky lights driven by `DayNightSystem`.
+#[derive(Clone, Copy, Debug)]
+pub struct Sun;
+
+/// System that advances `TimeOfDay` and updates `Sun` lights.
+///
+/// Inserts default `TimeOfDay` resource if it is missing.
+pub struct DayNightSystem {
+    sunlight: na::Vector3<f32>,
+    sunset: na::Vector3<f32>,
+    skylight: na::Vector3<f32>,
+    night_sky: na::Vector3<f32>,
+    tilt: f32,
+}
+
+impl DayNightSystem {
+    /// Creates system with noon sun and day sky radiance.
+    pub fn new(sunlight: [f32; 3], skylight: [f32; 3]) -> Self {
+        let sunlight = na::Vector3::from(sunlight);
+        let skylight = na::Vector3::from(skylight);
+
+        DayNightSystem {
+            sunset: sunlight.component_mul(&na::Vector3::new(1.0, 0.5, 0.2)),
+            night_sky: skylight * 0.01,
+            sunlight,
+            skylight,
+            tilt: 0.2,
+        }
+    }
+
+    /// Sets sun radiance near horizon.
+    pub fn with_sunset(mut self, radiance: [f32; 3]) -> Self {
+        self.sunset = radiance.into();
+        self
+    }
+
+    /// Sets sky radiance at night.
+    pub fn with_night_sky(mut self, radiance: [f32; 3]) -> Self {
+        self.night_sky = radiance.into();
+        self
+    }
+
+    /// Sets tilt of sun path from zenith towards Z axis.
+    pub fn with_tilt(mut self, tilt: f32) -> Self {
+        self.tilt = tilt;
+        self
+    }
+}
+
+impl System for DayNightSystem {
+    fn run(&mut self, ctx: SystemContext<'_>) {
+        let delta = ctx.clocks.sim_delta.as_secs_f32();
+
+        if !ctx.resources.contains::<TimeOfDayEvents>() {
+            ctx.resources.insert(TimeOfDayEvents::new());
+        }
+
+        if !ctx.resources.contains::<TimeOfDay>() {
+            ctx.resources.insert(TimeOfDay::default());
+        }
+
+        let mut time = *ctx.resources.get::<TimeOfDay>().unwrap();
+        let events = ctx.resources.get_mut::<TimeOfDayEvents>().unwrap();
+        events.clear();
+        time.advance(delta * time.speed, events);
+        ctx.resources.insert(time);
+
+        let angle = time.sun_angle();
+        let elevation = angle.sin();
+
+        // Direction from the sun.
+        let direction = -na::Vector3::new(
+            angle.cos(),
+            elevation * self.tilt.cos(),
+            elevation * self.tilt.sin(),
+        );
+
+        // Sun fades below horizon and is redder when low.
+        let warmth = smoothstep(0.0, 0.4, elevation);
+        let sun = (self.sunset + (self.sunlight - self.sunset) * warmth)
+            * smoothstep(-0.05, 0.1, elevation);
+
+        let daylight = smoothstep(-0.2, 0.2, elevation);
+        let sky = self.night_sky + (self.skylight - self.night_sky) * daylight;
+
+        for (_, light) in ctx
+            .world
+            .query::<&mut DirectionalLight>()
+            .with::<Sun>()
+            .iter()
+        {
+            light.direction = direction;
+            light.radiance = sun.into();
+        }
+
+        for (_, light) in
+            ctx.world.query::<&mut SkyLight>().with::<Sun>().iter()
+        {
+            light.radiance = sky.into();
+        }
+    }
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).max(0.0).min(1.0);
+    t * t * (3.0 - 2.0 * t)
+}
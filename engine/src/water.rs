@@ -0,0 +1,715 @@
+//! Ocean water.
+//!
+//! `Water` simulates wind waves with inverse FFT of Phillips spectrum.
+//! Surface is rendered as a grid that follows the camera.
+//! Renderer evaluates waves on the device, displacing the grid
+//! and writing slopes into its normal map.
+//! Same spectrum is evaluated on the host so physics can query heights.
+//!
+//! `WaterSystem` advances simulation and applies buoyancy
+//! to rigid bodies with `Buoyancy` component.
+
+use {
+    crate::{
+        camera::Camera,
+        engine::{System, SystemContext},
+        physics::RigidBody,
+        renderer::{
+            AlphaMode, Buffer, BufferInfo, BufferUsage, Context,
+            CreateImageError, Extent2d, Filter, Format, ImageInfo, ImageUsage,
+            ImageViewInfo, Material, Mesh, MeshData, Normal3d, OutOfMemory,
+            Position3d, PositionNormalTangent3dUV, PrimitiveTopology,
+            Renderable, SamplerAddressMode, SamplerInfo, Samples, Tangent3d,
+            Texture, UV, WATER_FIELDS,
+        },
+        scene::Global3,
+    },
+    bytemuck::{Pod, Zeroable},
+    hecs::{Entity, World},
+    nalgebra as na,
+    noise::{Canvas, Complex, Fft2d},
+    nphysics3d::{algebra::ForceType, object::Body as _},
+    ordered_float::OrderedFloat,
+    parking_lot::Mutex,
+    rand::{rngs::StdRng, Rng as _, SeedableRng as _},
+    std::{f32::consts::PI, mem::size_of, sync::Arc},
+};
+
+/// Gravity used by wave dispersion.
+const GRAVITY: f32 = 9.81;
+
+/// Parameters of water surface.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+#[serde(default)]
+pub struct WaterInfo {
+    /// Height of undisturbed surface.
+    pub level: f32,
+
+    /// Side of simulated grid in texels. Must be power of two.
+    pub size: u32,
+
+    /// Side of simulated patch in meters.
+    /// Patch repeats over the surface.
+    pub patch: f32,
+
+    /// Wind velocity over XZ plane.
+    pub wind: [f32; 2],
+
+    /// Significant wave height in meters.
+    pub wave_height: f32,
+
+    /// Scale of horizontal displacement that sharpens crests.
+    pub choppiness: f32,
+
+    /// Color that tints light refracted by the surface.
+    /// Alpha is strength of the tint.
+    pub color: [f32; 4],
+    pub roughness: f32,
+
+    /// Index of refraction.
+    pub ior: f32,
+
+    /// Half-extent of rendered grid.
+    pub extent: f32,
+
+    /// Spacing of grid vertices near the camera.
+    /// Spacing grows towards grid edges.
+    pub spacing: f32,
+
+    pub seed: u64,
+}
+
+impl Default for WaterInfo {
+    fn default() -> Self {
+        WaterInfo {
+            level: 0.0,
+            size: 64,
+            patch: 64.0,
+            wind: [8.0, 0.0],
+            wave_height: 1.0,
+            choppiness: 1.0,
+            color: [0.02, 0.08, 0.1, 0.8],
+            roughness: 0.05,
+            ior: 1.333,
+            extent: 1024.0,
+            spacing: 1.0,
+            seed: 0,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum WaterError {
+    #[error("Water simulation size {size} is not a power of two")]
+    InvalidSize { size: u32 },
+
+    #[error(transparent)]
+    CreateImage {
+        #[from]
+        source: CreateImageError,
+    },
+}
+
+impl From<OutOfMemory> for WaterError {
+    fn from(source: OutOfMemory) -> Self {
+        WaterError::CreateImage {
+            source: source.into(),
+        }
+    }
+}
+
+/// Component of water surface entity.
+///
+/// Cloned values share simulation.
+#[derive(Clone)]
+pub struct Water {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    info: WaterInfo,
+
+    /// Initial amplitudes and angular frequencies of waves.
+    h0: Vec<Complex<f32>>,
+    omega: Vec<f32>,
+
+    /// Device resources, replaced when device is recreated.
+    resources: Mutex<WaterResources>,
+    state: Mutex<State>,
+}
+
+/// Device resources of the surface.
+#[derive(Clone)]
+pub(crate) struct WaterResources {
+    pub normal_map: Texture,
+
+    /// Grid mesh. Texture coordinates of vertices are
+    /// their undisplaced positions in patches.
+    pub mesh: Mesh,
+
+    /// Initial amplitudes and angular frequencies of waves.
+    pub waves: Buffer,
+
+    /// Buffers with `WATER_FIELDS` planes of complex values
+    /// which FFT stages ping-pong between.
+    pub fields: [Buffer; 2],
+}
+
+/// Wave as seen by shaders.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct ShaderWave {
+    h0: [f32; 2],
+    omega: f32,
+    _pad: f32,
+}
+
+unsafe impl Zeroable for ShaderWave {}
+unsafe impl Pod for ShaderWave {}
+
+struct State {
+    fft: Fft2d,
+    height: Canvas<Complex<f32>>,
+    displacement_x: Canvas<Complex<f32>>,
+    displacement_z: Canvas<Complex<f32>>,
+    slope_x: Canvas<Complex<f32>>,
+    slope_z: Canvas<Complex<f32>>,
+
+    /// Time of last simulation step.
+    time: f32,
+}
+
+impl Water {
+    pub fn new(info: WaterInfo, ctx: &mut Context) -> Result<Self, WaterError> {
+        if !info.size.is_power_of_two() {
+            return Err(WaterError::InvalidSize { size: info.size });
+        }
+
+        let n = info.size as usize;
+        let (h0, omega) = phillips_spectrum(&info);
+
+        let resources = create_resources(&info, &h0, &omega, ctx)?;
+
+        let zero = || Canvas::from_fn(n, n, |_, _| Complex::new(0.0, 0.0));
+        let state = State {
+            fft: Fft2d::new(n, n),
+            height: zero(),
+            displacement_x: zero(),
+            displacement_z: zero(),
+            slope_x: zero(),
+            slope_z: zero(),
+            time: 0.0,
+        };
+
+        let water = Water {
+            shared: Arc::new(Shared {
+                info,
+                h0,
+                omega,
+                resources: Mutex::new(resources),
+                state: Mutex::new(state),
+            }),
+        };
+
+        water.simulate(0.0);
+        Ok(water)
+    }
+
+    pub fn info(&self) -> &WaterInfo {
+        &self.shared.info
+    }
+
+    /// Returns material that shades the surface with simulated waves.
+    /// Light passing through the surface is refracted.
+    pub fn material(&self) -> Material {
+        let info = &self.shared.info;
+        let [r, g, b, a] = info.color;
+
+        Material {
+            albedo_factor: [
+                OrderedFloat(r),
+                OrderedFloat(g),
+                OrderedFloat(b),
+                OrderedFloat(a),
+            ],
+            metallic_factor: OrderedFloat(0.0),
+            roughness_factor: OrderedFloat(info.roughness),
+            normal: Some(self.shared.resources.lock().normal_map.clone()),
+            alpha_mode: AlphaMode::Refract {
+                ior: OrderedFloat(info.ior),
+            },
+            double_sided: true,
+            ..Material::new()
+        }
+    }
+
+    /// Spawns entity that renders this water surface.
+    pub fn spawn(&self, world: &mut World) -> Entity {
+        let iso = na::Isometry3::translation(0.0, self.shared.info.level, 0.0);
+        world.spawn((
+            Renderable {
+                mesh: self.mesh(),
+                material: self.material(),
+            },
+            Global3::from_iso(iso),
+            self.clone(),
+        ))
+    }
+
+    /// Returns grid mesh of the surface.
+    pub fn mesh(&self) -> Mesh {
+        self.shared.resources.lock().mesh.clone()
+    }
+
+    /// Creates device resources on recreated device.
+    /// Renderables of the surface must be replaced
+    /// with new `mesh` and `material`.
+    pub(crate) fn recreate(&self, ctx: &mut Context) -> Result<(), WaterError> {
+        let shared = &*self.shared;
+        let resources =
+            create_resources(&shared.info, &shared.h0, &shared.omega, ctx)?;
+        *shared.resources.lock() = resources;
+        Ok(())
+    }
+
+    pub(crate) fn resources(&self) -> WaterResources {
+        self.shared.resources.lock().clone()
+    }
+
+    /// Returns time of last simulation step in seconds.
+    pub(crate) fn time(&self) -> f32 {
+        self.shared.state.lock().time
+    }
+
+    /// Evaluates waves at specified time in seconds.
+    pub fn simulate(&self, time: f32) {
+        let shared = &*self.shared;
+        let n = shared.info.size as usize;
+        let mut state = shared.state.lock();
+        let state = &mut *state;
+
+        for z in 0..n {
+            for x in 0..n {
+                let index = x + z * n;
+                let mirror = (n - x) % n + (n - z) % n * n;
+
+                let (sin, cos) = (shared.omega[index] * time).sin_cos();
+                let phase = Complex::new(cos, sin);
+                let h = shared.h0[index] * phase
+                    + shared.h0[mirror].conj() * phase.conj();
+
+                let k = wave_vector(x, z, &shared.info);
+                let k_len = k.magnitude();
+                let ik = Complex::new(0.0, 1.0);
+
+                state.height[(x, z)] = h;
+                state.slope_x[(x, z)] = ik * k.x * h;
+                state.slope_z[(x, z)] = ik * k.y * h;
+
+                if k_len > 0.0 {
+                    state.displacement_x[(x, z)] = -ik * (k.x / k_len) * h;
+                    state.displacement_z[(x, z)] = -ik * (k.y / k_len) * h;
+                } else {
+                    state.displacement_x[(x, z)] = Complex::new(0.0, 0.0);
+                    state.displacement_z[(x, z)] = Complex::new(0.0, 0.0);
+                }
+            }
+        }
+
+        state.fft.inverse(&mut state.height);
+        state.fft.inverse(&mut state.displacement_x);
+        state.fft.inverse(&mut state.displacement_z);
+        state.fft.inverse(&mut state.slope_x);
+        state.fft.inverse(&mut state.slope_z);
+
+        state.time = time;
+    }
+
+    /// Returns height of the surface at specified horizontal position.
+    pub fn height_at(&self, x: f32, z: f32) -> f32 {
+        let info = &self.shared.info;
+        let state = self.shared.state.lock();
+        let choppiness = info.choppiness;
+
+        // Surface point above `(x, z)` was displaced from nearby point.
+        let dx = sample(&state.displacement_x, x, z, info.patch) * choppiness;
+        let dz = sample(&state.displacement_z, x, z, info.patch) * choppiness;
+
+        info.level + sample(&state.height, x - dx, z - dz, info.patch)
+    }
+
+    /// Returns normal of the surface at specified horizontal position.
+    pub fn normal_at(&self, x: f32, z: f32) -> na::Vector3<f32> {
+        let patch = self.shared.info.patch;
+        let state = self.shared.state.lock();
+        na::Vector3::new(
+            -sample(&state.slope_x, x, z, patch),
+            1.0,
+            -sample(&state.slope_z, x, z, patch),
+        )
+        .normalize()
+    }
+}
+
+/// Applies buoyancy to rigid body of the entity.
+///
+/// Each submerged sample point pushes body up
+/// proportionally to its depth and drags it against its velocity.
+#[derive(Clone, Debug)]
+pub struct Buoyancy {
+    /// Sample points in entity space.
+    pub points: Vec<na::Point3<f32>>,
+
+    /// Upward force of each point per meter of depth.
+    pub force: f32,
+
+    /// Depth at which force stops growing.
+    pub max_depth: f32,
+
+    /// Force of each submerged point per unit of velocity.
+    pub drag: f32,
+}
+
+impl Buoyancy {
+    pub fn new(points: Vec<na::Point3<f32>>, force: f32) -> Self {
+        Buoyancy {
+            points,
+            force,
+            max_depth: 1.0,
+            drag: 0.0,
+        }
+    }
+
+    pub fn with_max_depth(mut self, max_depth: f32) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn with_drag(mut self, drag: f32) -> Self {
+        self.drag = drag;
+        self
+    }
+}
+
+/// Advances water simulation, moves water surfaces with camera
+/// and applies buoyancy.
+///
+/// Should run before `Physics` so that buoyancy
+/// is applied in the same frame.
+pub struct WaterSystem;
+
+impl System for WaterSystem {
+    fn run(&mut self, ctx: SystemContext<'_>) {
+        let time = ctx
+            .clocks
+            .step
+            .duration_since(ctx.clocks.start)
+            .as_secs_f32();
+
+        let camera = ctx
+            .world
+            .query::<&Global3>()
+            .with::<Camera>()
+            .iter()
+            .next()
+            .map(|(_, global)| global.iso.translation.vector);
+
+        let mut waters = Vec::new();
+        for (_, (water, global)) in
+            ctx.world.query::<(&Water, &mut Global3)>().iter()
+        {
+            water.simulate(time);
+
+            // Grid is moved by whole patches so normal map stays in place.
+            if let Some(camera) = camera {
+                let patch = water.shared.info.patch;
+                let snap = |v: f32| (v / patch).round() * patch;
+                let translation = na::Vector3::new(
+                    snap(camera.x),
+                    water.shared.info.level,
+                    snap(camera.z),
+                );
+                global.iso.translation.vector = translation;
+            }
+
+            waters.push(water.clone());
+        }
+
+        let water = match waters.first() {
+            Some(water) => water,
+            None => return,
+        };
+
+        for (_, (buoyancy, global, body)) in ctx
+            .world
+            .query::<(&Buoyancy, &Global3, &mut RigidBody<f32>)>()
+            .iter()
+        {
+            let velocity = body.velocity().linear;
+
+            for point in &buoyancy.points {
+                let point = global.iso * point;
+                let depth = water.height_at(point.x, point.z) - point.y;
+                if depth <= 0.0 {
+                    continue;
+                }
+
+                let force = na::Vector3::y()
+                    * (buoyancy.force * depth.min(buoyancy.max_depth))
+                    - velocity * buoyancy.drag;
+
+                body.apply_force_at_point(
+                    0,
+                    &force,
+                    &point,
+                    ForceType::Force,
+                    true,
+                );
+            }
+        }
+    }
+}
+
+/// Generates initial wave amplitudes and their angular frequencies.
+/// Amplitudes are scaled to match significant wave height.
+fn phillips_spectrum(info: &WaterInfo) -> (Vec<Complex<f32>>, Vec<f32>) {
+    let n = info.size as usize;
+    let wind = na::Vector2::from(info.wind);
+    let speed = wind.magnitude().max(0.001);
+    let wind_dir = wind / speed;
+
+    // Largest wave from continuous wind.
+    let largest = speed * speed / GRAVITY;
+
+    // Waves much smaller than largest are suppressed.
+    let smallest = largest * 0.001;
+
+    let mut rng = StdRng::seed_from_u64(info.seed);
+    let mut gaussian = move || {
+        let u1: f32 = rng.gen_range(f32::EPSILON..1.0);
+        let u2: f32 = rng.gen();
+        let r = (-2.0 * u1.ln()).sqrt();
+        let (sin, cos) = (2.0 * PI * u2).sin_cos();
+        Complex::new(r * cos, r * sin)
+    };
+
+    let mut h0 = Vec::with_capacity(n * n);
+    let mut omega = Vec::with_capacity(n * n);
+
+    for z in 0..n {
+        for x in 0..n {
+            let k = wave_vector(x, z, info);
+            let k_len = k.magnitude();
+
+            if k_len < 0.000001 {
+                h0.push(Complex::new(0.0, 0.0));
+                omega.push(0.0);
+                continue;
+            }
+
+            let k_dir = k / k_len;
+            let alignment = k_dir.dot(&wind_dir);
+            let k2 = k_len * k_len;
+
+            let phillips = (-1.0 / (k2 * largest * largest)).exp() / (k2 * k2)
+                * alignment
+                * alignment
+                * (-k2 * smallest * smallest).exp();
+
+            h0.push(gaussian() * (phillips * 0.5).sqrt());
+            omega.push((GRAVITY * k_len).sqrt());
+        }
+    }
+
+    // Variance of surface height is sum of squared amplitudes.
+    // Significant wave height is four standard deviations.
+    let variance: f32 = h0.iter().map(|h| 2.0 * h.norm_sqr()).sum();
+    if variance > 0.0 {
+        let scale = info.wave_height / (4.0 * variance.sqrt());
+        for h in &mut h0 {
+            *h *= scale;
+        }
+    }
+
+    (h0, omega)
+}
+
+/// Returns wave vector of frequency texel in unshifted spectrum.
+fn wave_vector(x: usize, z: usize, info: &WaterInfo) -> na::Vector2<f32> {
+    let n = info.size as i64;
+    let wrap = |i: usize| {
+        let i = i as i64;
+        if i < n / 2 {
+            i
+        } else {
+            i - n
+        }
+    };
+
+    na::Vector2::new(wrap(x) as f32, wrap(z) as f32) * (2.0 * PI / info.patch)
+}
+
+/// Bilinearly samples real part of periodic canvas at world position.
+fn sample(canvas: &Canvas<Complex<f32>>, x: f32, z: f32, patch: f32) -> f32 {
+    let n = canvas.width() as f32;
+    let u = (x / patch).rem_euclid(1.0) * n;
+    let v = (z / patch).rem_euclid(1.0) * n;
+
+    let x0 = u.floor() as isize;
+    let z0 = v.floor() as isize;
+    let fx = u - x0 as f32;
+    let fz = v - z0 as f32;
+
+    let at = |x: isize, z: isize| canvas.get_wrapping(x, z).re;
+    let top = at(x0, z0) * (1.0 - fx) + at(x0 + 1, z0) * fx;
+    let bottom = at(x0, z0 + 1) * (1.0 - fx) + at(x0 + 1, z0 + 1) * fx;
+    top * (1.0 - fz) + bottom * fz
+}
+
+/// Creates grid centered at origin with spacing growing
+/// away from the center.
+/// Creates normal map, grid mesh and simulation buffers of the surface.
+fn create_resources(
+    info: &WaterInfo,
+    h0: &[Complex<f32>],
+    omega: &[f32],
+    ctx: &mut Context,
+) -> Result<WaterResources, WaterError> {
+    let n = info.size as usize;
+
+    // Normal map is written by `WaterPass` every frame.
+    let image = ctx.create_image_static(
+        ImageInfo {
+            extent: Extent2d {
+                width: info.size,
+                height: info.size,
+            }
+            .into(),
+            format: Format::RGBA8Unorm,
+            levels: 1,
+            layers: 1,
+            samples: Samples::Samples1,
+            usage: ImageUsage::SAMPLED | ImageUsage::STORAGE,
+        },
+        0,
+        0,
+        &vec![[128u8, 128, 255, 255]; n * n],
+    )?;
+
+    let sampler = ctx.create_sampler(SamplerInfo {
+        mag_filter: Filter::Linear,
+        min_filter: Filter::Linear,
+        address_mode_u: SamplerAddressMode::Repeat,
+        address_mode_v: SamplerAddressMode::Repeat,
+        address_mode_w: SamplerAddressMode::Repeat,
+        ..SamplerInfo::new()
+    })?;
+
+    let normal_map = Texture {
+        image: ctx.create_image_view(ImageViewInfo::new(image))?,
+        sampler,
+    };
+
+    let mesh = create_water_mesh(info, ctx)?;
+
+    let waves: Vec<_> = h0
+        .iter()
+        .zip(omega)
+        .map(|(h0, &omega)| ShaderWave {
+            h0: [h0.re, h0.im],
+            omega,
+            _pad: 0.0,
+        })
+        .collect();
+
+    let waves = ctx.create_fast_buffer_static(
+        BufferInfo {
+            align: 255,
+            size: (size_of::<ShaderWave>() * waves.len()) as u64,
+            usage: BufferUsage::STORAGE | BufferUsage::TRANSFER_DST,
+            capture_address: None,
+        },
+        &waves,
+    )?;
+
+    // Two complex `f32` per field of each texel.
+    let fields_info = BufferInfo {
+        align: 255,
+        size: WATER_FIELDS * (n * n) as u64 * 8,
+        usage: BufferUsage::STORAGE,
+        capture_address: None,
+    };
+
+    let fields = [
+        ctx.create_buffer(fields_info)?,
+        ctx.create_buffer(fields_info)?,
+    ];
+
+    Ok(WaterResources {
+        normal_map,
+        mesh,
+        waves,
+        fields,
+    })
+}
+
+fn create_water_mesh(
+    info: &WaterInfo,
+    ctx: &mut Context,
+) -> Result<Mesh, OutOfMemory> {
+    // Spacing stays fine over one patch and then grows geometrically.
+    let mut coords = vec![0.0];
+    let mut step = info.spacing.max(0.01);
+    let mut coord = 0.0;
+    while coord < info.extent {
+        coord += step;
+        coords.push(coord);
+        if coord > info.patch * 0.5 {
+            step *= 1.1;
+        }
+    }
+
+    let axis: Vec<f32> = coords
+        .iter()
+        .rev()
+        .map(|c| -c)
+        .chain(coords.iter().skip(1).copied())
+        .collect();
+
+    let side = axis.len() as u32;
+    let mut vertices = Vec::with_capacity(axis.len() * axis.len());
+    for &z in &axis {
+        for &x in &axis {
+            vertices.push(PositionNormalTangent3dUV {
+                position: Position3d([x, 0.0, z]),
+                normal: Normal3d([0.0, 1.0, 0.0]),
+                tangent: Tangent3d([1.0, 0.0, 0.0, 1.0]),
+                uv: UV([x / info.patch, z / info.patch]),
+            });
+        }
+    }
+
+    let mut indices = Vec::with_capacity((side as usize - 1).pow(2) * 6);
+    for z in 1..side {
+        for x in 1..side {
+            let i = |x: u32, z: u32| x + z * side;
+            indices.extend_from_slice(&[
+                i(x - 1, z - 1),
+                i(x - 1, z),
+                i(x, z - 1),
+                i(x, z - 1),
+                i(x - 1, z),
+                i(x, z),
+            ]);
+        }
+    }
+
+    let usage = BufferUsage::ACCELERATION_STRUCTURE_BUILD_INPUT
+        | BufferUsage::STORAGE
+        | BufferUsage::DEVICE_ADDRESS;
+
+    MeshData::new(PrimitiveTopology::TriangleList)
+        .with_binding(&vertices)
+        .with_indices(&indices[..])
+        .build(ctx, usage, usage)
+}
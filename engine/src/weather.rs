@@ -0,0 +1,300 @@
+//! Weather simulation.
+//!
+//! `Weather` resource holds global wind, dust and precipitation
+//! parameters. Effects such as particle emitters or vegetation sway
+//! should sample wind with `Weather::wind_at`.
+//!
+//! `WeatherSystem` drives `Weather` from scripted fronts loaded from
+//! RON asset and feeds dust into `Fog` density.
+
+use {
+    crate::{
+        assets::{AssetKey, Assets},
+        engine::{Engine, System, SystemContext},
+        light::{Fog, SkyLight},
+    },
+    flume::{Receiver, Sender},
+    nalgebra as na,
+};
+
+/// Current weather state.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Weather {
+    /// Mean wind velocity.
+    pub wind: na::Vector3<f32>,
+
+    /// Relative strength of wind gusts.
+    pub gust: f32,
+
+    /// Amount of dust in the air in range `[0, 1]`.
+    pub dust: f32,
+
+    /// Precipitation intensity in range `[0, 1]`.
+    pub precipitation: f32,
+
+    /// Seconds since weather simulation started.
+    pub time: f32,
+}
+
+impl Weather {
+    /// Calm weather without wind.
+    pub fn calm() -> Self {
+        Weather {
+            wind: na::Vector3::zeros(),
+            gust: 0.0,
+            dust: 0.0,
+            precipitation: 0.0,
+            time: 0.0,
+        }
+    }
+
+    /// Returns wind velocity at specified point.
+    ///
+    /// Gusts are travelling waves along the wind direction,
+    /// so nearby points sway coherently.
+    pub fn wind_at(&self, point: &na::Point3<f32>) -> na::Vector3<f32> {
+        let speed = self.wind.magnitude();
+        if speed <= f32::EPSILON {
+            return self.wind;
+        }
+
+        let along = point.coords.dot(&self.wind) / speed;
+        let phase = along * 0.2 - self.time * speed * 0.2;
+        let wave =
+            phase.sin() * 0.6 + (phase * 2.3 + point.x * 0.1).sin() * 0.4;
+
+        self.wind * (1.0 + self.gust * wave)
+    }
+
+    fn blend(&mut self, from: &WeatherFront, to: &WeatherFront, t: f32) {
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        let wind =
+            na::Vector3::from(from.wind).lerp(&na::Vector3::from(to.wind), t);
+
+        self.wind = wind;
+        self.gust = lerp(from.gust, to.gust);
+        self.dust = lerp(from.dust, to.dust);
+        self.precipitation = lerp(from.precipitation, to.precipitation);
+    }
+}
+
+impl Default for Weather {
+    fn default() -> Self {
+        Weather::calm()
+    }
+}
+
+/// Weather front in weather script.
+#[derive(Clone, Copy, Debug, serde::Deserialize)]
+pub struct WeatherFront {
+    /// Seconds this front lasts, including transition.
+    pub duration: f32,
+
+    /// Seconds it takes to blend from previous front.
+    #[serde(default)]
+    pub transition: f32,
+
+    /// Mean wind velocity.
+    #[serde(default)]
+    pub wind: [f32; 3],
+
+    #[serde(default)]
+    pub gust: f32,
+
+    #[serde(default)]
+    pub dust: f32,
+
+    #[serde(default)]
+    pub precipitation: f32,
+}
+
+/// Sequence of weather fronts loaded from RON asset.
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct WeatherScript {
+    pub fronts: Vec<WeatherFront>,
+
+    /// Restart from first front after last one ends.
+    #[serde(default)]
+    pub repeat: bool,
+}
+
+impl WeatherScript {
+    fn total_duration(&self) -> f32 {
+        self.fronts
+            .iter()
+            .map(|front| front.duration.max(0.0))
+            .sum()
+    }
+
+    /// Samples weather at specified time since script start.
+    fn sample(&self, mut time: f32, weather: &mut Weather) {
+        let last = match self.fronts.last() {
+            Some(last) => last,
+            None => return,
+        };
+
+        let total = self.total_duration();
+        if self.repeat && total > 0.0 {
+            time = time.rem_euclid(total);
+        }
+
+        for (index, front) in self.fronts.iter().enumerate() {
+            let duration = front.duration.max(0.0);
+            if time < duration {
+                let prev = match index {
+                    0 if self.repeat => last,
+                    0 => front,
+                    _ => &self.fronts[index - 1],
+                };
+
+                let t = if front.transition > 0.0 {
+                    (time / front.transition).min(1.0)
+                } else {
+                    1.0
+                };
+
+                weather.blend(prev, front, t);
+                return;
+            }
+            time -= duration;
+        }
+
+        weather.blend(last, last, 1.0);
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum WeatherError {
+    #[error("Failed to load weather script: `{source}`")]
+    Load {
+        #[from]
+        source: goods::Error,
+    },
+
+    #[error("Failed to parse weather script: `{source}`")]
+    Parse {
+        #[from]
+        source: ron::Error,
+    },
+}
+
+/// System that advances `Weather` and applies dust to `Fog`.
+///
+/// Inserts calm `Weather` resource if it is missing.
+/// Without script the `Weather` resource may be modified directly.
+pub struct WeatherSystem {
+    assets: Assets,
+    script: Option<WeatherScript>,
+    elapsed: f32,
+    sender: Sender<Result<WeatherScript, WeatherError>>,
+    receiver: Receiver<Result<WeatherScript, WeatherError>>,
+    clear_density: f32,
+    dust_density: f32,
+    dust_color: [f32; 3],
+}
+
+impl WeatherSystem {
+    pub fn new(engine: &Engine) -> Self {
+        let (sender, receiver) = flume::unbounded();
+
+        WeatherSystem {
+            assets: engine.assets.clone(),
+            script: None,
+            elapsed: 0.0,
+            sender,
+            receiver,
+            clear_density: 0.0005,
+            dust_density: 0.05,
+            dust_color: [0.8, 0.65, 0.45],
+        }
+    }
+
+    /// Loads weather script from asset.
+    /// Script starts playing once loaded.
+    pub fn with_script(self, key: AssetKey) -> Self {
+        tracing::info!("Loading weather script '{}'", key);
+
+        let assets = self.assets.clone();
+        let sender = self.sender.clone();
+
+        smol::spawn(async move {
+            let result = async {
+                let bytes = assets.load::<Box<[u8]>>(key).await?;
+                Ok::<_, WeatherError>(ron::de::from_bytes(&bytes)?)
+            }
+            .await;
+
+            let _ = sender.send(result);
+        })
+        .detach();
+
+        self
+    }
+
+    /// Sets fog density in clear weather and at full dust.
+    pub fn with_fog_density(mut self, clear: f32, dust: f32) -> Self {
+        self.clear_density = clear;
+        self.dust_density = dust;
+        self
+    }
+
+    /// Sets color of dust that tints sky light scattered by fog.
+    pub fn with_dust_color(mut self, color: [f32; 3]) -> Self {
+        self.dust_color = color;
+        self
+    }
+
+    fn receive_script(&mut self) {
+        for result in self.receiver.try_iter() {
+            match result {
+                Ok(script) => {
+                    self.script = Some(script);
+                    self.elapsed = 0.0;
+                }
+                Err(err) => {
+                    tracing::error!("Failed to load weather script: {}", err);
+                }
+            }
+        }
+    }
+}
+
+impl System for WeatherSystem {
+    fn run(&mut self, ctx: SystemContext<'_>) {
+        self.receive_script();
+
+        let delta = ctx.clocks.sim_delta.as_secs_f32();
+
+        if !ctx.resources.contains::<Weather>() {
+            ctx.resources.insert(Weather::calm());
+        }
+
+        let weather = ctx.resources.get_mut::<Weather>().unwrap();
+        weather.time += delta;
+
+        if let Some(script) = &self.script {
+            self.elapsed += delta;
+            script.sample(self.elapsed, weather);
+        }
+
+        let dust = weather.dust.max(0.0).min(1.0);
+        let density = self.clear_density
+            + (self.dust_density - self.clear_density) * dust;
+
+        let sky = ctx
+            .world
+            .query::<&SkyLight>()
+            .iter()
+            .next()
+            .map_or([1.0; 3], |(_, sky)| sky.radiance);
+
+        let clear = na::Vector3::from(sky);
+        let dusty = clear.component_mul(&na::Vector3::from(self.dust_color));
+        let color = clear + (dusty - clear) * dust;
+
+        for (_, fog) in ctx.world.query::<&mut Fog>().iter() {
+            fog.density = density;
+            fog.color = color.into();
+        }
+    }
+}
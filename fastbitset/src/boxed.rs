@@ -1,9 +1,23 @@
-use alloc::boxed::Box;
+use {
+    alloc::{boxed::Box, vec::Vec},
+    core::convert::TryFrom as _,
+};
 
+/// Number of bits in single lazily allocated block.
+const BLOCK_BITS: u64 = 64 * 64;
+
+/// Hierarchical bitset.
+///
+/// Bits are stored in lazily allocated blocks of 4096 bits.
+/// Capacity grows as bits are set.
 pub struct BoxedBitSet {
-    level0: u64,
-    level1: [u64; 64],
-    level2: [Option<Box<[u64; 64]>>; 64],
+    /// Bit `i` of word `j` is set if `level1[j * 64 + i]` is not zero.
+    level0: Vec<u64>,
+
+    /// Bit `i` of `level1[b]` is set if word `i` of block `b` is not zero.
+    level1: Vec<u64>,
+
+    level2: Vec<Option<Box<[u64; 64]>>>,
 }
 
 impl Default for BoxedBitSet {
@@ -13,108 +27,393 @@ impl Default for BoxedBitSet {
 }
 
 impl BoxedBitSet {
-    pub const UPPER_BOUND: u32 = 64 * 64 * 64;
-
     pub const fn new() -> Self {
         BoxedBitSet {
-            level0: 0,
-            level1: [0; 64],
-            level2: [
-                None, None, None, None, None, None, None, None, None, None,
-                None, None, None, None, None, None, None, None, None, None,
-                None, None, None, None, None, None, None, None, None, None,
-                None, None, None, None, None, None, None, None, None, None,
-                None, None, None, None, None, None, None, None, None, None,
-                None, None, None, None, None, None, None, None, None, None,
-                None, None, None, None,
-            ],
+            level0: Vec::new(),
+            level1: Vec::new(),
+            level2: Vec::new(),
+        }
+    }
+
+    /// Creates bitset that can hold `capacity` bits without growing.
+    pub fn with_capacity(capacity: u64) -> Self {
+        let mut bitset = Self::new();
+        bitset.reserve(capacity);
+        bitset
+    }
+
+    /// Returns number of bits bitset can hold without growing.
+    pub fn capacity(&self) -> u64 {
+        self.level2.len() as u64 * BLOCK_BITS
+    }
+
+    /// Grows bitset to hold at least `capacity` bits.
+    pub fn reserve(&mut self, capacity: u64) {
+        let blocks = (capacity + BLOCK_BITS - 1) / BLOCK_BITS;
+
+        // Grow in whole `level0` words.
+        let words = ((blocks + 63) / 64) as usize;
+        if words > self.level0.len() {
+            self.level0.resize(words, 0);
+            self.level1.resize(words * 64, 0);
+            self.level2.resize_with(words * 64, || None);
         }
     }
 
     /// Returns first set bit index.
     pub fn find_set(&self) -> Option<u32> {
-        match self.level0.trailing_zeros() {
-            64 => None,
-            i0 => {
-                let i1 = unsafe {
-                    debug_assert!(i0 < 64);
-                    self.level1.get_unchecked(i0 as usize)
+        let word = self.next_nonzero_word(0)?;
+        let bits = self.word(word);
+        Some(((word as u32) << 6) | bits.trailing_zeros())
+    }
+
+    /// Returns first unset bit index.
+    /// This may be equal to capacity if all bits are set.
+    pub fn find_unset(&self) -> Option<u32> {
+        self.iter_zeros()
+            .next()
+            .or_else(|| u32::try_from(self.capacity()).ok())
+    }
+
+    pub fn get(&self, index: u32) -> bool {
+        let (b, w, bit) = Self::split_index(index);
+
+        match self.level2.get(b) {
+            Some(Some(block)) => 0 < block[w] & (1 << bit),
+            _ => false,
+        }
+    }
+
+    /// Sets bit.
+    /// Returns old value of the bit.
+    pub fn set(&mut self, index: u32) -> bool {
+        let (b, w, bit) = Self::split_index(index);
+
+        if b >= self.level2.len() {
+            self.reserve(u64::from(index) + 1);
+        }
+
+        let block = self.level2[b].get_or_insert_with(|| Box::new([0; 64]));
+        let old = block[w] & (1 << bit);
+        block[w] |= 1 << bit;
+
+        self.level1[b] |= 1 << w;
+        self.level0[b >> 6] |= 1 << (b & 63);
+        old > 0
+    }
+
+    /// Unsets bit.
+    /// Returns old value of the bit.
+    pub fn unset(&mut self, index: u32) -> bool {
+        let (b, w, bit) = Self::split_index(index);
+
+        match self.level2.get_mut(b) {
+            Some(Some(block)) => {
+                let old = block[w] & (1 << bit);
+                block[w] &= !(1 << bit);
+
+                if block[w] == 0 {
+                    self.level1[b] &= !(1 << w);
+                    if self.level1[b] == 0 {
+                        self.level0[b >> 6] &= !(1 << (b & 63));
+                    }
+                }
+                old > 0
+            }
+            _ => false,
+        }
+    }
+
+    /// Unsets all bits.
+    /// Capacity is retained.
+    pub fn clear(&mut self) {
+        for block in self.level2.iter_mut().flatten() {
+            **block = [0; 64];
+        }
+
+        for word in &mut self.level1 {
+            *word = 0;
+        }
+
+        for word in &mut self.level0 {
+            *word = 0;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.level0.iter().all(|&word| word == 0)
+    }
+
+    /// Returns number of set bits.
+    pub fn count_ones(&self) -> u64 {
+        self.level2
+            .iter()
+            .flatten()
+            .flat_map(|block| block.iter())
+            .map(|word| u64::from(word.count_ones()))
+            .sum()
+    }
+
+    /// Returns number of set bits with index less than `index`.
+    pub fn rank(&self, index: u32) -> u64 {
+        let (b, w, bit) = Self::split_index(index);
+
+        let full_blocks: u64 = self
+            .level2
+            .iter()
+            .take(b)
+            .flatten()
+            .flat_map(|block| block.iter())
+            .map(|word| u64::from(word.count_ones()))
+            .sum();
+
+        match self.level2.get(b) {
+            Some(Some(block)) => {
+                let full_words: u64 = block[..w]
+                    .iter()
+                    .map(|word| u64::from(word.count_ones()))
+                    .sum();
+
+                let partial = block[w] & ((1 << bit) - 1);
+                full_blocks + full_words + u64::from(partial.count_ones())
+            }
+            _ => full_blocks,
+        }
+    }
+
+    /// Returns index of set bit with specified rank.
+    /// That is `n`-th set bit counting from zero.
+    pub fn select(&self, mut n: u64) -> Option<u32> {
+        let mut word = 0;
+
+        loop {
+            word = self.next_nonzero_word(word)?;
+            let mut bits = self.word(word);
+            let ones = u64::from(bits.count_ones());
+
+            if n < ones {
+                for _ in 0..n {
+                    bits &= bits - 1;
+                }
+                return Some(((word as u32) << 6) | bits.trailing_zeros());
+            }
+
+            n -= ones;
+            word += 1;
+        }
+    }
+
+    /// Returns iterator over indices of set bits in ascending order.
+    pub fn iter_ones(&self) -> Ones<'_> {
+        Ones {
+            bitset: self,
+            next: 0,
+            base: 0,
+            bits: 0,
+        }
+    }
+
+    /// Returns iterator over indices of unset bits below capacity
+    /// in ascending order.
+    pub fn iter_zeros(&self) -> Zeros<'_> {
+        Zeros {
+            bitset: self,
+            next: 0,
+            base: 0,
+            bits: 0,
+        }
+    }
+
+    /// Sets all bits that are set in `other`.
+    pub fn union_with(&mut self, other: &Self) {
+        self.reserve(other.capacity());
+
+        for (b, block) in other.level2.iter().enumerate() {
+            if let Some(block) = block {
+                if other.level1[b] == 0 {
+                    continue;
+                }
+
+                let dst =
+                    self.level2[b].get_or_insert_with(|| Box::new([0; 64]));
+
+                for (dst, src) in dst.iter_mut().zip(block.iter()) {
+                    *dst |= *src;
                 }
-                .trailing_zeros();
-
-                let i2 = unsafe {
-                    debug_assert!(i1 < 64);
-                    let level2 = self.level2.get_unchecked(i0 as usize);
-                    debug_assert!(level2.is_some());
-                    match level2 {
-                        Some(level2) => {
-                            level2.get_unchecked(i1 as usize).trailing_zeros()
+
+                self.update_summary(b);
+            }
+        }
+    }
+
+    /// Unsets all bits that are not set in `other`.
+    pub fn intersect_with(&mut self, other: &Self) {
+        for b in 0..self.level2.len() {
+            if self.level1[b] == 0 {
+                continue;
+            }
+
+            if let Some(dst) = &mut self.level2[b] {
+                match other.level2.get(b) {
+                    Some(Some(src)) => {
+                        for (dst, src) in dst.iter_mut().zip(src.iter()) {
+                            *dst &= *src;
                         }
-                        None => core::hint::unreachable_unchecked(),
                     }
-                };
-                let result = (i0 << 12) + (i1 << 6) + i2;
-                Some(result)
+                    _ => **dst = [0; 64],
+                }
+
+                self.update_summary(b);
             }
         }
     }
 
-    pub fn get(&mut self, index: u32) -> bool {
-        let (i0, i1, i2) = Self::split_index(index);
+    /// Unsets all bits that are set in `other`.
+    pub fn difference_with(&mut self, other: &Self) {
+        let blocks = self.level2.len().min(other.level2.len());
+
+        for b in 0..blocks {
+            if self.level1[b] == 0 || other.level1[b] == 0 {
+                continue;
+            }
+
+            if let (Some(dst), Some(src)) =
+                (&mut self.level2[b], &other.level2[b])
+            {
+                for (dst, src) in dst.iter_mut().zip(src.iter()) {
+                    *dst &= !*src;
+                }
 
-        match &self.level2[usize::from(i0)] {
-            Some(level2_array) => 0 < level2_array[usize::from(i1)] & (1 << i2),
-            None => false,
+                self.update_summary(b);
+            }
         }
     }
 
-    /// Sets bit.
-    pub fn set(&mut self, index: u32) -> bool {
-        let (i0, i1, i2) = Self::split_index(index);
-
-        self.level0 |= 1 << i0;
-        self.level1[usize::from(i0)] |= 1 << i1;
-        match &mut self.level2[usize::from(i0)] {
-            Some(level2_array) => {
-                let level2 = &mut level2_array[usize::from(i1)];
-                let old = (*level2) & (1 << i2);
-                *level2 |= 1 << i2;
-                old > 0
+    /// Recomputes upper levels for block after bulk modification.
+    fn update_summary(&mut self, b: usize) {
+        let mut summary = 0;
+        if let Some(block) = &self.level2[b] {
+            for (w, word) in block.iter().enumerate() {
+                if *word != 0 {
+                    summary |= 1 << w;
+                }
+            }
+        }
+
+        self.level1[b] = summary;
+        if summary == 0 {
+            self.level0[b >> 6] &= !(1 << (b & 63));
+        } else {
+            self.level0[b >> 6] |= 1 << (b & 63);
+        }
+    }
+
+    /// Returns word with specified index.
+    fn word(&self, index: usize) -> u64 {
+        match &self.level2[index >> 6] {
+            Some(block) => block[index & 63],
+            None => 0,
+        }
+    }
+
+    /// Returns index of first non-zero word starting from `from`.
+    fn next_nonzero_word(&self, from: usize) -> Option<usize> {
+        let mut b = from >> 6;
+        let mut mask = !0u64 << (from & 63);
+
+        while b < self.level1.len() {
+            let bits = self.level1[b] & mask;
+            if bits != 0 {
+                return Some((b << 6) | bits.trailing_zeros() as usize);
             }
-            None => {
-                let mut level2_array = Box::new([0; 64]);
-                level2_array[usize::from(i1)] |= 1 << i2;
-                self.level2[usize::from(i0)] = Some(level2_array);
-                false
+
+            b = self.next_nonempty_block(b + 1)?;
+            mask = !0;
+        }
+
+        None
+    }
+
+    /// Returns index of first non-empty block starting from `from`.
+    fn next_nonempty_block(&self, from: usize) -> Option<usize> {
+        let mut i = from >> 6;
+        let mut mask = !0u64 << (from & 63);
+
+        while i < self.level0.len() {
+            let bits = self.level0[i] & mask;
+            if bits != 0 {
+                return Some((i << 6) | bits.trailing_zeros() as usize);
             }
+
+            i += 1;
+            mask = !0;
         }
+
+        None
     }
 
-    /// Unsets bit.
-    pub fn unset(&mut self, index: u32) -> bool {
-        let (i0, i1, i2) = Self::split_index(index);
-        self.level0 &= !(1 << i0);
-        self.level1[usize::from(i0)] &= !(1 << i1);
-        if let Some(level2_array) = &mut self.level2[usize::from(i0)] {
-            let level2 = &mut level2_array[usize::from(i1)];
-            let old = (*level2) & (1 << i2);
-            *level2 &= !(1 << i2);
-            old > 0
-        } else {
-            false
+    fn split_index(index: u32) -> (usize, usize, usize) {
+        let b = index >> 12;
+        let w = (index >> 6) & 63;
+        let bit = index & 63;
+
+        (b as usize, w as usize, bit as usize)
+    }
+}
+
+/// Iterator over indices of set bits.
+pub struct Ones<'a> {
+    bitset: &'a BoxedBitSet,
+    next: usize,
+    base: u32,
+    bits: u64,
+}
+
+impl Iterator for Ones<'_> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        loop {
+            if self.bits != 0 {
+                let bit = self.bits.trailing_zeros();
+                self.bits &= self.bits - 1;
+                return Some(self.base | bit);
+            }
+
+            let word = self.bitset.next_nonzero_word(self.next)?;
+            self.next = word + 1;
+            self.base = (word as u32) << 6;
+            self.bits = self.bitset.word(word);
         }
     }
+}
+
+/// Iterator over indices of unset bits.
+pub struct Zeros<'a> {
+    bitset: &'a BoxedBitSet,
+    next: usize,
+    base: u32,
+    bits: u64,
+}
+
+impl Iterator for Zeros<'_> {
+    type Item = u32;
 
-    fn split_index(index: u32) -> (u8, u8, u8) {
-        debug_assert!(
-            Self::UPPER_BOUND > index,
-            "`index` = {} must not exceed `64 ^ 3 - 1`",
-            index
-        );
-        let i0 = index >> 12;
-        let i1 = (index >> 6) & 63;
-        let i2 = index & 63;
+    fn next(&mut self) -> Option<u32> {
+        loop {
+            if self.bits != 0 {
+                let bit = self.bits.trailing_zeros();
+                self.bits &= self.bits - 1;
+                return Some(self.base | bit);
+            }
+
+            if self.next >= self.bitset.level1.len() * 64 {
+                return None;
+            }
 
-        (i0 as u8, i1 as u8, i2 as u8)
+            self.base = (self.next as u32) << 6;
+            self.bits = !self.bitset.word(self.next);
+            self.next += 1;
+        }
     }
 }
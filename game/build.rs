@@ -0,0 +1,20 @@
+use {eyre::Report, noise::pack_blue_noise, std::path::Path};
+
+fn main() -> Result<(), Report> {
+    // Pack bundled blue noise into file embedded into the game.
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let images = root
+        .join("..")
+        .join("engine")
+        .join("blue_noise")
+        .join("256_256");
+    let output = Path::new(&std::env::var("OUT_DIR")?).join("blue_noise.wbn");
+
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed={}", images.display());
+
+    let noise = pack_blue_noise(&images, 128)?;
+    std::fs::write(&output, noise.encode())?;
+
+    Ok(())
+}
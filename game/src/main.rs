@@ -8,29 +8,41 @@ use {
     hecs::{Entity, EntityBuilder, World},
     nalgebra as na,
     std::{alloc::System, cmp::max, time::Duration},
-    tracing_subscriber::layer::SubscriberExt as _,
     wilds::{
         animate::Pose,
         assets::{
-            GltfAsset, GltfFormat, Prefab, RonFormat, TerrainAsset,
+            BlueNoise, GltfAsset, GltfFormat, Prefab, RonFormat, TerrainAsset,
             TerrainFormat,
         },
+        behavior::BehaviorSystem,
         camera::{
             following::{FollowingCamera, FollowingCameraSystem},
             free::{FreeCamera, FreeCameraSystem},
+            rig::{CameraRig, CameraRigSystem, CameraShake},
             Camera,
         },
-        clocks::Clocks,
-        engine::{Engine, SystemContext},
+        clocks::{Clocks, TimeScale},
+        console::{ConsoleState, ConsoleSystem},
+        cvar::CVars,
+        engine::{Engine, RendererExit},
         fps_counter::FpsCounter,
-        light::{DirectionalLight, PointLight, SkyLight},
+        light::{DirectionalLight, Fog, PointLight, SkyLight},
+        logging::LogConfig,
+        navmesh::NavAgentSystem,
+        photo::PhotoModeSystem,
         physics::{Constants, Physics},
         renderer::{
-            BufferUsage, Extent2d, IndexType, Material, Mesh, Normal3d,
-            PoseMesh, Position3d, PositionNormalTangent3dUV, RenderConstants,
-            Renderable, Renderer, Skin, Tangent3d, VertexType as _, UV,
+            is_device_lost, AccumulationOverlay, BufferUsage, Extent2d,
+            FrameGraphOverlay, IndexType, Material, Mesh, Normal3d,
+            PixelInspectorOverlay, PoseMesh, Position3d,
+            PositionNormalTangent3dUV, Renderable, Renderer, Skin, Tangent3d,
+            VertexType as _, VirtualTextureSystem, UV,
         },
         scene::{Global3, Local3, SceneSystem},
+        script::ScriptSystem,
+        time_of_day::{DayNightSystem, Sun, TimeOfDay},
+        water::WaterSystem,
+        weather::WeatherSystem,
     },
     winit::{
         dpi::PhysicalSize,
@@ -50,13 +62,7 @@ const WINDOW_EXTENT: Extent2d = Extent2d {
 fn main() -> Result<(), Report> {
     color_eyre::install()?;
 
-    tracing::subscriber::set_global_default(
-        tracing_subscriber::fmt()
-            .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-            .pretty()
-            .finish()
-            .with(tracing_error::ErrorLayer::default()),
-    )?;
+    wilds::logging::init(LogConfig::default())?;
 
     tracing::info!("App started");
 
@@ -76,7 +82,11 @@ fn main() -> Result<(), Report> {
         let aspect = WINDOW_EXTENT.aspect_ratio();
 
         let mut bump = Bump::with_capacity(1024 * 1024);
-        let mut renderer = Renderer::new(&window)?;
+        let blue_noise = BlueNoise::decode(include_bytes!(concat!(
+            env!("OUT_DIR"),
+            "/blue_noise.wbn"
+        )))?;
+        let (mut renderer, mut view) = Renderer::new(&window, &blue_noise)?;
         let mut clocks = Clocks::new();
 
         let sunlight = (na::Vector3::new(255.0, 207.0, 72.0) / 255.0)
@@ -93,28 +103,23 @@ fn main() -> Result<(), Report> {
             SkyLight {
                 radiance: skyradiance.into(),
             },
+            Sun,
+            Fog {
+                density: 0.0,
+                color: skyradiance.into(),
+            },
         ));
 
-        engine.add_system(move |ctx: SystemContext<'_>| {
-            let elapsed = ctx.clocks.step - ctx.clocks.start;
-            let d = elapsed.as_secs_f32() / 10.0;
-            let mut query = ctx.world.query::<&mut DirectionalLight>();
-
-            for (_, dirlight) in query.iter() {
-                dirlight.direction = na::Vector3::new(
-                    d.sin() * 30.0,
-                    d.cos() * 25.0,
-                    d.cos() * 5.0,
-                );
-            }
-
-            let mut query = ctx.world.query::<&mut SkyLight>();
-
-            for (_, skylight) in query.iter() {
-                skylight.radiance =
-                    (skyradiance * (1.1 - d.cos()) / 2.1).into();
-            }
-        });
+        engine
+            .resources
+            .insert(TimeOfDay::new(12.0).with_day_length(60.0));
+        engine.add_system(DayNightSystem::new(
+            sunlight.into(),
+            skyradiance.into(),
+        ));
+        engine.add_system(WeatherSystem::new(&engine));
+        engine.add_system(VirtualTextureSystem::new(&engine));
+        engine.add_system(WaterSystem);
 
         // engine.world.spawn((
         //     PointLight {
@@ -184,6 +189,9 @@ fn main() -> Result<(), Report> {
             Global3::identity(),
             // FollowingCamera { follows: pawn },
             FreeCamera,
+            engine.config().camera,
+            CameraRig::new(),
+            CameraShake::new(),
         ));
 
         engine.add_system(
@@ -198,6 +206,60 @@ fn main() -> Result<(), Report> {
                 .with_speed(3.0),
         );
 
+        engine.add_system(CameraRigSystem::new());
+        engine.add_system(
+            PhotoModeSystem::new().with_factor(0.003).with_speed(3.0),
+        );
+
+        let mut scripts = ScriptSystem::new(&engine);
+        scripts.register_prefab::<GltfAsset, _>(
+            "gltf",
+            GltfFormat::for_raytracing(),
+        );
+        engine.add_system(scripts);
+
+        engine.add_system(BehaviorSystem::new(&engine));
+        engine.add_system(NavAgentSystem);
+
+        let prefabs = engine.prefab_loader();
+        engine.add_system(ConsoleSystem::new().with_command(
+            "spawn",
+            "spawn <prefab> [count] - spawns prefabs above the origin",
+            move |args, ctx| {
+                let (name, count): (&str, u32) = match args {
+                    [name] => (*name, 1),
+                    [name, count] => (*name, count.parse()?),
+                    _ => eyre::bail!("Expected prefab name and count"),
+                };
+
+                for index in 0..count {
+                    let iso = na::Isometry3::translation(
+                        (index % 10) as f32,
+                        5.0 + (index / 10) as f32,
+                        0.0,
+                    );
+
+                    match name {
+                        "pawn" => {
+                            prefabs.load_prefab_with_format::<PawnAsset, _>(
+                                ctx.world,
+                                "pawn.ron".into(),
+                                iso,
+                                RonFormat,
+                            );
+                        }
+                        _ => eyre::bail!("Unknown prefab `{}`", name),
+                    }
+                }
+
+                ctx.print(format!("Spawning {} {}", count, name));
+                Ok(())
+            },
+        ));
+        engine.add_system(FrameGraphOverlay);
+        engine.add_system(PixelInspectorOverlay::new());
+        engine.add_system(AccumulationOverlay);
+
         // engine.add_system(|context: SystemContext<'_>| {
         //     for (_, pose) in context.world.query::<&mut Pose>().iter() {
         //         if let [_, mid, ..] = &mut *pose.matrices {
@@ -265,13 +327,21 @@ fn main() -> Result<(), Report> {
                     break;
                 }
                 Event::MainEventsCleared => {
-                    engine.advance(&bump);
+                    engine.advance();
                     window.request_redraw();
 
                     // tracing::info!("Advance:\n{:#?}",
                     // reg.change_and_reset());
                 }
                 Event::RedrawRequested(_) => {
+                    // Renderer keeps drawing while simulation is paused,
+                    // particles advance with simulation time.
+                    let scale = engine
+                        .resources
+                        .get::<TimeScale>()
+                        .map_or(1.0, |t| t.scale);
+                    clocks.set_scale(scale);
+
                     let clock = clocks.step();
                     fps_counter.add_sample(clock.delta);
 
@@ -295,12 +365,37 @@ fn main() -> Result<(), Report> {
                     ticker -= clock.delta;
 
                     tracing::trace!("Request redraw");
-                    renderer.draw(
+                    if let Err(err) = renderer.draw(
+                        &mut view,
                         &mut engine.world,
                         &mut engine.resources,
                         &clock,
                         &bump,
-                    )?;
+                    ) {
+                        if !is_device_lost(&err) {
+                            return Err(err);
+                        }
+
+                        tracing::error!("Device lost. Recovering");
+                        let lost =
+                            renderer.recover(&mut view, &mut engine.world)?;
+
+                        // Prefabs are spawned again with assets
+                        // loaded on new device.
+                        engine.reload_assets();
+
+                        // Entities that are not part of prefabs
+                        // have nothing to be restored from.
+                        for entity in lost {
+                            if engine.world.contains(entity) {
+                                tracing::error!(
+                                    "Despawning {:?} that lost its renderable",
+                                    entity
+                                );
+                                engine.despawn(entity);
+                            }
+                        }
+                    }
                 }
                 Event::DeviceEvent {
                     event:
@@ -311,13 +406,39 @@ fn main() -> Result<(), Report> {
                         }),
                     ..
                 } => {
-                    let filter_enabled = &mut engine
+                    let console_open = engine
+                        .resources
+                        .get::<ConsoleState>()
+                        .map_or(false, |state| state.open);
+
+                    if !console_open {
+                        if let Some(cvars) = engine.resources.get_mut::<CVars>()
+                        {
+                            if let Err(err) = cvars.toggle("r.filter_enabled") {
+                                tracing::error!("{}", err);
+                            }
+                        }
+                    }
+                }
+                Event::DeviceEvent {
+                    event:
+                        DeviceEvent::Key(KeyboardInput {
+                            virtual_keycode: Some(VirtualKeyCode::Pause),
+                            state: ElementState::Released,
+                            ..
+                        }),
+                    ..
+                } => {
+                    let time_scale = engine
                         .resources
-                        .entry::<RenderConstants>()
-                        .or_insert_with(RenderConstants::new)
-                        .filter_enabled;
+                        .entry::<TimeScale>()
+                        .or_insert_with(TimeScale::new);
 
-                    *filter_enabled = !*filter_enabled;
+                    if time_scale.is_paused() {
+                        *time_scale = TimeScale::new();
+                    } else {
+                        *time_scale = TimeScale::paused();
+                    }
                 }
                 _ => {}
             }
@@ -326,6 +447,9 @@ fn main() -> Result<(), Report> {
             engine.assets.process(&mut *renderer);
         }
 
-        Ok(())
+        Ok(Some(RendererExit {
+            renderer,
+            views: vec![view],
+        }))
     })
 }
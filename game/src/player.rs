@@ -1,40 +1,16 @@
 use {
     hecs::Entity,
-    std::collections::HashMap,
-    wilds::engine::{System, SystemContext},
+    wilds::{
+        engine::{System, SystemContext},
+        input::{ActionMap, InputEvent},
+    },
     winit::{
         dpi::PhysicalSize,
-        event::{
-            ElementState, Event, KeyboardInput, MouseButton, MouseScrollDelta,
-            VirtualKeyCode, WindowEvent,
-        },
+        event::{ElementState, Event, MouseButton, WindowEvent},
         window::{Window, WindowId},
     },
 };
 
-#[derive(
-    Clone,
-    Copy,
-    Debug,
-    PartialEq,
-    Eq,
-    Hash,
-    serde::Serialize,
-    serde::Deserialize,
-)]
-pub enum InputEvent {
-    KeyboardInput {
-        code: VirtualKeyCode,
-        state: ElementState,
-    },
-    MouseInput {
-        button: MouseButton,
-        state: ElementState,
-    },
-    MouseWheelUp,
-    MouseWheelDown,
-}
-
 #[derive(
     Clone,
     Copy,
@@ -49,31 +25,19 @@ pub enum Action {
     MoveToCursor,
 }
 
-#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
-pub struct ActionMap {
-    map: HashMap<InputEvent, Action>,
-}
-
-impl ActionMap {
-    pub fn get(&self, input: &InputEvent) -> Option<Action> {
-        self.map.get(input).copied()
-    }
-}
-
-impl Default for ActionMap {
-    fn default() -> Self {
-        let mut map = HashMap::new();
+/// Returns action map player uses by default.
+pub fn default_action_map() -> ActionMap<Action> {
+    let mut map = ActionMap::new();
 
-        map.insert(
-            InputEvent::MouseInput {
-                button: MouseButton::Left,
-                state: ElementState::Pressed,
-            },
-            Action::MoveToCursor,
-        );
+    map.insert(
+        InputEvent::MouseInput {
+            button: MouseButton::Left,
+            state: ElementState::Pressed,
+        },
+        Action::MoveToCursor,
+    );
 
-        ActionMap { map }
-    }
+    map
 }
 
 /// Player controller.
@@ -83,19 +47,19 @@ pub struct Player {
     cursor_pos: [f64; 2],
     window_size: PhysicalSize<u32>,
     window_id: WindowId,
-    action_map: ActionMap,
+    action_map: ActionMap<Action>,
     controls: Entity,
 }
 
 impl Player {
     pub fn new(window: &Window, controls: Entity) -> Self {
-        Self::with_action_map(window, controls, ActionMap::default())
+        Self::with_action_map(window, controls, default_action_map())
     }
 
     pub fn with_action_map(
         window: &Window,
         controls: Entity,
-        action_map: ActionMap,
+        action_map: ActionMap<Action>,
     ) -> Self {
         Player {
             cursor_pos: [0.5; 2],
@@ -123,31 +87,7 @@ impl Player {
                         ];
                         None
                     }
-                    &WindowEvent::MouseWheel {
-                        delta: MouseScrollDelta::LineDelta(_, y),
-                        ..
-                    } => {
-                        if y > 0.1 {
-                            Some(InputEvent::MouseWheelUp)
-                        } else if y < -0.1 {
-                            Some(InputEvent::MouseWheelDown)
-                        } else {
-                            None
-                        }
-                    }
-                    &WindowEvent::MouseInput { button, state, .. } => {
-                        Some(InputEvent::MouseInput { button, state })
-                    }
-                    &WindowEvent::KeyboardInput {
-                        input:
-                            KeyboardInput {
-                                virtual_keycode: Some(code),
-                                state,
-                                ..
-                            },
-                        ..
-                    } => Some(InputEvent::KeyboardInput { code, state }),
-                    _ => None,
+                    event => InputEvent::from_window_event(event),
                 }
             }
             _ => None,
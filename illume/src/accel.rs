@@ -312,4 +312,23 @@ impl AccelerationStructureInstance {
 
         self
     }
+
+    /// Sets instance flags keeping shader binding offset.
+    pub fn with_flags(mut self, flags: GeometryInstanceFlags) -> Self {
+        let offset = self.shader_binding_offset_flags.0 & 0x00ff_ffff;
+        self.shader_binding_offset_flags =
+            InstanceShaderBindingOffsetAndFlags::new(offset, flags);
+
+        self
+    }
+
+    /// Sets instance custom index keeping mask.
+    /// Hit shaders read it as `gl_InstanceCustomIndexEXT`.
+    pub fn with_custom_index(mut self, custom_index: u32) -> Self {
+        let mask = (self.custom_index_mask.0 >> 24) as u8;
+        self.custom_index_mask =
+            InstanceCustomIndexAndMask::new(custom_index, mask);
+
+        self
+    }
 }
@@ -1,11 +1,36 @@
-#[cfg(feature = "vulkan")]
+//! Backend implementations.
+//!
+//! Exactly one backend is selected at build time by cargo feature.
+//! Backend module must export `Graphics`, `PhysicalDevice`, `Device`,
+//! `WeakDevice`, `Queue`, `CommandBuffer`, `Surface`, `Swapchain`,
+//! `SwapchainImage` and resource types, which are re-exported by
+//! backend-agnostic modules of this crate.
+//! Backend-specific handles must not appear in public API.
+//!
+//! There is no runtime dispatch between backends.
+//! Types are not trait objects nor enums over backends,
+//! each backend provides concrete types with the same inherent methods.
+//! Code written against this crate compiles with any backend,
+//! but one binary can use only one backend.
+//!
+//! `null` backend takes precedence over `wgpu` and `vulkan`
+//! when several are enabled, so tests can select it without disabling
+//! default features. `wgpu` backend takes precedence over `vulkan`.
+
+#[cfg(all(feature = "vulkan", not(feature = "wgpu"), not(feature = "null")))]
 mod vulkan;
 
-#[cfg(feature = "vulkan")]
+#[cfg(all(feature = "vulkan", not(feature = "wgpu"), not(feature = "null")))]
 pub use vulkan::*;
 
-// #[cfg(feature = "wgpu")]
-// mod wgpu;
+#[cfg(all(feature = "wgpu", not(feature = "null")))]
+mod webgpu;
+
+#[cfg(all(feature = "wgpu", not(feature = "null")))]
+pub use webgpu::*;
+
+#[cfg(feature = "null")]
+mod null;
 
-// #[cfg(feature = "wgpu")]
-// pub use wgpu::*;
+#[cfg(feature = "null")]
+pub use null::*;
@@ -0,0 +1,1179 @@
+use {
+    super::{
+        physical::{
+            format_properties, image_format_properties, image_format_usage,
+            max_image_dimension, MAX_IMAGE_LAYERS,
+        },
+        resources::MappableBuffer,
+    },
+    crate::{
+        accel::{
+            AccelerationStructure, AccelerationStructureBuildFlags,
+            AccelerationStructureBuildSizesInfo,
+            AccelerationStructureGeometryInfo, AccelerationStructureInfo,
+            AccelerationStructureLevel,
+        },
+        align_up, arith_le, arith_ne, assert_object,
+        buffer::{
+            Buffer, BufferCaptureAddress, BufferInfo, BufferUsage,
+            StridedBufferRegion,
+        },
+        descriptor::{
+            CopyDescriptorSet, CreateDescriptorSetError, DescriptorPoolConfig,
+            DescriptorSet, DescriptorSetInfo, DescriptorSetLayout,
+            DescriptorSetLayoutInfo, Descriptors, WriteDescriptorSet,
+        },
+        fence::{Fence, WaitError},
+        format::{Format, FormatProperties, ImageFormatProperties},
+        framebuffer::{Framebuffer, FramebufferInfo},
+        image::{Image, ImageExtent, ImageInfo, ImageUsage, Samples},
+        memory::{MemoryStats, MemoryUsage},
+        physical::Feature,
+        pipeline::{
+            ComputePipeline, ComputePipelineInfo, GraphicsPipeline,
+            GraphicsPipelineInfo, PipelineLayout, PipelineLayoutInfo,
+            RayTracingPipeline, RayTracingPipelineInfo, ShaderBindingTable,
+            ShaderBindingTableInfo,
+        },
+        query::{QueryPool, QueryPoolInfo},
+        render_pass::{RenderPass, RenderPassInfo},
+        sampler::{Sampler, SamplerInfo},
+        semaphore::Semaphore,
+        shader::{
+            CreateShaderModuleError, InvalidShader, ShaderLanguage,
+            ShaderModule, ShaderModuleInfo,
+        },
+        surface::{Surface, SurfaceError},
+        swapchain::Swapchain,
+        view::{ImageView, ImageViewInfo, ImageViewKind},
+        CreateImageError, DeviceAddress, ImageSize, MapError, OutOfMemory,
+    },
+    bytemuck::Pod,
+    std::{
+        convert::TryFrom as _,
+        fmt::{self, Debug},
+        mem::{size_of_val, MaybeUninit},
+        num::NonZeroU64,
+        ops::Range,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc, Weak,
+        },
+    },
+};
+
+/// Size of memory reported as device-local.
+const DEVICE_LOCAL_MEMORY: u64 = 1 << 32;
+
+/// First device address given to buffers.
+const FIRST_ADDRESS: u64 = 0x1_0000;
+
+/// Alignment mask of device addresses.
+const ADDRESS_ALIGN_MASK: u64 = 255;
+
+/// Size of shader group handles in shader binding tables.
+const SHADER_GROUP_HANDLE_SIZE: u64 = 32;
+
+/// Alignment mask of shader binding table regions.
+const SHADER_GROUP_BASE_ALIGN_MASK: u64 = 63;
+
+pub(crate) struct Inner {
+    features: Vec<Feature>,
+    allocated: AtomicU64,
+    next_address: AtomicU64,
+}
+
+impl Debug for Inner {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("Device")
+            .field("features", &self.features)
+            .field("allocated", &self.allocated)
+            .finish()
+    }
+}
+
+#[derive(Clone)]
+pub struct WeakDevice {
+    inner: Weak<Inner>,
+}
+
+impl Debug for WeakDevice {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.inner.upgrade() {
+            Some(device) => device.fmt(fmt),
+            None => write!(fmt, "Destroyed device: {:p}", self.inner.as_ptr()),
+        }
+    }
+}
+
+impl WeakDevice {
+    pub fn upgrade(&self) -> Option<Device> {
+        self.inner.upgrade().map(|inner| Device { inner })
+    }
+
+    pub fn is(&self, device: &Device) -> bool {
+        self.inner.as_ptr() == &*device.inner
+    }
+}
+
+impl PartialEq<WeakDevice> for WeakDevice {
+    fn eq(&self, weak: &WeakDevice) -> bool {
+        std::ptr::eq(weak.inner.as_ptr(), self.inner.as_ptr())
+    }
+}
+
+impl PartialEq<WeakDevice> for Device {
+    fn eq(&self, weak: &WeakDevice) -> bool {
+        std::ptr::eq(weak.inner.as_ptr(), &*self.inner)
+    }
+}
+
+impl PartialEq<WeakDevice> for &'_ WeakDevice {
+    fn eq(&self, weak: &WeakDevice) -> bool {
+        std::ptr::eq(weak.inner.as_ptr(), self.inner.as_ptr())
+    }
+}
+
+impl PartialEq<WeakDevice> for &'_ Device {
+    fn eq(&self, weak: &WeakDevice) -> bool {
+        std::ptr::eq(weak.inner.as_ptr(), &*self.inner)
+    }
+}
+
+/// Opaque value that represents graphics API device.
+/// It is used to manage (create, destroy, check state) most of the device
+/// resources.
+#[derive(Clone)]
+#[repr(transparent)]
+pub struct Device {
+    inner: Arc<Inner>,
+}
+
+impl Debug for Device {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if fmt.alternate() {
+            self.inner.fmt(fmt)
+        } else {
+            write!(fmt, "NullDevice({:p})", &*self.inner)
+        }
+    }
+}
+
+impl Device {
+    pub(super) fn new(features: Vec<Feature>) -> Self {
+        Device {
+            inner: Arc::new(Inner {
+                features,
+                allocated: AtomicU64::new(0),
+                next_address: AtomicU64::new(FIRST_ADDRESS),
+            }),
+        }
+    }
+
+    pub(crate) fn downgrade(&self) -> WeakDevice {
+        WeakDevice {
+            inner: Arc::downgrade(&self.inner),
+        }
+    }
+
+    fn is_enabled(&self, feature: Feature) -> bool {
+        self.inner.features.contains(&feature)
+    }
+
+    /// Returns new device address range of specified size.
+    fn allocate_address(&self, size: u64) -> DeviceAddress {
+        let size = align_up(ADDRESS_ALIGN_MASK, size.max(1))
+            .unwrap_or_else(|| panic!("Address space exhausted"));
+
+        let address =
+            self.inner.next_address.fetch_add(size, Ordering::Relaxed);
+
+        DeviceAddress(NonZeroU64::new(address).unwrap())
+    }
+
+    /// Called when buffer is destroyed.
+    pub(super) fn free_memory(&self, size: u64) {
+        self.inner.allocated.fetch_sub(size, Ordering::Relaxed);
+    }
+
+    /// Returns statistics of device memory usage.
+    /// Only memory of buffers is counted.
+    pub fn memory_stats(&self) -> MemoryStats {
+        MemoryStats {
+            allocated: self.inner.allocated.load(Ordering::Relaxed),
+            device_local: DEVICE_LOCAL_MEMORY,
+        }
+    }
+
+    /// Creates buffer without host memory.
+    #[tracing::instrument]
+    pub fn create_buffer(
+        &self,
+        info: BufferInfo,
+    ) -> Result<Buffer, OutOfMemory> {
+        self.create_buffer_impl(info, false)
+    }
+
+    /// Creates buffer with host memory that can be mapped.
+    #[tracing::instrument]
+    pub fn create_mappable_buffer(
+        &self,
+        info: BufferInfo,
+        memory_usage: MemoryUsage,
+    ) -> Result<MappableBuffer, OutOfMemory> {
+        let buffer = self.create_buffer_impl(info, true)?;
+        Ok(MappableBuffer::from_buffer(buffer, memory_usage))
+    }
+
+    fn create_buffer_impl(
+        &self,
+        info: BufferInfo,
+        mappable: bool,
+    ) -> Result<Buffer, OutOfMemory> {
+        assert!(info.is_valid());
+
+        if info.usage.contains(BufferUsage::DEVICE_ADDRESS) {
+            assert!(
+                self.is_enabled(Feature::BufferDeviceAddress),
+                "`BufferDeviceAddress` feature is not enabled"
+            );
+        }
+
+        if info.capture_address.is_some() {
+            assert!(
+                self.is_enabled(Feature::BufferDeviceAddressCaptureReplay),
+                "`BufferDeviceAddressCaptureReplay` feature is not enabled"
+            );
+
+            assert!(
+                info.usage.contains(BufferUsage::DEVICE_ADDRESS),
+                "Buffer with capture address must have `DEVICE_ADDRESS` usage"
+            );
+        }
+
+        let allocated = self.inner.allocated.load(Ordering::Relaxed);
+        if info.size > DEVICE_LOCAL_MEMORY - allocated.min(DEVICE_LOCAL_MEMORY)
+        {
+            return Err(OutOfMemory);
+        }
+
+        let memory = if mappable {
+            let size = usize::try_from(info.size).map_err(|_| OutOfMemory)?;
+            Some(vec![0; size].into_boxed_slice().into())
+        } else {
+            None
+        };
+
+        // Capture address is equal to device address.
+        let address = match info.capture_address {
+            Some(capture) => NonZeroU64::new(capture.buffer).map(DeviceAddress),
+            None if info.usage.contains(BufferUsage::DEVICE_ADDRESS) => {
+                Some(self.allocate_address(info.size))
+            }
+            None => None,
+        };
+
+        self.inner.allocated.fetch_add(info.size, Ordering::Relaxed);
+
+        tracing::debug!("Buffer created");
+        Ok(Buffer::new(info, self.downgrade(), address, memory))
+    }
+
+    /// Creates buffer with initial data.
+    /// Data is not kept because device-only buffers can't be read back.
+    #[tracing::instrument(skip(data))]
+    pub fn create_buffer_static<T: 'static>(
+        &self,
+        info: BufferInfo,
+        data: &[T],
+    ) -> Result<Buffer, OutOfMemory>
+    where
+        T: Pod,
+    {
+        assert!(info.is_valid());
+        if arith_ne(info.size, size_of_val(data)) {
+            panic!(
+                "Buffer size {} does not match data size {}",
+                info.size,
+                size_of_val(data)
+            );
+        }
+
+        self.create_buffer(info)
+    }
+
+    #[tracing::instrument]
+    pub fn create_fence(&self) -> Result<Fence, OutOfMemory> {
+        Ok(Fence::new(self.downgrade()))
+    }
+
+    /// Creates framebuffer for specified render pass from views.
+    #[tracing::instrument]
+    pub fn create_framebuffer(
+        &self,
+        info: FramebufferInfo,
+    ) -> Result<Framebuffer, OutOfMemory> {
+        for view in &info.views {
+            assert_owner!(view, self);
+        }
+
+        assert_owner!(info.render_pass, self);
+
+        assert!(
+            info.views.iter()
+                .all(|view| view.info().view_kind == ImageViewKind::D2),
+            "All image views for Framebuffer must have `view_kind == ImageViewKind::D2`",
+        );
+
+        assert!(
+            info.views.iter()
+                .all(|view| view.info().image.info().extent.into_2d() >= info.extent),
+            "All image views for Framebuffer must be at least as large as framebuffer extent",
+        );
+
+        assert_eq!(
+            info.views.len(),
+            info.render_pass.info().attachments.len(),
+            "Framebuffer must have view for each render pass attachment",
+        );
+
+        Ok(Framebuffer::new(info, self.downgrade()))
+    }
+
+    #[tracing::instrument]
+    pub fn create_graphics_pipeline(
+        &self,
+        info: GraphicsPipelineInfo,
+    ) -> Result<GraphicsPipeline, OutOfMemory> {
+        assert_owner!(info.layout, self);
+        assert_owner!(info.render_pass, self);
+        assert_owner!(info.vertex_shader.module(), self);
+
+        if let Some(fragment_shader) = info
+            .rasterizer
+            .as_ref()
+            .and_then(|r| r.fragment_shader.as_ref())
+        {
+            assert_owner!(fragment_shader.module(), self);
+        }
+
+        assert!(
+            (info.subpass as usize) < info.render_pass.info().subpasses.len(),
+            "Subpass {} is out of bounds",
+            info.subpass
+        );
+
+        Ok(GraphicsPipeline::new(info, self.downgrade()))
+    }
+
+    #[tracing::instrument]
+    pub fn create_compute_pipeline(
+        &self,
+        info: ComputePipelineInfo,
+    ) -> Result<ComputePipeline, OutOfMemory> {
+        assert_owner!(info.shader.module(), self);
+        assert_owner!(info.layout, self);
+
+        Ok(ComputePipeline::new(info, self.downgrade()))
+    }
+
+    /// Creates image. It has no memory that could be accessed by host.
+    #[tracing::instrument]
+    pub fn create_image(
+        &self,
+        info: ImageInfo,
+    ) -> Result<Image, CreateImageError> {
+        info.validate(self)?;
+
+        Ok(Image::new(info, self.downgrade()))
+    }
+
+    /// Creates image. Memory usage has no effect without device memory.
+    #[tracing::instrument]
+    pub fn create_image_with_memory_usage(
+        &self,
+        info: ImageInfo,
+        _memory_usage: MemoryUsage,
+    ) -> Result<Image, CreateImageError> {
+        self.create_image(info)
+    }
+
+    #[tracing::instrument]
+    pub fn create_image_view(
+        &self,
+        info: ImageViewInfo,
+    ) -> Result<ImageView, OutOfMemory> {
+        assert_owner!(info.image, self);
+
+        Ok(ImageView::new(info, self.downgrade()))
+    }
+
+    #[tracing::instrument]
+    pub fn create_pipeline_layout(
+        &self,
+        info: PipelineLayoutInfo,
+    ) -> Result<PipelineLayout, OutOfMemory> {
+        for set in &info.sets {
+            assert_owner!(set, self);
+        }
+
+        Ok(PipelineLayout::new(info, self.downgrade()))
+    }
+
+    #[tracing::instrument]
+    pub fn create_render_pass(
+        &self,
+        info: RenderPassInfo,
+    ) -> Result<RenderPass, CreateRenderPassError> {
+        for (subpass, s) in info.subpasses.iter().enumerate() {
+            for (index, &attachment) in s.colors.iter().enumerate() {
+                if attachment >= info.attachments.len() {
+                    return Err(
+                        CreateRenderPassError::ColorAttachmentReferenceOutOfBound {
+                            subpass,
+                            index,
+                            attachment,
+                        },
+                    );
+                }
+            }
+
+            if let Some(attachment) = s.depth {
+                if attachment >= info.attachments.len() {
+                    return Err(
+                        CreateRenderPassError::DepthAttachmentReferenceOutOfBound {
+                            subpass,
+                            attachment,
+                        },
+                    );
+                }
+            }
+
+            check_subpass_resolves(&info, subpass)?;
+        }
+
+        Ok(RenderPass::new(info, self.downgrade()))
+    }
+
+    #[tracing::instrument]
+    pub fn create_semaphore(&self) -> Result<Semaphore, OutOfMemory> {
+        Ok(Semaphore::new(self.downgrade()))
+    }
+
+    /// Creates shader module.
+    /// Only header of SPIR-V code is checked.
+    #[tracing::instrument(skip(info))]
+    pub fn create_shader_module(
+        &self,
+        info: ShaderModuleInfo,
+    ) -> Result<ShaderModule, CreateShaderModuleError> {
+        let code = match info.language {
+            ShaderLanguage::SPIRV => &*info.code,
+            _ => {
+                return Err(
+                    CreateShaderModuleError::UnsupportedShaderLanguage {
+                        language: info.language,
+                    },
+                )
+            }
+        };
+
+        if code.is_empty() {
+            return Err(CreateShaderModuleError::InvalidShader {
+                source: InvalidShader::EmptySource,
+            });
+        }
+
+        if code.len() & 3 > 0 {
+            return Err(CreateShaderModuleError::InvalidShader {
+                source: InvalidShader::SizeIsNotMultipleOfFour,
+            });
+        }
+
+        let magic = u32::from_ne_bytes([code[0], code[1], code[2], code[3]]);
+
+        if magic != 0x07230203 {
+            return Err(CreateShaderModuleError::InvalidShader {
+                source: InvalidShader::WrongMagic { found: magic },
+            });
+        }
+
+        Ok(ShaderModule::new(info, self.downgrade()))
+    }
+
+    /// Creates swapchain for surface.
+    /// Images of the swapchain have extent reported by
+    /// `PhysicalDevice::surface_capabilities`.
+    #[tracing::instrument]
+    pub fn create_swapchain(
+        &self,
+        surface: &mut Surface,
+    ) -> Result<Swapchain, SurfaceError> {
+        assert!(
+            self.is_enabled(Feature::SurfacePresentation),
+            "`Feature::SurfacePresentation` must be enabled in order to create a `Swapchain`"
+        );
+
+        Swapchain::new(surface, self)
+    }
+
+    #[tracing::instrument]
+    pub fn reset_fences(&self, fences: &[&Fence]) -> Result<(), WaitError> {
+        for fence in fences {
+            assert_owner!(fence, self);
+            fence.reset();
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument]
+    pub fn is_fence_signalled(&self, fence: &Fence) -> Result<bool, WaitError> {
+        assert_owner!(fence, self);
+        Ok(fence.is_signalled())
+    }
+
+    /// Returns immediately as submitted work completes on submission.
+    ///
+    /// # Panics
+    ///
+    /// Panics if waited fences are not submitted
+    /// as wait would never end.
+    #[tracing::instrument]
+    pub fn wait_fences(
+        &self,
+        fences: &[&Fence],
+        all: bool,
+    ) -> Result<(), WaitError> {
+        for fence in fences {
+            assert_owner!(fence, self);
+        }
+
+        let done = if all {
+            fences.iter().all(|fence| fence.is_signalled())
+        } else {
+            fences.iter().any(|fence| fence.is_signalled())
+        };
+
+        assert!(
+            done || fences.is_empty(),
+            "Waiting for fences that are never signalled"
+        );
+
+        Ok(())
+    }
+
+    #[tracing::instrument]
+    pub fn wait_idle(&self) -> Result<(), WaitError> {
+        Ok(())
+    }
+
+    /// Returns sizes that grow with number of primitives.
+    /// Acceleration structures are never built by this backend.
+    #[tracing::instrument]
+    pub fn get_acceleration_structure_build_sizes(
+        &self,
+        _level: AccelerationStructureLevel,
+        _flags: AccelerationStructureBuildFlags,
+        geometry: &[AccelerationStructureGeometryInfo],
+    ) -> AccelerationStructureBuildSizesInfo {
+        assert!(
+            self.is_enabled(Feature::AccelerationStructure),
+            "`AccelerationStructure` feature is not enabled"
+        );
+
+        let primitives: u64 = geometry
+            .iter()
+            .map(|geometry| match *geometry {
+                AccelerationStructureGeometryInfo::Triangles {
+                    max_primitive_count,
+                    ..
+                }
+                | AccelerationStructureGeometryInfo::AABBs {
+                    max_primitive_count,
+                }
+                | AccelerationStructureGeometryInfo::Instances {
+                    max_primitive_count,
+                } => u64::from(max_primitive_count),
+            })
+            .sum();
+
+        let size = 256 + primitives * 64;
+
+        AccelerationStructureBuildSizesInfo {
+            acceleration_structure_size: size,
+            update_scratch_size: size,
+            build_scratch_size: size,
+        }
+    }
+
+    #[tracing::instrument]
+    pub fn create_acceleration_structure(
+        &self,
+        info: AccelerationStructureInfo,
+    ) -> Result<AccelerationStructure, OutOfMemory> {
+        assert!(
+            self.is_enabled(Feature::AccelerationStructure),
+            "`AccelerationStructure` feature is not enabled"
+        );
+
+        assert_owner!(info.region.buffer, self);
+
+        assert!(
+            info.region
+                .offset
+                .checked_add(info.region.size)
+                .map_or(false, |end| end <= info.region.buffer.info().size),
+            "Acceleration structure region is out of buffer bounds"
+        );
+
+        let address = match info.region.buffer.address() {
+            Some(mut address) => address.offset(info.region.offset),
+            None => self.allocate_address(info.region.size),
+        };
+
+        Ok(AccelerationStructure::new(info, self.downgrade(), address))
+    }
+
+    #[tracing::instrument]
+    pub fn get_buffer_device_address(
+        &self,
+        buffer: &Buffer,
+    ) -> Option<DeviceAddress> {
+        assert_owner!(buffer, self);
+
+        if buffer.info().usage.contains(BufferUsage::DEVICE_ADDRESS) {
+            Some(buffer.address().expect(
+                "Device address for buffer must be set when `BufferUsage::DEVICE_ADDRESS` is specified",
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// Returns opaque capture addresses of the buffer.
+    /// Both are equal to device address.
+    #[tracing::instrument]
+    pub fn get_buffer_opaque_capture_address(
+        &self,
+        buffer: &Buffer,
+    ) -> Option<BufferCaptureAddress> {
+        assert_owner!(buffer, self);
+
+        assert!(
+            self.is_enabled(Feature::BufferDeviceAddressCaptureReplay),
+            "`BufferDeviceAddressCaptureReplay` feature is not enabled"
+        );
+
+        buffer.address().map(|address| BufferCaptureAddress {
+            buffer: address.0.get(),
+            memory: address.0.get(),
+        })
+    }
+
+    #[tracing::instrument]
+    pub fn get_acceleration_structure_device_address(
+        &self,
+        acceleration_structure: &AccelerationStructure,
+    ) -> DeviceAddress {
+        assert_owner!(acceleration_structure, self);
+
+        acceleration_structure.address()
+    }
+
+    #[tracing::instrument]
+    pub fn create_ray_tracing_pipeline(
+        &self,
+        info: RayTracingPipelineInfo,
+    ) -> Result<RayTracingPipeline, OutOfMemory> {
+        assert!(
+            self.is_enabled(Feature::RayTracingPipeline),
+            "`RayTracingPipeline` feature is not enabled"
+        );
+
+        assert_owner!(info.layout, self);
+
+        for shader in &info.shaders {
+            assert_owner!(shader.module(), self);
+        }
+
+        if info.library || !info.libraries.is_empty() {
+            assert!(
+                self.is_enabled(Feature::RayTracingPipelineLibrary),
+                "`RayTracingPipelineLibrary` feature is not enabled"
+            );
+
+            assert!(
+                info.interface.is_some(),
+                "Interface must be specified for pipeline libraries and pipelines that link them"
+            );
+        }
+
+        for library in &info.libraries {
+            assert_owner!(library, self);
+            assert!(
+                library.info().library,
+                "Only pipeline libraries can be linked"
+            );
+            assert_eq!(
+                library.info().interface,
+                info.interface,
+                "Pipeline libraries must have the same interface"
+            );
+        }
+
+        let group_count = info.groups.len()
+            + info
+                .libraries
+                .iter()
+                .map(RayTracingPipeline::group_count)
+                .sum::<usize>();
+
+        Ok(RayTracingPipeline::new(info, self.downgrade(), group_count))
+    }
+
+    #[tracing::instrument]
+    pub fn create_descriptor_set_layout(
+        &self,
+        info: DescriptorSetLayoutInfo,
+    ) -> Result<DescriptorSetLayout, OutOfMemory> {
+        Ok(DescriptorSetLayout::new(info, self.downgrade()))
+    }
+
+    #[tracing::instrument]
+    pub fn create_descriptor_set(
+        &self,
+        info: DescriptorSetInfo,
+    ) -> Result<DescriptorSet, CreateDescriptorSetError> {
+        assert_owner!(info.layout, self);
+
+        Ok(DescriptorSet::new(info, self.downgrade()))
+    }
+
+    /// Descriptor sets are not pooled by this backend.
+    pub fn set_descriptor_pool_config(&self, _config: DescriptorPoolConfig) {}
+
+    /// Checks that written descriptors are owned by the device.
+    /// Descriptors are not stored.
+    #[tracing::instrument]
+    pub fn update_descriptor_sets<'a>(
+        &self,
+        writes: &[WriteDescriptorSet<'a>],
+        copies: &[CopyDescriptorSet<'a>],
+    ) {
+        for write in writes {
+            assert_owner!(write.set, self);
+
+            match write.descriptors {
+                Descriptors::Sampler(samplers) => {
+                    for sampler in samplers {
+                        assert_owner!(sampler, self);
+                    }
+                }
+                Descriptors::CombinedImageSampler(combos) => {
+                    for (view, _, sampler) in combos {
+                        assert_owner!(view, self);
+                        assert_owner!(sampler, self);
+                    }
+                }
+                Descriptors::SampledImage(views)
+                | Descriptors::StorageImage(views)
+                | Descriptors::InputAttachment(views) => {
+                    for (view, _) in views {
+                        assert_owner!(view, self);
+                    }
+                }
+                Descriptors::UniformBuffer(buffers)
+                | Descriptors::StorageBuffer(buffers)
+                | Descriptors::UniformBufferDynamic(buffers)
+                | Descriptors::StorageBufferDynamic(buffers) => {
+                    for &(ref buffer, offset, size) in buffers {
+                        assert_owner!(buffer, self);
+                        assert_ne!(
+                            size, 0,
+                            "Cannot write 0 sized buffer range into descriptor"
+                        );
+                        assert!(
+                            offset <= buffer.info().size
+                                && size <= buffer.info().size - offset,
+                            "Buffer ({:?}) descriptor range {}..+{} is out of bounds",
+                            buffer,
+                            offset,
+                            size,
+                        );
+                    }
+                }
+                Descriptors::AccelerationStructure(acceleration_structures) => {
+                    for acceleration_structure in acceleration_structures {
+                        assert_owner!(acceleration_structure, self);
+                    }
+                }
+            }
+        }
+
+        for copy in copies {
+            assert_owner!(copy.src, self);
+            assert_owner!(copy.dst, self);
+        }
+    }
+
+    #[tracing::instrument]
+    pub fn create_sampler(
+        &self,
+        info: SamplerInfo,
+    ) -> Result<Sampler, OutOfMemory> {
+        Ok(Sampler::new(info, self.downgrade()))
+    }
+
+    #[tracing::instrument]
+    pub fn create_query_pool(
+        &self,
+        info: QueryPoolInfo,
+    ) -> Result<QueryPool, OutOfMemory> {
+        Ok(QueryPool::new(info, self.downgrade()))
+    }
+
+    /// Writes zero timestamps, as commands take no time.
+    /// Results are always available.
+    #[tracing::instrument(skip(timestamps))]
+    pub fn get_timestamps(
+        &self,
+        pool: &QueryPool,
+        first: u32,
+        timestamps: &mut [u64],
+    ) -> Result<bool, WaitError> {
+        assert_owner!(pool, self);
+        assert!(
+            arith_le(first as usize + timestamps.len(), pool.info().count),
+            "Query range is out of pool bounds"
+        );
+
+        for timestamp in timestamps {
+            *timestamp = 0;
+        }
+
+        Ok(true)
+    }
+
+    /// Returns number of nanoseconds per timestamp tick.
+    pub fn timestamp_period(&self) -> f32 {
+        1.0
+    }
+
+    /// Returns operations supported for format by device.
+    pub fn format_properties(&self, format: Format) -> FormatProperties {
+        format_properties(format)
+    }
+
+    /// Returns limits of images with specified format and usage.
+    /// Only dimensionality of `extent` is considered.
+    /// Returns `Ok(None)` if combination is not supported.
+    pub fn image_format_properties(
+        &self,
+        format: Format,
+        extent: ImageExtent,
+        usage: ImageUsage,
+    ) -> Result<Option<ImageFormatProperties>, OutOfMemory> {
+        Ok(image_format_properties(format, extent, usage))
+    }
+
+    /// Returns image usage supported with optimal tiling for the format.
+    pub fn image_format_usage(&self, format: Format) -> ImageUsage {
+        image_format_usage(format)
+    }
+
+    /// Returns maximum size of image dimension for the extent kind.
+    pub fn max_image_dimension(&self, extent: ImageExtent) -> ImageSize {
+        max_image_dimension(extent)
+    }
+
+    /// Returns maximum number of image array layers.
+    pub fn max_image_layers(&self) -> u32 {
+        MAX_IMAGE_LAYERS
+    }
+
+    /// Creates shader binding table with regions laid out
+    /// as on typical device. Group handles are zeroed.
+    #[tracing::instrument]
+    pub fn create_shader_binding_table(
+        &self,
+        pipeline: &RayTracingPipeline,
+        info: ShaderBindingTableInfo,
+    ) -> Result<ShaderBindingTable, OutOfMemory> {
+        assert_owner!(pipeline, self);
+        assert!(
+            !pipeline.info().library,
+            "Shader binding table can't be created for pipeline library"
+        );
+
+        let group_count = pipeline.group_count();
+        for &group in info
+            .raygen
+            .iter()
+            .chain(info.miss)
+            .chain(info.hit)
+            .chain(info.callable)
+        {
+            assert!(
+                (group as usize) < group_count,
+                "Group index {} is out of bounds",
+                group
+            );
+        }
+
+        let group_stride = SHADER_GROUP_HANDLE_SIZE;
+
+        let mut total_size = 0u64;
+        let mut region = |count: usize| -> Result<Range<u64>, OutOfMemory> {
+            let start = align_up(SHADER_GROUP_BASE_ALIGN_MASK, total_size)
+                .ok_or(OutOfMemory)?;
+            let size = u64::try_from(count)
+                .ok()
+                .and_then(|count| group_stride.checked_mul(count))
+                .ok_or(OutOfMemory)?;
+            total_size = start.checked_add(size).ok_or(OutOfMemory)?;
+            Ok(start..total_size)
+        };
+
+        let raygen = region(info.raygen.is_some() as usize)?;
+        let miss = region(info.miss.len())?;
+        let hit = region(info.hit.len())?;
+        let callable = region(info.callable.len())?;
+
+        let buffer = self.create_buffer(BufferInfo {
+            align: SHADER_GROUP_BASE_ALIGN_MASK,
+            size: total_size.max(1),
+            usage: BufferUsage::SHADER_BINDING_TABLE
+                | BufferUsage::DEVICE_ADDRESS,
+            capture_address: None,
+        })?;
+
+        let region = |range: Range<u64>| {
+            if range.start == range.end {
+                None
+            } else {
+                Some(StridedBufferRegion {
+                    buffer: buffer.clone(),
+                    offset: range.start,
+                    size: range.end - range.start,
+                    stride: group_stride,
+                })
+            }
+        };
+
+        tracing::debug!("ShaderBindingTable created");
+        Ok(ShaderBindingTable {
+            raygen: region(raygen),
+            miss: region(miss),
+            hit: region(hit),
+            callable: region(callable),
+        })
+    }
+
+    #[tracing::instrument]
+    pub fn map_memory(
+        &self,
+        buffer: &mut MappableBuffer,
+        offset: u64,
+        size: usize,
+    ) -> Result<&mut [MaybeUninit<u8>], MapError> {
+        assert_owner!(buffer, self);
+
+        let range = host_range(buffer, offset, size);
+
+        if !buffer.map() {
+            return Err(MapError::AlreadyMapped);
+        }
+
+        let bytes = &mut buffer.memory()[range];
+
+        // `MaybeUninit<u8>` has the same layout as `u8`.
+        Ok(unsafe {
+            std::slice::from_raw_parts_mut(
+                bytes.as_mut_ptr() as *mut MaybeUninit<u8>,
+                bytes.len(),
+            )
+        })
+    }
+
+    pub fn unmap_memory(&self, buffer: &mut MappableBuffer) -> bool {
+        assert_owner!(buffer, self);
+        buffer.unmap()
+    }
+
+    #[tracing::instrument(skip(data))]
+    pub fn write_buffer<T>(
+        &self,
+        buffer: &mut MappableBuffer,
+        offset: u64,
+        data: &[T],
+    ) -> Result<(), MapError>
+    where
+        T: Pod,
+    {
+        assert_owner!(buffer, self);
+
+        if size_of_val(data) == 0 {
+            return Ok(());
+        }
+
+        if buffer.is_mapped() {
+            return Err(MapError::AlreadyMapped);
+        }
+
+        let range = host_range(buffer, offset, size_of_val(data));
+        buffer.memory()[range].copy_from_slice(bytemuck::cast_slice(data));
+        Ok(())
+    }
+
+    /// Reads buffer content written by the host.
+    /// Commands never write to buffers in this backend.
+    #[tracing::instrument(skip(data))]
+    pub fn read_buffer<T>(
+        &self,
+        buffer: &mut MappableBuffer,
+        offset: u64,
+        data: &mut [T],
+    ) -> Result<(), MapError>
+    where
+        T: Pod,
+    {
+        assert_owner!(buffer, self);
+
+        if size_of_val(data) == 0 {
+            return Ok(());
+        }
+
+        if buffer.is_mapped() {
+            return Err(MapError::AlreadyMapped);
+        }
+
+        let range = host_range(buffer, offset, size_of_val(data));
+        bytemuck::cast_slice_mut(data).copy_from_slice(&buffer.memory()[range]);
+        Ok(())
+    }
+}
+
+/// Returns range of buffer memory.
+///
+/// # Panics
+///
+/// Panics if range is out of buffer bounds.
+fn host_range(
+    buffer: &MappableBuffer,
+    offset: u64,
+    size: usize,
+) -> Range<usize> {
+    let end = u64::try_from(size)
+        .ok()
+        .and_then(|size| offset.checked_add(size))
+        .filter(|&end| end <= buffer.info().size);
+
+    match end {
+        Some(end) => offset as usize..end as usize,
+        None => panic!(
+            "Range {}..+{} is out of bounds of {:?}",
+            offset, size, buffer
+        ),
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CreateRenderPassError {
+    #[error(transparent)]
+    OutOfMemory {
+        #[from]
+        source: OutOfMemory,
+    },
+
+    #[error(
+        "Subpass {subpass} attachment index {attachment} for color attachment {index} is out of bounds"
+    )]
+    ColorAttachmentReferenceOutOfBound {
+        subpass: usize,
+        index: usize,
+        attachment: usize,
+    },
+
+    #[error(
+        "Subpass {subpass} attachment index {attachment} for depth attachment is out of bounds"
+    )]
+    DepthAttachmentReferenceOutOfBound { subpass: usize, attachment: usize },
+
+    #[error(
+        "Subpass {subpass} attachment index {attachment} for resolve attachment {index} is out of bounds"
+    )]
+    ResolveAttachmentReferenceOutOfBound {
+        subpass: usize,
+        index: usize,
+        attachment: usize,
+    },
+
+    #[error(
+        "Subpass {subpass} has {resolves} resolve attachments for {colors} color attachments"
+    )]
+    ResolveAttachmentCountMismatch {
+        subpass: usize,
+        colors: usize,
+        resolves: usize,
+    },
+
+    #[error(
+        "Subpass {subpass} color attachment {index} must be multisampled and resolved into single-sampled attachment"
+    )]
+    ResolveAttachmentSamplesMismatch { subpass: usize, index: usize },
+}
+
+/// Checks that resolve attachments of the subpass match its color attachments.
+fn check_subpass_resolves(
+    info: &RenderPassInfo,
+    subpass: usize,
+) -> Result<(), CreateRenderPassError> {
+    let s = &info.subpasses[subpass];
+
+    if s.resolves.is_empty() {
+        return Ok(());
+    }
+
+    if s.resolves.len() != s.colors.len() {
+        return Err(CreateRenderPassError::ResolveAttachmentCountMismatch {
+            subpass,
+            colors: s.colors.len(),
+            resolves: s.resolves.len(),
+        });
+    }
+
+    for (index, (&color, &resolve)) in
+        s.colors.iter().zip(&s.resolves).enumerate()
+    {
+        let resolve_info = info.attachments.get(resolve).ok_or(
+            CreateRenderPassError::ResolveAttachmentReferenceOutOfBound {
+                subpass,
+                index,
+                attachment: resolve,
+            },
+        )?;
+
+        // Color attachment indices are checked separately.
+        let multisampled = info
+            .attachments
+            .get(color)
+            .map_or(false, |color| color.samples != Samples::Samples1);
+
+        if !multisampled || resolve_info.samples != Samples::Samples1 {
+            return Err(
+                CreateRenderPassError::ResolveAttachmentSamplesMismatch {
+                    subpass,
+                    index,
+                },
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[allow(dead_code)]
+fn check() {
+    assert_object::<Device>();
+}
@@ -0,0 +1,94 @@
+use {
+    super::{device::WeakDevice, new_handle},
+    crate::{
+        encode::Command, queue::QueueId, record::RecordedCommand, OutOfMemory,
+    },
+    std::{
+        fmt::{self, Debug},
+        sync::Arc,
+    },
+};
+
+/// Command buffer that keeps captured commands.
+/// They are validated when command buffer is submitted.
+pub struct CommandBuffer {
+    handle: u64,
+    queue: QueueId,
+    owner: WeakDevice,
+    reusable: bool,
+    captured: Option<Arc<[RecordedCommand]>>,
+}
+
+impl Debug for CommandBuffer {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if fmt.alternate() {
+            fmt.debug_struct("CommandBuffer")
+                .field("handle", &self.handle)
+                .field("owner", &self.owner)
+                .field("queue", &self.queue)
+                .finish()
+        } else {
+            write!(fmt, "CommandBuffer({})", self.handle)
+        }
+    }
+}
+
+impl CommandBuffer {
+    pub(super) fn new(
+        queue: QueueId,
+        owner: WeakDevice,
+        reusable: bool,
+    ) -> Self {
+        CommandBuffer {
+            handle: new_handle(),
+            queue,
+            owner,
+            reusable,
+            captured: None,
+        }
+    }
+
+    pub(super) fn is_owned_by(
+        &self,
+        owner: &impl PartialEq<WeakDevice>,
+    ) -> bool {
+        *owner == self.owner
+    }
+
+    pub fn queue(&self) -> QueueId {
+        self.queue
+    }
+
+    /// Returns `true` if command buffer can be submitted multiple times.
+    /// Transient command buffers must be submitted only once.
+    pub fn is_reusable(&self) -> bool {
+        self.reusable
+    }
+
+    /// Commands are always captured, as there is nothing else to execute.
+    pub(crate) fn is_capturing(&self) -> bool {
+        true
+    }
+
+    /// Stores commands captured from encoder.
+    pub(crate) fn set_captured(&mut self, commands: Arc<[RecordedCommand]>) {
+        self.captured = Some(commands);
+    }
+
+    /// Returns commands captured from encoder.
+    /// Empty if encoder was not finished.
+    pub(super) fn captured(&self) -> Arc<[RecordedCommand]> {
+        match &self.captured {
+            Some(commands) => commands.clone(),
+            None => Arc::new([]),
+        }
+    }
+
+    /// Does nothing. Commands are already captured by `set_captured`.
+    pub fn write(
+        &mut self,
+        _commands: &[Command<'_>],
+    ) -> Result<(), OutOfMemory> {
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+use {
+    super::{physical::PhysicalDevice, surface::Surface},
+    crate::{
+        physical::EnumerateDeviceError,
+        surface::{CreateSurfaceError, SurfaceError, SurfaceInfo},
+    },
+    once_cell::sync::OnceCell,
+    raw_window_handle::HasRawWindowHandle,
+    std::{
+        fmt::{self, Debug},
+        sync::atomic::AtomicBool,
+    },
+};
+
+/// Root object of the null graphics system.
+pub struct NullGraphics {
+    _private: (),
+}
+
+pub type Graphics = NullGraphics;
+
+static GLOBAL_GRAPHICS: OnceCell<NullGraphics> = OnceCell::new();
+
+impl Debug for NullGraphics {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str("NullGraphics")
+    }
+}
+
+/// Null backend initialization never fails.
+#[derive(Debug)]
+pub enum InitError {}
+
+impl fmt::Display for InitError {
+    fn fmt(&self, _fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {}
+    }
+}
+
+impl std::error::Error for InitError {}
+
+impl NullGraphics {
+    pub fn get_or_init() -> Result<&'static NullGraphics, InitError> {
+        Ok(GLOBAL_GRAPHICS.get_or_init(|| {
+            tracing::debug!("Null graphics initialized");
+            NullGraphics { _private: () }
+        }))
+    }
+
+    pub fn name(&self) -> &str {
+        "Null"
+    }
+
+    /// Returns single device that supports all features.
+    pub fn devices(&self) -> Result<Vec<PhysicalDevice>, EnumerateDeviceError> {
+        Ok(vec![PhysicalDevice::new()])
+    }
+
+    /// Creates surface for any window.
+    /// Window is never drawn to.
+    pub fn create_surface(
+        &self,
+        window: &impl HasRawWindowHandle,
+    ) -> Result<Surface, CreateSurfaceError> {
+        Ok(Surface::make(
+            AtomicBool::new(false),
+            SurfaceInfo {
+                window: window.raw_window_handle(),
+            },
+        ))
+    }
+
+    /// Destroys surface.
+    ///
+    /// Fails with `SurfaceError::AlreadyUsed` if surface is used
+    /// by a swapchain or other clones of it exist.
+    pub fn destroy_surface(
+        &self,
+        surface: Surface,
+    ) -> Result<(), SurfaceError> {
+        if !surface.into_unique() {
+            return Err(SurfaceError::AlreadyUsed);
+        }
+
+        Ok(())
+    }
+}
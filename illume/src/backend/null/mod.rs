@@ -0,0 +1,45 @@
+//! Headless backend that talks to no graphics API.
+//!
+//! Objects are plain host values with unique handles.
+//! Memory of mappable buffers is allocated on the host,
+//! so it can be written and read back.
+//! Submitted commands are not executed. They are checked by
+//! `CommandValidator` instead and errors are collected by the `Queue`.
+//! Fences are signalled on submission.
+//!
+//! This backend allows renderer code to run in tests and on machines
+//! without GPU.
+
+macro_rules! assert_owner {
+    ($resource:expr, $owner:expr) => {{
+        assert!(
+            $resource.is_owned_by(&$owner),
+            "{:?} is not owned by {:?}",
+            $resource,
+            $owner
+        )
+    }};
+}
+
+mod device;
+mod encode;
+mod graphics;
+mod physical;
+mod queue;
+mod resources;
+mod surface;
+mod swapchain;
+
+pub use self::{
+    device::*, encode::*, graphics::*, physical::*, queue::*, resources::*,
+    surface::*, swapchain::*,
+};
+
+use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+
+static NEXT_HANDLE: AtomicU64 = AtomicU64::new(1);
+
+/// Returns handle unique among all objects of the backend.
+fn new_handle() -> u64 {
+    NEXT_HANDLE.fetch_add(1, Relaxed)
+}
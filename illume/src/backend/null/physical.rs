@@ -0,0 +1,319 @@
+use {
+    super::{device::Device, queue::Queue, surface::Surface},
+    crate::{
+        format::{
+            Format, FormatFeatures, FormatProperties, ImageFormatProperties,
+        },
+        image::{ImageExtent, ImageUsage, Samples},
+        physical::{
+            ApiCapability, ApiVersion, CapabilitySource, DeviceInfo,
+            DeviceKind, Feature,
+        },
+        queue::{
+            Family, FamilyInfo, QueueCapabilityFlags, QueueId, QueuesQuery,
+        },
+        surface::{PresentMode, SurfaceCapabilities, SurfaceError},
+        CreateDeviceError, Extent2d, Extent3d, ImageSize, OutOfMemory,
+    },
+};
+
+/// Features reported by the device.
+/// Null device supports all of them.
+const FEATURES: &[Feature] = &[
+    Feature::BufferDeviceAddress,
+    Feature::BufferDeviceAddressCaptureReplay,
+    Feature::ShaderSampledImageDynamicIndexing,
+    Feature::ShaderStorageImageDynamicIndexing,
+    Feature::ShaderUniformBufferDynamicIndexing,
+    Feature::ShaderStorageBufferDynamicIndexing,
+    Feature::ShaderSampledImageNonUniformIndexing,
+    Feature::ShaderStorageImageNonUniformIndexing,
+    Feature::ShaderUniformBufferNonUniformIndexing,
+    Feature::ShaderStorageBufferNonUniformIndexing,
+    Feature::DescriptorBindingSampledImageUpdateAfterBind,
+    Feature::DescriptorBindingStorageImageUpdateAfterBind,
+    Feature::DescriptorBindingStorageBufferUpdateAfterBind,
+    Feature::DescriptorBindingStorageTexelBufferUpdateAfterBind,
+    Feature::DescriptorBindingUniformBufferUpdateAfterBind,
+    Feature::DescriptorBindingUniformTexelBufferUpdateAfterBind,
+    Feature::DescriptorBindingUpdateUnusedWhilePending,
+    Feature::DescriptorBindingPartiallyBound,
+    Feature::AccelerationStructure,
+    Feature::RayTracingPipeline,
+    Feature::RayTracingPipelineLibrary,
+    Feature::RuntimeDescriptorArray,
+    Feature::ScalarBlockLayout,
+    Feature::SurfacePresentation,
+    Feature::ExtendedDynamicState,
+    Feature::MultiViewport,
+    Feature::Synchronization2,
+];
+
+/// Number of queues in the only family.
+const FAMILY_QUEUES: usize = 4;
+
+pub(super) const MAX_IMAGE_DIMENSION: ImageSize = 16384;
+pub(super) const MAX_IMAGE_DIMENSION_3D: ImageSize = 2048;
+pub(super) const MAX_IMAGE_LAYERS: u32 = 2048;
+const MAX_IMAGE_SIZE: u64 = 1 << 31;
+
+/// Extent of all surfaces.
+pub(super) const SURFACE_EXTENT: Extent2d = Extent2d {
+    width: 1280,
+    height: 720,
+};
+
+pub(super) const SURFACE_FORMATS: &[Format] =
+    &[Format::BGRA8Srgb, Format::BGRA8Unorm, Format::RGBA8Srgb];
+
+pub(super) const SURFACE_PRESENT_MODES: &[PresentMode] = &[
+    PresentMode::Fifo,
+    PresentMode::Mailbox,
+    PresentMode::Immediate,
+];
+
+pub(super) const SURFACE_IMAGES: u32 = 3;
+
+#[derive(Debug)]
+pub struct PhysicalDevice {
+    _private: (),
+}
+
+impl PhysicalDevice {
+    pub(super) fn new() -> Self {
+        PhysicalDevice { _private: () }
+    }
+
+    pub fn info(&self) -> DeviceInfo {
+        DeviceInfo {
+            name: "Null Device".to_owned(),
+            kind: Some(DeviceKind::Software),
+            api_version: ApiVersion {
+                major: 1,
+                minor: 2,
+                patch: 0,
+            },
+            features: FEATURES.to_vec(),
+            families: vec![FamilyInfo {
+                capabilities: QueueCapabilityFlags::all(),
+                count: FAMILY_QUEUES,
+            }],
+            capabilities: vec![
+                (ApiCapability::BufferDeviceAddress, CapabilitySource::Core),
+                (ApiCapability::DescriptorIndexing, CapabilitySource::Core),
+                (ApiCapability::ScalarBlockLayout, CapabilitySource::Core),
+                (ApiCapability::TimelineSemaphore, CapabilitySource::Core),
+            ],
+        }
+    }
+
+    /// Returns operations supported for format by device.
+    pub fn format_properties(&self, format: Format) -> FormatProperties {
+        format_properties(format)
+    }
+
+    /// Returns limits of images with specified format and usage.
+    /// Only dimensionality of `extent` is considered.
+    /// Returns `Ok(None)` if combination is not supported.
+    pub fn image_format_properties(
+        &self,
+        format: Format,
+        extent: ImageExtent,
+        usage: ImageUsage,
+    ) -> Result<Option<ImageFormatProperties>, OutOfMemory> {
+        Ok(image_format_properties(format, extent, usage))
+    }
+
+    pub fn surface_capabilities(
+        &self,
+        _surface: &Surface,
+    ) -> Result<Option<SurfaceCapabilities>, SurfaceError> {
+        Ok(Some(SurfaceCapabilities {
+            families: vec![0],
+            image_count: SURFACE_IMAGES..=SURFACE_IMAGES,
+            current_extent: SURFACE_EXTENT,
+            image_extent: SURFACE_EXTENT..=SURFACE_EXTENT,
+            supported_usage: surface_usage(),
+            present_modes: SURFACE_PRESENT_MODES.to_vec(),
+            formats: SURFACE_FORMATS.to_vec(),
+        }))
+    }
+
+    /// Creates device with requested queues.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `features` contains duplicates.
+    #[tracing::instrument(skip(queues))]
+    pub fn create_device<Q>(
+        self,
+        features: &[Feature],
+        queues: Q,
+    ) -> Result<(Device, Q::Queues), CreateDeviceError<Q::Error>>
+    where
+        Q: QueuesQuery,
+    {
+        let info = self.info();
+
+        let (query, collector) =
+            queues.query(&info.families).map_err(|source| {
+                CreateDeviceError::CannotFindRequeredQueues { source }
+            })?;
+
+        let query = query.as_ref();
+
+        for &(family, count) in query {
+            match info.families.get(family) {
+                Some(family) if family.count >= count => {}
+                _ => return Err(CreateDeviceError::BadFamiliesRequested),
+            }
+        }
+
+        for (index, feature) in features.iter().enumerate() {
+            assert!(
+                !features[..index].contains(feature),
+                "Feature {:?} is requested twice",
+                feature
+            );
+        }
+
+        let device = Device::new(features.to_vec());
+
+        let families = query
+            .iter()
+            .map(|&(family, count)| {
+                let capabilities = info.families[family].capabilities;
+
+                Family {
+                    capabilities,
+                    queues: (0..count)
+                        .map(|index| {
+                            Queue::new(
+                                device.clone(),
+                                QueueId { family, index },
+                                capabilities,
+                            )
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+
+        tracing::debug!("Device created");
+
+        Ok((device, Q::collect(collector, families)))
+    }
+}
+
+pub(super) fn format_properties(format: Format) -> FormatProperties {
+    if format.is_color() {
+        FormatProperties {
+            linear_tiling: FormatFeatures::all()
+                - FormatFeatures::DEPTH_STENCIL_ATTACHMENT,
+            optimal_tiling: FormatFeatures::all()
+                - FormatFeatures::DEPTH_STENCIL_ATTACHMENT,
+            buffer: FormatFeatures::VERTEX_BUFFER
+                | FormatFeatures::UNIFORM_TEXEL_BUFFER
+                | FormatFeatures::STORAGE_TEXEL_BUFFER,
+        }
+    } else {
+        FormatProperties {
+            linear_tiling: FormatFeatures::empty(),
+            optimal_tiling: FormatFeatures::SAMPLED_IMAGE
+                | FormatFeatures::DEPTH_STENCIL_ATTACHMENT
+                | FormatFeatures::BLIT_SRC
+                | FormatFeatures::TRANSFER_SRC
+                | FormatFeatures::TRANSFER_DST,
+            buffer: FormatFeatures::empty(),
+        }
+    }
+}
+
+pub(super) fn image_format_usage(format: Format) -> ImageUsage {
+    let features = format_properties(format).optimal_tiling;
+    let mut usage = ImageUsage::TRANSIENT;
+
+    if features.contains(FormatFeatures::TRANSFER_SRC) {
+        usage |= ImageUsage::TRANSFER_SRC;
+    }
+    if features.contains(FormatFeatures::TRANSFER_DST) {
+        usage |= ImageUsage::TRANSFER_DST;
+    }
+    if features.contains(FormatFeatures::SAMPLED_IMAGE) {
+        usage |= ImageUsage::SAMPLED;
+    }
+    if features.contains(FormatFeatures::STORAGE_IMAGE) {
+        usage |= ImageUsage::STORAGE;
+    }
+    if features.contains(FormatFeatures::COLOR_ATTACHMENT) {
+        usage |= ImageUsage::COLOR_ATTACHMENT;
+    }
+    if features.contains(FormatFeatures::DEPTH_STENCIL_ATTACHMENT) {
+        usage |= ImageUsage::DEPTH_STENCIL_ATTACHMENT;
+    }
+    if usage.is_render_target() {
+        usage |=
+            ImageUsage::TRANSIENT_ATTACHMENT | ImageUsage::INPUT_ATTACHMENT;
+    }
+
+    usage
+}
+
+pub(super) fn max_image_dimension(extent: ImageExtent) -> ImageSize {
+    match extent {
+        ImageExtent::D1 { .. } | ImageExtent::D2 { .. } => MAX_IMAGE_DIMENSION,
+        ImageExtent::D3 { .. } => MAX_IMAGE_DIMENSION_3D,
+    }
+}
+
+pub(super) fn image_format_properties(
+    format: Format,
+    extent: ImageExtent,
+    usage: ImageUsage,
+) -> Option<ImageFormatProperties> {
+    if !image_format_usage(format).contains(usage) {
+        return None;
+    }
+
+    let max = max_image_dimension(extent);
+
+    let max_extent = match extent {
+        ImageExtent::D1 { .. } => Extent3d {
+            width: max,
+            height: 1,
+            depth: 1,
+        },
+        ImageExtent::D2 { .. } => Extent3d {
+            width: max,
+            height: max,
+            depth: 1,
+        },
+        ImageExtent::D3 { .. } => Extent3d {
+            width: max,
+            height: max,
+            depth: max,
+        },
+    };
+
+    Some(ImageFormatProperties {
+        max_extent,
+        max_levels: 32 - max.leading_zeros(),
+        max_layers: match extent {
+            ImageExtent::D3 { .. } => 1,
+            _ => MAX_IMAGE_LAYERS,
+        },
+        max_samples: match extent {
+            ImageExtent::D2 { .. } => Samples::Samples8,
+            _ => Samples::Samples1,
+        },
+        max_size: MAX_IMAGE_SIZE,
+    })
+}
+
+pub(super) fn surface_usage() -> ImageUsage {
+    ImageUsage::COLOR_ATTACHMENT
+        | ImageUsage::TRANSFER_SRC
+        | ImageUsage::TRANSFER_DST
+        | ImageUsage::SAMPLED
+        | ImageUsage::STORAGE
+}
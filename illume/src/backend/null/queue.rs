@@ -0,0 +1,231 @@
+use {
+    super::{device::Device, swapchain::SwapchainImage},
+    crate::{
+        encode::{CommandBuffer, Encoder},
+        fence::{Fence, WaitError},
+        queue::*,
+        record::{CommandRecorder, RecordedSubmission},
+        semaphore::Semaphore,
+        stage::PipelineStageFlags,
+        validate::{CommandValidator, ValidationError},
+        OutOfMemory,
+    },
+    std::fmt::{self, Debug},
+};
+
+/// Queue that validates submitted command buffers instead of executing them.
+pub struct Queue {
+    device: Device,
+    id: QueueId,
+    capabilities: QueueCapabilityFlags,
+    recorder: Option<CommandRecorder>,
+    validator: CommandValidator,
+
+    /// Index of the current frame.
+    frame: u64,
+
+    /// Number of submissions made in the current frame.
+    submissions: usize,
+
+    /// Errors found in submitted command buffers.
+    errors: Vec<ValidationError>,
+}
+
+impl Debug for Queue {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if fmt.alternate() {
+            fmt.debug_struct("Queue")
+                .field("id", &self.id)
+                .field("capabilities", &self.capabilities)
+                .field("device", &self.device)
+                .field("frame", &self.frame)
+                .finish()
+        } else {
+            write!(fmt, "Queue({}, {})", self.id.family, self.id.index)
+        }
+    }
+}
+
+impl Queue {
+    pub(super) fn new(
+        device: Device,
+        id: QueueId,
+        capabilities: QueueCapabilityFlags,
+    ) -> Self {
+        Queue {
+            validator: CommandValidator::new().with_device(&device),
+            device,
+            id,
+            capabilities,
+            recorder: None,
+            frame: 0,
+            submissions: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn id(&self) -> QueueId {
+        self.id
+    }
+
+    /// Installs recorder that captures submitted command buffers.
+    pub fn set_recorder(&mut self, recorder: Option<CommandRecorder>) {
+        self.recorder = recorder;
+    }
+
+    pub fn recorder(&self) -> Option<&CommandRecorder> {
+        self.recorder.as_ref()
+    }
+
+    /// Returns errors found in command buffers submitted so far
+    /// and clears them.
+    pub fn take_validation_errors(&mut self) -> Vec<ValidationError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// Creates encoder for transient command buffer.
+    #[tracing::instrument]
+    pub fn create_encoder(&mut self) -> Result<Encoder<'static>, OutOfMemory> {
+        let cbuf = CommandBuffer::new(self.id, self.device.downgrade(), false);
+        Ok(Encoder::new(cbuf, self.capabilities))
+    }
+
+    /// Creates encoder for command buffer that can be submitted
+    /// multiple times with `submit_reusable`.
+    #[tracing::instrument]
+    pub fn create_reusable_encoder(
+        &mut self,
+    ) -> Result<Encoder<'static>, OutOfMemory> {
+        let cbuf = CommandBuffer::new(self.id, self.device.downgrade(), true);
+        Ok(Encoder::new(cbuf, self.capabilities))
+    }
+
+    #[tracing::instrument]
+    pub fn free_reusable(
+        &mut self,
+        cbuf: CommandBuffer,
+    ) -> Result<(), OutOfMemory> {
+        assert_owner!(cbuf, self.device);
+        assert!(cbuf.is_reusable(), "Transient command buffers are recycled");
+        Ok(())
+    }
+
+    /// Finishes current frame and starts next one.
+    #[tracing::instrument]
+    pub fn begin_frame(&mut self) -> Result<(), WaitError> {
+        if let Some(recorder) = &self.recorder {
+            recorder.end_frame();
+        }
+
+        self.frame += 1;
+        self.submissions = 0;
+        Ok(())
+    }
+
+    /// Submits transient command buffer.
+    #[tracing::instrument]
+    pub fn submit(
+        &mut self,
+        wait: &[(PipelineStageFlags, Semaphore)],
+        cbuf: CommandBuffer,
+        signal: &[Semaphore],
+        fence: Option<&Fence>,
+    ) -> Result<(), WaitError> {
+        assert!(
+            !cbuf.is_reusable(),
+            "Reusable command buffers are submitted with `submit_reusable`"
+        );
+
+        self.submit_impl(wait, &cbuf, signal, fence)
+    }
+
+    /// Submits reusable command buffer.
+    #[tracing::instrument]
+    pub fn submit_reusable(
+        &mut self,
+        wait: &[(PipelineStageFlags, Semaphore)],
+        cbuf: &CommandBuffer,
+        signal: &[Semaphore],
+        fence: Option<&Fence>,
+    ) -> Result<(), WaitError> {
+        assert!(cbuf.is_reusable(), "Command buffer is not reusable");
+        self.submit_impl(wait, cbuf, signal, fence)
+    }
+
+    fn submit_impl(
+        &mut self,
+        wait: &[(PipelineStageFlags, Semaphore)],
+        cbuf: &CommandBuffer,
+        signal: &[Semaphore],
+        fence: Option<&Fence>,
+    ) -> Result<(), WaitError> {
+        assert_owner!(cbuf, self.device);
+        assert_eq!(self.id, cbuf.queue());
+
+        for (_, semaphore) in wait {
+            assert_owner!(semaphore, self.device);
+        }
+
+        for semaphore in signal {
+            assert_owner!(semaphore, self.device);
+        }
+
+        if let Some(fence) = fence {
+            assert_owner!(fence, self.device);
+        }
+
+        let commands = cbuf.captured();
+
+        if let Some(recorder) = &self.recorder {
+            recorder.record(commands.clone());
+        }
+
+        let errors = self.validator.validate_submissions(
+            self.frame,
+            self.submissions,
+            &[RecordedSubmission { commands }],
+        );
+
+        for error in &errors {
+            tracing::error!("Invalid command stream. {}", error);
+        }
+
+        self.errors.extend(errors);
+        self.submissions += 1;
+
+        // Work is complete as soon as it is submitted.
+        if let Some(fence) = fence {
+            fence.signal();
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument]
+    pub fn submit_no_semaphores(
+        &mut self,
+        buffer: CommandBuffer,
+        fence: Option<&Fence>,
+    ) -> Result<(), WaitError> {
+        self.submit(&[], buffer, &[], fence)
+    }
+
+    #[tracing::instrument]
+    pub fn present(
+        &mut self,
+        image: SwapchainImage,
+    ) -> Result<PresentOk, PresentError> {
+        assert_owner!(image, self.device);
+        Ok(PresentOk::Success)
+    }
+
+    #[tracing::instrument]
+    pub fn finish_frames(&mut self) -> Result<(), WaitError> {
+        Ok(())
+    }
+
+    #[tracing::instrument]
+    pub fn wait_for_idle(&self) -> Result<(), WaitError> {
+        Ok(())
+    }
+}
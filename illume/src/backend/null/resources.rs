@@ -0,0 +1,462 @@
+use {
+    super::{device::WeakDevice, new_handle},
+    crate::{
+        accel::AccelerationStructureInfo,
+        buffer::BufferInfo,
+        descriptor::{DescriptorSetInfo, DescriptorSetLayoutInfo},
+        framebuffer::FramebufferInfo,
+        image::ImageInfo,
+        memory::MemoryUsage,
+        pipeline::{
+            ComputePipelineInfo, GraphicsPipelineInfo, PipelineLayoutInfo,
+            RayTracingPipelineInfo,
+        },
+        query::QueryPoolInfo,
+        render_pass::RenderPassInfo,
+        sampler::SamplerInfo,
+        shader::ShaderModuleInfo,
+        view::ImageViewInfo,
+        DeviceAddress,
+    },
+    std::{
+        cell::UnsafeCell,
+        fmt::{self, Debug},
+        hash::{Hash, Hasher},
+        ops::Deref,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+    },
+};
+
+/// Defines resource type that shares info, owner and unique handle
+/// between clones.
+macro_rules! define_handle {
+    (
+        pub struct $resource:ident : $inner:ident {
+            info: $info:ty,
+            $($fname:ident: $fty:ty,)*
+        }
+    ) => {
+        struct $inner {
+            info: $info,
+            owner: WeakDevice,
+            handle: u64,
+            $($fname: $fty,)*
+        }
+
+        #[derive(Clone)]
+        pub struct $resource {
+            inner: Arc<$inner>,
+        }
+
+        impl Debug for $resource {
+            fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+                if fmt.alternate() {
+                    fmt.debug_struct(stringify!($resource))
+                        .field("info", &self.inner.info)
+                        .field("owner", &self.inner.owner)
+                        .field("handle", &self.inner.handle)
+                        $(.field(stringify!($fname), &self.inner.$fname))*
+                        .finish()
+                } else {
+                    write!(
+                        fmt,
+                        concat!(stringify!($resource), "({})"),
+                        self.inner.handle
+                    )
+                }
+            }
+        }
+
+        impl PartialEq for $resource {
+            fn eq(&self, rhs: &Self) -> bool {
+                self.inner.handle == rhs.inner.handle
+            }
+        }
+
+        impl Eq for $resource {}
+
+        impl Hash for $resource {
+            fn hash<H>(&self, hasher: &mut H)
+            where
+                H: Hasher,
+            {
+                self.inner.handle.hash(hasher)
+            }
+        }
+
+        impl $resource {
+            pub fn info(&self) -> &$info {
+                &self.inner.info
+            }
+
+            pub fn is_owned_by(
+                &self,
+                owner: &impl PartialEq<WeakDevice>,
+            ) -> bool {
+                *owner == self.inner.owner
+            }
+
+            pub(super) fn new(
+                info: $info,
+                owner: WeakDevice,
+                $($fname: $fty,)*
+            ) -> Self {
+                $resource {
+                    inner: Arc::new($inner {
+                        info,
+                        owner,
+                        handle: new_handle(),
+                        $($fname,)*
+                    }),
+                }
+            }
+        }
+    };
+}
+
+define_handle! {
+    pub struct Buffer: BufferInner {
+        info: BufferInfo,
+        address: Option<DeviceAddress>,
+        memory: Option<UnsafeCell<Box<[u8]>>>,
+    }
+}
+
+impl Drop for BufferInner {
+    fn drop(&mut self) {
+        if let Some(device) = self.owner.upgrade() {
+            device.free_memory(self.info.size);
+        }
+    }
+}
+
+// Memory is accessed only through `MappableBuffer` which is not `Clone`.
+unsafe impl Send for Buffer {}
+unsafe impl Sync for Buffer {}
+
+impl Buffer {
+    pub fn address(&self) -> Option<DeviceAddress> {
+        self.inner.address
+    }
+}
+
+pub struct MappableBuffer {
+    buffer: Buffer,
+    memory_usage: MemoryUsage,
+    mapped: bool,
+}
+
+impl From<MappableBuffer> for Buffer {
+    fn from(buffer: MappableBuffer) -> Self {
+        buffer.buffer
+    }
+}
+
+impl PartialEq for MappableBuffer {
+    fn eq(&self, rhs: &Self) -> bool {
+        std::ptr::eq(self, rhs)
+    }
+}
+
+impl Eq for MappableBuffer {}
+
+impl Hash for MappableBuffer {
+    fn hash<H>(&self, hasher: &mut H)
+    where
+        H: Hasher,
+    {
+        self.buffer.inner.handle.hash(hasher)
+    }
+}
+
+impl Debug for MappableBuffer {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if fmt.alternate() {
+            fmt.debug_struct("MappableBuffer")
+                .field("buffer", &self.buffer)
+                .field("memory_usage", &self.memory_usage)
+                .field("mapped", &self.mapped)
+                .finish()
+        } else {
+            write!(fmt, "MappableBuffer({})", self.buffer.inner.handle)
+        }
+    }
+}
+
+impl Deref for MappableBuffer {
+    type Target = Buffer;
+
+    fn deref(&self) -> &Buffer {
+        &self.buffer
+    }
+}
+
+impl MappableBuffer {
+    pub fn share(&self) -> Buffer {
+        self.buffer.clone()
+    }
+
+    pub(super) fn from_buffer(
+        buffer: Buffer,
+        memory_usage: MemoryUsage,
+    ) -> Self {
+        debug_assert!(buffer.inner.memory.is_some());
+
+        MappableBuffer {
+            buffer,
+            memory_usage,
+            mapped: false,
+        }
+    }
+
+    /// Returns host memory of the buffer.
+    pub(super) fn memory(&mut self) -> &mut [u8] {
+        let memory = self
+            .buffer
+            .inner
+            .memory
+            .as_ref()
+            .expect("Mappable buffers have host memory");
+
+        // Exclusive access to `MappableBuffer`
+        // grants exclusive access to its memory.
+        unsafe { &mut **memory.get() }
+    }
+
+    /// Marks buffer mapped.
+    /// Returns `false` if it is already mapped.
+    pub(super) fn map(&mut self) -> bool {
+        !std::mem::replace(&mut self.mapped, true)
+    }
+
+    /// Marks buffer unmapped.
+    /// Returns `false` if it was not mapped.
+    pub(super) fn unmap(&mut self) -> bool {
+        std::mem::replace(&mut self.mapped, false)
+    }
+
+    pub(super) fn is_mapped(&self) -> bool {
+        self.mapped
+    }
+}
+
+define_handle! {
+    pub struct Image: ImageInner {
+        info: ImageInfo,
+    }
+}
+
+define_handle! {
+    pub struct ImageView: ImageViewInner {
+        info: ImageViewInfo,
+    }
+}
+
+/// Fence that is signalled when submission is made.
+#[derive(Clone)]
+pub struct Fence {
+    handle: u64,
+    owner: WeakDevice,
+    signalled: Arc<AtomicBool>,
+}
+
+impl Debug for Fence {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if fmt.alternate() {
+            fmt.debug_struct("Fence")
+                .field("handle", &self.handle)
+                .field("owner", &self.owner)
+                .field("signalled", &self.signalled)
+                .finish()
+        } else {
+            write!(fmt, "Fence({})", self.handle)
+        }
+    }
+}
+
+impl PartialEq for Fence {
+    fn eq(&self, rhs: &Self) -> bool {
+        self.handle == rhs.handle
+    }
+}
+
+impl Eq for Fence {}
+
+impl Hash for Fence {
+    fn hash<H>(&self, hasher: &mut H)
+    where
+        H: Hasher,
+    {
+        self.handle.hash(hasher)
+    }
+}
+
+impl Fence {
+    pub(super) fn new(owner: WeakDevice) -> Self {
+        Fence {
+            handle: new_handle(),
+            owner,
+            signalled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub(super) fn is_owned_by(
+        &self,
+        owner: &impl PartialEq<WeakDevice>,
+    ) -> bool {
+        *owner == self.owner
+    }
+
+    pub(super) fn is_signalled(&self) -> bool {
+        self.signalled.load(Ordering::Acquire)
+    }
+
+    pub(super) fn signal(&self) {
+        self.signalled.store(true, Ordering::Release);
+    }
+
+    pub(super) fn reset(&self) {
+        self.signalled.store(false, Ordering::Release);
+    }
+}
+
+#[derive(Clone)]
+pub struct Semaphore {
+    handle: u64,
+    owner: WeakDevice,
+}
+
+impl Debug for Semaphore {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if fmt.alternate() {
+            fmt.debug_struct("Semaphore")
+                .field("handle", &self.handle)
+                .field("owner", &self.owner)
+                .finish()
+        } else {
+            write!(fmt, "Semaphore({})", self.handle)
+        }
+    }
+}
+
+impl PartialEq for Semaphore {
+    fn eq(&self, rhs: &Self) -> bool {
+        self.handle == rhs.handle
+    }
+}
+
+impl Eq for Semaphore {}
+
+impl Hash for Semaphore {
+    fn hash<H>(&self, hasher: &mut H)
+    where
+        H: Hasher,
+    {
+        self.handle.hash(hasher)
+    }
+}
+
+impl Semaphore {
+    pub(super) fn new(owner: WeakDevice) -> Self {
+        Semaphore {
+            handle: new_handle(),
+            owner,
+        }
+    }
+
+    pub(super) fn is_owned_by(
+        &self,
+        owner: &impl PartialEq<WeakDevice>,
+    ) -> bool {
+        *owner == self.owner
+    }
+}
+
+define_handle! {
+    pub struct RenderPass: RenderPassInner {
+        info: RenderPassInfo,
+    }
+}
+
+define_handle! {
+    pub struct Sampler: SamplerInner {
+        info: SamplerInfo,
+    }
+}
+
+define_handle! {
+    pub struct QueryPool: QueryPoolInner {
+        info: QueryPoolInfo,
+    }
+}
+
+define_handle! {
+    pub struct Framebuffer: FramebufferInner {
+        info: FramebufferInfo,
+    }
+}
+
+define_handle! {
+    pub struct ShaderModule: ShaderModuleInner {
+        info: ShaderModuleInfo,
+    }
+}
+
+define_handle! {
+    pub struct DescriptorSetLayout: DescriptorSetLayoutInner {
+        info: DescriptorSetLayoutInfo,
+    }
+}
+
+define_handle! {
+    pub struct DescriptorSet: DescriptorSetInner {
+        info: DescriptorSetInfo,
+    }
+}
+
+define_handle! {
+    pub struct PipelineLayout: PipelineLayoutInner {
+        info: PipelineLayoutInfo,
+    }
+}
+
+define_handle! {
+    pub struct ComputePipeline: ComputePipelineInner {
+        info: ComputePipelineInfo,
+    }
+}
+
+define_handle! {
+    pub struct GraphicsPipeline: GraphicsPipelineInner {
+        info: GraphicsPipelineInfo,
+    }
+}
+
+define_handle! {
+    pub struct AccelerationStructure: AccelerationStructureInner {
+        info: AccelerationStructureInfo,
+        address: DeviceAddress,
+    }
+}
+
+impl AccelerationStructure {
+    pub fn address(&self) -> DeviceAddress {
+        self.inner.address
+    }
+}
+
+define_handle! {
+    pub struct RayTracingPipeline: RayTracingPipelineInner {
+        info: RayTracingPipelineInfo,
+        group_count: usize,
+    }
+}
+
+impl RayTracingPipeline {
+    pub fn group_count(&self) -> usize {
+        self.inner.group_count
+    }
+}
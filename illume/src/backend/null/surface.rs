@@ -0,0 +1,62 @@
+use {
+    crate::surface::{SurfaceError, SurfaceInfo},
+    std::{
+        fmt::Debug,
+        sync::atomic::{AtomicBool, Ordering},
+    },
+};
+
+#[derive(Debug)]
+pub(crate) struct Inner {
+    pub used: AtomicBool,
+    pub info: SurfaceInfo,
+}
+
+#[derive(Clone, Debug)]
+#[repr(transparent)]
+pub struct Surface {
+    inner: std::sync::Arc<Inner>,
+}
+
+impl std::cmp::PartialEq for Surface {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(&*self.inner, &*other.inner)
+    }
+}
+
+impl std::cmp::Eq for Surface {}
+
+impl std::hash::Hash for Surface {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        std::ptr::hash(&*self.inner, state)
+    }
+}
+
+impl Surface {
+    pub(crate) fn make(used: AtomicBool, info: SurfaceInfo) -> Self {
+        Surface {
+            inner: std::sync::Arc::new(Inner { used, info }),
+        }
+    }
+
+    pub(crate) fn mark_used(&self) -> Result<(), SurfaceError> {
+        if self.inner.used.fetch_or(true, Ordering::SeqCst) {
+            Err(SurfaceError::AlreadyUsed)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub(crate) fn mark_unused(&self) {
+        self.inner.used.store(false, Ordering::SeqCst);
+    }
+
+    /// Consumes surface and returns `true` if no other clone exists.
+    pub(crate) fn into_unique(self) -> bool {
+        std::sync::Arc::try_unwrap(self.inner).is_ok()
+    }
+
+    pub fn info(&self) -> &SurfaceInfo {
+        &self.inner.info
+    }
+}
@@ -0,0 +1,202 @@
+use {
+    super::{
+        device::{Device, WeakDevice},
+        physical::{
+            surface_usage, SURFACE_EXTENT, SURFACE_FORMATS, SURFACE_IMAGES,
+            SURFACE_PRESENT_MODES,
+        },
+        surface::Surface,
+    },
+    crate::{
+        format::Format,
+        image::{Image, ImageInfo, ImageUsage, Samples},
+        surface::{PresentMode, SurfaceError},
+        swapchain::SwapchainImageInfo,
+    },
+    std::sync::{
+        atomic::{AtomicUsize, Ordering::*},
+        Arc,
+    },
+};
+
+#[derive(Debug)]
+pub struct SwapchainImage {
+    info: SwapchainImageInfo,
+    owner: WeakDevice,
+    counter: Arc<AtomicUsize>,
+    index: u32,
+}
+
+impl SwapchainImage {
+    pub fn info(&self) -> &SwapchainImageInfo {
+        &self.info
+    }
+
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub(super) fn is_owned_by(
+        &self,
+        owner: &impl PartialEq<WeakDevice>,
+    ) -> bool {
+        *owner == self.owner
+    }
+}
+
+impl Drop for SwapchainImage {
+    fn drop(&mut self) {
+        self.counter.fetch_sub(1, Release);
+    }
+}
+
+#[derive(Debug)]
+struct SwapchainInner {
+    images: Vec<Image>,
+    next: usize,
+    counter: Arc<AtomicUsize>,
+}
+
+/// Swapchain that cycles through images of fixed extent.
+/// Images are never shown.
+#[derive(Debug)]
+pub struct Swapchain {
+    inner: Option<SwapchainInner>,
+    device: WeakDevice,
+    surface: Surface,
+}
+
+impl Swapchain {
+    pub(crate) fn new(
+        surface: &Surface,
+        device: &Device,
+    ) -> Result<Self, SurfaceError> {
+        surface.mark_used()?;
+
+        tracing::debug!("Swapchain created");
+        Ok(Swapchain {
+            inner: None,
+            device: device.downgrade(),
+            surface: surface.clone(),
+        })
+    }
+
+    #[tracing::instrument]
+    pub fn configure(
+        &mut self,
+        usage: ImageUsage,
+        format: Format,
+        mode: PresentMode,
+    ) -> Result<(), SurfaceError> {
+        let device = self
+            .device
+            .upgrade()
+            .ok_or_else(|| SurfaceError::SurfaceLost)?;
+
+        if !surface_usage().contains(usage) {
+            return Err(SurfaceError::UsageNotSupported { usage });
+        }
+
+        if !SURFACE_FORMATS.contains(&format) {
+            return Err(SurfaceError::FormatUnsupported { format });
+        }
+
+        if !SURFACE_PRESENT_MODES.contains(&mode) {
+            return Err(SurfaceError::PresentModeUnsupported { mode });
+        }
+
+        if let Some(inner) = &self.inner {
+            assert_eq!(
+                inner.counter.load(Acquire),
+                0,
+                "Swapchain images must be released before reconfiguration"
+            );
+        }
+
+        let images = (0..SURFACE_IMAGES)
+            .map(|_| {
+                device.create_image(ImageInfo {
+                    extent: SURFACE_EXTENT.into(),
+                    format,
+                    levels: 1,
+                    layers: 1,
+                    samples: Samples::Samples1,
+                    usage,
+                })
+            })
+            .collect::<Result<_, _>>()
+            .expect("Surface images are supported");
+
+        self.inner = Some(SwapchainInner {
+            images,
+            next: 0,
+            counter: Arc::new(AtomicUsize::new(0)),
+        });
+
+        tracing::debug!("Swapchain configured");
+        Ok(())
+    }
+
+    pub fn supported_usage(&self) -> Result<ImageUsage, SurfaceError> {
+        Ok(surface_usage())
+    }
+
+    /// Returns `true` if swapchain images can be acquired.
+    /// Returns `false` before first `configure` call.
+    pub fn is_configured(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    /// Destroys swapchain and returns its surface.
+    ///
+    /// # Panics
+    ///
+    /// Panics if swapchain images are still acquired.
+    pub fn destroy(self) -> Surface {
+        if let Some(inner) = &self.inner {
+            assert_eq!(
+                inner.counter.load(Acquire),
+                0,
+                "Swapchain images must be released before destruction"
+            );
+        }
+
+        tracing::debug!("Swapchain destroyed");
+        self.surface.mark_unused();
+        self.surface
+    }
+
+    pub fn acquire_image(
+        &mut self,
+    ) -> Result<Option<SwapchainImage>, SurfaceError> {
+        let device = self
+            .device
+            .upgrade()
+            .ok_or_else(|| SurfaceError::SurfaceLost)?;
+
+        let inner = match &mut self.inner {
+            Some(inner) => inner,
+            None => return Ok(None),
+        };
+
+        if inner.counter.load(Acquire) >= inner.images.len() {
+            tracing::error!("Acquire would block");
+            return Ok(None);
+        }
+
+        let index = inner.next;
+        inner.next = (index + 1) % inner.images.len();
+        inner.counter.fetch_add(1, Acquire);
+
+        Ok(Some(SwapchainImage {
+            info: SwapchainImageInfo {
+                image: inner.images[index].clone(),
+                wait: device.create_semaphore()?,
+                signal: device.create_semaphore()?,
+            },
+            owner: self.device.clone(),
+            counter: inner.counter.clone(),
+            index: index as u32,
+        }))
+    }
+}
@@ -1,85 +1,106 @@
-use erupt::vk1_0::{AccessFlags, Flags, PipelineStageFlags};
+use {
+    crate::stage::PipelineStageFlags,
+    erupt::{
+        extensions::khr_synchronization2::AccessFlags2KHR, vk1_0::AccessFlags,
+    },
+};
 
-// pub(crate) fn supported_stages(access: AccessFlags) ->
-// PipelineStageFlags {     let mut result =
-// PipelineStageFlags::empty();     let mut bits = access.as_raw();
-//     while bits != 0 {
-//         let bit = 1 << bits.trailing_zeros();
-//         bits &= !bit;
-//         result |= supported_stages_inner(AccessFlags::from_raw(bit));
-//     }
-//     result
-// }
+/// Accesses stages may perform, split by their use in a dependency.
+struct StageAccess {
+    /// Writes that must be made available when stage is in first scope.
+    /// Reads never need to be made available, execution dependency
+    /// is enough to prevent write-after-read hazards.
+    src: AccessFlags,
 
-fn supported_stages_inner(access: AccessFlags) -> PipelineStageFlags {
+    /// Reads and writes that must see available writes
+    /// when stage is in second scope.
+    dst: AccessFlags,
+}
+
+fn stage_access(stage: PipelineStageFlags) -> StageAccess {
     type AF = AccessFlags;
     type PS = PipelineStageFlags;
 
-    match access {
-        AF::INDIRECT_COMMAND_READ => PS::DRAW_INDIRECT,
-        AF::INDEX_READ => PS::VERTEX_INPUT,
-        AF::VERTEX_ATTRIBUTE_READ => PS::VERTEX_INPUT,
-        AF::UNIFORM_READ => {
-            PS::TASK_SHADER_NV
-                | PS::MESH_SHADER_NV
-                | PS::RAY_TRACING_SHADER_NV
-                | PS::RAY_TRACING_SHADER_KHR
-                | PS::VERTEX_SHADER
-                | PS::TESSELLATION_CONTROL_SHADER
-                | PS::TESSELLATION_EVALUATION_SHADER
-                | PS::GEOMETRY_SHADER
-                | PS::FRAGMENT_SHADER
-                | PS::COMPUTE_SHADER
+    let shader_dst = AF::UNIFORM_READ | AF::SHADER_READ | AF::SHADER_WRITE;
+
+    let (src, dst) = match stage {
+        PS::DRAW_INDIRECT => (AF::empty(), AF::INDIRECT_COMMAND_READ),
+        PS::VERTEX_INPUT => {
+            (AF::empty(), AF::INDEX_READ | AF::VERTEX_ATTRIBUTE_READ)
         }
-        AF::SHADER_READ | AF::SHADER_WRITE => {
-            PS::TASK_SHADER_NV
-                | PS::MESH_SHADER_NV
-                | PS::RAY_TRACING_SHADER_KHR
-                | PS::VERTEX_SHADER
-                | PS::TESSELLATION_CONTROL_SHADER
-                | PS::TESSELLATION_EVALUATION_SHADER
-                | PS::GEOMETRY_SHADER
-                | PS::FRAGMENT_SHADER
-                | PS::COMPUTE_SHADER
+        PS::INDEX_INPUT => (AF::empty(), AF::INDEX_READ),
+        PS::VERTEX_ATTRIBUTE_INPUT => (AF::empty(), AF::VERTEX_ATTRIBUTE_READ),
+        PS::VERTEX_SHADER | PS::COMPUTE_SHADER => {
+            (AF::SHADER_WRITE, shader_dst)
         }
-        AF::INPUT_ATTACHMENT_READ => PS::FRAGMENT_SHADER,
-        AF::COLOR_ATTACHMENT_READ | AF::COLOR_ATTACHMENT_WRITE => {
-            PS::COLOR_ATTACHMENT_OUTPUT
+        PS::FRAGMENT_SHADER => {
+            (AF::SHADER_WRITE, shader_dst | AF::INPUT_ATTACHMENT_READ)
         }
-        AF::DEPTH_STENCIL_ATTACHMENT_READ
-        | AF::DEPTH_STENCIL_ATTACHMENT_WRITE => {
-            PS::EARLY_FRAGMENT_TESTS | PS::LATE_FRAGMENT_TESTS
+        PS::RAY_TRACING_SHADER => (
+            AF::SHADER_WRITE,
+            shader_dst | AF::ACCELERATION_STRUCTURE_READ_KHR,
+        ),
+        PS::EARLY_FRAGMENT_TESTS | PS::LATE_FRAGMENT_TESTS => (
+            AF::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            AF::DEPTH_STENCIL_ATTACHMENT_READ
+                | AF::DEPTH_STENCIL_ATTACHMENT_WRITE,
+        ),
+        PS::COLOR_ATTACHMENT_OUTPUT => (
+            AF::COLOR_ATTACHMENT_WRITE,
+            AF::COLOR_ATTACHMENT_READ | AF::COLOR_ATTACHMENT_WRITE,
+        ),
+        PS::TRANSFER | PS::COPY | PS::BLIT | PS::RESOLVE | PS::CLEAR => {
+            (AF::TRANSFER_WRITE, AF::TRANSFER_READ | AF::TRANSFER_WRITE)
         }
-        AF::TRANSFER_READ | AF::TRANSFER_WRITE => PS::TRANSFER,
-        AF::HOST_READ | AF::HOST_WRITE => PS::HOST,
-        AF::MEMORY_READ | AF::MEMORY_WRITE => PS::from_bits_truncate(!0),
-        AF::ACCELERATION_STRUCTURE_READ_KHR
-        | AF::ACCELERATION_STRUCTURE_WRITE_KHR => {
-            PS::ACCELERATION_STRUCTURE_BUILD_KHR
+        PS::HOST => (AF::HOST_WRITE, AF::HOST_READ | AF::HOST_WRITE),
+        PS::ACCELERATION_STRUCTURE_BUILD => (
+            AF::ACCELERATION_STRUCTURE_WRITE_KHR,
+            // Geometry and instance buffers are read as shader resources.
+            AF::ACCELERATION_STRUCTURE_READ_KHR
+                | AF::ACCELERATION_STRUCTURE_WRITE_KHR
+                | AF::SHADER_READ,
+        ),
+        PS::ALL_GRAPHICS | PS::ALL_COMMANDS => {
+            (AF::MEMORY_WRITE, AF::MEMORY_READ | AF::MEMORY_WRITE)
         }
-        _ if access.bits().count_ones() != 1 => {
-            panic!("Only one-bit access flags must be supplied")
+        _ if stage.bits().count_ones() != 1 => {
+            panic!("Only one-bit stage flags must be supplied")
         }
-        _ => PS::empty(),
-    }
+        // `TOP_OF_PIPE` and `BOTTOM_OF_PIPE` perform no accesses.
+        _ => (AF::empty(), AF::empty()),
+    };
+
+    StageAccess { src, dst }
 }
 
-pub(crate) fn supported_access(stages: PipelineStageFlags) -> AccessFlags {
+fn collect_access(
+    stages: PipelineStageFlags,
+    f: impl Fn(StageAccess) -> AccessFlags,
+) -> AccessFlags {
     let mut result = AccessFlags::empty();
-
-    let mut bits: Flags = !0;
+    let mut bits = stages.bits();
 
     while bits != 0 {
         let bit = 1 << bits.trailing_zeros();
-
         bits &= !bit;
-
-        if let Some(flag) = AccessFlags::from_bits(bit) {
-            if supported_stages_inner(flag).intersects(stages) {
-                result |= flag;
-            }
-        }
+        result |= f(stage_access(PipelineStageFlags::from_bits_truncate(bit)));
     }
 
     result
 }
+
+/// Returns access mask for first scope of dependency.
+pub(crate) fn src_access(stages: PipelineStageFlags) -> AccessFlags {
+    collect_access(stages, |access| access.src)
+}
+
+/// Returns access mask for second scope of dependency.
+pub(crate) fn dst_access(stages: PipelineStageFlags) -> AccessFlags {
+    collect_access(stages, |access| access.dst)
+}
+
+/// Converts access mask for `VK_KHR_synchronization2` commands.
+/// Flags of Vulkan 1.0 have same bits in both flag types.
+pub(crate) fn sync2_access(access: AccessFlags) -> AccessFlags2KHR {
+    AccessFlags2KHR::from_bits_truncate(u64::from(access.bits()))
+}
@@ -1,21 +1,22 @@
 use crate::{
-    out_of_host_memory, AccelerationStructureBuildFlags,
+    fence::WaitError, out_of_host_memory, AccelerationStructureBuildFlags,
     AccelerationStructureLevel, AspectFlags, AttachmentLoadOp,
     AttachmentStoreOp, BlendFactor, BlendOp, BorderColor, BufferCopy,
     BufferImageCopy, BufferUsage, CompareOp, ComponentMask, Culling,
     DescriptorBindingFlags, DescriptorSetLayoutFlags, DescriptorType,
-    DeviceAddress, Extent2d, Extent3d, Filter, Format, FrontFace,
-    GeometryFlags, ImageBlit, ImageCopy, ImageExtent, ImageSubresource,
-    ImageSubresourceLayers, ImageSubresourceRange, ImageUsage, ImageViewKind,
-    IndexType, Layout, LogicOp, MemoryUsage, MipmapMode, Offset2d, Offset3d,
-    OutOfMemory, PipelineStageFlags, PolygonMode, PresentMode,
-    PrimitiveTopology, QueueCapabilityFlags, Rect2d, SamplerAddressMode,
-    Samples, ShaderStage, ShaderStageFlags, StencilOp, VertexInputRate,
-    Viewport,
+    DeviceAddress, Extent2d, Extent3d, Filter, Format, FormatFeatures,
+    FrontFace, GeometryFlags, ImageBlit, ImageCopy, ImageExtent, ImageResolve,
+    ImageSubresource, ImageSubresourceLayers, ImageSubresourceRange,
+    ImageUsage, ImageViewKind, IndexType, Layout, LogicOp, MemoryUsage,
+    MipmapMode, Offset2d, Offset3d, OutOfMemory, PipelineStageFlags,
+    PolygonMode, PresentMode, PrimitiveTopology, QueueCapabilityFlags, Rect2d,
+    SamplerAddressMode, Samples, ShaderStage, ShaderStageFlags, StencilOp,
+    VertexInputRate, Viewport,
 };
 use erupt::{
     extensions::{
         khr_acceleration_structure as vkacc, khr_surface::PresentModeKHR,
+        khr_synchronization2 as vks2,
     },
     vk1_0, vk1_2,
 };
@@ -612,6 +613,15 @@ pub(crate) fn oom_error_from_erupt(err: vk1_0::Result) -> OutOfMemory {
     }
 }
 
+pub(crate) fn wait_error_from_erupt(err: vk1_0::Result) -> WaitError {
+    match err {
+        vk1_0::Result::ERROR_OUT_OF_HOST_MEMORY => out_of_host_memory(),
+        vk1_0::Result::ERROR_OUT_OF_DEVICE_MEMORY => OutOfMemory.into(),
+        vk1_0::Result::ERROR_DEVICE_LOST => WaitError::DeviceLost,
+        _ => unreachable!("Error {} is unexpected", err),
+    }
+}
+
 impl ToErupt<vk1_0::AttachmentLoadOp> for AttachmentLoadOp {
     fn to_erupt(self) -> vk1_0::AttachmentLoadOp {
         match self {
@@ -651,6 +661,82 @@ impl FromErupt<vk1_0::QueueFlags> for QueueCapabilityFlags {
     }
 }
 
+impl FromErupt<vk1_0::FormatFeatureFlags> for FormatFeatures {
+    fn from_erupt(flags: vk1_0::FormatFeatureFlags) -> FormatFeatures {
+        use vk1_0::FormatFeatureFlags as Flags;
+
+        let mut result = FormatFeatures::empty();
+
+        for &(flag, feature) in &[
+            (Flags::SAMPLED_IMAGE, FormatFeatures::SAMPLED_IMAGE),
+            (Flags::STORAGE_IMAGE, FormatFeatures::STORAGE_IMAGE),
+            (
+                Flags::STORAGE_IMAGE_ATOMIC,
+                FormatFeatures::STORAGE_IMAGE_ATOMIC,
+            ),
+            (
+                Flags::UNIFORM_TEXEL_BUFFER,
+                FormatFeatures::UNIFORM_TEXEL_BUFFER,
+            ),
+            (
+                Flags::STORAGE_TEXEL_BUFFER,
+                FormatFeatures::STORAGE_TEXEL_BUFFER,
+            ),
+            (
+                Flags::STORAGE_TEXEL_BUFFER_ATOMIC,
+                FormatFeatures::STORAGE_TEXEL_BUFFER_ATOMIC,
+            ),
+            (Flags::VERTEX_BUFFER, FormatFeatures::VERTEX_BUFFER),
+            (Flags::COLOR_ATTACHMENT, FormatFeatures::COLOR_ATTACHMENT),
+            (
+                Flags::COLOR_ATTACHMENT_BLEND,
+                FormatFeatures::COLOR_ATTACHMENT_BLEND,
+            ),
+            (
+                Flags::DEPTH_STENCIL_ATTACHMENT,
+                FormatFeatures::DEPTH_STENCIL_ATTACHMENT,
+            ),
+            (Flags::BLIT_SRC, FormatFeatures::BLIT_SRC),
+            (Flags::BLIT_DST, FormatFeatures::BLIT_DST),
+            (
+                Flags::SAMPLED_IMAGE_FILTER_LINEAR,
+                FormatFeatures::SAMPLED_IMAGE_FILTER_LINEAR,
+            ),
+            (Flags::TRANSFER_SRC, FormatFeatures::TRANSFER_SRC),
+            (Flags::TRANSFER_DST, FormatFeatures::TRANSFER_DST),
+        ] {
+            if flags.contains(flag) {
+                result |= feature;
+            }
+        }
+
+        result
+    }
+}
+
+impl FromErupt<vk1_0::SampleCountFlags> for Samples {
+    /// Converts to largest sample count in flags.
+    fn from_erupt(flags: vk1_0::SampleCountFlags) -> Samples {
+        use vk1_0::SampleCountFlags as Flags;
+
+        if flags.contains(Flags::_64) {
+            Samples::Samples64
+        } else if flags.contains(Flags::_32) {
+            Samples::Samples32
+        } else if flags.contains(Flags::_16) {
+            Samples::Samples16
+        } else if flags.contains(Flags::_8) {
+            Samples::Samples8
+        } else if flags.contains(Flags::_4) {
+            Samples::Samples4
+        } else if flags.contains(Flags::_2) {
+            Samples::Samples2
+        } else {
+            Samples::Samples1
+        }
+    }
+}
+
 impl ToErupt<vk1_0::ImageAspectFlags> for AspectFlags {
     fn to_erupt(self) -> vk1_0::ImageAspectFlags {
         let mut result = vk1_0::ImageAspectFlags::empty();
@@ -711,7 +797,20 @@ impl ToErupt<vk1_0::PipelineStageFlags> for PipelineStageFlags {
             result |= vk1_0::PipelineStageFlags::COMPUTE_SHADER
         }
 
-        if self.contains(PipelineStageFlags::TRANSFER) {
+        if self.intersects(
+            PipelineStageFlags::INDEX_INPUT
+                | PipelineStageFlags::VERTEX_ATTRIBUTE_INPUT,
+        ) {
+            result |= vk1_0::PipelineStageFlags::VERTEX_INPUT
+        }
+
+        if self.intersects(
+            PipelineStageFlags::TRANSFER
+                | PipelineStageFlags::COPY
+                | PipelineStageFlags::BLIT
+                | PipelineStageFlags::RESOLVE
+                | PipelineStageFlags::CLEAR,
+        ) {
             result |= vk1_0::PipelineStageFlags::TRANSFER
         }
 
@@ -744,6 +843,49 @@ impl ToErupt<vk1_0::PipelineStageFlags> for PipelineStageFlags {
     }
 }
 
+impl ToErupt<vks2::PipelineStageFlags2KHR> for PipelineStageFlags {
+    fn to_erupt(self) -> vks2::PipelineStageFlags2KHR {
+        let precise = PipelineStageFlags::INDEX_INPUT
+            | PipelineStageFlags::VERTEX_ATTRIBUTE_INPUT
+            | PipelineStageFlags::COPY
+            | PipelineStageFlags::BLIT
+            | PipelineStageFlags::RESOLVE
+            | PipelineStageFlags::CLEAR;
+
+        // Stages of Vulkan 1.0 have same bits in both flag types.
+        let legacy: vk1_0::PipelineStageFlags = (self - precise).to_erupt();
+        let mut result = vks2::PipelineStageFlags2KHR::from_bits_truncate(
+            u64::from(legacy.bits()),
+        );
+
+        if self.contains(PipelineStageFlags::INDEX_INPUT) {
+            result |= vks2::PipelineStageFlags2KHR::INDEX_INPUT_KHR
+        }
+
+        if self.contains(PipelineStageFlags::VERTEX_ATTRIBUTE_INPUT) {
+            result |= vks2::PipelineStageFlags2KHR::VERTEX_ATTRIBUTE_INPUT_KHR
+        }
+
+        if self.contains(PipelineStageFlags::COPY) {
+            result |= vks2::PipelineStageFlags2KHR::COPY_KHR
+        }
+
+        if self.contains(PipelineStageFlags::BLIT) {
+            result |= vks2::PipelineStageFlags2KHR::BLIT_KHR
+        }
+
+        if self.contains(PipelineStageFlags::RESOLVE) {
+            result |= vks2::PipelineStageFlags2KHR::RESOLVE_KHR
+        }
+
+        if self.contains(PipelineStageFlags::CLEAR) {
+            result |= vks2::PipelineStageFlags2KHR::CLEAR_KHR
+        }
+
+        result
+    }
+}
+
 impl ToErupt<vk1_0::ShaderStageFlags> for ShaderStageFlags {
     fn to_erupt(self) -> vk1_0::ShaderStageFlags {
         if self == ShaderStageFlags::ALL {
@@ -1130,7 +1272,9 @@ pub(crate) fn buffer_memory_usage_to_gpu_alloc(
         if memory_usage.contains(MemoryUsage::UPLOAD) {
             result |= UsageFlags::UPLOAD;
         }
-        if memory_usage.contains(MemoryUsage::DOWNLOAD) {
+        if memory_usage
+            .intersects(MemoryUsage::DOWNLOAD | MemoryUsage::HOST_CACHED)
+        {
             result |= UsageFlags::DOWNLOAD;
         }
         if memory_usage.contains(MemoryUsage::FAST_DEVICE_ACCESS) {
@@ -1351,6 +1495,18 @@ impl ToErupt<vk1_0::ImageCopy> for ImageCopy {
     }
 }
 
+impl ToErupt<vk1_0::ImageResolve> for ImageResolve {
+    fn to_erupt(self) -> vk1_0::ImageResolve {
+        vk1_0::ImageResolve {
+            src_subresource: self.src_subresource.to_erupt(),
+            src_offset: self.src_offset.to_erupt(),
+            dst_subresource: self.dst_subresource.to_erupt(),
+            dst_offset: self.dst_offset.to_erupt(),
+            extent: self.extent.to_erupt(),
+        }
+    }
+}
+
 impl ToErupt<vk1_0::BufferCopy> for BufferCopy {
     fn to_erupt(self) -> vk1_0::BufferCopy {
         vk1_0::BufferCopy {
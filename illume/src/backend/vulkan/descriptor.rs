@@ -1,7 +1,9 @@
 use {
     crate::descriptor::*,
     erupt::vk1_0,
+    smallvec::SmallVec,
     std::{
+        collections::HashMap,
         hash::{Hash, Hasher},
         ops::Deref,
     },
@@ -96,7 +98,7 @@ fn descriptor_type_from_index(index: usize) -> vk1_0::DescriptorType {
 }
 
 #[derive(Clone, Debug)]
-pub struct DescriptorSizesBuilder {
+pub(crate) struct DescriptorSizesBuilder {
     sizes: [u32; DESCRIPTOR_TYPES_COUNT],
 }
 
@@ -149,7 +151,7 @@ impl DescriptorSizesBuilder {
 
 /// Number of descriptors per type.
 #[derive(Clone, Debug)]
-pub struct DescriptorSizes {
+pub(crate) struct DescriptorSizes {
     sizes: [vk1_0::DescriptorPoolSizeBuilder<'static>; DESCRIPTOR_TYPES_COUNT],
     count: u8,
 }
@@ -163,6 +165,67 @@ impl DescriptorSizes {
     pub fn from_bindings(bindings: &[DescriptorSetLayoutBinding]) -> Self {
         DescriptorSizesBuilder::from_bindings(bindings).build()
     }
+
+    /// Returns pool sizes for specified number of sets.
+    pub fn scaled(
+        &self,
+        sets: u32,
+    ) -> SmallVec<
+        [vk1_0::DescriptorPoolSizeBuilder<'static>; DESCRIPTOR_TYPES_COUNT],
+    > {
+        self.as_slice()
+            .iter()
+            .map(|size| {
+                vk1_0::DescriptorPoolSizeBuilder::new()
+                    ._type(size._type)
+                    .descriptor_count(
+                        size.descriptor_count.saturating_mul(sets),
+                    )
+            })
+            .collect()
+    }
+}
+
+/// Pool from which sets of a layout are allocated.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct LayoutPool {
+    pub handle: vk1_0::DescriptorPool,
+    pub index: usize,
+
+    /// Maximum number of sets in the pool.
+    pub sets: u32,
+}
+
+/// Tracks pools used for descriptor set allocation.
+#[derive(Debug, Default)]
+pub(crate) struct DescriptorAllocator {
+    pub config: DescriptorPoolConfig,
+
+    /// Last created pool for each layout.
+    pools: HashMap<vk1_0::DescriptorSetLayout, LayoutPool>,
+}
+
+impl DescriptorAllocator {
+    pub fn current(
+        &self,
+        layout: vk1_0::DescriptorSetLayout,
+    ) -> Option<LayoutPool> {
+        self.pools.get(&layout).copied()
+    }
+
+    /// Returns number of sets for next pool of the layout.
+    pub fn next_pool_sets(&self, layout: vk1_0::DescriptorSetLayout) -> u32 {
+        self.config
+            .next_pool_sets(self.pools.get(&layout).map(|pool| pool.sets))
+    }
+
+    pub fn set_current(
+        &mut self,
+        layout: vk1_0::DescriptorSetLayout,
+        pool: LayoutPool,
+    ) {
+        self.pools.insert(layout, pool);
+    }
 }
 
 impl Deref for DescriptorSizes {
@@ -1,15 +1,19 @@
 use {
     super::{
-        access::supported_access,
+        access::{dst_access, src_access},
         convert::{
             buffer_memory_usage_to_gpu_alloc, from_erupt,
             image_memory_usage_to_gpu_alloc, oom_error_from_erupt,
-            ToErupt as _,
+            wait_error_from_erupt, ToErupt as _,
         },
-        descriptor::DescriptorSizes,
-        device_lost,
+        descriptor::{DescriptorAllocator, DescriptorSizes, LayoutPool},
         graphics::Graphics,
-        physical::{Features, Properties},
+        leak::{HandleKind, LeakDetector},
+        magazine::{BlockClass, Magazines},
+        physical::{
+            format_properties, image_format_properties, Features, Properties,
+        },
+        resources::ImageMemory,
         unexpected_result,
     },
     crate::{
@@ -19,29 +23,34 @@ use {
             AccelerationStructureGeometryInfo, AccelerationStructureInfo,
             AccelerationStructureLevel,
         },
-        align_up, arith_eq, arith_ne, assert_object,
+        align_up, arith_eq, arith_le, arith_ne, assert_object,
         buffer::{
-            Buffer, BufferInfo, BufferUsage, MappableBuffer,
-            StridedBufferRegion,
+            Buffer, BufferCaptureAddress, BufferInfo, BufferUsage,
+            MappableBuffer, StridedBufferRegion,
         },
         descriptor::{
-            CopyDescriptorSet, DescriptorSet, DescriptorSetInfo,
-            DescriptorSetLayout, DescriptorSetLayoutFlags,
-            DescriptorSetLayoutInfo, Descriptors, WriteDescriptorSet,
+            CopyDescriptorSet, CreateDescriptorSetError, DescriptorPoolConfig,
+            DescriptorSet, DescriptorSetInfo, DescriptorSetLayout,
+            DescriptorSetLayoutFlags, DescriptorSetLayoutInfo, Descriptors,
+            WriteDescriptorSet,
+        },
+        fence::{Fence, WaitError},
+        format::{
+            Format, FormatFeatures, FormatProperties, ImageFormatProperties,
         },
-        fence::Fence,
         framebuffer::{Framebuffer, FramebufferInfo},
         host_memory_space_overlow,
-        image::{Image, ImageInfo},
-        memory::MemoryUsage,
+        image::{Image, ImageExtent, ImageInfo, ImageUsage, Samples},
+        memory::{MemoryStats, MemoryUsage},
         out_of_host_memory,
         pipeline::{
-            ColorBlend, ComputePipeline, ComputePipelineInfo, GraphicsPipeline,
-            GraphicsPipelineInfo, PipelineLayout, PipelineLayoutInfo,
-            RayTracingPipeline, RayTracingPipelineInfo,
+            ColorBlend, ComputePipeline, ComputePipelineInfo, DynamicStates,
+            GraphicsPipeline, GraphicsPipelineInfo, PipelineLayout,
+            PipelineLayoutInfo, RayTracingPipeline, RayTracingPipelineInfo,
             RayTracingShaderGroupInfo, ShaderBindingTable,
             ShaderBindingTableInfo, State,
         },
+        query::{QueryPool, QueryPoolInfo},
         render_pass::{RenderPass, RenderPassInfo},
         sampler::{Sampler, SamplerInfo},
         semaphore::Semaphore,
@@ -52,18 +61,19 @@ use {
         surface::{Surface, SurfaceError},
         swapchain::Swapchain,
         view::{ImageView, ImageViewInfo, ImageViewKind},
-        CreateImageError, DeviceAddress, IndexType, MapError, OutOfMemory,
+        CreateImageError, DeviceAddress, ImageSize, IndexType, MapError,
+        OutOfMemory,
     },
     bumpalo::{collections::Vec as BVec, Bump},
     bytemuck::Pod,
     erupt::{
         extensions::{
-            khr_acceleration_structure as vkacc,
+            khr_acceleration_structure as vkacc, khr_pipeline_library as vkpl,
             khr_ray_tracing_pipeline as vkrt, khr_swapchain as vksw,
         },
-        vk1_0, vk1_2, DeviceLoader, ExtendableFrom as _,
+        vk1_0, vk1_1, vk1_2, DeviceLoader, ExtendableFrom as _,
     },
-    gpu_alloc::GpuAllocator,
+    gpu_alloc::{GpuAllocator, MemoryBlock},
     gpu_alloc_erupt::EruptMemoryDevice,
     parking_lot::Mutex,
     slab::Slab,
@@ -74,7 +84,11 @@ use {
         fmt::{self, Debug},
         mem::{size_of_val, MaybeUninit},
         ops::Range,
-        sync::{Arc, Weak},
+        panic::Location,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc, Weak,
+        },
     },
 };
 
@@ -98,10 +112,14 @@ pub(crate) struct Inner {
     properties: Properties,
     features: Features,
     allocator: Mutex<GpuAllocator<vk1_0::DeviceMemory>>,
+
+    /// Recently freed small blocks for buffers.
+    magazines: Magazines,
     version: u32,
     buffers: Mutex<Slab<vk1_0::Buffer>>,
     // buffer_views: Mutex<Slab<vk1_0::BufferView>>,
     descriptor_pools: Mutex<Slab<vk1_0::DescriptorPool>>,
+    descriptor_allocator: Mutex<DescriptorAllocator>,
     // descriptor_sets: Mutex<Slab<vk1_0::DescriptorSet>>,
     descriptor_set_layouts: Mutex<Slab<vk1_0::DescriptorSetLayout>>,
     fences: Mutex<Slab<vk1_0::Fence>>,
@@ -110,12 +128,42 @@ pub(crate) struct Inner {
     image_views: Mutex<Slab<vk1_0::ImageView>>,
     pipelines: Mutex<Slab<vk1_0::Pipeline>>,
     pipeline_layouts: Mutex<Slab<vk1_0::PipelineLayout>>,
+    query_pools: Mutex<Slab<vk1_0::QueryPool>>,
     render_passes: Mutex<Slab<vk1_0::RenderPass>>,
     semaphores: Mutex<Slab<vk1_0::Semaphore>>,
     shaders: Mutex<Slab<vk1_0::ShaderModule>>,
     acceleration_strucutres: Mutex<Slab<vkacc::AccelerationStructureKHR>>,
     samplers: Mutex<Slab<vk1_0::Sampler>>,
     swapchains: Mutex<Slab<vksw::SwapchainKHR>>,
+
+    /// Resources dropped by user and not yet handed to the queue.
+    garbage: Mutex<Vec<Garbage>>,
+
+    /// Bytes of memory bound to buffers and images.
+    allocated: AtomicU64,
+
+    /// Live handle counts checked for leaks each frame.
+    #[cfg(debug_assertions)]
+    leaks: Mutex<LeakDetector>,
+}
+
+/// Dropped resource waiting for device to finish using it.
+pub(crate) enum Garbage {
+    Buffer {
+        handle: vk1_0::Buffer,
+        index: usize,
+        block: MemoryBlock<vk1_0::DeviceMemory>,
+        class: Option<BlockClass>,
+    },
+    Image {
+        handle: vk1_0::Image,
+        index: usize,
+        memory: ImageMemory,
+    },
+    AccelerationStructure {
+        handle: vkacc::AccelerationStructureKHR,
+        index: usize,
+    },
 }
 
 impl Debug for Inner {
@@ -131,6 +179,17 @@ impl Debug for Inner {
     }
 }
 
+impl Drop for Inner {
+    fn drop(&mut self) {
+        let device = EruptMemoryDevice::wrap(&self.logical);
+        let allocator = self.allocator.get_mut();
+
+        for block in self.magazines.drain() {
+            unsafe { allocator.dealloc(device, block) }
+        }
+    }
+}
+
 #[derive(Clone)]
 #[repr(transparent)]
 pub struct WeakDevice {
@@ -312,6 +371,7 @@ impl Device {
                     gpu_alloc::Config::i_am_prototyping(),
                     memory_device_properties(&logical, &properties, &features),
                 )),
+                magazines: Magazines::new(),
                 logical,
                 physical,
                 version,
@@ -322,6 +382,7 @@ impl Device {
                 buffers: Mutex::new(Slab::with_capacity(4096)),
                 // buffer_views: Mutex::new(Slab::with_capacity(4096)),
                 descriptor_pools: Mutex::new(Slab::with_capacity(64)),
+                descriptor_allocator: Mutex::new(DescriptorAllocator::default()),
                 // descriptor_sets: Mutex::new(Slab::with_capacity(1024)),
                 descriptor_set_layouts: Mutex::new(Slab::with_capacity(64)),
                 fences: Mutex::new(Slab::with_capacity(128)),
@@ -330,12 +391,17 @@ impl Device {
                 image_views: Mutex::new(Slab::with_capacity(4096)),
                 pipelines: Mutex::new(Slab::with_capacity(128)),
                 pipeline_layouts: Mutex::new(Slab::with_capacity(64)),
+                query_pools: Mutex::new(Slab::with_capacity(16)),
                 render_passes: Mutex::new(Slab::with_capacity(32)),
                 semaphores: Mutex::new(Slab::with_capacity(128)),
                 shaders: Mutex::new(Slab::with_capacity(512)),
                 swapchains: Mutex::new(Slab::with_capacity(32)),
                 acceleration_strucutres: Mutex::new(Slab::with_capacity(1024)),
                 samplers: Mutex::new(Slab::with_capacity(128)),
+                garbage: Mutex::new(Vec::new()),
+                allocated: AtomicU64::new(0),
+                #[cfg(debug_assertions)]
+                leaks: Mutex::new(LeakDetector::new()),
             }),
         }
     }
@@ -353,8 +419,299 @@ impl Device {
         }
     }
 
+    /// Enqueues dropped resource for destruction.
+    pub(crate) fn destroy_later(&self, garbage: Garbage) {
+        self.inner.garbage.lock().push(garbage);
+    }
+
+    /// Takes resources dropped since last call.
+    pub(crate) fn take_garbage(&self) -> Vec<Garbage> {
+        std::mem::take(&mut *self.inner.garbage.lock())
+    }
+
+    /// Destroys dropped resources.
+    ///
+    /// # Safety
+    ///
+    /// Work submitted before resources were dropped must be complete.
+    pub(crate) unsafe fn destroy_garbage(
+        &self,
+        garbage: impl IntoIterator<Item = Garbage>,
+    ) {
+        let logical = &self.inner.logical;
+
+        for garbage in garbage {
+            match garbage {
+                Garbage::Buffer {
+                    handle,
+                    index,
+                    block,
+                    class,
+                } => {
+                    logical.destroy_buffer(Some(handle), None);
+                    self.inner.buffers.lock().remove(index);
+                    self.track_destroyed(HandleKind::Buffer);
+                    self.dealloc(block, class);
+                }
+                Garbage::Image {
+                    handle,
+                    index,
+                    memory,
+                } => {
+                    logical.destroy_image(Some(handle), None);
+                    self.inner.images.lock().remove(index);
+                    self.track_destroyed(HandleKind::Image);
+                    match memory {
+                        ImageMemory::Block(block) => self.dealloc(block, None),
+                        ImageMemory::Lazy { memory, size } => {
+                            self.inner
+                                .allocated
+                                .fetch_sub(size, Ordering::Relaxed);
+                            logical.free_memory(Some(memory), None);
+                        }
+                    }
+                }
+                Garbage::AccelerationStructure { handle, index } => {
+                    logical
+                        .destroy_acceleration_structure_khr(Some(handle), None);
+                    self.inner.acceleration_strucutres.lock().remove(index);
+                }
+            }
+        }
+    }
+
+    /// Counts created handle and its creation site
+    /// for leak detection in debug builds.
+    #[track_caller]
+    #[inline]
+    fn track_created(&self, kind: HandleKind) {
+        #[cfg(debug_assertions)]
+        self.inner.leaks.lock().created(kind, Location::caller());
+
+        #[cfg(not(debug_assertions))]
+        let _ = kind;
+    }
+
+    #[inline]
+    fn track_destroyed(&self, kind: HandleKind) {
+        #[cfg(debug_assertions)]
+        self.inner.leaks.lock().destroyed(kind);
+
+        #[cfg(not(debug_assertions))]
+        let _ = kind;
+    }
+
+    /// Checks whether live handle counts grew in every recent frame
+    /// and warns with creation sites if so.
+    /// Does nothing in release builds.
+    pub(crate) fn check_leaks(&self) {
+        #[cfg(debug_assertions)]
+        self.inner.leaks.lock().end_frame();
+    }
+
+    /// Allocates memory block for a resource.
+    ///
+    /// If device-local memory is exhausted retries with memory types
+    /// that are not device-local, so resources degrade to system memory
+    /// instead of failing.
+    unsafe fn alloc_memory(
+        &self,
+        request: gpu_alloc::Request,
+    ) -> Result<MemoryBlock<vk1_0::DeviceMemory>, gpu_alloc::AllocationError>
+    {
+        let device = EruptMemoryDevice::wrap(&self.inner.logical);
+        let mut allocator = self.inner.allocator.lock();
+
+        match allocator.alloc(device, request) {
+            Err(gpu_alloc::AllocationError::OutOfDeviceMemory) => {}
+            result => return result,
+        }
+
+        // Blocks cached in depot may occupy memory required for the request.
+        let cached = self.inner.magazines.drain_depot();
+        if !cached.is_empty() {
+            for block in cached {
+                allocator.dealloc(device, block);
+            }
+
+            match allocator.alloc(device, request) {
+                Err(gpu_alloc::AllocationError::OutOfDeviceMemory) => {}
+                result => return result,
+            }
+        }
+
+        let memory = &self.inner.properties.memory;
+        let fallback = memory.memory_types[..memory.memory_type_count as usize]
+            .iter()
+            .enumerate()
+            .filter(|(_, memory_type)| {
+                !memory_type
+                    .property_flags
+                    .contains(vk1_0::MemoryPropertyFlags::DEVICE_LOCAL)
+            })
+            .fold(0u32, |mask, (index, _)| mask | 1 << index)
+            & request.memory_types;
+
+        if fallback == 0 {
+            return Err(gpu_alloc::AllocationError::OutOfDeviceMemory);
+        }
+
+        tracing::warn!(
+            "Device-local memory is exhausted. Allocating {} bytes in system memory",
+            request.size
+        );
+
+        allocator.alloc(
+            device,
+            gpu_alloc::Request {
+                memory_types: fallback,
+                ..request
+            },
+        )
+    }
+
+    /// Allocates memory block for a buffer.
+    ///
+    /// Small requests are rounded up to size class and served
+    /// from blocks cached by `dealloc` before locking allocator.
+    /// Returned class must be passed to `dealloc` with the block.
+    unsafe fn alloc_buffer_memory(
+        &self,
+        request: gpu_alloc::Request,
+    ) -> Result<
+        (MemoryBlock<vk1_0::DeviceMemory>, Option<BlockClass>),
+        gpu_alloc::AllocationError,
+    > {
+        let class = match BlockClass::of(&request) {
+            Some(class) => class,
+            None => return Ok((self.alloc_memory(request)?, None)),
+        };
+
+        match self.inner.magazines.take(class) {
+            Some(block) => Ok((block, Some(class))),
+            None => Ok((self.alloc_memory(class.request())?, Some(class))),
+        }
+    }
+
+    /// Allocates dedicated memory at opaque address recorded by capture tool.
+    /// Such blocks are never cached in magazines.
+    unsafe fn alloc_capture_replay_memory(
+        &self,
+        request: gpu_alloc::Request,
+        address: u64,
+    ) -> Result<MemoryBlock<vk1_0::DeviceMemory>, gpu_alloc::AllocationError>
+    {
+        let device = CaptureReplayMemoryDevice {
+            device: EruptMemoryDevice::wrap(&self.inner.logical),
+            logical: &self.inner.logical,
+            address,
+        };
+
+        self.inner.allocator.lock().alloc_with_dedicated(
+            &device,
+            request,
+            gpu_alloc::Dedicated::Required,
+        )
+    }
+
+    unsafe fn dealloc(
+        &self,
+        block: MemoryBlock<vk1_0::DeviceMemory>,
+        class: Option<BlockClass>,
+    ) {
+        self.inner
+            .allocated
+            .fetch_sub(block.size(), Ordering::Relaxed);
+        self.release_memory(block, class);
+    }
+
+    /// Allocates lazily allocated memory for an image.
+    /// Returns `None` if device has no suitable memory type,
+    /// so ordinary memory is used instead.
+    unsafe fn alloc_lazy_memory(
+        &self,
+        reqs: &vk1_0::MemoryRequirements,
+    ) -> Option<ImageMemory> {
+        let memory = &self.inner.properties.memory;
+        let index = memory.memory_types[..memory.memory_type_count as usize]
+            .iter()
+            .enumerate()
+            .position(|(index, memory_type)| {
+                reqs.memory_type_bits & (1 << index) != 0
+                    && memory_type
+                        .property_flags
+                        .contains(vk1_0::MemoryPropertyFlags::LAZILY_ALLOCATED)
+            })?;
+
+        let memory = self
+            .inner
+            .logical
+            .allocate_memory(
+                &vk1_0::MemoryAllocateInfoBuilder::new()
+                    .allocation_size(reqs.size)
+                    .memory_type_index(index as u32),
+                None,
+                None,
+            )
+            .result();
+
+        match memory {
+            Ok(memory) => Some(ImageMemory::Lazy {
+                memory,
+                size: reqs.size,
+            }),
+            Err(err) => {
+                tracing::debug!("Failed to allocate lazy memory: {}", err);
+                None
+            }
+        }
+    }
+
+    /// Returns memory block to allocator.
+    /// Blocks of a size class are cached for reuse instead.
+    unsafe fn release_memory(
+        &self,
+        block: MemoryBlock<vk1_0::DeviceMemory>,
+        class: Option<BlockClass>,
+    ) {
+        let blocks = match class {
+            Some(class) => self.inner.magazines.put(class, block),
+            None => vec![block],
+        };
+
+        if !blocks.is_empty() {
+            let device = EruptMemoryDevice::wrap(&self.inner.logical);
+            let mut allocator = self.inner.allocator.lock();
+            for block in blocks {
+                allocator.dealloc(device, block);
+            }
+        }
+    }
+
+    /// Returns statistics of memory used by buffers and images.
+    pub fn memory_stats(&self) -> MemoryStats {
+        let memory = &self.inner.properties.memory;
+        let device_local = memory.memory_heaps
+            [..memory.memory_heap_count as usize]
+            .iter()
+            .filter(|heap| {
+                heap.flags.contains(vk1_0::MemoryHeapFlags::DEVICE_LOCAL)
+            })
+            .map(|heap| heap.size)
+            .sum();
+
+        // Blocks cached in magazines stay allocated from the device.
+        let cached = self.inner.magazines.cached();
+
+        MemoryStats {
+            allocated: self.inner.allocated.load(Ordering::Relaxed) + cached,
+            device_local,
+        }
+    }
+
     /// Creates buffer with uninitialized content.
     #[tracing::instrument]
+    #[track_caller]
     pub fn create_buffer(
         &self,
         info: BufferInfo,
@@ -364,6 +721,7 @@ impl Device {
 
     /// Creates buffer with uninitialized content.
     #[tracing::instrument]
+    #[track_caller]
     pub fn create_mappable_buffer(
         &self,
         info: BufferInfo,
@@ -372,6 +730,48 @@ impl Device {
         self.create_buffer_impl(info, Some(memory_usage))
     }
 
+    /// Narrows memory types suitable for buffer down to host-cached ones
+    /// if memory is used for downloading or host-cached memory is required.
+    /// Returns `None` if it is required but none is suitable.
+    fn buffer_memory_types(
+        &self,
+        memory_types: u32,
+        memory_usage: Option<MemoryUsage>,
+    ) -> Option<u32> {
+        let memory_usage = match memory_usage {
+            Some(memory_usage) => memory_usage,
+            None => return Some(memory_types),
+        };
+
+        if !memory_usage
+            .intersects(MemoryUsage::DOWNLOAD | MemoryUsage::HOST_CACHED)
+        {
+            return Some(memory_types);
+        }
+
+        let memory = &self.inner.properties.memory;
+        let cached = memory.memory_types[..memory.memory_type_count as usize]
+            .iter()
+            .enumerate()
+            .filter(|(_, memory_type)| {
+                memory_type.property_flags.contains(
+                    vk1_0::MemoryPropertyFlags::HOST_VISIBLE
+                        | vk1_0::MemoryPropertyFlags::HOST_CACHED,
+                )
+            })
+            .fold(0u32, |mask, (index, _)| mask | 1 << index)
+            & memory_types;
+
+        if cached != 0 {
+            Some(cached)
+        } else if memory_usage.contains(MemoryUsage::HOST_CACHED) {
+            None
+        } else {
+            Some(memory_types)
+        }
+    }
+
+    #[track_caller]
     fn create_buffer_impl(
         &self,
         info: BufferInfo,
@@ -381,15 +781,36 @@ impl Device {
             assert_ne!(self.inner.features.v12.buffer_device_address, 0);
         }
 
+        if info.capture_address.is_some() {
+            assert_ne!(
+                self.inner.features.v12.buffer_device_address_capture_replay, 0,
+                "`BufferDeviceAddressCaptureReplay` feature is not enabled"
+            );
+
+            assert!(
+                info.usage.contains(BufferUsage::DEVICE_ADDRESS),
+                "Buffer with capture address must have `DEVICE_ADDRESS` usage"
+            );
+        }
+
+        let mut create_info = vk1_0::BufferCreateInfoBuilder::new()
+            .size(info.size)
+            .usage(info.usage.to_erupt())
+            .sharing_mode(vk1_0::SharingMode::EXCLUSIVE);
+
+        let mut capture_info = info.capture_address.map(|address| {
+            vk1_2::BufferOpaqueCaptureAddressCreateInfoBuilder::new()
+                .opaque_capture_address(address.buffer)
+        });
+
+        if let Some(capture_info) = &mut capture_info {
+            create_info = create_info
+                .flags(vk1_0::BufferCreateFlags::DEVICE_ADDRESS_CAPTURE_REPLAY)
+                .extend_from(capture_info);
+        }
+
         let handle = unsafe {
-            self.inner.logical.create_buffer(
-                &vk1_0::BufferCreateInfoBuilder::new()
-                    .size(info.size)
-                    .usage(info.usage.to_erupt())
-                    .sharing_mode(vk1_0::SharingMode::EXCLUSIVE),
-                None,
-                None,
-            )
+            self.inner.logical.create_buffer(&create_info, None, None)
         }
         .result()
         .map_err(oom_error_from_erupt)?;
@@ -402,19 +823,34 @@ impl Device {
 
         debug_assert!(reqs.alignment.is_power_of_two());
 
-        let block = unsafe {
-            self.inner.allocator.lock().alloc(
-                EruptMemoryDevice::wrap(&self.inner.logical),
-                gpu_alloc::Request {
-                    size: reqs.size,
-                    align_mask: (reqs.alignment - 1) | info.align,
-                    memory_types: reqs.memory_type_bits,
-                    usage: buffer_memory_usage_to_gpu_alloc(
-                        info.usage,
-                        memory_usage,
-                    ),
-                },
-            )
+        let memory_types = match self
+            .buffer_memory_types(reqs.memory_type_bits, memory_usage)
+        {
+            Some(memory_types) => memory_types,
+            None => {
+                unsafe { self.inner.logical.destroy_buffer(Some(handle), None) }
+
+                tracing::error!(
+                    "No host-cached memory type is suitable for buffer"
+                );
+                return Err(OutOfMemory);
+            }
+        };
+
+        let request = gpu_alloc::Request {
+            size: reqs.size,
+            align_mask: (reqs.alignment - 1) | info.align,
+            memory_types,
+            usage: buffer_memory_usage_to_gpu_alloc(info.usage, memory_usage),
+        };
+
+        let (block, class) = unsafe {
+            match info.capture_address {
+                Some(address) => self
+                    .alloc_capture_replay_memory(request, address.memory)
+                    .map(|block| (block, None)),
+                None => self.alloc_buffer_memory(request),
+            }
         }
         .map_err(|err| {
             unsafe { self.inner.logical.destroy_buffer(Some(handle), None) }
@@ -436,27 +872,35 @@ impl Device {
             unsafe {
                 self.inner.logical.destroy_buffer(Some(handle), None);
 
-                self.inner.allocator.lock().dealloc(
-                    EruptMemoryDevice::wrap(&self.inner.logical),
-                    block,
-                );
+                self.release_memory(block, class);
             }
 
             return Err(oom_error_from_erupt(err));
         }
 
         let address = if info.usage.contains(BufferUsage::DEVICE_ADDRESS) {
-            Some(Option::unwrap(from_erupt(unsafe {
-                self.inner.logical.get_buffer_device_address(
-                    &vk1_2::BufferDeviceAddressInfoBuilder::new()
-                        .buffer(handle),
-                )
-            })))
+            let info =
+                vk1_2::BufferDeviceAddressInfoBuilder::new().buffer(handle);
+
+            // Core function is not loaded prior to Vulkan 1.2.
+            let address = unsafe {
+                if self.inner.version >= vk1_0::make_version(1, 2, 0) {
+                    self.inner.logical.get_buffer_device_address(&info)
+                } else {
+                    self.inner.logical.get_buffer_device_address_khr(&info)
+                }
+            };
+
+            Some(Option::unwrap(from_erupt(address)))
         } else {
             None
         };
 
         let buffer_index = self.inner.buffers.lock().insert(handle);
+        self.track_created(HandleKind::Buffer);
+        self.inner
+            .allocated
+            .fetch_add(block.size(), Ordering::Relaxed);
 
         tracing::debug!("Buffer created {:p}", handle);
         Ok(MappableBuffer::new(
@@ -466,6 +910,7 @@ impl Device {
             address,
             buffer_index,
             block,
+            class,
             memory_usage.unwrap_or(MemoryUsage::empty()),
         ))
     }
@@ -478,6 +923,7 @@ impl Device {
     /// Function will panic if creating buffer size does not equal data size.
     /// E.g. if `info.size != std::mem::size_of(data)`.
     #[tracing::instrument(skip(data))]
+    #[track_caller]
     pub fn create_buffer_static<T: 'static>(
         &self,
         info: BufferInfo,
@@ -625,7 +1071,21 @@ impl Device {
         let vertex_shader_entry: CString;
         let fragment_shader_entry: CString;
         let mut shader_stages = BVec::with_capacity_in(2, &bump);
-        let mut dynamic_states = BVec::with_capacity_in(7, &bump);
+        let mut dynamic_states = BVec::with_capacity_in(12, &bump);
+
+        if !info.dynamic_states.is_empty() {
+            assert!(
+                self.inner.logical.enabled().ext_extended_dynamic_state,
+                "`ExtendedDynamicState` feature is not enabled"
+            );
+        }
+
+        if info
+            .dynamic_states
+            .contains(DynamicStates::PRIMITIVE_TOPOLOGY)
+        {
+            dynamic_states.push(vk1_0::DynamicState::PRIMITIVE_TOPOLOGY_EXT);
+        }
 
         let vertex_binding_descriptions = info
             .vertex_bindings
@@ -672,9 +1132,9 @@ impl Device {
 
         let rasterization_state;
 
-        let viewport;
+        let viewports;
 
-        let scissor;
+        let scissors;
 
         let mut viewport_state = None;
 
@@ -688,33 +1148,70 @@ impl Device {
             let mut builder =
                 vk1_0::PipelineViewportStateCreateInfoBuilder::new();
 
+            let viewport_count = rasterizer.viewport_count;
+
+            if viewport_count != 1 {
+                assert_ne!(
+                    self.inner.features.v10.multi_viewport, 0,
+                    "`MultiViewport` feature is not enabled"
+                );
+            }
+
+            assert!(
+                viewport_count > 0
+                    && viewport_count
+                        <= self.inner.properties.v10.limits.max_viewports,
+                "Viewport count {} is out of supported range",
+                viewport_count
+            );
+
             match &rasterizer.viewport {
                 State::Static { value } => {
-                    viewport = value.to_erupt().into_builder();
+                    viewports = std::iter::repeat(value.to_erupt())
+                        .take(viewport_count as usize)
+                        .map(|viewport| viewport.into_builder())
+                        .collect::<SmallVec<[_; 16]>>();
 
-                    builder =
-                        builder.viewports(std::slice::from_ref(&viewport));
+                    builder = builder.viewports(&viewports);
                 }
                 State::Dynamic => {
                     dynamic_states.push(vk1_0::DynamicState::VIEWPORT);
-                    builder = builder.viewport_count(1);
+                    builder = builder.viewport_count(viewport_count);
                 }
             }
 
             match &rasterizer.scissor {
                 State::Static { value } => {
-                    scissor = value.to_erupt().into_builder();
+                    scissors = std::iter::repeat(value.to_erupt())
+                        .take(viewport_count as usize)
+                        .map(|scissor| scissor.into_builder())
+                        .collect::<SmallVec<[_; 16]>>();
 
-                    builder = builder.scissors(std::slice::from_ref(&scissor));
+                    builder = builder.scissors(&scissors);
                 }
                 State::Dynamic => {
                     dynamic_states.push(vk1_0::DynamicState::SCISSOR);
-                    builder = builder.scissor_count(1);
+                    builder = builder.scissor_count(viewport_count);
                 }
             }
 
             viewport_state = Some(builder);
 
+            if info.dynamic_states.contains(DynamicStates::CULL_MODE) {
+                dynamic_states.push(vk1_0::DynamicState::CULL_MODE_EXT);
+            }
+
+            if info.dynamic_states.contains(DynamicStates::FRONT_FACE) {
+                dynamic_states.push(vk1_0::DynamicState::FRONT_FACE_EXT);
+            }
+
+            if info.dynamic_states.contains(DynamicStates::DEPTH_TEST) {
+                dynamic_states.push(vk1_0::DynamicState::DEPTH_TEST_ENABLE_EXT);
+                dynamic_states
+                    .push(vk1_0::DynamicState::DEPTH_WRITE_ENABLE_EXT);
+                dynamic_states.push(vk1_0::DynamicState::DEPTH_COMPARE_OP_EXT);
+            }
+
             rasterization_state =
                 vk1_0::PipelineRasterizationStateCreateInfoBuilder::new()
                     .rasterizer_discard_enable(false)
@@ -726,7 +1223,12 @@ impl Device {
 
             multisample_state = Some(
                 vk1_0::PipelineMultisampleStateCreateInfoBuilder::new()
-                    .rasterization_samples(vk1_0::SampleCountFlagBits::_1),
+                    .rasterization_samples(
+                        info.render_pass
+                            .info()
+                            .subpass_samples(info.subpass as usize)
+                            .to_erupt(),
+                    ),
             );
 
             let mut builder =
@@ -1017,10 +1519,25 @@ impl Device {
 
     /// Creates image with uninitialized content.
     #[tracing::instrument]
+    #[track_caller]
     pub fn create_image(
         &self,
         info: ImageInfo,
     ) -> Result<Image, CreateImageError> {
+        self.create_image_with_memory_usage(info, MemoryUsage::empty())
+    }
+
+    /// Creates image with uninitialized content
+    /// in memory suitable for specified usage.
+    #[tracing::instrument]
+    #[track_caller]
+    pub fn create_image_with_memory_usage(
+        &self,
+        info: ImageInfo,
+        memory_usage: MemoryUsage,
+    ) -> Result<Image, CreateImageError> {
+        info.validate(self)?;
+
         let image = unsafe {
             self.inner.logical.create_image(
                 &vk1_0::ImageCreateInfoBuilder::new()
@@ -1049,32 +1566,38 @@ impl Device {
 
         debug_assert!(reqs.alignment.is_power_of_two());
 
-        let block = unsafe {
-            self.inner
-                .allocator
-                .lock()
-                .alloc(
-                    EruptMemoryDevice::wrap(&self.inner.logical),
-                    gpu_alloc::Request {
-                        size: reqs.size,
-                        align_mask: reqs.alignment - 1,
-                        memory_types: reqs.memory_type_bits,
-                        usage: image_memory_usage_to_gpu_alloc(info.usage),
-                    },
-                )
+        let lazy = if memory_usage.contains(MemoryUsage::LAZILY_ALLOCATED)
+            && info.usage.contains(ImageUsage::TRANSIENT_ATTACHMENT)
+        {
+            unsafe { self.alloc_lazy_memory(&reqs) }
+        } else {
+            None
+        };
+
+        let memory = match lazy {
+            Some(memory) => memory,
+            None => unsafe {
+                self.alloc_memory(gpu_alloc::Request {
+                    size: reqs.size,
+                    align_mask: reqs.alignment - 1,
+                    memory_types: reqs.memory_type_bits,
+                    usage: image_memory_usage_to_gpu_alloc(info.usage),
+                })
+                .map(ImageMemory::Block)
                 .map_err(|err| {
                     self.inner.logical.destroy_image(Some(image), None);
 
                     tracing::error!("{}", err);
                     OutOfMemory
                 })
-        }?;
+            }?,
+        };
 
         let result = unsafe {
             self.inner.logical.bind_image_memory(
                 image,
-                *block.memory(),
-                block.offset(),
+                memory.memory(),
+                memory.offset(),
             )
         }
         .result();
@@ -1082,23 +1605,34 @@ impl Device {
         match result {
             Ok(()) => {
                 let index = self.inner.images.lock().insert(image);
+                self.track_created(HandleKind::Image);
+                self.inner
+                    .allocated
+                    .fetch_add(memory.size(), Ordering::Relaxed);
 
                 tracing::debug!("Image created {:p}", image);
                 Ok(Image::new(
                     info,
                     self.downgrade(),
                     image,
-                    Some(block),
+                    Some(memory),
                     Some(index),
                 ))
             }
             Err(err) => {
                 unsafe {
                     self.inner.logical.destroy_image(Some(image), None);
-                    self.inner.allocator.lock().dealloc(
-                        EruptMemoryDevice::wrap(&self.inner.logical),
-                        block,
-                    );
+                    match memory {
+                        ImageMemory::Block(block) => {
+                            self.inner.allocator.lock().dealloc(
+                                EruptMemoryDevice::wrap(&self.inner.logical),
+                                block,
+                            )
+                        }
+                        ImageMemory::Lazy { memory, .. } => {
+                            self.inner.logical.free_memory(Some(memory), None);
+                        }
+                    }
                 }
 
                 Err(oom_error_from_erupt(err).into())
@@ -1235,6 +1769,7 @@ impl Device {
 
     /// Creates view to an image.
     #[tracing::instrument]
+    #[track_caller]
     pub fn create_image_view(
         &self,
         info: ImageViewInfo,
@@ -1266,6 +1801,7 @@ impl Device {
         .map_err(oom_error_from_erupt)?;
 
         let index = self.inner.image_views.lock().insert(view);
+        self.track_created(HandleKind::ImageView);
 
         tracing::debug!("ImageView created {:p}", view);
         Ok(ImageView::new(info, self.downgrade(), view, index))
@@ -1327,6 +1863,10 @@ impl Device {
         &self,
         info: RenderPassInfo,
     ) -> Result<RenderPass, CreateRenderPassError> {
+        for subpass in 0..info.subpasses.len() {
+            check_subpass_resolves(&info, subpass)?;
+        }
+
         let mut subpass_attachments = Vec::new();
 
         let subpasses =
@@ -1381,7 +1921,16 @@ impl Device {
                                 .layout(vk1_0::ImageLayout::GENERAL),
                         );
                     }
-                    Ok((color_offset, depth_offset))
+
+                    // Resolve attachments are validated above.
+                    let resolve_offset = subpass_attachments.len();
+                    subpass_attachments.extend(s.resolves.iter().map(|&r| {
+                        vk1_0::AttachmentReferenceBuilder::new()
+                            .attachment(r as u32)
+                            .layout(vk1_0::ImageLayout::GENERAL)
+                    }));
+
+                    Ok((color_offset, depth_offset, resolve_offset))
                 })
                 .collect::<Result<SmallVec<[_; 16]>, _>>()?;
 
@@ -1389,12 +1938,19 @@ impl Device {
             .subpasses
             .iter()
             .zip(subpasses)
-            .map(|(s, (color_offset, depth_offset))| {
-                let builder = vk1_0::SubpassDescriptionBuilder::new()
+            .map(|(s, (color_offset, depth_offset, resolve_offset))| {
+                let mut builder = vk1_0::SubpassDescriptionBuilder::new()
                     .color_attachments(
                         &subpass_attachments[color_offset..depth_offset],
                     );
 
+                if !s.resolves.is_empty() {
+                    builder = builder.resolve_attachments(
+                        &subpass_attachments[resolve_offset..]
+                            [..s.resolves.len()],
+                    );
+                }
+
                 if s.depth.is_some() {
                     builder.depth_stencil_attachment(
                         &subpass_attachments[depth_offset],
@@ -1415,7 +1971,7 @@ impl Device {
                     .store_op(a.store_op.to_erupt())
                     .initial_layout(a.initial_layout.to_erupt())
                     .final_layout(a.final_layout.to_erupt())
-                    .samples(vk1_0::SampleCountFlagBits::_1)
+                    .samples(a.samples.to_erupt())
             })
             .collect::<SmallVec<[_; 16]>>();
 
@@ -1442,8 +1998,8 @@ impl Device {
                     )
                     .src_stage_mask(d.src_stages.to_erupt())
                     .dst_stage_mask(d.dst_stages.to_erupt())
-                    .src_access_mask(supported_access(d.src_stages.to_erupt()))
-                    .dst_access_mask(supported_access(d.dst_stages.to_erupt()))
+                    .src_access_mask(src_access(d.src_stages))
+                    .dst_access_mask(dst_access(d.dst_stages))
             })
             .collect::<SmallVec<[_; 16]>>();
 
@@ -1604,7 +2160,7 @@ impl Device {
     /// All specified fences must be in signalled state.
     /// Fences are moved into unsignalled state.
     #[tracing::instrument]
-    pub fn reset_fences(&self, fences: &[&Fence]) {
+    pub fn reset_fences(&self, fences: &[&Fence]) -> Result<(), WaitError> {
         for fence in fences {
             assert_owner!(fence, self);
         }
@@ -1614,23 +2170,20 @@ impl Device {
             .map(|fence| fence.handle())
             .collect::<SmallVec<[_; 16]>>();
 
-        match unsafe { self.inner.logical.reset_fences(&fences) }.result() {
-            Ok(()) => {}
-            Err(vk1_0::Result::ERROR_DEVICE_LOST) => device_lost(),
-            Err(result) => unexpected_result(result),
-        }
+        unsafe { self.inner.logical.reset_fences(&fences) }
+            .result()
+            .map_err(wait_error_from_erupt)
     }
 
     #[tracing::instrument]
-    pub fn is_fence_signalled(&self, fence: &Fence) -> bool {
+    pub fn is_fence_signalled(&self, fence: &Fence) -> Result<bool, WaitError> {
         assert_owner!(fence, self);
         let fence = fence.handle();
 
         match unsafe { self.inner.logical.get_fence_status(fence) }.raw {
-            vk1_0::Result::SUCCESS => true,
-            vk1_0::Result::NOT_READY => true,
-            vk1_0::Result::ERROR_DEVICE_LOST => device_lost(),
-            err => unexpected_result(err),
+            vk1_0::Result::SUCCESS => Ok(true),
+            vk1_0::Result::NOT_READY => Ok(false),
+            err => Err(wait_error_from_erupt(err)),
         }
     }
 
@@ -1641,7 +2194,11 @@ impl Device {
     /// one is signaled if `all == false`). Fences are signaled by `Queue`s.
     /// See `Queue::submit`.
     #[tracing::instrument]
-    pub fn wait_fences(&self, fences: &[&Fence], all: bool) {
+    pub fn wait_fences(
+        &self,
+        fences: &[&Fence],
+        all: bool,
+    ) -> Result<(), WaitError> {
         for fence in fences {
             assert_owner!(fence, self);
         }
@@ -1651,13 +2208,9 @@ impl Device {
             .map(|fence| fence.handle())
             .collect::<SmallVec<[_; 16]>>();
 
-        match unsafe { self.inner.logical.wait_for_fences(&fences, all, !0) }
+        unsafe { self.inner.logical.wait_for_fences(&fences, all, !0) }
             .result()
-        {
-            Ok(()) => {}
-            Err(vk1_0::Result::ERROR_DEVICE_LOST) => device_lost(),
-            Err(result) => unexpected_result(result),
-        }
+            .map_err(wait_error_from_erupt)
     }
 
     /// Wait for whole device to become idle. That is, wait for all pending
@@ -1665,12 +2218,10 @@ impl Device {
     /// `Queue::wait_idle` for all queues. Typically used only before device
     /// destruction.
     #[tracing::instrument]
-    pub fn wait_idle(&self) {
-        match unsafe { self.inner.logical.device_wait_idle() }.result() {
-            Ok(()) => {}
-            Err(vk1_0::Result::ERROR_DEVICE_LOST) => device_lost(),
-            Err(result) => unexpected_result(result),
-        }
+    pub fn wait_idle(&self) -> Result<(), WaitError> {
+        unsafe { self.inner.logical.device_wait_idle() }
+            .result()
+            .map_err(wait_error_from_erupt)
     }
 
     #[tracing::instrument]
@@ -1849,6 +2400,59 @@ impl Device {
         }
     }
 
+    /// Returns opaque capture addresses of the buffer and its memory.
+    /// Capture tools record them to replay buffer at the same device address
+    /// with `BufferInfo::capture_address`.
+    /// Returns `None` if buffer has no device address.
+    #[tracing::instrument]
+    pub fn get_buffer_opaque_capture_address(
+        &self,
+        buffer: &Buffer,
+    ) -> Option<BufferCaptureAddress> {
+        assert_owner!(buffer, self);
+
+        assert_ne!(
+            self.inner.features.v12.buffer_device_address_capture_replay, 0,
+            "`BufferDeviceAddressCaptureReplay` feature is not enabled"
+        );
+
+        if !buffer.info().usage.contains(BufferUsage::DEVICE_ADDRESS) {
+            return None;
+        }
+
+        let info = vk1_2::BufferDeviceAddressInfoBuilder::new()
+            .buffer(buffer.handle());
+
+        let memory_info =
+            vk1_2::DeviceMemoryOpaqueCaptureAddressInfoBuilder::new()
+                .memory(buffer.memory_handle());
+
+        // Core functions are not loaded prior to Vulkan 1.2.
+        let (buffer, memory) = unsafe {
+            if self.inner.version >= vk1_0::make_version(1, 2, 0) {
+                (
+                    self.inner.logical.get_buffer_opaque_capture_address(&info),
+                    self.inner
+                        .logical
+                        .get_device_memory_opaque_capture_address(&memory_info),
+                )
+            } else {
+                (
+                    self.inner
+                        .logical
+                        .get_buffer_opaque_capture_address_khr(&info),
+                    self.inner
+                        .logical
+                        .get_device_memory_opaque_capture_address_khr(
+                            &memory_info,
+                        ),
+                )
+            }
+        };
+
+        Some(BufferCaptureAddress { buffer, memory })
+    }
+
     #[tracing::instrument]
     pub fn get_acceleration_structure_device_address(
         &self,
@@ -1874,6 +2478,31 @@ impl Device {
             assert_owner!(shader.module(), self);
         }
 
+        if info.library || !info.libraries.is_empty() {
+            assert!(
+                self.inner.logical.enabled().khr_pipeline_library,
+                "`RayTracingPipelineLibrary` feature is not enabled"
+            );
+
+            assert!(
+                info.interface.is_some(),
+                "Interface must be specified for pipeline libraries and pipelines that link them"
+            );
+        }
+
+        for library in &info.libraries {
+            assert_owner!(library, self);
+            assert!(
+                library.info().library,
+                "Only pipeline libraries can be linked"
+            );
+            assert_eq!(
+                library.info().interface,
+                info.interface,
+                "Pipeline libraries must have the same interface"
+            );
+        }
+
         let entries: Vec<_> = info
             .shaders
             .iter()
@@ -1945,15 +2574,48 @@ impl Device {
             })
             .collect();
 
+        let libraries: Vec<_> = info
+            .libraries
+            .iter()
+            .map(|library| library.handle())
+            .collect();
+
+        let library_info = vkpl::PipelineLibraryCreateInfoKHRBuilder::new()
+            .libraries(&libraries);
+
+        let library_interface = info.interface.map(|interface| {
+            vkrt::RayTracingPipelineInterfaceCreateInfoKHRBuilder::new()
+                .max_pipeline_ray_payload_size(interface.max_payload_size)
+                .max_pipeline_ray_hit_attribute_size(
+                    interface.max_hit_attribute_size,
+                )
+        });
+
+        let mut create_info =
+            vkrt::RayTracingPipelineCreateInfoKHRBuilder::new()
+                .stages(&stages)
+                .groups(&groups)
+                .max_pipeline_ray_recursion_depth(info.max_recursion_depth)
+                .layout(info.layout.handle());
+
+        if info.library {
+            create_info =
+                create_info.flags(vk1_0::PipelineCreateFlags::LIBRARY_KHR);
+        }
+
+        if !libraries.is_empty() {
+            create_info = create_info.library_info(&library_info);
+        }
+
+        if let Some(library_interface) = &library_interface {
+            create_info = create_info.library_interface(library_interface);
+        }
+
         let handles = unsafe {
             self.inner.logical.create_ray_tracing_pipelines_khr(
                 None,
                 None,
-                &[vkrt::RayTracingPipelineCreateInfoKHRBuilder::new()
-                    .stages(&stages)
-                    .groups(&groups)
-                    .max_pipeline_ray_recursion_depth(info.max_recursion_depth)
-                    .layout(info.layout.handle())],
+                &[create_info],
                 None,
             )
         }
@@ -1964,17 +2626,39 @@ impl Device {
 
         let handle = handles[0];
 
+        if info.library {
+            // Group handles of libraries are queried from linked pipelines.
+            let index = self.inner.pipelines.lock().insert(handle);
+
+            tracing::debug!("RayTracingPipeline library created {:p}", handle);
+            return Ok(RayTracingPipeline::new(
+                info,
+                self.downgrade(),
+                handle,
+                Vec::new().into(),
+                index,
+            ));
+        }
+
         let group_size = self.inner.properties.rt.shader_group_handle_size;
 
         let group_size_usize =
             usize::try_from(group_size).map_err(|_| out_of_host_memory())?;
 
+        // Groups of linked libraries follow own groups.
+        let total_groups = info.groups.len()
+            + info
+                .libraries
+                .iter()
+                .map(RayTracingPipeline::group_count)
+                .sum::<usize>();
+
         let total_size_usize = group_size_usize
-            .checked_mul(info.groups.len())
+            .checked_mul(total_groups)
             .ok_or_else(host_memory_space_overlow)?;
 
         let group_count =
-            u32::try_from(info.groups.len()).map_err(|_| OutOfMemory)?;
+            u32::try_from(total_groups).map_err(|_| OutOfMemory)?;
 
         let mut bytes = vec![0u8; total_size_usize];
 
@@ -2011,12 +2695,11 @@ impl Device {
         &self,
         info: DescriptorSetLayoutInfo,
     ) -> Result<DescriptorSetLayout, OutOfMemory> {
-        let handle = if vk1_0::make_version(1, 2, 0) > self.inner.version {
-            assert!(
-                info.bindings.iter().all(|binding| binding.flags.is_empty()),
-                "Vulkan 1.2 is required for non-empty `DescriptorBindingFlags`",
-            );
-
+        let handle = if info
+            .bindings
+            .iter()
+            .all(|binding| binding.flags.is_empty())
+        {
             unsafe {
                 self.inner.logical.create_descriptor_set_layout(
                     &vk1_0::DescriptorSetLayoutCreateInfoBuilder::new()
@@ -2039,6 +2722,11 @@ impl Device {
                 )
             }
         } else {
+            assert_ne!(
+                self.inner.features.v12.descriptor_indexing, 0,
+                "Descriptor indexing is required for non-empty `DescriptorBindingFlags`",
+            );
+
             let flags = info
                 .bindings
                 .iter()
@@ -2093,11 +2781,40 @@ impl Device {
     }
 
     #[tracing::instrument]
+    #[track_caller]
     pub fn create_descriptor_set(
         &self,
         info: DescriptorSetInfo,
-    ) -> Result<DescriptorSet, OutOfMemory> {
+    ) -> Result<DescriptorSet, CreateDescriptorSetError> {
         assert_owner!(info.layout, self);
+
+        let layout = info.layout.handle();
+        let mut allocator = self.inner.descriptor_allocator.lock();
+
+        if let Some(pool) = allocator.current(layout) {
+            match self.allocate_descriptor_set(pool.handle, layout) {
+                Ok(handle) => {
+                    self.track_created(HandleKind::DescriptorSet);
+                    tracing::debug!("DescriptorSet created {:p}", handle);
+                    return Ok(DescriptorSet::new(
+                        info,
+                        self.downgrade(),
+                        handle,
+                        pool.handle,
+                        pool.index,
+                    ));
+                }
+                Err(vk1_0::Result::ERROR_FRAGMENTED_POOL)
+                | Err(vk1_0::Result::ERROR_OUT_OF_POOL_MEMORY) => {
+                    tracing::debug!(
+                        "DescriptorPool {:p} is exhausted",
+                        pool.handle
+                    );
+                }
+                Err(err) => return Err(oom_error_from_erupt(err).into()),
+            }
+        }
+
         let mut pool_flags = vk1_0::DescriptorPoolCreateFlags::empty();
 
         if info
@@ -2109,11 +2826,13 @@ impl Device {
             pool_flags |= vk1_0::DescriptorPoolCreateFlags::UPDATE_AFTER_BIND;
         }
 
-        let pool = unsafe {
+        let sets = allocator.next_pool_sets(layout);
+
+        let handle = unsafe {
             self.inner.logical.create_descriptor_pool(
                 &vk1_0::DescriptorPoolCreateInfoBuilder::new()
-                    .max_sets(1)
-                    .pool_sizes(&info.layout.sizes())
+                    .max_sets(sets)
+                    .pool_sizes(&info.layout.sizes().scaled(sets))
                     .flags(pool_flags),
                 None,
                 None,
@@ -2122,33 +2841,67 @@ impl Device {
         .result()
         .map_err(oom_error_from_erupt)?;
 
-        let handles = unsafe {
-            self.inner.logical.allocate_descriptor_sets(
-                &vk1_0::DescriptorSetAllocateInfoBuilder::new()
-                    .descriptor_pool(pool)
-                    .set_layouts(&[info.layout.handle()]),
-            )
-        }
-        .result()
-        .map_err(oom_error_from_erupt)?;
+        let index = self.inner.descriptor_pools.lock().insert(handle);
 
-        debug_assert_eq!(handles.len(), 1);
+        tracing::debug!(
+            "DescriptorPool created {:p} for {} sets",
+            handle,
+            sets
+        );
 
-        let handle = handles[0];
+        let pool = LayoutPool {
+            handle,
+            index,
+            sets,
+        };
+        allocator.set_current(layout, pool);
 
-        // let index = self.inner.descriptor_sets.lock().insert(handle);
-        let pool_index = self.inner.descriptor_pools.lock().insert(pool);
+        let handle = self
+            .allocate_descriptor_set(pool.handle, layout)
+            .map_err(|err| match err {
+                vk1_0::Result::ERROR_FRAGMENTED_POOL => {
+                    CreateDescriptorSetError::FragmentedPool
+                }
+                vk1_0::Result::ERROR_OUT_OF_POOL_MEMORY => {
+                    CreateDescriptorSetError::OutOfPoolMemory
+                }
+                err => oom_error_from_erupt(err).into(),
+            })?;
 
+        self.track_created(HandleKind::DescriptorSet);
         tracing::debug!("DescriptorSet created {:p}", handle);
         Ok(DescriptorSet::new(
             info,
             self.downgrade(),
             handle,
-            pool,
-            pool_index,
+            pool.handle,
+            pool.index,
         ))
     }
 
+    fn allocate_descriptor_set(
+        &self,
+        pool: vk1_0::DescriptorPool,
+        layout: vk1_0::DescriptorSetLayout,
+    ) -> Result<vk1_0::DescriptorSet, vk1_0::Result> {
+        let handles = unsafe {
+            self.inner.logical.allocate_descriptor_sets(
+                &vk1_0::DescriptorSetAllocateInfoBuilder::new()
+                    .descriptor_pool(pool)
+                    .set_layouts(&[layout]),
+            )
+        }
+        .result()?;
+
+        debug_assert_eq!(handles.len(), 1);
+        Ok(handles[0])
+    }
+
+    /// Sets sizing of descriptor pools created afterwards.
+    pub fn set_descriptor_pool_config(&self, config: DescriptorPoolConfig) {
+        self.inner.descriptor_allocator.lock().config = config;
+    }
+
     #[tracing::instrument]
     pub fn update_descriptor_sets<'a>(
         &self,
@@ -2456,6 +3209,137 @@ impl Device {
         Ok(Sampler::new(info, self.downgrade(), handle, index))
     }
 
+    #[tracing::instrument]
+    pub fn create_query_pool(
+        &self,
+        info: QueryPoolInfo,
+    ) -> Result<QueryPool, OutOfMemory> {
+        let handle = unsafe {
+            self.inner.logical.create_query_pool(
+                &vk1_0::QueryPoolCreateInfoBuilder::new()
+                    .query_type(vk1_0::QueryType::TIMESTAMP)
+                    .query_count(info.count),
+                None,
+                None,
+            )
+        }
+        .result()
+        .map_err(oom_error_from_erupt)?;
+
+        let index = self.inner.query_pools.lock().insert(handle);
+
+        tracing::debug!("QueryPool created {:p}", handle);
+        Ok(QueryPool::new(info, self.downgrade(), handle, index))
+    }
+
+    /// Reads timestamps written into query pool.
+    /// Returns `false` without waiting if some queries are not available yet.
+    #[tracing::instrument]
+    pub fn get_timestamps(
+        &self,
+        pool: &QueryPool,
+        first: u32,
+        timestamps: &mut [u64],
+    ) -> Result<bool, WaitError> {
+        assert_owner!(pool, self);
+        assert!(
+            arith_le(first as usize + timestamps.len(), pool.info().count),
+            "Query range is out of pool bounds"
+        );
+
+        let result = unsafe {
+            self.inner.logical.get_query_pool_results(
+                pool.handle(),
+                first,
+                timestamps.len() as u32,
+                size_of_val(timestamps),
+                timestamps.as_mut_ptr() as _,
+                8,
+                Some(vk1_0::QueryResultFlags::_64),
+            )
+        };
+
+        match result.raw {
+            vk1_0::Result::SUCCESS => Ok(true),
+            vk1_0::Result::NOT_READY => Ok(false),
+            _ => Err(wait_error_from_erupt(result.raw)),
+        }
+    }
+
+    /// Returns number of nanoseconds per timestamp tick.
+    pub fn timestamp_period(&self) -> f32 {
+        self.inner.properties.v10.limits.timestamp_period
+    }
+
+    /// Returns operations supported for format by device.
+    pub fn format_properties(&self, format: Format) -> FormatProperties {
+        format_properties(self.inner.physical, format)
+    }
+
+    /// Returns limits of images with specified format and usage.
+    /// Only dimensionality of `extent` is considered.
+    /// Returns `Ok(None)` if combination is not supported.
+    pub fn image_format_properties(
+        &self,
+        format: Format,
+        extent: ImageExtent,
+        usage: ImageUsage,
+    ) -> Result<Option<ImageFormatProperties>, OutOfMemory> {
+        image_format_properties(self.inner.physical, format, extent, usage)
+    }
+
+    /// Returns image usage supported with optimal tiling for the format.
+    pub fn image_format_usage(&self, format: Format) -> ImageUsage {
+        let features = self.format_properties(format).optimal_tiling;
+        let mut usage = ImageUsage::TRANSIENT;
+
+        if self.inner.version >= vk1_0::make_version(1, 1, 0) {
+            if features.contains(FormatFeatures::TRANSFER_SRC) {
+                usage |= ImageUsage::TRANSFER_SRC;
+            }
+            if features.contains(FormatFeatures::TRANSFER_DST) {
+                usage |= ImageUsage::TRANSFER_DST;
+            }
+        } else if !features.is_empty() {
+            // Transfer features are implied before 1.1.
+            usage |= ImageUsage::TRANSFER_SRC | ImageUsage::TRANSFER_DST;
+        }
+
+        if features.contains(FormatFeatures::SAMPLED_IMAGE) {
+            usage |= ImageUsage::SAMPLED;
+        }
+        if features.contains(FormatFeatures::STORAGE_IMAGE) {
+            usage |= ImageUsage::STORAGE;
+        }
+        if features.contains(FormatFeatures::COLOR_ATTACHMENT) {
+            usage |= ImageUsage::COLOR_ATTACHMENT;
+        }
+        if features.contains(FormatFeatures::DEPTH_STENCIL_ATTACHMENT) {
+            usage |= ImageUsage::DEPTH_STENCIL_ATTACHMENT;
+        }
+        if usage.is_render_target() {
+            usage |=
+                ImageUsage::TRANSIENT_ATTACHMENT | ImageUsage::INPUT_ATTACHMENT;
+        }
+
+        usage
+    }
+
+    /// Returns maximum size of image dimension for the extent kind.
+    pub fn max_image_dimension(&self, extent: ImageExtent) -> ImageSize {
+        let limits = &self.inner.properties.v10.limits;
+        match extent {
+            ImageExtent::D1 { .. } => limits.max_image_dimension1_d,
+            ImageExtent::D2 { .. } => limits.max_image_dimension2_d,
+            ImageExtent::D3 { .. } => limits.max_image_dimension3_d,
+        }
+    }
+
+    /// Returns maximum number of image array layers.
+    pub fn max_image_layers(&self) -> u32 {
+        self.inner.properties.v10.limits.max_image_array_layers
+    }
+
     #[tracing::instrument]
     pub fn create_shader_binding_table(
         &self,
@@ -2463,72 +3347,83 @@ impl Device {
         info: ShaderBindingTableInfo,
     ) -> Result<ShaderBindingTable, OutOfMemory> {
         assert_owner!(pipeline, self);
+        assert!(
+            !pipeline.info().library,
+            "Shader binding table can't be created for pipeline library"
+        );
 
-        let group_size =
-            u64::from(self.inner.properties.rt.shader_group_handle_size);
-        let group_align =
-            u64::from(self.inner.properties.rt.shader_group_base_alignment - 1);
-
-        let group_count_usize = info.raygen.is_some() as usize
-            + info.miss.len()
-            + info.hit.len()
-            + info.callable.len();
-
-        let group_count =
-            u32::try_from(group_count_usize).map_err(|_| OutOfMemory)?;
+        let rt = &self.inner.properties.rt;
+        let group_size = u64::from(rt.shader_group_handle_size);
+        let handle_align = u64::from(rt.shader_group_handle_alignment - 1);
+        let group_align = u64::from(rt.shader_group_base_alignment - 1);
 
+        // Handles are tightly packed with stride aligned to handle alignment.
+        // Each region starts at base alignment.
         let group_stride =
-            align_up(group_align, group_size).ok_or(OutOfMemory)?;
+            align_up(handle_align, group_size).ok_or(OutOfMemory)?;
 
-        let group_stride_usize =
-            usize::try_from(group_stride).map_err(|_| OutOfMemory)?;
+        assert!(
+            group_stride <= u64::from(rt.max_shader_group_stride),
+            "Shader group stride exceeds device limit"
+        );
 
-        let total_size = (group_stride.checked_mul(u64::from(group_count)))
-            .ok_or(OutOfMemory)?;
+        let mut total_size = 0u64;
+        let mut region = |count: usize| -> Result<Range<u64>, OutOfMemory> {
+            let start = align_up(group_align, total_size).ok_or(OutOfMemory)?;
+            let size = u64::try_from(count)
+                .ok()
+                .and_then(|count| group_stride.checked_mul(count))
+                .ok_or(OutOfMemory)?;
+            total_size = start.checked_add(size).ok_or(OutOfMemory)?;
+            Ok(start..total_size)
+        };
+
+        let raygen_region = region(info.raygen.is_some() as usize)?;
+        let miss_region = region(info.miss.len())?;
+        let hit_region = region(info.hit.len())?;
+        let callable_region = region(info.callable.len())?;
 
         let total_size_usize = usize::try_from(total_size)
             .unwrap_or_else(|_| out_of_host_memory());
 
         let mut bytes = vec![0; total_size_usize];
 
-        let mut write_offset = 0;
-
         let group_handlers = pipeline.group_handlers();
 
         let raygen_handlers = copy_group_handlers(
             group_handlers,
             &mut bytes,
             info.raygen.iter().copied(),
-            &mut write_offset,
+            raygen_region,
             group_size,
-            group_stride_usize,
+            group_stride,
         );
 
         let miss_handlers = copy_group_handlers(
             group_handlers,
             &mut bytes,
             info.miss.iter().copied(),
-            &mut write_offset,
+            miss_region,
             group_size,
-            group_stride_usize,
+            group_stride,
         );
 
         let hit_handlers = copy_group_handlers(
             group_handlers,
             &mut bytes,
             info.hit.iter().copied(),
-            &mut write_offset,
+            hit_region,
             group_size,
-            group_stride_usize,
+            group_stride,
         );
 
         let callable_handlers = copy_group_handlers(
             group_handlers,
             &mut bytes,
             info.callable.iter().copied(),
-            &mut write_offset,
+            callable_region,
             group_size,
-            group_stride_usize,
+            group_stride,
         );
 
         let buffer = self.create_buffer_static(
@@ -2537,6 +3432,7 @@ impl Device {
                 size: total_size,
                 usage: BufferUsage::SHADER_BINDING_TABLE
                     | BufferUsage::DEVICE_ADDRESS,
+                capture_address: None,
             },
             &bytes,
         )?;
@@ -2626,6 +3522,35 @@ impl Device {
         }
         .map_err(Into::into)
     }
+
+    /// Reads buffer content written by the device.
+    /// Caller must ensure that device writes are finished and made
+    /// available to the host.
+    #[tracing::instrument(skip(data))]
+    pub fn read_buffer<T>(
+        &self,
+        buffer: &mut MappableBuffer,
+        offset: u64,
+        data: &mut [T],
+    ) -> Result<(), MapError>
+    where
+        T: Pod,
+    {
+        assert_owner!(buffer, self);
+
+        if size_of_val(data) == 0 {
+            return Ok(());
+        }
+
+        unsafe {
+            buffer.memory_block().read_bytes(
+                EruptMemoryDevice::wrap(&self.inner.logical),
+                offset,
+                bytemuck::cast_slice_mut(data),
+            )
+        }
+        .map_err(Into::into)
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -2649,6 +3574,78 @@ pub enum CreateRenderPassError {
         "Subpass {subpass} attachment index {attachment} for depth attachment is out of bounds"
     )]
     DepthAttachmentReferenceOutOfBound { subpass: usize, attachment: usize },
+
+    #[error(
+        "Subpass {subpass} attachment index {attachment} for resolve attachment {index} is out of bounds"
+    )]
+    ResolveAttachmentReferenceOutOfBound {
+        subpass: usize,
+        index: usize,
+        attachment: usize,
+    },
+
+    #[error(
+        "Subpass {subpass} has {resolves} resolve attachments for {colors} color attachments"
+    )]
+    ResolveAttachmentCountMismatch {
+        subpass: usize,
+        colors: usize,
+        resolves: usize,
+    },
+
+    #[error(
+        "Subpass {subpass} color attachment {index} must be multisampled and resolved into single-sampled attachment"
+    )]
+    ResolveAttachmentSamplesMismatch { subpass: usize, index: usize },
+}
+
+/// Checks that resolve attachments of the subpass match its color attachments.
+fn check_subpass_resolves(
+    info: &RenderPassInfo,
+    subpass: usize,
+) -> Result<(), CreateRenderPassError> {
+    let s = &info.subpasses[subpass];
+
+    if s.resolves.is_empty() {
+        return Ok(());
+    }
+
+    if s.resolves.len() != s.colors.len() {
+        return Err(CreateRenderPassError::ResolveAttachmentCountMismatch {
+            subpass,
+            colors: s.colors.len(),
+            resolves: s.resolves.len(),
+        });
+    }
+
+    for (index, (&color, &resolve)) in
+        s.colors.iter().zip(&s.resolves).enumerate()
+    {
+        let resolve_info = info.attachments.get(resolve).ok_or(
+            CreateRenderPassError::ResolveAttachmentReferenceOutOfBound {
+                subpass,
+                index,
+                attachment: resolve,
+            },
+        )?;
+
+        // Color attachment indices are checked separately.
+        let multisampled = info
+            .attachments
+            .get(color)
+            .map_or(false, |color| color.samples != Samples::Samples1);
+
+        if !multisampled || resolve_info.samples != Samples::Samples1 {
+            return Err(
+                CreateRenderPassError::ResolveAttachmentSamplesMismatch {
+                    subpass,
+                    index,
+                },
+            );
+        }
+    }
+
+    Ok(())
 }
 
 #[allow(dead_code)]
@@ -2661,16 +3658,23 @@ fn entry_name_to_cstr(name: &str) -> CString {
         .expect("Shader names should not contain zero bytes")
 }
 
+/// Copies handles of groups into specified region of shader binding table.
+/// Returns `None` if region is empty.
 fn copy_group_handlers(
     group_handlers: &[u8],
     write: &mut [u8],
     group_indices: impl IntoIterator<Item = u32>,
-    write_offset: &mut usize,
+    region: Range<u64>,
     group_size: u64,
-    group_stride: usize,
+    group_stride: u64,
 ) -> Option<Range<u64>> {
-    let result_start = u64::try_from(*write_offset).ok()?;
+    if region.start == region.end {
+        return None;
+    }
+
     let group_size_usize = usize::try_from(group_size).ok()?;
+    let group_stride_usize = usize::try_from(group_stride).ok()?;
+    let mut write_offset = usize::try_from(region.start).ok()?;
 
     for group_index in group_indices {
         let group_offset =
@@ -2680,17 +3684,16 @@ fn copy_group_handlers(
         let write_end = write_offset.checked_add(group_size_usize)?;
 
         let group_range = group_offset..group_end;
-        let write_range = *write_offset..write_end;
+        let write_range = write_offset..write_end;
 
         let handler = &group_handlers[group_range];
         let output = &mut write[write_range];
 
         output.copy_from_slice(handler);
-        *write_offset = write_offset.checked_add(group_stride)?;
+        write_offset = write_offset.checked_add(group_stride_usize)?;
     }
 
-    let result_end = u64::try_from(*write_offset).ok()?;
-    Some(result_start..result_end)
+    Some(region)
 }
 
 pub(crate) fn create_render_pass_error_from_erupt(
@@ -2742,3 +3745,84 @@ fn memory_device_properties(
             || device.enabled().ext_buffer_device_address,
     }
 }
+
+/// Memory device that allocates memory objects
+/// at opaque address recorded by capture tool.
+struct CaptureReplayMemoryDevice<'a> {
+    device: &'a EruptMemoryDevice,
+    logical: &'a DeviceLoader,
+    address: u64,
+}
+
+impl gpu_alloc::MemoryDevice<vk1_0::DeviceMemory>
+    for CaptureReplayMemoryDevice<'_>
+{
+    unsafe fn allocate_memory(
+        &self,
+        size: u64,
+        memory_type: u32,
+        _flags: gpu_alloc::AllocationFlags,
+    ) -> Result<vk1_0::DeviceMemory, gpu_alloc::OutOfMemory> {
+        let mut flags = vk1_1::MemoryAllocateFlagsInfoBuilder::new().flags(
+            vk1_1::MemoryAllocateFlags::DEVICE_ADDRESS
+                | vk1_1::MemoryAllocateFlags::DEVICE_ADDRESS_CAPTURE_REPLAY,
+        );
+
+        let mut address =
+            vk1_2::MemoryOpaqueCaptureAddressAllocateInfoBuilder::new()
+                .opaque_capture_address(self.address);
+
+        let info = vk1_0::MemoryAllocateInfoBuilder::new()
+            .allocation_size(size)
+            .memory_type_index(memory_type)
+            .extend_from(&mut flags)
+            .extend_from(&mut address);
+
+        match self.logical.allocate_memory(&info, None, None).result() {
+            Ok(memory) => Ok(memory),
+            Err(vk1_0::Result::ERROR_OUT_OF_HOST_MEMORY) => {
+                Err(gpu_alloc::OutOfMemory::OutOfHostMemory)
+            }
+            Err(err) => {
+                // Address may be taken or not recorded by this device.
+                tracing::error!(
+                    "Failed to allocate memory at capture address {:#x}: {}",
+                    self.address,
+                    err
+                );
+                Err(gpu_alloc::OutOfMemory::OutOfDeviceMemory)
+            }
+        }
+    }
+
+    unsafe fn deallocate_memory(&self, memory: vk1_0::DeviceMemory) {
+        self.device.deallocate_memory(memory)
+    }
+
+    unsafe fn map_memory(
+        &self,
+        memory: &mut vk1_0::DeviceMemory,
+        offset: u64,
+        size: u64,
+    ) -> Result<std::ptr::NonNull<u8>, gpu_alloc::DeviceMapError> {
+        self.device.map_memory(memory, offset, size)
+    }
+
+    unsafe fn unmap_memory(&self, memory: &mut vk1_0::DeviceMemory) {
+        self.device.unmap_memory(memory)
+    }
+
+    unsafe fn invalidate_memory_ranges(
+        &self,
+        ranges: &[gpu_alloc::MappedMemoryRange<'_, vk1_0::DeviceMemory>],
+    ) -> Result<(), gpu_alloc::OutOfMemory> {
+        self.device.invalidate_memory_ranges(ranges)
+    }
+
+    unsafe fn flush_memory_ranges(
+        &self,
+        ranges: &[gpu_alloc::MappedMemoryRange<'_, vk1_0::DeviceMemory>],
+    ) -> Result<(), gpu_alloc::OutOfMemory> {
+        self.device.flush_memory_ranges(ranges)
+    }
+}
@@ -2,7 +2,7 @@ use crate::AttachmentInfo;
 
 use {
     super::{
-        access::supported_access,
+        access::{dst_access, src_access, sync2_access},
         convert::{oom_error_from_erupt, ToErupt},
         device::{Device, WeakDevice},
     },
@@ -15,6 +15,7 @@ use {
         encode::*,
         format::{FormatDescription, FormatType, Repr},
         queue::QueueId,
+        record::RecordedCommand,
         render_pass::{
             AttachmentLoadOp, ClearValue, RENDERPASS_SMALLVEC_ATTACHMENTS,
         },
@@ -23,7 +24,7 @@ use {
     erupt::{
         extensions::{
             khr_acceleration_structure as vkacc,
-            khr_ray_tracing_pipeline as vkrt,
+            khr_ray_tracing_pipeline as vkrt, khr_synchronization2 as vks2,
         },
         vk1_0,
     },
@@ -31,6 +32,8 @@ use {
     std::{
         convert::TryFrom as _,
         fmt::{self, Debug},
+        ops::Range,
+        sync::Arc,
     },
 };
 
@@ -39,6 +42,11 @@ pub struct CommandBuffer {
     queue: QueueId,
     owner: WeakDevice,
     recording: bool,
+    reusable: bool,
+
+    /// Whether commands are captured for `CommandRecorder`.
+    capture: bool,
+    captured: Option<Arc<[RecordedCommand]>>,
 }
 
 impl Debug for CommandBuffer {
@@ -60,12 +68,17 @@ impl CommandBuffer {
         handle: vk1_0::CommandBuffer,
         queue: QueueId,
         owner: WeakDevice,
+        reusable: bool,
+        capture: bool,
     ) -> Self {
         CommandBuffer {
             handle,
             queue,
             owner,
             recording: false,
+            reusable,
+            capture,
+            captured: None,
         }
     }
 
@@ -88,6 +101,26 @@ impl CommandBuffer {
         self.queue
     }
 
+    /// Returns `true` if command buffer can be submitted multiple times.
+    /// Transient command buffers are recycled with their frame
+    /// and must be submitted only once.
+    pub fn is_reusable(&self) -> bool {
+        self.reusable
+    }
+
+    pub(crate) fn is_capturing(&self) -> bool {
+        self.capture
+    }
+
+    /// Stores commands captured from encoder.
+    pub(crate) fn set_captured(&mut self, commands: Arc<[RecordedCommand]>) {
+        self.captured = Some(commands);
+    }
+
+    pub(super) fn captured(&self) -> Option<&Arc<[RecordedCommand]>> {
+        self.captured.as_ref()
+    }
+
     pub fn write(
         &mut self,
         commands: &[Command<'_>],
@@ -98,11 +131,16 @@ impl CommandBuffer {
         };
 
         if !self.recording {
+            let flags = if self.reusable {
+                vk1_0::CommandBufferUsageFlags::empty()
+            } else {
+                vk1_0::CommandBufferUsageFlags::ONE_TIME_SUBMIT
+            };
+
             unsafe {
                 device.logical().begin_command_buffer(
                     self.handle,
-                    &vk1_0::CommandBufferBeginInfoBuilder::new()
-                        .flags(vk1_0::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+                    &vk1_0::CommandBufferBeginInfoBuilder::new().flags(flags),
                 )
             }
             .result()
@@ -112,6 +150,7 @@ impl CommandBuffer {
         }
 
         let logical = &device.logical();
+        let synchronization2 = logical.enabled().khr_synchronization2;
 
         for command in commands {
             match *command {
@@ -182,6 +221,12 @@ impl CommandBuffer {
                         )
                     }
                 }
+                Command::NextSubpass => unsafe {
+                    logical.cmd_next_subpass(
+                        self.handle,
+                        vk1_0::SubpassContents::INLINE,
+                    )
+                },
                 Command::EndRenderPass => unsafe {
                     logical.cmd_end_render_pass(self.handle)
                 },
@@ -247,6 +292,84 @@ impl CommandBuffer {
                         &[scissor.to_erupt().into_builder()],
                     );
                 },
+                Command::SetViewports { first, viewports } => unsafe {
+                    let viewports = viewports
+                        .iter()
+                        .map(|viewport| viewport.to_erupt().into_builder())
+                        .collect::<SmallVec<[_; 16]>>();
+
+                    logical.cmd_set_viewport(self.handle, first, &viewports);
+                },
+                Command::SetScissors { first, scissors } => unsafe {
+                    let scissors = scissors
+                        .iter()
+                        .map(|scissor| scissor.to_erupt().into_builder())
+                        .collect::<SmallVec<[_; 16]>>();
+
+                    logical.cmd_set_scissor(self.handle, first, &scissors);
+                },
+                Command::SetCullMode { culling } => unsafe {
+                    assert!(
+                        device.logical().enabled().ext_extended_dynamic_state,
+                        "`ExtendedDynamicState` feature is not enabled"
+                    );
+                    logical.cmd_set_cull_mode_ext(
+                        self.handle,
+                        Some(culling.to_erupt()),
+                    );
+                },
+                Command::SetFrontFace { front_face } => unsafe {
+                    assert!(
+                        device.logical().enabled().ext_extended_dynamic_state,
+                        "`ExtendedDynamicState` feature is not enabled"
+                    );
+                    logical.cmd_set_front_face_ext(
+                        self.handle,
+                        front_face.to_erupt(),
+                    );
+                },
+                Command::SetPrimitiveTopology { topology } => unsafe {
+                    assert!(
+                        device.logical().enabled().ext_extended_dynamic_state,
+                        "`ExtendedDynamicState` feature is not enabled"
+                    );
+                    logical.cmd_set_primitive_topology_ext(
+                        self.handle,
+                        topology.to_erupt(),
+                    );
+                },
+                Command::SetDepthTest { depth_test } => unsafe {
+                    assert!(
+                        device.logical().enabled().ext_extended_dynamic_state,
+                        "`ExtendedDynamicState` feature is not enabled"
+                    );
+                    match depth_test {
+                        Some(depth_test) => {
+                            logical.cmd_set_depth_test_enable_ext(
+                                self.handle,
+                                true,
+                            );
+                            logical.cmd_set_depth_write_enable_ext(
+                                self.handle,
+                                depth_test.write,
+                            );
+                            logical.cmd_set_depth_compare_op_ext(
+                                self.handle,
+                                depth_test.compare.to_erupt(),
+                            );
+                        }
+                        None => {
+                            logical.cmd_set_depth_test_enable_ext(
+                                self.handle,
+                                false,
+                            );
+                            logical.cmd_set_depth_write_enable_ext(
+                                self.handle,
+                                false,
+                            );
+                        }
+                    }
+                },
                 Command::UpdateBuffer {
                     buffer,
                     offset,
@@ -658,6 +781,82 @@ impl CommandBuffer {
                             .collect::<SmallVec<[_; 4]>>(),
                     );
                 },
+                Command::FillBuffer {
+                    buffer,
+                    offset,
+                    size,
+                    value,
+                } => unsafe {
+                    assert_eq!(offset % 4, 0);
+                    assert!(size == vk1_0::WHOLE_SIZE || size % 4 == 0);
+                    assert_owner!(buffer, device);
+
+                    logical.cmd_fill_buffer(
+                        self.handle,
+                        buffer.handle(),
+                        offset,
+                        size,
+                        value,
+                    );
+                },
+
+                Command::ClearColorImage {
+                    image,
+                    layout,
+                    color: [r, g, b, a],
+                    ranges,
+                } => unsafe {
+                    assert_owner!(image, device);
+
+                    use FormatDescription::*;
+                    let color = match image.info().format.description() {
+                        R(repr) | RG(repr) | RGB(repr) | RGBA(repr)
+                        | BGR(repr) | BGRA(repr) => {
+                            colors_f32_to_value(r, g, b, a, repr)
+                        }
+                        _ => panic!(
+                            "Attempt to clear depth-stencil image with color \
+                             value"
+                        ),
+                    };
+
+                    logical.cmd_clear_color_image(
+                        self.handle,
+                        image.handle(),
+                        layout.to_erupt(),
+                        &color,
+                        &ranges
+                            .iter()
+                            .map(|range| range.to_erupt().into_builder())
+                            .collect::<SmallVec<[_; 4]>>(),
+                    );
+                },
+
+                Command::ClearDepthStencilImage {
+                    image,
+                    layout,
+                    depth,
+                    stencil,
+                    ranges,
+                } => unsafe {
+                    assert_owner!(image, device);
+                    assert!(
+                        image.info().format.is_depth()
+                            || image.info().format.is_stencil()
+                    );
+
+                    logical.cmd_clear_depth_stencil_image(
+                        self.handle,
+                        image.handle(),
+                        layout.to_erupt(),
+                        &vk1_0::ClearDepthStencilValue { depth, stencil },
+                        &ranges
+                            .iter()
+                            .map(|range| range.to_erupt().into_builder())
+                            .collect::<SmallVec<[_; 4]>>(),
+                    );
+                },
+
                 Command::CopyBufferImage {
                     src_buffer,
                     dst_image,
@@ -704,57 +903,177 @@ impl CommandBuffer {
                     );
                 },
 
+                Command::ResolveImage {
+                    src_image,
+                    src_layout,
+                    dst_image,
+                    dst_layout,
+                    regions,
+                } => unsafe {
+                    assert_owner!(src_image, device);
+                    assert_owner!(dst_image, device);
+
+                    logical.cmd_resolve_image(
+                        self.handle,
+                        src_image.handle(),
+                        src_layout.to_erupt(),
+                        dst_image.handle(),
+                        dst_layout.to_erupt(),
+                        &regions
+                            .iter()
+                            .map(|region| region.to_erupt().into_builder())
+                            .collect::<SmallVec<[_; 4]>>(),
+                    );
+                },
+
                 Command::PipelineBarrier { src, dst, images } => unsafe {
                     for barrier in images {
                         assert_owner!(barrier.image, device);
                     }
 
-                    logical.cmd_pipeline_barrier(
-                        self.handle,
-                        src.to_erupt(),
-                        dst.to_erupt(),
-                        None,
-                        &[vk1_0::MemoryBarrierBuilder::new()
-                            .src_access_mask(supported_access(src.to_erupt()))
-                            .dst_access_mask(supported_access(dst.to_erupt()))],
-                        &[],
-                        &images
-                            .iter()
-                            .map(|image| {
-                                vk1_0::ImageMemoryBarrierBuilder::new()
-                                    .image(image.image.handle())
-                                    .src_access_mask(supported_access(
-                                        src.to_erupt(),
-                                    ))
-                                    .dst_access_mask(supported_access(
-                                        dst.to_erupt(),
-                                    ))
-                                    .old_layout(image.old_layout.to_erupt())
-                                    .new_layout(image.new_layout.to_erupt())
-                                    .src_queue_family_index(
-                                        image
-                                            .family_transfer
-                                            .as_ref()
-                                            .map(|r| r.start)
-                                            .unwrap_or(
-                                                vk1_0::QUEUE_FAMILY_IGNORED,
-                                            ),
-                                    )
-                                    .dst_queue_family_index(
-                                        image
-                                            .family_transfer
-                                            .as_ref()
-                                            .map(|r| r.end)
-                                            .unwrap_or(
-                                                vk1_0::QUEUE_FAMILY_IGNORED,
-                                            ),
-                                    )
-                                    .subresource_range(
-                                        image.subresource.to_erupt(),
-                                    )
-                            })
-                            .collect::<SmallVec<[_; 8]>>(),
-                    )
+                    let src_access = src_access(src);
+                    let dst_access = dst_access(dst);
+
+                    if synchronization2 {
+                        let src_stages: vks2::PipelineStageFlags2KHR = src.to_erupt();
+                        let dst_stages: vks2::PipelineStageFlags2KHR = dst.to_erupt();
+
+                        logical.cmd_pipeline_barrier2_khr(
+                            self.handle,
+                            &vks2::DependencyInfoKHRBuilder::new()
+                                .memory_barriers(&[
+                                    vks2::MemoryBarrier2KHRBuilder::new()
+                                        .src_stage_mask(src_stages)
+                                        .src_access_mask(sync2_access(
+                                            src_access,
+                                        ))
+                                        .dst_stage_mask(dst_stages)
+                                        .dst_access_mask(sync2_access(
+                                            dst_access,
+                                        )),
+                                ])
+                                .image_memory_barriers(
+                                    &images
+                                        .iter()
+                                        .map(|image| {
+                                            let (src_family, dst_family) =
+                                                queue_families(
+                                                    &image.family_transfer,
+                                                );
+
+                                            vks2::ImageMemoryBarrier2KHRBuilder::new()
+                                                .image(image.image.handle())
+                                                .src_stage_mask(src_stages)
+                                                .src_access_mask(sync2_access(src_access))
+                                                .dst_stage_mask(dst_stages)
+                                                .dst_access_mask(sync2_access(dst_access))
+                                                .old_layout(image.old_layout.to_erupt())
+                                                .new_layout(image.new_layout.to_erupt())
+                                                .src_queue_family_index(src_family)
+                                                .dst_queue_family_index(dst_family)
+                                                .subresource_range(
+                                                    image.subresource.to_erupt(),
+                                                )
+                                        })
+                                        .collect::<SmallVec<[_; 8]>>(),
+                                ),
+                        )
+                    } else {
+                        logical.cmd_pipeline_barrier(
+                            self.handle,
+                            src.to_erupt(),
+                            dst.to_erupt(),
+                            None,
+                            &[vk1_0::MemoryBarrierBuilder::new()
+                                .src_access_mask(src_access)
+                                .dst_access_mask(dst_access)],
+                            &[],
+                            &images
+                                .iter()
+                                .map(|image| {
+                                    let (src_family, dst_family) =
+                                        queue_families(&image.family_transfer);
+
+                                    vk1_0::ImageMemoryBarrierBuilder::new()
+                                        .image(image.image.handle())
+                                        .src_access_mask(src_access)
+                                        .dst_access_mask(dst_access)
+                                        .old_layout(image.old_layout.to_erupt())
+                                        .new_layout(image.new_layout.to_erupt())
+                                        .src_queue_family_index(src_family)
+                                        .dst_queue_family_index(dst_family)
+                                        .subresource_range(
+                                            image.subresource.to_erupt(),
+                                        )
+                                })
+                                .collect::<SmallVec<[_; 8]>>(),
+                        )
+                    }
+                },
+                Command::BufferBarriers { src, dst, buffers } => unsafe {
+                    for barrier in buffers {
+                        assert_owner!(barrier.buffer, device);
+                    }
+
+                    let src_access = src_access(src);
+                    let dst_access = dst_access(dst);
+
+                    if synchronization2 {
+                        let src_stages: vks2::PipelineStageFlags2KHR = src.to_erupt();
+                        let dst_stages: vks2::PipelineStageFlags2KHR = dst.to_erupt();
+
+                        logical.cmd_pipeline_barrier2_khr(
+                            self.handle,
+                            &vks2::DependencyInfoKHRBuilder::new()
+                                .buffer_memory_barriers(
+                                    &buffers
+                                        .iter()
+                                        .map(|buffer| {
+                                            let (src_family, dst_family) =
+                                                queue_families(
+                                                    &buffer.family_transfer,
+                                                );
+
+                                            vks2::BufferMemoryBarrier2KHRBuilder::new()
+                                                .buffer(buffer.buffer.handle())
+                                                .offset(buffer.offset)
+                                                .size(buffer.size)
+                                                .src_stage_mask(src_stages)
+                                                .src_access_mask(sync2_access(src_access))
+                                                .dst_stage_mask(dst_stages)
+                                                .dst_access_mask(sync2_access(dst_access))
+                                                .src_queue_family_index(src_family)
+                                                .dst_queue_family_index(dst_family)
+                                        })
+                                        .collect::<SmallVec<[_; 8]>>(),
+                                ),
+                        )
+                    } else {
+                        logical.cmd_pipeline_barrier(
+                            self.handle,
+                            src.to_erupt(),
+                            dst.to_erupt(),
+                            None,
+                            &[],
+                            &buffers
+                                .iter()
+                                .map(|buffer| {
+                                    let (src_family, dst_family) =
+                                        queue_families(&buffer.family_transfer);
+
+                                    vk1_0::BufferMemoryBarrierBuilder::new()
+                                        .buffer(buffer.buffer.handle())
+                                        .offset(buffer.offset)
+                                        .size(buffer.size)
+                                        .src_access_mask(src_access)
+                                        .dst_access_mask(dst_access)
+                                        .src_queue_family_index(src_family)
+                                        .dst_queue_family_index(dst_family)
+                                })
+                                .collect::<SmallVec<[_; 8]>>(),
+                            &[],
+                        )
+                    }
                 },
                 Command::PushConstants {
                     layout,
@@ -776,6 +1095,25 @@ impl CommandBuffer {
                 Command::Dispatch { x, y, z } => unsafe {
                     logical.cmd_dispatch(self.handle, x, y, z)
                 },
+                Command::ResetQueryPool { pool, ref queries } => unsafe {
+                    assert_owner!(pool, device);
+                    logical.cmd_reset_query_pool(
+                        self.handle,
+                        pool.handle(),
+                        queries.start,
+                        queries.end - queries.start,
+                    )
+                },
+                Command::WriteTimestamp { pool, query, stage } => unsafe {
+                    assert_owner!(pool, device);
+                    let stage: vk1_0::PipelineStageFlags = stage.to_erupt();
+                    logical.cmd_write_timestamp(
+                        self.handle,
+                        vk1_0::PipelineStageFlagBits(stage.bits()),
+                        pool.handle(),
+                        query,
+                    )
+                },
             }
         }
 
@@ -928,3 +1266,11 @@ fn colors_f32_to_value(
         },
     }
 }
+
+/// Returns source and destination queue family indices of barrier.
+fn queue_families(family_transfer: &Option<Range<u32>>) -> (u32, u32) {
+    match family_transfer {
+        Some(transfer) => (transfer.start, transfer.end),
+        None => (vk1_0::QUEUE_FAMILY_IGNORED, vk1_0::QUEUE_FAMILY_IGNORED),
+    }
+}
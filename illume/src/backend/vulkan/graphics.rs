@@ -4,7 +4,8 @@ use {
         out_of_host_memory,
         physical::EnumerateDeviceError,
         surface::{
-            CreateSurfaceError, RawWindowHandleKind, Surface, SurfaceInfo,
+            CreateSurfaceError, RawWindowHandleKind, Surface, SurfaceError,
+            SurfaceInfo,
         },
         OutOfMemory,
     },
@@ -482,6 +483,29 @@ impl Graphics {
             SurfaceInfo { window },
         ))
     }
+
+    /// Destroys surface.
+    ///
+    /// Fails with `SurfaceError::AlreadyUsed` if surface is used
+    /// by a swapchain or other clones of it exist.
+    /// Surface is destroyed with the last clone passed here then.
+    pub fn destroy_surface(
+        &self,
+        surface: Surface,
+    ) -> Result<(), SurfaceError> {
+        let handle = surface.handle();
+
+        if !surface.into_unique() {
+            return Err(SurfaceError::AlreadyUsed);
+        }
+
+        unsafe {
+            // No swapchain is using the surface.
+            self.instance.destroy_surface_khr(Some(handle), None);
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
@@ -0,0 +1,147 @@
+//! Detection of device handles leaked every frame.
+//!
+//! Debug builds count live handles of few kinds and remember where
+//! they were created. If count of some kind grows in every frame
+//! of the window a warning with the growth and creation sites is emitted.
+
+#![cfg_attr(not(debug_assertions), allow(dead_code))]
+
+use std::{
+    collections::{HashMap, VecDeque},
+    panic::Location,
+};
+
+/// Number of frames live handle counts must grow for to be reported.
+const LEAK_CHECK_FRAMES: usize = 60;
+
+/// Maximum number of creation sites listed in a warning.
+const LEAK_REPORT_SITES: usize = 4;
+
+/// Kind of device handle counted by leak detector.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(super) enum HandleKind {
+    Buffer,
+    Image,
+    ImageView,
+    DescriptorSet,
+}
+
+impl HandleKind {
+    const ALL: [HandleKind; 4] = [
+        HandleKind::Buffer,
+        HandleKind::Image,
+        HandleKind::ImageView,
+        HandleKind::DescriptorSet,
+    ];
+}
+
+type Site = (HandleKind, &'static Location<'static>);
+
+struct FrameHandles {
+    live: [usize; 4],
+    created: HashMap<Site, usize>,
+}
+
+pub(super) struct LeakDetector {
+    live: [usize; 4],
+    created: HashMap<Site, usize>,
+    history: VecDeque<FrameHandles>,
+}
+
+impl LeakDetector {
+    pub fn new() -> Self {
+        LeakDetector {
+            live: [0; 4],
+            created: HashMap::new(),
+            history: VecDeque::with_capacity(LEAK_CHECK_FRAMES),
+        }
+    }
+
+    pub fn created(
+        &mut self,
+        kind: HandleKind,
+        site: &'static Location<'static>,
+    ) {
+        self.live[kind as usize] += 1;
+        *self.created.entry((kind, site)).or_default() += 1;
+    }
+
+    pub fn destroyed(&mut self, kind: HandleKind) {
+        let live = &mut self.live[kind as usize];
+        *live = live.saturating_sub(1);
+    }
+
+    /// Snapshots counts of the frame and reports kinds
+    /// that grew in every frame of the window.
+    pub fn end_frame(&mut self) {
+        if self.history.len() == LEAK_CHECK_FRAMES {
+            self.history.pop_front();
+        }
+
+        self.history.push_back(FrameHandles {
+            live: self.live,
+            created: std::mem::take(&mut self.created),
+        });
+
+        if self.history.len() < LEAK_CHECK_FRAMES {
+            return;
+        }
+
+        let mut reported = false;
+
+        for &kind in &HandleKind::ALL {
+            let index = kind as usize;
+            let growing = self
+                .history
+                .iter()
+                .zip(self.history.iter().skip(1))
+                .all(|(prev, next)| next.live[index] > prev.live[index]);
+
+            if !growing {
+                continue;
+            }
+
+            let first = self.history.front().unwrap().live[index];
+            let last = self.history.back().unwrap().live[index];
+
+            let mut sites = HashMap::new();
+            for frame in &self.history {
+                for (&(site_kind, site), &count) in &frame.created {
+                    if site_kind == kind {
+                        *sites.entry(site).or_insert(0) += count;
+                    }
+                }
+            }
+
+            let mut sites: Vec<_> = sites.into_iter().collect();
+            sites.sort_by(|lhs, rhs| rhs.1.cmp(&lhs.1));
+
+            let sites = sites
+                .iter()
+                .take(LEAK_REPORT_SITES)
+                .map(|(site, count)| format!("{} ({} times)", site, count))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            tracing::warn_span!("handle_leak", kind = ?kind).in_scope(|| {
+                tracing::warn!(
+                    "Live {:?} handles grew in each of last {} frames: \
+                     {} -> {} (+{}). Created at {}",
+                    kind,
+                    LEAK_CHECK_FRAMES,
+                    first,
+                    last,
+                    last - first,
+                    sites,
+                )
+            });
+
+            reported = true;
+        }
+
+        // Start new window to not repeat the warning every frame.
+        if reported {
+            self.history.clear();
+        }
+    }
+}
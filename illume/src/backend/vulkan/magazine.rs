@@ -0,0 +1,171 @@
+//! Per-thread caches of recently freed small memory blocks.
+//!
+//! Small buffer allocations are rounded up to power-of-two size classes.
+//! Freed blocks are kept in a magazine of the freeing thread
+//! and reused by later allocations of the same class on that thread.
+//! Overfull magazines are split and the excess is moved to shared depot,
+//! and threads with empty magazines refill them from there.
+//! This way mutexes are locked once per magazine instead of once per block,
+//! even if blocks are freed by one thread and allocated by others.
+
+use {
+    erupt::vk1_0,
+    gpu_alloc::{MemoryBlock, Request, UsageFlags},
+    parking_lot::Mutex,
+    std::{
+        cell::RefCell,
+        collections::HashMap,
+        sync::atomic::{AtomicU64, Ordering},
+    },
+    thread_local::ThreadLocal,
+};
+
+/// Largest request served from magazines.
+const SMALL_BLOCK_MAX: u64 = 256 * 1024;
+
+/// Size of smallest class.
+const SMALL_BLOCK_MIN: u64 = 256;
+
+/// Number of blocks moved between thread and depot at once.
+const MAGAZINE_SIZE: usize = 16;
+
+/// Number of magazines depot keeps for each class.
+/// Blocks beyond that are returned to allocator.
+const DEPOT_MAGAZINES: usize = 4;
+
+type Block = MemoryBlock<vk1_0::DeviceMemory>;
+
+/// Allocation requests that can share cached blocks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct BlockClass {
+    size: u64,
+    align_mask: u64,
+    memory_types: u32,
+    usage: UsageFlags,
+}
+
+impl BlockClass {
+    /// Returns class of the request.
+    /// Returns `None` if request is too large to be cached.
+    pub(super) fn of(request: &Request) -> Option<Self> {
+        if request.size > SMALL_BLOCK_MAX {
+            return None;
+        }
+
+        Some(BlockClass {
+            size: request.size.next_power_of_two().max(SMALL_BLOCK_MIN),
+            align_mask: request.align_mask,
+            memory_types: request.memory_types,
+            usage: request.usage,
+        })
+    }
+
+    /// Returns request for new block of this class.
+    pub(super) fn request(&self) -> Request {
+        Request {
+            size: self.size,
+            align_mask: self.align_mask,
+            memory_types: self.memory_types,
+            usage: self.usage,
+        }
+    }
+}
+
+pub(super) struct Magazines {
+    threads: ThreadLocal<RefCell<HashMap<BlockClass, Vec<Block>>>>,
+    depot: Mutex<HashMap<BlockClass, Vec<Vec<Block>>>>,
+
+    /// Total size of cached blocks.
+    cached: AtomicU64,
+}
+
+impl Magazines {
+    pub fn new() -> Self {
+        Magazines {
+            threads: ThreadLocal::new(),
+            depot: Mutex::new(HashMap::new()),
+            cached: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns total size of blocks cached in all threads and depot.
+    pub fn cached(&self) -> u64 {
+        self.cached.load(Ordering::Relaxed)
+    }
+
+    /// Removes blocks leaving cache from the total.
+    fn uncache(&self, blocks: &[Block]) {
+        let size = blocks.iter().map(Block::size).sum();
+        self.cached.fetch_sub(size, Ordering::Relaxed);
+    }
+
+    /// Takes cached block of the class.
+    pub fn take(&self, class: BlockClass) -> Option<Block> {
+        let mut thread = self.threads.get_or_default().borrow_mut();
+        let magazine = thread.entry(class).or_default();
+
+        if magazine.is_empty() {
+            *magazine = self.depot.lock().get_mut(&class)?.pop()?;
+        }
+
+        let block = magazine.pop()?;
+        self.cached.fetch_sub(block.size(), Ordering::Relaxed);
+        Some(block)
+    }
+
+    /// Caches freed block of the class.
+    /// Returns blocks that do not fit into cache.
+    /// They must be returned to allocator.
+    pub fn put(&self, class: BlockClass, block: Block) -> Vec<Block> {
+        let mut thread = self.threads.get_or_default().borrow_mut();
+        let magazine = thread.entry(class).or_default();
+        self.cached.fetch_add(block.size(), Ordering::Relaxed);
+        magazine.push(block);
+
+        // Keeping one magazine in thread avoids moving blocks back and forth
+        // when thread frees and allocates at the boundary.
+        if magazine.len() < 2 * MAGAZINE_SIZE {
+            return Vec::new();
+        }
+
+        let full = magazine.split_off(MAGAZINE_SIZE);
+        let mut depot = self.depot.lock();
+        let magazines = depot.entry(class).or_default();
+
+        if magazines.len() < DEPOT_MAGAZINES {
+            magazines.push(full);
+            Vec::new()
+        } else {
+            self.uncache(&full);
+            full
+        }
+    }
+
+    /// Takes all blocks from depot.
+    pub fn drain_depot(&self) -> Vec<Block> {
+        let blocks: Vec<_> = self
+            .depot
+            .lock()
+            .drain()
+            .flat_map(|(_, magazines)| magazines)
+            .flatten()
+            .collect();
+
+        self.uncache(&blocks);
+        blocks
+    }
+
+    /// Takes all cached blocks.
+    pub fn drain(&mut self) -> Vec<Block> {
+        let mut blocks = self.drain_depot();
+
+        for thread in self.threads.iter_mut() {
+            for (_, magazine) in thread.get_mut().drain() {
+                self.uncache(&magazine);
+                blocks.extend(magazine);
+            }
+        }
+
+        blocks
+    }
+}
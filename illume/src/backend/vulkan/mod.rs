@@ -111,6 +111,8 @@ mod descriptor;
 mod device;
 mod encode;
 mod graphics;
+mod leak;
+mod magazine;
 mod physical;
 mod queue;
 mod resources;
@@ -122,11 +124,6 @@ pub use self::{
     resources::*, surface::*, swapchain::*,
 };
 
-#[track_caller]
-fn device_lost() -> ! {
-    panic!("Device lost")
-}
-
 #[track_caller]
 fn unexpected_result(result: erupt::vk1_0::Result) -> ! {
     panic!("Unexpected Vulkan result {}", result)
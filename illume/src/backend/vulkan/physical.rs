@@ -1,10 +1,16 @@
 use {
     super::{
-        convert::from_erupt, device::Device, graphics::Graphics,
-        surface::surface_error_from_erupt, unexpected_result,
+        convert::{from_erupt, oom_error_from_erupt, ToErupt as _},
+        device::Device,
+        graphics::Graphics,
+        surface::surface_error_from_erupt,
+        unexpected_result,
     },
     crate::{
-        arith_gt, assert_object, out_of_host_memory,
+        arith_gt, assert_object,
+        format::{Format, FormatProperties, ImageFormatProperties},
+        image::{ImageExtent, ImageUsage},
+        out_of_host_memory,
         physical::*,
         queue::{Family, FamilyInfo, Queue, QueueId, QueuesQuery},
         surface::{Surface, SurfaceCapabilities, SurfaceError},
@@ -12,11 +18,17 @@ use {
     },
     erupt::{
         extensions::{
+            ext_descriptor_indexing::EXT_DESCRIPTOR_INDEXING_EXTENSION_NAME,
+            ext_extended_dynamic_state::{
+                self as vkeds, EXT_EXTENDED_DYNAMIC_STATE_EXTENSION_NAME,
+            },
+            ext_scalar_block_layout::EXT_SCALAR_BLOCK_LAYOUT_EXTENSION_NAME,
             khr_16bit_storage::KHR_16BIT_STORAGE_EXTENSION_NAME,
             khr_8bit_storage::KHR_8BIT_STORAGE_EXTENSION_NAME,
             khr_acceleration_structure::{
                 self as vkacc, KHR_ACCELERATION_STRUCTURE_EXTENSION_NAME,
             },
+            khr_buffer_device_address::KHR_BUFFER_DEVICE_ADDRESS_EXTENSION_NAME,
             khr_deferred_host_operations::KHR_DEFERRED_HOST_OPERATIONS_EXTENSION_NAME,
             khr_pipeline_library::KHR_PIPELINE_LIBRARY_EXTENSION_NAME,
             khr_push_descriptor::KHR_PUSH_DESCRIPTOR_EXTENSION_NAME,
@@ -24,6 +36,10 @@ use {
                 self as vkrt, KHR_RAY_TRACING_PIPELINE_EXTENSION_NAME,
             },
             khr_swapchain::KHR_SWAPCHAIN_EXTENSION_NAME,
+            khr_synchronization2::{
+                self as vks2, KHR_SYNCHRONIZATION_2_EXTENSION_NAME,
+            },
+            khr_timeline_semaphore::KHR_TIMELINE_SEMAPHORE_EXTENSION_NAME,
         },
         vk1_0, vk1_1, vk1_2, DeviceLoader, ExtendableFrom as _, LoaderError,
     },
@@ -37,6 +53,9 @@ pub(crate) struct Properties {
     pub(crate) family: Vec<vk1_0::QueueFamilyProperties>,
     pub(crate) memory: vk1_0::PhysicalDeviceMemoryProperties,
 
+    /// Version supported by both instance and device.
+    pub(crate) version: u32,
+
     pub(crate) v10: vk1_0::PhysicalDeviceProperties,
     pub(crate) v11: vk1_2::PhysicalDeviceVulkan11Properties,
     pub(crate) v12: vk1_2::PhysicalDeviceVulkan12Properties,
@@ -49,6 +68,10 @@ pub(crate) struct Properties {
 unsafe impl Sync for Properties {}
 unsafe impl Send for Properties {}
 
+/// Supported features.
+///
+/// When device supports Vulkan 1.1 only, `v12` is filled
+/// from features of extensions promoted to Vulkan 1.2.
 #[derive(Clone, Debug)]
 pub(crate) struct Features {
     pub(crate) v10: vk1_0::PhysicalDeviceFeatures,
@@ -56,6 +79,8 @@ pub(crate) struct Features {
     pub(crate) v12: vk1_2::PhysicalDeviceVulkan12Features,
     pub(crate) acc: vkacc::PhysicalDeviceAccelerationStructureFeaturesKHR,
     pub(crate) rt: vkrt::PhysicalDeviceRayTracingPipelineFeaturesKHR,
+    pub(crate) eds: vkeds::PhysicalDeviceExtendedDynamicStateFeaturesEXT,
+    pub(crate) sync2: vks2::PhysicalDeviceSynchronization2FeaturesKHR,
 }
 
 // Not auto-implemented because of raw pointer in fields.
@@ -63,6 +88,26 @@ pub(crate) struct Features {
 unsafe impl Sync for Features {}
 unsafe impl Send for Features {}
 
+/// Returns extension that provides capability prior to Vulkan 1.2.
+fn promoted_extension(capability: ApiCapability) -> &'static CStr {
+    let name = match capability {
+        ApiCapability::BufferDeviceAddress => {
+            KHR_BUFFER_DEVICE_ADDRESS_EXTENSION_NAME
+        }
+        ApiCapability::DescriptorIndexing => {
+            EXT_DESCRIPTOR_INDEXING_EXTENSION_NAME
+        }
+        ApiCapability::ScalarBlockLayout => {
+            EXT_SCALAR_BLOCK_LAYOUT_EXTENSION_NAME
+        }
+        ApiCapability::TimelineSemaphore => {
+            KHR_TIMELINE_SEMAPHORE_EXTENSION_NAME
+        }
+    };
+
+    unsafe { CStr::from_ptr(name) }
+}
+
 unsafe fn collect_propeties_and_features(
     physical: vk1_0::PhysicalDevice,
 ) -> (Properties, Features) {
@@ -80,6 +125,15 @@ unsafe fn collect_propeties_and_features(
             .any(|p| CStr::from_ptr(&p.extension_name[0]) == name)
     };
 
+    // Functions of newer versions can't be used
+    // unless both instance and device support them.
+    let version = graphics.version.min(
+        graphics
+            .instance
+            .get_physical_device_properties(physical, None)
+            .api_version,
+    );
+
     let properties10;
     let mut properties11 =
         vk1_2::PhysicalDeviceVulkan11PropertiesBuilder::new();
@@ -96,17 +150,46 @@ unsafe fn collect_propeties_and_features(
         vkacc::PhysicalDeviceAccelerationStructureFeaturesKHRBuilder::new();
     let mut features_rt =
         vkrt::PhysicalDeviceRayTracingPipelineFeaturesKHRBuilder::new();
-
-    if graphics.version >= vk1_0::make_version(1, 1, 0) {
+    let mut features_eds =
+        vkeds::PhysicalDeviceExtendedDynamicStateFeaturesEXTBuilder::new();
+    let mut features_sync2 =
+        vks2::PhysicalDeviceSynchronization2FeaturesKHRBuilder::new();
+
+    // Features of extensions promoted to Vulkan 1.2.
+    let mut features_bda =
+        vk1_2::PhysicalDeviceBufferDeviceAddressFeaturesBuilder::new();
+    let mut features_di =
+        vk1_2::PhysicalDeviceDescriptorIndexingFeaturesBuilder::new();
+    let mut features_sbl =
+        vk1_2::PhysicalDeviceScalarBlockLayoutFeaturesBuilder::new();
+    let mut features_ts =
+        vk1_2::PhysicalDeviceTimelineSemaphoreFeaturesBuilder::new();
+
+    let has_promoted =
+        |capability| has_extension(promoted_extension(capability).as_ptr());
+
+    if version >= vk1_0::make_version(1, 1, 0) {
         let mut properties2 = vk1_1::PhysicalDeviceProperties2Builder::new();
         let mut features2 = vk1_1::PhysicalDeviceFeatures2Builder::new();
 
-        properties2 = properties2.extend_from(&mut properties11);
-        features2 = features2.extend_from(&mut features11);
-
-        if graphics.version >= vk1_0::make_version(1, 2, 0) {
+        if version >= vk1_0::make_version(1, 2, 0) {
+            properties2 = properties2.extend_from(&mut properties11);
+            features2 = features2.extend_from(&mut features11);
             properties2 = properties2.extend_from(&mut properties12);
             features2 = features2.extend_from(&mut features12);
+        } else {
+            if has_promoted(ApiCapability::BufferDeviceAddress) {
+                features2 = features2.extend_from(&mut features_bda);
+            }
+            if has_promoted(ApiCapability::DescriptorIndexing) {
+                features2 = features2.extend_from(&mut features_di);
+            }
+            if has_promoted(ApiCapability::ScalarBlockLayout) {
+                features2 = features2.extend_from(&mut features_sbl);
+            }
+            if has_promoted(ApiCapability::TimelineSemaphore) {
+                features2 = features2.extend_from(&mut features_ts);
+            }
         }
 
         if has_extension(KHR_ACCELERATION_STRUCTURE_EXTENSION_NAME) {
@@ -119,6 +202,14 @@ unsafe fn collect_propeties_and_features(
             features2 = features2.extend_from(&mut features_rt);
         }
 
+        if has_extension(EXT_EXTENDED_DYNAMIC_STATE_EXTENSION_NAME) {
+            features2 = features2.extend_from(&mut features_eds);
+        }
+
+        if has_extension(KHR_SYNCHRONIZATION_2_EXTENSION_NAME) {
+            features2 = features2.extend_from(&mut features_sync2);
+        }
+
         *properties2 = graphics
             .instance
             .get_physical_device_properties2(physical, Some(*properties2));
@@ -129,6 +220,17 @@ unsafe fn collect_propeties_and_features(
 
         properties10 = properties2.properties;
         features10 = features2.features;
+
+        if version < vk1_0::make_version(1, 2, 0) {
+            promote_features(
+                &mut *features12,
+                &*features_bda,
+                &*features_di,
+                &*features_sbl,
+                &*features_ts,
+                has_promoted(ApiCapability::DescriptorIndexing),
+            );
+        }
     } else {
         properties10 = graphics
             .instance
@@ -151,6 +253,7 @@ unsafe fn collect_propeties_and_features(
         extension: extension_properties,
         family: family_properties,
         memory: memory_properties,
+        version,
         v10: properties10,
         v11: properties11.build(),
         v12: properties12.build(),
@@ -164,18 +267,84 @@ unsafe fn collect_propeties_and_features(
         v12: features12.build(),
         acc: features_acc.build(),
         rt: features_rt.build(),
+        eds: features_eds.build(),
+        sync2: features_sync2.build(),
     };
 
     properties.v11.p_next = std::ptr::null_mut();
     properties.v12.p_next = std::ptr::null_mut();
+    properties.acc.p_next = std::ptr::null_mut();
     properties.rt.p_next = std::ptr::null_mut();
     features.v11.p_next = std::ptr::null_mut();
     features.v12.p_next = std::ptr::null_mut();
+    features.acc.p_next = std::ptr::null_mut();
     features.rt.p_next = std::ptr::null_mut();
+    features.eds.p_next = std::ptr::null_mut();
+    features.sync2.p_next = std::ptr::null_mut();
 
     (properties, features)
 }
 
+/// Copies features of extensions into Vulkan 1.2 features.
+fn promote_features(
+    v12: &mut vk1_2::PhysicalDeviceVulkan12Features,
+    bda: &vk1_2::PhysicalDeviceBufferDeviceAddressFeatures,
+    di: &vk1_2::PhysicalDeviceDescriptorIndexingFeatures,
+    sbl: &vk1_2::PhysicalDeviceScalarBlockLayoutFeatures,
+    ts: &vk1_2::PhysicalDeviceTimelineSemaphoreFeatures,
+    descriptor_indexing: bool,
+) {
+    v12.buffer_device_address = bda.buffer_device_address;
+    v12.buffer_device_address_capture_replay =
+        bda.buffer_device_address_capture_replay;
+    v12.buffer_device_address_multi_device =
+        bda.buffer_device_address_multi_device;
+
+    v12.descriptor_indexing = descriptor_indexing as _;
+    v12.shader_input_attachment_array_dynamic_indexing =
+        di.shader_input_attachment_array_dynamic_indexing;
+    v12.shader_uniform_texel_buffer_array_dynamic_indexing =
+        di.shader_uniform_texel_buffer_array_dynamic_indexing;
+    v12.shader_storage_texel_buffer_array_dynamic_indexing =
+        di.shader_storage_texel_buffer_array_dynamic_indexing;
+    v12.shader_uniform_buffer_array_non_uniform_indexing =
+        di.shader_uniform_buffer_array_non_uniform_indexing;
+    v12.shader_sampled_image_array_non_uniform_indexing =
+        di.shader_sampled_image_array_non_uniform_indexing;
+    v12.shader_storage_buffer_array_non_uniform_indexing =
+        di.shader_storage_buffer_array_non_uniform_indexing;
+    v12.shader_storage_image_array_non_uniform_indexing =
+        di.shader_storage_image_array_non_uniform_indexing;
+    v12.shader_input_attachment_array_non_uniform_indexing =
+        di.shader_input_attachment_array_non_uniform_indexing;
+    v12.shader_uniform_texel_buffer_array_non_uniform_indexing =
+        di.shader_uniform_texel_buffer_array_non_uniform_indexing;
+    v12.shader_storage_texel_buffer_array_non_uniform_indexing =
+        di.shader_storage_texel_buffer_array_non_uniform_indexing;
+    v12.descriptor_binding_uniform_buffer_update_after_bind =
+        di.descriptor_binding_uniform_buffer_update_after_bind;
+    v12.descriptor_binding_sampled_image_update_after_bind =
+        di.descriptor_binding_sampled_image_update_after_bind;
+    v12.descriptor_binding_storage_image_update_after_bind =
+        di.descriptor_binding_storage_image_update_after_bind;
+    v12.descriptor_binding_storage_buffer_update_after_bind =
+        di.descriptor_binding_storage_buffer_update_after_bind;
+    v12.descriptor_binding_uniform_texel_buffer_update_after_bind =
+        di.descriptor_binding_uniform_texel_buffer_update_after_bind;
+    v12.descriptor_binding_storage_texel_buffer_update_after_bind =
+        di.descriptor_binding_storage_texel_buffer_update_after_bind;
+    v12.descriptor_binding_update_unused_while_pending =
+        di.descriptor_binding_update_unused_while_pending;
+    v12.descriptor_binding_partially_bound =
+        di.descriptor_binding_partially_bound;
+    v12.descriptor_binding_variable_descriptor_count =
+        di.descriptor_binding_variable_descriptor_count;
+    v12.runtime_descriptor_array = di.runtime_descriptor_array;
+
+    v12.scalar_block_layout = sbl.scalar_block_layout;
+    v12.timeline_semaphore = ts.timeline_semaphore;
+}
+
 impl Properties {
     pub(crate) fn has_extension(&self, name: &CStr) -> bool {
         self.extension
@@ -184,6 +353,65 @@ impl Properties {
     }
 }
 
+/// Queries format properties of physical device.
+pub(super) fn format_properties(
+    physical: vk1_0::PhysicalDevice,
+    format: Format,
+) -> FormatProperties {
+    let graphics = unsafe { Graphics::get_unchecked() };
+    let properties = unsafe {
+        graphics.instance.get_physical_device_format_properties(
+            physical,
+            format.to_erupt(),
+            None,
+        )
+    };
+
+    FormatProperties {
+        linear_tiling: from_erupt(properties.linear_tiling_features),
+        optimal_tiling: from_erupt(properties.optimal_tiling_features),
+        buffer: from_erupt(properties.buffer_features),
+    }
+}
+
+/// Queries limits of images with optimal tiling on physical device.
+/// Only dimensionality of `extent` is considered.
+/// Returns `Ok(None)` if combination is not supported.
+pub(super) fn image_format_properties(
+    physical: vk1_0::PhysicalDevice,
+    format: Format,
+    extent: ImageExtent,
+    usage: ImageUsage,
+) -> Result<Option<ImageFormatProperties>, OutOfMemory> {
+    let graphics = unsafe { Graphics::get_unchecked() };
+    let result = unsafe {
+        graphics
+            .instance
+            .get_physical_device_image_format_properties(
+                physical,
+                format.to_erupt(),
+                extent.to_erupt(),
+                vk1_0::ImageTiling::OPTIMAL,
+                usage.to_erupt(),
+                vk1_0::ImageCreateFlags::empty(),
+                None,
+            )
+            .result()
+    };
+
+    match result {
+        Ok(properties) => Ok(Some(ImageFormatProperties {
+            max_extent: from_erupt(properties.max_extent),
+            max_levels: properties.max_mip_levels,
+            max_layers: properties.max_array_layers,
+            max_samples: from_erupt(properties.sample_counts),
+            max_size: properties.max_resource_size,
+        })),
+        Err(vk1_0::Result::ERROR_FORMAT_NOT_SUPPORTED) => Ok(None),
+        Err(err) => Err(oom_error_from_erupt(err)),
+    }
+}
+
 /// Opaque value representing a device (software emulated of hardware).
 /// Can be used to fetch information about device,
 /// its support of the surface and create graphics device.
@@ -213,6 +441,36 @@ impl PhysicalDevice {
         }
     }
 
+    /// Returns how capability is provided by this device.
+    /// Returns `None` if capability is not supported.
+    fn capability_source(
+        &self,
+        capability: ApiCapability,
+    ) -> Option<CapabilitySource> {
+        let supported = match capability {
+            ApiCapability::BufferDeviceAddress => {
+                self.features.v12.buffer_device_address
+            }
+            ApiCapability::DescriptorIndexing => {
+                self.features.v12.descriptor_indexing
+            }
+            ApiCapability::ScalarBlockLayout => {
+                self.features.v12.scalar_block_layout
+            }
+            ApiCapability::TimelineSemaphore => {
+                self.features.v12.timeline_semaphore
+            }
+        };
+
+        if supported == 0 {
+            None
+        } else if self.properties.version >= vk1_0::make_version(1, 2, 0) {
+            Some(CapabilitySource::Core)
+        } else {
+            Some(CapabilitySource::Extension)
+        }
+    }
+
     /// Returns information about this device.
     pub fn info(&self) -> DeviceInfo {
         let mut features = Vec::new();
@@ -221,6 +479,11 @@ impl PhysicalDevice {
             features.push(Feature::BufferDeviceAddress);
         }
 
+        if self.features.v12.buffer_device_address_capture_replay > 0 {
+            assert!(features.contains(&Feature::BufferDeviceAddress));
+            features.push(Feature::BufferDeviceAddressCaptureReplay);
+        }
+
         if self.properties.has_extension(unsafe {
             CStr::from_ptr(KHR_ACCELERATION_STRUCTURE_EXTENSION_NAME)
         }) && self.features.acc.acceleration_structure != 0
@@ -235,12 +498,30 @@ impl PhysicalDevice {
         {
             assert!(features.contains(&Feature::AccelerationStructure));
             features.push(Feature::RayTracingPipeline);
+
+            if self.properties.has_extension(unsafe {
+                CStr::from_ptr(KHR_PIPELINE_LIBRARY_EXTENSION_NAME)
+            }) {
+                features.push(Feature::RayTracingPipelineLibrary);
+            }
         }
 
         if self.features.v12.scalar_block_layout > 0 {
             features.push(Feature::ScalarBlockLayout);
         }
 
+        if self.features.eds.extended_dynamic_state != 0 {
+            features.push(Feature::ExtendedDynamicState);
+        }
+
+        if self.features.sync2.synchronization2 != 0 {
+            features.push(Feature::Synchronization2);
+        }
+
+        if self.features.v10.multi_viewport != 0 {
+            features.push(Feature::MultiViewport);
+        }
+
         if self.features.v12.runtime_descriptor_array > 0 {
             features.push(Feature::RuntimeDescriptorArray);
         }
@@ -344,6 +625,11 @@ impl PhysicalDevice {
             }
             .to_string_lossy()
             .into_owned(),
+            api_version: ApiVersion {
+                major: vk1_0::version_major(self.properties.version),
+                minor: vk1_0::version_minor(self.properties.version),
+                patch: vk1_0::version_patch(self.properties.version),
+            },
             features,
             families: self
                 .properties
@@ -357,9 +643,37 @@ impl PhysicalDevice {
                     capabilities: from_erupt(f.queue_flags),
                 })
                 .collect(),
+            capabilities: [
+                ApiCapability::BufferDeviceAddress,
+                ApiCapability::DescriptorIndexing,
+                ApiCapability::ScalarBlockLayout,
+                ApiCapability::TimelineSemaphore,
+            ]
+            .iter()
+            .filter_map(|&capability| {
+                Some((capability, self.capability_source(capability)?))
+            })
+            .collect(),
         }
     }
 
+    /// Returns operations supported for format by this device.
+    pub fn format_properties(&self, format: Format) -> FormatProperties {
+        format_properties(self.physical, format)
+    }
+
+    /// Returns limits of images with specified format and usage.
+    /// Only dimensionality of `extent` is considered.
+    /// Returns `Ok(None)` if combination is not supported.
+    pub fn image_format_properties(
+        &self,
+        format: Format,
+        extent: ImageExtent,
+        usage: ImageUsage,
+    ) -> Result<Option<ImageFormatProperties>, OutOfMemory> {
+        image_format_properties(self.physical, format, extent, usage)
+    }
+
     /// Returns surface capabilities.
     /// Returns `Ok(None)` if this device does not support surface.
     pub fn surface_capabilities(
@@ -557,10 +871,16 @@ impl PhysicalDevice {
             vkacc::PhysicalDeviceAccelerationStructureFeaturesKHRBuilder::new();
         let mut features_rt =
             vkrt::PhysicalDeviceRayTracingPipelineFeaturesKHRBuilder::new();
+        let mut features_eds =
+            vkeds::PhysicalDeviceExtendedDynamicStateFeaturesEXTBuilder::new();
+        let mut features_sync2 =
+            vks2::PhysicalDeviceSynchronization2FeaturesKHRBuilder::new();
         let include_features11 = false;
         let mut include_features12 = false;
         let mut include_features_acc = false;
         let mut include_features_rt = false;
+        let mut include_features_eds = false;
+        let mut include_features_sync2 = false;
 
         // Enable requested extensions.
         let mut enable_exts = SmallVec::<[_; 10]>::new();
@@ -580,6 +900,21 @@ impl PhysicalDevice {
             push_ext(KHR_SWAPCHAIN_EXTENSION_NAME);
         }
 
+        if requested_features.take(Feature::RayTracingPipelineLibrary) {
+            assert!(
+                self.properties.has_extension(unsafe {
+                    CStr::from_ptr(KHR_PIPELINE_LIBRARY_EXTENSION_NAME)
+                }),
+                "Attempt to enable unsupported feature `RayTracingPipelineLibrary`"
+            );
+            assert!(
+                requested_features.check(Feature::RayTracingPipeline),
+                "`RayTracingPipeline` feature must be enabled when `RayTracingPipelineLibrary` feature is enabled"
+            );
+
+            push_ext(KHR_PIPELINE_LIBRARY_EXTENSION_NAME);
+        }
+
         if requested_features.take(Feature::RayTracingPipeline) {
             assert_ne!(
                 self.features.rt.ray_tracing_pipeline, 0,
@@ -593,7 +928,6 @@ impl PhysicalDevice {
             include_features_rt = true;
 
             push_ext(KHR_RAY_TRACING_PIPELINE_EXTENSION_NAME);
-            // push_ext(KHR_PIPELINE_LIBRARY_EXTENSION_NAME);
             // push_ext(KHR_DEFERRED_HOST_OPERATIONS_EXTENSION_NAME);
             // push_ext(KHR_8BIT_STORAGE_EXTENSION_NAME);
             // push_ext(KHR_16BIT_STORAGE_EXTENSION_NAME);
@@ -618,6 +952,21 @@ impl PhysicalDevice {
             push_ext(KHR_DEFERRED_HOST_OPERATIONS_EXTENSION_NAME);
         }
 
+        if requested_features.take(Feature::BufferDeviceAddressCaptureReplay) {
+            assert_ne!(
+                self.features.v12.buffer_device_address_capture_replay, 0,
+                "Attempt to enable unsupported feature `BufferDeviceAddressCaptureReplay`"
+            );
+
+            assert!(
+                requested_features.check(Feature::BufferDeviceAddress),
+                "`BufferDeviceAddress` feature must be enabled when `BufferDeviceAddressCaptureReplay` feature is enabled"
+            );
+
+            features12.buffer_device_address_capture_replay = 1;
+            include_features12 = true;
+        }
+
         if requested_features.take(Feature::BufferDeviceAddress) {
             assert_ne!(
                 self.features.v12.buffer_device_address, 0,
@@ -628,6 +977,30 @@ impl PhysicalDevice {
             include_features12 = true;
         }
 
+        if requested_features.take(Feature::ExtendedDynamicState) {
+            assert_ne!(
+                self.features.eds.extended_dynamic_state, 0,
+                "Attempt to enable unsupported feature `ExtendedDynamicState`"
+            );
+
+            features_eds.extended_dynamic_state = 1;
+            include_features_eds = true;
+
+            push_ext(EXT_EXTENDED_DYNAMIC_STATE_EXTENSION_NAME);
+        }
+
+        if requested_features.take(Feature::Synchronization2) {
+            assert_ne!(
+                self.features.sync2.synchronization2, 0,
+                "Attempt to enable unsupported feature `Synchronization2`"
+            );
+
+            features_sync2.synchronization2 = 1;
+            include_features_sync2 = true;
+
+            push_ext(KHR_SYNCHRONIZATION_2_EXTENSION_NAME);
+        }
+
         if requested_features.take(Feature::ScalarBlockLayout) {
             assert_ne!(
                 self.features.v12.scalar_block_layout, 0,
@@ -838,20 +1211,148 @@ impl PhysicalDevice {
             features.shader_storage_buffer_array_dynamic_indexing = 1;
         }
 
+        if requested_features.take(Feature::MultiViewport) {
+            assert_ne!(
+                self.features.v10.multi_viewport, 0,
+                "Attempt to enable unsupported feature `MultiViewport`"
+            );
+            features.multi_viewport = 1;
+        }
+
+        // Timeline semaphores are reported in device capabilities,
+        // so they are enabled whenever supported.
+        if self.features.v12.timeline_semaphore != 0 {
+            features12.timeline_semaphore = 1;
+            include_features12 = true;
+        }
+
+        let version = self.properties.version;
+
+        // Features promoted to Vulkan 1.2 are enabled with
+        // corresponding extensions on Vulkan 1.1 devices.
+        let mut features_bda =
+            vk1_2::PhysicalDeviceBufferDeviceAddressFeaturesBuilder::new();
+        let mut features_di =
+            vk1_2::PhysicalDeviceDescriptorIndexingFeaturesBuilder::new();
+        let mut features_sbl =
+            vk1_2::PhysicalDeviceScalarBlockLayoutFeaturesBuilder::new();
+        let mut features_ts =
+            vk1_2::PhysicalDeviceTimelineSemaphoreFeaturesBuilder::new();
+        let mut include_features_bda = false;
+        let mut include_features_di = false;
+        let mut include_features_sbl = false;
+        let mut include_features_ts = false;
+
+        if include_features12
+            && version >= vk1_0::make_version(1, 1, 0)
+            && version < vk1_0::make_version(1, 2, 0)
+        {
+            include_features12 = false;
+
+            if features12.buffer_device_address != 0 {
+                features_bda.buffer_device_address = 1;
+                features_bda.buffer_device_address_capture_replay =
+                    features12.buffer_device_address_capture_replay;
+                include_features_bda = true;
+                push_ext(
+                    promoted_extension(ApiCapability::BufferDeviceAddress)
+                        .as_ptr(),
+                );
+            }
+
+            if features12.scalar_block_layout != 0 {
+                features_sbl.scalar_block_layout = 1;
+                include_features_sbl = true;
+                push_ext(
+                    promoted_extension(ApiCapability::ScalarBlockLayout)
+                        .as_ptr(),
+                );
+            }
+
+            if features12.timeline_semaphore != 0 {
+                features_ts.timeline_semaphore = 1;
+                include_features_ts = true;
+                push_ext(
+                    promoted_extension(ApiCapability::TimelineSemaphore)
+                        .as_ptr(),
+                );
+            }
+
+            features_di.runtime_descriptor_array =
+                features12.runtime_descriptor_array;
+            features_di.descriptor_binding_uniform_buffer_update_after_bind =
+                features12.descriptor_binding_uniform_buffer_update_after_bind;
+            features_di.descriptor_binding_sampled_image_update_after_bind =
+                features12.descriptor_binding_sampled_image_update_after_bind;
+            features_di.descriptor_binding_storage_image_update_after_bind =
+                features12.descriptor_binding_storage_image_update_after_bind;
+            features_di.descriptor_binding_storage_buffer_update_after_bind =
+                features12.descriptor_binding_storage_buffer_update_after_bind;
+            features_di
+                .descriptor_binding_uniform_texel_buffer_update_after_bind =
+                features12
+                    .descriptor_binding_uniform_texel_buffer_update_after_bind;
+            features_di
+                .descriptor_binding_storage_texel_buffer_update_after_bind =
+                features12
+                    .descriptor_binding_storage_texel_buffer_update_after_bind;
+            features_di.descriptor_binding_update_unused_while_pending =
+                features12.descriptor_binding_update_unused_while_pending;
+            features_di.descriptor_binding_partially_bound =
+                features12.descriptor_binding_partially_bound;
+            features_di.shader_sampled_image_array_non_uniform_indexing =
+                features12.shader_sampled_image_array_non_uniform_indexing;
+            features_di.shader_storage_image_array_non_uniform_indexing =
+                features12.shader_storage_image_array_non_uniform_indexing;
+            features_di.shader_uniform_buffer_array_non_uniform_indexing =
+                features12.shader_uniform_buffer_array_non_uniform_indexing;
+            features_di.shader_storage_buffer_array_non_uniform_indexing =
+                features12.shader_storage_buffer_array_non_uniform_indexing;
+
+            include_features_di = [
+                features_di.runtime_descriptor_array,
+                features_di.descriptor_binding_uniform_buffer_update_after_bind,
+                features_di.descriptor_binding_sampled_image_update_after_bind,
+                features_di.descriptor_binding_storage_image_update_after_bind,
+                features_di.descriptor_binding_storage_buffer_update_after_bind,
+                features_di
+                    .descriptor_binding_uniform_texel_buffer_update_after_bind,
+                features_di
+                    .descriptor_binding_storage_texel_buffer_update_after_bind,
+                features_di.descriptor_binding_update_unused_while_pending,
+                features_di.descriptor_binding_partially_bound,
+                features_di.shader_sampled_image_array_non_uniform_indexing,
+                features_di.shader_storage_image_array_non_uniform_indexing,
+                features_di.shader_uniform_buffer_array_non_uniform_indexing,
+                features_di.shader_storage_buffer_array_non_uniform_indexing,
+            ]
+            .iter()
+            .any(|&feature| feature != 0);
+
+            if include_features_di {
+                push_ext(
+                    promoted_extension(ApiCapability::DescriptorIndexing)
+                        .as_ptr(),
+                );
+            }
+        }
+
         device_create_info =
             device_create_info.enabled_extension_names(&enable_exts);
 
-        let version = self.graphics().version;
-
         if version < vk1_0::make_version(1, 1, 0) {
             device_create_info = device_create_info.enabled_features(&features);
             assert!(!include_features11);
-            assert!(!include_features12);
+            assert!(
+                !include_features12,
+                "Vulkan 1.1 is required to enable Vulkan 1.2 features"
+            );
+            assert!(!include_features_acc);
             assert!(!include_features_rt);
+            assert!(!include_features_eds);
+            assert!(!include_features_sync2);
         } else {
-            if version < vk1_0::make_version(1, 2, 0) {
-                assert!(!include_features12);
-            }
+            features2 = features2.features(*features);
 
             // Push structure to the list if at least one feature is enabled.
             if include_features_acc {
@@ -864,6 +1365,16 @@ impl PhysicalDevice {
                     device_create_info.extend_from(&mut features_rt);
             }
 
+            if include_features_eds {
+                device_create_info =
+                    device_create_info.extend_from(&mut features_eds);
+            }
+
+            if include_features_sync2 {
+                device_create_info =
+                    device_create_info.extend_from(&mut features_sync2);
+            }
+
             if include_features12 {
                 device_create_info =
                     device_create_info.extend_from(&mut features12);
@@ -874,6 +1385,26 @@ impl PhysicalDevice {
                     device_create_info.extend_from(&mut features11);
             }
 
+            if include_features_bda {
+                device_create_info =
+                    device_create_info.extend_from(&mut features_bda);
+            }
+
+            if include_features_di {
+                device_create_info =
+                    device_create_info.extend_from(&mut features_di);
+            }
+
+            if include_features_sbl {
+                device_create_info =
+                    device_create_info.extend_from(&mut features_sbl);
+            }
+
+            if include_features_ts {
+                device_create_info =
+                    device_create_info.extend_from(&mut features_ts);
+            }
+
             device_create_info = device_create_info.extend_from(&mut features2);
         }
 
@@ -935,7 +1466,6 @@ impl PhysicalDevice {
 
                             Queue::new(
                                 queue,
-                                vk1_0::CommandPool::null(),
                                 device.clone(),
                                 QueueId {
                                     family: family as usize,
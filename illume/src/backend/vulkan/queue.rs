@@ -1,16 +1,16 @@
 use {
     super::{
-        convert::{oom_error_from_erupt, ToErupt as _},
-        device::Device,
-        device_lost,
+        convert::{oom_error_from_erupt, wait_error_from_erupt, ToErupt as _},
+        device::{Device, Garbage},
         swapchain::SwapchainImage,
         unexpected_result,
     },
     crate::{
         encode::{CommandBuffer, Encoder},
-        fence::Fence,
+        fence::{Fence, WaitError},
         out_of_host_memory,
         queue::*,
+        record::CommandRecorder,
         semaphore::Semaphore,
         stage::PipelineStageFlags,
         OutOfMemory,
@@ -20,12 +20,44 @@ use {
     std::fmt::{self, Debug},
 };
 
+/// Number of frames command buffer pools are cycled through.
+/// Pool of a frame is reset when it is started again.
+const FRAMES_IN_FLIGHT: usize = 2;
+
 pub struct Queue {
     handle: vk1_0::Queue,
-    pool: vk1_0::CommandPool,
+    frames: Vec<FramePool>,
+    frame: usize,
+    reusable_pool: vk1_0::CommandPool,
     device: Device,
     id: QueueId,
     capabilities: QueueCapabilityFlags,
+    recorder: Option<CommandRecorder>,
+}
+
+/// Command pool for transient command buffers of one frame.
+struct FramePool {
+    pool: vk1_0::CommandPool,
+
+    /// Command buffers ready for reuse.
+    free: Vec<vk1_0::CommandBuffer>,
+
+    /// Command buffers allocated during the frame.
+    used: Vec<vk1_0::CommandBuffer>,
+
+    /// Reusable command buffers to be freed when frame completes.
+    retired: Vec<vk1_0::CommandBuffer>,
+
+    /// Fence signalled when all work submitted up to the end
+    /// of the frame completes.
+    fence: Option<Fence>,
+
+    /// Whether fence was submitted and not yet waited.
+    fence_pending: bool,
+
+    /// Resources dropped before the end of the frame.
+    /// Destroyed once fence is signalled.
+    garbage: Vec<Garbage>,
 }
 
 impl Debug for Queue {
@@ -46,19 +78,75 @@ impl Debug for Queue {
 impl Queue {
     pub(crate) fn new(
         handle: vk1_0::Queue,
-        pool: vk1_0::CommandPool,
         device: Device,
         id: QueueId,
         capabilities: QueueCapabilityFlags,
     ) -> Self {
         Queue {
             handle,
+            frames: Vec::new(),
+            frame: 0,
+            reusable_pool: vk1_0::CommandPool::null(),
             device,
-            pool,
             id,
             capabilities,
+            recorder: None,
         }
     }
+
+    fn create_pool(
+        &self,
+        flags: vk1_0::CommandPoolCreateFlags,
+    ) -> Result<vk1_0::CommandPool, OutOfMemory> {
+        unsafe {
+            self.device.logical().create_command_pool(
+                &vk1_0::CommandPoolCreateInfoBuilder::new()
+                    .flags(flags)
+                    .queue_family_index(self.id.family as u32),
+                None,
+                None,
+            )
+        }
+        .result()
+        .map_err(oom_error_from_erupt)
+    }
+
+    fn allocate_command_buffer(
+        &self,
+        pool: vk1_0::CommandPool,
+    ) -> Result<vk1_0::CommandBuffer, OutOfMemory> {
+        let mut buffers = unsafe {
+            self.device.logical().allocate_command_buffers(
+                &vk1_0::CommandBufferAllocateInfoBuilder::new()
+                    .command_pool(pool)
+                    .level(vk1_0::CommandBufferLevel::PRIMARY)
+                    .command_buffer_count(1),
+            )
+        }
+        .result()
+        .map_err(oom_error_from_erupt)?;
+
+        Ok(buffers.remove(0))
+    }
+
+    fn frame_pool(&mut self) -> Result<&mut FramePool, OutOfMemory> {
+        while self.frames.len() <= self.frame {
+            let pool =
+                self.create_pool(vk1_0::CommandPoolCreateFlags::TRANSIENT)?;
+
+            self.frames.push(FramePool {
+                pool,
+                free: Vec::new(),
+                used: Vec::new(),
+                retired: Vec::new(),
+                fence: None,
+                fence_pending: false,
+                garbage: Vec::new(),
+            });
+        }
+
+        Ok(&mut self.frames[self.frame])
+    }
 }
 
 impl Queue {
@@ -66,46 +154,146 @@ impl Queue {
         self.id
     }
 
+    /// Installs recorder that captures submitted command buffers.
+    /// Only command buffers created after this call are captured.
+    pub fn set_recorder(&mut self, recorder: Option<CommandRecorder>) {
+        self.recorder = recorder;
+    }
+
+    pub fn recorder(&self) -> Option<&CommandRecorder> {
+        self.recorder.as_ref()
+    }
+
+    /// Creates encoder for transient command buffer.
+    ///
+    /// Command buffer is allocated from the pool of the current frame
+    /// and recycled when the frame's pool is reset by `begin_frame`.
+    /// It must be submitted once before that.
     #[tracing::instrument]
     pub fn create_encoder(&mut self) -> Result<Encoder<'static>, OutOfMemory> {
-        if self.pool == vk1_0::CommandPool::null() {
-            self.pool = unsafe {
-                self.device.logical().create_command_pool(
-                    &vk1_0::CommandPoolCreateInfoBuilder::new()
-                        .flags(
-                            vk1_0::CommandPoolCreateFlags::RESET_COMMAND_BUFFER,
-                        )
-                        .queue_family_index(self.id.family as u32),
-                    None,
-                    None,
-                )
+        let frame = self.frame_pool()?;
+
+        let handle = match frame.free.pop() {
+            Some(handle) => handle,
+            None => {
+                let pool = frame.pool;
+                self.allocate_command_buffer(pool)?
             }
-            .result()
-            .map_err(oom_error_from_erupt)?;
-        }
+        };
 
-        assert_ne!(self.pool, vk1_0::CommandPool::null());
+        self.frames[self.frame].used.push(handle);
 
-        let mut buffers = unsafe {
-            self.device.logical().allocate_command_buffers(
-                &vk1_0::CommandBufferAllocateInfoBuilder::new()
-                    .command_pool(self.pool)
-                    .level(vk1_0::CommandBufferLevel::PRIMARY)
-                    .command_buffer_count(1),
-            )
+        let cbuf = CommandBuffer::new(
+            handle,
+            self.id,
+            self.device.downgrade(),
+            false,
+            self.recorder.is_some(),
+        );
+
+        Ok(Encoder::new(cbuf, self.capabilities))
+    }
+
+    /// Creates encoder for command buffer that can be submitted
+    /// multiple times with `submit_reusable`.
+    ///
+    /// Command buffer must be released with `free_reusable`.
+    #[tracing::instrument]
+    pub fn create_reusable_encoder(
+        &mut self,
+    ) -> Result<Encoder<'static>, OutOfMemory> {
+        if self.reusable_pool == vk1_0::CommandPool::null() {
+            self.reusable_pool = self.create_pool(
+                vk1_0::CommandPoolCreateFlags::RESET_COMMAND_BUFFER,
+            )?;
         }
-        .result()
-        .map_err(oom_error_from_erupt)?;
+
+        let handle = self.allocate_command_buffer(self.reusable_pool)?;
 
         let cbuf = CommandBuffer::new(
-            buffers.remove(0),
+            handle,
             self.id,
             self.device.downgrade(),
+            true,
+            self.recorder.is_some(),
         );
 
         Ok(Encoder::new(cbuf, self.capabilities))
     }
 
+    /// Releases reusable command buffer.
+    /// It is freed once all work submitted in the current frame completes.
+    #[tracing::instrument]
+    pub fn free_reusable(
+        &mut self,
+        cbuf: CommandBuffer,
+    ) -> Result<(), OutOfMemory> {
+        assert_owner!(cbuf, self.device);
+        assert!(cbuf.is_reusable(), "Transient command buffers are recycled");
+
+        let handle = cbuf.handle();
+        self.frame_pool()?.retired.push(handle);
+        Ok(())
+    }
+
+    /// Finishes current frame and starts next one.
+    ///
+    /// Waits until all work submitted before the frame that used
+    /// the same pool is complete, then resets that pool
+    /// recycling its transient command buffers.
+    #[tracing::instrument]
+    pub fn begin_frame(&mut self) -> Result<(), WaitError> {
+        let reusable_pool = self.reusable_pool;
+        let device = self.device.clone();
+
+        self.submit_frame_fence()?;
+        device.check_leaks();
+
+        if let Some(recorder) = &self.recorder {
+            recorder.end_frame();
+        }
+
+        self.frame = (self.frame + 1) % FRAMES_IN_FLIGHT;
+
+        let next = self.frame_pool()?;
+        if next.fence_pending {
+            if let Some(fence) = &next.fence {
+                device.wait_fences(&[fence], true)?;
+                device.reset_fences(&[fence])?;
+            }
+            next.fence_pending = false;
+        }
+
+        unsafe {
+            // Fence guarantees that work that could use resources is complete.
+            device.destroy_garbage(next.garbage.drain(..));
+        }
+
+        let logical = device.logical();
+
+        unsafe {
+            logical
+                .reset_command_pool(
+                    next.pool,
+                    vk1_0::CommandPoolResetFlags::empty(),
+                )
+                .result()
+                .map_err(oom_error_from_erupt)?;
+        }
+
+        next.free.append(&mut next.used);
+
+        if !next.retired.is_empty() {
+            unsafe {
+                logical.free_command_buffers(reusable_pool, &next.retired);
+            }
+            next.retired.clear();
+        }
+
+        Ok(())
+    }
+
+    /// Submits transient command buffer.
     #[tracing::instrument]
     pub fn submit(
         &mut self,
@@ -113,7 +301,36 @@ impl Queue {
         cbuf: CommandBuffer,
         signal: &[Semaphore],
         fence: Option<&Fence>,
-    ) {
+    ) -> Result<(), WaitError> {
+        assert!(
+            !cbuf.is_reusable(),
+            "Reusable command buffers are submitted with `submit_reusable`"
+        );
+
+        self.submit_impl(wait, &cbuf, signal, fence)
+    }
+
+    /// Submits reusable command buffer.
+    /// It may be submitted again once execution completes.
+    #[tracing::instrument]
+    pub fn submit_reusable(
+        &mut self,
+        wait: &[(PipelineStageFlags, Semaphore)],
+        cbuf: &CommandBuffer,
+        signal: &[Semaphore],
+        fence: Option<&Fence>,
+    ) -> Result<(), WaitError> {
+        assert!(cbuf.is_reusable(), "Command buffer is not reusable");
+        self.submit_impl(wait, cbuf, signal, fence)
+    }
+
+    fn submit_impl(
+        &mut self,
+        wait: &[(PipelineStageFlags, Semaphore)],
+        cbuf: &CommandBuffer,
+        signal: &[Semaphore],
+        fence: Option<&Fence>,
+    ) -> Result<(), WaitError> {
         assert_owner!(cbuf, self.device);
         assert_eq!(self.id, cbuf.queue());
 
@@ -129,11 +346,17 @@ impl Queue {
             assert_owner!(fence, self.device);
         }
 
+        if let (Some(recorder), Some(commands)) =
+            (&self.recorder, cbuf.captured())
+        {
+            recorder.record(commands.clone());
+        }
+
         let cbuf = cbuf.handle();
 
         // FIXME: Check semaphore states.
         let (wait_stages, wait_semaphores): (
-            SmallVec<[_; 8]>,
+            SmallVec<[vk1_0::PipelineStageFlags; 8]>,
             SmallVec<[_; 8]>,
         ) = wait
             .iter()
@@ -155,8 +378,9 @@ impl Queue {
                         .command_buffers(&[cbuf])],
                     fence.map(|f| f.handle()),
                 )
-                .expect("TODO: Handle queue submit error")
-        };
+                .result()
+                .map_err(wait_error_from_erupt)
+        }
     }
 
     #[tracing::instrument]
@@ -164,8 +388,8 @@ impl Queue {
         &mut self,
         buffer: CommandBuffer,
         fence: Option<&Fence>,
-    ) {
-        self.submit(&[], buffer, &[], fence);
+    ) -> Result<(), WaitError> {
+        self.submit(&[], buffer, &[], fence)
     }
 
     #[tracing::instrument]
@@ -207,6 +431,7 @@ impl Queue {
             vk1_0::Result::ERROR_SURFACE_LOST_KHR => {
                 Err(PresentError::SurfaceLost)
             }
+            vk1_0::Result::ERROR_DEVICE_LOST => Err(PresentError::DeviceLost),
             // vk1_0::Result::ERROR_FULL_SCREEN_EXCLUSIVE_MODE_LOST_EXT => {}
             result => Err(PresentError::OutOfMemory {
                 source: queue_error(result),
@@ -214,11 +439,78 @@ impl Queue {
         }
     }
 
+    /// Waits until work submitted in all frames is complete
+    /// and destroys resources dropped so far.
+    ///
+    /// Unlike `wait_for_idle` waits only for fences of frames,
+    /// work submitted to other queues is not waited for.
+    #[tracing::instrument]
+    pub fn finish_frames(&mut self) -> Result<(), WaitError> {
+        let reusable_pool = self.reusable_pool;
+        let device = self.device.clone();
+
+        self.submit_frame_fence()?;
+
+        for frame in &mut self.frames {
+            if frame.fence_pending {
+                if let Some(fence) = &frame.fence {
+                    device.wait_fences(&[fence], true)?;
+                    device.reset_fences(&[fence])?;
+                }
+                frame.fence_pending = false;
+            }
+
+            unsafe {
+                // Fences of all frames are signalled.
+                device.destroy_garbage(frame.garbage.drain(..));
+            }
+
+            if !frame.retired.is_empty() {
+                unsafe {
+                    device
+                        .logical()
+                        .free_command_buffers(reusable_pool, &frame.retired);
+                }
+                frame.retired.clear();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Submits fence of current frame signalled after all work
+    /// submitted so far and moves resources dropped so far to the frame.
+    fn submit_frame_fence(&mut self) -> Result<(), WaitError> {
+        let handle = self.handle;
+        let device = self.device.clone();
+
+        let current = self.frame_pool()?;
+        let fence = match &current.fence {
+            Some(fence) => fence.clone(),
+            None => {
+                let fence = device.create_fence()?;
+                current.fence = Some(fence.clone());
+                fence
+            }
+        };
+
+        unsafe {
+            device
+                .logical()
+                .queue_submit(handle, &[], Some(fence.handle()))
+                .result()
+                .map_err(wait_error_from_erupt)?;
+        }
+        current.fence_pending = true;
+        current.garbage.extend(device.take_garbage());
+        Ok(())
+    }
+
     #[tracing::instrument]
-    pub fn wait_for_idle(&self) -> Result<(), OutOfMemory> {
+    pub fn wait_for_idle(&self) -> Result<(), WaitError> {
         unsafe { self.device.logical().queue_wait_idle(self.handle) }
             .result()
-            .map_err(queue_error)
+            .map_err(wait_error_from_erupt)
     }
 }
 
@@ -226,7 +518,6 @@ fn queue_error(result: vk1_0::Result) -> OutOfMemory {
     match result {
         vk1_0::Result::ERROR_OUT_OF_HOST_MEMORY => out_of_host_memory(),
         vk1_0::Result::ERROR_OUT_OF_DEVICE_MEMORY => OutOfMemory,
-        vk1_0::Result::ERROR_DEVICE_LOST => device_lost(),
         result => unexpected_result(result),
     }
 }
@@ -1,5 +1,9 @@
 use {
-    super::{descriptor::DescriptorSizes, device::WeakDevice},
+    super::{
+        descriptor::DescriptorSizes,
+        device::{Garbage, WeakDevice},
+        magazine::BlockClass,
+    },
     crate::{
         accel::AccelerationStructureInfo,
         buffer::BufferInfo,
@@ -11,6 +15,7 @@ use {
             ComputePipelineInfo, GraphicsPipelineInfo, PipelineLayoutInfo,
             RayTracingPipelineInfo,
         },
+        query::QueryPoolInfo,
         render_pass::RenderPassInfo,
         sampler::SamplerInfo,
         shader::ShaderModuleInfo,
@@ -23,6 +28,7 @@ use {
         cell::UnsafeCell,
         fmt::{self, Debug},
         hash::{Hash, Hasher},
+        mem::ManuallyDrop,
         ops::Deref,
         sync::Arc,
     },
@@ -37,7 +43,25 @@ struct BufferInner {
     memory_handle: vk1_0::DeviceMemory,
     memory_offset: u64,
     memory_size: u64,
-    memory_block: UnsafeCell<MemoryBlock<vk1_0::DeviceMemory>>,
+    memory_block: UnsafeCell<ManuallyDrop<MemoryBlock<vk1_0::DeviceMemory>>>,
+    memory_class: Option<BlockClass>,
+}
+
+impl Drop for BufferInner {
+    fn drop(&mut self) {
+        if let Some(device) = self.owner.upgrade() {
+            // Block is not accessed after this point.
+            let block =
+                unsafe { ManuallyDrop::take(self.memory_block.get_mut()) };
+
+            device.destroy_later(Garbage::Buffer {
+                handle: self.handle,
+                index: self.index,
+                block,
+                class: self.memory_class,
+            });
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -106,7 +130,7 @@ impl Buffer {
         self.inner.address
     }
 
-    pub(super) fn is_owned_by(
+    pub fn is_owned_by(
         &self,
         owner: &impl PartialEq<WeakDevice>,
     ) -> bool {
@@ -120,6 +144,10 @@ impl Buffer {
     pub(super) fn handle(&self) -> vk1_0::Buffer {
         self.inner.handle
     }
+
+    pub(super) fn memory_handle(&self) -> vk1_0::DeviceMemory {
+        self.inner.memory_handle
+    }
 }
 
 pub struct MappableBuffer {
@@ -205,6 +233,7 @@ impl MappableBuffer {
         address: Option<DeviceAddress>,
         index: usize,
         memory_block: MemoryBlock<vk1_0::DeviceMemory>,
+        memory_class: Option<BlockClass>,
         memory_usage: MemoryUsage,
     ) -> Self {
         MappableBuffer {
@@ -217,7 +246,10 @@ impl MappableBuffer {
                     memory_handle: *memory_block.memory(),
                     memory_offset: memory_block.offset(),
                     memory_size: memory_block.size(),
-                    memory_block: UnsafeCell::new(memory_block),
+                    memory_block: UnsafeCell::new(ManuallyDrop::new(
+                        memory_block,
+                    )),
+                    memory_class,
                     index,
                 }),
             },
@@ -232,7 +264,44 @@ impl MappableBuffer {
         &mut self,
     ) -> &mut MemoryBlock<vk1_0::DeviceMemory> {
         // exclusive access
-        &mut *self.inner.memory_block.get()
+        &mut **self.inner.memory_block.get()
+    }
+}
+
+/// Memory bound to an image.
+#[derive(Debug)]
+pub(super) enum ImageMemory {
+    Block(MemoryBlock<vk1_0::DeviceMemory>),
+
+    /// Lazily allocated memory.
+    /// Allocator never picks lazily allocated memory types,
+    /// so it is allocated for each image separately.
+    Lazy {
+        memory: vk1_0::DeviceMemory,
+        size: u64,
+    },
+}
+
+impl ImageMemory {
+    pub(super) fn memory(&self) -> vk1_0::DeviceMemory {
+        match self {
+            ImageMemory::Block(block) => *block.memory(),
+            ImageMemory::Lazy { memory, .. } => *memory,
+        }
+    }
+
+    pub(super) fn offset(&self) -> u64 {
+        match self {
+            ImageMemory::Block(block) => block.offset(),
+            ImageMemory::Lazy { .. } => 0,
+        }
+    }
+
+    pub(super) fn size(&self) -> u64 {
+        match self {
+            ImageMemory::Block(block) => block.size(),
+            ImageMemory::Lazy { size, .. } => *size,
+        }
     }
 }
 
@@ -240,10 +309,25 @@ struct ImageInner {
     info: ImageInfo,
     handle: vk1_0::Image,
     owner: WeakDevice,
-    memory_block: Option<MemoryBlock<vk1_0::DeviceMemory>>,
+    memory: Option<ImageMemory>,
     index: Option<usize>,
 }
 
+impl Drop for ImageInner {
+    fn drop(&mut self) {
+        // Swapchain images are not owned and have no memory.
+        if let (Some(index), Some(memory)) = (self.index, self.memory.take()) {
+            if let Some(device) = self.owner.upgrade() {
+                device.destroy_later(Garbage::Image {
+                    handle: self.handle,
+                    index,
+                    memory,
+                });
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Image {
     inner: Arc<ImageInner>,
@@ -273,7 +357,7 @@ impl Debug for Image {
                 .field("info", &self.inner.info)
                 .field("owner", &self.inner.owner)
                 .field("handle", &self.inner.handle)
-                .field("memory_block", &self.inner.memory_block)
+                .field("memory", &self.inner.memory)
                 .field("index", &self.inner.index)
                 .finish()
         } else {
@@ -287,11 +371,18 @@ impl Image {
         &self.inner.info
     }
 
+    pub fn is_owned_by(
+        &self,
+        owner: &impl PartialEq<WeakDevice>,
+    ) -> bool {
+        *owner == self.inner.owner
+    }
+
     pub(super) fn new(
         info: ImageInfo,
         owner: WeakDevice,
         handle: vk1_0::Image,
-        memory_block: Option<MemoryBlock<vk1_0::DeviceMemory>>,
+        memory: Option<ImageMemory>,
         index: Option<usize>,
     ) -> Self {
         Image {
@@ -299,19 +390,12 @@ impl Image {
                 info,
                 owner,
                 handle,
-                memory_block,
+                memory,
                 index,
             }),
         }
     }
 
-    pub(super) fn is_owned_by(
-        &self,
-        owner: &impl PartialEq<WeakDevice>,
-    ) -> bool {
-        *owner == self.inner.owner
-    }
-
     pub(super) fn owner(&self) -> &WeakDevice {
         &self.inner.owner
     }
@@ -678,6 +762,79 @@ impl Sampler {
     }
 }
 
+#[derive(Clone)]
+pub struct QueryPool {
+    info: QueryPoolInfo,
+    handle: vk1_0::QueryPool,
+    owner: WeakDevice,
+    index: usize,
+}
+
+impl Debug for QueryPool {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if fmt.alternate() {
+            fmt.debug_struct("QueryPool")
+                .field("handle", &self.handle)
+                .field("owner", &self.owner)
+                .finish()
+        } else {
+            write!(fmt, "QueryPool({:p})", self.handle)
+        }
+    }
+}
+
+impl PartialEq for QueryPool {
+    fn eq(&self, rhs: &Self) -> bool {
+        self.handle == rhs.handle
+    }
+}
+
+impl Eq for QueryPool {}
+
+impl Hash for QueryPool {
+    fn hash<H>(&self, hasher: &mut H)
+    where
+        H: Hasher,
+    {
+        self.handle.hash(hasher)
+    }
+}
+
+impl QueryPool {
+    pub fn info(&self) -> &QueryPoolInfo {
+        &self.info
+    }
+
+    pub(super) fn new(
+        info: QueryPoolInfo,
+        owner: WeakDevice,
+        handle: vk1_0::QueryPool,
+        index: usize,
+    ) -> Self {
+        QueryPool {
+            info,
+            owner,
+            handle,
+            index,
+        }
+    }
+
+    pub(super) fn is_owned_by(
+        &self,
+        owner: &impl PartialEq<WeakDevice>,
+    ) -> bool {
+        *owner == self.owner
+    }
+
+    pub(super) fn owner(&self) -> &WeakDevice {
+        &self.owner
+    }
+
+    pub(super) fn handle(&self) -> vk1_0::QueryPool {
+        self.handle
+    }
+}
+
 /// Framebuffer is a collection of attachments for render pass.
 /// Images format and sample count should match attachment definitions.
 /// All image views must be 2D with 1 mip level and 1 array level.
@@ -1209,9 +1366,7 @@ impl GraphicsPipeline {
     }
 }
 
-/// Bottom-level acceleration structure.
-#[derive(Clone)]
-pub struct AccelerationStructure {
+struct AccelerationStructureInner {
     info: AccelerationStructureInfo,
     handle: vkacc::AccelerationStructureKHR,
     owner: WeakDevice,
@@ -1219,23 +1374,40 @@ pub struct AccelerationStructure {
     index: usize,
 }
 
+impl Drop for AccelerationStructureInner {
+    fn drop(&mut self) {
+        if let Some(device) = self.owner.upgrade() {
+            device.destroy_later(Garbage::AccelerationStructure {
+                handle: self.handle,
+                index: self.index,
+            });
+        }
+    }
+}
+
+/// Bottom-level acceleration structure.
+#[derive(Clone)]
+pub struct AccelerationStructure {
+    inner: Arc<AccelerationStructureInner>,
+}
+
 impl Debug for AccelerationStructure {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         if fmt.alternate() {
             fmt.debug_struct("AccelerationStructure")
-                .field("handle", &self.handle)
-                .field("owner", &self.owner)
-                .field("address", &self.address)
+                .field("handle", &self.inner.handle)
+                .field("owner", &self.inner.owner)
+                .field("address", &self.inner.address)
                 .finish()
         } else {
-            write!(fmt, "AccelerationStructure({:p})", self.handle)
+            write!(fmt, "AccelerationStructure({:p})", self.inner.handle)
         }
     }
 }
 
 impl PartialEq for AccelerationStructure {
     fn eq(&self, rhs: &Self) -> bool {
-        self.handle == rhs.handle
+        self.inner.handle == rhs.inner.handle
     }
 }
 
@@ -1246,17 +1418,17 @@ impl Hash for AccelerationStructure {
     where
         H: Hasher,
     {
-        self.handle.hash(hasher)
+        self.inner.handle.hash(hasher)
     }
 }
 
 impl AccelerationStructure {
     pub fn info(&self) -> &AccelerationStructureInfo {
-        &self.info
+        &self.inner.info
     }
 
     pub fn address(&self) -> DeviceAddress {
-        self.address
+        self.inner.address
     }
 
     pub(super) fn new(
@@ -1267,11 +1439,13 @@ impl AccelerationStructure {
         index: usize,
     ) -> Self {
         AccelerationStructure {
-            info,
-            owner,
-            handle,
-            address,
-            index,
+            inner: Arc::new(AccelerationStructureInner {
+                info,
+                owner,
+                handle,
+                address,
+                index,
+            }),
         }
     }
 
@@ -1279,15 +1453,15 @@ impl AccelerationStructure {
         &self,
         owner: &impl PartialEq<WeakDevice>,
     ) -> bool {
-        *owner == self.owner
+        *owner == self.inner.owner
     }
 
     pub(super) fn owner(&self) -> &WeakDevice {
-        &self.owner
+        &self.inner.owner
     }
 
     pub(super) fn handle(&self) -> vkacc::AccelerationStructureKHR {
-        self.handle
+        self.inner.handle
     }
 }
 
@@ -1336,6 +1510,17 @@ impl RayTracingPipeline {
         &self.info
     }
 
+    /// Returns number of shader groups including groups of linked libraries.
+    pub fn group_count(&self) -> usize {
+        self.info.groups.len()
+            + self
+                .info
+                .libraries
+                .iter()
+                .map(RayTracingPipeline::group_count)
+                .sum::<usize>()
+    }
+
     pub(super) fn new(
         info: RayTracingPipelineInfo,
         owner: WeakDevice,
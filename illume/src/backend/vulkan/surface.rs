@@ -62,6 +62,15 @@ impl Surface {
         }
     }
 
+    pub(crate) fn mark_unused(&self) {
+        self.inner.used.store(false, Ordering::SeqCst);
+    }
+
+    /// Consumes surface and returns `true` if no other clone exists.
+    pub(crate) fn into_unique(self) -> bool {
+        std::sync::Arc::try_unwrap(self.inner).is_ok()
+    }
+
     pub fn info(&self) -> &SurfaceInfo {
         &self.inner.info
     }
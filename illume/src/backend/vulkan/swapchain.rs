@@ -10,6 +10,7 @@ use crate::{
     out_of_host_memory,
     semaphore::Semaphore,
     surface::{PresentMode, SurfaceError},
+    swapchain::SwapchainImageInfo,
     Extent2d, OutOfMemory,
 };
 use erupt::{
@@ -69,24 +70,6 @@ impl Drop for SwapchainImage {
     }
 }
 
-#[derive(Clone, Debug)]
-pub struct SwapchainImageInfo {
-    /// Swapchain image.
-    pub image: Image,
-
-    /// Semaphore that should be waited upon before accessing an image.
-    ///
-    /// Acquisition semaphore management may be rather complex,
-    /// so keep that to the implementation.
-    pub wait: Semaphore,
-
-    /// Semaphore that should be signaled after last image access.
-    ///
-    /// Presentation semaphore management may be rather complex,
-    /// so keep that to the implementation.
-    pub signal: Semaphore,
-}
-
 #[derive(Debug)]
 struct SwapchainImageAndSemaphores {
     image: Image,
@@ -208,29 +191,23 @@ impl Swapchain {
         let instance = &device.graphics().instance;
         let logical = &device.logical();
 
-        let caps = unsafe {
-            instance.get_physical_device_surface_capabilities_khr(
-                device.physical(),
-                surface,
-                None,
-            )
-        }
-        .result()
-        .map_err(|err| match err {
-            vk1_0::Result::ERROR_OUT_OF_HOST_MEMORY => out_of_host_memory(),
-            vk1_0::Result::ERROR_OUT_OF_DEVICE_MEMORY => {
-                SurfaceError::OutOfMemory {
-                    source: OutOfMemory,
-                }
-            }
-            vk1_0::Result::ERROR_SURFACE_LOST_KHR => SurfaceError::SurfaceLost,
-            _ => unexpected_result(err),
-        })?;
+        let caps = self.capabilities(&device)?;
 
         if !ImageUsage::from_erupt(caps.supported_usage_flags).contains(usage) {
             return Err(SurfaceError::UsageNotSupported { usage });
         }
 
+        // Minimized windows may report zero extent.
+        // Swapchain can't be created until surface is restored.
+        if caps.current_extent.width == 0 || caps.current_extent.height == 0 {
+            if let Some(inner) = self.inner.take() {
+                self.retired.push(inner);
+            }
+
+            tracing::debug!("Surface extent is zero. Swapchain is not created");
+            return Ok(());
+        }
+
         let formats = unsafe {
             instance.get_physical_device_surface_formats_khr(
                 device.physical(),
@@ -386,6 +363,86 @@ impl Swapchain {
         Ok(())
     }
 
+    /// Returns image usage supported by the surface.
+    /// Capabilities are queried on each call.
+    pub fn supported_usage(&self) -> Result<ImageUsage, SurfaceError> {
+        let device = self
+            .device
+            .upgrade()
+            .ok_or_else(|| SurfaceError::SurfaceLost)?;
+
+        let caps = self.capabilities(&device)?;
+        Ok(ImageUsage::from_erupt(caps.supported_usage_flags))
+    }
+
+    fn capabilities(
+        &self,
+        device: &Device,
+    ) -> Result<vks::SurfaceCapabilitiesKHR, SurfaceError> {
+        unsafe {
+            device
+                .graphics()
+                .instance
+                .get_physical_device_surface_capabilities_khr(
+                    device.physical(),
+                    self.surface.handle(),
+                    None,
+                )
+        }
+        .result()
+        .map_err(|err| match err {
+            vk1_0::Result::ERROR_OUT_OF_HOST_MEMORY => out_of_host_memory(),
+            vk1_0::Result::ERROR_OUT_OF_DEVICE_MEMORY => {
+                SurfaceError::OutOfMemory {
+                    source: OutOfMemory,
+                }
+            }
+            vk1_0::Result::ERROR_SURFACE_LOST_KHR => SurfaceError::SurfaceLost,
+            _ => unexpected_result(err),
+        })
+    }
+
+    /// Returns `true` if swapchain images can be acquired.
+    /// Returns `false` before first `configure` call and while
+    /// surface has zero extent.
+    pub fn is_configured(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    /// Destroys swapchain along with retired ones
+    /// and returns its surface.
+    ///
+    /// All work that uses swapchain images must be complete,
+    /// e.g. after `Queue::finish_frames`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if swapchain images are still acquired.
+    pub fn destroy(mut self) -> Surface {
+        if let Some(device) = self.device.upgrade() {
+            let logical = device.logical();
+
+            for inner in self.inner.take().into_iter().chain(self.retired) {
+                assert_eq!(
+                    inner.counter.load(Acquire),
+                    0,
+                    "Swapchain images must be released before destruction"
+                );
+
+                device.swapchains().lock().remove(inner.index);
+
+                unsafe {
+                    // Work that used images is complete.
+                    logical.destroy_swapchain_khr(Some(inner.handle), None);
+                }
+            }
+        }
+
+        tracing::debug!("Swapchain destroyed");
+        self.surface.mark_unused();
+        self.surface
+    }
+
     pub fn acquire_image(
         &mut self,
     ) -> Result<Option<SwapchainImage>, SurfaceError> {
@@ -0,0 +1,675 @@
+use crate::{
+    AspectFlags, BlendFactor, BlendOp, BorderColor, BufferUsage, CompareOp,
+    ComponentMask, Extent3d, Filter, Format, FormatDescription, FormatFeatures,
+    FormatProperties, FormatType, FrontFace, ImageExtent, ImageUsage,
+    ImageViewKind, IndexType, MipmapMode, Offset3d, PolygonMode, PresentMode,
+    PrimitiveTopology, SamplerAddressMode, Samples, ShaderStageFlags,
+    StencilOp, VertexInputRate,
+};
+
+pub(super) trait ToWgpu<T> {
+    fn to_wgpu(self) -> T;
+}
+
+pub(super) trait FromWgpu<T> {
+    fn from_wgpu(value: T) -> Self;
+}
+
+pub(super) fn from_wgpu<T, U: FromWgpu<T>>(value: T) -> U {
+    U::from_wgpu(value)
+}
+
+impl ToWgpu<Option<wgpu::TextureFormat>> for Format {
+    fn to_wgpu(self) -> Option<wgpu::TextureFormat> {
+        use wgpu::TextureFormat as TF;
+
+        Some(match self {
+            Format::R8Unorm => TF::R8Unorm,
+            Format::R8Snorm => TF::R8Snorm,
+            Format::R8Uint => TF::R8Uint,
+            Format::R8Sint => TF::R8Sint,
+            Format::RG8Unorm => TF::Rg8Unorm,
+            Format::RG8Snorm => TF::Rg8Snorm,
+            Format::RG8Uint => TF::Rg8Uint,
+            Format::RG8Sint => TF::Rg8Sint,
+            Format::RGBA8Unorm => TF::Rgba8Unorm,
+            Format::RGBA8Snorm => TF::Rgba8Snorm,
+            Format::RGBA8Uint => TF::Rgba8Uint,
+            Format::RGBA8Sint => TF::Rgba8Sint,
+            Format::RGBA8Srgb => TF::Rgba8UnormSrgb,
+            Format::BGRA8Unorm => TF::Bgra8Unorm,
+            Format::BGRA8Srgb => TF::Bgra8UnormSrgb,
+            Format::R16Uint => TF::R16Uint,
+            Format::R16Sint => TF::R16Sint,
+            Format::R16Sfloat => TF::R16Float,
+            Format::RG16Uint => TF::Rg16Uint,
+            Format::RG16Sint => TF::Rg16Sint,
+            Format::RG16Sfloat => TF::Rg16Float,
+            Format::RGBA16Uint => TF::Rgba16Uint,
+            Format::RGBA16Sint => TF::Rgba16Sint,
+            Format::RGBA16Sfloat => TF::Rgba16Float,
+            Format::R32Uint => TF::R32Uint,
+            Format::R32Sint => TF::R32Sint,
+            Format::R32Sfloat => TF::R32Float,
+            Format::RG32Uint => TF::Rg32Uint,
+            Format::RG32Sint => TF::Rg32Sint,
+            Format::RG32Sfloat => TF::Rg32Float,
+            Format::RGBA32Uint => TF::Rgba32Uint,
+            Format::RGBA32Sint => TF::Rgba32Sint,
+            Format::RGBA32Sfloat => TF::Rgba32Float,
+            Format::D32Sfloat => TF::Depth32Float,
+            Format::D24UnormS8Uint => TF::Depth24PlusStencil8,
+            _ => return None,
+        })
+    }
+}
+
+impl FromWgpu<wgpu::TextureFormat> for Option<Format> {
+    fn from_wgpu(format: wgpu::TextureFormat) -> Self {
+        use wgpu::TextureFormat as TF;
+
+        Some(match format {
+            TF::R8Unorm => Format::R8Unorm,
+            TF::R8Snorm => Format::R8Snorm,
+            TF::R8Uint => Format::R8Uint,
+            TF::R8Sint => Format::R8Sint,
+            TF::Rg8Unorm => Format::RG8Unorm,
+            TF::Rg8Snorm => Format::RG8Snorm,
+            TF::Rg8Uint => Format::RG8Uint,
+            TF::Rg8Sint => Format::RG8Sint,
+            TF::Rgba8Unorm => Format::RGBA8Unorm,
+            TF::Rgba8Snorm => Format::RGBA8Snorm,
+            TF::Rgba8Uint => Format::RGBA8Uint,
+            TF::Rgba8Sint => Format::RGBA8Sint,
+            TF::Rgba8UnormSrgb => Format::RGBA8Srgb,
+            TF::Bgra8Unorm => Format::BGRA8Unorm,
+            TF::Bgra8UnormSrgb => Format::BGRA8Srgb,
+            TF::R16Uint => Format::R16Uint,
+            TF::R16Sint => Format::R16Sint,
+            TF::R16Float => Format::R16Sfloat,
+            TF::Rg16Uint => Format::RG16Uint,
+            TF::Rg16Sint => Format::RG16Sint,
+            TF::Rg16Float => Format::RG16Sfloat,
+            TF::Rgba16Uint => Format::RGBA16Uint,
+            TF::Rgba16Sint => Format::RGBA16Sint,
+            TF::Rgba16Float => Format::RGBA16Sfloat,
+            TF::R32Uint => Format::R32Uint,
+            TF::R32Sint => Format::R32Sint,
+            TF::R32Float => Format::R32Sfloat,
+            TF::Rg32Uint => Format::RG32Uint,
+            TF::Rg32Sint => Format::RG32Sint,
+            TF::Rg32Float => Format::RG32Sfloat,
+            TF::Rgba32Uint => Format::RGBA32Uint,
+            TF::Rgba32Sint => Format::RGBA32Sint,
+            TF::Rgba32Float => Format::RGBA32Sfloat,
+            TF::Depth32Float => Format::D32Sfloat,
+            TF::Depth24PlusStencil8 => Format::D24UnormS8Uint,
+            _ => return None,
+        })
+    }
+}
+
+impl ToWgpu<Option<wgpu::VertexFormat>> for Format {
+    fn to_wgpu(self) -> Option<wgpu::VertexFormat> {
+        use wgpu::VertexFormat as VF;
+
+        Some(match self {
+            Format::RG8Uint => VF::Uchar2,
+            Format::RGBA8Uint => VF::Uchar4,
+            Format::RG8Sint => VF::Char2,
+            Format::RGBA8Sint => VF::Char4,
+            Format::RG8Unorm => VF::Uchar2Norm,
+            Format::RGBA8Unorm => VF::Uchar4Norm,
+            Format::RG8Snorm => VF::Char2Norm,
+            Format::RGBA8Snorm => VF::Char4Norm,
+            Format::RG16Uint => VF::Ushort2,
+            Format::RGBA16Uint => VF::Ushort4,
+            Format::RG16Sint => VF::Short2,
+            Format::RGBA16Sint => VF::Short4,
+            Format::RG16Unorm => VF::Ushort2Norm,
+            Format::RGBA16Unorm => VF::Ushort4Norm,
+            Format::RG16Snorm => VF::Short2Norm,
+            Format::RGBA16Snorm => VF::Short4Norm,
+            Format::RG16Sfloat => VF::Half2,
+            Format::RGBA16Sfloat => VF::Half4,
+            Format::R32Sfloat => VF::Float,
+            Format::RG32Sfloat => VF::Float2,
+            Format::RGB32Sfloat => VF::Float3,
+            Format::RGBA32Sfloat => VF::Float4,
+            Format::R32Uint => VF::Uint,
+            Format::RG32Uint => VF::Uint2,
+            Format::RGB32Uint => VF::Uint3,
+            Format::RGBA32Uint => VF::Uint4,
+            Format::R32Sint => VF::Int,
+            Format::RG32Sint => VF::Int2,
+            Format::RGB32Sint => VF::Int3,
+            Format::RGBA32Sint => VF::Int4,
+            _ => return None,
+        })
+    }
+}
+
+/// Returns size of one texel of the format in bytes.
+pub(super) fn texel_size(format: Format) -> u32 {
+    let bits = match format.description() {
+        FormatDescription::R(repr)
+        | FormatDescription::Depth(repr)
+        | FormatDescription::Stencil(repr) => u32::from(repr.bits),
+        FormatDescription::RG(repr) => u32::from(repr.bits) * 2,
+        FormatDescription::RGB(repr) | FormatDescription::BGR(repr) => {
+            u32::from(repr.bits) * 3
+        }
+        FormatDescription::RGBA(repr) | FormatDescription::BGRA(repr) => {
+            u32::from(repr.bits) * 4
+        }
+        FormatDescription::DepthStencil { depth, stencil } => {
+            u32::from(depth.bits) + u32::from(stencil.bits)
+        }
+    };
+
+    (bits + 7) / 8
+}
+
+/// Returns `true` if format can be sampled with linear filter.
+pub(super) fn is_filterable(format: Format) -> bool {
+    match format.color_type() {
+        Some(FormatType::Uint) | Some(FormatType::Sint) => false,
+        Some(FormatType::Sfloat) => match format.description() {
+            FormatDescription::R(repr)
+            | FormatDescription::RG(repr)
+            | FormatDescription::RGBA(repr) => repr.bits < 32,
+            _ => false,
+        },
+        Some(_) => true,
+        None => false,
+    }
+}
+
+/// Returns type of texture samples read from images of the format.
+pub(super) fn sample_type(format: Format) -> wgpu::TextureSampleType {
+    match format.description() {
+        FormatDescription::Depth(_)
+        | FormatDescription::DepthStencil { .. } => {
+            wgpu::TextureSampleType::Depth
+        }
+        FormatDescription::Stencil(_) => wgpu::TextureSampleType::Uint,
+        _ => match format.color_type() {
+            Some(FormatType::Uint) => wgpu::TextureSampleType::Uint,
+            Some(FormatType::Sint) => wgpu::TextureSampleType::Sint,
+            _ => wgpu::TextureSampleType::Float {
+                filterable: is_filterable(format),
+            },
+        },
+    }
+}
+
+/// Returns `true` if format can be used for storage images.
+pub(super) fn is_storage_format(format: Format) -> bool {
+    match format {
+        Format::RGBA8Unorm
+        | Format::RGBA8Snorm
+        | Format::RGBA8Uint
+        | Format::RGBA8Sint
+        | Format::RGBA16Uint
+        | Format::RGBA16Sint
+        | Format::RGBA16Sfloat
+        | Format::R32Uint
+        | Format::R32Sint
+        | Format::R32Sfloat
+        | Format::RG32Uint
+        | Format::RG32Sint
+        | Format::RG32Sfloat
+        | Format::RGBA32Uint
+        | Format::RGBA32Sint
+        | Format::RGBA32Sfloat => true,
+        _ => false,
+    }
+}
+
+pub(super) fn format_properties(format: Format) -> FormatProperties {
+    let vertex =
+        if ToWgpu::<Option<wgpu::VertexFormat>>::to_wgpu(format).is_some() {
+            FormatFeatures::VERTEX_BUFFER
+        } else {
+            FormatFeatures::empty()
+        };
+
+    let optimal_tiling =
+        match ToWgpu::<Option<wgpu::TextureFormat>>::to_wgpu(format) {
+            None => FormatFeatures::empty(),
+            Some(_) if !format.is_color() => {
+                FormatFeatures::SAMPLED_IMAGE
+                    | FormatFeatures::DEPTH_STENCIL_ATTACHMENT
+            }
+            Some(_) => {
+                let mut features = FormatFeatures::SAMPLED_IMAGE
+                    | FormatFeatures::COLOR_ATTACHMENT
+                    | FormatFeatures::TRANSFER_SRC
+                    | FormatFeatures::TRANSFER_DST;
+
+                if is_filterable(format) {
+                    features |= FormatFeatures::SAMPLED_IMAGE_FILTER_LINEAR
+                        | FormatFeatures::COLOR_ATTACHMENT_BLEND;
+                }
+
+                if is_storage_format(format) {
+                    features |= FormatFeatures::STORAGE_IMAGE;
+                }
+
+                features
+            }
+        };
+
+    FormatProperties {
+        linear_tiling: FormatFeatures::empty(),
+        optimal_tiling,
+        buffer: vertex,
+    }
+}
+
+impl ToWgpu<wgpu::TextureUsage> for ImageUsage {
+    fn to_wgpu(self) -> wgpu::TextureUsage {
+        let mut result = wgpu::TextureUsage::empty();
+
+        if self.contains(ImageUsage::TRANSFER_SRC) {
+            result |= wgpu::TextureUsage::COPY_SRC;
+        }
+        if self.contains(ImageUsage::TRANSFER_DST) {
+            result |= wgpu::TextureUsage::COPY_DST;
+        }
+        if self.intersects(ImageUsage::SAMPLED | ImageUsage::INPUT_ATTACHMENT) {
+            result |= wgpu::TextureUsage::SAMPLED;
+        }
+        if self.contains(ImageUsage::STORAGE) {
+            result |= wgpu::TextureUsage::STORAGE;
+        }
+        if self.intersects(
+            ImageUsage::COLOR_ATTACHMENT
+                | ImageUsage::DEPTH_STENCIL_ATTACHMENT
+                | ImageUsage::TRANSIENT_ATTACHMENT,
+        ) {
+            result |= wgpu::TextureUsage::RENDER_ATTACHMENT;
+        }
+
+        result
+    }
+}
+
+impl ToWgpu<wgpu::BufferUsage> for BufferUsage {
+    fn to_wgpu(self) -> wgpu::BufferUsage {
+        let mut result = wgpu::BufferUsage::empty();
+
+        if self.contains(BufferUsage::TRANSFER_SRC) {
+            result |= wgpu::BufferUsage::COPY_SRC;
+        }
+        if self.contains(BufferUsage::TRANSFER_DST) {
+            result |= wgpu::BufferUsage::COPY_DST;
+        }
+        if self.contains(BufferUsage::UNIFORM) {
+            result |= wgpu::BufferUsage::UNIFORM;
+        }
+        if self.contains(BufferUsage::STORAGE) {
+            result |= wgpu::BufferUsage::STORAGE;
+        }
+        if self.contains(BufferUsage::INDEX) {
+            result |= wgpu::BufferUsage::INDEX;
+        }
+        if self.contains(BufferUsage::VERTEX) {
+            result |= wgpu::BufferUsage::VERTEX;
+        }
+        if self.contains(BufferUsage::INDIRECT) {
+            result |= wgpu::BufferUsage::INDIRECT;
+        }
+
+        result
+    }
+}
+
+/// Returns dimension of texture and its size.
+/// Layers of 1D and 2D textures are counted as depth.
+pub(super) fn texture_size(
+    extent: ImageExtent,
+    layers: u32,
+) -> (wgpu::TextureDimension, wgpu::Extent3d) {
+    match extent {
+        ImageExtent::D1 { width } => (
+            wgpu::TextureDimension::D1,
+            wgpu::Extent3d {
+                width,
+                height: 1,
+                depth: layers,
+            },
+        ),
+        ImageExtent::D2 { width, height } => (
+            wgpu::TextureDimension::D2,
+            wgpu::Extent3d {
+                width,
+                height,
+                depth: layers,
+            },
+        ),
+        ImageExtent::D3 {
+            width,
+            height,
+            depth,
+        } => (
+            wgpu::TextureDimension::D3,
+            wgpu::Extent3d {
+                width,
+                height,
+                depth,
+            },
+        ),
+    }
+}
+
+pub(super) fn view_dimension(
+    kind: ImageViewKind,
+    layers: u32,
+) -> wgpu::TextureViewDimension {
+    match kind {
+        ImageViewKind::D1 => wgpu::TextureViewDimension::D1,
+        ImageViewKind::D2 if layers > 1 => wgpu::TextureViewDimension::D2Array,
+        ImageViewKind::D2 => wgpu::TextureViewDimension::D2,
+        ImageViewKind::D3 => wgpu::TextureViewDimension::D3,
+        ImageViewKind::Cube if layers > 6 => {
+            wgpu::TextureViewDimension::CubeArray
+        }
+        ImageViewKind::Cube => wgpu::TextureViewDimension::Cube,
+    }
+}
+
+impl ToWgpu<wgpu::TextureAspect> for AspectFlags {
+    fn to_wgpu(self) -> wgpu::TextureAspect {
+        if self == AspectFlags::DEPTH {
+            wgpu::TextureAspect::DepthOnly
+        } else if self == AspectFlags::STENCIL {
+            wgpu::TextureAspect::StencilOnly
+        } else {
+            wgpu::TextureAspect::All
+        }
+    }
+}
+
+impl ToWgpu<wgpu::Extent3d> for Extent3d {
+    fn to_wgpu(self) -> wgpu::Extent3d {
+        wgpu::Extent3d {
+            width: self.width,
+            height: self.height,
+            depth: self.depth,
+        }
+    }
+}
+
+/// Converts offset into texture origin.
+/// Offsets of images are never negative.
+impl ToWgpu<wgpu::Origin3d> for Offset3d {
+    fn to_wgpu(self) -> wgpu::Origin3d {
+        wgpu::Origin3d {
+            x: self.x as u32,
+            y: self.y as u32,
+            z: self.z as u32,
+        }
+    }
+}
+
+impl ToWgpu<u32> for Samples {
+    fn to_wgpu(self) -> u32 {
+        match self {
+            Samples::Samples1 => 1,
+            Samples::Samples2 => 2,
+            Samples::Samples4 => 4,
+            Samples::Samples8 => 8,
+            Samples::Samples16 => 16,
+            Samples::Samples32 => 32,
+            Samples::Samples64 => 64,
+        }
+    }
+}
+
+impl ToWgpu<wgpu::FilterMode> for Filter {
+    fn to_wgpu(self) -> wgpu::FilterMode {
+        match self {
+            Filter::Nearest => wgpu::FilterMode::Nearest,
+            Filter::Linear => wgpu::FilterMode::Linear,
+        }
+    }
+}
+
+impl ToWgpu<wgpu::FilterMode> for MipmapMode {
+    fn to_wgpu(self) -> wgpu::FilterMode {
+        match self {
+            MipmapMode::Nearest => wgpu::FilterMode::Nearest,
+            MipmapMode::Linear => wgpu::FilterMode::Linear,
+        }
+    }
+}
+
+impl ToWgpu<wgpu::AddressMode> for SamplerAddressMode {
+    fn to_wgpu(self) -> wgpu::AddressMode {
+        match self {
+            SamplerAddressMode::Repeat => wgpu::AddressMode::Repeat,
+            SamplerAddressMode::MirroredRepeat => {
+                wgpu::AddressMode::MirrorRepeat
+            }
+            SamplerAddressMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToBorder => {
+                wgpu::AddressMode::ClampToBorder
+            }
+            // There is no mirrored clamp in WebGPU.
+            // Both modes match for coordinates in [-1, 1] range.
+            SamplerAddressMode::MirrorClampToEdge => {
+                wgpu::AddressMode::MirrorRepeat
+            }
+        }
+    }
+}
+
+impl ToWgpu<wgpu::SamplerBorderColor> for BorderColor {
+    fn to_wgpu(self) -> wgpu::SamplerBorderColor {
+        match self {
+            BorderColor::FloatTransparentBlack
+            | BorderColor::IntTransparentBlack => {
+                wgpu::SamplerBorderColor::TransparentBlack
+            }
+            BorderColor::FloatOpaqueBlack | BorderColor::IntOpaqueBlack => {
+                wgpu::SamplerBorderColor::OpaqueBlack
+            }
+            BorderColor::FloatOpaqueWhite | BorderColor::IntOpaqueWhite => {
+                wgpu::SamplerBorderColor::OpaqueWhite
+            }
+        }
+    }
+}
+
+impl ToWgpu<wgpu::CompareFunction> for CompareOp {
+    fn to_wgpu(self) -> wgpu::CompareFunction {
+        match self {
+            CompareOp::Never => wgpu::CompareFunction::Never,
+            CompareOp::Less => wgpu::CompareFunction::Less,
+            CompareOp::Equal => wgpu::CompareFunction::Equal,
+            CompareOp::LessOrEqual => wgpu::CompareFunction::LessEqual,
+            CompareOp::Greater => wgpu::CompareFunction::Greater,
+            CompareOp::NotEqual => wgpu::CompareFunction::NotEqual,
+            CompareOp::GreaterOrEqual => wgpu::CompareFunction::GreaterEqual,
+            CompareOp::Always => wgpu::CompareFunction::Always,
+        }
+    }
+}
+
+/// Triangle fans are not supported by WebGPU.
+impl ToWgpu<Option<wgpu::PrimitiveTopology>> for PrimitiveTopology {
+    fn to_wgpu(self) -> Option<wgpu::PrimitiveTopology> {
+        Some(match self {
+            PrimitiveTopology::PointList => wgpu::PrimitiveTopology::PointList,
+            PrimitiveTopology::LineList => wgpu::PrimitiveTopology::LineList,
+            PrimitiveTopology::LineStrip => wgpu::PrimitiveTopology::LineStrip,
+            PrimitiveTopology::TriangleList => {
+                wgpu::PrimitiveTopology::TriangleList
+            }
+            PrimitiveTopology::TriangleStrip => {
+                wgpu::PrimitiveTopology::TriangleStrip
+            }
+            PrimitiveTopology::TriangleFan => return None,
+        })
+    }
+}
+
+impl ToWgpu<wgpu::FrontFace> for FrontFace {
+    fn to_wgpu(self) -> wgpu::FrontFace {
+        match self {
+            FrontFace::Clockwise => wgpu::FrontFace::Cw,
+            FrontFace::CounterClockwise => wgpu::FrontFace::Ccw,
+        }
+    }
+}
+
+impl ToWgpu<wgpu::PolygonMode> for PolygonMode {
+    fn to_wgpu(self) -> wgpu::PolygonMode {
+        match self {
+            PolygonMode::Fill => wgpu::PolygonMode::Fill,
+            PolygonMode::Line => wgpu::PolygonMode::Line,
+            PolygonMode::Point => wgpu::PolygonMode::Point,
+        }
+    }
+}
+
+impl ToWgpu<wgpu::StencilOperation> for StencilOp {
+    fn to_wgpu(self) -> wgpu::StencilOperation {
+        match self {
+            StencilOp::Keep => wgpu::StencilOperation::Keep,
+            StencilOp::Zero => wgpu::StencilOperation::Zero,
+            StencilOp::Replace => wgpu::StencilOperation::Replace,
+            StencilOp::IncrementAndClamp => {
+                wgpu::StencilOperation::IncrementClamp
+            }
+            StencilOp::DecrementAndClamp => {
+                wgpu::StencilOperation::DecrementClamp
+            }
+            StencilOp::Invert => wgpu::StencilOperation::Invert,
+            StencilOp::IncrementAndWrap => {
+                wgpu::StencilOperation::IncrementWrap
+            }
+            StencilOp::DecrementAndWrap => {
+                wgpu::StencilOperation::DecrementWrap
+            }
+        }
+    }
+}
+
+/// WebGPU has single blend constant factor for color and alpha.
+impl ToWgpu<wgpu::BlendFactor> for BlendFactor {
+    fn to_wgpu(self) -> wgpu::BlendFactor {
+        match self {
+            BlendFactor::Zero => wgpu::BlendFactor::Zero,
+            BlendFactor::One => wgpu::BlendFactor::One,
+            BlendFactor::SrcColor => wgpu::BlendFactor::SrcColor,
+            BlendFactor::OneMinusSrcColor => {
+                wgpu::BlendFactor::OneMinusSrcColor
+            }
+            BlendFactor::DstColor => wgpu::BlendFactor::DstColor,
+            BlendFactor::OneMinusDstColor => {
+                wgpu::BlendFactor::OneMinusDstColor
+            }
+            BlendFactor::SrcAlpha => wgpu::BlendFactor::SrcAlpha,
+            BlendFactor::OneMinusSrcAlpha => {
+                wgpu::BlendFactor::OneMinusSrcAlpha
+            }
+            BlendFactor::DstAlpha => wgpu::BlendFactor::DstAlpha,
+            BlendFactor::OneMinusDstAlpha => {
+                wgpu::BlendFactor::OneMinusDstAlpha
+            }
+            BlendFactor::ConstantColor | BlendFactor::ConstantAlpha => {
+                wgpu::BlendFactor::BlendColor
+            }
+            BlendFactor::OneMinusConstantColor
+            | BlendFactor::OneMinusConstantAlpha => {
+                wgpu::BlendFactor::OneMinusBlendColor
+            }
+            BlendFactor::SrcAlphaSaturate => {
+                wgpu::BlendFactor::SrcAlphaSaturated
+            }
+        }
+    }
+}
+
+impl ToWgpu<wgpu::BlendOperation> for BlendOp {
+    fn to_wgpu(self) -> wgpu::BlendOperation {
+        match self {
+            BlendOp::Add => wgpu::BlendOperation::Add,
+            BlendOp::Subtract => wgpu::BlendOperation::Subtract,
+            BlendOp::ReverseSubtract => wgpu::BlendOperation::ReverseSubtract,
+            BlendOp::Min => wgpu::BlendOperation::Min,
+            BlendOp::Max => wgpu::BlendOperation::Max,
+        }
+    }
+}
+
+impl ToWgpu<wgpu::ColorWrite> for ComponentMask {
+    fn to_wgpu(self) -> wgpu::ColorWrite {
+        let mut result = wgpu::ColorWrite::empty();
+
+        if self.contains(ComponentMask::R) {
+            result |= wgpu::ColorWrite::RED;
+        }
+        if self.contains(ComponentMask::G) {
+            result |= wgpu::ColorWrite::GREEN;
+        }
+        if self.contains(ComponentMask::B) {
+            result |= wgpu::ColorWrite::BLUE;
+        }
+        if self.contains(ComponentMask::A) {
+            result |= wgpu::ColorWrite::ALPHA;
+        }
+
+        result
+    }
+}
+
+impl ToWgpu<wgpu::ShaderStage> for ShaderStageFlags {
+    fn to_wgpu(self) -> wgpu::ShaderStage {
+        let mut result = wgpu::ShaderStage::NONE;
+
+        if self.contains(ShaderStageFlags::VERTEX) {
+            result |= wgpu::ShaderStage::VERTEX;
+        }
+        if self.contains(ShaderStageFlags::FRAGMENT) {
+            result |= wgpu::ShaderStage::FRAGMENT;
+        }
+        if self.contains(ShaderStageFlags::COMPUTE) {
+            result |= wgpu::ShaderStage::COMPUTE;
+        }
+
+        result
+    }
+}
+
+impl ToWgpu<wgpu::InputStepMode> for VertexInputRate {
+    fn to_wgpu(self) -> wgpu::InputStepMode {
+        match self {
+            VertexInputRate::Vertex => wgpu::InputStepMode::Vertex,
+            VertexInputRate::Instance => wgpu::InputStepMode::Instance,
+        }
+    }
+}
+
+impl ToWgpu<wgpu::IndexFormat> for IndexType {
+    fn to_wgpu(self) -> wgpu::IndexFormat {
+        match self {
+            IndexType::U16 => wgpu::IndexFormat::Uint16,
+            IndexType::U32 => wgpu::IndexFormat::Uint32,
+        }
+    }
+}
+
+/// Relaxed FIFO is not supported by WebGPU.
+impl ToWgpu<Option<wgpu::PresentMode>> for PresentMode {
+    fn to_wgpu(self) -> Option<wgpu::PresentMode> {
+        match self {
+            PresentMode::Immediate => Some(wgpu::PresentMode::Immediate),
+            PresentMode::Mailbox => Some(wgpu::PresentMode::Mailbox),
+            PresentMode::Fifo => Some(wgpu::PresentMode::Fifo),
+            PresentMode::FifoRelaxed => None,
+        }
+    }
+}
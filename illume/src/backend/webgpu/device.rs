@@ -0,0 +1,1351 @@
+use {
+    super::{
+        convert::{format_properties, texture_size, ToWgpu as _},
+        physical::{
+            image_format_properties, image_format_usage, max_image_dimension,
+            MAX_IMAGE_LAYERS,
+        },
+        poll_once,
+        resources::{BoundSet, Descriptor, ImageMemory, MappableBuffer},
+    },
+    crate::{
+        accel::{
+            AccelerationStructure, AccelerationStructureBuildFlags,
+            AccelerationStructureBuildSizesInfo,
+            AccelerationStructureGeometryInfo, AccelerationStructureInfo,
+            AccelerationStructureLevel,
+        },
+        align_up, arith_le, arith_ne, assert_object,
+        buffer::{Buffer, BufferCaptureAddress, BufferInfo, BufferUsage},
+        descriptor::{
+            CopyDescriptorSet, CreateDescriptorSetError, DescriptorPoolConfig,
+            DescriptorSet, DescriptorSetInfo, DescriptorSetLayout,
+            DescriptorSetLayoutInfo, Descriptors, WriteDescriptorSet,
+        },
+        fence::{Fence, WaitError},
+        format::{
+            Format, FormatFeatures, FormatProperties, ImageFormatProperties,
+        },
+        framebuffer::{Framebuffer, FramebufferInfo},
+        image::{Image, ImageExtent, ImageInfo, ImageUsage, Samples},
+        memory::{MemoryStats, MemoryUsage},
+        physical::Feature,
+        pipeline::{
+            ComputePipeline, ComputePipelineInfo, GraphicsPipeline,
+            GraphicsPipelineInfo, PipelineLayout, PipelineLayoutInfo,
+            RayTracingPipeline, RayTracingPipelineInfo, ShaderBindingTable,
+            ShaderBindingTableInfo,
+        },
+        query::{QueryPool, QueryPoolInfo},
+        render_pass::{RenderPass, RenderPassInfo},
+        sampler::{Sampler, SamplerAddressMode, SamplerInfo},
+        semaphore::Semaphore,
+        shader::{
+            CreateShaderModuleError, InvalidShader, ShaderLanguage,
+            ShaderModule, ShaderModuleInfo,
+        },
+        surface::{Surface, SurfaceError},
+        swapchain::Swapchain,
+        view::{ImageView, ImageViewInfo, ImageViewKind},
+        CreateImageError, DeviceAddress, ImageSize, MapError, OutOfMemory,
+    },
+    bytemuck::Pod,
+    std::{
+        borrow::Cow,
+        convert::TryFrom as _,
+        fmt::{self, Debug},
+        mem::{size_of_val, MaybeUninit},
+        num::NonZeroU8,
+        ops::Range,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc, Weak,
+        },
+    },
+    wgpu::util::DeviceExt as _,
+};
+
+/// Size of memory reported as device-local.
+/// WebGPU doesn't expose memory heaps.
+const DEVICE_LOCAL_MEMORY: u64 = 1 << 32;
+
+/// Alignment mask for sizes and offsets of buffer copies.
+const COPY_ALIGN_MASK: u64 = wgpu::COPY_BUFFER_ALIGNMENT - 1;
+
+pub(crate) struct Inner {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    features: Vec<Feature>,
+    allocated: AtomicU64,
+
+    /// Bind group bound in place of descriptor sets that are not bound.
+    empty_set: BoundSet,
+}
+
+// Browsers run `wgpu` on single thread.
+#[cfg(target_arch = "wasm32")]
+unsafe impl Send for Inner {}
+#[cfg(target_arch = "wasm32")]
+unsafe impl Sync for Inner {}
+
+impl Debug for Inner {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("Device")
+            .field("features", &self.features)
+            .field("allocated", &self.allocated)
+            .finish()
+    }
+}
+
+#[derive(Clone)]
+pub struct WeakDevice {
+    inner: Weak<Inner>,
+}
+
+impl Debug for WeakDevice {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.inner.upgrade() {
+            Some(device) => device.fmt(fmt),
+            None => write!(fmt, "Destroyed device: {:p}", self.inner.as_ptr()),
+        }
+    }
+}
+
+impl WeakDevice {
+    pub fn upgrade(&self) -> Option<Device> {
+        self.inner.upgrade().map(|inner| Device { inner })
+    }
+
+    pub fn is(&self, device: &Device) -> bool {
+        self.inner.as_ptr() == &*device.inner
+    }
+}
+
+impl PartialEq<WeakDevice> for WeakDevice {
+    fn eq(&self, weak: &WeakDevice) -> bool {
+        std::ptr::eq(weak.inner.as_ptr(), self.inner.as_ptr())
+    }
+}
+
+impl PartialEq<WeakDevice> for Device {
+    fn eq(&self, weak: &WeakDevice) -> bool {
+        std::ptr::eq(weak.inner.as_ptr(), &*self.inner)
+    }
+}
+
+impl PartialEq<WeakDevice> for &'_ WeakDevice {
+    fn eq(&self, weak: &WeakDevice) -> bool {
+        std::ptr::eq(weak.inner.as_ptr(), self.inner.as_ptr())
+    }
+}
+
+impl PartialEq<WeakDevice> for &'_ Device {
+    fn eq(&self, weak: &WeakDevice) -> bool {
+        std::ptr::eq(weak.inner.as_ptr(), &*self.inner)
+    }
+}
+
+/// Opaque value that represents graphics API device.
+/// It is used to manage (create, destroy, check state) most of the device
+/// resources.
+#[derive(Clone)]
+#[repr(transparent)]
+pub struct Device {
+    inner: Arc<Inner>,
+}
+
+impl Debug for Device {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if fmt.alternate() {
+            self.inner.fmt(fmt)
+        } else {
+            write!(fmt, "WgpuDevice({:p})", &*self.inner)
+        }
+    }
+}
+
+impl Device {
+    pub(super) fn new(
+        features: Vec<Feature>,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+    ) -> Self {
+        let layout = Arc::new(device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                label: None,
+                entries: &[],
+            },
+        ));
+
+        let group =
+            Arc::new(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: None,
+                layout: &layout,
+                entries: &[],
+            }));
+
+        Device {
+            inner: Arc::new(Inner {
+                device,
+                queue,
+                features,
+                allocated: AtomicU64::new(0),
+                empty_set: BoundSet {
+                    layout,
+                    group,
+                    dynamic_count: 0,
+                },
+            }),
+        }
+    }
+
+    pub(crate) fn downgrade(&self) -> WeakDevice {
+        WeakDevice {
+            inner: Arc::downgrade(&self.inner),
+        }
+    }
+
+    pub(super) fn handle(&self) -> &wgpu::Device {
+        &self.inner.device
+    }
+
+    pub(super) fn queue(&self) -> &wgpu::Queue {
+        &self.inner.queue
+    }
+
+    pub(super) fn empty_set(&self) -> &BoundSet {
+        &self.inner.empty_set
+    }
+
+    fn is_enabled(&self, feature: Feature) -> bool {
+        self.inner.features.contains(&feature)
+    }
+
+    /// Called when buffer is destroyed.
+    pub(super) fn free_memory(&self, size: u64) {
+        self.inner.allocated.fetch_sub(size, Ordering::Relaxed);
+    }
+
+    /// Returns statistics of device memory usage.
+    /// Only memory of buffers is counted.
+    pub fn memory_stats(&self) -> MemoryStats {
+        MemoryStats {
+            allocated: self.inner.allocated.load(Ordering::Relaxed),
+            device_local: DEVICE_LOCAL_MEMORY,
+        }
+    }
+
+    #[tracing::instrument]
+    pub fn create_buffer(
+        &self,
+        info: BufferInfo,
+    ) -> Result<Buffer, OutOfMemory> {
+        self.create_buffer_impl(info, None)
+    }
+
+    /// Creates buffer with host copy of its content.
+    /// Content is read back when buffer is mapped
+    /// if `memory_usage` contains `DOWNLOAD`.
+    #[tracing::instrument]
+    pub fn create_mappable_buffer(
+        &self,
+        info: BufferInfo,
+        memory_usage: MemoryUsage,
+    ) -> Result<MappableBuffer, OutOfMemory> {
+        let buffer = self.create_buffer_impl(info, None)?;
+        let size = buffer_size(info.size).ok_or(OutOfMemory)?;
+        let size = usize::try_from(size).map_err(|_| OutOfMemory)?;
+
+        Ok(MappableBuffer::from_buffer(buffer, memory_usage, size))
+    }
+
+    fn create_buffer_impl(
+        &self,
+        info: BufferInfo,
+        data: Option<&[u8]>,
+    ) -> Result<Buffer, OutOfMemory> {
+        assert!(info.is_valid());
+
+        if info.usage.contains(BufferUsage::DEVICE_ADDRESS) {
+            assert!(
+                self.is_enabled(Feature::BufferDeviceAddress),
+                "`BufferDeviceAddress` feature is not enabled"
+            );
+        }
+
+        // Buffers are always copied to and from,
+        // to write host data and read it back.
+        let usage = info.usage.to_wgpu()
+            | wgpu::BufferUsage::COPY_SRC
+            | wgpu::BufferUsage::COPY_DST;
+
+        let buffer = match data {
+            Some(contents) => self.inner.device.create_buffer_init(
+                &wgpu::util::BufferInitDescriptor {
+                    label: None,
+                    contents,
+                    usage,
+                },
+            ),
+            None => self.inner.device.create_buffer(&wgpu::BufferDescriptor {
+                label: None,
+                size: buffer_size(info.size).ok_or(OutOfMemory)?,
+                usage,
+                mapped_at_creation: false,
+            }),
+        };
+
+        self.inner.allocated.fetch_add(info.size, Ordering::Relaxed);
+
+        tracing::debug!("Buffer created");
+        Ok(Buffer::new(info, self.downgrade(), buffer))
+    }
+
+    /// Creates buffer with initial data.
+    #[tracing::instrument(skip(data))]
+    pub fn create_buffer_static<T: 'static>(
+        &self,
+        info: BufferInfo,
+        data: &[T],
+    ) -> Result<Buffer, OutOfMemory>
+    where
+        T: Pod,
+    {
+        assert!(info.is_valid());
+        if arith_ne(info.size, size_of_val(data)) {
+            panic!(
+                "Buffer size {} does not match data size {}",
+                info.size,
+                size_of_val(data)
+            );
+        }
+
+        self.create_buffer_impl(info, Some(bytemuck::cast_slice(data)))
+    }
+
+    #[tracing::instrument]
+    pub fn create_fence(&self) -> Result<Fence, OutOfMemory> {
+        Ok(Fence::new(self.downgrade()))
+    }
+
+    /// Creates framebuffer for specified render pass from views.
+    #[tracing::instrument]
+    pub fn create_framebuffer(
+        &self,
+        info: FramebufferInfo,
+    ) -> Result<Framebuffer, OutOfMemory> {
+        for view in &info.views {
+            assert_owner!(view, self);
+        }
+
+        assert_owner!(info.render_pass, self);
+
+        assert!(
+            info.views.iter()
+                .all(|view| view.info().view_kind == ImageViewKind::D2),
+            "All image views for Framebuffer must have `view_kind == ImageViewKind::D2`",
+        );
+
+        assert!(
+            info.views.iter()
+                .all(|view| view.info().image.info().extent.into_2d() >= info.extent),
+            "All image views for Framebuffer must be at least as large as framebuffer extent",
+        );
+
+        assert_eq!(
+            info.views.len(),
+            info.render_pass.info().attachments.len(),
+            "Framebuffer must have view for each render pass attachment",
+        );
+
+        Ok(Framebuffer::new(info, self.downgrade()))
+    }
+
+    /// Creates graphics pipeline.
+    /// `wgpu` pipelines are created when it is first used in submitted
+    /// commands, as they depend on dynamic states and bound descriptors.
+    #[tracing::instrument]
+    pub fn create_graphics_pipeline(
+        &self,
+        info: GraphicsPipelineInfo,
+    ) -> Result<GraphicsPipeline, OutOfMemory> {
+        assert_owner!(info.layout, self);
+        assert_owner!(info.render_pass, self);
+        assert_owner!(info.vertex_shader.module(), self);
+
+        if let Some(fragment_shader) = info
+            .rasterizer
+            .as_ref()
+            .and_then(|r| r.fragment_shader.as_ref())
+        {
+            assert_owner!(fragment_shader.module(), self);
+        }
+
+        assert!(
+            (info.subpass as usize) < info.render_pass.info().subpasses.len(),
+            "Subpass {} is out of bounds",
+            info.subpass
+        );
+
+        if !info.dynamic_states.is_empty() {
+            assert!(
+                self.is_enabled(Feature::ExtendedDynamicState),
+                "`ExtendedDynamicState` feature is not enabled"
+            );
+        }
+
+        Ok(GraphicsPipeline::new(
+            info,
+            self.downgrade(),
+            Default::default(),
+        ))
+    }
+
+    /// Creates compute pipeline.
+    /// `wgpu` pipeline is created when it is first used in submitted commands.
+    #[tracing::instrument]
+    pub fn create_compute_pipeline(
+        &self,
+        info: ComputePipelineInfo,
+    ) -> Result<ComputePipeline, OutOfMemory> {
+        assert_owner!(info.shader.module(), self);
+        assert_owner!(info.layout, self);
+
+        Ok(ComputePipeline::new(
+            info,
+            self.downgrade(),
+            Default::default(),
+        ))
+    }
+
+    /// Creates image.
+    ///
+    /// Clears are performed with render passes,
+    /// so transfer destinations that can be rendered to
+    /// are created with render attachment usage.
+    #[tracing::instrument]
+    pub fn create_image(
+        &self,
+        info: ImageInfo,
+    ) -> Result<Image, CreateImageError> {
+        info.validate(self)?;
+
+        let format = match info.format.to_wgpu() {
+            Some(format) => format,
+            None => return Err(CreateImageError::Unsupported { info }),
+        };
+
+        let mut usage = info.usage.to_wgpu();
+
+        if info.usage.contains(ImageUsage::TRANSFER_DST)
+            && format_properties(info.format).optimal_tiling.intersects(
+                FormatFeatures::COLOR_ATTACHMENT
+                    | FormatFeatures::DEPTH_STENCIL_ATTACHMENT,
+            )
+        {
+            usage |= wgpu::TextureUsage::RENDER_ATTACHMENT;
+        }
+
+        let (dimension, size) = texture_size(info.extent, info.layers);
+
+        let texture =
+            self.inner.device.create_texture(&wgpu::TextureDescriptor {
+                label: None,
+                size,
+                mip_level_count: info.levels,
+                sample_count: info.samples.to_wgpu(),
+                dimension,
+                format,
+                usage,
+            });
+
+        tracing::debug!("Image created");
+        Ok(Image::new(
+            info,
+            self.downgrade(),
+            ImageMemory::Texture(texture),
+        ))
+    }
+
+    /// Creates image. `wgpu` manages texture memory itself.
+    #[tracing::instrument]
+    pub fn create_image_with_memory_usage(
+        &self,
+        info: ImageInfo,
+        _memory_usage: MemoryUsage,
+    ) -> Result<Image, CreateImageError> {
+        self.create_image(info)
+    }
+
+    #[tracing::instrument]
+    pub fn create_image_view(
+        &self,
+        info: ImageViewInfo,
+    ) -> Result<ImageView, OutOfMemory> {
+        assert_owner!(info.image, self);
+
+        let view = info.image.create_view(info.view_kind, &info.subresource);
+
+        Ok(ImageView::new(info, self.downgrade(), view))
+    }
+
+    #[tracing::instrument]
+    pub fn create_pipeline_layout(
+        &self,
+        info: PipelineLayoutInfo,
+    ) -> Result<PipelineLayout, OutOfMemory> {
+        for set in &info.sets {
+            assert_owner!(set, self);
+        }
+
+        Ok(PipelineLayout::new(
+            info,
+            self.downgrade(),
+            Default::default(),
+        ))
+    }
+
+    #[tracing::instrument]
+    pub fn create_render_pass(
+        &self,
+        info: RenderPassInfo,
+    ) -> Result<RenderPass, CreateRenderPassError> {
+        for (subpass, s) in info.subpasses.iter().enumerate() {
+            for (index, &attachment) in s.colors.iter().enumerate() {
+                if attachment >= info.attachments.len() {
+                    return Err(
+                        CreateRenderPassError::ColorAttachmentReferenceOutOfBound {
+                            subpass,
+                            index,
+                            attachment,
+                        },
+                    );
+                }
+            }
+
+            if let Some(attachment) = s.depth {
+                if attachment >= info.attachments.len() {
+                    return Err(
+                        CreateRenderPassError::DepthAttachmentReferenceOutOfBound {
+                            subpass,
+                            attachment,
+                        },
+                    );
+                }
+            }
+
+            check_subpass_resolves(&info, subpass)?;
+        }
+
+        Ok(RenderPass::new(info, self.downgrade()))
+    }
+
+    #[tracing::instrument]
+    pub fn create_semaphore(&self) -> Result<Semaphore, OutOfMemory> {
+        Ok(Semaphore::new(self.downgrade()))
+    }
+
+    /// Creates shader module from SPIR-V code.
+    /// Module is translated and validated by `wgpu`.
+    #[tracing::instrument(skip(info))]
+    pub fn create_shader_module(
+        &self,
+        info: ShaderModuleInfo,
+    ) -> Result<ShaderModule, CreateShaderModuleError> {
+        let code = match info.language {
+            ShaderLanguage::SPIRV => &*info.code,
+            _ => {
+                return Err(
+                    CreateShaderModuleError::UnsupportedShaderLanguage {
+                        language: info.language,
+                    },
+                )
+            }
+        };
+
+        if code.is_empty() {
+            return Err(CreateShaderModuleError::InvalidShader {
+                source: InvalidShader::EmptySource,
+            });
+        }
+
+        if code.len() & 3 > 0 {
+            return Err(CreateShaderModuleError::InvalidShader {
+                source: InvalidShader::SizeIsNotMultipleOfFour,
+            });
+        }
+
+        let magic = u32::from_ne_bytes([code[0], code[1], code[2], code[3]]);
+
+        if magic != 0x07230203 {
+            return Err(CreateShaderModuleError::InvalidShader {
+                source: InvalidShader::WrongMagic { found: magic },
+            });
+        }
+
+        // Code is not guaranteed to be aligned for `u32`.
+        let words: Vec<u32> = code
+            .chunks_exact(4)
+            .map(|word| {
+                u32::from_ne_bytes([word[0], word[1], word[2], word[3]])
+            })
+            .collect();
+
+        let module = self.inner.device.create_shader_module(
+            &wgpu::ShaderModuleDescriptor {
+                label: None,
+                source: wgpu::ShaderSource::SpirV(Cow::Owned(words)),
+                flags: wgpu::ShaderFlags::VALIDATION,
+            },
+        );
+
+        Ok(ShaderModule::new(info, self.downgrade(), module))
+    }
+
+    /// Creates swapchain for surface.
+    /// Images are presented into the window, or canvas on the web.
+    #[tracing::instrument]
+    pub fn create_swapchain(
+        &self,
+        surface: &mut Surface,
+    ) -> Result<Swapchain, SurfaceError> {
+        assert!(
+            self.is_enabled(Feature::SurfacePresentation),
+            "`Feature::SurfacePresentation` must be enabled in order to create a `Swapchain`"
+        );
+
+        Swapchain::new(surface, self)
+    }
+
+    #[tracing::instrument]
+    pub fn reset_fences(&self, fences: &[&Fence]) -> Result<(), WaitError> {
+        for fence in fences {
+            assert_owner!(fence, self);
+            fence.reset();
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument]
+    pub fn is_fence_signalled(&self, fence: &Fence) -> Result<bool, WaitError> {
+        assert_owner!(fence, self);
+        Ok(fence.is_signalled())
+    }
+
+    /// Waits until submitted work completes.
+    ///
+    /// Fences are signalled on submission, as host writes made afterwards
+    /// are ordered after the work by `wgpu` queue.
+    ///
+    /// # Panics
+    ///
+    /// Panics if waited fences are not submitted
+    /// as wait would never end.
+    #[tracing::instrument]
+    pub fn wait_fences(
+        &self,
+        fences: &[&Fence],
+        all: bool,
+    ) -> Result<(), WaitError> {
+        for fence in fences {
+            assert_owner!(fence, self);
+        }
+
+        let done = if all {
+            fences.iter().all(|fence| fence.is_signalled())
+        } else {
+            fences.iter().any(|fence| fence.is_signalled())
+        };
+
+        assert!(
+            done || fences.is_empty(),
+            "Waiting for fences that are never signalled"
+        );
+
+        self.inner.device.poll(wgpu::Maintain::Wait);
+        Ok(())
+    }
+
+    #[tracing::instrument]
+    pub fn wait_idle(&self) -> Result<(), WaitError> {
+        self.inner.device.poll(wgpu::Maintain::Wait);
+        Ok(())
+    }
+
+    /// Acceleration structures are not supported by `wgpu`.
+    #[tracing::instrument]
+    pub fn get_acceleration_structure_build_sizes(
+        &self,
+        _level: AccelerationStructureLevel,
+        _flags: AccelerationStructureBuildFlags,
+        _geometry: &[AccelerationStructureGeometryInfo],
+    ) -> AccelerationStructureBuildSizesInfo {
+        assert!(
+            self.is_enabled(Feature::AccelerationStructure),
+            "`AccelerationStructure` feature is not enabled"
+        );
+
+        unreachable!("`AccelerationStructure` feature is never supported")
+    }
+
+    /// Acceleration structures are not supported by `wgpu`.
+    #[tracing::instrument]
+    pub fn create_acceleration_structure(
+        &self,
+        _info: AccelerationStructureInfo,
+    ) -> Result<AccelerationStructure, OutOfMemory> {
+        assert!(
+            self.is_enabled(Feature::AccelerationStructure),
+            "`AccelerationStructure` feature is not enabled"
+        );
+
+        unreachable!("`AccelerationStructure` feature is never supported")
+    }
+
+    #[tracing::instrument]
+    pub fn get_buffer_device_address(
+        &self,
+        buffer: &Buffer,
+    ) -> Option<DeviceAddress> {
+        assert_owner!(buffer, self);
+        buffer.address()
+    }
+
+    #[tracing::instrument]
+    pub fn get_buffer_opaque_capture_address(
+        &self,
+        buffer: &Buffer,
+    ) -> Option<BufferCaptureAddress> {
+        assert_owner!(buffer, self);
+
+        assert!(
+            self.is_enabled(Feature::BufferDeviceAddressCaptureReplay),
+            "`BufferDeviceAddressCaptureReplay` feature is not enabled"
+        );
+
+        None
+    }
+
+    #[tracing::instrument]
+    pub fn get_acceleration_structure_device_address(
+        &self,
+        acceleration_structure: &AccelerationStructure,
+    ) -> DeviceAddress {
+        assert_owner!(acceleration_structure, self);
+
+        acceleration_structure.address()
+    }
+
+    /// Ray-tracing pipelines are not supported by `wgpu`.
+    #[tracing::instrument]
+    pub fn create_ray_tracing_pipeline(
+        &self,
+        _info: RayTracingPipelineInfo,
+    ) -> Result<RayTracingPipeline, OutOfMemory> {
+        assert!(
+            self.is_enabled(Feature::RayTracingPipeline),
+            "`RayTracingPipeline` feature is not enabled"
+        );
+
+        unreachable!("`RayTracingPipeline` feature is never supported")
+    }
+
+    #[tracing::instrument]
+    pub fn create_descriptor_set_layout(
+        &self,
+        info: DescriptorSetLayoutInfo,
+    ) -> Result<DescriptorSetLayout, OutOfMemory> {
+        Ok(DescriptorSetLayout::new(
+            info,
+            self.downgrade(),
+            Default::default(),
+        ))
+    }
+
+    #[tracing::instrument]
+    pub fn create_descriptor_set(
+        &self,
+        info: DescriptorSetInfo,
+    ) -> Result<DescriptorSet, CreateDescriptorSetError> {
+        assert_owner!(info.layout, self);
+
+        Ok(DescriptorSet::new(
+            info,
+            self.downgrade(),
+            Default::default(),
+        ))
+    }
+
+    /// Descriptor sets are not pooled by this backend.
+    pub fn set_descriptor_pool_config(&self, _config: DescriptorPoolConfig) {}
+
+    /// Stores written descriptors in sets.
+    /// Bind groups are created when sets are used in submitted commands.
+    ///
+    /// # Panics
+    ///
+    /// Panics if descriptors are not supported by `wgpu`.
+    /// These are combined image samplers, acceleration structures
+    /// and arrays of samplers and buffers.
+    #[tracing::instrument]
+    pub fn update_descriptor_sets<'a>(
+        &self,
+        writes: &[WriteDescriptorSet<'a>],
+        copies: &[CopyDescriptorSet<'a>],
+    ) {
+        for write in writes {
+            assert_owner!(write.set, self);
+
+            match write.descriptors {
+                Descriptors::Sampler(samplers) => {
+                    assert_single(write, samplers.len());
+
+                    for sampler in samplers {
+                        assert_owner!(sampler, self);
+                    }
+
+                    write.set.write(
+                        write.binding,
+                        write.element,
+                        samplers.iter().cloned().map(Descriptor::Sampler),
+                    );
+                }
+                Descriptors::CombinedImageSampler(_) => {
+                    panic!(
+                        "Combined image samplers are not supported by `wgpu`, use separate samplers and images"
+                    )
+                }
+                Descriptors::SampledImage(views)
+                | Descriptors::StorageImage(views)
+                | Descriptors::InputAttachment(views) => {
+                    for (view, _) in views {
+                        assert_owner!(view, self);
+                    }
+
+                    write.set.write(
+                        write.binding,
+                        write.element,
+                        views
+                            .iter()
+                            .map(|(view, _)| Descriptor::Image(view.clone())),
+                    );
+                }
+                Descriptors::UniformBuffer(buffers)
+                | Descriptors::StorageBuffer(buffers)
+                | Descriptors::UniformBufferDynamic(buffers)
+                | Descriptors::StorageBufferDynamic(buffers) => {
+                    assert_single(write, buffers.len());
+
+                    for &(ref buffer, offset, size) in buffers {
+                        assert_owner!(buffer, self);
+                        assert_ne!(
+                            size, 0,
+                            "Cannot write 0 sized buffer range into descriptor"
+                        );
+                        assert!(
+                            offset <= buffer.info().size
+                                && size <= buffer.info().size - offset,
+                            "Buffer ({:?}) descriptor range {}..+{} is out of bounds",
+                            buffer,
+                            offset,
+                            size,
+                        );
+                    }
+
+                    write.set.write(
+                        write.binding,
+                        write.element,
+                        buffers.iter().map(|&(ref buffer, offset, size)| {
+                            Descriptor::Buffer {
+                                buffer: buffer.clone(),
+                                offset,
+                                size,
+                            }
+                        }),
+                    );
+                }
+                Descriptors::AccelerationStructure(_) => {
+                    panic!(
+                        "Acceleration structures are not supported by `wgpu`"
+                    )
+                }
+            }
+        }
+
+        for copy in copies {
+            assert_owner!(copy.src, self);
+            assert_owner!(copy.dst, self);
+
+            let descriptors =
+                copy.src
+                    .read(copy.src_binding, copy.src_element, copy.count);
+
+            for (element, descriptor) in (copy.dst_element..).zip(descriptors) {
+                copy.dst.write(copy.dst_binding, element, descriptor);
+            }
+        }
+    }
+
+    #[tracing::instrument]
+    pub fn create_sampler(
+        &self,
+        info: SamplerInfo,
+    ) -> Result<Sampler, OutOfMemory> {
+        let clamp_to_border = [
+            info.address_mode_u,
+            info.address_mode_v,
+            info.address_mode_w,
+        ]
+        .contains(&SamplerAddressMode::ClampToBorder);
+
+        let border_color = if clamp_to_border
+            && self
+                .inner
+                .device
+                .features()
+                .contains(wgpu::Features::ADDRESS_MODE_CLAMP_TO_BORDER)
+        {
+            Some(info.border_color.to_wgpu())
+        } else {
+            None
+        };
+
+        let sampler =
+            self.inner.device.create_sampler(&wgpu::SamplerDescriptor {
+                label: None,
+                address_mode_u: info.address_mode_u.to_wgpu(),
+                address_mode_v: info.address_mode_v.to_wgpu(),
+                address_mode_w: info.address_mode_w.to_wgpu(),
+                mag_filter: info.mag_filter.to_wgpu(),
+                min_filter: info.min_filter.to_wgpu(),
+                mipmap_filter: info.mipmap_mode.to_wgpu(),
+                lod_min_clamp: info.min_lod.into_inner(),
+                lod_max_clamp: info.max_lod.into_inner(),
+                compare: info.compare_op.map(|op| op.to_wgpu()),
+                anisotropy_clamp: info
+                    .max_anisotropy
+                    .and_then(|max| anisotropy_clamp(max.into_inner())),
+                border_color,
+            });
+
+        Ok(Sampler::new(info, self.downgrade(), sampler))
+    }
+
+    #[tracing::instrument]
+    pub fn create_query_pool(
+        &self,
+        info: QueryPoolInfo,
+    ) -> Result<QueryPool, OutOfMemory> {
+        Ok(QueryPool::new(info, self.downgrade()))
+    }
+
+    /// Writes zero timestamps, as `wgpu` doesn't write them.
+    /// Results are always available.
+    #[tracing::instrument(skip(timestamps))]
+    pub fn get_timestamps(
+        &self,
+        pool: &QueryPool,
+        first: u32,
+        timestamps: &mut [u64],
+    ) -> Result<bool, WaitError> {
+        assert_owner!(pool, self);
+        assert!(
+            arith_le(first as usize + timestamps.len(), pool.info().count),
+            "Query range is out of pool bounds"
+        );
+
+        for timestamp in timestamps {
+            *timestamp = 0;
+        }
+
+        Ok(true)
+    }
+
+    /// Returns number of nanoseconds per timestamp tick.
+    pub fn timestamp_period(&self) -> f32 {
+        1.0
+    }
+
+    /// Returns operations supported for format by device.
+    pub fn format_properties(&self, format: Format) -> FormatProperties {
+        format_properties(format)
+    }
+
+    /// Returns limits of images with specified format and usage.
+    /// Only dimensionality of `extent` is considered.
+    /// Returns `Ok(None)` if combination is not supported.
+    pub fn image_format_properties(
+        &self,
+        format: Format,
+        extent: ImageExtent,
+        usage: ImageUsage,
+    ) -> Result<Option<ImageFormatProperties>, OutOfMemory> {
+        Ok(image_format_properties(format, extent, usage))
+    }
+
+    /// Returns image usage supported with optimal tiling for the format.
+    pub fn image_format_usage(&self, format: Format) -> ImageUsage {
+        image_format_usage(format)
+    }
+
+    /// Returns maximum size of image dimension for the extent kind.
+    pub fn max_image_dimension(&self, extent: ImageExtent) -> ImageSize {
+        max_image_dimension(extent)
+    }
+
+    /// Returns maximum number of image array layers.
+    pub fn max_image_layers(&self) -> u32 {
+        MAX_IMAGE_LAYERS
+    }
+
+    /// Ray-tracing pipelines are not supported by `wgpu`.
+    #[tracing::instrument]
+    pub fn create_shader_binding_table(
+        &self,
+        pipeline: &RayTracingPipeline,
+        _info: ShaderBindingTableInfo,
+    ) -> Result<ShaderBindingTable, OutOfMemory> {
+        assert_owner!(pipeline, self);
+
+        unreachable!("`RayTracingPipeline` feature is never supported")
+    }
+
+    /// Maps range of the buffer's host copy.
+    ///
+    /// Content is read back first if buffer's memory usage contains
+    /// `DOWNLOAD`. Read back blocks until the buffer is copied,
+    /// on the web it fails with `MapError::MapFailed`
+    /// as browsers resolve mapping in the event loop.
+    #[tracing::instrument]
+    pub fn map_memory(
+        &self,
+        buffer: &mut MappableBuffer,
+        offset: u64,
+        size: usize,
+    ) -> Result<&mut [MaybeUninit<u8>], MapError> {
+        assert_owner!(buffer, self);
+
+        let range = host_range(buffer, offset, size);
+
+        if buffer.is_mapped() {
+            return Err(MapError::AlreadyMapped);
+        }
+
+        if buffer.memory_usage().contains(MemoryUsage::DOWNLOAD) {
+            self.read_back(buffer, range.clone())?;
+        }
+
+        buffer.map(range.clone());
+
+        let bytes = &mut buffer.memory()[range];
+
+        // `MaybeUninit<u8>` has the same layout as `u8`.
+        Ok(unsafe {
+            std::slice::from_raw_parts_mut(
+                bytes.as_mut_ptr() as *mut MaybeUninit<u8>,
+                bytes.len(),
+            )
+        })
+    }
+
+    /// Unmaps buffer, writing mapped range to the buffer
+    /// unless buffer is used only for downloading.
+    pub fn unmap_memory(&self, buffer: &mut MappableBuffer) -> bool {
+        assert_owner!(buffer, self);
+
+        match buffer.unmap() {
+            Some(range) => {
+                let usage = buffer.memory_usage();
+                if usage.contains(MemoryUsage::UPLOAD)
+                    || !usage.contains(MemoryUsage::DOWNLOAD)
+                {
+                    self.write_back(buffer, range);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    #[tracing::instrument(skip(data))]
+    pub fn write_buffer<T>(
+        &self,
+        buffer: &mut MappableBuffer,
+        offset: u64,
+        data: &[T],
+    ) -> Result<(), MapError>
+    where
+        T: Pod,
+    {
+        assert_owner!(buffer, self);
+
+        if size_of_val(data) == 0 {
+            return Ok(());
+        }
+
+        if buffer.is_mapped() {
+            return Err(MapError::AlreadyMapped);
+        }
+
+        let range = host_range(buffer, offset, size_of_val(data));
+        buffer.memory()[range.clone()]
+            .copy_from_slice(bytemuck::cast_slice(data));
+
+        self.write_back(buffer, range);
+        Ok(())
+    }
+
+    /// Reads buffer content back.
+    /// See `map_memory` for limitations.
+    #[tracing::instrument(skip(data))]
+    pub fn read_buffer<T>(
+        &self,
+        buffer: &mut MappableBuffer,
+        offset: u64,
+        data: &mut [T],
+    ) -> Result<(), MapError>
+    where
+        T: Pod,
+    {
+        assert_owner!(buffer, self);
+
+        if size_of_val(data) == 0 {
+            return Ok(());
+        }
+
+        if buffer.is_mapped() {
+            return Err(MapError::AlreadyMapped);
+        }
+
+        let range = host_range(buffer, offset, size_of_val(data));
+        self.read_back(buffer, range.clone())?;
+
+        bytemuck::cast_slice_mut(data).copy_from_slice(&buffer.memory()[range]);
+        Ok(())
+    }
+
+    /// Writes range of host copy to the buffer.
+    /// Range is extended to copy alignment.
+    fn write_back(&self, buffer: &mut MappableBuffer, range: Range<usize>) {
+        let range = copy_range(range);
+        let handle = buffer.share();
+
+        self.inner.queue.write_buffer(
+            handle.handle(),
+            range.start as u64,
+            &buffer.memory()[range],
+        );
+    }
+
+    /// Reads range of the buffer into host copy.
+    /// Range is extended to copy alignment.
+    fn read_back(
+        &self,
+        buffer: &mut MappableBuffer,
+        range: Range<usize>,
+    ) -> Result<(), MapError> {
+        let range = copy_range(range);
+        let size = (range.end - range.start) as u64;
+
+        let staging =
+            self.inner.device.create_buffer(&wgpu::BufferDescriptor {
+                label: None,
+                size,
+                usage: wgpu::BufferUsage::MAP_READ
+                    | wgpu::BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            });
+
+        let mut encoder = self.inner.device.create_command_encoder(
+            &wgpu::CommandEncoderDescriptor { label: None },
+        );
+
+        encoder.copy_buffer_to_buffer(
+            buffer.handle(),
+            range.start as u64,
+            &staging,
+            0,
+            size,
+        );
+
+        self.inner.queue.submit(Some(encoder.finish()));
+
+        let slice = staging.slice(..);
+        let request = slice.map_async(wgpu::MapMode::Read);
+        self.inner.device.poll(wgpu::Maintain::Wait);
+
+        match poll_once(request) {
+            Some(Ok(())) => {}
+            Some(Err(err)) => {
+                tracing::error!("Failed to map buffer: {:?}", err);
+                return Err(MapError::MapFailed);
+            }
+            None => {
+                tracing::error!("Buffer mapping is not resolved");
+                return Err(MapError::MapFailed);
+            }
+        }
+
+        buffer.memory()[range].copy_from_slice(&slice.get_mapped_range());
+        staging.unmap();
+
+        Ok(())
+    }
+}
+
+/// Returns size of `wgpu` buffer for buffer of specified size.
+fn buffer_size(size: u64) -> Option<u64> {
+    align_up(COPY_ALIGN_MASK, size.max(1))
+}
+
+/// Extends range of host copy to copy alignment.
+/// Host copy is large enough, as its size is aligned too.
+fn copy_range(range: Range<usize>) -> Range<usize> {
+    let mask = COPY_ALIGN_MASK as usize;
+    (range.start & !mask)..((range.end + mask) & !mask)
+}
+
+/// Returns anisotropy clamp supported by `wgpu` that doesn't exceed `max`.
+fn anisotropy_clamp(max: f32) -> Option<NonZeroU8> {
+    [16u8, 8, 4, 2]
+        .iter()
+        .copied()
+        .find(|&clamp| f32::from(clamp) <= max)
+        .and_then(NonZeroU8::new)
+}
+
+/// Panics if descriptors are written into array elements,
+/// which `wgpu` supports only for images.
+fn assert_single(write: &WriteDescriptorSet<'_>, count: usize) {
+    assert!(
+        write.element + count as u32 <= 1,
+        "Arrays of {:?} descriptors are not supported by `wgpu`",
+        write.descriptors
+    );
+}
+
+/// Returns range of buffer memory.
+///
+/// # Panics
+///
+/// Panics if range is out of buffer bounds.
+fn host_range(
+    buffer: &MappableBuffer,
+    offset: u64,
+    size: usize,
+) -> Range<usize> {
+    let end = u64::try_from(size)
+        .ok()
+        .and_then(|size| offset.checked_add(size))
+        .filter(|&end| end <= buffer.info().size);
+
+    match end {
+        Some(end) => offset as usize..end as usize,
+        None => panic!(
+            "Range {}..+{} is out of bounds of {:?}",
+            offset, size, buffer
+        ),
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CreateRenderPassError {
+    #[error(transparent)]
+    OutOfMemory {
+        #[from]
+        source: OutOfMemory,
+    },
+
+    #[error(
+        "Subpass {subpass} attachment index {attachment} for color attachment {index} is out of bounds"
+    )]
+    ColorAttachmentReferenceOutOfBound {
+        subpass: usize,
+        index: usize,
+        attachment: usize,
+    },
+
+    #[error(
+        "Subpass {subpass} attachment index {attachment} for depth attachment is out of bounds"
+    )]
+    DepthAttachmentReferenceOutOfBound { subpass: usize, attachment: usize },
+
+    #[error(
+        "Subpass {subpass} attachment index {attachment} for resolve attachment {index} is out of bounds"
+    )]
+    ResolveAttachmentReferenceOutOfBound {
+        subpass: usize,
+        index: usize,
+        attachment: usize,
+    },
+
+    #[error(
+        "Subpass {subpass} has {resolves} resolve attachments for {colors} color attachments"
+    )]
+    ResolveAttachmentCountMismatch {
+        subpass: usize,
+        colors: usize,
+        resolves: usize,
+    },
+
+    #[error(
+        "Subpass {subpass} color attachment {index} must be multisampled and resolved into single-sampled attachment"
+    )]
+    ResolveAttachmentSamplesMismatch { subpass: usize, index: usize },
+}
+
+/// Checks that resolve attachments of the subpass match its color attachments.
+fn check_subpass_resolves(
+    info: &RenderPassInfo,
+    subpass: usize,
+) -> Result<(), CreateRenderPassError> {
+    let s = &info.subpasses[subpass];
+
+    if s.resolves.is_empty() {
+        return Ok(());
+    }
+
+    if s.resolves.len() != s.colors.len() {
+        return Err(CreateRenderPassError::ResolveAttachmentCountMismatch {
+            subpass,
+            colors: s.colors.len(),
+            resolves: s.resolves.len(),
+        });
+    }
+
+    for (index, (&color, &resolve)) in
+        s.colors.iter().zip(&s.resolves).enumerate()
+    {
+        let resolve_info = info.attachments.get(resolve).ok_or(
+            CreateRenderPassError::ResolveAttachmentReferenceOutOfBound {
+                subpass,
+                index,
+                attachment: resolve,
+            },
+        )?;
+
+        // Color attachment indices are checked separately.
+        let multisampled = info
+            .attachments
+            .get(color)
+            .map_or(false, |color| color.samples != Samples::Samples1);
+
+        if !multisampled || resolve_info.samples != Samples::Samples1 {
+            return Err(
+                CreateRenderPassError::ResolveAttachmentSamplesMismatch {
+                    subpass,
+                    index,
+                },
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[allow(dead_code)]
+#[cfg(not(target_arch = "wasm32"))]
+fn check() {
+    assert_object::<Device>();
+}
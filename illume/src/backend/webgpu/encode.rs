@@ -0,0 +1,1419 @@
+use {
+    super::{
+        convert::{texel_size, ToWgpu as _},
+        device::{Device, WeakDevice},
+        new_handle,
+        resources::{blend_constants, BoundSet, ImageMemory, RasterState},
+    },
+    crate::{
+        buffer::Buffer,
+        descriptor::DescriptorSet,
+        encode::{BufferImageCopy, Command, ImageCopy, ImageResolve},
+        framebuffer::Framebuffer,
+        image::{
+            Image, ImageBlit, ImageExtent, ImageSubresourceLayers,
+            ImageSubresourceRange,
+        },
+        pipeline::{
+            ComputePipeline, Culling, DepthTest, DynamicStates, FrontFace,
+            GraphicsPipeline, PipelineLayout, PrimitiveTopology, State,
+            Viewport,
+        },
+        queue::QueueId,
+        record::RecordedCommand,
+        render_pass::{
+            AttachmentLoadOp, AttachmentStoreOp, ClearValue, RenderPass,
+        },
+        shader::ShaderStageFlags,
+        view::ImageViewKind,
+        Extent2d, Extent3d, IndexType, Offset3d, OutOfMemory, Rect2d,
+    },
+    std::{
+        fmt::{self, Debug},
+        ops::Range,
+        sync::Arc,
+    },
+    wgpu::util::DeviceExt as _,
+};
+
+/// Command buffer that keeps captured commands.
+/// They are translated into `wgpu` commands when command buffer is submitted.
+pub struct CommandBuffer {
+    handle: u64,
+    queue: QueueId,
+    owner: WeakDevice,
+    reusable: bool,
+    captured: Option<Arc<[RecordedCommand]>>,
+}
+
+impl Debug for CommandBuffer {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if fmt.alternate() {
+            fmt.debug_struct("CommandBuffer")
+                .field("handle", &self.handle)
+                .field("owner", &self.owner)
+                .field("queue", &self.queue)
+                .finish()
+        } else {
+            write!(fmt, "CommandBuffer({})", self.handle)
+        }
+    }
+}
+
+impl CommandBuffer {
+    pub(super) fn new(
+        queue: QueueId,
+        owner: WeakDevice,
+        reusable: bool,
+    ) -> Self {
+        CommandBuffer {
+            handle: new_handle(),
+            queue,
+            owner,
+            reusable,
+            captured: None,
+        }
+    }
+
+    pub(super) fn is_owned_by(
+        &self,
+        owner: &impl PartialEq<WeakDevice>,
+    ) -> bool {
+        *owner == self.owner
+    }
+
+    pub fn queue(&self) -> QueueId {
+        self.queue
+    }
+
+    /// Returns `true` if command buffer can be submitted multiple times.
+    /// Transient command buffers must be submitted only once.
+    pub fn is_reusable(&self) -> bool {
+        self.reusable
+    }
+
+    /// Commands are always captured,
+    /// `wgpu` command encoder can't outlive single submission.
+    pub(crate) fn is_capturing(&self) -> bool {
+        true
+    }
+
+    /// Stores commands captured from encoder.
+    pub(crate) fn set_captured(&mut self, commands: Arc<[RecordedCommand]>) {
+        self.captured = Some(commands);
+    }
+
+    /// Returns commands captured from encoder.
+    /// Empty if encoder was not finished.
+    pub(super) fn captured(&self) -> Arc<[RecordedCommand]> {
+        match &self.captured {
+            Some(commands) => commands.clone(),
+            None => Arc::new([]),
+        }
+    }
+
+    /// Does nothing. Commands are already captured by `set_captured`.
+    pub fn write(
+        &mut self,
+        _commands: &[Command<'_>],
+    ) -> Result<(), OutOfMemory> {
+        Ok(())
+    }
+}
+
+/// Bind group bound at set index with its dynamic offsets.
+#[derive(Clone, Debug)]
+struct BoundGroup {
+    set: BoundSet,
+    dynamic_offsets: Vec<u32>,
+}
+
+/// Push constants set by command.
+#[derive(Debug)]
+struct PushConstants {
+    stages: ShaderStageFlags,
+    offset: u32,
+    data: Vec<u8>,
+}
+
+/// Graphics states set by commands.
+/// States persist across render passes within command buffer.
+#[derive(Debug, Default)]
+struct GraphicsState {
+    pipeline: Option<GraphicsPipeline>,
+    sets: Vec<Option<BoundGroup>>,
+    vertex_buffers: Vec<Option<(Buffer, u64)>>,
+    index_buffer: Option<(Buffer, u64, IndexType)>,
+    viewport: Option<Viewport>,
+    scissor: Option<Rect2d>,
+    culling: Option<Option<Culling>>,
+    front_face: Option<FrontFace>,
+    topology: Option<PrimitiveTopology>,
+    depth_test: Option<Option<DepthTest>>,
+}
+
+#[derive(Debug, Default)]
+struct ComputeState {
+    pipeline: Option<ComputePipeline>,
+    sets: Vec<Option<BoundGroup>>,
+}
+
+#[derive(Debug)]
+enum Draw {
+    Vertices {
+        vertices: Range<u32>,
+        instances: Range<u32>,
+    },
+    Indices {
+        indices: Range<u32>,
+        vertex_offset: i32,
+        instances: Range<u32>,
+    },
+}
+
+/// Draw call with all states it depends on.
+///
+/// `wgpu` render pass borrows everything set on it,
+/// so draws of a subpass are prepared before the pass begins.
+#[derive(Debug)]
+struct DrawOp {
+    pipeline: Arc<wgpu::RenderPipeline>,
+    groups: Vec<BoundGroup>,
+    vertex_buffers: Vec<(u32, Buffer, u64)>,
+    index_buffer: Option<(Buffer, u64, IndexType)>,
+    viewport: Option<Viewport>,
+    scissor: Option<Rect2d>,
+    blend_constant: Option<wgpu::Color>,
+    stencil_reference: Option<u32>,
+    push_constants: Vec<(wgpu::ShaderStage, u32, Vec<u8>)>,
+    draw: Draw,
+}
+
+/// Render pass being recorded.
+#[derive(Debug)]
+struct PassState {
+    pass: RenderPass,
+    framebuffer: Framebuffer,
+
+    /// Clear value for each attachment cleared on load.
+    clears: Vec<Option<ClearValue>>,
+
+    /// First and last subpass that use each attachment.
+    uses: Vec<Option<(usize, usize)>>,
+
+    subpass: usize,
+    draws: Vec<DrawOp>,
+}
+
+impl PassState {
+    fn new(
+        pass: &RenderPass,
+        framebuffer: &Framebuffer,
+        clears: &[ClearValue],
+    ) -> Self {
+        let info = pass.info();
+        let mut clears = clears.iter();
+
+        let clears = info
+            .attachments
+            .iter()
+            .map(|attachment| {
+                if attachment.load_op == AttachmentLoadOp::Clear {
+                    Some(*clears.next().expect("Not enough clear values"))
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        let mut uses = vec![None; info.attachments.len()];
+
+        for (index, subpass) in info.subpasses.iter().enumerate() {
+            for &attachment in subpass
+                .colors
+                .iter()
+                .chain(subpass.depth.iter())
+                .chain(subpass.resolves.iter())
+            {
+                let range = uses[attachment].get_or_insert((index, index));
+                range.1 = index;
+            }
+        }
+
+        PassState {
+            pass: pass.clone(),
+            framebuffer: framebuffer.clone(),
+            clears,
+            uses,
+            subpass: 0,
+            draws: Vec::new(),
+        }
+    }
+
+    /// Returns value attachment is cleared with at the start of current
+    /// subpass, or `None` if its content is loaded.
+    /// Attachments with undefined content are cleared with zeros,
+    /// as `wgpu` has no such load operation.
+    fn clear_value(&self, attachment: usize) -> Option<ClearValue> {
+        match self.uses[attachment] {
+            Some((first, _)) if first == self.subpass => {}
+            _ => return None,
+        }
+
+        let info = &self.pass.info().attachments[attachment];
+
+        match info.load_op {
+            AttachmentLoadOp::Load => None,
+            AttachmentLoadOp::Clear => self.clears[attachment],
+            AttachmentLoadOp::DontCare => {
+                if info.format.is_depth() || info.format.is_stencil() {
+                    Some(ClearValue::DepthStencil(0.0, 0))
+                } else {
+                    Some(ClearValue::Color(0.0, 0.0, 0.0, 0.0))
+                }
+            }
+        }
+    }
+
+    /// Returns `false` if attachment content may be discarded at the end of
+    /// current subpass.
+    fn store(&self, attachment: usize) -> bool {
+        match self.uses[attachment] {
+            Some((_, last)) if last == self.subpass => {
+                self.pass.info().attachments[attachment].store_op
+                    == AttachmentStoreOp::Store
+            }
+            _ => true,
+        }
+    }
+}
+
+/// Translates captured commands into `wgpu` command buffer.
+pub(super) fn encode(
+    device: &Device,
+    commands: &[RecordedCommand],
+) -> wgpu::CommandBuffer {
+    let mut encoder = device.handle().create_command_encoder(
+        &wgpu::CommandEncoderDescriptor { label: None },
+    );
+
+    let mut graphics = GraphicsState::default();
+    let mut compute = ComputeState::default();
+    let mut push_constants: Vec<PushConstants> = Vec::new();
+    let mut pass: Option<PassState> = None;
+
+    for command in commands {
+        match command {
+            RecordedCommand::BeginRenderPass {
+                pass: render_pass,
+                framebuffer,
+                clears,
+            } => {
+                assert!(pass.is_none(), "Render pass is already begun");
+                pass = Some(PassState::new(render_pass, framebuffer, clears));
+            }
+            RecordedCommand::NextSubpass => {
+                let pass = pass.as_mut().expect("Render pass is not begun");
+                encode_subpass(&mut encoder, pass);
+                pass.subpass += 1;
+                pass.draws.clear();
+            }
+            RecordedCommand::EndRenderPass => {
+                let pass = pass.take().expect("Render pass is not begun");
+                encode_subpass(&mut encoder, &pass);
+            }
+            RecordedCommand::BindGraphicsPipeline { pipeline } => {
+                graphics.pipeline = Some(pipeline.clone());
+            }
+            RecordedCommand::BindComputePipeline { pipeline } => {
+                compute.pipeline = Some(pipeline.clone());
+            }
+            RecordedCommand::BindGraphicsDescriptorSets {
+                first_set,
+                sets,
+                dynamic_offsets,
+                ..
+            } => {
+                bind_sets(
+                    device,
+                    &mut graphics.sets,
+                    *first_set,
+                    sets,
+                    dynamic_offsets,
+                );
+            }
+            RecordedCommand::BindComputeDescriptorSets {
+                first_set,
+                sets,
+                dynamic_offsets,
+                ..
+            } => {
+                bind_sets(
+                    device,
+                    &mut compute.sets,
+                    *first_set,
+                    sets,
+                    dynamic_offsets,
+                );
+            }
+            RecordedCommand::SetViewport { viewport } => {
+                graphics.viewport = Some(*viewport);
+            }
+            RecordedCommand::SetScissor { scissor } => {
+                graphics.scissor = Some(*scissor);
+            }
+            // `wgpu` has single viewport.
+            RecordedCommand::SetViewports { first, viewports } => {
+                if *first == 0 {
+                    if let Some(viewport) = viewports.first() {
+                        graphics.viewport = Some(*viewport);
+                    }
+                }
+            }
+            RecordedCommand::SetScissors { first, scissors } => {
+                if *first == 0 {
+                    if let Some(scissor) = scissors.first() {
+                        graphics.scissor = Some(*scissor);
+                    }
+                }
+            }
+            RecordedCommand::SetCullMode { culling } => {
+                graphics.culling = Some(*culling);
+            }
+            RecordedCommand::SetFrontFace { front_face } => {
+                graphics.front_face = Some(*front_face);
+            }
+            RecordedCommand::SetPrimitiveTopology { topology } => {
+                graphics.topology = Some(*topology);
+            }
+            RecordedCommand::SetDepthTest { depth_test } => {
+                graphics.depth_test = Some(*depth_test);
+            }
+            RecordedCommand::BindVertexBuffers { first, buffers } => {
+                for (index, (buffer, offset)) in
+                    (*first as usize..).zip(buffers)
+                {
+                    if graphics.vertex_buffers.len() <= index {
+                        graphics.vertex_buffers.resize(index + 1, None);
+                    }
+                    graphics.vertex_buffers[index] =
+                        Some((buffer.clone(), *offset));
+                }
+            }
+            RecordedCommand::BindIndexBuffer {
+                buffer,
+                offset,
+                index_type,
+            } => {
+                graphics.index_buffer =
+                    Some((buffer.clone(), *offset, *index_type));
+            }
+            RecordedCommand::PushConstants {
+                stages,
+                offset,
+                data,
+                ..
+            } => {
+                if !device
+                    .handle()
+                    .features()
+                    .contains(wgpu::Features::PUSH_CONSTANTS)
+                {
+                    tracing::error!("Push constants are not supported");
+                    continue;
+                }
+
+                push_constants
+                    .retain(|pc| pc.stages != *stages || pc.offset != *offset);
+
+                push_constants.push(PushConstants {
+                    stages: *stages,
+                    offset: *offset,
+                    data: data.clone(),
+                });
+            }
+            RecordedCommand::Draw {
+                vertices,
+                instances,
+            } => {
+                let pass = pass.as_mut().expect("Render pass is not begun");
+                let draw = Draw::Vertices {
+                    vertices: vertices.clone(),
+                    instances: instances.clone(),
+                };
+
+                if let Some(op) =
+                    prepare_draw(device, &graphics, &push_constants, draw)
+                {
+                    pass.draws.push(op);
+                }
+            }
+            RecordedCommand::DrawIndexed {
+                indices,
+                vertex_offset,
+                instances,
+            } => {
+                let pass = pass.as_mut().expect("Render pass is not begun");
+                let draw = Draw::Indices {
+                    indices: indices.clone(),
+                    vertex_offset: *vertex_offset,
+                    instances: instances.clone(),
+                };
+
+                if let Some(op) =
+                    prepare_draw(device, &graphics, &push_constants, draw)
+                {
+                    pass.draws.push(op);
+                }
+            }
+            RecordedCommand::Dispatch { x, y, z } => {
+                let pipeline = compute
+                    .pipeline
+                    .as_ref()
+                    .expect("Compute pipeline is not bound");
+
+                let groups = pipeline_groups(
+                    device,
+                    &pipeline.info().layout,
+                    &compute.sets,
+                );
+
+                let layouts: Vec<_> = groups
+                    .iter()
+                    .map(|group| group.set.layout.clone())
+                    .collect();
+
+                let compute_pipeline =
+                    pipeline.compute_pipeline(device.handle(), &layouts);
+
+                let mut compute_pass =
+                    encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                        label: None,
+                    });
+
+                compute_pass.set_pipeline(&compute_pipeline);
+
+                for (index, group) in groups.iter().enumerate() {
+                    compute_pass.set_bind_group(
+                        index as u32,
+                        &group.set.group,
+                        &group.dynamic_offsets,
+                    );
+                }
+
+                for pc in &push_constants {
+                    if pc.stages.contains(ShaderStageFlags::COMPUTE) {
+                        compute_pass.set_push_constants(pc.offset, &pc.data);
+                    }
+                }
+
+                compute_pass.dispatch(*x, *y, *z);
+            }
+            RecordedCommand::UpdateBuffer {
+                buffer,
+                offset,
+                data,
+            } => {
+                if data.is_empty() {
+                    continue;
+                }
+
+                let staging = device.handle().create_buffer_init(
+                    &wgpu::util::BufferInitDescriptor {
+                        label: None,
+                        contents: data,
+                        usage: wgpu::BufferUsage::COPY_SRC,
+                    },
+                );
+
+                encoder.copy_buffer_to_buffer(
+                    &staging,
+                    0,
+                    buffer.handle(),
+                    *offset,
+                    data.len() as u64,
+                );
+            }
+            RecordedCommand::FillBuffer {
+                buffer,
+                offset,
+                size,
+                value,
+            } => {
+                let size = if *size == !0 {
+                    (buffer.info().size - offset) & !3
+                } else {
+                    *size
+                };
+
+                if size == 0 {
+                    continue;
+                }
+
+                let mut data = Vec::with_capacity(size as usize);
+                for _ in 0..size / 4 {
+                    data.extend_from_slice(&value.to_ne_bytes());
+                }
+
+                let staging = device.handle().create_buffer_init(
+                    &wgpu::util::BufferInitDescriptor {
+                        label: None,
+                        contents: &data,
+                        usage: wgpu::BufferUsage::COPY_SRC,
+                    },
+                );
+
+                encoder.copy_buffer_to_buffer(
+                    &staging,
+                    0,
+                    buffer.handle(),
+                    *offset,
+                    size,
+                );
+            }
+            RecordedCommand::CopyBuffer {
+                src_buffer,
+                dst_buffer,
+                regions,
+            } => {
+                for region in regions {
+                    encoder.copy_buffer_to_buffer(
+                        src_buffer.handle(),
+                        region.src_offset,
+                        dst_buffer.handle(),
+                        region.dst_offset,
+                        region.size,
+                    );
+                }
+            }
+            RecordedCommand::CopyBufferImage {
+                src_buffer,
+                dst_image,
+                regions,
+                ..
+            } => {
+                for region in regions {
+                    copy_buffer_to_image(
+                        device,
+                        &mut encoder,
+                        src_buffer,
+                        dst_image,
+                        region,
+                    );
+                }
+            }
+            RecordedCommand::CopyImage {
+                src_image,
+                dst_image,
+                regions,
+                ..
+            } => {
+                for region in regions {
+                    copy_image(&mut encoder, src_image, dst_image, region);
+                }
+            }
+            RecordedCommand::BlitImage {
+                src_image,
+                dst_image,
+                regions,
+                ..
+            } => {
+                for region in regions {
+                    blit_image(&mut encoder, src_image, dst_image, region);
+                }
+            }
+            RecordedCommand::ResolveImage {
+                src_image,
+                dst_image,
+                regions,
+                ..
+            } => {
+                for region in regions {
+                    resolve_image(&mut encoder, src_image, dst_image, region);
+                }
+            }
+            RecordedCommand::ClearColorImage {
+                image,
+                color,
+                ranges,
+                ..
+            } => {
+                let [r, g, b, a] = *color;
+                let color = wgpu::Color {
+                    r: r.into(),
+                    g: g.into(),
+                    b: b.into(),
+                    a: a.into(),
+                };
+
+                for_each_layer(image, ranges, |view| {
+                    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: None,
+                        color_attachments: &[
+                            wgpu::RenderPassColorAttachmentDescriptor {
+                                attachment: view,
+                                resolve_target: None,
+                                ops: wgpu::Operations {
+                                    load: wgpu::LoadOp::Clear(color),
+                                    store: true,
+                                },
+                            },
+                        ],
+                        depth_stencil_attachment: None,
+                    });
+                });
+            }
+            RecordedCommand::ClearDepthStencilImage {
+                image,
+                depth,
+                stencil,
+                ranges,
+                ..
+            } => {
+                let format = image.info().format;
+
+                for_each_layer(image, ranges, |view| {
+                    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: None,
+                        color_attachments: &[],
+                        depth_stencil_attachment: Some(
+                            wgpu::RenderPassDepthStencilAttachmentDescriptor {
+                                attachment: view,
+                                depth_ops: if format.is_depth() {
+                                    Some(wgpu::Operations {
+                                        load: wgpu::LoadOp::Clear(*depth),
+                                        store: true,
+                                    })
+                                } else {
+                                    None
+                                },
+                                stencil_ops: if format.is_stencil() {
+                                    Some(wgpu::Operations {
+                                        load: wgpu::LoadOp::Clear(*stencil),
+                                        store: true,
+                                    })
+                                } else {
+                                    None
+                                },
+                            },
+                        ),
+                    });
+                });
+            }
+            // `wgpu` tracks resource usage and inserts barriers itself.
+            RecordedCommand::PipelineBarrier { .. }
+            | RecordedCommand::BufferBarriers { .. } => {}
+
+            // Timestamps are not supported, queries read as zeros.
+            RecordedCommand::ResetQueryPool { .. }
+            | RecordedCommand::WriteTimestamp { .. } => {}
+
+            RecordedCommand::BindRayTracingPipeline { .. }
+            | RecordedCommand::BindRayTracingDescriptorSets { .. }
+            | RecordedCommand::BuildAccelerationStructure { .. }
+            | RecordedCommand::TraceRays { .. } => {
+                unreachable!("Ray tracing is not supported")
+            }
+        }
+    }
+
+    assert!(pass.is_none(), "Render pass is not ended");
+    encoder.finish()
+}
+
+/// Binds descriptor sets starting from `first_set`.
+/// Dynamic offsets are split between sets by their dynamic bindings.
+fn bind_sets(
+    device: &Device,
+    bound: &mut Vec<Option<BoundGroup>>,
+    first_set: u32,
+    sets: &[DescriptorSet],
+    dynamic_offsets: &[u32],
+) {
+    let mut offsets = dynamic_offsets;
+
+    for (index, set) in (first_set as usize..).zip(sets) {
+        let set = set.bind(device.handle());
+        let (set_offsets, rest) =
+            offsets.split_at(set.dynamic_count.min(offsets.len()));
+        offsets = rest;
+
+        if bound.len() <= index {
+            bound.resize(index + 1, None);
+        }
+
+        bound[index] = Some(BoundGroup {
+            set,
+            dynamic_offsets: set_offsets.to_vec(),
+        });
+    }
+}
+
+/// Returns bind groups for each set of the pipeline layout.
+/// Sets that were not bound are filled with empty group.
+fn pipeline_groups(
+    device: &Device,
+    layout: &PipelineLayout,
+    bound: &[Option<BoundGroup>],
+) -> Vec<BoundGroup> {
+    (0..layout.info().sets.len())
+        .map(|index| match bound.get(index) {
+            Some(Some(group)) => group.clone(),
+            _ => BoundGroup {
+                set: device.empty_set().clone(),
+                dynamic_offsets: Vec::new(),
+            },
+        })
+        .collect()
+}
+
+/// Returns draw call with current states.
+/// Returns `None` if nothing would be drawn.
+fn prepare_draw(
+    device: &Device,
+    state: &GraphicsState,
+    push_constants: &[PushConstants],
+    draw: Draw,
+) -> Option<DrawOp> {
+    let pipeline = state
+        .pipeline
+        .as_ref()
+        .expect("Graphics pipeline is not bound");
+
+    let info = pipeline.info();
+    let rasterizer = info.rasterizer.as_ref()?;
+    let dynamic = info.dynamic_states;
+
+    // Dynamic states fall back to values in pipeline info until set.
+    let raster_state = RasterState {
+        culling: match state.culling {
+            Some(culling) if dynamic.contains(DynamicStates::CULL_MODE) => {
+                culling
+            }
+            _ => rasterizer.culling,
+        },
+        front_face: match state.front_face {
+            Some(front_face) if dynamic.contains(DynamicStates::FRONT_FACE) => {
+                front_face
+            }
+            _ => rasterizer.front_face,
+        },
+        topology: match state.topology {
+            Some(topology)
+                if dynamic.contains(DynamicStates::PRIMITIVE_TOPOLOGY) =>
+            {
+                topology
+            }
+            _ => info.primitive_topology,
+        },
+        depth_test: match state.depth_test {
+            Some(depth_test) if dynamic.contains(DynamicStates::DEPTH_TEST) => {
+                depth_test
+            }
+            _ => rasterizer.depth_test,
+        },
+    };
+
+    let groups = pipeline_groups(device, &info.layout, &state.sets);
+    let layouts: Vec<_> = groups
+        .iter()
+        .map(|group| group.set.layout.clone())
+        .collect();
+
+    let render_pipeline =
+        pipeline.render_pipeline(device.handle(), raster_state, &layouts)?;
+
+    let vertex_buffers = state
+        .vertex_buffers
+        .iter()
+        .take(info.vertex_bindings.len())
+        .enumerate()
+        .filter_map(|(index, buffer)| {
+            let (buffer, offset) = buffer.as_ref()?;
+            Some((index as u32, buffer.clone(), *offset))
+        })
+        .collect();
+
+    let viewport = match rasterizer.viewport {
+        State::Static { value } => Some(value),
+        State::Dynamic => state.viewport,
+    };
+
+    let scissor = match rasterizer.scissor {
+        State::Static { value } => Some(value),
+        State::Dynamic => state.scissor,
+    };
+
+    let stencil_reference =
+        rasterizer.stencil_tests.as_ref().and_then(|tests| {
+            match tests.front.reference {
+                State::Static { value } => Some(value),
+                State::Dynamic => None,
+            }
+        });
+
+    let push_constants = push_constants
+        .iter()
+        .filter(|pc| pc.stages.intersects(ShaderStageFlags::ALL_GRAPHICS))
+        .map(|pc| (pc.stages.to_wgpu(), pc.offset, pc.data.clone()))
+        .collect();
+
+    Some(DrawOp {
+        pipeline: render_pipeline,
+        groups,
+        vertex_buffers,
+        index_buffer: state.index_buffer.clone(),
+        viewport,
+        scissor,
+        blend_constant: blend_constants(&rasterizer.color_blend),
+        stencil_reference,
+        push_constants,
+        draw,
+    })
+}
+
+/// Encodes current subpass as `wgpu` render pass.
+fn encode_subpass(encoder: &mut wgpu::CommandEncoder, pass: &PassState) {
+    let info = pass.pass.info();
+    let subpass = &info.subpasses[pass.subpass];
+    let framebuffer = pass.framebuffer.info();
+
+    // Frames are locked while render pass borrows their views.
+    let frames: Vec<_> = framebuffer
+        .views
+        .iter()
+        .map(|view| match view.info().image.memory() {
+            ImageMemory::Frame(slot) => Some(slot.lock()),
+            ImageMemory::Texture(_) => None,
+        })
+        .collect();
+
+    let views: Option<Vec<&wgpu::TextureView>> = framebuffer
+        .views
+        .iter()
+        .zip(&frames)
+        .map(|(view, frame)| match frame {
+            Some(frame) => frame.as_ref().map(|frame| &frame.output.view),
+            None => view.handle(),
+        })
+        .collect();
+
+    let views = match views {
+        Some(views) => views,
+        None => {
+            tracing::error!("Swapchain image is not acquired");
+            return;
+        }
+    };
+
+    let color_attachments: Vec<_> = subpass
+        .colors
+        .iter()
+        .enumerate()
+        .map(|(index, &color)| {
+            let load = match pass.clear_value(color) {
+                None => wgpu::LoadOp::Load,
+                Some(ClearValue::Color(r, g, b, a)) => {
+                    wgpu::LoadOp::Clear(wgpu::Color {
+                        r: r.into(),
+                        g: g.into(),
+                        b: b.into(),
+                        a: a.into(),
+                    })
+                }
+                Some(ClearValue::DepthStencil(..)) => panic!(
+                    "Attempt to clear color attachment with depth-stencil value"
+                ),
+            };
+
+            wgpu::RenderPassColorAttachmentDescriptor {
+                attachment: views[color],
+                resolve_target: subpass
+                    .resolves
+                    .get(index)
+                    .map(|&resolve| views[resolve]),
+                ops: wgpu::Operations {
+                    load,
+                    store: pass.store(color),
+                },
+            }
+        })
+        .collect();
+
+    let depth_stencil_attachment = subpass.depth.map(|depth| {
+        let format = info.attachments[depth].format;
+        let store = pass.store(depth);
+
+        let (depth_load, stencil_load) = match pass.clear_value(depth) {
+            None => (wgpu::LoadOp::Load, wgpu::LoadOp::Load),
+            Some(ClearValue::DepthStencil(depth, stencil)) => {
+                (wgpu::LoadOp::Clear(depth), wgpu::LoadOp::Clear(stencil))
+            }
+            Some(ClearValue::Color(..)) => panic!(
+                "Attempt to clear depth-stencil attachment with color value"
+            ),
+        };
+
+        wgpu::RenderPassDepthStencilAttachmentDescriptor {
+            attachment: views[depth],
+            depth_ops: if format.is_depth() {
+                Some(wgpu::Operations {
+                    load: depth_load,
+                    store,
+                })
+            } else {
+                None
+            },
+            stencil_ops: if format.is_stencil() {
+                Some(wgpu::Operations {
+                    load: stencil_load,
+                    store,
+                })
+            } else {
+                None
+            },
+        }
+    });
+
+    let mut render_pass =
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &color_attachments,
+            depth_stencil_attachment,
+        });
+
+    for op in &pass.draws {
+        render_pass.set_pipeline(&op.pipeline);
+
+        for (index, group) in op.groups.iter().enumerate() {
+            render_pass.set_bind_group(
+                index as u32,
+                &group.set.group,
+                &group.dynamic_offsets,
+            );
+        }
+
+        for (slot, buffer, offset) in &op.vertex_buffers {
+            render_pass
+                .set_vertex_buffer(*slot, buffer.handle().slice(*offset..));
+        }
+
+        if let Some((buffer, offset, index_type)) = &op.index_buffer {
+            render_pass.set_index_buffer(
+                buffer.handle().slice(*offset..),
+                index_type.to_wgpu(),
+            );
+        }
+
+        if let Some(viewport) = &op.viewport {
+            render_pass.set_viewport(
+                viewport.x.offset.into_inner(),
+                viewport.y.offset.into_inner(),
+                viewport.x.size.into_inner(),
+                viewport.y.size.into_inner(),
+                viewport.z.offset.into_inner(),
+                viewport.z.offset.into_inner() + viewport.z.size.into_inner(),
+            );
+        }
+
+        if let Some(scissor) = &op.scissor {
+            let (x, y, width, height) =
+                clamp_scissor(scissor, framebuffer.extent);
+            render_pass.set_scissor_rect(x, y, width, height);
+        }
+
+        if let Some(color) = &op.blend_constant {
+            render_pass.set_blend_color(*color);
+        }
+
+        if let Some(reference) = op.stencil_reference {
+            render_pass.set_stencil_reference(reference);
+        }
+
+        for (stages, offset, data) in &op.push_constants {
+            render_pass.set_push_constants(*stages, *offset, data);
+        }
+
+        match &op.draw {
+            Draw::Vertices {
+                vertices,
+                instances,
+            } => render_pass.draw(vertices.clone(), instances.clone()),
+            Draw::Indices {
+                indices,
+                vertex_offset,
+                instances,
+            } => render_pass.draw_indexed(
+                indices.clone(),
+                *vertex_offset,
+                instances.clone(),
+            ),
+        }
+    }
+}
+
+/// Clamps scissor to framebuffer extent, as `wgpu` requires.
+fn clamp_scissor(scissor: &Rect2d, extent: Extent2d) -> (u32, u32, u32, u32) {
+    let x = (scissor.offset.x.max(0) as u32).min(extent.width);
+    let y = (scissor.offset.y.max(0) as u32).min(extent.height);
+    let width = scissor.extent.width.min(extent.width - x);
+    let height = scissor.extent.height.min(extent.height - y);
+    (x, y, width, height)
+}
+
+/// Returns texture of the image.
+/// Textures of swapchain frames can be used only as render attachments.
+fn texture(image: &Image) -> Option<&wgpu::Texture> {
+    match image.memory() {
+        ImageMemory::Texture(texture) => Some(texture),
+        ImageMemory::Frame(_) => {
+            tracing::error!(
+                "Swapchain image {:?} can be used only as render attachment",
+                image
+            );
+            None
+        }
+    }
+}
+
+/// Returns origin and extent of copied region.
+/// Array layers of non-3D images are addressed along depth.
+fn copy_region(
+    image_extent: ImageExtent,
+    subresource: &ImageSubresourceLayers,
+    offset: Offset3d,
+    extent: Extent3d,
+) -> (wgpu::Origin3d, wgpu::Extent3d) {
+    match image_extent {
+        ImageExtent::D3 { .. } => (offset.to_wgpu(), extent.to_wgpu()),
+        _ => (
+            wgpu::Origin3d {
+                x: offset.x as u32,
+                y: offset.y as u32,
+                z: subresource.first_layer,
+            },
+            wgpu::Extent3d {
+                width: extent.width,
+                height: extent.height,
+                depth: subresource.layer_count,
+            },
+        ),
+    }
+}
+
+/// Copies buffer region into image.
+///
+/// `wgpu` requires rows of the buffer to be aligned to
+/// `COPY_BYTES_PER_ROW_ALIGNMENT`. Unaligned rows are copied
+/// into temporary buffer first.
+fn copy_buffer_to_image(
+    device: &Device,
+    encoder: &mut wgpu::CommandEncoder,
+    buffer: &Buffer,
+    image: &Image,
+    region: &BufferImageCopy,
+) {
+    let texture = match texture(image) {
+        Some(texture) => texture,
+        None => return,
+    };
+
+    let info = image.info();
+    let texel = texel_size(info.format);
+
+    let row_length = match region.buffer_row_length {
+        0 => region.image_extent.width,
+        row_length => row_length,
+    };
+
+    let image_height = match region.buffer_image_height {
+        0 => region.image_extent.height,
+        image_height => image_height,
+    };
+
+    let (origin, extent) = copy_region(
+        info.extent,
+        &region.image_subresource,
+        region.image_offset,
+        region.image_extent,
+    );
+
+    let destination = wgpu::TextureCopyView {
+        texture,
+        mip_level: region.image_subresource.level,
+        origin,
+    };
+
+    let bytes_per_row = row_length * texel;
+    let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+    if bytes_per_row % alignment == 0 {
+        encoder.copy_buffer_to_texture(
+            wgpu::BufferCopyView {
+                buffer: buffer.handle(),
+                layout: wgpu::TextureDataLayout {
+                    offset: region.buffer_offset,
+                    bytes_per_row,
+                    rows_per_image: image_height,
+                },
+            },
+            destination,
+            extent,
+        );
+        return;
+    }
+
+    let row_size = u64::from(extent.width * texel);
+
+    if row_size % wgpu::COPY_BUFFER_ALIGNMENT != 0
+        || region.buffer_offset % wgpu::COPY_BUFFER_ALIGNMENT != 0
+        || u64::from(bytes_per_row) % wgpu::COPY_BUFFER_ALIGNMENT != 0
+    {
+        tracing::error!(
+            "Rows of {:?} copy are not aligned to {} bytes",
+            region,
+            wgpu::COPY_BUFFER_ALIGNMENT,
+        );
+        return;
+    }
+
+    let aligned_bytes_per_row =
+        (bytes_per_row + alignment - 1) / alignment * alignment;
+
+    let staging = device.handle().create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: u64::from(aligned_bytes_per_row)
+            * u64::from(extent.height)
+            * u64::from(extent.depth),
+        usage: wgpu::BufferUsage::COPY_SRC | wgpu::BufferUsage::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    for slice in 0..u64::from(extent.depth) {
+        for row in 0..u64::from(extent.height) {
+            encoder.copy_buffer_to_buffer(
+                buffer.handle(),
+                region.buffer_offset
+                    + (slice * u64::from(image_height) + row)
+                        * u64::from(bytes_per_row),
+                &staging,
+                (slice * u64::from(extent.height) + row)
+                    * u64::from(aligned_bytes_per_row),
+                row_size,
+            );
+        }
+    }
+
+    encoder.copy_buffer_to_texture(
+        wgpu::BufferCopyView {
+            buffer: &staging,
+            layout: wgpu::TextureDataLayout {
+                offset: 0,
+                bytes_per_row: aligned_bytes_per_row,
+                rows_per_image: extent.height,
+            },
+        },
+        destination,
+        extent,
+    );
+}
+
+fn copy_image(
+    encoder: &mut wgpu::CommandEncoder,
+    src_image: &Image,
+    dst_image: &Image,
+    region: &ImageCopy,
+) {
+    let (src_texture, dst_texture) =
+        match (texture(src_image), texture(dst_image)) {
+            (Some(src_texture), Some(dst_texture)) => {
+                (src_texture, dst_texture)
+            }
+            _ => return,
+        };
+
+    let (src_origin, extent) = copy_region(
+        src_image.info().extent,
+        &region.src_subresource,
+        region.src_offset,
+        region.extent,
+    );
+
+    let (dst_origin, _) = copy_region(
+        dst_image.info().extent,
+        &region.dst_subresource,
+        region.dst_offset,
+        region.extent,
+    );
+
+    encoder.copy_texture_to_texture(
+        wgpu::TextureCopyView {
+            texture: src_texture,
+            mip_level: region.src_subresource.level,
+            origin: src_origin,
+        },
+        wgpu::TextureCopyView {
+            texture: dst_texture,
+            mip_level: region.dst_subresource.level,
+            origin: dst_origin,
+        },
+        extent,
+    );
+}
+
+/// Blits are performed as copies.
+/// `wgpu` has no scaling or format converting blits.
+fn blit_image(
+    encoder: &mut wgpu::CommandEncoder,
+    src_image: &Image,
+    dst_image: &Image,
+    region: &ImageBlit,
+) {
+    let src_extent = blit_extent(&region.src_offsets);
+    let dst_extent = blit_extent(&region.dst_offsets);
+
+    match (src_extent, dst_extent) {
+        (Some(src_extent), Some(dst_extent))
+            if src_extent == dst_extent
+                && src_image.info().format == dst_image.info().format =>
+        {
+            copy_image(
+                encoder,
+                src_image,
+                dst_image,
+                &ImageCopy {
+                    src_subresource: region.src_subresource,
+                    src_offset: region.src_offsets[0],
+                    dst_subresource: region.dst_subresource,
+                    dst_offset: region.dst_offsets[0],
+                    extent: src_extent,
+                },
+            );
+        }
+        _ => tracing::error!("Blit {:?} is not supported", region),
+    }
+}
+
+/// Returns extent of blit region.
+/// Returns `None` for mirrored regions.
+fn blit_extent(offsets: &[Offset3d; 2]) -> Option<Extent3d> {
+    let width = offsets[1].x - offsets[0].x;
+    let height = offsets[1].y - offsets[0].y;
+    let depth = offsets[1].z - offsets[0].z;
+
+    if width < 0 || height < 0 || depth < 0 {
+        return None;
+    }
+
+    Some(Extent3d {
+        width: width as u32,
+        height: height as u32,
+        depth: depth as u32,
+    })
+}
+
+/// Resolves whole layers with render pass resolve targets.
+/// `wgpu` can't resolve image regions.
+fn resolve_image(
+    encoder: &mut wgpu::CommandEncoder,
+    src_image: &Image,
+    dst_image: &Image,
+    region: &ImageResolve,
+) {
+    let zero =
+        |offset: Offset3d| offset.x == 0 && offset.y == 0 && offset.z == 0;
+
+    if !zero(region.src_offset) || !zero(region.dst_offset) {
+        tracing::error!("Resolve {:?} is not supported", region);
+        return;
+    }
+
+    for layer in 0..region.src_subresource.layer_count {
+        let src_view = src_image.create_view(
+            ImageViewKind::D2,
+            &single_layer(&region.src_subresource, layer),
+        );
+
+        let dst_view = dst_image.create_view(
+            ImageViewKind::D2,
+            &single_layer(&region.dst_subresource, layer),
+        );
+
+        let (src_view, dst_view) = match (src_view, dst_view) {
+            (Some(src_view), Some(dst_view)) => (src_view, dst_view),
+            _ => {
+                tracing::error!("Swapchain images can't be resolved");
+                return;
+            }
+        };
+
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                attachment: &src_view,
+                resolve_target: Some(&dst_view),
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+    }
+}
+
+fn single_layer(
+    subresource: &ImageSubresourceLayers,
+    layer: u32,
+) -> ImageSubresourceRange {
+    ImageSubresourceRange {
+        aspect: subresource.aspect,
+        first_level: subresource.level,
+        level_count: 1,
+        first_layer: subresource.first_layer + layer,
+        layer_count: 1,
+    }
+}
+
+/// Calls `f` with view of each level and layer in ranges.
+/// Images are cleared with render passes, as `wgpu` has no clear commands.
+fn for_each_layer(
+    image: &Image,
+    ranges: &[ImageSubresourceRange],
+    mut f: impl FnMut(&wgpu::TextureView),
+) {
+    match image.info().extent {
+        ImageExtent::D2 { .. } => {}
+        _ => {
+            tracing::error!("Only 2D images can be cleared");
+            return;
+        }
+    }
+
+    for range in ranges {
+        for level in range.first_level..range.first_level + range.level_count {
+            for layer in
+                range.first_layer..range.first_layer + range.layer_count
+            {
+                let view = image.create_view(
+                    ImageViewKind::D2,
+                    &ImageSubresourceRange {
+                        aspect: range.aspect,
+                        first_level: level,
+                        level_count: 1,
+                        first_layer: layer,
+                        layer_count: 1,
+                    },
+                );
+
+                match view {
+                    Some(view) => f(&view),
+                    None => {
+                        tracing::error!("Swapchain images can't be cleared");
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use {
+    super::{
+        physical::{Adapter, PhysicalDevice},
+        surface::Surface,
+    },
+    crate::{
+        physical::EnumerateDeviceError,
+        surface::{
+            CreateSurfaceError, RawWindowHandleKind, SurfaceError, SurfaceInfo,
+        },
+    },
+    once_cell::sync::OnceCell,
+    raw_window_handle::HasRawWindowHandle,
+    std::{
+        fmt::{self, Debug},
+        sync::Arc,
+    },
+};
+
+/// Root object of the `wgpu` graphics system.
+pub struct WgpuGraphics {
+    pub(super) instance: wgpu::Instance,
+
+    /// Adapters requested by `init_async`.
+    /// Browsers can't enumerate adapters synchronously.
+    #[cfg(target_arch = "wasm32")]
+    adapters: Vec<Arc<Adapter>>,
+}
+
+pub type Graphics = WgpuGraphics;
+
+// Browsers run `wgpu` on single thread.
+#[cfg(target_arch = "wasm32")]
+unsafe impl Send for WgpuGraphics {}
+#[cfg(target_arch = "wasm32")]
+unsafe impl Sync for WgpuGraphics {}
+
+static GLOBAL_GRAPHICS: OnceCell<WgpuGraphics> = OnceCell::new();
+
+impl Debug for WgpuGraphics {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str("WgpuGraphics")
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum InitError {
+    #[error("No suitable adapter found")]
+    AdapterNotFound,
+
+    #[error(transparent)]
+    RequestDevice {
+        #[from]
+        source: wgpu::RequestDeviceError,
+    },
+
+    /// On the web graphics must be initialized with `Graphics::init_async`
+    /// before `Graphics::get_or_init` is called.
+    #[error("Graphics are not initialized")]
+    NotInitialized,
+}
+
+impl WgpuGraphics {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn get_or_init() -> Result<&'static WgpuGraphics, InitError> {
+        Ok(GLOBAL_GRAPHICS.get_or_init(|| {
+            tracing::debug!("Wgpu graphics initialized");
+            WgpuGraphics {
+                instance: wgpu::Instance::new(wgpu::BackendBit::PRIMARY),
+            }
+        }))
+    }
+
+    /// Returns graphics initialized by `init_async`.
+    #[cfg(target_arch = "wasm32")]
+    pub fn get_or_init() -> Result<&'static WgpuGraphics, InitError> {
+        GLOBAL_GRAPHICS.get().ok_or(InitError::NotInitialized)
+    }
+
+    /// Initializes graphics in the browser.
+    ///
+    /// Adapter and device are requested here,
+    /// as browsers resolve requests in the event loop and
+    /// `PhysicalDevice::create_device` can't wait for them.
+    /// Device is requested with all features adapter supports.
+    #[cfg(target_arch = "wasm32")]
+    pub async fn init_async() -> Result<&'static WgpuGraphics, InitError> {
+        if let Some(graphics) = GLOBAL_GRAPHICS.get() {
+            return Ok(graphics);
+        }
+
+        let instance = wgpu::Instance::new(wgpu::BackendBit::BROWSER_WEBGPU);
+
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                compatible_surface: None,
+            })
+            .await
+            .ok_or(InitError::AdapterNotFound)?;
+
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: None,
+                    features: adapter.features(),
+                    limits: adapter.limits(),
+                },
+                None,
+            )
+            .await?;
+
+        let adapter = Adapter::prepared(adapter, device, queue);
+
+        tracing::debug!("Wgpu graphics initialized");
+        Ok(GLOBAL_GRAPHICS.get_or_init(|| WgpuGraphics {
+            instance,
+            adapters: vec![Arc::new(adapter)],
+        }))
+    }
+
+    pub fn name(&self) -> &str {
+        "Wgpu"
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn devices(&self) -> Result<Vec<PhysicalDevice>, EnumerateDeviceError> {
+        Ok(self
+            .instance
+            .enumerate_adapters(wgpu::BackendBit::PRIMARY)
+            .map(|adapter| PhysicalDevice::new(Arc::new(Adapter::new(adapter))))
+            .collect())
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn devices(&self) -> Result<Vec<PhysicalDevice>, EnumerateDeviceError> {
+        Ok(self
+            .adapters
+            .iter()
+            .map(|adapter| PhysicalDevice::new(adapter.clone()))
+            .collect())
+    }
+
+    /// Creates surface for the window.
+    /// On the web window is a canvas element.
+    #[tracing::instrument(skip(window))]
+    pub fn create_surface(
+        &self,
+        window: &impl HasRawWindowHandle,
+    ) -> Result<Surface, CreateSurfaceError> {
+        let handle = window.raw_window_handle();
+
+        if RawWindowHandleKind::of(&handle) == RawWindowHandleKind::Unknown {
+            return Err(CreateSurfaceError::UnsupportedWindow {
+                window: RawWindowHandleKind::Unknown,
+                source: None,
+            });
+        }
+
+        let surface = unsafe { self.instance.create_surface(window) };
+
+        tracing::debug!("Surface created");
+        Ok(Surface::make(surface, SurfaceInfo { window: handle }))
+    }
+
+    /// Destroys surface.
+    ///
+    /// Fails with `SurfaceError::AlreadyUsed` if surface is used
+    /// by a swapchain or other clones of it exist.
+    pub fn destroy_surface(
+        &self,
+        surface: Surface,
+    ) -> Result<(), SurfaceError> {
+        if !surface.into_unique() {
+            return Err(SurfaceError::AlreadyUsed);
+        }
+
+        Ok(())
+    }
+}
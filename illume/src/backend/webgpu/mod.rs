@@ -0,0 +1,87 @@
+//! Backend on top of `wgpu`.
+//!
+//! Runs over native APIs and over WebGPU in browsers,
+//! where swapchain images are presented into the window's canvas.
+//! Only raster and compute pipelines are available,
+//! ray-tracing features are never reported.
+//!
+//! Commands are captured by encoder and translated into `wgpu` commands
+//! when command buffer is submitted.
+//! Each subpass is encoded as separate `wgpu` render pass.
+//! Barriers and semaphores have no effect as `wgpu` tracks resource usage
+//! itself. All work goes through single `wgpu` queue in submission order,
+//! and host writes are queued before work submitted after them,
+//! so fences are signalled on submission.
+//!
+//! `wgpu` pipelines and bind group layouts are immutable and depend on
+//! states and resources that are dynamic in this crate's API,
+//! such as cull mode, texture sample types and storage image formats.
+//! They are created when first used by submitted commands and cached.
+
+macro_rules! assert_owner {
+    ($resource:expr, $owner:expr) => {{
+        assert!(
+            $resource.is_owned_by(&$owner),
+            "{:?} is not owned by {:?}",
+            $resource,
+            $owner
+        )
+    }};
+}
+
+mod convert;
+mod device;
+mod encode;
+mod graphics;
+mod physical;
+mod queue;
+mod resources;
+mod surface;
+mod swapchain;
+
+pub use self::{
+    device::*, encode::*, graphics::*, physical::*, queue::*, resources::*,
+    surface::*, swapchain::*,
+};
+
+use std::{
+    future::Future,
+    sync::atomic::{AtomicU64, Ordering::Relaxed},
+    task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+};
+
+static NEXT_HANDLE: AtomicU64 = AtomicU64::new(1);
+
+/// Returns handle unique among all objects of the backend.
+fn new_handle() -> u64 {
+    NEXT_HANDLE.fetch_add(1, Relaxed)
+}
+
+/// Polls future once without blocking.
+///
+/// Native `wgpu` futures are ready once requested or once `Device::poll`
+/// processed their callbacks. Browser futures are resolved by the event loop
+/// and can't be waited for.
+fn poll_once<F: Future>(future: F) -> Option<F::Output> {
+    fn noop_raw_waker() -> RawWaker {
+        fn clone(_: *const ()) -> RawWaker {
+            noop_raw_waker()
+        }
+
+        fn noop(_: *const ()) {}
+
+        static VTABLE: RawWakerVTable =
+            RawWakerVTable::new(clone, noop, noop, noop);
+
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+
+    let waker = unsafe { Waker::from_raw(noop_raw_waker()) };
+    let mut cx = Context::from_waker(&waker);
+    let mut future = Box::pin(future);
+
+    match future.as_mut().poll(&mut cx) {
+        Poll::Ready(output) => Some(output),
+        Poll::Pending => None,
+    }
+}
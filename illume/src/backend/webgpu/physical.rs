@@ -0,0 +1,453 @@
+use {
+    super::{
+        convert::{format_properties, from_wgpu},
+        device::Device,
+        poll_once,
+        queue::Queue,
+        surface::Surface,
+    },
+    crate::{
+        format::{
+            Format, FormatFeatures, FormatProperties, ImageFormatProperties,
+        },
+        image::{ImageExtent, ImageUsage, Samples},
+        physical::{ApiVersion, DeviceInfo, DeviceKind, Feature},
+        queue::{
+            Family, FamilyInfo, QueueCapabilityFlags, QueueId, QueuesQuery,
+        },
+        surface::{PresentMode, SurfaceCapabilities, SurfaceError},
+        CreateDeviceError, Extent2d, Extent3d, ImageSize, OutOfMemory,
+    },
+    parking_lot::Mutex,
+    std::{
+        fmt::{self, Debug},
+        sync::Arc,
+    },
+};
+
+/// Maximum size of 1D and 2D texture dimensions guaranteed by WebGPU.
+pub(super) const MAX_IMAGE_DIMENSION: ImageSize = 8192;
+
+/// Maximum size of 3D texture dimensions guaranteed by WebGPU.
+pub(super) const MAX_IMAGE_DIMENSION_3D: ImageSize = 2048;
+
+/// Maximum number of texture array layers guaranteed by WebGPU.
+pub(super) const MAX_IMAGE_LAYERS: u32 = 256;
+
+const MAX_IMAGE_SIZE: u64 = 1 << 31;
+
+/// Largest surface extent reported in capabilities.
+const MAX_SURFACE_EXTENT: Extent2d = Extent2d {
+    width: MAX_IMAGE_DIMENSION,
+    height: MAX_IMAGE_DIMENSION,
+};
+
+pub(super) const SURFACE_PRESENT_MODES: &[PresentMode] = &[
+    PresentMode::Fifo,
+    PresentMode::Mailbox,
+    PresentMode::Immediate,
+];
+
+/// Returns `wgpu` features requested when adapter supports them.
+/// They don't correspond to features of this crate directly.
+fn optional_features() -> wgpu::Features {
+    wgpu::Features::PUSH_CONSTANTS
+        | wgpu::Features::DEPTH_CLAMPING
+        | wgpu::Features::NON_FILL_POLYGON_MODE
+        | wgpu::Features::ADDRESS_MODE_CLAMP_TO_BORDER
+}
+
+/// Adapter shared by physical devices that represent it.
+pub(super) struct Adapter {
+    adapter: wgpu::Adapter,
+
+    /// Device requested when graphics were initialized.
+    /// On the web requests can't be waited for, so device is created
+    /// up front and taken by `PhysicalDevice::create_device`.
+    prepared: Mutex<Option<(wgpu::Device, wgpu::Queue)>>,
+}
+
+// Browsers run `wgpu` on single thread.
+#[cfg(target_arch = "wasm32")]
+unsafe impl Send for Adapter {}
+#[cfg(target_arch = "wasm32")]
+unsafe impl Sync for Adapter {}
+
+impl Adapter {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(super) fn new(adapter: wgpu::Adapter) -> Self {
+        Adapter {
+            adapter,
+            prepared: Mutex::new(None),
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub(super) fn prepared(
+        adapter: wgpu::Adapter,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+    ) -> Self {
+        Adapter {
+            adapter,
+            prepared: Mutex::new(Some((device, queue))),
+        }
+    }
+}
+
+pub struct PhysicalDevice {
+    adapter: Arc<Adapter>,
+}
+
+impl Debug for PhysicalDevice {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "PhysicalDevice({})", self.name())
+    }
+}
+
+impl PhysicalDevice {
+    pub(super) fn new(adapter: Arc<Adapter>) -> Self {
+        PhysicalDevice { adapter }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn name(&self) -> String {
+        self.adapter.adapter.get_info().name
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn name(&self) -> String {
+        "WebGPU".to_owned()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn kind(&self) -> Option<DeviceKind> {
+        match self.adapter.adapter.get_info().device_type {
+            wgpu::DeviceType::Cpu => Some(DeviceKind::Software),
+            wgpu::DeviceType::IntegratedGpu => Some(DeviceKind::Integrated),
+            wgpu::DeviceType::DiscreteGpu => Some(DeviceKind::Discrete),
+            wgpu::DeviceType::VirtualGpu | wgpu::DeviceType::Other => None,
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn kind(&self) -> Option<DeviceKind> {
+        None
+    }
+
+    /// Returns features of this crate supported by the adapter.
+    fn features(&self) -> Vec<Feature> {
+        let supported = self.adapter.adapter.features();
+
+        // Per-draw state is emulated by pipeline variants.
+        let mut features =
+            vec![Feature::SurfacePresentation, Feature::ExtendedDynamicState];
+
+        if supported.contains(
+            wgpu::Features::SAMPLED_TEXTURE_BINDING_ARRAY
+                | wgpu::Features::SAMPLED_TEXTURE_ARRAY_DYNAMIC_INDEXING,
+        ) {
+            features.push(Feature::ShaderSampledImageDynamicIndexing);
+        }
+
+        if supported.contains(
+            wgpu::Features::SAMPLED_TEXTURE_BINDING_ARRAY
+                | wgpu::Features::SAMPLED_TEXTURE_ARRAY_NON_UNIFORM_INDEXING,
+        ) {
+            features.push(Feature::ShaderSampledImageNonUniformIndexing);
+        }
+
+        if supported.contains(
+            wgpu::Features::SAMPLED_TEXTURE_BINDING_ARRAY
+                | wgpu::Features::UNSIZED_BINDING_ARRAY,
+        ) {
+            features.push(Feature::RuntimeDescriptorArray);
+        }
+
+        features
+    }
+
+    /// Returns `wgpu` features required for features of this crate.
+    fn wgpu_features(&self, features: &[Feature]) -> wgpu::Features {
+        let mut result = self.adapter.adapter.features() & optional_features();
+
+        for feature in features {
+            match feature {
+                Feature::ShaderSampledImageDynamicIndexing => {
+                    result |= wgpu::Features::SAMPLED_TEXTURE_BINDING_ARRAY
+                        | wgpu::Features::SAMPLED_TEXTURE_ARRAY_DYNAMIC_INDEXING;
+                }
+                Feature::ShaderSampledImageNonUniformIndexing => {
+                    result |= wgpu::Features::SAMPLED_TEXTURE_BINDING_ARRAY
+                        | wgpu::Features::SAMPLED_TEXTURE_ARRAY_NON_UNIFORM_INDEXING;
+                }
+                Feature::RuntimeDescriptorArray => {
+                    result |= wgpu::Features::SAMPLED_TEXTURE_BINDING_ARRAY
+                        | wgpu::Features::UNSIZED_BINDING_ARRAY;
+                }
+                _ => {}
+            }
+        }
+
+        result
+    }
+
+    pub fn info(&self) -> DeviceInfo {
+        DeviceInfo {
+            name: self.name(),
+            kind: self.kind(),
+            api_version: ApiVersion {
+                major: 1,
+                minor: 0,
+                patch: 0,
+            },
+            features: self.features(),
+            families: vec![FamilyInfo {
+                capabilities: QueueCapabilityFlags::all(),
+                count: 1,
+            }],
+            capabilities: Vec::new(),
+        }
+    }
+
+    /// Returns operations supported for format by device.
+    pub fn format_properties(&self, format: Format) -> FormatProperties {
+        format_properties(format)
+    }
+
+    /// Returns limits of images with specified format and usage.
+    /// Only dimensionality of `extent` is considered.
+    /// Returns `Ok(None)` if combination is not supported.
+    pub fn image_format_properties(
+        &self,
+        format: Format,
+        extent: ImageExtent,
+        usage: ImageUsage,
+    ) -> Result<Option<ImageFormatProperties>, OutOfMemory> {
+        Ok(image_format_properties(format, extent, usage))
+    }
+
+    /// Returns capabilities of the surface.
+    /// Only format preferred by adapter is reported.
+    pub fn surface_capabilities(
+        &self,
+        surface: &Surface,
+    ) -> Result<Option<SurfaceCapabilities>, SurfaceError> {
+        let preferred = self
+            .adapter
+            .adapter
+            .get_swap_chain_preferred_format(surface.handle());
+
+        let format = match from_wgpu::<_, Option<Format>>(preferred) {
+            Some(format) => format,
+            None => return Ok(None),
+        };
+
+        let current_extent = surface.extent();
+
+        Ok(Some(SurfaceCapabilities {
+            families: vec![0],
+            image_count: 1..=1,
+            current_extent,
+            image_extent: Extent2d {
+                width: 1,
+                height: 1,
+            }..=MAX_SURFACE_EXTENT,
+            supported_usage: surface_usage(),
+            present_modes: SURFACE_PRESENT_MODES.to_vec(),
+            formats: vec![format],
+        }))
+    }
+
+    /// Creates device with requested queues.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `features` contains duplicates or unsupported features.
+    #[tracing::instrument(skip(queues))]
+    pub fn create_device<Q>(
+        self,
+        features: &[Feature],
+        queues: Q,
+    ) -> Result<(Device, Q::Queues), CreateDeviceError<Q::Error>>
+    where
+        Q: QueuesQuery,
+    {
+        let info = self.info();
+
+        let (query, collector) =
+            queues.query(&info.families).map_err(|source| {
+                CreateDeviceError::CannotFindRequeredQueues { source }
+            })?;
+
+        let query = query.as_ref();
+
+        for &(family, count) in query {
+            match info.families.get(family) {
+                Some(family) if family.count >= count => {}
+                _ => return Err(CreateDeviceError::BadFamiliesRequested),
+            }
+        }
+
+        for (index, feature) in features.iter().enumerate() {
+            assert!(
+                !features[..index].contains(feature),
+                "Feature {:?} is requested twice",
+                feature
+            );
+
+            assert!(
+                info.features.contains(feature),
+                "Attempt to enable unsupported feature `{:?}`",
+                feature
+            );
+        }
+
+        let prepared = self.adapter.prepared.lock().take();
+
+        let (device, queue) = match prepared {
+            Some(prepared) => prepared,
+            None => {
+                let request = self.adapter.adapter.request_device(
+                    &wgpu::DeviceDescriptor {
+                        label: None,
+                        features: self.wgpu_features(features),
+                        limits: self.adapter.adapter.limits(),
+                    },
+                    None,
+                );
+
+                match poll_once(request) {
+                    Some(Ok(device)) => device,
+                    Some(Err(err)) => {
+                        tracing::error!("Failed to request device: {}", err);
+                        return Err(CreateDeviceError::FunctionLoadFailed);
+                    }
+                    None => {
+                        tracing::error!("Device request is not resolved");
+                        return Err(CreateDeviceError::FunctionLoadFailed);
+                    }
+                }
+            }
+        };
+
+        device.on_uncaptured_error(|err| {
+            tracing::error!("Wgpu error: {}", err);
+        });
+
+        let device = Device::new(features.to_vec(), device, queue);
+
+        let families = query
+            .iter()
+            .map(|&(family, count)| {
+                let capabilities = info.families[family].capabilities;
+
+                Family {
+                    capabilities,
+                    queues: (0..count)
+                        .map(|index| {
+                            Queue::new(
+                                device.clone(),
+                                QueueId { family, index },
+                                capabilities,
+                            )
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+
+        tracing::debug!("Device created");
+
+        Ok((device, Q::collect(collector, families)))
+    }
+}
+
+pub(super) fn image_format_usage(format: Format) -> ImageUsage {
+    let features = format_properties(format).optimal_tiling;
+    let mut usage = ImageUsage::TRANSIENT;
+
+    if features.contains(FormatFeatures::TRANSFER_SRC) {
+        usage |= ImageUsage::TRANSFER_SRC;
+    }
+    if features.contains(FormatFeatures::TRANSFER_DST) {
+        usage |= ImageUsage::TRANSFER_DST;
+    }
+    if features.contains(FormatFeatures::SAMPLED_IMAGE) {
+        usage |= ImageUsage::SAMPLED;
+    }
+    if features.contains(FormatFeatures::STORAGE_IMAGE) {
+        usage |= ImageUsage::STORAGE;
+    }
+    if features.contains(FormatFeatures::COLOR_ATTACHMENT) {
+        usage |= ImageUsage::COLOR_ATTACHMENT;
+    }
+    if features.contains(FormatFeatures::DEPTH_STENCIL_ATTACHMENT) {
+        usage |= ImageUsage::DEPTH_STENCIL_ATTACHMENT;
+    }
+    if usage.is_render_target() {
+        usage |=
+            ImageUsage::TRANSIENT_ATTACHMENT | ImageUsage::INPUT_ATTACHMENT;
+    }
+
+    usage
+}
+
+pub(super) fn max_image_dimension(extent: ImageExtent) -> ImageSize {
+    match extent {
+        ImageExtent::D1 { .. } | ImageExtent::D2 { .. } => MAX_IMAGE_DIMENSION,
+        ImageExtent::D3 { .. } => MAX_IMAGE_DIMENSION_3D,
+    }
+}
+
+pub(super) fn image_format_properties(
+    format: Format,
+    extent: ImageExtent,
+    usage: ImageUsage,
+) -> Option<ImageFormatProperties> {
+    if !image_format_usage(format).contains(usage) {
+        return None;
+    }
+
+    let max = max_image_dimension(extent);
+
+    let max_extent = match extent {
+        ImageExtent::D1 { .. } => Extent3d {
+            width: max,
+            height: 1,
+            depth: 1,
+        },
+        ImageExtent::D2 { .. } => Extent3d {
+            width: max,
+            height: max,
+            depth: 1,
+        },
+        ImageExtent::D3 { .. } => Extent3d {
+            width: max,
+            height: max,
+            depth: max,
+        },
+    };
+
+    // WebGPU supports only 4 samples for multisampled render targets.
+    let max_samples = match extent {
+        ImageExtent::D2 { .. } if usage.is_render_target() => Samples::Samples4,
+        _ => Samples::Samples1,
+    };
+
+    Some(ImageFormatProperties {
+        max_extent,
+        max_levels: 32 - max.leading_zeros(),
+        max_layers: match extent {
+            ImageExtent::D3 { .. } => 1,
+            _ => MAX_IMAGE_LAYERS,
+        },
+        max_samples,
+        max_size: MAX_IMAGE_SIZE,
+    })
+}
+
+/// Swapchain textures can only be rendered to.
+pub(super) fn surface_usage() -> ImageUsage {
+    ImageUsage::COLOR_ATTACHMENT
+}
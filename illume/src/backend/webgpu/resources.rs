@@ -0,0 +1,1268 @@
+use {
+    super::{
+        convert::{sample_type, view_dimension, ToWgpu as _},
+        device::WeakDevice,
+        new_handle,
+        swapchain::FrameSlot,
+    },
+    crate::{
+        accel::AccelerationStructureInfo,
+        buffer::BufferInfo,
+        descriptor::{
+            DescriptorSetInfo, DescriptorSetLayoutInfo, DescriptorType,
+        },
+        framebuffer::FramebufferInfo,
+        image::{ImageInfo, ImageSubresourceRange, Samples},
+        memory::MemoryUsage,
+        pipeline::{
+            BlendFactor, Blending, ColorBlend, ComponentMask,
+            ComputePipelineInfo, Culling, DepthTest, FrontFace,
+            GraphicsPipelineInfo, PipelineLayoutInfo, PrimitiveTopology,
+            RayTracingPipelineInfo, State, StencilTest,
+        },
+        query::QueryPoolInfo,
+        render_pass::RenderPassInfo,
+        sampler::{Filter, MipmapMode, SamplerInfo},
+        shader::ShaderModuleInfo,
+        view::{ImageViewInfo, ImageViewKind},
+        DeviceAddress,
+    },
+    parking_lot::Mutex,
+    std::{
+        collections::{BTreeMap, HashMap},
+        fmt::{self, Debug},
+        hash::{Hash, Hasher},
+        num::{NonZeroU32, NonZeroU64},
+        ops::{Deref, Range},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+    },
+};
+
+/// Defines resource type that shares info, owner and unique handle
+/// between clones.
+macro_rules! define_handle {
+    (
+        pub struct $resource:ident : $inner:ident {
+            info: $info:ty,
+            $($fname:ident: $fty:ty,)*
+        }
+    ) => {
+        struct $inner {
+            info: $info,
+            owner: WeakDevice,
+            handle: u64,
+            $($fname: $fty,)*
+        }
+
+        // Browsers run `wgpu` on single thread.
+        #[cfg(target_arch = "wasm32")]
+        unsafe impl Send for $inner {}
+        #[cfg(target_arch = "wasm32")]
+        unsafe impl Sync for $inner {}
+
+        #[derive(Clone)]
+        pub struct $resource {
+            inner: Arc<$inner>,
+        }
+
+        impl Debug for $resource {
+            fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+                if fmt.alternate() {
+                    fmt.debug_struct(stringify!($resource))
+                        .field("info", &self.inner.info)
+                        .field("owner", &self.inner.owner)
+                        .field("handle", &self.inner.handle)
+                        $(.field(stringify!($fname), &self.inner.$fname))*
+                        .finish()
+                } else {
+                    write!(
+                        fmt,
+                        concat!(stringify!($resource), "({})"),
+                        self.inner.handle
+                    )
+                }
+            }
+        }
+
+        impl PartialEq for $resource {
+            fn eq(&self, rhs: &Self) -> bool {
+                self.inner.handle == rhs.inner.handle
+            }
+        }
+
+        impl Eq for $resource {}
+
+        impl Hash for $resource {
+            fn hash<H>(&self, hasher: &mut H)
+            where
+                H: Hasher,
+            {
+                self.inner.handle.hash(hasher)
+            }
+        }
+
+        impl $resource {
+            pub fn info(&self) -> &$info {
+                &self.inner.info
+            }
+
+            pub fn is_owned_by(
+                &self,
+                owner: &impl PartialEq<WeakDevice>,
+            ) -> bool {
+                *owner == self.inner.owner
+            }
+
+            pub(super) fn new(
+                info: $info,
+                owner: WeakDevice,
+                $($fname: $fty,)*
+            ) -> Self {
+                $resource {
+                    inner: Arc::new($inner {
+                        info,
+                        owner,
+                        handle: new_handle(),
+                        $($fname,)*
+                    }),
+                }
+            }
+        }
+    };
+}
+
+define_handle! {
+    pub struct Buffer: BufferInner {
+        info: BufferInfo,
+        buffer: wgpu::Buffer,
+    }
+}
+
+impl Drop for BufferInner {
+    fn drop(&mut self) {
+        if let Some(device) = self.owner.upgrade() {
+            device.free_memory(self.info.size);
+        }
+    }
+}
+
+impl Buffer {
+    /// Buffers have no device addresses in `wgpu`.
+    pub fn address(&self) -> Option<DeviceAddress> {
+        None
+    }
+
+    pub(super) fn handle(&self) -> &wgpu::Buffer {
+        &self.inner.buffer
+    }
+}
+
+/// Buffer with host copy of its content.
+/// Mapped ranges are read back from and written to the buffer
+/// through the queue.
+pub struct MappableBuffer {
+    buffer: Buffer,
+    memory_usage: MemoryUsage,
+
+    /// Host copy of the content.
+    /// Size is rounded up to `wgpu::COPY_BUFFER_ALIGNMENT`.
+    memory: Box<[u8]>,
+
+    /// Currently mapped range.
+    mapped: Option<Range<usize>>,
+}
+
+impl From<MappableBuffer> for Buffer {
+    fn from(buffer: MappableBuffer) -> Self {
+        buffer.buffer
+    }
+}
+
+impl PartialEq for MappableBuffer {
+    fn eq(&self, rhs: &Self) -> bool {
+        std::ptr::eq(self, rhs)
+    }
+}
+
+impl Eq for MappableBuffer {}
+
+impl Hash for MappableBuffer {
+    fn hash<H>(&self, hasher: &mut H)
+    where
+        H: Hasher,
+    {
+        self.buffer.inner.handle.hash(hasher)
+    }
+}
+
+impl Debug for MappableBuffer {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if fmt.alternate() {
+            fmt.debug_struct("MappableBuffer")
+                .field("buffer", &self.buffer)
+                .field("memory_usage", &self.memory_usage)
+                .field("mapped", &self.mapped)
+                .finish()
+        } else {
+            write!(fmt, "MappableBuffer({})", self.buffer.inner.handle)
+        }
+    }
+}
+
+impl Deref for MappableBuffer {
+    type Target = Buffer;
+
+    fn deref(&self) -> &Buffer {
+        &self.buffer
+    }
+}
+
+impl MappableBuffer {
+    pub fn share(&self) -> Buffer {
+        self.buffer.clone()
+    }
+
+    pub(super) fn from_buffer(
+        buffer: Buffer,
+        memory_usage: MemoryUsage,
+        size: usize,
+    ) -> Self {
+        MappableBuffer {
+            buffer,
+            memory_usage,
+            memory: vec![0; size].into_boxed_slice(),
+            mapped: None,
+        }
+    }
+
+    pub(super) fn memory_usage(&self) -> MemoryUsage {
+        self.memory_usage
+    }
+
+    /// Returns host copy of the content.
+    pub(super) fn memory(&mut self) -> &mut [u8] {
+        &mut self.memory
+    }
+
+    /// Marks range mapped.
+    /// Returns `false` if buffer is already mapped.
+    pub(super) fn map(&mut self, range: Range<usize>) -> bool {
+        if self.mapped.is_some() {
+            false
+        } else {
+            self.mapped = Some(range);
+            true
+        }
+    }
+
+    /// Marks buffer unmapped and returns range that was mapped.
+    pub(super) fn unmap(&mut self) -> Option<Range<usize>> {
+        self.mapped.take()
+    }
+
+    pub(super) fn is_mapped(&self) -> bool {
+        self.mapped.is_some()
+    }
+}
+
+/// Memory that backs an image.
+#[derive(Debug)]
+pub(super) enum ImageMemory {
+    Texture(wgpu::Texture),
+
+    /// Swapchain image.
+    /// Texture of the acquired frame is kept in the slot until presented.
+    Frame(Arc<FrameSlot>),
+}
+
+define_handle! {
+    pub struct Image: ImageInner {
+        info: ImageInfo,
+        memory: ImageMemory,
+    }
+}
+
+impl Image {
+    pub(super) fn memory(&self) -> &ImageMemory {
+        &self.inner.memory
+    }
+
+    /// Creates `wgpu` view of the image subresource.
+    /// Returns `None` for swapchain images, frame view is used for them.
+    pub(super) fn create_view(
+        &self,
+        kind: ImageViewKind,
+        subresource: &ImageSubresourceRange,
+    ) -> Option<wgpu::TextureView> {
+        let texture = match &self.inner.memory {
+            ImageMemory::Texture(texture) => texture,
+            ImageMemory::Frame(_) => return None,
+        };
+
+        Some(texture.create_view(&wgpu::TextureViewDescriptor {
+            label: None,
+            format: self.inner.info.format.to_wgpu(),
+            dimension: Some(view_dimension(kind, subresource.layer_count)),
+            aspect: subresource.aspect.to_wgpu(),
+            base_mip_level: subresource.first_level,
+            level_count: NonZeroU32::new(subresource.level_count),
+            base_array_layer: subresource.first_layer,
+            array_layer_count: NonZeroU32::new(subresource.layer_count),
+        }))
+    }
+}
+
+define_handle! {
+    pub struct ImageView: ImageViewInner {
+        info: ImageViewInfo,
+        view: Option<wgpu::TextureView>,
+    }
+}
+
+impl ImageView {
+    /// Returns `None` for views of swapchain images.
+    pub(super) fn handle(&self) -> Option<&wgpu::TextureView> {
+        self.inner.view.as_ref()
+    }
+}
+
+/// Fence that is signalled when submission is made.
+#[derive(Clone)]
+pub struct Fence {
+    handle: u64,
+    owner: WeakDevice,
+    signalled: Arc<AtomicBool>,
+}
+
+impl Debug for Fence {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if fmt.alternate() {
+            fmt.debug_struct("Fence")
+                .field("handle", &self.handle)
+                .field("owner", &self.owner)
+                .field("signalled", &self.signalled)
+                .finish()
+        } else {
+            write!(fmt, "Fence({})", self.handle)
+        }
+    }
+}
+
+impl PartialEq for Fence {
+    fn eq(&self, rhs: &Self) -> bool {
+        self.handle == rhs.handle
+    }
+}
+
+impl Eq for Fence {}
+
+impl Hash for Fence {
+    fn hash<H>(&self, hasher: &mut H)
+    where
+        H: Hasher,
+    {
+        self.handle.hash(hasher)
+    }
+}
+
+impl Fence {
+    pub(super) fn new(owner: WeakDevice) -> Self {
+        Fence {
+            handle: new_handle(),
+            owner,
+            signalled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub(super) fn is_owned_by(
+        &self,
+        owner: &impl PartialEq<WeakDevice>,
+    ) -> bool {
+        *owner == self.owner
+    }
+
+    pub(super) fn is_signalled(&self) -> bool {
+        self.signalled.load(Ordering::Acquire)
+    }
+
+    pub(super) fn signal(&self) {
+        self.signalled.store(true, Ordering::Release);
+    }
+
+    pub(super) fn reset(&self) {
+        self.signalled.store(false, Ordering::Release);
+    }
+}
+
+/// Semaphores have no effect, `wgpu` orders work itself.
+#[derive(Clone)]
+pub struct Semaphore {
+    handle: u64,
+    owner: WeakDevice,
+}
+
+impl Debug for Semaphore {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if fmt.alternate() {
+            fmt.debug_struct("Semaphore")
+                .field("handle", &self.handle)
+                .field("owner", &self.owner)
+                .finish()
+        } else {
+            write!(fmt, "Semaphore({})", self.handle)
+        }
+    }
+}
+
+impl PartialEq for Semaphore {
+    fn eq(&self, rhs: &Self) -> bool {
+        self.handle == rhs.handle
+    }
+}
+
+impl Eq for Semaphore {}
+
+impl Hash for Semaphore {
+    fn hash<H>(&self, hasher: &mut H)
+    where
+        H: Hasher,
+    {
+        self.handle.hash(hasher)
+    }
+}
+
+impl Semaphore {
+    pub(super) fn new(owner: WeakDevice) -> Self {
+        Semaphore {
+            handle: new_handle(),
+            owner,
+        }
+    }
+
+    pub(super) fn is_owned_by(
+        &self,
+        owner: &impl PartialEq<WeakDevice>,
+    ) -> bool {
+        *owner == self.owner
+    }
+}
+
+define_handle! {
+    pub struct RenderPass: RenderPassInner {
+        info: RenderPassInfo,
+    }
+}
+
+define_handle! {
+    pub struct Sampler: SamplerInner {
+        info: SamplerInfo,
+        sampler: wgpu::Sampler,
+    }
+}
+
+impl Sampler {
+    pub(super) fn handle(&self) -> &wgpu::Sampler {
+        &self.inner.sampler
+    }
+
+    /// Returns `true` if sampler uses linear filtering.
+    pub(super) fn is_filtering(&self) -> bool {
+        let info = &self.inner.info;
+
+        info.mag_filter == Filter::Linear
+            || info.min_filter == Filter::Linear
+            || info.mipmap_mode == MipmapMode::Linear
+    }
+}
+
+define_handle! {
+    pub struct QueryPool: QueryPoolInner {
+        info: QueryPoolInfo,
+    }
+}
+
+define_handle! {
+    pub struct Framebuffer: FramebufferInner {
+        info: FramebufferInfo,
+    }
+}
+
+define_handle! {
+    pub struct ShaderModule: ShaderModuleInner {
+        info: ShaderModuleInfo,
+        module: wgpu::ShaderModule,
+    }
+}
+
+impl ShaderModule {
+    pub(super) fn handle(&self) -> &wgpu::ShaderModule {
+        &self.inner.module
+    }
+}
+
+define_handle! {
+    pub struct DescriptorSetLayout: DescriptorSetLayoutInner {
+        info: DescriptorSetLayoutInfo,
+        bind_group_layouts: Mutex<
+            HashMap<Vec<wgpu::BindGroupLayoutEntry>, Arc<wgpu::BindGroupLayout>>,
+        >,
+    }
+}
+
+impl DescriptorSetLayout {
+    /// Returns bind group layout with specified entries.
+    ///
+    /// Entries depend on descriptors written to the set,
+    /// so one descriptor set layout may map to multiple bind group layouts.
+    pub(super) fn bind_group_layout(
+        &self,
+        device: &wgpu::Device,
+        entries: Vec<wgpu::BindGroupLayoutEntry>,
+    ) -> Arc<wgpu::BindGroupLayout> {
+        let mut layouts = self.inner.bind_group_layouts.lock();
+
+        if let Some(layout) = layouts.get(&entries) {
+            return layout.clone();
+        }
+
+        let layout = Arc::new(device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                label: None,
+                entries: &entries,
+            },
+        ));
+
+        layouts.insert(entries, layout.clone());
+        layout
+    }
+}
+
+/// Descriptor written to a descriptor set.
+#[derive(Clone, Debug)]
+pub(super) enum Descriptor {
+    Sampler(Sampler),
+    Image(ImageView),
+    Buffer {
+        buffer: Buffer,
+        offset: u64,
+        size: u64,
+    },
+}
+
+/// Bind group created for descriptor set.
+#[derive(Clone, Debug)]
+pub(super) struct BoundSet {
+    pub layout: Arc<wgpu::BindGroupLayout>,
+    pub group: Arc<wgpu::BindGroup>,
+
+    /// Number of bindings with dynamic offsets.
+    pub dynamic_count: usize,
+}
+
+#[derive(Debug, Default)]
+pub(super) struct DescriptorSetState {
+    /// Descriptors written to each binding.
+    descriptors: BTreeMap<u32, Vec<Option<Descriptor>>>,
+
+    /// Bind group for written descriptors.
+    /// Reset when descriptors are updated.
+    bound: Option<BoundSet>,
+}
+
+define_handle! {
+    pub struct DescriptorSet: DescriptorSetInner {
+        info: DescriptorSetInfo,
+        state: Mutex<DescriptorSetState>,
+    }
+}
+
+impl DescriptorSet {
+    pub(super) fn write(
+        &self,
+        binding: u32,
+        element: u32,
+        descriptors: impl IntoIterator<Item = Descriptor>,
+    ) {
+        let mut state = self.inner.state.lock();
+        let slots = state.descriptors.entry(binding).or_default();
+
+        for (index, descriptor) in (element as usize..).zip(descriptors) {
+            if slots.len() <= index {
+                slots.resize(index + 1, None);
+            }
+            slots[index] = Some(descriptor);
+        }
+
+        state.bound = None;
+    }
+
+    pub(super) fn read(
+        &self,
+        binding: u32,
+        element: u32,
+        count: u32,
+    ) -> Vec<Option<Descriptor>> {
+        let state = self.inner.state.lock();
+        let slots = state.descriptors.get(&binding);
+
+        (element..element + count)
+            .map(|index| {
+                slots
+                    .and_then(|slots| slots.get(index as usize))
+                    .cloned()
+                    .flatten()
+            })
+            .collect()
+    }
+
+    /// Returns bind group with descriptors written to the set.
+    ///
+    /// Only bindings with descriptors written are included.
+    /// Array bindings include elements written contiguously from the first.
+    pub(super) fn bind(&self, device: &wgpu::Device) -> BoundSet {
+        let mut state = self.inner.state.lock();
+
+        if let Some(bound) = &state.bound {
+            return bound.clone();
+        }
+
+        let layout_info = self.inner.info.layout.info();
+
+        let mut layout_entries = Vec::new();
+        let mut dynamic_count = 0;
+
+        for binding in &layout_info.bindings {
+            let written = match state.descriptors.get(&binding.binding) {
+                Some(written) => written,
+                None => continue,
+            };
+
+            let count = written
+                .iter()
+                .take_while(|descriptor| descriptor.is_some())
+                .count()
+                .min(binding.count as usize);
+
+            let first = match written.first() {
+                Some(Some(first)) if count > 0 => first,
+                _ => continue,
+            };
+
+            if let DescriptorType::UniformBufferDynamic
+            | DescriptorType::StorageBufferDynamic = binding.ty
+            {
+                dynamic_count += 1;
+            }
+
+            layout_entries.push(wgpu::BindGroupLayoutEntry {
+                binding: binding.binding,
+                visibility: binding.stages.to_wgpu(),
+                ty: binding_type(binding.ty, first),
+                count: if binding.count > 1 {
+                    NonZeroU32::new(count as u32)
+                } else {
+                    None
+                },
+            });
+        }
+
+        let layout = self
+            .inner
+            .info
+            .layout
+            .bind_group_layout(device, layout_entries.clone());
+
+        let group = {
+            let arrays: Vec<Vec<wgpu::TextureView>> = layout_entries
+                .iter()
+                .map(|entry| match entry.count {
+                    Some(count) => state.descriptors[&entry.binding]
+                        [..count.get() as usize]
+                        .iter()
+                        .map(|descriptor| match descriptor {
+                            Some(Descriptor::Image(view)) => view
+                                .info()
+                                .image
+                                .create_view(
+                                    view.info().view_kind,
+                                    &view.info().subresource,
+                                )
+                                .expect(
+                                    "Swapchain images can't be used in descriptor sets",
+                                ),
+                            _ => unreachable!(),
+                        })
+                        .collect(),
+                    None => Vec::new(),
+                })
+                .collect();
+
+            let entries: Vec<_> = layout_entries
+                .iter()
+                .zip(&arrays)
+                .map(|(entry, views)| {
+                    let resource = match entry.count {
+                        Some(_) => wgpu::BindingResource::TextureViewArray(views),
+                        None => match &state.descriptors[&entry.binding][0] {
+                            Some(Descriptor::Sampler(sampler)) => {
+                                wgpu::BindingResource::Sampler(sampler.handle())
+                            }
+                            Some(Descriptor::Image(view)) => {
+                                wgpu::BindingResource::TextureView(
+                                    view.handle().expect(
+                                        "Swapchain images can't be used in descriptor sets",
+                                    ),
+                                )
+                            }
+                            Some(Descriptor::Buffer {
+                                buffer,
+                                offset,
+                                size,
+                            }) => wgpu::BindingResource::Buffer {
+                                buffer: buffer.handle(),
+                                offset: *offset,
+                                size: NonZeroU64::new(*size),
+                            },
+                            None => unreachable!(),
+                        },
+                    };
+
+                    wgpu::BindGroupEntry {
+                        binding: entry.binding,
+                        resource,
+                    }
+                })
+                .collect();
+
+            Arc::new(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: None,
+                layout: &layout,
+                entries: &entries,
+            }))
+        };
+
+        let bound = BoundSet {
+            layout,
+            group,
+            dynamic_count,
+        };
+
+        state.bound = Some(bound.clone());
+        bound
+    }
+}
+
+/// Returns binding type for descriptor written to binding of specified type.
+fn binding_type(
+    ty: DescriptorType,
+    descriptor: &Descriptor,
+) -> wgpu::BindingType {
+    match (ty, descriptor) {
+        (DescriptorType::Sampler, Descriptor::Sampler(sampler)) => {
+            wgpu::BindingType::Sampler {
+                filtering: sampler.is_filtering(),
+                comparison: sampler.info().compare_op.is_some(),
+            }
+        }
+        (DescriptorType::SampledImage, Descriptor::Image(view))
+        | (DescriptorType::InputAttachment, Descriptor::Image(view)) => {
+            let image = view.info().image.info();
+            wgpu::BindingType::Texture {
+                sample_type: sample_type(image.format),
+                view_dimension: view_dimension(
+                    view.info().view_kind,
+                    view.info().subresource.layer_count,
+                ),
+                multisampled: image.samples != Samples::Samples1,
+            }
+        }
+        // WebGPU guarantees only write-only access to storage textures.
+        (DescriptorType::StorageImage, Descriptor::Image(view)) => {
+            let image = view.info().image.info();
+            wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::WriteOnly,
+                format: image
+                    .format
+                    .to_wgpu()
+                    .expect("Storage image format is supported"),
+                view_dimension: view_dimension(
+                    view.info().view_kind,
+                    view.info().subresource.layer_count,
+                ),
+            }
+        }
+        (DescriptorType::UniformBuffer, Descriptor::Buffer { .. }) => {
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            }
+        }
+        (DescriptorType::UniformBufferDynamic, Descriptor::Buffer { .. }) => {
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: true,
+                min_binding_size: None,
+            }
+        }
+        (DescriptorType::StorageBuffer, Descriptor::Buffer { .. }) => {
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            }
+        }
+        (DescriptorType::StorageBufferDynamic, Descriptor::Buffer { .. }) => {
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: true,
+                min_binding_size: None,
+            }
+        }
+        (ty, descriptor) => panic!(
+            "Descriptor {:?} written to binding of type {:?}",
+            descriptor, ty
+        ),
+    }
+}
+
+/// Key of cached `wgpu` objects that depend on bind group layouts.
+/// Layouts are kept alive by pipeline layout cache,
+/// so their addresses are never reused while key is stored.
+fn layouts_key(layouts: &[Arc<wgpu::BindGroupLayout>]) -> Vec<usize> {
+    layouts
+        .iter()
+        .map(|layout| &**layout as *const wgpu::BindGroupLayout as usize)
+        .collect()
+}
+
+#[derive(Debug)]
+pub(super) struct CachedPipelineLayout {
+    layout: Arc<wgpu::PipelineLayout>,
+    _bind_group_layouts: Vec<Arc<wgpu::BindGroupLayout>>,
+}
+
+define_handle! {
+    pub struct PipelineLayout: PipelineLayoutInner {
+        info: PipelineLayoutInfo,
+        layouts: Mutex<HashMap<Vec<usize>, CachedPipelineLayout>>,
+    }
+}
+
+impl PipelineLayout {
+    /// Returns pipeline layout with specified bind group layouts.
+    /// Push constants are omitted if device doesn't support them.
+    pub(super) fn pipeline_layout(
+        &self,
+        device: &wgpu::Device,
+        bind_group_layouts: &[Arc<wgpu::BindGroupLayout>],
+    ) -> Arc<wgpu::PipelineLayout> {
+        let key = layouts_key(bind_group_layouts);
+        let mut layouts = self.inner.layouts.lock();
+
+        if let Some(cached) = layouts.get(&key) {
+            return cached.layout.clone();
+        }
+
+        let push_constant_ranges: Vec<_> =
+            if device.features().contains(wgpu::Features::PUSH_CONSTANTS) {
+                self.inner
+                    .info
+                    .push_constants
+                    .iter()
+                    .map(|pc| wgpu::PushConstantRange {
+                        stages: pc.stages.to_wgpu(),
+                        range: pc.offset..pc.offset + pc.size,
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            };
+
+        let refs: Vec<&wgpu::BindGroupLayout> =
+            bind_group_layouts.iter().map(|layout| &**layout).collect();
+
+        let layout = Arc::new(device.create_pipeline_layout(
+            &wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &refs,
+                push_constant_ranges: &push_constant_ranges,
+            },
+        ));
+
+        layouts.insert(
+            key,
+            CachedPipelineLayout {
+                layout: layout.clone(),
+                _bind_group_layouts: bind_group_layouts.to_vec(),
+            },
+        );
+
+        layout
+    }
+}
+
+define_handle! {
+    pub struct ComputePipeline: ComputePipelineInner {
+        info: ComputePipelineInfo,
+        pipelines: Mutex<HashMap<Vec<usize>, Arc<wgpu::ComputePipeline>>>,
+    }
+}
+
+impl ComputePipeline {
+    /// Returns pipeline for specified bind group layouts.
+    pub(super) fn compute_pipeline(
+        &self,
+        device: &wgpu::Device,
+        bind_group_layouts: &[Arc<wgpu::BindGroupLayout>],
+    ) -> Arc<wgpu::ComputePipeline> {
+        let key = layouts_key(bind_group_layouts);
+        let mut pipelines = self.inner.pipelines.lock();
+
+        if let Some(pipeline) = pipelines.get(&key) {
+            return pipeline.clone();
+        }
+
+        let info = &self.inner.info;
+        let layout = info.layout.pipeline_layout(device, bind_group_layouts);
+
+        let pipeline = Arc::new(device.create_compute_pipeline(
+            &wgpu::ComputePipelineDescriptor {
+                label: None,
+                layout: Some(&layout),
+                module: info.shader.module().handle(),
+                entry_point: info.shader.entry(),
+            },
+        ));
+
+        pipelines.insert(key, pipeline.clone());
+        pipeline
+    }
+}
+
+/// Graphics pipeline states that may be dynamic in this crate,
+/// but are baked into `wgpu` pipelines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(super) struct RasterState {
+    pub culling: Option<Culling>,
+    pub front_face: FrontFace,
+    pub topology: PrimitiveTopology,
+    pub depth_test: Option<DepthTest>,
+}
+
+define_handle! {
+    pub struct GraphicsPipeline: GraphicsPipelineInner {
+        info: GraphicsPipelineInfo,
+        pipelines: Mutex<
+            HashMap<(RasterState, Vec<usize>), Arc<wgpu::RenderPipeline>>,
+        >,
+    }
+}
+
+impl GraphicsPipeline {
+    /// Returns pipeline for specified states and bind group layouts.
+    ///
+    /// Returns `None` if nothing would be drawn with the states,
+    /// that is rasterization is disabled or both faces are culled,
+    /// or if topology is not supported by `wgpu`.
+    pub(super) fn render_pipeline(
+        &self,
+        device: &wgpu::Device,
+        state: RasterState,
+        bind_group_layouts: &[Arc<wgpu::BindGroupLayout>],
+    ) -> Option<Arc<wgpu::RenderPipeline>> {
+        let info = &self.inner.info;
+        let rasterizer = info.rasterizer.as_ref()?;
+
+        let cull_mode = match state.culling {
+            None => wgpu::CullMode::None,
+            Some(Culling::Front) => wgpu::CullMode::Front,
+            Some(Culling::Back) => wgpu::CullMode::Back,
+            Some(Culling::FrontAndBack) => return None,
+        };
+
+        let topology = match state.topology.to_wgpu() {
+            Some(topology) => topology,
+            None => {
+                tracing::error!(
+                    "Primitive topology {:?} is not supported",
+                    state.topology
+                );
+                return None;
+            }
+        };
+
+        let key = (state, layouts_key(bind_group_layouts));
+        let mut pipelines = self.inner.pipelines.lock();
+
+        if let Some(pipeline) = pipelines.get(&key) {
+            return Some(pipeline.clone());
+        }
+
+        let layout = info.layout.pipeline_layout(device, bind_group_layouts);
+        let pass = info.render_pass.info();
+        let subpass = &pass.subpasses[info.subpass as usize];
+
+        let attributes: Vec<Vec<wgpu::VertexAttribute>> = (0..info
+            .vertex_bindings
+            .len())
+            .map(|binding| {
+                info.vertex_attributes
+                    .iter()
+                    .filter(|attribute| attribute.binding as usize == binding)
+                    .map(|attribute| wgpu::VertexAttribute {
+                        format: attribute
+                            .format
+                            .to_wgpu()
+                            .expect("Vertex attribute format is supported"),
+                        offset: attribute.offset.into(),
+                        shader_location: attribute.location,
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let buffers: Vec<_> = info
+            .vertex_bindings
+            .iter()
+            .zip(&attributes)
+            .map(|(binding, attributes)| wgpu::VertexBufferLayout {
+                array_stride: binding.stride.into(),
+                step_mode: binding.rate.to_wgpu(),
+                attributes,
+            })
+            .collect();
+
+        let depth_stencil = subpass.depth.map(|depth| {
+            let format = pass.attachments[depth].format;
+            let (stencil, read_mask, write_mask) =
+                match &rasterizer.stencil_tests {
+                    Some(tests) => (
+                        wgpu::StencilState {
+                            front: stencil_face(&tests.front),
+                            back: stencil_face(&tests.back),
+                            read_mask: 0,
+                            write_mask: 0,
+                        },
+                        static_or(tests.front.compare_mask, !0),
+                        static_or(tests.front.write_mask, !0),
+                    ),
+                    None => (wgpu::StencilState::default(), 0, 0),
+                };
+
+            wgpu::DepthStencilState {
+                format: format
+                    .to_wgpu()
+                    .expect("Depth attachment format is supported"),
+                depth_write_enabled: state
+                    .depth_test
+                    .map_or(false, |test| test.write),
+                depth_compare: state
+                    .depth_test
+                    .map_or(wgpu::CompareFunction::Always, |test| {
+                        test.compare.to_wgpu()
+                    }),
+                stencil: wgpu::StencilState {
+                    read_mask,
+                    write_mask,
+                    ..stencil
+                },
+                bias: wgpu::DepthBiasState::default(),
+                clamp_depth: rasterizer.depth_clamp
+                    && device
+                        .features()
+                        .contains(wgpu::Features::DEPTH_CLAMPING),
+            }
+        });
+
+        let targets: Vec<_> = subpass
+            .colors
+            .iter()
+            .enumerate()
+            .map(|(index, &color)| {
+                let (blending, write_mask) =
+                    color_blending(&rasterizer.color_blend, index);
+
+                let (color_blend, alpha_blend) = match blending {
+                    Some(blending) => (
+                        wgpu::BlendState {
+                            src_factor: blending.color_src_factor.to_wgpu(),
+                            dst_factor: blending.color_dst_factor.to_wgpu(),
+                            operation: blending.color_op.to_wgpu(),
+                        },
+                        wgpu::BlendState {
+                            src_factor: blending.alpha_src_factor.to_wgpu(),
+                            dst_factor: blending.alpha_dst_factor.to_wgpu(),
+                            operation: blending.alpha_op.to_wgpu(),
+                        },
+                    ),
+                    None => {
+                        (wgpu::BlendState::REPLACE, wgpu::BlendState::REPLACE)
+                    }
+                };
+
+                wgpu::ColorTargetState {
+                    format: pass.attachments[color]
+                        .format
+                        .to_wgpu()
+                        .expect("Color attachment format is supported"),
+                    alpha_blend,
+                    color_blend,
+                    write_mask: write_mask.to_wgpu(),
+                }
+            })
+            .collect();
+
+        let pipeline = Arc::new(device.create_render_pipeline(
+            &wgpu::RenderPipelineDescriptor {
+                label: None,
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: info.vertex_shader.module().handle(),
+                    entry_point: info.vertex_shader.entry(),
+                    buffers: &buffers,
+                },
+                primitive: wgpu::PrimitiveState {
+                    topology,
+                    strip_index_format: None,
+                    front_face: state.front_face.to_wgpu(),
+                    cull_mode,
+                    polygon_mode: rasterizer.polygon_mode.to_wgpu(),
+                },
+                depth_stencil,
+                multisample: wgpu::MultisampleState {
+                    count:
+                        pass.subpass_samples(info.subpass as usize).to_wgpu(),
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                fragment: rasterizer.fragment_shader.as_ref().map(|shader| {
+                    wgpu::FragmentState {
+                        module: shader.module().handle(),
+                        entry_point: shader.entry(),
+                        targets: &targets,
+                    }
+                }),
+            },
+        ));
+
+        pipelines.insert(key, pipeline.clone());
+        Some(pipeline)
+    }
+}
+
+/// Returns value of static state or default for dynamic one.
+/// `wgpu` has no commands to set these states.
+fn static_or<T>(state: State<T>, default: T) -> T {
+    match state {
+        State::Static { value } => value,
+        State::Dynamic => default,
+    }
+}
+
+fn stencil_face(test: &StencilTest) -> wgpu::StencilFaceState {
+    wgpu::StencilFaceState {
+        compare: test.compare.to_wgpu(),
+        fail_op: test.fail.to_wgpu(),
+        depth_fail_op: test.depth_fail.to_wgpu(),
+        pass_op: test.pass.to_wgpu(),
+    }
+}
+
+/// Returns blending and write mask of color attachment.
+/// Logic operations are not supported by `wgpu`,
+/// source color is written as is.
+fn color_blending(
+    color_blend: &ColorBlend,
+    index: usize,
+) -> (Option<Blending>, ComponentMask) {
+    match color_blend {
+        ColorBlend::Logic { .. } => (None, ComponentMask::RGBA),
+        ColorBlend::Blending {
+            blending,
+            write_mask,
+            ..
+        } => (*blending, *write_mask),
+        ColorBlend::IndependentBlending { blending, .. } => blending
+            .get(index)
+            .copied()
+            .unwrap_or((None, ComponentMask::RGBA)),
+    }
+}
+
+/// Returns blend constants if they are static.
+pub(super) fn blend_constants(color_blend: &ColorBlend) -> Option<wgpu::Color> {
+    let constants = match color_blend {
+        ColorBlend::Logic { .. } => return None,
+        ColorBlend::Blending { constants, .. }
+        | ColorBlend::IndependentBlending { constants, .. } => constants,
+    };
+
+    let uses_constants = |blending: &Blending| {
+        [
+            blending.color_src_factor,
+            blending.color_dst_factor,
+            blending.alpha_src_factor,
+            blending.alpha_dst_factor,
+        ]
+        .iter()
+        .any(|factor| match factor {
+            BlendFactor::ConstantColor
+            | BlendFactor::OneMinusConstantColor
+            | BlendFactor::ConstantAlpha
+            | BlendFactor::OneMinusConstantAlpha => true,
+            _ => false,
+        })
+    };
+
+    let used = match color_blend {
+        ColorBlend::Blending {
+            blending: Some(blending),
+            ..
+        } => uses_constants(blending),
+        ColorBlend::IndependentBlending { blending, .. } => blending
+            .iter()
+            .filter_map(|(blending, _)| blending.as_ref())
+            .any(uses_constants),
+        _ => false,
+    };
+
+    match constants {
+        State::Static { value } if used => Some(wgpu::Color {
+            r: value[0].into_inner().into(),
+            g: value[1].into_inner().into(),
+            b: value[2].into_inner().into(),
+            a: value[3].into_inner().into(),
+        }),
+        _ => None,
+    }
+}
+
+define_handle! {
+    pub struct AccelerationStructure: AccelerationStructureInner {
+        info: AccelerationStructureInfo,
+        address: DeviceAddress,
+    }
+}
+
+impl AccelerationStructure {
+    pub fn address(&self) -> DeviceAddress {
+        self.inner.address
+    }
+}
+
+define_handle! {
+    pub struct RayTracingPipeline: RayTracingPipelineInner {
+        info: RayTracingPipelineInfo,
+        group_count: usize,
+    }
+}
+
+impl RayTracingPipeline {
+    pub fn group_count(&self) -> usize {
+        self.inner.group_count
+    }
+}
@@ -0,0 +1,140 @@
+use {
+    crate::{
+        surface::{SurfaceError, SurfaceInfo},
+        Extent2d,
+    },
+    parking_lot::Mutex,
+    std::{
+        fmt::{self, Debug},
+        sync::atomic::{AtomicBool, Ordering},
+    },
+};
+
+pub(crate) struct Inner {
+    pub surface: wgpu::Surface,
+    pub used: AtomicBool,
+    pub info: SurfaceInfo,
+
+    /// Extent set by `Surface::set_extent`.
+    /// Native windows can't be queried for their size by `wgpu`.
+    pub extent: Mutex<Extent2d>,
+}
+
+// Browsers run `wgpu` on single thread.
+#[cfg(target_arch = "wasm32")]
+unsafe impl Send for Inner {}
+#[cfg(target_arch = "wasm32")]
+unsafe impl Sync for Inner {}
+
+impl Debug for Inner {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("Surface")
+            .field("used", &self.used)
+            .field("info", &self.info)
+            .field("extent", &*self.extent.lock())
+            .finish()
+    }
+}
+
+#[derive(Clone, Debug)]
+#[repr(transparent)]
+pub struct Surface {
+    inner: std::sync::Arc<Inner>,
+}
+
+impl std::cmp::PartialEq for Surface {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(&*self.inner, &*other.inner)
+    }
+}
+
+impl std::cmp::Eq for Surface {}
+
+impl std::hash::Hash for Surface {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        std::ptr::hash(&*self.inner, state)
+    }
+}
+
+impl Surface {
+    pub(crate) fn make(surface: wgpu::Surface, info: SurfaceInfo) -> Self {
+        Surface {
+            inner: std::sync::Arc::new(Inner {
+                surface,
+                used: AtomicBool::new(false),
+                info,
+                extent: Mutex::new(Extent2d {
+                    width: 0,
+                    height: 0,
+                }),
+            }),
+        }
+    }
+
+    pub(crate) fn handle(&self) -> &wgpu::Surface {
+        &self.inner.surface
+    }
+
+    pub(crate) fn mark_used(&self) -> Result<(), SurfaceError> {
+        if self.inner.used.fetch_or(true, Ordering::SeqCst) {
+            Err(SurfaceError::AlreadyUsed)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub(crate) fn mark_unused(&self) {
+        self.inner.used.store(false, Ordering::SeqCst);
+    }
+
+    /// Consumes surface and returns `true` if no other clone exists.
+    pub(crate) fn into_unique(self) -> bool {
+        std::sync::Arc::try_unwrap(self.inner).is_ok()
+    }
+
+    pub fn info(&self) -> &SurfaceInfo {
+        &self.inner.info
+    }
+
+    /// Sets extent of swapchain images configured for native window.
+    /// Canvas size is used on the web instead.
+    pub fn set_extent(&self, extent: Extent2d) {
+        *self.inner.extent.lock() = extent;
+    }
+
+    /// Returns current extent of the surface.
+    /// Zero extent means that window is minimized or its size is unknown.
+    pub(crate) fn extent(&self) -> Extent2d {
+        #[cfg(target_arch = "wasm32")]
+        {
+            if let Some(extent) = canvas_extent(&self.inner.info) {
+                return extent;
+            }
+        }
+
+        *self.inner.extent.lock()
+    }
+}
+
+/// Returns size of the canvas element the surface was created for.
+#[cfg(target_arch = "wasm32")]
+fn canvas_extent(info: &SurfaceInfo) -> Option<Extent2d> {
+    use {raw_window_handle::RawWindowHandle, wasm_bindgen::JsCast as _};
+
+    let id = match info.window {
+        RawWindowHandle::Web(handle) => handle.id,
+        _ => return None,
+    };
+
+    let canvas = web_sys::window()?
+        .document()?
+        .query_selector(&format!("canvas[data-raw-handle=\"{}\"]", id))
+        .ok()??
+        .dyn_into::<web_sys::HtmlCanvasElement>()
+        .ok()?;
+
+    Some(Extent2d {
+        width: canvas.width(),
+        height: canvas.height(),
+    })
+}
@@ -0,0 +1,302 @@
+use {
+    super::{
+        convert::ToWgpu as _,
+        device::{Device, WeakDevice},
+        physical::{surface_usage, SURFACE_PRESENT_MODES},
+        resources::ImageMemory,
+        surface::Surface,
+    },
+    crate::{
+        format::Format,
+        image::{Image, ImageInfo, ImageUsage, Samples},
+        surface::{PresentMode, SurfaceError},
+        swapchain::SwapchainImageInfo,
+        Extent2d, OutOfMemory,
+    },
+    parking_lot::Mutex,
+    std::{
+        fmt::{self, Debug},
+        sync::{
+            atomic::{AtomicUsize, Ordering::*},
+            Arc,
+        },
+    },
+};
+
+/// Frame acquired from `wgpu` swap chain.
+/// Frame is presented when dropped.
+pub(super) type FrameSlot = Mutex<Option<wgpu::SwapChainFrame>>;
+
+#[derive(Debug)]
+pub struct SwapchainImage {
+    info: SwapchainImageInfo,
+    owner: WeakDevice,
+    counter: Arc<AtomicUsize>,
+    slot: Arc<FrameSlot>,
+    index: u32,
+}
+
+impl SwapchainImage {
+    pub fn info(&self) -> &SwapchainImageInfo {
+        &self.info
+    }
+
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub(super) fn is_owned_by(
+        &self,
+        owner: &impl PartialEq<WeakDevice>,
+    ) -> bool {
+        *owner == self.owner
+    }
+
+    /// Takes acquired frame out of the image.
+    /// Dropping the frame presents it.
+    pub(super) fn take_frame(&self) -> Option<wgpu::SwapChainFrame> {
+        self.slot.lock().take()
+    }
+}
+
+impl Drop for SwapchainImage {
+    fn drop(&mut self) {
+        self.counter.fetch_sub(1, Release);
+    }
+}
+
+struct SwapchainInner {
+    swap_chain: wgpu::SwapChain,
+    extent: Extent2d,
+
+    /// Image that represents frames of the swap chain.
+    image: Image,
+    slot: Arc<FrameSlot>,
+    counter: Arc<AtomicUsize>,
+}
+
+impl Debug for SwapchainInner {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("SwapchainInner")
+            .field("extent", &self.extent)
+            .field("image", &self.image)
+            .field("counter", &self.counter)
+            .finish()
+    }
+}
+
+/// Swapchain over `wgpu` swap chain.
+///
+/// Single image is acquired at a time, it is backed by current frame
+/// of the swap chain. Swap chain is sized to the surface extent and
+/// acquisition reports it out of date when the extent changes.
+#[derive(Debug)]
+pub struct Swapchain {
+    inner: Option<SwapchainInner>,
+    device: WeakDevice,
+    surface: Surface,
+}
+
+// Browsers run `wgpu` on single thread.
+#[cfg(target_arch = "wasm32")]
+unsafe impl Send for Swapchain {}
+#[cfg(target_arch = "wasm32")]
+unsafe impl Sync for Swapchain {}
+
+impl Swapchain {
+    pub(crate) fn new(
+        surface: &Surface,
+        device: &Device,
+    ) -> Result<Self, SurfaceError> {
+        surface.mark_used()?;
+
+        tracing::debug!("Swapchain created");
+        Ok(Swapchain {
+            inner: None,
+            device: device.downgrade(),
+            surface: surface.clone(),
+        })
+    }
+
+    /// Configures swap chain for current extent of the surface.
+    /// Swapchain stays unconfigured while extent is zero.
+    #[tracing::instrument]
+    pub fn configure(
+        &mut self,
+        usage: ImageUsage,
+        format: Format,
+        mode: PresentMode,
+    ) -> Result<(), SurfaceError> {
+        let device = self
+            .device
+            .upgrade()
+            .ok_or_else(|| SurfaceError::SurfaceLost)?;
+
+        if !surface_usage().contains(usage) {
+            return Err(SurfaceError::UsageNotSupported { usage });
+        }
+
+        let wgpu_format: Option<wgpu::TextureFormat> = match format {
+            Format::BGRA8Unorm
+            | Format::BGRA8Srgb
+            | Format::RGBA8Unorm
+            | Format::RGBA8Srgb => format.to_wgpu(),
+            _ => None,
+        };
+
+        let wgpu_format = match wgpu_format {
+            Some(wgpu_format) => wgpu_format,
+            None => return Err(SurfaceError::FormatUnsupported { format }),
+        };
+
+        let present_mode: Option<wgpu::PresentMode> = mode.to_wgpu();
+        let present_mode = match present_mode {
+            Some(present_mode) if SURFACE_PRESENT_MODES.contains(&mode) => {
+                present_mode
+            }
+            _ => return Err(SurfaceError::PresentModeUnsupported { mode }),
+        };
+
+        if let Some(inner) = self.inner.take() {
+            assert_eq!(
+                inner.counter.load(Acquire),
+                0,
+                "Swapchain images must be released before reconfiguration"
+            );
+        }
+
+        let extent = self.surface.extent();
+
+        if extent.width == 0 || extent.height == 0 {
+            tracing::debug!("Surface has zero extent");
+            return Ok(());
+        }
+
+        let swap_chain = device.handle().create_swap_chain(
+            self.surface.handle(),
+            &wgpu::SwapChainDescriptor {
+                usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
+                format: wgpu_format,
+                width: extent.width,
+                height: extent.height,
+                present_mode,
+            },
+        );
+
+        let slot = Arc::new(Mutex::new(None));
+
+        let image = Image::new(
+            ImageInfo {
+                extent: extent.into(),
+                format,
+                levels: 1,
+                layers: 1,
+                samples: Samples::Samples1,
+                usage,
+            },
+            device.downgrade(),
+            ImageMemory::Frame(slot.clone()),
+        );
+
+        self.inner = Some(SwapchainInner {
+            swap_chain,
+            extent,
+            image,
+            slot,
+            counter: Arc::new(AtomicUsize::new(0)),
+        });
+
+        tracing::debug!("Swapchain configured");
+        Ok(())
+    }
+
+    pub fn supported_usage(&self) -> Result<ImageUsage, SurfaceError> {
+        Ok(surface_usage())
+    }
+
+    /// Returns `true` if swapchain images can be acquired.
+    /// Returns `false` before first `configure` call
+    /// and while surface extent is zero.
+    pub fn is_configured(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    /// Destroys swapchain and returns its surface.
+    ///
+    /// # Panics
+    ///
+    /// Panics if swapchain images are still acquired.
+    pub fn destroy(mut self) -> Surface {
+        if let Some(inner) = self.inner.take() {
+            assert_eq!(
+                inner.counter.load(Acquire),
+                0,
+                "Swapchain images must be released before destruction"
+            );
+        }
+
+        tracing::debug!("Swapchain destroyed");
+        self.surface.mark_unused();
+        self.surface
+    }
+
+    /// Acquires next frame.
+    /// Returns `Ok(None)` if swapchain is out of date
+    /// and must be reconfigured.
+    pub fn acquire_image(
+        &mut self,
+    ) -> Result<Option<SwapchainImage>, SurfaceError> {
+        let device = self
+            .device
+            .upgrade()
+            .ok_or_else(|| SurfaceError::SurfaceLost)?;
+
+        let extent = self.surface.extent();
+
+        let inner = match &mut self.inner {
+            Some(inner) => inner,
+            None => return Ok(None),
+        };
+
+        if inner.extent != extent {
+            tracing::debug!("Surface extent changed");
+            return Ok(None);
+        }
+
+        if inner.counter.load(Acquire) > 0 {
+            tracing::error!("Acquire would block");
+            return Ok(None);
+        }
+
+        // Frame that was never presented must be released
+        // before next one is acquired.
+        drop(inner.slot.lock().take());
+
+        let frame = match inner.swap_chain.get_current_frame() {
+            Ok(frame) => frame,
+            Err(wgpu::SwapChainError::Timeout)
+            | Err(wgpu::SwapChainError::Outdated) => return Ok(None),
+            Err(wgpu::SwapChainError::Lost) => {
+                return Err(SurfaceError::SurfaceLost)
+            }
+            Err(wgpu::SwapChainError::OutOfMemory) => {
+                return Err(OutOfMemory.into())
+            }
+        };
+
+        *inner.slot.lock() = Some(frame);
+        inner.counter.fetch_add(1, Acquire);
+
+        Ok(Some(SwapchainImage {
+            info: SwapchainImageInfo {
+                image: inner.image.clone(),
+                wait: device.create_semaphore()?,
+                signal: device.create_semaphore()?,
+            },
+            owner: self.device.clone(),
+            counter: inner.counter.clone(),
+            slot: inner.slot.clone(),
+            index: 0,
+        }))
+    }
+}
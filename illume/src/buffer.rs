@@ -1,5 +1,6 @@
 use crate::align_up;
 pub use crate::backend::{Buffer, MappableBuffer};
+use std::ops::Range;
 
 bitflags::bitflags! {
     #[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
@@ -36,6 +37,25 @@ pub struct BufferInfo {
 
     /// Usage types supported by buffer.
     pub usage: BufferUsage,
+
+    /// Opaque addresses recorded by capture tool.
+    /// Buffer is recreated at the same device address on replay.
+    ///
+    /// Requires `BufferDeviceAddressCaptureReplay` feature
+    /// and `BufferUsage::DEVICE_ADDRESS` usage.
+    /// Ignored by backends without device addresses.
+    pub capture_address: Option<BufferCaptureAddress>,
+}
+
+/// Opaque capture addresses of a buffer and memory bound to it.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
+pub struct BufferCaptureAddress {
+    /// Opaque capture address of the buffer.
+    pub buffer: u64,
+
+    /// Opaque capture address of memory object bound to the buffer.
+    pub memory: u64,
 }
 
 impl BufferInfo {
@@ -50,6 +70,90 @@ impl BufferInfo {
     }
 }
 
+/// Builder for `BufferInfo` that validates it.
+#[derive(Clone, Copy, Debug)]
+pub struct BufferInfoBuilder {
+    info: BufferInfo,
+}
+
+impl BufferInfoBuilder {
+    /// Starts building info for buffer of `size` bytes without alignment.
+    pub fn new(size: u64) -> Self {
+        BufferInfoBuilder {
+            info: BufferInfo {
+                align: 0,
+                size,
+                usage: BufferUsage::empty(),
+                capture_address: None,
+            },
+        }
+    }
+
+    /// Sets alignment mask.
+    pub fn align(mut self, align: u64) -> Self {
+        self.info.align = align;
+        self
+    }
+
+    pub fn usage(mut self, usage: BufferUsage) -> Self {
+        self.info.usage = usage;
+        self
+    }
+
+    /// Sets addresses to replay buffer at.
+    pub fn capture_address(mut self, address: BufferCaptureAddress) -> Self {
+        self.info.capture_address = Some(address);
+        self
+    }
+
+    /// Validates and returns built info.
+    pub fn build(self) -> Result<BufferInfo, InvalidBufferInfo> {
+        let info = self.info;
+
+        if info.size == 0 {
+            return Err(InvalidBufferInfo::EmptySize);
+        }
+
+        let is_mask = info
+            .align
+            .checked_add(1)
+            .map_or(false, u64::is_power_of_two);
+
+        if !is_mask {
+            return Err(InvalidBufferInfo::InvalidAlign { align: info.align });
+        }
+
+        if align_up(info.align, info.size).is_none() {
+            return Err(InvalidBufferInfo::SizeOverflow {
+                size: info.size,
+                align: info.align,
+            });
+        }
+
+        if info.usage.is_empty() {
+            return Err(InvalidBufferInfo::EmptyUsage);
+        }
+
+        Ok(info)
+    }
+}
+
+/// Reason why `BufferInfo` is rejected.
+#[derive(Clone, Copy, Debug, thiserror::Error)]
+pub enum InvalidBufferInfo {
+    #[error("Buffer size is zero")]
+    EmptySize,
+
+    #[error("Buffer alignment {align:#x} is not a mask of power of two")]
+    InvalidAlign { align: u64 },
+
+    #[error("Buffer size {size} overflows when aligned with {align:#x}")]
+    SizeOverflow { size: u64, align: u64 },
+
+    #[error("Buffer usage is empty")]
+    EmptyUsage,
+}
+
 /// Buffer region.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct BufferRegion {
@@ -76,3 +180,14 @@ pub struct StridedBufferRegion {
     pub size: u64,
     pub stride: u64,
 }
+
+/// Barrier for a range of buffer.
+/// Needed only to transfer ownership of the range between queue families,
+/// global memory barriers cover everything else.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct BufferMemoryBarrier<'a> {
+    pub buffer: &'a Buffer,
+    pub offset: u64,
+    pub size: u64,
+    pub family_transfer: Option<Range<u32>>,
+}
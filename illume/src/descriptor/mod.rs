@@ -3,8 +3,8 @@ mod layout;
 pub use {self::layout::*, crate::backend::DescriptorSet};
 
 use crate::{
-    accel::AccelerationStructure, buffer::Buffer, image::Layout,
-    sampler::Sampler, view::ImageView,
+    accel::AccelerationStructure, assert_error, buffer::Buffer,
+    image::Layout, sampler::Sampler, view::ImageView, OutOfMemory,
 };
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -12,6 +12,62 @@ pub struct DescriptorSetInfo {
     pub layout: DescriptorSetLayout,
 }
 
+/// Error occured during descriptor set creation.
+#[derive(Clone, Copy, Debug, thiserror::Error)]
+pub enum CreateDescriptorSetError {
+    #[error(transparent)]
+    OutOfMemory {
+        #[from]
+        source: OutOfMemory,
+    },
+
+    /// Allocation failed due to fragmentation of newly created pool.
+    #[error("Descriptor pool is fragmented")]
+    FragmentedPool,
+
+    /// Newly created pool has not enough descriptors for the set.
+    #[error("Descriptor pool is out of memory")]
+    OutOfPoolMemory,
+}
+
+/// Sizing of descriptor pools.
+///
+/// Sets are allocated from pools shared by sets with the same layout.
+/// When pool is exhausted, new pool is created for the layout.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DescriptorPoolConfig {
+    /// Number of sets in first pool for a layout.
+    pub initial_sets: u32,
+
+    /// Each next pool for a layout holds this many times more sets.
+    pub growth_factor: u32,
+
+    /// Maximum number of sets in one pool.
+    pub max_sets: u32,
+}
+
+impl Default for DescriptorPoolConfig {
+    fn default() -> Self {
+        DescriptorPoolConfig {
+            initial_sets: 4,
+            growth_factor: 2,
+            max_sets: 256,
+        }
+    }
+}
+
+impl DescriptorPoolConfig {
+    /// Returns number of sets in pool that follows pool with `sets`.
+    pub fn next_pool_sets(&self, sets: Option<u32>) -> u32 {
+        let sets = match sets {
+            Some(sets) => sets.saturating_mul(self.growth_factor),
+            None => self.initial_sets,
+        };
+
+        sets.min(self.max_sets).max(1)
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct WriteDescriptorSet<'a> {
     pub set: &'a DescriptorSet,
@@ -46,3 +102,8 @@ pub struct CopyDescriptorSet<'a> {
     pub dst_element: u32,
     pub count: u32,
 }
+
+#[allow(dead_code)]
+fn check() {
+    assert_error::<CreateDescriptorSetError>();
+}
@@ -2,22 +2,27 @@ pub use crate::backend::CommandBuffer;
 use crate::{
     accel::AccelerationStructureBuildGeometryInfo,
     arith_le,
-    buffer::Buffer,
+    buffer::{Buffer, BufferMemoryBarrier},
     descriptor::DescriptorSet,
+    fence::WaitError,
     framebuffer::Framebuffer,
     image::{
-        Image, ImageBlit, ImageMemoryBarrier, ImageSubresourceLayers, Layout,
+        Image, ImageBlit, ImageMemoryBarrier, ImageSubresourceLayers,
+        ImageSubresourceRange, Layout, Samples,
     },
     pipeline::{
-        ComputePipeline, GraphicsPipeline, PipelineLayout, RayTracingPipeline,
+        ComputePipeline, Culling, DepthTest, FrontFace, GraphicsPipeline,
+        PipelineLayout, PrimitiveTopology, RayTracingPipeline,
         ShaderBindingTable, Viewport,
     },
+    query::QueryPool,
     queue::QueueCapabilityFlags,
+    record::RecordedCommand,
     render_pass::{ClearValue, RenderPass},
     sampler::Filter,
     shader::ShaderStageFlags,
     stage::PipelineStageFlags,
-    Extent3d, IndexType, Offset3d, Rect2d,
+    Extent3d, IndexType, Offset3d, OutOfMemory, Rect2d,
 };
 use bytemuck::{cast_slice, Pod};
 use std::{fmt::Debug, mem::size_of_val, ops::Range};
@@ -40,6 +45,17 @@ pub struct ImageCopy {
     pub extent: Extent3d,
 }
 
+/// Region of multisampled image resolved into single-sample image.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
+pub struct ImageResolve {
+    pub src_subresource: ImageSubresourceLayers,
+    pub src_offset: Offset3d,
+    pub dst_subresource: ImageSubresourceLayers,
+    pub dst_offset: Offset3d,
+    pub extent: Extent3d,
+}
+
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
 #[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
 pub struct BufferImageCopy {
@@ -58,6 +74,7 @@ pub enum Command<'a> {
         framebuffer: &'a Framebuffer,
         clears: &'a [ClearValue],
     },
+    NextSubpass,
     EndRenderPass,
 
     BindGraphicsPipeline {
@@ -101,6 +118,32 @@ pub enum Command<'a> {
         scissor: Rect2d,
     },
 
+    SetViewports {
+        first: u32,
+        viewports: &'a [Viewport],
+    },
+
+    SetScissors {
+        first: u32,
+        scissors: &'a [Rect2d],
+    },
+
+    SetCullMode {
+        culling: Option<Culling>,
+    },
+
+    SetFrontFace {
+        front_face: FrontFace,
+    },
+
+    SetPrimitiveTopology {
+        topology: PrimitiveTopology,
+    },
+
+    SetDepthTest {
+        depth_test: Option<DepthTest>,
+    },
+
     Draw {
         vertices: Range<u32>,
         instances: Range<u32>,
@@ -144,6 +187,28 @@ pub enum Command<'a> {
         regions: &'a [BufferCopy],
     },
 
+    FillBuffer {
+        buffer: &'a Buffer,
+        offset: u64,
+        size: u64,
+        value: u32,
+    },
+
+    ClearColorImage {
+        image: &'a Image,
+        layout: Layout,
+        color: [f32; 4],
+        ranges: &'a [ImageSubresourceRange],
+    },
+
+    ClearDepthStencilImage {
+        image: &'a Image,
+        layout: Layout,
+        depth: f32,
+        stencil: u32,
+        ranges: &'a [ImageSubresourceRange],
+    },
+
     CopyImage {
         src_image: &'a Image,
         src_layout: Layout,
@@ -168,12 +233,26 @@ pub enum Command<'a> {
         filter: Filter,
     },
 
+    ResolveImage {
+        src_image: &'a Image,
+        src_layout: Layout,
+        dst_image: &'a Image,
+        dst_layout: Layout,
+        regions: &'a [ImageResolve],
+    },
+
     PipelineBarrier {
         src: PipelineStageFlags,
         dst: PipelineStageFlags,
         images: &'a [ImageMemoryBarrier<'a>],
     },
 
+    BufferBarriers {
+        src: PipelineStageFlags,
+        dst: PipelineStageFlags,
+        buffers: &'a [BufferMemoryBarrier<'a>],
+    },
+
     PushConstants {
         layout: &'a PipelineLayout,
         stages: ShaderStageFlags,
@@ -186,41 +265,171 @@ pub enum Command<'a> {
         y: u32,
         z: u32,
     },
+
+    ResetQueryPool {
+        pool: &'a QueryPool,
+        queries: Range<u32>,
+    },
+
+    WriteTimestamp {
+        pool: &'a QueryPool,
+        query: u32,
+        stage: PipelineStageFlags,
+    },
+}
+
+/// Error that may occur when encoded commands are written
+/// into command buffer.
+#[derive(Copy, Clone, Debug, thiserror::Error)]
+pub enum EncodeError {
+    #[error(transparent)]
+    OutOfMemory(#[from] OutOfMemory),
+
+    /// Device was lost while commands were submitted.
+    #[error("Device lost")]
+    DeviceLost,
+}
+
+impl From<WaitError> for EncodeError {
+    fn from(err: WaitError) -> Self {
+        match err {
+            WaitError::OutOfMemory(err) => EncodeError::OutOfMemory(err),
+            WaitError::DeviceLost => EncodeError::DeviceLost,
+        }
+    }
 }
 
+/// Render pass scope in which command may be encoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CommandScope {
+    /// Command may be encoded only outside render pass.
+    Outside,
+
+    /// Command may be encoded only inside render pass.
+    Inside,
+
+    /// Command may be encoded both inside and outside render pass.
+    Both,
+}
+
+impl Command<'_> {
+    /// Returns render pass scope in which command may be encoded.
+    pub fn scope(&self) -> CommandScope {
+        match self {
+            Command::BeginRenderPass { .. }
+            | Command::UpdateBuffer { .. }
+            | Command::BuildAccelerationStructure { .. }
+            | Command::TraceRays { .. }
+            | Command::CopyBuffer { .. }
+            | Command::FillBuffer { .. }
+            | Command::ClearColorImage { .. }
+            | Command::ClearDepthStencilImage { .. }
+            | Command::CopyImage { .. }
+            | Command::CopyBufferImage { .. }
+            | Command::BlitImage { .. }
+            | Command::ResolveImage { .. }
+            | Command::BufferBarriers { .. }
+            | Command::Dispatch { .. }
+            | Command::ResetQueryPool { .. } => CommandScope::Outside,
+            Command::NextSubpass
+            | Command::EndRenderPass
+            | Command::Draw { .. }
+            | Command::DrawIndexed { .. } => CommandScope::Inside,
+            _ => CommandScope::Both,
+        }
+    }
+}
+
+/// Stages that barriers inside render pass may synchronize.
+const FRAMEBUFFER_STAGES: PipelineStageFlags =
+    PipelineStageFlags::from_bits_truncate(
+        PipelineStageFlags::FRAGMENT_SHADER.bits()
+            | PipelineStageFlags::EARLY_FRAGMENT_TESTS.bits()
+            | PipelineStageFlags::LATE_FRAGMENT_TESTS.bits()
+            | PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT.bits(),
+    );
+
 /// Basis for encoding capabilities.
 /// Implements encoding of commands that can be inside and outside of render
 /// pass.
+///
+/// Tracks whether render pass is begun and panics
+/// when command is encoded in scope where it is not allowed.
 #[derive(Debug)]
 pub struct EncoderCommon<'a> {
     capabilities: QueueCapabilityFlags,
     commands: Vec<Command<'a>>,
+    inside_render_pass: bool,
 }
 
 impl<'a> EncoderCommon<'a> {
     pub fn set_viewport(&mut self, viewport: Viewport) {
         assert!(self.capabilities.supports_graphics());
 
-        self.commands.push(Command::SetViewport { viewport })
+        self.push(Command::SetViewport { viewport })
     }
 
     pub fn set_scissor(&mut self, scissor: Rect2d) {
         assert!(self.capabilities.supports_graphics());
 
-        self.commands.push(Command::SetScissor { scissor })
+        self.push(Command::SetScissor { scissor })
+    }
+
+    /// Sets viewports starting from `first`.
+    /// Requires `MultiViewport` feature unless single viewport 0 is set.
+    pub fn set_viewports(&mut self, first: u32, viewports: &'a [Viewport]) {
+        assert!(self.capabilities.supports_graphics());
+
+        self.push(Command::SetViewports { first, viewports })
+    }
+
+    /// Sets scissors starting from `first`.
+    /// Requires `MultiViewport` feature unless single scissor 0 is set.
+    pub fn set_scissors(&mut self, first: u32, scissors: &'a [Rect2d]) {
+        assert!(self.capabilities.supports_graphics());
+
+        self.push(Command::SetScissors { first, scissors })
+    }
+
+    /// Sets cull mode for bound pipeline with dynamic `CULL_MODE` state.
+    pub fn set_cull_mode(&mut self, culling: Option<Culling>) {
+        assert!(self.capabilities.supports_graphics());
+
+        self.push(Command::SetCullMode { culling })
+    }
+
+    /// Sets front face for bound pipeline with dynamic `FRONT_FACE` state.
+    pub fn set_front_face(&mut self, front_face: FrontFace) {
+        assert!(self.capabilities.supports_graphics());
+
+        self.push(Command::SetFrontFace { front_face })
+    }
+
+    /// Sets primitive topology for bound pipeline
+    /// with dynamic `PRIMITIVE_TOPOLOGY` state.
+    pub fn set_primitive_topology(&mut self, topology: PrimitiveTopology) {
+        assert!(self.capabilities.supports_graphics());
+
+        self.push(Command::SetPrimitiveTopology { topology })
+    }
+
+    /// Sets depth test for bound pipeline with dynamic `DEPTH_TEST` state.
+    /// `None` disables depth test.
+    pub fn set_depth_test(&mut self, depth_test: Option<DepthTest>) {
+        assert!(self.capabilities.supports_graphics());
+
+        self.push(Command::SetDepthTest { depth_test })
     }
 
     pub fn bind_graphics_pipeline(&mut self, pipeline: &'a GraphicsPipeline) {
         assert!(self.capabilities.supports_graphics());
 
-        self.commands
-            .push(Command::BindGraphicsPipeline { pipeline })
+        self.push(Command::BindGraphicsPipeline { pipeline })
     }
 
     pub fn bind_compute_pipeline(&mut self, pipeline: &'a ComputePipeline) {
         assert!(self.capabilities.supports_compute());
-        self.commands
-            .push(Command::BindComputePipeline { pipeline })
+        self.push(Command::BindComputePipeline { pipeline })
     }
 
     pub fn bind_ray_tracing_pipeline(
@@ -229,8 +438,7 @@ impl<'a> EncoderCommon<'a> {
     ) {
         assert!(self.capabilities.supports_compute());
 
-        self.commands
-            .push(Command::BindRayTracingPipeline { pipeline })
+        self.push(Command::BindRayTracingPipeline { pipeline })
     }
 
     pub fn bind_vertex_buffers(
@@ -240,8 +448,7 @@ impl<'a> EncoderCommon<'a> {
     ) {
         assert!(self.capabilities.supports_graphics());
 
-        self.commands
-            .push(Command::BindVertexBuffers { first, buffers })
+        self.push(Command::BindVertexBuffers { first, buffers })
     }
 
     pub fn bind_index_buffer(
@@ -252,7 +459,7 @@ impl<'a> EncoderCommon<'a> {
     ) {
         assert!(self.capabilities.supports_graphics());
 
-        self.commands.push(Command::BindIndexBuffer {
+        self.push(Command::BindIndexBuffer {
             buffer,
             offset,
             index_type,
@@ -268,7 +475,7 @@ impl<'a> EncoderCommon<'a> {
     ) {
         assert!(self.capabilities.supports_graphics());
 
-        self.commands.push(Command::BindGraphicsDescriptorSets {
+        self.push(Command::BindGraphicsDescriptorSets {
             layout,
             first_set,
             sets,
@@ -285,7 +492,7 @@ impl<'a> EncoderCommon<'a> {
     ) {
         assert!(self.capabilities.supports_compute());
 
-        self.commands.push(Command::BindComputeDescriptorSets {
+        self.push(Command::BindComputeDescriptorSets {
             layout,
             first_set,
             sets,
@@ -302,7 +509,7 @@ impl<'a> EncoderCommon<'a> {
     ) {
         assert!(self.capabilities.supports_compute());
 
-        self.commands.push(Command::BindRayTracingDescriptorSets {
+        self.push(Command::BindRayTracingDescriptorSets {
             layout,
             first_set,
             sets,
@@ -315,7 +522,7 @@ impl<'a> EncoderCommon<'a> {
         src: PipelineStageFlags,
         dst: PipelineStageFlags,
     ) {
-        self.commands.push(Command::PipelineBarrier {
+        self.push(Command::PipelineBarrier {
             src,
             dst,
             images: &[],
@@ -328,8 +535,19 @@ impl<'a> EncoderCommon<'a> {
         dst: PipelineStageFlags,
         images: &'a [ImageMemoryBarrier<'a>],
     ) {
-        self.commands
-            .push(Command::PipelineBarrier { src, dst, images });
+        self.push(Command::PipelineBarrier { src, dst, images });
+    }
+
+    /// Records barriers for buffer ranges.
+    /// Used to transfer ownership between queue families,
+    /// see `QueueFamilyTransfer`.
+    pub fn buffer_barriers(
+        &mut self,
+        src: PipelineStageFlags,
+        dst: PipelineStageFlags,
+        buffers: &'a [BufferMemoryBarrier<'a>],
+    ) {
+        self.push(Command::BufferBarriers { src, dst, buffers });
     }
 
     pub fn push_constants<T>(
@@ -343,13 +561,78 @@ impl<'a> EncoderCommon<'a> {
     {
         assert!(arith_le(size_of_val(data), u32::max_value()));
 
-        self.commands.push(Command::PushConstants {
+        self.push(Command::PushConstants {
             layout,
             stages,
             offset,
             data: cast_slice(data),
         });
     }
+
+    /// Pushes prepared command.
+    /// Used to replay recorded commands.
+    pub(crate) fn push_command(&mut self, command: Command<'a>) {
+        self.push(command);
+    }
+
+    /// Returns `true` if render pass is begun and not yet ended.
+    pub fn is_inside_render_pass(&self) -> bool {
+        self.inside_render_pass
+    }
+
+    /// Pushes command after checking that it is allowed
+    /// in current render pass scope.
+    fn push(&mut self, command: Command<'a>) {
+        match (command.scope(), self.inside_render_pass) {
+            (CommandScope::Outside, true) => panic!(
+                "`{}` command cannot be encoded inside render pass",
+                RecordedCommand::new(&command).name(),
+            ),
+            (CommandScope::Inside, false) => panic!(
+                "`{}` command cannot be encoded outside render pass",
+                RecordedCommand::new(&command).name(),
+            ),
+            _ => {}
+        }
+
+        match &command {
+            Command::BeginRenderPass { .. } => self.inside_render_pass = true,
+            Command::EndRenderPass => self.inside_render_pass = false,
+            Command::PipelineBarrier { src, dst, images }
+                if self.inside_render_pass =>
+            {
+                check_render_pass_barrier(*src, *dst, images)
+            }
+            _ => {}
+        }
+
+        self.commands.push(command);
+    }
+
+    /// Returns number of pipeline barriers recorded so far.
+    pub fn barrier_count(&self) -> usize {
+        self.commands
+            .iter()
+            .filter(|command| {
+                matches!(
+                    command,
+                    Command::PipelineBarrier { .. }
+                        | Command::BufferBarriers { .. }
+                )
+            })
+            .count()
+    }
+
+    /// Writes timestamp into query when all previous commands
+    /// complete specified stage.
+    pub fn write_timestamp(
+        &mut self,
+        pool: &'a QueryPool,
+        query: u32,
+        stage: PipelineStageFlags,
+    ) {
+        self.push(Command::WriteTimestamp { pool, query, stage });
+    }
 }
 
 /// Command encoder that can encode commands outside render pass.
@@ -383,6 +666,7 @@ impl<'a> Encoder<'a> {
             inner: EncoderCommon {
                 capabilities,
                 commands: Vec::new(),
+                inside_render_pass: false,
             },
             command_buffer,
         }
@@ -408,7 +692,7 @@ impl<'a> Encoder<'a> {
     ) -> RenderPassEncoder<'_, 'a> {
         assert!(self.inner.capabilities.supports_graphics());
 
-        self.inner.commands.push(Command::BeginRenderPass {
+        self.inner.push(Command::BeginRenderPass {
             pass,
             framebuffer,
             clears,
@@ -436,7 +720,7 @@ impl<'a> Encoder<'a> {
             )
         };
 
-        self.inner.commands.push(Command::UpdateBuffer {
+        self.inner.push(Command::UpdateBuffer {
             buffer,
             offset,
             data,
@@ -480,7 +764,6 @@ impl<'a> Encoder<'a> {
         }
 
         self.inner
-            .commands
             .push(Command::BuildAccelerationStructure { infos })
     }
 
@@ -491,7 +774,7 @@ impl<'a> Encoder<'a> {
     ) {
         assert!(self.inner.capabilities.supports_compute());
 
-        self.commands.push(Command::TraceRays {
+        self.inner.push(Command::TraceRays {
             shader_binding_table,
             extent,
         })
@@ -503,13 +786,71 @@ impl<'a> Encoder<'a> {
         dst_buffer: &'a Buffer,
         regions: &'a [BufferCopy],
     ) {
-        self.commands.push(Command::CopyBuffer {
+        self.inner.push(Command::CopyBuffer {
             src_buffer,
             dst_buffer,
             regions,
         })
     }
 
+    /// Fills buffer range with repeated 4-byte value.
+    /// `u64::MAX` size fills up to the end of the buffer.
+    pub fn fill_buffer(
+        &mut self,
+        buffer: &'a Buffer,
+        offset: u64,
+        size: u64,
+        value: u32,
+    ) {
+        self.inner.push(Command::FillBuffer {
+            buffer,
+            offset,
+            size,
+            value,
+        })
+    }
+
+    /// Clears color image ranges outside of render pass.
+    pub fn clear_color_image(
+        &mut self,
+        image: &'a Image,
+        layout: Layout,
+        color: [f32; 4],
+        ranges: &'a [ImageSubresourceRange],
+    ) {
+        assert!(
+            self.capabilities.supports_graphics()
+                || self.capabilities.supports_compute()
+        );
+
+        self.inner.push(Command::ClearColorImage {
+            image,
+            layout,
+            color,
+            ranges,
+        })
+    }
+
+    /// Clears depth-stencil image ranges outside of render pass.
+    pub fn clear_depth_stencil_image(
+        &mut self,
+        image: &'a Image,
+        layout: Layout,
+        depth: f32,
+        stencil: u32,
+        ranges: &'a [ImageSubresourceRange],
+    ) {
+        assert!(self.capabilities.supports_graphics());
+
+        self.inner.push(Command::ClearDepthStencilImage {
+            image,
+            layout,
+            depth,
+            stencil,
+            ranges,
+        })
+    }
+
     pub fn copy_image(
         &mut self,
         src_image: &'a Image,
@@ -518,7 +859,7 @@ impl<'a> Encoder<'a> {
         dst_layout: Layout,
         regions: &'a [ImageCopy],
     ) {
-        self.commands.push(Command::CopyImage {
+        self.inner.push(Command::CopyImage {
             src_image,
             src_layout,
             dst_image,
@@ -534,7 +875,7 @@ impl<'a> Encoder<'a> {
         dst_layout: Layout,
         regions: &'a [BufferImageCopy],
     ) {
-        self.commands.push(Command::CopyBufferImage {
+        self.inner.push(Command::CopyBufferImage {
             src_buffer,
             dst_image,
             dst_layout,
@@ -553,7 +894,7 @@ impl<'a> Encoder<'a> {
     ) {
         assert!(self.capabilities.supports_graphics());
 
-        self.commands.push(Command::BlitImage {
+        self.inner.push(Command::BlitImage {
             src_image,
             src_layout,
             dst_image,
@@ -563,20 +904,72 @@ impl<'a> Encoder<'a> {
         })
     }
 
+    /// Resolves multisampled image into single-sample image.
+    pub fn resolve_image(
+        &mut self,
+        src_image: &'a Image,
+        src_layout: Layout,
+        dst_image: &'a Image,
+        dst_layout: Layout,
+        regions: &'a [ImageResolve],
+    ) {
+        assert!(self.capabilities.supports_graphics());
+        assert_ne!(
+            src_image.info().samples,
+            Samples::Samples1,
+            "Resolve source must be multisampled"
+        );
+        assert_eq!(
+            dst_image.info().samples,
+            Samples::Samples1,
+            "Resolve destination must be single-sampled"
+        );
+
+        self.inner.push(Command::ResolveImage {
+            src_image,
+            src_layout,
+            dst_image,
+            dst_layout,
+            regions,
+        })
+    }
+
     pub fn dispatch(&mut self, x: u32, y: u32, z: u32) {
         assert!(self.capabilities.supports_compute());
 
-        self.commands.push(Command::Dispatch { x, y, z });
+        self.inner.push(Command::Dispatch { x, y, z });
+    }
+
+    /// Resets queries before they are written.
+    pub fn reset_query_pool(
+        &mut self,
+        pool: &'a QueryPool,
+        queries: Range<u32>,
+    ) {
+        self.inner.push(Command::ResetQueryPool { pool, queries });
     }
 
     /// Flushes commands recorded into this encoder to the underlying command
     /// buffer.
-    pub fn finish(mut self) -> CommandBuffer {
-        self.command_buffer
-            .write(&self.inner.commands)
-            .expect("TODO: Handle command buffer writing error");
+    pub fn finish(mut self) -> Result<CommandBuffer, EncodeError> {
+        assert!(
+            !self.inner.inside_render_pass,
+            "Encoder finished inside render pass",
+        );
+
+        if self.command_buffer.is_capturing() {
+            let commands = self
+                .inner
+                .commands
+                .iter()
+                .map(RecordedCommand::new)
+                .collect();
+            self.command_buffer.set_captured(commands);
+        }
+
+        self.command_buffer.write(&self.inner.commands)?;
 
-        self.command_buffer
+        Ok(self.command_buffer)
     }
 }
 
@@ -588,8 +981,13 @@ pub struct RenderPassEncoder<'a, 'b> {
 }
 
 impl<'a, 'b> RenderPassEncoder<'a, 'b> {
+    /// Ends current subpass and begins next one.
+    pub fn next_subpass(&mut self) {
+        self.inner.push(Command::NextSubpass);
+    }
+
     pub fn draw(&mut self, vertices: Range<u32>, instances: Range<u32>) {
-        self.inner.commands.push(Command::Draw {
+        self.inner.push(Command::Draw {
             vertices,
             instances,
         });
@@ -601,7 +999,7 @@ impl<'a, 'b> RenderPassEncoder<'a, 'b> {
         vertex_offset: i32,
         instances: Range<u32>,
     ) {
-        self.inner.commands.push(Command::DrawIndexed {
+        self.inner.push(Command::DrawIndexed {
             indices,
             vertex_offset,
             instances,
@@ -611,7 +1009,7 @@ impl<'a, 'b> RenderPassEncoder<'a, 'b> {
 
 impl Drop for RenderPassEncoder<'_, '_> {
     fn drop(&mut self) {
-        self.inner.commands.push(Command::EndRenderPass);
+        self.inner.push(Command::EndRenderPass);
     }
 }
 
@@ -628,3 +1026,34 @@ impl<'a, 'b> std::ops::DerefMut for RenderPassEncoder<'a, 'b> {
         self.inner
     }
 }
+
+/// Panics if barrier cannot be encoded inside render pass.
+/// Such barriers may only synchronize framebuffer-space stages
+/// and must not change image layouts or transfer ownership.
+fn check_render_pass_barrier(
+    src: PipelineStageFlags,
+    dst: PipelineStageFlags,
+    images: &[ImageMemoryBarrier<'_>],
+) {
+    assert!(
+        FRAMEBUFFER_STAGES.contains(src | dst),
+        "Pipeline barrier inside render pass synchronizes {:?} -> {:?}, \
+         only framebuffer-space stages are allowed",
+        src,
+        dst,
+    );
+
+    for barrier in images {
+        assert!(
+            barrier.old_layout == Some(barrier.new_layout),
+            "Image barrier inside render pass changes layout {:?} -> {:?}",
+            barrier.old_layout,
+            barrier.new_layout,
+        );
+
+        assert!(
+            barrier.family_transfer.is_none(),
+            "Image barrier inside render pass transfers queue family ownership",
+        );
+    }
+}
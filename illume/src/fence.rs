@@ -1 +1,14 @@
 pub use crate::backend::Fence;
+use crate::OutOfMemory;
+
+/// Error that may occur when waiting for device work to complete.
+#[derive(Copy, Clone, Debug, thiserror::Error)]
+pub enum WaitError {
+    #[error(transparent)]
+    OutOfMemory(#[from] OutOfMemory),
+
+    /// Device is lost and all its resources are unusable.
+    /// Device must be recreated to continue rendering.
+    #[error("Device lost")]
+    DeviceLost,
+}
@@ -1,3 +1,5 @@
+use crate::{image::Samples, Extent3d};
+
 /// Texel format.
 /// Images can have different texel formats.
 /// Some of which are color or depth and/or stencil.
@@ -182,6 +184,39 @@ impl Format {
         }
     }
 
+    /// Returns `true` if format stores color in sRGB encoding.
+    pub fn is_srgb(&self) -> bool {
+        self.color_type() == Some(FormatType::Srgb)
+    }
+
+    /// Returns sRGB counterpart of UNORM format.
+    /// Returns `None` if there is no such format.
+    pub fn to_srgb(&self) -> Option<Format> {
+        match self {
+            Self::R8Unorm | Self::R8Srgb => Some(Self::R8Srgb),
+            Self::RG8Unorm | Self::RG8Srgb => Some(Self::RG8Srgb),
+            Self::RGB8Unorm | Self::RGB8Srgb => Some(Self::RGB8Srgb),
+            Self::BGR8Unorm | Self::BGR8Srgb => Some(Self::BGR8Srgb),
+            Self::RGBA8Unorm | Self::RGBA8Srgb => Some(Self::RGBA8Srgb),
+            Self::BGRA8Unorm | Self::BGRA8Srgb => Some(Self::BGRA8Srgb),
+            _ => None,
+        }
+    }
+
+    /// Returns UNORM counterpart of sRGB format.
+    /// Non-sRGB formats are returned as is.
+    pub fn to_linear(&self) -> Format {
+        match self {
+            Self::R8Srgb => Self::R8Unorm,
+            Self::RG8Srgb => Self::RG8Unorm,
+            Self::RGB8Srgb => Self::RGB8Unorm,
+            Self::BGR8Srgb => Self::BGR8Unorm,
+            Self::RGBA8Srgb => Self::RGBA8Unorm,
+            Self::BGRA8Srgb => Self::BGRA8Unorm,
+            _ => *self,
+        }
+    }
+
     pub fn color_type(&self) -> Option<FormatType> {
         match self.description() {
             FormatDescription::R(repr) => Some(repr.ty),
@@ -638,3 +673,55 @@ impl Format {
         }
     }
 }
+
+bitflags::bitflags! {
+    /// Operations supported for format.
+    #[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
+    pub struct FormatFeatures: u32 {
+        const SAMPLED_IMAGE =               0x0001;
+        const STORAGE_IMAGE =               0x0002;
+        const STORAGE_IMAGE_ATOMIC =        0x0004;
+        const UNIFORM_TEXEL_BUFFER =        0x0008;
+        const STORAGE_TEXEL_BUFFER =        0x0010;
+        const STORAGE_TEXEL_BUFFER_ATOMIC = 0x0020;
+        const VERTEX_BUFFER =               0x0040;
+        const COLOR_ATTACHMENT =            0x0080;
+        const COLOR_ATTACHMENT_BLEND =      0x0100;
+        const DEPTH_STENCIL_ATTACHMENT =    0x0200;
+        const BLIT_SRC =                    0x0400;
+        const BLIT_DST =                    0x0800;
+        const SAMPLED_IMAGE_FILTER_LINEAR = 0x1000;
+        const TRANSFER_SRC =                0x2000;
+        const TRANSFER_DST =                0x4000;
+    }
+}
+
+/// Operations supported for format by device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
+pub struct FormatProperties {
+    /// Features supported by images with linear tiling.
+    pub linear_tiling: FormatFeatures,
+
+    /// Features supported by images with optimal tiling.
+    /// Images are always created with optimal tiling.
+    pub optimal_tiling: FormatFeatures,
+
+    /// Features supported by buffers.
+    pub buffer: FormatFeatures,
+}
+
+/// Limits of images with particular format, dimensionality and usage.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
+pub struct ImageFormatProperties {
+    pub max_extent: Extent3d,
+    pub max_levels: u32,
+    pub max_layers: u32,
+
+    /// Largest supported number of samples.
+    pub max_samples: Samples,
+
+    /// Maximum size of image in bytes.
+    pub max_size: u64,
+}
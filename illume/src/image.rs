@@ -2,8 +2,7 @@ pub use {self::Samples::*, crate::backend::Image};
 use {
     crate::{
         format::{AspectFlags, Format},
-        memory::MemoryUsage,
-        Extent2d, Extent3d, ImageSize, Offset3d,
+        Device, Extent2d, Extent3d, ImageSize, Offset3d,
     },
     std::ops::Range,
 };
@@ -176,6 +175,23 @@ impl ImageExtent {
             Self::D3 { width, height, .. } => Extent2d { width, height },
         }
     }
+
+    /// Returns `true` if any dimension is zero.
+    pub fn is_empty(self) -> bool {
+        let extent = self.into_3d();
+        extent.width == 0 || extent.height == 0 || extent.depth == 0
+    }
+
+    /// Returns largest dimension of the extent.
+    pub fn max_dimension(self) -> ImageSize {
+        let extent = self.into_3d();
+        extent.width.max(extent.height).max(extent.depth)
+    }
+
+    /// Returns number of MIP levels in full chain for this extent.
+    pub fn max_levels(self) -> u32 {
+        32 - self.max_dimension().leading_zeros()
+    }
 }
 
 impl PartialEq<Extent2d> for ImageExtent {
@@ -282,6 +298,131 @@ pub struct ImageInfo {
     /// Usage types supported by image.
     pub usage: ImageUsage,
 }
+
+impl ImageInfo {
+    /// Checks that image with this info can be created on the device.
+    pub fn validate(&self, device: &Device) -> Result<(), InvalidImageInfo> {
+        if self.extent.is_empty() {
+            return Err(InvalidImageInfo::EmptyExtent {
+                extent: self.extent,
+            });
+        }
+
+        let max = device.max_image_dimension(self.extent);
+        if self.extent.max_dimension() > max {
+            return Err(InvalidImageInfo::ExtentTooLarge {
+                extent: self.extent,
+                max,
+            });
+        }
+
+        let max = self.extent.max_levels();
+        if self.levels == 0 || self.levels > max {
+            return Err(InvalidImageInfo::InvalidLevels {
+                levels: self.levels,
+                max,
+            });
+        }
+
+        let max = device.max_image_layers();
+        if self.layers == 0 || self.layers > max {
+            return Err(InvalidImageInfo::InvalidLayers {
+                layers: self.layers,
+                max,
+            });
+        }
+
+        if self.usage.is_empty() {
+            return Err(InvalidImageInfo::EmptyUsage);
+        }
+
+        let supported = device.image_format_usage(self.format);
+        if !supported.contains(self.usage) {
+            return Err(InvalidImageInfo::UnsupportedUsage {
+                format: self.format,
+                usage: self.usage - supported,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Builder for `ImageInfo` that validates it against device.
+#[derive(Clone, Copy, Debug)]
+pub struct ImageInfoBuilder {
+    info: ImageInfo,
+}
+
+impl ImageInfoBuilder {
+    /// Starts building info for single-level single-layer image.
+    pub fn new(extent: impl Into<ImageExtent>, format: Format) -> Self {
+        ImageInfoBuilder {
+            info: ImageInfo {
+                extent: extent.into(),
+                format,
+                levels: 1,
+                layers: 1,
+                samples: Samples1,
+                usage: ImageUsage::empty(),
+            },
+        }
+    }
+
+    pub fn levels(mut self, levels: u32) -> Self {
+        self.info.levels = levels;
+        self
+    }
+
+    /// Sets number of levels to full MIP chain for the extent.
+    pub fn full_mip_chain(mut self) -> Self {
+        self.info.levels = self.info.extent.max_levels();
+        self
+    }
+
+    pub fn layers(mut self, layers: u32) -> Self {
+        self.info.layers = layers;
+        self
+    }
+
+    pub fn samples(mut self, samples: Samples) -> Self {
+        self.info.samples = samples;
+        self
+    }
+
+    pub fn usage(mut self, usage: ImageUsage) -> Self {
+        self.info.usage = usage;
+        self
+    }
+
+    /// Validates and returns built info.
+    pub fn build(self, device: &Device) -> Result<ImageInfo, InvalidImageInfo> {
+        self.info.validate(device)?;
+        Ok(self.info)
+    }
+}
+
+/// Reason why `ImageInfo` is rejected.
+#[derive(Clone, Copy, Debug, thiserror::Error)]
+pub enum InvalidImageInfo {
+    #[error("Image extent {extent:?} is empty")]
+    EmptyExtent { extent: ImageExtent },
+
+    #[error("Image extent {extent:?} exceeds device limit {max}")]
+    ExtentTooLarge { extent: ImageExtent, max: ImageSize },
+
+    #[error("Image levels count {levels} is not in 1..={max}")]
+    InvalidLevels { levels: u32, max: u32 },
+
+    #[error("Image layers count {layers} is not in 1..={max}")]
+    InvalidLayers { layers: u32, max: u32 },
+
+    #[error("Image usage is empty")]
+    EmptyUsage,
+
+    #[error("Usage {usage:?} is not supported for format {format:?}")]
+    UnsupportedUsage { format: Format, usage: ImageUsage },
+}
 /// Subresorce range of the image.
 /// Used to create `ImageView`s.
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
@@ -15,7 +15,7 @@ use std::{
     num::TryFromIntError,
 };
 
-pub mod backend;
+mod backend;
 
 mod accel;
 mod buffer;
@@ -26,9 +26,12 @@ mod format;
 mod framebuffer;
 mod image;
 mod memory;
+mod ownership;
 mod physical;
 mod pipeline;
+mod query;
 mod queue;
+mod record;
 mod render_pass;
 mod sampler;
 mod semaphore;
@@ -36,11 +39,12 @@ mod shader;
 mod stage;
 mod surface;
 mod swapchain;
+mod validate;
 mod view;
 
 pub use self::{
     accel::*,
-    backend::{Device, Graphics},
+    backend::{CreateRenderPassError, Device, Graphics, InitError, WeakDevice},
     buffer::*,
     descriptor::*,
     encode::*,
@@ -49,9 +53,12 @@ pub use self::{
     framebuffer::*,
     image::*,
     memory::*,
+    ownership::*,
     physical::*,
     pipeline::*,
+    query::*,
     queue::*,
+    record::*,
     render_pass::*,
     sampler::*,
     semaphore::*,
@@ -59,6 +66,7 @@ pub use self::{
     stage::*,
     surface::*,
     swapchain::*,
+    validate::*,
     view::*,
 };
 
@@ -292,6 +300,12 @@ pub enum CreateImageError {
 
     #[error("Combination paramters `{info:?}` is unsupported")]
     Unsupported { info: ImageInfo },
+
+    #[error(transparent)]
+    Invalid {
+        #[from]
+        source: InvalidImageInfo,
+    },
 }
 
 /// Possible error that may occur during memory mapping.
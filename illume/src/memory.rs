@@ -4,7 +4,7 @@ bitflags::bitflags! {
     #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct MemoryUsage: u8 {
         /// Hints allocator that memory will be used for data downloading.
-        /// Allocator will strongly prefer host-cached memory.
+        /// Host-cached memory is used when any suitable type is available.
         /// Implies `HOST_ACCESS` flag.
         const DOWNLOAD = 0x04;
 
@@ -17,5 +17,27 @@ bitflags::bitflags! {
 
         /// Hints for device to find memory with fast device access.
         const FAST_DEVICE_ACCESS = 0x10;
+
+        /// Requires host-cached memory.
+        /// Allocation fails if no host-cached memory type is suitable.
+        /// Implies `HOST_ACCESS` flag.
+        const HOST_CACHED = 0x20;
+
+        /// Requests lazily allocated memory, which tile-based devices
+        /// may never back if content is not stored.
+        /// Applies only to images with `TRANSIENT_ATTACHMENT` usage.
+        /// Ordinary device memory is used if no such memory is available.
+        const LAZILY_ALLOCATED = 0x40;
     }
 }
+
+/// Statistics of device memory usage.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryStats {
+    /// Bytes of memory bound to buffers and images,
+    /// including freed blocks that are cached for reuse.
+    pub allocated: u64,
+
+    /// Total size of device-local memory heaps.
+    pub device_local: u64,
+}
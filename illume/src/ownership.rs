@@ -0,0 +1,145 @@
+//! Helpers for transferring resource ownership between queue families.
+//!
+//! Resources with exclusive sharing mode are owned by single queue family.
+//! To use one on a queue of another family ownership must be released
+//! by a barrier recorded on the source queue and acquired by identical
+//! barrier on the destination queue, after a semaphore wait.
+
+use crate::{
+    buffer::{Buffer, BufferMemoryBarrier},
+    image::{Image, ImageMemoryBarrier, ImageSubresourceRange, Layout},
+    queue::QueueId,
+    stage::PipelineStageFlags,
+};
+
+/// Transfer of ownership from one queue family to another.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct QueueFamilyTransfer {
+    pub src_family: u32,
+    pub dst_family: u32,
+}
+
+/// Matched pair of barriers.
+///
+/// `release` must be recorded on a queue of source family
+/// with destination stage `BOTTOM_OF_PIPE`, `acquire` - on a queue of
+/// destination family with source stage `TOP_OF_PIPE`.
+/// Submission with `acquire` must wait for a semaphore signalled by
+/// submission with `release`.
+///
+/// If both families are the same there is nothing to release
+/// and `acquire` is an ordinary barrier.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OwnershipTransfer<B> {
+    pub release: Option<B>,
+    pub acquire: B,
+}
+
+impl QueueFamilyTransfer {
+    /// Stage that release barrier waits for on source queue.
+    pub const RELEASE_DST_STAGE: PipelineStageFlags =
+        PipelineStageFlags::BOTTOM_OF_PIPE;
+
+    /// Stage that acquire barrier follows on destination queue.
+    pub const ACQUIRE_SRC_STAGE: PipelineStageFlags =
+        PipelineStageFlags::TOP_OF_PIPE;
+
+    /// Transfer between families of two queues.
+    pub fn new(src: QueueId, dst: QueueId) -> Self {
+        QueueFamilyTransfer {
+            src_family: src.family as u32,
+            dst_family: dst.family as u32,
+        }
+    }
+
+    /// Returns `true` if both queues belong to the same family
+    /// and no ownership transfer is required.
+    pub fn is_noop(&self) -> bool {
+        self.src_family == self.dst_family
+    }
+
+    /// Returns barriers that move image subresources to destination family.
+    /// Layout transition, if any, is performed once, but both barriers
+    /// must specify it.
+    pub fn image<'a>(
+        &self,
+        image: &'a Image,
+        old_layout: Option<Layout>,
+        new_layout: Layout,
+        subresource: ImageSubresourceRange,
+    ) -> OwnershipTransfer<ImageMemoryBarrier<'a>> {
+        let family_transfer = if self.is_noop() {
+            None
+        } else {
+            Some(self.src_family..self.dst_family)
+        };
+
+        let acquire = ImageMemoryBarrier {
+            image,
+            old_layout,
+            new_layout,
+            family_transfer,
+            subresource,
+        };
+
+        OwnershipTransfer {
+            release: if self.is_noop() {
+                None
+            } else {
+                Some(acquire.clone())
+            },
+            acquire,
+        }
+    }
+
+    /// Returns barriers that move whole image to destination family.
+    pub fn image_whole<'a>(
+        &self,
+        image: &'a Image,
+        old_layout: Option<Layout>,
+        new_layout: Layout,
+    ) -> OwnershipTransfer<ImageMemoryBarrier<'a>> {
+        self.image(
+            image,
+            old_layout,
+            new_layout,
+            ImageSubresourceRange::whole(image.info()),
+        )
+    }
+
+    /// Returns barriers that move buffer range to destination family.
+    pub fn buffer<'a>(
+        &self,
+        buffer: &'a Buffer,
+        offset: u64,
+        size: u64,
+    ) -> OwnershipTransfer<BufferMemoryBarrier<'a>> {
+        let acquire = BufferMemoryBarrier {
+            buffer,
+            offset,
+            size,
+            family_transfer: if self.is_noop() {
+                None
+            } else {
+                Some(self.src_family..self.dst_family)
+            },
+        };
+
+        OwnershipTransfer {
+            release: if self.is_noop() {
+                None
+            } else {
+                Some(acquire.clone())
+            },
+            acquire,
+        }
+    }
+
+    /// Returns barriers that move whole buffer to destination family.
+    pub fn buffer_whole<'a>(
+        &self,
+        buffer: &'a Buffer,
+    ) -> OwnershipTransfer<BufferMemoryBarrier<'a>> {
+        self.buffer(buffer, 0, buffer.info().size)
+    }
+}
@@ -1,5 +1,8 @@
 pub use crate::backend::PhysicalDevice;
-use crate::{assert_error, queue::FamilyInfo, OutOfMemory};
+use {
+    crate::{assert_error, queue::FamilyInfo, OutOfMemory},
+    std::fmt::{self, Display},
+};
 
 /// Error occured during device enumeration.
 #[derive(Debug, thiserror::Error)]
@@ -21,11 +24,54 @@ pub struct DeviceInfo {
     /// Kind of the device.
     pub kind: Option<DeviceKind>,
 
+    /// Version of graphics API supported by both device and instance.
+    pub api_version: ApiVersion,
+
     /// Features supported by device.
     pub features: Vec<Feature>,
 
     /// Information about queue families that device has.
     pub families: Vec<FamilyInfo>,
+
+    /// Supported capabilities and how they are provided.
+    pub capabilities: Vec<(ApiCapability, CapabilitySource)>,
+}
+
+/// Version of graphics API.
+#[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
+pub struct ApiVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl Display for ApiVersion {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Functionality that is part of core API in newer versions
+/// and provided by extensions in older ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
+pub enum ApiCapability {
+    BufferDeviceAddress,
+    DescriptorIndexing,
+    ScalarBlockLayout,
+    TimelineSemaphore,
+}
+
+/// How capability is provided by device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
+pub enum CapabilitySource {
+    /// Capability is part of supported API version.
+    Core,
+
+    /// Capability is provided by extension.
+    Extension,
 }
 
 /// Kind of the device.
@@ -48,6 +94,10 @@ pub enum DeviceKind {
 pub enum Feature {
     BufferDeviceAddress,
 
+    /// Allows capture tools to record opaque buffer addresses
+    /// and replay buffers at the same device addresses.
+    BufferDeviceAddressCaptureReplay,
+
     ShaderSampledImageDynamicIndexing,
     ShaderStorageImageDynamicIndexing,
     ShaderUniformBufferDynamicIndexing,
@@ -68,9 +118,29 @@ pub enum Feature {
     DescriptorBindingPartiallyBound,
     AccelerationStructure,
     RayTracingPipeline,
+
+    /// Allows ray-tracing pipelines to be created as libraries
+    /// and linked into other ray-tracing pipelines.
+    RayTracingPipelineLibrary,
     RuntimeDescriptorArray,
     ScalarBlockLayout,
     SurfacePresentation,
+
+    /// Allows cull mode, front face, primitive topology and depth test
+    /// to be set by commands for pipelines with `DynamicStates`.
+    ///
+    /// Backed by `VK_EXT_extended_dynamic_state` only.
+    /// Vertex input and states of `VK_EXT_extended_dynamic_state2`
+    /// and later extensions are always baked into pipelines.
+    ExtendedDynamicState,
+
+    /// Allows pipelines to use multiple viewports and scissors.
+    MultiViewport,
+
+    /// Makes barriers use `VK_KHR_synchronization2`
+    /// with per-barrier stages and precise copy, blit, resolve,
+    /// clear and vertex input stages.
+    Synchronization2,
 }
 
 #[allow(dead_code)]
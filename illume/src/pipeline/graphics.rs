@@ -87,6 +87,10 @@ pub struct GraphicsPipelineInfo {
 
     /// Subpass of the render pass within which this pipeline will be executed.
     pub subpass: u32,
+
+    /// States that are set by commands instead of values in this info.
+    /// Non-empty set requires `ExtendedDynamicState` feature.
+    pub dynamic_states: DynamicStates,
 }
 
 /// Builder for `GraphicsPipelineInfo`.
@@ -100,6 +104,7 @@ pub struct GraphicsPipelineInfoBuilder {
     pub primitive_restart_enable: bool,
     pub rasterizer: Option<Rasterizer>,
     pub subpass: u32,
+    pub dynamic_states: DynamicStates,
 }
 
 #[doc(hidden)]
@@ -112,6 +117,7 @@ impl GraphicsPipelineInfoBuilder {
             primitive_restart_enable: false,
             rasterizer: None,
             subpass: 0,
+            dynamic_states: DynamicStates::empty(),
         }
     }
 }
@@ -167,6 +173,10 @@ macro_rules! graphics_pipeline_info {
         graphics_pipeline_info!(@UNFOLD $builder { $($stmts)* $builder.subpass = $subpass.into(); } { $($field: $value),* } {$($rfield:$rvalue),*})
     };
 
+    (@UNFOLD $builder:ident { $($stmts:stmt)* } { dynamic_states: $dynamic_states:expr $(, $field:ident : $value:expr)* } { $($rfield:ident : $rvalue:expr),* }) => {
+        graphics_pipeline_info!(@UNFOLD $builder { $($stmts)* $builder.dynamic_states = $dynamic_states.into(); } { $($field: $value),* } {$($rfield:$rvalue),*})
+    };
+
     (@UNFOLD $builder:ident { $($stmts:stmt)* } { layout: $layout:expr $(, $field:ident : $value:expr)* } { $($rfield:ident : $rvalue:expr),* }) => {
         graphics_pipeline_info!(@UNFOLD $builder { $($stmts)* } { $($field: $value),* } { layout: $layout $(,$rfield:$rvalue)*})
     };
@@ -189,12 +199,42 @@ macro_rules! graphics_pipeline_info {
                 primitive_restart_enable: $builder.primitive_restart_enable,
                 rasterizer: $builder.rasterizer,
                 subpass: $builder.subpass,
+                dynamic_states: $builder.dynamic_states,
                 $($rfield: $rvalue,)*
             }
         }
     };
 }
 
+bitflags::bitflags! {
+    /// Pipeline states that can be set dynamically
+    /// with `ExtendedDynamicState` feature enabled.
+    ///
+    /// Corresponding values in pipeline info are ignored
+    /// and must be set by commands before drawing.
+    /// With dynamic `PRIMITIVE_TOPOLOGY` the topology set by command
+    /// must be of the same class (points, lines or triangles)
+    /// as `primitive_topology` in pipeline info.
+    ///
+    /// Vertex bindings and attributes cannot be dynamic,
+    /// pipelines with different vertex layouts must be created separately.
+    #[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
+    pub struct DynamicStates: u8 {
+        /// `Rasterizer::culling` is set with `set_cull_mode`.
+        const CULL_MODE = 0x01;
+
+        /// `Rasterizer::front_face` is set with `set_front_face`.
+        const FRONT_FACE = 0x02;
+
+        /// `GraphicsPipelineInfo::primitive_topology` is set with
+        /// `set_primitive_topology`.
+        const PRIMITIVE_TOPOLOGY = 0x04;
+
+        /// `Rasterizer::depth_test` is set with `set_depth_test`.
+        const DEPTH_TEST = 0x08;
+    }
+}
+
 /// Vertex buffer binding bahavior.
 /// Controls what subrange corresponds for vertex X of instance Y.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
@@ -330,6 +370,12 @@ pub struct Rasterizer {
     /// If the test fails for generated fragment that fragment is discared.
     pub scissor: State<Rect2d>,
 
+    /// Number of viewports and scissors.
+    /// Static viewport and scissor are used for each of them.
+    ///
+    /// If `MultiViewport` feature is not enabled this value must be `1`.
+    pub viewport_count: u32,
+
     /// Should fragments out of bounds on Z axis are clamped or discared.
     /// If `true` - fragments are clamped. This also disables primitive
     /// clipping. Otherwise they are clipped.
@@ -380,6 +426,7 @@ impl Rasterizer {
         Rasterizer {
             viewport: Dynamic,
             scissor: Dynamic,
+            viewport_count: 1,
             depth_clamp: false,
             front_face: FrontFace::Clockwise,
             culling: None,
@@ -430,6 +477,10 @@ macro_rules! rasterizer {
         rasterizer!(@UNFOLD $builder { $($stmts)* $builder.scissor = $scissor.into(); } { $($field: $value),* })
     };
 
+    (@UNFOLD $builder:ident { $($stmts:stmt)* } { viewport_count: $viewport_count:expr $(, $field:ident : $value:expr)* }) => {
+        rasterizer!(@UNFOLD $builder { $($stmts)* $builder.viewport_count = $viewport_count.into(); } { $($field: $value),* })
+    };
+
     (@UNFOLD $builder:ident { $($stmts:stmt)* } { depth_clamp: $depth_clamp:expr $(, $field:ident : $value:expr)* }) => {
         rasterizer!(@UNFOLD $builder { $($stmts)* $builder.depth_clamp = $depth_clamp.into(); } { $($field: $value),* })
     };
@@ -476,6 +527,7 @@ macro_rules! rasterizer {
             Rasterizer {
                 viewport: $builder.viewport,
                 scissor: $builder.scissor,
+                viewport_count: $builder.viewport_count,
                 depth_clamp: $builder.depth_clamp,
                 front_face: $builder.front_face,
                 culling: $builder.culling,
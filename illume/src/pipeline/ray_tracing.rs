@@ -15,6 +15,29 @@ pub struct RayTracingPipelineInfo {
 
     /// Pipeline layout.
     pub layout: PipelineLayout,
+
+    /// Pipeline libraries linked into this pipeline.
+    /// Their shader groups follow groups of this pipeline,
+    /// in order of libraries.
+    pub libraries: Vec<RayTracingPipeline>,
+
+    /// Interface of shaders in pipeline and libraries.
+    /// Required for libraries and pipelines that link them.
+    pub interface: Option<RayTracingPipelineInterface>,
+
+    /// Whether pipeline is created as library.
+    /// Libraries can only be linked into other pipelines.
+    pub library: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
+pub struct RayTracingPipelineInterface {
+    /// Maximum size of ray payload in bytes.
+    pub max_payload_size: u32,
+
+    /// Maximum size of hit attributes in bytes.
+    pub max_hit_attribute_size: u32,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
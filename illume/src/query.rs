@@ -0,0 +1,10 @@
+pub use crate::backend::QueryPool;
+
+/// Information required to create query pool.
+/// Only timestamp queries are supported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
+pub struct QueryPoolInfo {
+    /// Number of queries in the pool.
+    pub count: u32,
+}
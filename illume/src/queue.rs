@@ -192,6 +192,10 @@ pub enum PresentError {
 
     #[error("Surface was lost")]
     SurfaceLost,
+
+    /// Device is lost and must be recreated to continue presenting.
+    #[error("Device lost")]
+    DeviceLost,
     // FullScreenExclusiveModeLost,
 }
 
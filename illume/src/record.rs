@@ -0,0 +1,1594 @@
+//! Command stream recording.
+//!
+//! `CommandRecorder` installed on a `Queue` captures every command buffer
+//! submitted to the queue.
+//! Captured frames are written into text files, one command per line,
+//! and can be replayed against the queue to reproduce the command stream.
+
+use crate::{
+    accel::{
+        AccelerationStructure, AccelerationStructureBuildFlags,
+        AccelerationStructureBuildGeometryInfo, AccelerationStructureGeometry,
+    },
+    buffer::{Buffer, BufferMemoryBarrier},
+    descriptor::DescriptorSet,
+    encode::{
+        BufferCopy, BufferImageCopy, Command, EncodeError, ImageCopy,
+        ImageResolve,
+    },
+    framebuffer::Framebuffer,
+    image::{
+        Image, ImageBlit, ImageMemoryBarrier, ImageSubresourceRange, Layout,
+    },
+    pipeline::{
+        ComputePipeline, Culling, DepthTest, FrontFace, GraphicsPipeline,
+        PipelineLayout, PrimitiveTopology, RayTracingPipeline,
+        ShaderBindingTable, Viewport,
+    },
+    query::QueryPool,
+    queue::Queue,
+    render_pass::{ClearValue, RenderPass},
+    sampler::Filter,
+    shader::ShaderStageFlags,
+    stage::PipelineStageFlags,
+    validate::CommandValidator,
+    DeviceAddress, Extent3d, IndexType, Rect2d,
+};
+use parking_lot::Mutex;
+use std::{
+    collections::VecDeque,
+    fmt::{self, Display},
+    fs::File,
+    io::{self, BufWriter, Write},
+    ops::Range,
+    path::PathBuf,
+    sync::Arc,
+};
+
+/// Owned copy of `ImageMemoryBarrier`.
+#[derive(Clone, Debug)]
+pub struct RecordedImageBarrier {
+    pub image: Image,
+    pub old_layout: Option<Layout>,
+    pub new_layout: Layout,
+    pub family_transfer: Option<Range<u32>>,
+    pub subresource: ImageSubresourceRange,
+}
+
+impl RecordedImageBarrier {
+    fn as_barrier(&self) -> ImageMemoryBarrier<'_> {
+        ImageMemoryBarrier {
+            image: &self.image,
+            old_layout: self.old_layout,
+            new_layout: self.new_layout,
+            family_transfer: self.family_transfer.clone(),
+            subresource: self.subresource,
+        }
+    }
+}
+
+/// Owned copy of `BufferMemoryBarrier`.
+#[derive(Clone, Debug)]
+pub struct RecordedBufferBarrier {
+    pub buffer: Buffer,
+    pub offset: u64,
+    pub size: u64,
+    pub family_transfer: Option<Range<u32>>,
+}
+
+impl RecordedBufferBarrier {
+    fn as_barrier(&self) -> BufferMemoryBarrier<'_> {
+        BufferMemoryBarrier {
+            buffer: &self.buffer,
+            offset: self.offset,
+            size: self.size,
+            family_transfer: self.family_transfer.clone(),
+        }
+    }
+}
+
+/// Owned copy of `AccelerationStructureBuildGeometryInfo`.
+#[derive(Clone, Debug)]
+pub struct RecordedBuildInfo {
+    pub src: Option<AccelerationStructure>,
+    pub dst: AccelerationStructure,
+    pub flags: AccelerationStructureBuildFlags,
+    pub geometries: Vec<AccelerationStructureGeometry>,
+    pub scratch: DeviceAddress,
+}
+
+impl RecordedBuildInfo {
+    fn as_info(&self) -> AccelerationStructureBuildGeometryInfo<'_> {
+        AccelerationStructureBuildGeometryInfo {
+            src: self.src.clone(),
+            dst: self.dst.clone(),
+            flags: self.flags,
+            geometries: &self.geometries,
+            scratch: self.scratch,
+        }
+    }
+}
+
+/// Owned copy of `Command`.
+/// Keeps referenced resources alive.
+#[derive(Clone, Debug)]
+pub enum RecordedCommand {
+    BeginRenderPass {
+        pass: RenderPass,
+        framebuffer: Framebuffer,
+        clears: Vec<ClearValue>,
+    },
+    NextSubpass,
+    EndRenderPass,
+
+    BindGraphicsPipeline {
+        pipeline: GraphicsPipeline,
+    },
+
+    BindComputePipeline {
+        pipeline: ComputePipeline,
+    },
+
+    BindRayTracingPipeline {
+        pipeline: RayTracingPipeline,
+    },
+
+    BindGraphicsDescriptorSets {
+        layout: PipelineLayout,
+        first_set: u32,
+        sets: Vec<DescriptorSet>,
+        dynamic_offsets: Vec<u32>,
+    },
+
+    BindComputeDescriptorSets {
+        layout: PipelineLayout,
+        first_set: u32,
+        sets: Vec<DescriptorSet>,
+        dynamic_offsets: Vec<u32>,
+    },
+
+    BindRayTracingDescriptorSets {
+        layout: PipelineLayout,
+        first_set: u32,
+        sets: Vec<DescriptorSet>,
+        dynamic_offsets: Vec<u32>,
+    },
+
+    SetViewport {
+        viewport: Viewport,
+    },
+
+    SetScissor {
+        scissor: Rect2d,
+    },
+
+    SetViewports {
+        first: u32,
+        viewports: Vec<Viewport>,
+    },
+
+    SetScissors {
+        first: u32,
+        scissors: Vec<Rect2d>,
+    },
+
+    SetCullMode {
+        culling: Option<Culling>,
+    },
+
+    SetFrontFace {
+        front_face: FrontFace,
+    },
+
+    SetPrimitiveTopology {
+        topology: PrimitiveTopology,
+    },
+
+    SetDepthTest {
+        depth_test: Option<DepthTest>,
+    },
+
+    Draw {
+        vertices: Range<u32>,
+        instances: Range<u32>,
+    },
+
+    DrawIndexed {
+        indices: Range<u32>,
+        vertex_offset: i32,
+        instances: Range<u32>,
+    },
+
+    UpdateBuffer {
+        buffer: Buffer,
+        offset: u64,
+        data: Vec<u8>,
+    },
+
+    BindVertexBuffers {
+        first: u32,
+        buffers: Vec<(Buffer, u64)>,
+    },
+
+    BindIndexBuffer {
+        buffer: Buffer,
+        offset: u64,
+        index_type: IndexType,
+    },
+
+    BuildAccelerationStructure {
+        infos: Vec<RecordedBuildInfo>,
+    },
+
+    TraceRays {
+        shader_binding_table: ShaderBindingTable,
+        extent: Extent3d,
+    },
+
+    CopyBuffer {
+        src_buffer: Buffer,
+        dst_buffer: Buffer,
+        regions: Vec<BufferCopy>,
+    },
+
+    FillBuffer {
+        buffer: Buffer,
+        offset: u64,
+        size: u64,
+        value: u32,
+    },
+
+    ClearColorImage {
+        image: Image,
+        layout: Layout,
+        color: [f32; 4],
+        ranges: Vec<ImageSubresourceRange>,
+    },
+
+    ClearDepthStencilImage {
+        image: Image,
+        layout: Layout,
+        depth: f32,
+        stencil: u32,
+        ranges: Vec<ImageSubresourceRange>,
+    },
+
+    CopyImage {
+        src_image: Image,
+        src_layout: Layout,
+        dst_image: Image,
+        dst_layout: Layout,
+        regions: Vec<ImageCopy>,
+    },
+
+    CopyBufferImage {
+        src_buffer: Buffer,
+        dst_image: Image,
+        dst_layout: Layout,
+        regions: Vec<BufferImageCopy>,
+    },
+
+    BlitImage {
+        src_image: Image,
+        src_layout: Layout,
+        dst_image: Image,
+        dst_layout: Layout,
+        regions: Vec<ImageBlit>,
+        filter: Filter,
+    },
+
+    ResolveImage {
+        src_image: Image,
+        src_layout: Layout,
+        dst_image: Image,
+        dst_layout: Layout,
+        regions: Vec<ImageResolve>,
+    },
+
+    PipelineBarrier {
+        src: PipelineStageFlags,
+        dst: PipelineStageFlags,
+        images: Vec<RecordedImageBarrier>,
+    },
+
+    BufferBarriers {
+        src: PipelineStageFlags,
+        dst: PipelineStageFlags,
+        buffers: Vec<RecordedBufferBarrier>,
+    },
+
+    PushConstants {
+        layout: PipelineLayout,
+        stages: ShaderStageFlags,
+        offset: u32,
+        data: Vec<u8>,
+    },
+
+    Dispatch {
+        x: u32,
+        y: u32,
+        z: u32,
+    },
+
+    ResetQueryPool {
+        pool: QueryPool,
+        queries: Range<u32>,
+    },
+
+    WriteTimestamp {
+        pool: QueryPool,
+        query: u32,
+        stage: PipelineStageFlags,
+    },
+}
+
+impl RecordedCommand {
+    pub fn new(command: &Command<'_>) -> Self {
+        match *command {
+            Command::BeginRenderPass {
+                pass,
+                framebuffer,
+                clears,
+            } => RecordedCommand::BeginRenderPass {
+                pass: pass.clone(),
+                framebuffer: framebuffer.clone(),
+                clears: clears.to_vec(),
+            },
+            Command::NextSubpass => RecordedCommand::NextSubpass,
+            Command::EndRenderPass => RecordedCommand::EndRenderPass,
+            Command::BindGraphicsPipeline { pipeline } => {
+                RecordedCommand::BindGraphicsPipeline {
+                    pipeline: pipeline.clone(),
+                }
+            }
+            Command::BindComputePipeline { pipeline } => {
+                RecordedCommand::BindComputePipeline {
+                    pipeline: pipeline.clone(),
+                }
+            }
+            Command::BindRayTracingPipeline { pipeline } => {
+                RecordedCommand::BindRayTracingPipeline {
+                    pipeline: pipeline.clone(),
+                }
+            }
+            Command::BindGraphicsDescriptorSets {
+                layout,
+                first_set,
+                sets,
+                dynamic_offsets,
+            } => RecordedCommand::BindGraphicsDescriptorSets {
+                layout: layout.clone(),
+                first_set,
+                sets: sets.to_vec(),
+                dynamic_offsets: dynamic_offsets.to_vec(),
+            },
+            Command::BindComputeDescriptorSets {
+                layout,
+                first_set,
+                sets,
+                dynamic_offsets,
+            } => RecordedCommand::BindComputeDescriptorSets {
+                layout: layout.clone(),
+                first_set,
+                sets: sets.to_vec(),
+                dynamic_offsets: dynamic_offsets.to_vec(),
+            },
+            Command::BindRayTracingDescriptorSets {
+                layout,
+                first_set,
+                sets,
+                dynamic_offsets,
+            } => RecordedCommand::BindRayTracingDescriptorSets {
+                layout: layout.clone(),
+                first_set,
+                sets: sets.to_vec(),
+                dynamic_offsets: dynamic_offsets.to_vec(),
+            },
+            Command::SetViewport { viewport } => {
+                RecordedCommand::SetViewport { viewport }
+            }
+            Command::SetScissor { scissor } => {
+                RecordedCommand::SetScissor { scissor }
+            }
+            Command::SetViewports { first, viewports } => {
+                RecordedCommand::SetViewports {
+                    first,
+                    viewports: viewports.to_vec(),
+                }
+            }
+            Command::SetScissors { first, scissors } => {
+                RecordedCommand::SetScissors {
+                    first,
+                    scissors: scissors.to_vec(),
+                }
+            }
+            Command::SetCullMode { culling } => {
+                RecordedCommand::SetCullMode { culling }
+            }
+            Command::SetFrontFace { front_face } => {
+                RecordedCommand::SetFrontFace { front_face }
+            }
+            Command::SetPrimitiveTopology { topology } => {
+                RecordedCommand::SetPrimitiveTopology { topology }
+            }
+            Command::SetDepthTest { depth_test } => {
+                RecordedCommand::SetDepthTest { depth_test }
+            }
+            Command::Draw {
+                ref vertices,
+                ref instances,
+            } => RecordedCommand::Draw {
+                vertices: vertices.clone(),
+                instances: instances.clone(),
+            },
+            Command::DrawIndexed {
+                ref indices,
+                vertex_offset,
+                ref instances,
+            } => RecordedCommand::DrawIndexed {
+                indices: indices.clone(),
+                vertex_offset,
+                instances: instances.clone(),
+            },
+            Command::UpdateBuffer {
+                buffer,
+                offset,
+                data,
+            } => RecordedCommand::UpdateBuffer {
+                buffer: buffer.clone(),
+                offset,
+                data: data.to_vec(),
+            },
+            Command::BindVertexBuffers { first, buffers } => {
+                RecordedCommand::BindVertexBuffers {
+                    first,
+                    buffers: buffers.to_vec(),
+                }
+            }
+            Command::BindIndexBuffer {
+                buffer,
+                offset,
+                index_type,
+            } => RecordedCommand::BindIndexBuffer {
+                buffer: buffer.clone(),
+                offset,
+                index_type,
+            },
+            Command::BuildAccelerationStructure { infos } => {
+                RecordedCommand::BuildAccelerationStructure {
+                    infos: infos
+                        .iter()
+                        .map(|info| RecordedBuildInfo {
+                            src: info.src.clone(),
+                            dst: info.dst.clone(),
+                            flags: info.flags,
+                            geometries: info.geometries.to_vec(),
+                            scratch: info.scratch,
+                        })
+                        .collect(),
+                }
+            }
+            Command::TraceRays {
+                shader_binding_table,
+                extent,
+            } => RecordedCommand::TraceRays {
+                shader_binding_table: shader_binding_table.clone(),
+                extent,
+            },
+            Command::CopyBuffer {
+                src_buffer,
+                dst_buffer,
+                regions,
+            } => RecordedCommand::CopyBuffer {
+                src_buffer: src_buffer.clone(),
+                dst_buffer: dst_buffer.clone(),
+                regions: regions.to_vec(),
+            },
+            Command::FillBuffer {
+                buffer,
+                offset,
+                size,
+                value,
+            } => RecordedCommand::FillBuffer {
+                buffer: buffer.clone(),
+                offset,
+                size,
+                value,
+            },
+            Command::ClearColorImage {
+                image,
+                layout,
+                color,
+                ranges,
+            } => RecordedCommand::ClearColorImage {
+                image: image.clone(),
+                layout,
+                color,
+                ranges: ranges.to_vec(),
+            },
+            Command::ClearDepthStencilImage {
+                image,
+                layout,
+                depth,
+                stencil,
+                ranges,
+            } => RecordedCommand::ClearDepthStencilImage {
+                image: image.clone(),
+                layout,
+                depth,
+                stencil,
+                ranges: ranges.to_vec(),
+            },
+            Command::CopyImage {
+                src_image,
+                src_layout,
+                dst_image,
+                dst_layout,
+                regions,
+            } => RecordedCommand::CopyImage {
+                src_image: src_image.clone(),
+                src_layout,
+                dst_image: dst_image.clone(),
+                dst_layout,
+                regions: regions.to_vec(),
+            },
+            Command::CopyBufferImage {
+                src_buffer,
+                dst_image,
+                dst_layout,
+                regions,
+            } => RecordedCommand::CopyBufferImage {
+                src_buffer: src_buffer.clone(),
+                dst_image: dst_image.clone(),
+                dst_layout,
+                regions: regions.to_vec(),
+            },
+            Command::BlitImage {
+                src_image,
+                src_layout,
+                dst_image,
+                dst_layout,
+                regions,
+                filter,
+            } => RecordedCommand::BlitImage {
+                src_image: src_image.clone(),
+                src_layout,
+                dst_image: dst_image.clone(),
+                dst_layout,
+                regions: regions.to_vec(),
+                filter,
+            },
+            Command::ResolveImage {
+                src_image,
+                src_layout,
+                dst_image,
+                dst_layout,
+                regions,
+            } => RecordedCommand::ResolveImage {
+                src_image: src_image.clone(),
+                src_layout,
+                dst_image: dst_image.clone(),
+                dst_layout,
+                regions: regions.to_vec(),
+            },
+            Command::PipelineBarrier { src, dst, images } => {
+                RecordedCommand::PipelineBarrier {
+                    src,
+                    dst,
+                    images: images
+                        .iter()
+                        .map(|barrier| RecordedImageBarrier {
+                            image: barrier.image.clone(),
+                            old_layout: barrier.old_layout,
+                            new_layout: barrier.new_layout,
+                            family_transfer: barrier.family_transfer.clone(),
+                            subresource: barrier.subresource,
+                        })
+                        .collect(),
+                }
+            }
+            Command::BufferBarriers { src, dst, buffers } => {
+                RecordedCommand::BufferBarriers {
+                    src,
+                    dst,
+                    buffers: buffers
+                        .iter()
+                        .map(|barrier| RecordedBufferBarrier {
+                            buffer: barrier.buffer.clone(),
+                            offset: barrier.offset,
+                            size: barrier.size,
+                            family_transfer: barrier.family_transfer.clone(),
+                        })
+                        .collect(),
+                }
+            }
+            Command::PushConstants {
+                layout,
+                stages,
+                offset,
+                data,
+            } => RecordedCommand::PushConstants {
+                layout: layout.clone(),
+                stages,
+                offset,
+                data: data.to_vec(),
+            },
+            Command::Dispatch { x, y, z } => {
+                RecordedCommand::Dispatch { x, y, z }
+            }
+            Command::ResetQueryPool { pool, ref queries } => {
+                RecordedCommand::ResetQueryPool {
+                    pool: pool.clone(),
+                    queries: queries.clone(),
+                }
+            }
+            Command::WriteTimestamp { pool, query, stage } => {
+                RecordedCommand::WriteTimestamp {
+                    pool: pool.clone(),
+                    query,
+                    stage,
+                }
+            }
+        }
+    }
+
+    /// Returns command borrowing this one.
+    ///
+    /// `barriers`, `buffer_barriers` and `infos` must be produced
+    /// from this command by `as_barriers`, `as_buffer_barriers`
+    /// and `as_build_infos`.
+    fn as_command<'a>(
+        &'a self,
+        barriers: &'a [ImageMemoryBarrier<'a>],
+        buffer_barriers: &'a [BufferMemoryBarrier<'a>],
+        infos: &'a [AccelerationStructureBuildGeometryInfo<'a>],
+    ) -> Command<'a> {
+        match self {
+            RecordedCommand::BeginRenderPass {
+                pass,
+                framebuffer,
+                clears,
+            } => Command::BeginRenderPass {
+                pass,
+                framebuffer,
+                clears,
+            },
+            RecordedCommand::NextSubpass => Command::NextSubpass,
+            RecordedCommand::EndRenderPass => Command::EndRenderPass,
+            RecordedCommand::BindGraphicsPipeline { pipeline } => {
+                Command::BindGraphicsPipeline { pipeline }
+            }
+            RecordedCommand::BindComputePipeline { pipeline } => {
+                Command::BindComputePipeline { pipeline }
+            }
+            RecordedCommand::BindRayTracingPipeline { pipeline } => {
+                Command::BindRayTracingPipeline { pipeline }
+            }
+            RecordedCommand::BindGraphicsDescriptorSets {
+                layout,
+                first_set,
+                sets,
+                dynamic_offsets,
+            } => Command::BindGraphicsDescriptorSets {
+                layout,
+                first_set: *first_set,
+                sets,
+                dynamic_offsets,
+            },
+            RecordedCommand::BindComputeDescriptorSets {
+                layout,
+                first_set,
+                sets,
+                dynamic_offsets,
+            } => Command::BindComputeDescriptorSets {
+                layout,
+                first_set: *first_set,
+                sets,
+                dynamic_offsets,
+            },
+            RecordedCommand::BindRayTracingDescriptorSets {
+                layout,
+                first_set,
+                sets,
+                dynamic_offsets,
+            } => Command::BindRayTracingDescriptorSets {
+                layout,
+                first_set: *first_set,
+                sets,
+                dynamic_offsets,
+            },
+            RecordedCommand::SetViewport { viewport } => Command::SetViewport {
+                viewport: *viewport,
+            },
+            RecordedCommand::SetScissor { scissor } => {
+                Command::SetScissor { scissor: *scissor }
+            }
+            RecordedCommand::SetViewports { first, viewports } => {
+                Command::SetViewports {
+                    first: *first,
+                    viewports,
+                }
+            }
+            RecordedCommand::SetScissors { first, scissors } => {
+                Command::SetScissors {
+                    first: *first,
+                    scissors,
+                }
+            }
+            RecordedCommand::SetCullMode { culling } => {
+                Command::SetCullMode { culling: *culling }
+            }
+            RecordedCommand::SetFrontFace { front_face } => {
+                Command::SetFrontFace {
+                    front_face: *front_face,
+                }
+            }
+            RecordedCommand::SetPrimitiveTopology { topology } => {
+                Command::SetPrimitiveTopology {
+                    topology: *topology,
+                }
+            }
+            RecordedCommand::SetDepthTest { depth_test } => {
+                Command::SetDepthTest {
+                    depth_test: *depth_test,
+                }
+            }
+            RecordedCommand::Draw {
+                vertices,
+                instances,
+            } => Command::Draw {
+                vertices: vertices.clone(),
+                instances: instances.clone(),
+            },
+            RecordedCommand::DrawIndexed {
+                indices,
+                vertex_offset,
+                instances,
+            } => Command::DrawIndexed {
+                indices: indices.clone(),
+                vertex_offset: *vertex_offset,
+                instances: instances.clone(),
+            },
+            RecordedCommand::UpdateBuffer {
+                buffer,
+                offset,
+                data,
+            } => Command::UpdateBuffer {
+                buffer,
+                offset: *offset,
+                data,
+            },
+            RecordedCommand::BindVertexBuffers { first, buffers } => {
+                Command::BindVertexBuffers {
+                    first: *first,
+                    buffers,
+                }
+            }
+            RecordedCommand::BindIndexBuffer {
+                buffer,
+                offset,
+                index_type,
+            } => Command::BindIndexBuffer {
+                buffer,
+                offset: *offset,
+                index_type: *index_type,
+            },
+            RecordedCommand::BuildAccelerationStructure { .. } => {
+                Command::BuildAccelerationStructure { infos }
+            }
+            RecordedCommand::TraceRays {
+                shader_binding_table,
+                extent,
+            } => Command::TraceRays {
+                shader_binding_table,
+                extent: *extent,
+            },
+            RecordedCommand::CopyBuffer {
+                src_buffer,
+                dst_buffer,
+                regions,
+            } => Command::CopyBuffer {
+                src_buffer,
+                dst_buffer,
+                regions,
+            },
+            RecordedCommand::FillBuffer {
+                buffer,
+                offset,
+                size,
+                value,
+            } => Command::FillBuffer {
+                buffer,
+                offset: *offset,
+                size: *size,
+                value: *value,
+            },
+            RecordedCommand::ClearColorImage {
+                image,
+                layout,
+                color,
+                ranges,
+            } => Command::ClearColorImage {
+                image,
+                layout: *layout,
+                color: *color,
+                ranges,
+            },
+            RecordedCommand::ClearDepthStencilImage {
+                image,
+                layout,
+                depth,
+                stencil,
+                ranges,
+            } => Command::ClearDepthStencilImage {
+                image,
+                layout: *layout,
+                depth: *depth,
+                stencil: *stencil,
+                ranges,
+            },
+            RecordedCommand::CopyImage {
+                src_image,
+                src_layout,
+                dst_image,
+                dst_layout,
+                regions,
+            } => Command::CopyImage {
+                src_image,
+                src_layout: *src_layout,
+                dst_image,
+                dst_layout: *dst_layout,
+                regions,
+            },
+            RecordedCommand::CopyBufferImage {
+                src_buffer,
+                dst_image,
+                dst_layout,
+                regions,
+            } => Command::CopyBufferImage {
+                src_buffer,
+                dst_image,
+                dst_layout: *dst_layout,
+                regions,
+            },
+            RecordedCommand::BlitImage {
+                src_image,
+                src_layout,
+                dst_image,
+                dst_layout,
+                regions,
+                filter,
+            } => Command::BlitImage {
+                src_image,
+                src_layout: *src_layout,
+                dst_image,
+                dst_layout: *dst_layout,
+                regions,
+                filter: *filter,
+            },
+            RecordedCommand::ResolveImage {
+                src_image,
+                src_layout,
+                dst_image,
+                dst_layout,
+                regions,
+            } => Command::ResolveImage {
+                src_image,
+                src_layout: *src_layout,
+                dst_image,
+                dst_layout: *dst_layout,
+                regions,
+            },
+            RecordedCommand::PipelineBarrier { src, dst, .. } => {
+                Command::PipelineBarrier {
+                    src: *src,
+                    dst: *dst,
+                    images: barriers,
+                }
+            }
+            RecordedCommand::BufferBarriers { src, dst, .. } => {
+                Command::BufferBarriers {
+                    src: *src,
+                    dst: *dst,
+                    buffers: buffer_barriers,
+                }
+            }
+            RecordedCommand::PushConstants {
+                layout,
+                stages,
+                offset,
+                data,
+            } => Command::PushConstants {
+                layout,
+                stages: *stages,
+                offset: *offset,
+                data,
+            },
+            RecordedCommand::Dispatch { x, y, z } => Command::Dispatch {
+                x: *x,
+                y: *y,
+                z: *z,
+            },
+            RecordedCommand::ResetQueryPool { pool, queries } => {
+                Command::ResetQueryPool {
+                    pool,
+                    queries: queries.clone(),
+                }
+            }
+            RecordedCommand::WriteTimestamp { pool, query, stage } => {
+                Command::WriteTimestamp {
+                    pool,
+                    query: *query,
+                    stage: *stage,
+                }
+            }
+        }
+    }
+
+    /// Returns name of the command.
+    pub fn name(&self) -> &'static str {
+        match self {
+            RecordedCommand::BeginRenderPass { .. } => "BeginRenderPass",
+            RecordedCommand::NextSubpass => "NextSubpass",
+            RecordedCommand::EndRenderPass => "EndRenderPass",
+            RecordedCommand::BindGraphicsPipeline { .. } => {
+                "BindGraphicsPipeline"
+            }
+            RecordedCommand::BindComputePipeline { .. } => {
+                "BindComputePipeline"
+            }
+            RecordedCommand::BindRayTracingPipeline { .. } => {
+                "BindRayTracingPipeline"
+            }
+            RecordedCommand::BindGraphicsDescriptorSets { .. } => {
+                "BindGraphicsDescriptorSets"
+            }
+            RecordedCommand::BindComputeDescriptorSets { .. } => {
+                "BindComputeDescriptorSets"
+            }
+            RecordedCommand::BindRayTracingDescriptorSets { .. } => {
+                "BindRayTracingDescriptorSets"
+            }
+            RecordedCommand::SetViewport { .. } => "SetViewport",
+            RecordedCommand::SetScissor { .. } => "SetScissor",
+            RecordedCommand::SetViewports { .. } => "SetViewports",
+            RecordedCommand::SetScissors { .. } => "SetScissors",
+            RecordedCommand::SetCullMode { .. } => "SetCullMode",
+            RecordedCommand::SetFrontFace { .. } => "SetFrontFace",
+            RecordedCommand::SetPrimitiveTopology { .. } => {
+                "SetPrimitiveTopology"
+            }
+            RecordedCommand::SetDepthTest { .. } => "SetDepthTest",
+            RecordedCommand::Draw { .. } => "Draw",
+            RecordedCommand::DrawIndexed { .. } => "DrawIndexed",
+            RecordedCommand::UpdateBuffer { .. } => "UpdateBuffer",
+            RecordedCommand::BindVertexBuffers { .. } => "BindVertexBuffers",
+            RecordedCommand::BindIndexBuffer { .. } => "BindIndexBuffer",
+            RecordedCommand::BuildAccelerationStructure { .. } => {
+                "BuildAccelerationStructure"
+            }
+            RecordedCommand::TraceRays { .. } => "TraceRays",
+            RecordedCommand::CopyBuffer { .. } => "CopyBuffer",
+            RecordedCommand::FillBuffer { .. } => "FillBuffer",
+            RecordedCommand::ClearColorImage { .. } => "ClearColorImage",
+            RecordedCommand::ClearDepthStencilImage { .. } => {
+                "ClearDepthStencilImage"
+            }
+            RecordedCommand::CopyImage { .. } => "CopyImage",
+            RecordedCommand::CopyBufferImage { .. } => "CopyBufferImage",
+            RecordedCommand::BlitImage { .. } => "BlitImage",
+            RecordedCommand::ResolveImage { .. } => "ResolveImage",
+            RecordedCommand::PipelineBarrier { .. } => "PipelineBarrier",
+            RecordedCommand::BufferBarriers { .. } => "BufferBarriers",
+            RecordedCommand::PushConstants { .. } => "PushConstants",
+            RecordedCommand::Dispatch { .. } => "Dispatch",
+            RecordedCommand::ResetQueryPool { .. } => "ResetQueryPool",
+            RecordedCommand::WriteTimestamp { .. } => "WriteTimestamp",
+        }
+    }
+
+    fn as_barriers(&self) -> Vec<ImageMemoryBarrier<'_>> {
+        match self {
+            RecordedCommand::PipelineBarrier { images, .. } => images
+                .iter()
+                .map(RecordedImageBarrier::as_barrier)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn as_buffer_barriers(&self) -> Vec<BufferMemoryBarrier<'_>> {
+        match self {
+            RecordedCommand::BufferBarriers { buffers, .. } => buffers
+                .iter()
+                .map(RecordedBufferBarrier::as_barrier)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn as_build_infos(
+        &self,
+    ) -> Vec<AccelerationStructureBuildGeometryInfo<'_>> {
+        match self {
+            RecordedCommand::BuildAccelerationStructure { infos } => {
+                infos.iter().map(RecordedBuildInfo::as_info).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Formats buffer with its size.
+struct BufferDump<'a>(&'a Buffer);
+
+impl Display for BufferDump<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "{:?}[{} bytes]", self.0, self.0.info().size)
+    }
+}
+
+/// Formats image with its extent and format.
+struct ImageDump<'a>(&'a Image);
+
+impl Display for ImageDump<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let info = self.0.info();
+        let extent = info.extent.into_3d();
+        write!(
+            fmt,
+            "{:?}[{}x{}x{} {:?}]",
+            self.0, extent.width, extent.height, extent.depth, info.format
+        )
+    }
+}
+
+/// Writes command as single line of `Name key=value` pairs.
+impl Display for RecordedCommand {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordedCommand::BeginRenderPass {
+                pass,
+                framebuffer,
+                clears,
+            } => write!(
+                fmt,
+                "BeginRenderPass pass={:?} framebuffer={:?} clears={:?}",
+                pass, framebuffer, clears
+            ),
+            RecordedCommand::NextSubpass => fmt.write_str("NextSubpass"),
+            RecordedCommand::EndRenderPass => fmt.write_str("EndRenderPass"),
+            RecordedCommand::BindGraphicsPipeline { pipeline } => {
+                write!(fmt, "BindGraphicsPipeline pipeline={:?}", pipeline)
+            }
+            RecordedCommand::BindComputePipeline { pipeline } => {
+                write!(fmt, "BindComputePipeline pipeline={:?}", pipeline)
+            }
+            RecordedCommand::BindRayTracingPipeline { pipeline } => {
+                write!(fmt, "BindRayTracingPipeline pipeline={:?}", pipeline)
+            }
+            RecordedCommand::BindGraphicsDescriptorSets {
+                layout,
+                first_set,
+                sets,
+                dynamic_offsets,
+            } => write!(
+                fmt,
+                "BindGraphicsDescriptorSets layout={:?} first_set={} \
+                 sets={:?} dynamic_offsets={:?}",
+                layout, first_set, sets, dynamic_offsets
+            ),
+            RecordedCommand::BindComputeDescriptorSets {
+                layout,
+                first_set,
+                sets,
+                dynamic_offsets,
+            } => write!(
+                fmt,
+                "BindComputeDescriptorSets layout={:?} first_set={} \
+                 sets={:?} dynamic_offsets={:?}",
+                layout, first_set, sets, dynamic_offsets
+            ),
+            RecordedCommand::BindRayTracingDescriptorSets {
+                layout,
+                first_set,
+                sets,
+                dynamic_offsets,
+            } => write!(
+                fmt,
+                "BindRayTracingDescriptorSets layout={:?} first_set={} \
+                 sets={:?} dynamic_offsets={:?}",
+                layout, first_set, sets, dynamic_offsets
+            ),
+            RecordedCommand::SetViewport { viewport } => {
+                write!(fmt, "SetViewport viewport={:?}", viewport)
+            }
+            RecordedCommand::SetScissor { scissor } => {
+                write!(fmt, "SetScissor scissor={:?}", scissor)
+            }
+            RecordedCommand::SetViewports { first, viewports } => write!(
+                fmt,
+                "SetViewports first={} viewports={:?}",
+                first, viewports
+            ),
+            RecordedCommand::SetScissors { first, scissors } => write!(
+                fmt,
+                "SetScissors first={} scissors={:?}",
+                first, scissors
+            ),
+            RecordedCommand::SetCullMode { culling } => {
+                write!(fmt, "SetCullMode culling={:?}", culling)
+            }
+            RecordedCommand::SetFrontFace { front_face } => {
+                write!(fmt, "SetFrontFace front_face={:?}", front_face)
+            }
+            RecordedCommand::SetPrimitiveTopology { topology } => {
+                write!(fmt, "SetPrimitiveTopology topology={:?}", topology)
+            }
+            RecordedCommand::SetDepthTest { depth_test } => {
+                write!(fmt, "SetDepthTest depth_test={:?}", depth_test)
+            }
+            RecordedCommand::Draw {
+                vertices,
+                instances,
+            } => write!(
+                fmt,
+                "Draw vertices={:?} instances={:?}",
+                vertices, instances
+            ),
+            RecordedCommand::DrawIndexed {
+                indices,
+                vertex_offset,
+                instances,
+            } => write!(
+                fmt,
+                "DrawIndexed indices={:?} vertex_offset={} instances={:?}",
+                indices, vertex_offset, instances
+            ),
+            RecordedCommand::UpdateBuffer {
+                buffer,
+                offset,
+                data,
+            } => write!(
+                fmt,
+                "UpdateBuffer buffer={} offset={} size={}",
+                BufferDump(buffer),
+                offset,
+                data.len()
+            ),
+            RecordedCommand::BindVertexBuffers { first, buffers } => {
+                write!(fmt, "BindVertexBuffers first={} buffers=[", first)?;
+                for (index, (buffer, offset)) in buffers.iter().enumerate() {
+                    if index > 0 {
+                        fmt.write_str(", ")?;
+                    }
+                    write!(fmt, "{}+{}", BufferDump(buffer), offset)?;
+                }
+                fmt.write_str("]")
+            }
+            RecordedCommand::BindIndexBuffer {
+                buffer,
+                offset,
+                index_type,
+            } => write!(
+                fmt,
+                "BindIndexBuffer buffer={} offset={} index_type={:?}",
+                BufferDump(buffer),
+                offset,
+                index_type
+            ),
+            RecordedCommand::BuildAccelerationStructure { infos } => {
+                fmt.write_str("BuildAccelerationStructure infos=[")?;
+                for (index, info) in infos.iter().enumerate() {
+                    if index > 0 {
+                        fmt.write_str(", ")?;
+                    }
+                    write!(
+                        fmt,
+                        "{{ src={:?} dst={:?} flags={:?} geometries={} }}",
+                        info.src,
+                        info.dst,
+                        info.flags,
+                        info.geometries.len()
+                    )?;
+                }
+                fmt.write_str("]")
+            }
+            RecordedCommand::TraceRays {
+                shader_binding_table,
+                extent,
+            } => write!(
+                fmt,
+                "TraceRays shader_binding_table={:?} extent={}x{}x{}",
+                shader_binding_table, extent.width, extent.height, extent.depth
+            ),
+            RecordedCommand::CopyBuffer {
+                src_buffer,
+                dst_buffer,
+                regions,
+            } => write!(
+                fmt,
+                "CopyBuffer src_buffer={} dst_buffer={} regions={:?}",
+                BufferDump(src_buffer),
+                BufferDump(dst_buffer),
+                regions
+            ),
+            RecordedCommand::FillBuffer {
+                buffer,
+                offset,
+                size,
+                value,
+            } => write!(
+                fmt,
+                "FillBuffer buffer={} offset={} size={} value={:#x}",
+                BufferDump(buffer),
+                offset,
+                size,
+                value
+            ),
+            RecordedCommand::ClearColorImage {
+                image,
+                layout,
+                color,
+                ranges,
+            } => write!(
+                fmt,
+                "ClearColorImage image={} layout={:?} color={:?} ranges={:?}",
+                ImageDump(image),
+                layout,
+                color,
+                ranges
+            ),
+            RecordedCommand::ClearDepthStencilImage {
+                image,
+                layout,
+                depth,
+                stencil,
+                ranges,
+            } => write!(
+                fmt,
+                "ClearDepthStencilImage image={} layout={:?} depth={} \
+                 stencil={} ranges={:?}",
+                ImageDump(image),
+                layout,
+                depth,
+                stencil,
+                ranges
+            ),
+            RecordedCommand::CopyImage {
+                src_image,
+                src_layout,
+                dst_image,
+                dst_layout,
+                regions,
+            } => write!(
+                fmt,
+                "CopyImage src_image={} src_layout={:?} dst_image={} \
+                 dst_layout={:?} regions={:?}",
+                ImageDump(src_image),
+                src_layout,
+                ImageDump(dst_image),
+                dst_layout,
+                regions
+            ),
+            RecordedCommand::CopyBufferImage {
+                src_buffer,
+                dst_image,
+                dst_layout,
+                regions,
+            } => write!(
+                fmt,
+                "CopyBufferImage src_buffer={} dst_image={} dst_layout={:?} \
+                 regions={:?}",
+                BufferDump(src_buffer),
+                ImageDump(dst_image),
+                dst_layout,
+                regions
+            ),
+            RecordedCommand::BlitImage {
+                src_image,
+                src_layout,
+                dst_image,
+                dst_layout,
+                regions,
+                filter,
+            } => write!(
+                fmt,
+                "BlitImage src_image={} src_layout={:?} dst_image={} \
+                 dst_layout={:?} regions={:?} filter={:?}",
+                ImageDump(src_image),
+                src_layout,
+                ImageDump(dst_image),
+                dst_layout,
+                regions,
+                filter
+            ),
+            RecordedCommand::ResolveImage {
+                src_image,
+                src_layout,
+                dst_image,
+                dst_layout,
+                regions,
+            } => write!(
+                fmt,
+                "ResolveImage src_image={} src_layout={:?} dst_image={} \
+                 dst_layout={:?} regions={:?}",
+                ImageDump(src_image),
+                src_layout,
+                ImageDump(dst_image),
+                dst_layout,
+                regions
+            ),
+            RecordedCommand::PipelineBarrier { src, dst, images } => {
+                write!(
+                    fmt,
+                    "PipelineBarrier src={:?} dst={:?} images=[",
+                    src, dst
+                )?;
+                for (index, barrier) in images.iter().enumerate() {
+                    if index > 0 {
+                        fmt.write_str(", ")?;
+                    }
+                    write!(
+                        fmt,
+                        "{} {:?}..{:?}",
+                        ImageDump(&barrier.image),
+                        barrier.old_layout,
+                        barrier.new_layout
+                    )?;
+                }
+                fmt.write_str("]")
+            }
+            RecordedCommand::BufferBarriers { src, dst, buffers } => {
+                write!(
+                    fmt,
+                    "BufferBarriers src={:?} dst={:?} buffers=[",
+                    src, dst
+                )?;
+                for (index, barrier) in buffers.iter().enumerate() {
+                    if index > 0 {
+                        fmt.write_str(", ")?;
+                    }
+                    write!(
+                        fmt,
+                        "{} {}..+{} {:?}",
+                        BufferDump(&barrier.buffer),
+                        barrier.offset,
+                        barrier.size,
+                        barrier.family_transfer
+                    )?;
+                }
+                fmt.write_str("]")
+            }
+            RecordedCommand::PushConstants {
+                layout,
+                stages,
+                offset,
+                data,
+            } => write!(
+                fmt,
+                "PushConstants layout={:?} stages={:?} offset={} size={}",
+                layout,
+                stages,
+                offset,
+                data.len()
+            ),
+            RecordedCommand::Dispatch { x, y, z } => {
+                write!(fmt, "Dispatch x={} y={} z={}", x, y, z)
+            }
+            RecordedCommand::ResetQueryPool { pool, queries } => {
+                write!(
+                    fmt,
+                    "ResetQueryPool pool={:?} queries={:?}",
+                    pool, queries
+                )
+            }
+            RecordedCommand::WriteTimestamp { pool, query, stage } => write!(
+                fmt,
+                "WriteTimestamp pool={:?} query={} stage={:?}",
+                pool, query, stage
+            ),
+        }
+    }
+}
+
+/// Commands of one submitted command buffer.
+#[derive(Clone, Debug, Default)]
+pub struct RecordedSubmission {
+    pub commands: Arc<[RecordedCommand]>,
+}
+
+/// Submissions made to a queue between two `Queue::begin_frame` calls.
+#[derive(Clone, Debug, Default)]
+pub struct RecordedFrame {
+    pub index: u64,
+    pub submissions: Vec<RecordedSubmission>,
+}
+
+impl RecordedFrame {
+    /// Writes frame as text, one command per line.
+    pub fn write(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "# frame {}", self.index)?;
+
+        for (index, submission) in self.submissions.iter().enumerate() {
+            writeln!(
+                writer,
+                "# submission {} commands={}",
+                index,
+                submission.commands.len()
+            )?;
+
+            for command in submission.commands.iter() {
+                writeln!(writer, "{}", command)?;
+            }
+        }
+
+        writer.flush()
+    }
+
+    /// Encodes and submits recorded commands again.
+    ///
+    /// Semaphores and fences are not recorded,
+    /// submissions are replayed without synchronization with the swapchain
+    /// and must not touch its images.
+    pub fn replay(&self, queue: &mut Queue) -> Result<(), EncodeError> {
+        for submission in &self.submissions {
+            // Arguments borrowed by commands must outlive the encoder.
+            let barriers: Vec<_> = submission
+                .commands
+                .iter()
+                .map(RecordedCommand::as_barriers)
+                .collect();
+
+            let buffer_barriers: Vec<_> = submission
+                .commands
+                .iter()
+                .map(RecordedCommand::as_buffer_barriers)
+                .collect();
+
+            let infos: Vec<_> = submission
+                .commands
+                .iter()
+                .map(RecordedCommand::as_build_infos)
+                .collect();
+
+            let mut encoder = queue.create_encoder()?;
+
+            for (((command, barriers), buffer_barriers), infos) in submission
+                .commands
+                .iter()
+                .zip(&barriers)
+                .zip(&buffer_barriers)
+                .zip(&infos)
+            {
+                encoder.push_command(command.as_command(
+                    barriers,
+                    buffer_barriers,
+                    infos,
+                ));
+            }
+
+            queue.submit_no_semaphores(encoder.finish()?, None)?;
+        }
+
+        Ok(())
+    }
+}
+
+struct RecorderInner {
+    /// Directory for frame files.
+    directory: Option<PathBuf>,
+
+    /// Number of frames kept for replay.
+    keep: usize,
+
+    /// Validates every finished frame if set.
+    validator: Option<CommandValidator>,
+
+    current: RecordedFrame,
+    frames: VecDeque<RecordedFrame>,
+}
+
+/// Records command buffers submitted to a queue.
+///
+/// Recorded frames keep referenced resources alive
+/// until they are dropped.
+#[derive(Clone)]
+pub struct CommandRecorder {
+    inner: Arc<Mutex<RecorderInner>>,
+}
+
+impl fmt::Debug for CommandRecorder {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str("CommandRecorder")
+    }
+}
+
+impl CommandRecorder {
+    /// Returns recorder that keeps `keep` last frames in memory.
+    pub fn new(keep: usize) -> Self {
+        CommandRecorder {
+            inner: Arc::new(Mutex::new(RecorderInner {
+                directory: None,
+                keep,
+                validator: None,
+                current: RecordedFrame::default(),
+                frames: VecDeque::new(),
+            })),
+        }
+    }
+
+    /// Writes every finished frame into `frame-<index>.txt` file
+    /// in specified directory.
+    pub fn with_directory(self, directory: impl Into<PathBuf>) -> Self {
+        self.inner.lock().directory = Some(directory.into());
+        self
+    }
+
+    /// Validates every finished frame and reports errors with `tracing`.
+    pub fn with_validation(self) -> Self {
+        self.inner.lock().validator = Some(CommandValidator::new());
+        self
+    }
+
+    /// Returns last finished frames, oldest first.
+    pub fn frames(&self) -> Vec<RecordedFrame> {
+        self.inner.lock().frames.iter().cloned().collect()
+    }
+
+    /// Returns last finished frame.
+    pub fn last_frame(&self) -> Option<RecordedFrame> {
+        self.inner.lock().frames.back().cloned()
+    }
+
+    /// Returns submissions of the frame being recorded.
+    pub fn current_frame(&self) -> RecordedFrame {
+        self.inner.lock().current.clone()
+    }
+
+    pub(crate) fn record(&self, commands: Arc<[RecordedCommand]>) {
+        self.inner
+            .lock()
+            .current
+            .submissions
+            .push(RecordedSubmission { commands });
+    }
+
+    pub(crate) fn end_frame(&self) {
+        let mut inner = self.inner.lock();
+        let index = inner.current.index;
+        let frame = std::mem::replace(
+            &mut inner.current,
+            RecordedFrame {
+                index: index + 1,
+                submissions: Vec::new(),
+            },
+        );
+
+        if let Some(directory) = &inner.directory {
+            let path = directory.join(format!("frame-{:06}.txt", frame.index));
+            let result = std::fs::create_dir_all(directory)
+                .and_then(|()| File::create(&path))
+                .and_then(|file| frame.write(BufWriter::new(file)));
+
+            if let Err(err) = result {
+                tracing::error!(
+                    "Failed to write recorded frame '{}': {}",
+                    path.display(),
+                    err
+                );
+            }
+        }
+
+        if let Some(validator) = &mut inner.validator {
+            for error in validator.validate(&frame) {
+                tracing::warn!("Invalid command stream. {}", error);
+            }
+        }
+
+        inner.frames.push_back(frame);
+        while inner.frames.len() > inner.keep {
+            inner.frames.pop_front();
+        }
+    }
+}
@@ -37,6 +37,24 @@ pub struct RenderPassInfo {
     pub dependencies: SmallVec<[SubpassDependency; SMALLVEC_SUBPASSES]>,
 }
 
+impl RenderPassInfo {
+    /// Returns number of samples of color and depth attachments
+    /// used in specified subpass.
+    /// Graphics pipelines rasterize with this number of samples.
+    pub fn subpass_samples(&self, subpass: usize) -> Samples {
+        let subpass = &self.subpasses[subpass];
+
+        subpass
+            .colors
+            .iter()
+            .chain(&subpass.depth)
+            .next()
+            .map_or(Samples::Samples1, |&attachment| {
+                self.attachments[attachment].samples
+            })
+    }
+}
+
 /// Describes one attachment of a render pass.
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
 #[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
@@ -117,6 +135,16 @@ pub struct Subpass {
         serde(skip_serializing_if = "Option::is_none", default)
     )]
     pub depth: Option<usize>,
+
+    /// Indices of single-sampled attachments into which multisampled color
+    /// attachments of this subpass are resolved at the end of the subpass.
+    ///
+    /// Must be either empty or have one index per color attachment.
+    #[cfg_attr(
+        feature = "serde-1",
+        serde(skip_serializing_if = "SmallVec::is_empty", default)
+    )]
+    pub resolves: SmallVec<[usize; RENDERPASS_SMALLVEC_ATTACHMENTS]>,
 }
 
 /// Defines memory dependency between two subpasses
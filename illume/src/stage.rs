@@ -64,5 +64,28 @@ bitflags::bitflags! {
 
         /// Stage at which acceleration structures are built.
         const ACCELERATION_STRUCTURE_BUILD = 0x02000000;
+
+        // Stages below are parts of `VERTEX_INPUT` and `TRANSFER`.
+        // They are synchronized separately only with
+        // `Synchronization2` feature enabled.
+        // Otherwise they are treated as their whole stage.
+
+        /// Stage at which index buffers are read.
+        const INDEX_INPUT = 0x00020000;
+
+        /// Stage at which vertex attribute buffers are read.
+        const VERTEX_ATTRIBUTE_INPUT = 0x00040000;
+
+        /// Stage at which copy commands are executed.
+        const COPY = 0x00080000;
+
+        /// Stage at which blit commands are executed.
+        const BLIT = 0x00100000;
+
+        /// Stage at which resolve commands are executed.
+        const RESOLVE = 0x00400000;
+
+        /// Stage at which clear commands are executed.
+        const CLEAR = 0x00800000;
     }
 }
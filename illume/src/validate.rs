@@ -0,0 +1,484 @@
+//! Headless validation of recorded command streams.
+//!
+//! `CommandValidator` checks frames captured by `CommandRecorder`
+//! without touching the device, so encoder usage may be verified
+//! where no GPU is available.
+//! Image layouts are tracked for whole images across frames.
+
+use crate::{
+    backend::{Device, WeakDevice},
+    buffer::Buffer,
+    framebuffer::Framebuffer,
+    image::{Image, Layout},
+    record::{RecordedCommand, RecordedFrame, RecordedSubmission},
+};
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, thiserror::Error)]
+pub enum ValidationErrorKind {
+    #[error("Render pass begins inside another render pass")]
+    NestedRenderPass,
+
+    #[error("Render pass ends without beginning")]
+    EndWithoutBegin,
+
+    #[error("Render pass is not ended in command buffer")]
+    UnterminatedRenderPass,
+
+    #[error("`{command}` must be encoded inside render pass")]
+    OutsideRenderPass { command: &'static str },
+
+    #[error("`{command}` must be encoded outside render pass")]
+    InsideRenderPass { command: &'static str },
+
+    #[error("`{command}` is encoded without bound pipeline")]
+    NoPipeline { command: &'static str },
+
+    #[error("Image {image} is in {actual:?} layout, {expected:?} is expected")]
+    LayoutMismatch {
+        image: String,
+        expected: Layout,
+        actual: Layout,
+    },
+
+    #[error("{resource} is not owned by validated device")]
+    ForeignResource { resource: String },
+}
+
+/// Invalid command found in recorded frame.
+#[derive(Clone, Debug, thiserror::Error)]
+#[error("Frame {frame}, submission {submission}, command {command}: {kind}")]
+pub struct ValidationError {
+    pub frame: u64,
+    pub submission: usize,
+    pub command: usize,
+    pub kind: ValidationErrorKind,
+}
+
+/// Bind points with bound pipelines.
+#[derive(Default)]
+struct Bound {
+    graphics: bool,
+    compute: bool,
+    ray_tracing: bool,
+}
+
+/// Validates recorded frames.
+#[derive(Debug, Default)]
+pub struct CommandValidator {
+    /// Last known layouts of images.
+    layouts: HashMap<Image, Layout>,
+
+    /// Device that must own referenced images and buffers.
+    device: Option<WeakDevice>,
+}
+
+impl CommandValidator {
+    pub fn new() -> Self {
+        CommandValidator::default()
+    }
+
+    /// Checks that images and buffers referenced by commands
+    /// are owned by the device.
+    /// Resources of destroyed devices never are.
+    pub fn with_device(mut self, device: &Device) -> Self {
+        self.device = Some(device.downgrade());
+        self
+    }
+
+    /// Forgets tracked layouts.
+    /// Should be called when images are transitioned outside recorded
+    /// command buffers.
+    pub fn reset(&mut self) {
+        self.layouts.clear();
+    }
+
+    /// Validates frame and returns all errors found.
+    pub fn validate(&mut self, frame: &RecordedFrame) -> Vec<ValidationError> {
+        self.validate_submissions(frame.index, 0, &frame.submissions)
+    }
+
+    /// Validates part of the frame, starting from submission `first`.
+    /// Layouts are tracked across calls, so submissions
+    /// must be validated in order and only once.
+    pub fn validate_submissions(
+        &mut self,
+        frame: u64,
+        first: usize,
+        submissions: &[RecordedSubmission],
+    ) -> Vec<ValidationError> {
+        let mut errors = Vec::new();
+
+        for (submission, recorded) in (first..).zip(submissions) {
+            let mut error = |command, kind| {
+                errors.push(ValidationError {
+                    frame,
+                    submission,
+                    command,
+                    kind,
+                })
+            };
+
+            // Binding state doesn't survive command buffer boundary.
+            let mut bound = Bound::default();
+            let mut render_pass: Option<&Framebuffer> = None;
+
+            for (index, command) in recorded.commands.iter().enumerate() {
+                let inside = render_pass.is_some();
+
+                if let Some(device) = &self.device {
+                    for resource in foreign_resources(command, device) {
+                        error(
+                            index,
+                            ValidationErrorKind::ForeignResource { resource },
+                        );
+                    }
+                }
+
+                match command {
+                    RecordedCommand::BeginRenderPass {
+                        pass,
+                        framebuffer,
+                        ..
+                    } => {
+                        if inside {
+                            error(index, ValidationErrorKind::NestedRenderPass);
+                        }
+
+                        for (attachment, view) in pass
+                            .info()
+                            .attachments
+                            .iter()
+                            .zip(&framebuffer.info().views)
+                        {
+                            if let Some(expected) = attachment.initial_layout {
+                                self.expect_layout(
+                                    &view.info().image,
+                                    expected,
+                                    |kind| error(index, kind),
+                                );
+                            }
+                        }
+
+                        render_pass = Some(framebuffer);
+                    }
+                    RecordedCommand::NextSubpass => {
+                        if !inside {
+                            error(
+                                index,
+                                ValidationErrorKind::OutsideRenderPass {
+                                    command: command.name(),
+                                },
+                            );
+                        }
+                    }
+                    RecordedCommand::EndRenderPass => {
+                        match render_pass.take() {
+                            Some(framebuffer) => {
+                                let pass = &framebuffer.info().render_pass;
+                                for (attachment, view) in pass
+                                    .info()
+                                    .attachments
+                                    .iter()
+                                    .zip(&framebuffer.info().views)
+                                {
+                                    self.layouts.insert(
+                                        view.info().image.clone(),
+                                        attachment.final_layout,
+                                    );
+                                }
+                            }
+                            None => error(
+                                index,
+                                ValidationErrorKind::EndWithoutBegin,
+                            ),
+                        }
+                    }
+                    RecordedCommand::BindGraphicsPipeline { .. } => {
+                        bound.graphics = true
+                    }
+                    RecordedCommand::BindComputePipeline { .. } => {
+                        bound.compute = true
+                    }
+                    RecordedCommand::BindRayTracingPipeline { .. } => {
+                        bound.ray_tracing = true
+                    }
+                    RecordedCommand::Draw { .. }
+                    | RecordedCommand::DrawIndexed { .. } => {
+                        if !inside {
+                            error(
+                                index,
+                                ValidationErrorKind::OutsideRenderPass {
+                                    command: command.name(),
+                                },
+                            );
+                        }
+                        if !bound.graphics {
+                            error(
+                                index,
+                                ValidationErrorKind::NoPipeline {
+                                    command: command.name(),
+                                },
+                            );
+                        }
+                    }
+                    RecordedCommand::Dispatch { .. }
+                    | RecordedCommand::TraceRays { .. } => {
+                        if inside {
+                            error(
+                                index,
+                                ValidationErrorKind::InsideRenderPass {
+                                    command: command.name(),
+                                },
+                            );
+                        }
+
+                        let bound = match command {
+                            RecordedCommand::Dispatch { .. } => bound.compute,
+                            _ => bound.ray_tracing,
+                        };
+                        if !bound {
+                            error(
+                                index,
+                                ValidationErrorKind::NoPipeline {
+                                    command: command.name(),
+                                },
+                            );
+                        }
+                    }
+                    RecordedCommand::BufferBarriers { .. } => {
+                        if inside {
+                            error(
+                                index,
+                                ValidationErrorKind::InsideRenderPass {
+                                    command: command.name(),
+                                },
+                            );
+                        }
+                    }
+                    RecordedCommand::PipelineBarrier { images, .. } => {
+                        if inside && !images.is_empty() {
+                            error(
+                                index,
+                                ValidationErrorKind::InsideRenderPass {
+                                    command: command.name(),
+                                },
+                            );
+                        }
+
+                        for barrier in images {
+                            if let Some(old) = barrier.old_layout {
+                                self.expect_layout(
+                                    &barrier.image,
+                                    old,
+                                    |kind| error(index, kind),
+                                );
+                            }
+                            self.layouts.insert(
+                                barrier.image.clone(),
+                                barrier.new_layout,
+                            );
+                        }
+                    }
+                    RecordedCommand::CopyImage {
+                        src_image,
+                        src_layout,
+                        dst_image,
+                        dst_layout,
+                        ..
+                    }
+                    | RecordedCommand::BlitImage {
+                        src_image,
+                        src_layout,
+                        dst_image,
+                        dst_layout,
+                        ..
+                    }
+                    | RecordedCommand::ResolveImage {
+                        src_image,
+                        src_layout,
+                        dst_image,
+                        dst_layout,
+                        ..
+                    } => {
+                        if inside {
+                            error(
+                                index,
+                                ValidationErrorKind::InsideRenderPass {
+                                    command: command.name(),
+                                },
+                            );
+                        }
+                        self.expect_layout(src_image, *src_layout, |kind| {
+                            error(index, kind)
+                        });
+                        self.expect_layout(dst_image, *dst_layout, |kind| {
+                            error(index, kind)
+                        });
+                    }
+                    RecordedCommand::CopyBufferImage {
+                        dst_image,
+                        dst_layout,
+                        ..
+                    } => {
+                        if inside {
+                            error(
+                                index,
+                                ValidationErrorKind::InsideRenderPass {
+                                    command: command.name(),
+                                },
+                            );
+                        }
+                        self.expect_layout(dst_image, *dst_layout, |kind| {
+                            error(index, kind)
+                        });
+                    }
+                    RecordedCommand::ClearColorImage {
+                        image, layout, ..
+                    }
+                    | RecordedCommand::ClearDepthStencilImage {
+                        image,
+                        layout,
+                        ..
+                    } => {
+                        if inside {
+                            error(
+                                index,
+                                ValidationErrorKind::InsideRenderPass {
+                                    command: command.name(),
+                                },
+                            );
+                        }
+                        self.expect_layout(image, *layout, |kind| {
+                            error(index, kind)
+                        });
+                    }
+                    RecordedCommand::CopyBuffer { .. }
+                    | RecordedCommand::FillBuffer { .. }
+                    | RecordedCommand::UpdateBuffer { .. }
+                    | RecordedCommand::BuildAccelerationStructure { .. }
+                    | RecordedCommand::ResetQueryPool { .. } => {
+                        if inside {
+                            error(
+                                index,
+                                ValidationErrorKind::InsideRenderPass {
+                                    command: command.name(),
+                                },
+                            );
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            if render_pass.is_some() {
+                error(
+                    recorded.commands.len(),
+                    ValidationErrorKind::UnterminatedRenderPass,
+                );
+            }
+        }
+
+        errors
+    }
+
+    /// Reports mismatch if image is known to be in another layout.
+    fn expect_layout(
+        &self,
+        image: &Image,
+        expected: Layout,
+        error: impl FnOnce(ValidationErrorKind),
+    ) {
+        match self.layouts.get(image) {
+            Some(&actual) if actual != expected => {
+                error(ValidationErrorKind::LayoutMismatch {
+                    image: format!("{:?}", image),
+                    expected,
+                    actual,
+                })
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Returns images and buffers referenced by command
+/// that are not owned by the device.
+fn foreign_resources(
+    command: &RecordedCommand,
+    device: &WeakDevice,
+) -> Vec<String> {
+    let mut images: Vec<&Image> = Vec::new();
+    let mut buffers: Vec<&Buffer> = Vec::new();
+
+    match command {
+        RecordedCommand::BeginRenderPass { framebuffer, .. } => {
+            images.extend(
+                framebuffer
+                    .info()
+                    .views
+                    .iter()
+                    .map(|view| &view.info().image),
+            );
+        }
+        RecordedCommand::UpdateBuffer { buffer, .. }
+        | RecordedCommand::FillBuffer { buffer, .. }
+        | RecordedCommand::BindIndexBuffer { buffer, .. } => {
+            buffers.push(buffer)
+        }
+        RecordedCommand::BindVertexBuffers { buffers: bound, .. } => {
+            buffers.extend(bound.iter().map(|(buffer, _)| buffer))
+        }
+        RecordedCommand::CopyBuffer {
+            src_buffer,
+            dst_buffer,
+            ..
+        } => buffers.extend(&[src_buffer, dst_buffer]),
+        RecordedCommand::CopyImage {
+            src_image,
+            dst_image,
+            ..
+        }
+        | RecordedCommand::BlitImage {
+            src_image,
+            dst_image,
+            ..
+        }
+        | RecordedCommand::ResolveImage {
+            src_image,
+            dst_image,
+            ..
+        } => images.extend(&[src_image, dst_image]),
+        RecordedCommand::CopyBufferImage {
+            src_buffer,
+            dst_image,
+            ..
+        } => {
+            buffers.push(src_buffer);
+            images.push(dst_image);
+        }
+        RecordedCommand::ClearColorImage { image, .. }
+        | RecordedCommand::ClearDepthStencilImage { image, .. } => {
+            images.push(image)
+        }
+        RecordedCommand::PipelineBarrier {
+            images: barriers, ..
+        } => images.extend(barriers.iter().map(|barrier| &barrier.image)),
+        RecordedCommand::BufferBarriers {
+            buffers: barriers, ..
+        } => buffers.extend(barriers.iter().map(|barrier| &barrier.buffer)),
+        _ => {}
+    }
+
+    images
+        .into_iter()
+        .filter(|image| !image.is_owned_by(device))
+        .map(|image| format!("{:?}", image))
+        .chain(
+            buffers
+                .into_iter()
+                .filter(|buffer| !buffer.is_owned_by(device))
+                .map(|buffer| format!("{:?}", buffer)),
+        )
+        .collect()
+}
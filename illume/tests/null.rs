@@ -0,0 +1,257 @@
+//! Tests that run on headless `null` backend.
+//!
+//! Run with `cargo test -p illume --features null`.
+
+#![cfg(feature = "null")]
+
+use illume::*;
+
+fn device() -> (Device, Queue) {
+    Graphics::get_or_init()
+        .unwrap()
+        .devices()
+        .unwrap()
+        .remove(0)
+        .create_device(&[], SingleQueueQuery::GENERAL)
+        .unwrap()
+}
+
+fn buffer_info(size: u64) -> BufferInfo {
+    BufferInfoBuilder::new(size)
+        .usage(BufferUsage::TRANSFER_SRC | BufferUsage::TRANSFER_DST)
+        .build()
+        .unwrap()
+}
+
+fn image(device: &Device) -> Image {
+    device
+        .create_image(ImageInfo {
+            extent: Extent2d {
+                width: 64,
+                height: 64,
+            }
+            .into(),
+            format: Format::RGBA8Unorm,
+            levels: 1,
+            layers: 1,
+            samples: Samples1,
+            usage: ImageUsage::TRANSFER_DST | ImageUsage::SAMPLED,
+        })
+        .unwrap()
+}
+
+#[test]
+fn buffer_round_trip() {
+    let (device, _queue) = device();
+
+    let mut buffer = device
+        .create_mappable_buffer(buffer_info(16), MemoryUsage::UPLOAD)
+        .unwrap();
+
+    device.write_buffer(&mut buffer, 4, &[1u32, 2, 3]).unwrap();
+
+    let mut data = [0u32; 4];
+    device.read_buffer(&mut buffer, 0, &mut data).unwrap();
+    assert_eq!(data, [0, 1, 2, 3]);
+}
+
+#[test]
+fn buffer_map_twice() {
+    let (device, _queue) = device();
+
+    let mut buffer = device
+        .create_mappable_buffer(buffer_info(16), MemoryUsage::UPLOAD)
+        .unwrap();
+
+    device.map_memory(&mut buffer, 0, 16).unwrap();
+
+    assert!(matches!(
+        device.map_memory(&mut buffer, 0, 16),
+        Err(MapError::AlreadyMapped)
+    ));
+
+    assert!(matches!(
+        device.write_buffer(&mut buffer, 0, &[0u32]),
+        Err(MapError::AlreadyMapped)
+    ));
+
+    device.unmap_memory(&mut buffer);
+    device.write_buffer(&mut buffer, 0, &[0u32]).unwrap();
+}
+
+#[test]
+fn fence_signalled_on_submit() {
+    let (device, mut queue) = device();
+
+    let fence = device.create_fence().unwrap();
+    assert!(!device.is_fence_signalled(&fence).unwrap());
+
+    let encoder = queue.create_encoder().unwrap();
+    queue
+        .submit_no_semaphores(encoder.finish().unwrap(), Some(&fence))
+        .unwrap();
+
+    assert!(device.is_fence_signalled(&fence).unwrap());
+    device.reset_fences(&[&fence]).unwrap();
+    assert!(!device.is_fence_signalled(&fence).unwrap());
+}
+
+#[test]
+fn valid_submission() {
+    let (device, mut queue) = device();
+
+    let image = image(&device);
+    let range = ImageSubresourceRange::whole(image.info());
+    let barriers = [ImageMemoryBarrier {
+        image: &image,
+        old_layout: None,
+        new_layout: Layout::TransferDstOptimal,
+        family_transfer: None,
+        subresource: range,
+    }];
+    let ranges = [range];
+
+    let mut encoder = queue.create_encoder().unwrap();
+    encoder.image_barriers(
+        PipelineStageFlags::TOP_OF_PIPE,
+        PipelineStageFlags::TRANSFER,
+        &barriers,
+    );
+    encoder.clear_color_image(
+        &image,
+        Layout::TransferDstOptimal,
+        [0.0; 4],
+        &ranges,
+    );
+
+    queue
+        .submit_no_semaphores(encoder.finish().unwrap(), None)
+        .unwrap();
+
+    assert!(queue.take_validation_errors().is_empty());
+}
+
+#[test]
+fn layout_mismatch() {
+    let (device, mut queue) = device();
+
+    let image = image(&device);
+    let range = ImageSubresourceRange::whole(image.info());
+    let barriers = [ImageMemoryBarrier {
+        image: &image,
+        old_layout: None,
+        new_layout: Layout::TransferDstOptimal,
+        family_transfer: None,
+        subresource: range,
+    }];
+    let ranges = [range];
+
+    let mut encoder = queue.create_encoder().unwrap();
+    encoder.image_barriers(
+        PipelineStageFlags::TOP_OF_PIPE,
+        PipelineStageFlags::TRANSFER,
+        &barriers,
+    );
+    encoder.clear_color_image(&image, Layout::General, [0.0; 4], &ranges);
+
+    queue
+        .submit_no_semaphores(encoder.finish().unwrap(), None)
+        .unwrap();
+
+    let errors = queue.take_validation_errors();
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(
+        errors[0].kind,
+        ValidationErrorKind::LayoutMismatch {
+            expected: Layout::General,
+            actual: Layout::TransferDstOptimal,
+            ..
+        }
+    ));
+
+    assert!(queue.take_validation_errors().is_empty());
+}
+
+#[test]
+fn foreign_resource() {
+    let (_device, mut queue) = device();
+    let (other, _other_queue) = device();
+
+    let buffer = other.create_buffer(buffer_info(16)).unwrap();
+
+    let mut encoder = queue.create_encoder().unwrap();
+    encoder.fill_buffer(&buffer, 0, 16, 0);
+
+    queue
+        .submit_no_semaphores(encoder.finish().unwrap(), None)
+        .unwrap();
+
+    let errors = queue.take_validation_errors();
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(
+        errors[0].kind,
+        ValidationErrorKind::ForeignResource { .. }
+    ));
+}
+
+#[test]
+fn shader_module_magic() {
+    let (device, _queue) = device();
+
+    let mut code = 0x07230203u32.to_ne_bytes().to_vec();
+    code.extend_from_slice(&[0; 16]);
+    device
+        .create_shader_module(ShaderModuleInfo::spirv(code))
+        .unwrap();
+
+    assert!(matches!(
+        device.create_shader_module(ShaderModuleInfo::spirv(vec![0; 20])),
+        Err(CreateShaderModuleError::InvalidShader { .. })
+    ));
+
+    assert!(matches!(
+        device.create_shader_module(ShaderModuleInfo::glsl(vec![0; 20])),
+        Err(CreateShaderModuleError::UnsupportedShaderLanguage { .. })
+    ));
+}
+
+#[test]
+fn render_pass_resolve() {
+    let (device, _queue) = device();
+
+    let attachment = |samples| AttachmentInfo {
+        format: Format::RGBA8Unorm,
+        samples,
+        load_op: AttachmentLoadOp::DontCare,
+        store_op: AttachmentStoreOp::Store,
+        initial_layout: None,
+        final_layout: Layout::General,
+    };
+
+    let info = |samples, resolve| RenderPassInfo {
+        attachments: smallvec::smallvec![
+            attachment(samples),
+            attachment(resolve)
+        ],
+        subpasses: smallvec::smallvec![Subpass {
+            colors: smallvec::smallvec![0],
+            depth: None,
+            resolves: smallvec::smallvec![1],
+        }],
+        dependencies: smallvec::smallvec![],
+    };
+
+    let render_pass =
+        device.create_render_pass(info(Samples4, Samples1)).unwrap();
+    assert_eq!(render_pass.info().subpass_samples(0), Samples4);
+
+    assert!(matches!(
+        device.create_render_pass(info(Samples1, Samples1)),
+        Err(CreateRenderPassError::ResolveAttachmentSamplesMismatch { .. })
+    ));
+
+    assert!(matches!(
+        device.create_render_pass(info(Samples4, Samples4)),
+        Err(CreateRenderPassError::ResolveAttachmentSamplesMismatch { .. })
+    ));
+}
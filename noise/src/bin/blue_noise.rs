@@ -0,0 +1,77 @@
+//! Offline tool that produces blue noise files loaded by the engine.
+//!
+//! Usage:
+//!
+//! `blue-noise generate <output> [size] [layers] [seed] [algorithm]`
+//! generates spatiotemporal blue noise.
+//! Algorithm is either `spectral` (default) or `void-and-cluster`.
+//!
+//! `blue-noise pack <dir> <layers> <output>`
+//! packs sequence of 16 bit RGBA `HDR_RGBA_XXXX.png` images.
+
+use {
+    noise::{
+        generate_spatiotemporal_blue_noise, pack_blue_noise, BlueNoise,
+        BlueNoiseAlgorithm,
+    },
+    std::{
+        error::Error,
+        path::{Path, PathBuf},
+    },
+};
+
+const USAGE: &str = "Usage:
+    blue-noise generate <output> [size] [layers] [seed] [algorithm]
+    blue-noise pack <dir> <layers> <output>";
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match args.first().map(String::as_str) {
+        Some("generate") if args.len() >= 2 => {
+            let size = parse_arg(&args, 2, 256)?;
+            let layers = parse_arg(&args, 3, 128)?;
+            let seed = parse_arg(&args, 4, 0)?;
+            let algorithm = match args.get(5).map(String::as_str) {
+                None | Some("spectral") => BlueNoiseAlgorithm::Spectral,
+                Some("void-and-cluster") => BlueNoiseAlgorithm::VoidAndCluster,
+                Some(other) => {
+                    return Err(format!("Unknown algorithm `{}`", other).into())
+                }
+            };
+            let noise = generate_spatiotemporal_blue_noise(
+                size as u32,
+                size as u32,
+                layers as u32,
+                algorithm,
+                seed,
+            );
+            write(&args[1], &noise)
+        }
+        Some("pack") if args.len() == 4 => {
+            let layers = parse_arg(&args, 2, 0)?;
+            let noise = pack_blue_noise(Path::new(&args[1]), layers as u32)?;
+            write(&args[3], &noise)
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn parse_arg(
+    args: &[String],
+    index: usize,
+    default: u64,
+) -> Result<u64, Box<dyn Error>> {
+    match args.get(index) {
+        Some(arg) => Ok(arg.parse()?),
+        None => Ok(default),
+    }
+}
+
+fn write(path: &str, noise: &BlueNoise) -> Result<(), Box<dyn Error>> {
+    std::fs::write(PathBuf::from(path), noise.encode())?;
+    Ok(())
+}
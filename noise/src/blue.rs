@@ -0,0 +1,201 @@
+//! Blue noise generation.
+//!
+//! Two algorithms are available.
+//! Spectral shaping repeatedly filters white noise with high-pass filter
+//! in frequency domain and remaps it to uniform distribution.
+//! Void-and-cluster ranks pixels one by one producing dither mask
+//! where every threshold is well distributed.
+
+use {
+    crate::{
+        canvas::Canvas,
+        fft::{Complex, Fft2d},
+        file::BlueNoise,
+        texture::{NoiseTexture, Tiling},
+        void_cluster::generate_void_and_cluster,
+    },
+    rand::{rngs::StdRng, Rng, SeedableRng as _},
+};
+
+/// Number of filter-remap iterations.
+const ITERATIONS: usize = 4;
+
+/// Radius in normalized frequency space below which frequencies
+/// are attenuated.
+const CUTOFF: f32 = 0.5;
+
+/// Fractional part of golden ratio.
+/// Offsetting values by it in each layer produces
+/// low-discrepancy sequence in time.
+const GOLDEN_RATIO_FRACT: f32 = 0.618_034;
+
+/// Algorithm used to generate blue noise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BlueNoiseAlgorithm {
+    /// Fast spectral shaping of white noise.
+    /// Good for sampling, but distribution of low and high values
+    /// is poor for dithering.
+    Spectral,
+
+    /// Slow void-and-cluster method.
+    /// Produces rank-ordered mask suitable for dithering.
+    VoidAndCluster,
+}
+
+/// Generates blue noise mask of specified size.
+///
+/// Any size is supported. With `BlueNoiseAlgorithm::Spectral`
+/// sizes with small prime factors are processed faster.
+///
+/// With `Tiling::Periodic` the mask tiles seamlessly.
+/// With `Tiling::Clamped` values near opposite edges are not correlated.
+///
+/// # Panics
+///
+/// This function panics if `width` or `height` is zero.
+pub fn generate_blue_noise(
+    width: usize,
+    height: usize,
+    tiling: Tiling,
+    algorithm: BlueNoiseAlgorithm,
+    rng: &mut impl Rng,
+) -> NoiseTexture {
+    assert!(width > 0 && height > 0, "Blue noise size must not be zero");
+
+    let values = match (algorithm, tiling) {
+        (BlueNoiseAlgorithm::VoidAndCluster, _) => {
+            generate_void_and_cluster(width, height, tiling, rng)
+        }
+        (BlueNoiseAlgorithm::Spectral, Tiling::Periodic) => {
+            generate_periodic(width, height, rng)
+        }
+        (BlueNoiseAlgorithm::Spectral, Tiling::Clamped) => {
+            // Spectral shaping operates on periodic signal,
+            // so non-periodic mask is cut out of a larger periodic one.
+            let padded = generate_periodic(
+                width + padding(width),
+                height + padding(height),
+                rng,
+            );
+
+            let mut values =
+                Canvas::from_fn(width, height, |x, y| padded[(x, y)]);
+
+            // Cropped region is no longer uniformly distributed.
+            rank_normalize(values.data_mut());
+            values
+        }
+    };
+
+    NoiseTexture::new(values, tiling)
+}
+
+/// Generates periodic blue noise with values in `[0, 1)`.
+fn generate_periodic(
+    width: usize,
+    height: usize,
+    rng: &mut impl Rng,
+) -> Canvas<f32> {
+    let mut fft = Fft2d::new(width, height);
+
+    let mut canvas =
+        Canvas::from_fn(width, height, |_, _| Complex::new(rng.gen(), 0.0));
+
+    for _ in 0..ITERATIONS {
+        fft.forward(&mut canvas);
+
+        for y in 0..height {
+            for x in 0..width {
+                canvas[(x, y)] *= high_pass(x, y, width, height);
+            }
+        }
+
+        fft.inverse(&mut canvas);
+
+        let mut values = canvas.map(|value| value.re);
+        rank_normalize(values.data_mut());
+        canvas = values.map(|&value| Complex::new(value, 0.0));
+    }
+
+    canvas.map(|value| value.re)
+}
+
+/// Returns number of extra texels generated along dimension
+/// for non-periodic noise.
+fn padding(size: usize) -> usize {
+    (size / 4).max(4)
+}
+
+/// Generates sequence of RGBA blue noise layers.
+///
+/// Each channel is independent blue noise mask in space.
+/// Consecutive layers are offset with golden ratio so that
+/// values of each texel are well distributed in time as well.
+pub fn generate_spatiotemporal_blue_noise(
+    width: u32,
+    height: u32,
+    layers: u32,
+    algorithm: BlueNoiseAlgorithm,
+    seed: u64,
+) -> BlueNoise {
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let masks: Vec<_> = (0..4)
+        .map(|_| {
+            generate_blue_noise(
+                width as usize,
+                height as usize,
+                Tiling::Periodic,
+                algorithm,
+                &mut rng,
+            )
+        })
+        .collect();
+
+    let mut texels =
+        Vec::with_capacity(width as usize * height as usize * layers as usize);
+
+    for layer in 0..layers {
+        let offset = (layer as f32 * GOLDEN_RATIO_FRACT).fract();
+
+        for y in 0..height as usize {
+            for x in 0..width as usize {
+                let mut texel = [0.0; 4];
+                for (value, mask) in texel.iter_mut().zip(&masks) {
+                    *value = (mask.get(x, y) + offset).fract();
+                }
+                texels.push(texel);
+            }
+        }
+    }
+
+    BlueNoise::new(width, height, layers, texels)
+        .expect("Generated texels must match dimensions")
+}
+
+/// Returns filter gain for frequency at specified position
+/// of the unshifted spectrum.
+fn high_pass(x: usize, y: usize, width: usize, height: usize) -> f32 {
+    let fx = x.min(width - x) as f32 / width as f32;
+    let fy = y.min(height - y) as f32 / height as f32;
+
+    // Normalize so that highest diagonal frequency has radius 1.
+    let r = (fx * fx + fy * fy).sqrt() * std::f32::consts::SQRT_2;
+    (r / CUTOFF).min(1.0)
+}
+
+/// Replaces values with their ranks mapped into `[0, 1)` range.
+fn rank_normalize(data: &mut [f32]) {
+    let count = data.len();
+
+    let mut order: Vec<usize> = (0..count).collect();
+    order.sort_unstable_by(|&a, &b| {
+        data[a]
+            .partial_cmp(&data[b])
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    for (rank, index) in order.into_iter().enumerate() {
+        data[index] = rank as f32 / count as f32;
+    }
+}
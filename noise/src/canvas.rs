@@ -0,0 +1,215 @@
+use {
+    rayon::prelude::*,
+    std::ops::{Index, IndexMut},
+};
+
+/// Two-dimensional grid of values.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Canvas<T> {
+    width: usize,
+    height: usize,
+    data: Vec<T>,
+}
+
+impl<T> Canvas<T> {
+    /// Creates canvas filled with default values.
+    pub fn new(width: usize, height: usize) -> Self
+    where
+        T: Default + Clone,
+    {
+        Canvas {
+            width,
+            height,
+            data: vec![T::default(); width * height],
+        }
+    }
+
+    /// Creates canvas with values returned by the function
+    /// for each `(x, y)` coordinate.
+    pub fn from_fn(
+        width: usize,
+        height: usize,
+        mut f: impl FnMut(usize, usize) -> T,
+    ) -> Self {
+        let mut data = Vec::with_capacity(width * height);
+
+        for y in 0..height {
+            for x in 0..width {
+                data.push(f(x, y));
+            }
+        }
+
+        Canvas {
+            width,
+            height,
+            data,
+        }
+    }
+
+    /// Creates canvas with values returned by the function
+    /// for each `(x, y)` coordinate.
+    /// Values are computed in parallel.
+    pub fn par_from_fn(
+        width: usize,
+        height: usize,
+        f: impl Fn(usize, usize) -> T + Sync,
+    ) -> Self
+    where
+        T: Send,
+    {
+        let data = (0..width * height)
+            .into_par_iter()
+            .map(|index| f(index % width, index / width))
+            .collect();
+
+        Canvas {
+            width,
+            height,
+            data,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns values in row-major order.
+    pub fn data(&self) -> &[T] {
+        &self.data
+    }
+
+    /// Returns values in row-major order.
+    pub fn data_mut(&mut self) -> &mut [T] {
+        &mut self.data
+    }
+
+    pub fn into_data(self) -> Vec<T> {
+        self.data
+    }
+
+    pub fn row(&self, y: usize) -> &[T] {
+        &self.data[y * self.width..][..self.width]
+    }
+
+    pub fn row_mut(&mut self, y: usize) -> &mut [T] {
+        &mut self.data[y * self.width..][..self.width]
+    }
+
+    /// Returns value at coordinates wrapped around canvas edges.
+    pub fn get_wrapping(&self, x: isize, y: isize) -> &T {
+        let x = x.rem_euclid(self.width as isize) as usize;
+        let y = y.rem_euclid(self.height as isize) as usize;
+        &self[(x, y)]
+    }
+
+    /// Returns new canvas with function applied to each value.
+    pub fn map<U>(&self, f: impl FnMut(&T) -> U) -> Canvas<U> {
+        Canvas {
+            width: self.width,
+            height: self.height,
+            data: self.data.iter().map(f).collect(),
+        }
+    }
+}
+
+impl<T> Canvas<T>
+where
+    T: Clone,
+{
+    /// Slices row-major values of `width * height * depth` volume
+    /// into `depth` canvases, one per Z-layer.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if length of `data` is not a multiple
+    /// of `width * height`.
+    pub fn slice_layers(width: usize, height: usize, data: &[T]) -> Vec<Self> {
+        let layer = width * height;
+        assert!(layer > 0, "Layer must not be empty");
+
+        let chunks = data.chunks_exact(layer);
+        assert!(
+            chunks.remainder().is_empty(),
+            "Data length must be multiple of layer size"
+        );
+
+        chunks
+            .map(|chunk| Canvas {
+                width,
+                height,
+                data: chunk.to_vec(),
+            })
+            .collect()
+    }
+}
+
+impl Canvas<f32> {
+    /// Encodes values as 8 bit unsigned normalized texels.
+    /// Values in `[0, 1)` are mapped uniformly onto all levels.
+    pub fn to_r8(&self) -> Vec<u8> {
+        self.data.iter().map(|&v| quantize(v, 8) as u8).collect()
+    }
+
+    /// Encodes values as 16 bit unsigned normalized texels
+    /// in little-endian byte order.
+    /// Values in `[0, 1)` are mapped uniformly onto all levels.
+    pub fn to_r16(&self) -> Vec<u8> {
+        self.data
+            .iter()
+            .flat_map(|&v| (quantize(v, 16) as u16).to_le_bytes())
+            .collect()
+    }
+
+    /// Encodes values as 32 bit float texels in little-endian byte order.
+    pub fn to_r32f(&self) -> Vec<u8> {
+        self.data.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+}
+
+impl Canvas<[f32; 4]> {
+    /// Encodes values as 8 bit per channel unsigned normalized texels.
+    /// Values in `[0, 1)` are mapped uniformly onto all levels.
+    pub fn to_rgba8(&self) -> Vec<u8> {
+        self.data
+            .iter()
+            .flatten()
+            .map(|&v| quantize(v, 8) as u8)
+            .collect()
+    }
+
+    /// Encodes values as 32 bit float per channel texels
+    /// in little-endian byte order.
+    pub fn to_rgba32f(&self) -> Vec<u8> {
+        self.data
+            .iter()
+            .flatten()
+            .flat_map(|v| v.to_le_bytes())
+            .collect()
+    }
+}
+
+/// Maps value from `[0, 1]` onto integer with specified number of bits.
+fn quantize(value: f32, bits: u32) -> u32 {
+    let levels = (1u32 << bits) as f32;
+    (value.clamp(0.0, 1.0) * levels).min(levels - 1.0) as u32
+}
+
+impl<T> Index<(usize, usize)> for Canvas<T> {
+    type Output = T;
+
+    fn index(&self, (x, y): (usize, usize)) -> &T {
+        assert!(x < self.width && y < self.height);
+        &self.data[x + y * self.width]
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for Canvas<T> {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut T {
+        assert!(x < self.width && y < self.height);
+        &mut self.data[x + y * self.width]
+    }
+}
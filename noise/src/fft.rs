@@ -0,0 +1,88 @@
+//! Two-dimensional FFT over canvases.
+
+use {
+    crate::canvas::Canvas,
+    rustfft::{Fft, FftPlanner},
+    std::sync::Arc,
+};
+
+pub use num_complex::Complex;
+
+/// Planned forward and inverse 2D FFT of fixed size.
+///
+/// Inverse transform is not normalized,
+/// so values are scaled by `width * height` after roundtrip.
+pub struct Fft2d {
+    forward_row: Arc<dyn Fft<f32>>,
+    forward_column: Arc<dyn Fft<f32>>,
+    inverse_row: Arc<dyn Fft<f32>>,
+    inverse_column: Arc<dyn Fft<f32>>,
+    scratch: Vec<Complex<f32>>,
+}
+
+impl Fft2d {
+    pub fn new(width: usize, height: usize) -> Self {
+        let mut planner = FftPlanner::new();
+        Fft2d {
+            forward_row: planner.plan_fft_forward(width),
+            forward_column: planner.plan_fft_forward(height),
+            inverse_row: planner.plan_fft_inverse(width),
+            inverse_column: planner.plan_fft_inverse(height),
+            scratch: vec![Complex::new(0.0, 0.0); height],
+        }
+    }
+
+    /// Transforms canvas into frequency domain in place.
+    ///
+    /// # Panics
+    ///
+    /// Panics if canvas size differs from planned one.
+    pub fn forward(&mut self, canvas: &mut Canvas<Complex<f32>>) {
+        fft2d(
+            canvas,
+            &*self.forward_row,
+            &*self.forward_column,
+            &mut self.scratch,
+        );
+    }
+
+    /// Transforms canvas from frequency domain in place.
+    ///
+    /// # Panics
+    ///
+    /// Panics if canvas size differs from planned one.
+    pub fn inverse(&mut self, canvas: &mut Canvas<Complex<f32>>) {
+        fft2d(
+            canvas,
+            &*self.inverse_row,
+            &*self.inverse_column,
+            &mut self.scratch,
+        );
+    }
+}
+
+fn fft2d(
+    canvas: &mut Canvas<Complex<f32>>,
+    row: &dyn Fft<f32>,
+    column: &dyn Fft<f32>,
+    scratch: &mut [Complex<f32>],
+) {
+    assert_eq!(canvas.width(), row.len());
+    assert_eq!(canvas.height(), column.len());
+
+    for y in 0..canvas.height() {
+        row.process(canvas.row_mut(y));
+    }
+
+    for x in 0..canvas.width() {
+        for (y, value) in scratch.iter_mut().enumerate() {
+            *value = canvas[(x, y)];
+        }
+
+        column.process(scratch);
+
+        for (y, value) in scratch.iter().enumerate() {
+            canvas[(x, y)] = *value;
+        }
+    }
+}
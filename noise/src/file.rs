@@ -0,0 +1,228 @@
+//! Versioned file format for pregenerated blue noise.
+//!
+//! Layout, all values are little-endian:
+//!
+//! | Offset | Size | Content                      |
+//! |--------|------|------------------------------|
+//! | 0      | 4    | Magic bytes `WBNZ`           |
+//! | 4      | 4    | Format version               |
+//! | 8      | 4    | Width                        |
+//! | 12     | 4    | Height                       |
+//! | 16     | 4    | Number of layers             |
+//! | 20     | 4    | Number of channels, always 4 |
+//! | 24     | ..   | Texels as `f32` values       |
+
+use {crate::canvas::Canvas, std::convert::TryInto as _};
+
+/// Magic bytes at the beginning of the blue noise file.
+pub const MAGIC: [u8; 4] = *b"WBNZ";
+
+/// Current version of the file format.
+pub const VERSION: u32 = 1;
+
+const HEADER_SIZE: usize = 24;
+const CHANNELS: u32 = 4;
+
+/// Error that may occur when blue noise is decoded or constructed.
+#[derive(Debug, thiserror::Error)]
+pub enum BlueNoiseError {
+    #[error("Blue noise file is too short")]
+    UnexpectedEof,
+
+    #[error("Blue noise file has invalid magic bytes")]
+    BadMagic,
+
+    #[error("Blue noise file version {version} is not supported. Expected version {}", VERSION)]
+    UnsupportedVersion { version: u32 },
+
+    #[error("Blue noise with {channels} channels is not supported")]
+    UnsupportedChannels { channels: u32 },
+
+    #[error("Blue noise dimensions {width}x{height}x{layers} are invalid")]
+    BadDimensions {
+        width: u32,
+        height: u32,
+        layers: u32,
+    },
+
+    #[error("Blue noise has {actual} texels but {expected} are expected")]
+    SizeMismatch { expected: usize, actual: usize },
+
+    #[error("Blue noise value at texel {texel} is out of [0, 1] range")]
+    ValueOutOfRange { texel: usize },
+}
+
+/// Sequence of RGBA blue noise layers with values in `[0, 1]` range.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlueNoise {
+    width: u32,
+    height: u32,
+    layers: u32,
+    texels: Vec<[f32; 4]>,
+}
+
+impl BlueNoise {
+    /// Wraps texels into blue noise.
+    /// Texels are ordered by layer, then row, then column.
+    pub fn new(
+        width: u32,
+        height: u32,
+        layers: u32,
+        texels: Vec<[f32; 4]>,
+    ) -> Result<Self, BlueNoiseError> {
+        let expected = texel_count(width, height, layers)?;
+
+        if texels.len() != expected {
+            return Err(BlueNoiseError::SizeMismatch {
+                expected,
+                actual: texels.len(),
+            });
+        }
+
+        if let Some(texel) = texels
+            .iter()
+            .position(|t| t.iter().any(|v| !(0.0..=1.0).contains(v)))
+        {
+            return Err(BlueNoiseError::ValueOutOfRange { texel });
+        }
+
+        Ok(BlueNoise {
+            width,
+            height,
+            layers,
+            texels,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn layers(&self) -> u32 {
+        self.layers
+    }
+
+    pub fn texels(&self) -> &[[f32; 4]] {
+        &self.texels
+    }
+
+    /// Returns texel at specified coordinates.
+    pub fn texel(&self, x: u32, y: u32, layer: u32) -> [f32; 4] {
+        assert!(x < self.width && y < self.height && layer < self.layers);
+
+        let index = x as usize
+            + y as usize * self.width as usize
+            + layer as usize * self.width as usize * self.height as usize;
+
+        self.texels[index]
+    }
+
+    /// Returns canvas for each layer of the noise.
+    pub fn to_canvases(&self) -> Vec<Canvas<[f32; 4]>> {
+        Canvas::slice_layers(
+            self.width as usize,
+            self.height as usize,
+            &self.texels,
+        )
+    }
+
+    /// Encodes blue noise into file format.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes =
+            Vec::with_capacity(HEADER_SIZE + self.texels.len() * 16);
+
+        bytes.extend_from_slice(&MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&self.width.to_le_bytes());
+        bytes.extend_from_slice(&self.height.to_le_bytes());
+        bytes.extend_from_slice(&self.layers.to_le_bytes());
+        bytes.extend_from_slice(&CHANNELS.to_le_bytes());
+
+        for layer in self.to_canvases() {
+            bytes.extend_from_slice(&layer.to_rgba32f());
+        }
+
+        bytes
+    }
+
+    /// Decodes blue noise from file format.
+    /// Header and all values are validated.
+    pub fn decode(bytes: &[u8]) -> Result<Self, BlueNoiseError> {
+        if bytes.len() < HEADER_SIZE {
+            return Err(BlueNoiseError::UnexpectedEof);
+        }
+
+        let (header, data) = bytes.split_at(HEADER_SIZE);
+
+        if header[0..4] != MAGIC {
+            return Err(BlueNoiseError::BadMagic);
+        }
+
+        let read_u32 = |offset: usize| {
+            u32::from_le_bytes(header[offset..][..4].try_into().unwrap())
+        };
+
+        let version = read_u32(4);
+        if version != VERSION {
+            return Err(BlueNoiseError::UnsupportedVersion { version });
+        }
+
+        let width = read_u32(8);
+        let height = read_u32(12);
+        let layers = read_u32(16);
+        let channels = read_u32(20);
+
+        if channels != CHANNELS {
+            return Err(BlueNoiseError::UnsupportedChannels { channels });
+        }
+
+        let expected = texel_count(width, height, layers)?;
+
+        if data.len() != expected * 16 {
+            return Err(BlueNoiseError::SizeMismatch {
+                expected,
+                actual: data.len() / 16,
+            });
+        }
+
+        let texels = data
+            .chunks_exact(16)
+            .map(|texel| {
+                let mut values = [0.0; 4];
+                for (value, bytes) in
+                    values.iter_mut().zip(texel.chunks_exact(4))
+                {
+                    *value = f32::from_le_bytes(bytes.try_into().unwrap());
+                }
+                values
+            })
+            .collect();
+
+        BlueNoise::new(width, height, layers, texels)
+    }
+}
+
+fn texel_count(
+    width: u32,
+    height: u32,
+    layers: u32,
+) -> Result<usize, BlueNoiseError> {
+    let bad = || BlueNoiseError::BadDimensions {
+        width,
+        height,
+        layers,
+    };
+
+    if width == 0 || height == 0 || layers == 0 {
+        return Err(bad());
+    }
+
+    (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|count| count.checked_mul(layers as usize))
+        .ok_or_else(bad)
+}
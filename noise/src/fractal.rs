@@ -0,0 +1,173 @@
+//! Combinators that build complex noise from simple noise functions.
+
+use crate::gradient::{Noise2, Noise3};
+
+/// Fractal Brownian motion.
+/// Sums octaves of source noise with increasing frequency
+/// and decreasing amplitude.
+#[derive(Clone, Debug)]
+pub struct Fbm<N> {
+    pub source: N,
+    pub octaves: u32,
+    pub lacunarity: f32,
+    pub gain: f32,
+}
+
+impl<N> Fbm<N> {
+    /// Returns fBm with 6 octaves, lacunarity of 2 and gain of 0.5.
+    pub fn new(source: N) -> Self {
+        Fbm {
+            source,
+            octaves: 6,
+            lacunarity: 2.0,
+            gain: 0.5,
+        }
+    }
+
+    fn sum(&self, mut sample: impl FnMut(f32) -> f32) -> f32 {
+        let mut sum = 0.0;
+        let mut amplitude = 1.0;
+        let mut norm = 0.0;
+        let mut frequency = 1.0;
+
+        for _ in 0..self.octaves {
+            sum += sample(frequency) * amplitude;
+            norm += amplitude;
+            amplitude *= self.gain;
+            frequency *= self.lacunarity;
+        }
+
+        if norm > 0.0 {
+            sum / norm
+        } else {
+            0.0
+        }
+    }
+}
+
+impl<N: Noise2> Noise2 for Fbm<N> {
+    fn noise2(&self, x: f32, y: f32) -> f32 {
+        self.sum(|f| self.source.noise2(x * f, y * f))
+    }
+}
+
+impl<N: Noise3> Noise3 for Fbm<N> {
+    fn noise3(&self, x: f32, y: f32, z: f32) -> f32 {
+        self.sum(|f| self.source.noise3(x * f, y * f, z * f))
+    }
+}
+
+/// Ridged multifractal noise.
+/// Produces sharp ridges suitable for mountain ranges.
+#[derive(Clone, Debug)]
+pub struct Ridged<N> {
+    pub source: N,
+    pub octaves: u32,
+    pub lacunarity: f32,
+    pub gain: f32,
+}
+
+impl<N> Ridged<N> {
+    /// Returns ridged noise with 6 octaves, lacunarity of 2 and gain of 0.5.
+    pub fn new(source: N) -> Self {
+        Ridged {
+            source,
+            octaves: 6,
+            lacunarity: 2.0,
+            gain: 0.5,
+        }
+    }
+
+    fn sum(&self, mut sample: impl FnMut(f32) -> f32) -> f32 {
+        let mut sum = 0.0;
+        let mut amplitude = 1.0;
+        let mut norm = 0.0;
+        let mut frequency = 1.0;
+        let mut weight = 1.0;
+
+        for _ in 0..self.octaves {
+            let signal = 1.0 - sample(frequency).abs();
+            let signal = signal * signal * weight;
+            weight = signal.clamp(0.0, 1.0);
+
+            sum += signal * amplitude;
+            norm += amplitude;
+            amplitude *= self.gain;
+            frequency *= self.lacunarity;
+        }
+
+        // Remap from [0, 1] to [-1, 1] to match other noise functions.
+        if norm > 0.0 {
+            sum / norm * 2.0 - 1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+impl<N: Noise2> Noise2 for Ridged<N> {
+    fn noise2(&self, x: f32, y: f32) -> f32 {
+        self.sum(|f| self.source.noise2(x * f, y * f))
+    }
+}
+
+impl<N: Noise3> Noise3 for Ridged<N> {
+    fn noise3(&self, x: f32, y: f32, z: f32) -> f32 {
+        self.sum(|f| self.source.noise3(x * f, y * f, z * f))
+    }
+}
+
+/// Domain warping.
+/// Offsets sample coordinates of source noise by values of warp noise.
+#[derive(Clone, Debug)]
+pub struct DomainWarp<N, W> {
+    pub source: N,
+    pub warp: W,
+    pub strength: f32,
+}
+
+impl<N, W> DomainWarp<N, W> {
+    pub fn new(source: N, warp: W, strength: f32) -> Self {
+        DomainWarp {
+            source,
+            warp,
+            strength,
+        }
+    }
+}
+
+// Offsets decorrelate warp noise along different axes.
+const WARP_OFFSET_Y: f32 = 5.2;
+const WARP_OFFSET_Z: f32 = 1.3;
+
+impl<N: Noise2, W: Noise2> Noise2 for DomainWarp<N, W> {
+    fn noise2(&self, x: f32, y: f32) -> f32 {
+        let wx = self.warp.noise2(x, y);
+        let wy = self.warp.noise2(x + WARP_OFFSET_Y, y + WARP_OFFSET_Y);
+
+        self.source
+            .noise2(x + wx * self.strength, y + wy * self.strength)
+    }
+}
+
+impl<N: Noise3, W: Noise3> Noise3 for DomainWarp<N, W> {
+    fn noise3(&self, x: f32, y: f32, z: f32) -> f32 {
+        let wx = self.warp.noise3(x, y, z);
+        let wy = self.warp.noise3(
+            x + WARP_OFFSET_Y,
+            y + WARP_OFFSET_Y,
+            z + WARP_OFFSET_Y,
+        );
+        let wz = self.warp.noise3(
+            x + WARP_OFFSET_Z,
+            y + WARP_OFFSET_Z,
+            z + WARP_OFFSET_Z,
+        );
+
+        self.source.noise3(
+            x + wx * self.strength,
+            y + wy * self.strength,
+            z + wz * self.strength,
+        )
+    }
+}
@@ -0,0 +1,315 @@
+//! Coherent gradient noise.
+//!
+//! Both generators produce values roughly in `[-1, 1]` range
+//! and are deterministic for given seed.
+
+use {
+    crate::canvas::Canvas,
+    rand::{rngs::StdRng, seq::SliceRandom as _, SeedableRng as _},
+};
+
+/// Two-dimensional noise function.
+pub trait Noise2 {
+    fn noise2(&self, x: f32, y: f32) -> f32;
+}
+
+/// Three-dimensional noise function.
+pub trait Noise3 {
+    fn noise3(&self, x: f32, y: f32, z: f32) -> f32;
+}
+
+impl<N: Noise2 + ?Sized> Noise2 for &N {
+    fn noise2(&self, x: f32, y: f32) -> f32 {
+        (**self).noise2(x, y)
+    }
+}
+
+impl<N: Noise3 + ?Sized> Noise3 for &N {
+    fn noise3(&self, x: f32, y: f32, z: f32) -> f32 {
+        (**self).noise3(x, y, z)
+    }
+}
+
+/// Samples 2D noise into canvas in parallel.
+/// Texel `(x, y)` receives value at `(x * frequency, y * frequency)`.
+pub fn generate_noise2(
+    noise: &(impl Noise2 + Sync),
+    width: usize,
+    height: usize,
+    frequency: f32,
+) -> Canvas<f32> {
+    Canvas::par_from_fn(width, height, |x, y| {
+        noise.noise2(x as f32 * frequency, y as f32 * frequency)
+    })
+}
+
+/// Samples slice of 3D noise at specified `z` into canvas in parallel.
+/// Texel `(x, y)` receives value at `(x * frequency, y * frequency, z)`.
+pub fn generate_noise3_slice(
+    noise: &(impl Noise3 + Sync),
+    width: usize,
+    height: usize,
+    z: f32,
+    frequency: f32,
+) -> Canvas<f32> {
+    Canvas::par_from_fn(width, height, |x, y| {
+        noise.noise3(x as f32 * frequency, y as f32 * frequency, z)
+    })
+}
+
+/// Permutation table shared by gradient noise generators.
+#[derive(Clone)]
+struct Permutation {
+    table: [u8; 512],
+}
+
+impl Permutation {
+    fn new(seed: u64) -> Self {
+        let mut values: Vec<u8> = (0..=255).collect();
+        values.shuffle(&mut StdRng::seed_from_u64(seed));
+
+        let mut table = [0; 512];
+        for (index, value) in table.iter_mut().enumerate() {
+            *value = values[index & 255];
+        }
+
+        Permutation { table }
+    }
+
+    fn hash2(&self, x: i32, y: i32) -> u8 {
+        let x = (x & 255) as usize;
+        let y = (y & 255) as usize;
+        self.table[self.table[x] as usize + y]
+    }
+
+    fn hash3(&self, x: i32, y: i32, z: i32) -> u8 {
+        let x = (x & 255) as usize;
+        let y = (y & 255) as usize;
+        let z = (z & 255) as usize;
+        self.table[self.table[self.table[x] as usize + y] as usize + z]
+    }
+}
+
+impl std::fmt::Debug for Permutation {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.write_str("Permutation")
+    }
+}
+
+fn grad2(hash: u8, x: f32, y: f32) -> f32 {
+    match hash & 7 {
+        0 => x + y,
+        1 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x,
+        5 => -x,
+        6 => y,
+        _ => -y,
+    }
+}
+
+fn grad3(hash: u8, x: f32, y: f32, z: f32) -> f32 {
+    match hash & 15 {
+        0 | 12 => x + y,
+        1 | 14 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x + z,
+        5 => -x + z,
+        6 => x - z,
+        7 => -x - z,
+        8 => y + z,
+        9 | 13 => -y + z,
+        10 => y - z,
+        _ => -y - z,
+    }
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// Improved Perlin noise.
+#[derive(Clone, Debug)]
+pub struct Perlin {
+    perm: Permutation,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        Perlin {
+            perm: Permutation::new(seed),
+        }
+    }
+}
+
+impl Noise2 for Perlin {
+    fn noise2(&self, x: f32, y: f32) -> f32 {
+        let xf = x.floor();
+        let yf = y.floor();
+        let (xi, yi) = (xf as i32, yf as i32);
+        let (x, y) = (x - xf, y - yf);
+        let (u, v) = (fade(x), fade(y));
+
+        let p = &self.perm;
+        let n00 = grad2(p.hash2(xi, yi), x, y);
+        let n10 = grad2(p.hash2(xi + 1, yi), x - 1.0, y);
+        let n01 = grad2(p.hash2(xi, yi + 1), x, y - 1.0);
+        let n11 = grad2(p.hash2(xi + 1, yi + 1), x - 1.0, y - 1.0);
+
+        lerp(lerp(n00, n10, u), lerp(n01, n11, u), v)
+    }
+}
+
+impl Noise3 for Perlin {
+    fn noise3(&self, x: f32, y: f32, z: f32) -> f32 {
+        let xf = x.floor();
+        let yf = y.floor();
+        let zf = z.floor();
+        let (xi, yi, zi) = (xf as i32, yf as i32, zf as i32);
+        let (x, y, z) = (x - xf, y - yf, z - zf);
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+
+        let p = &self.perm;
+        let n000 = grad3(p.hash3(xi, yi, zi), x, y, z);
+        let n100 = grad3(p.hash3(xi + 1, yi, zi), x - 1.0, y, z);
+        let n010 = grad3(p.hash3(xi, yi + 1, zi), x, y - 1.0, z);
+        let n110 = grad3(p.hash3(xi + 1, yi + 1, zi), x - 1.0, y - 1.0, z);
+        let n001 = grad3(p.hash3(xi, yi, zi + 1), x, y, z - 1.0);
+        let n101 = grad3(p.hash3(xi + 1, yi, zi + 1), x - 1.0, y, z - 1.0);
+        let n011 = grad3(p.hash3(xi, yi + 1, zi + 1), x, y - 1.0, z - 1.0);
+        let n111 =
+            grad3(p.hash3(xi + 1, yi + 1, zi + 1), x - 1.0, y - 1.0, z - 1.0);
+
+        lerp(
+            lerp(lerp(n000, n100, u), lerp(n010, n110, u), v),
+            lerp(lerp(n001, n101, u), lerp(n011, n111, u), v),
+            w,
+        )
+    }
+}
+
+/// Simplex noise.
+/// Has fewer directional artifacts than Perlin noise
+/// and is cheaper in higher dimensions.
+#[derive(Clone, Debug)]
+pub struct Simplex {
+    perm: Permutation,
+}
+
+impl Simplex {
+    pub fn new(seed: u64) -> Self {
+        Simplex {
+            perm: Permutation::new(seed),
+        }
+    }
+}
+
+impl Noise2 for Simplex {
+    fn noise2(&self, x: f32, y: f32) -> f32 {
+        const F2: f32 = 0.366_025_42; // (sqrt(3) - 1) / 2
+        const G2: f32 = 0.211_324_87; // (3 - sqrt(3)) / 6
+
+        let s = (x + y) * F2;
+        let i = (x + s).floor();
+        let j = (y + s).floor();
+        let t = (i + j) * G2;
+        let x0 = x - (i - t);
+        let y0 = y - (j - t);
+
+        let (i1, j1) = if x0 > y0 { (1, 0) } else { (0, 1) };
+
+        let x1 = x0 - i1 as f32 + G2;
+        let y1 = y0 - j1 as f32 + G2;
+        let x2 = x0 - 1.0 + 2.0 * G2;
+        let y2 = y0 - 1.0 + 2.0 * G2;
+
+        let (i, j) = (i as i32, j as i32);
+        let p = &self.perm;
+
+        let corner = |hash: u8, x: f32, y: f32| {
+            let t = 0.5 - x * x - y * y;
+            if t < 0.0 {
+                0.0
+            } else {
+                let t = t * t;
+                t * t * grad2(hash, x, y)
+            }
+        };
+
+        let n0 = corner(p.hash2(i, j), x0, y0);
+        let n1 = corner(p.hash2(i + i1, j + j1), x1, y1);
+        let n2 = corner(p.hash2(i + 1, j + 1), x2, y2);
+
+        // Scale to fit into [-1, 1].
+        70.0 * (n0 + n1 + n2)
+    }
+}
+
+impl Noise3 for Simplex {
+    fn noise3(&self, x: f32, y: f32, z: f32) -> f32 {
+        const F3: f32 = 1.0 / 3.0;
+        const G3: f32 = 1.0 / 6.0;
+
+        let s = (x + y + z) * F3;
+        let i = (x + s).floor();
+        let j = (y + s).floor();
+        let k = (z + s).floor();
+        let t = (i + j + k) * G3;
+        let x0 = x - (i - t);
+        let y0 = y - (j - t);
+        let z0 = z - (k - t);
+
+        let ((i1, j1, k1), (i2, j2, k2)) = if x0 >= y0 {
+            if y0 >= z0 {
+                ((1, 0, 0), (1, 1, 0))
+            } else if x0 >= z0 {
+                ((1, 0, 0), (1, 0, 1))
+            } else {
+                ((0, 0, 1), (1, 0, 1))
+            }
+        } else if y0 < z0 {
+            ((0, 0, 1), (0, 1, 1))
+        } else if x0 < z0 {
+            ((0, 1, 0), (0, 1, 1))
+        } else {
+            ((0, 1, 0), (1, 1, 0))
+        };
+
+        let x1 = x0 - i1 as f32 + G3;
+        let y1 = y0 - j1 as f32 + G3;
+        let z1 = z0 - k1 as f32 + G3;
+        let x2 = x0 - i2 as f32 + 2.0 * G3;
+        let y2 = y0 - j2 as f32 + 2.0 * G3;
+        let z2 = z0 - k2 as f32 + 2.0 * G3;
+        let x3 = x0 - 1.0 + 3.0 * G3;
+        let y3 = y0 - 1.0 + 3.0 * G3;
+        let z3 = z0 - 1.0 + 3.0 * G3;
+
+        let (i, j, k) = (i as i32, j as i32, k as i32);
+        let p = &self.perm;
+
+        let corner = |hash: u8, x: f32, y: f32, z: f32| {
+            let t = 0.6 - x * x - y * y - z * z;
+            if t < 0.0 {
+                0.0
+            } else {
+                let t = t * t;
+                t * t * grad3(hash, x, y, z)
+            }
+        };
+
+        let n0 = corner(p.hash3(i, j, k), x0, y0, z0);
+        let n1 = corner(p.hash3(i + i1, j + j1, k + k1), x1, y1, z1);
+        let n2 = corner(p.hash3(i + i2, j + j2, k + k2), x2, y2, z2);
+        let n3 = corner(p.hash3(i + 1, j + 1, k + 1), x3, y3, z3);
+
+        // Scale to fit into [-1, 1].
+        32.0 * (n0 + n1 + n2 + n3)
+    }
+}
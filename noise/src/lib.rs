@@ -0,0 +1,32 @@
+//! Noise generators used to produce textures for the engine
+//! and procedural content such as terrain heightmaps.
+//!
+//! Generation may be slow, so results are expected to be produced offline
+//! with `blue-noise` tool and loaded at runtime from files.
+
+pub mod blue;
+pub mod canvas;
+pub mod fft;
+pub mod file;
+pub mod fractal;
+pub mod gradient;
+pub mod pack;
+pub mod texture;
+
+mod void_cluster;
+
+pub use self::{
+    blue::{
+        generate_blue_noise, generate_spatiotemporal_blue_noise,
+        BlueNoiseAlgorithm,
+    },
+    canvas::Canvas,
+    fft::{Complex, Fft2d},
+    file::{BlueNoise, BlueNoiseError},
+    fractal::{DomainWarp, Fbm, Ridged},
+    gradient::{
+        generate_noise2, generate_noise3_slice, Noise2, Noise3, Perlin, Simplex,
+    },
+    pack::{pack_blue_noise, PackBlueNoiseError},
+    texture::{NoiseTexture, Tiling},
+};
@@ -0,0 +1,74 @@
+//! Packing of blue noise distributed as image sequences.
+
+use {
+    crate::file::{BlueNoise, BlueNoiseError},
+    std::path::{Path, PathBuf},
+};
+
+/// Error that may occur when image sequence is packed into blue noise.
+#[derive(Debug, thiserror::Error)]
+pub enum PackBlueNoiseError {
+    #[error("Failed to read `{}`: `{source}`", path.display())]
+    Image {
+        path: PathBuf,
+        source: image::ImageError,
+    },
+
+    #[error("`{}` is not 16 bit RGBA image", path.display())]
+    NotRgba16 { path: PathBuf },
+
+    #[error("`{}` size differs from previous layers", path.display())]
+    SizeMismatch { path: PathBuf },
+
+    #[error(transparent)]
+    BlueNoise {
+        #[from]
+        source: BlueNoiseError,
+    },
+}
+
+/// Packs sequence of 16 bit RGBA `HDR_RGBA_XXXX.png` images
+/// from specified directory into blue noise.
+/// Each image becomes one layer.
+pub fn pack_blue_noise(
+    dir: &Path,
+    layers: u32,
+) -> Result<BlueNoise, PackBlueNoiseError> {
+    let mut width = 0;
+    let mut height = 0;
+    let mut texels = Vec::new();
+
+    for layer in 0..layers {
+        let path = dir.join(format!("HDR_RGBA_{:04}.png", layer));
+
+        let image = match image::open(&path) {
+            Ok(image) => image,
+            Err(source) => {
+                return Err(PackBlueNoiseError::Image { path, source })
+            }
+        };
+
+        let image = match image.as_rgba16() {
+            Some(image) => image,
+            None => return Err(PackBlueNoiseError::NotRgba16 { path }),
+        };
+
+        if layer == 0 {
+            width = image.width();
+            height = image.height();
+        } else if width != image.width() || height != image.height() {
+            return Err(PackBlueNoiseError::SizeMismatch { path });
+        }
+
+        for &image::Rgba(rgba) in image.pixels() {
+            texels.push([
+                rgba[0] as f32 / 65535.0,
+                rgba[1] as f32 / 65535.0,
+                rgba[2] as f32 / 65535.0,
+                rgba[3] as f32 / 65535.0,
+            ]);
+        }
+    }
+
+    Ok(BlueNoise::new(width, height, layers, texels)?)
+}
@@ -0,0 +1,75 @@
+use crate::canvas::Canvas;
+
+/// Behavior of the noise texture across its edges.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Tiling {
+    /// Texture is periodic.
+    /// Opposite edges match seamlessly and texture can be repeated.
+    Periodic,
+
+    /// Texture is not periodic.
+    /// Values near opposite edges are unrelated.
+    Clamped,
+}
+
+/// Single channel noise texture with values in `[0, 1)` range.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NoiseTexture {
+    values: Canvas<f32>,
+    tiling: Tiling,
+}
+
+impl NoiseTexture {
+    /// Wraps canvas of values into texture.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if any value is outside `[0, 1)` range.
+    pub fn new(values: Canvas<f32>, tiling: Tiling) -> Self {
+        assert!(
+            values.data().iter().all(|v| (0.0..1.0).contains(v)),
+            "Noise values must be in [0, 1) range"
+        );
+
+        NoiseTexture { values, tiling }
+    }
+
+    pub fn width(&self) -> usize {
+        self.values.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.values.height()
+    }
+
+    pub fn tiling(&self) -> Tiling {
+        self.tiling
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> f32 {
+        self.values[(x, y)]
+    }
+
+    /// Returns value at coordinates outside of the texture bounds.
+    /// Coordinates wrap around for periodic textures
+    /// and are clamped to the edges otherwise.
+    pub fn sample(&self, x: isize, y: isize) -> f32 {
+        match self.tiling {
+            Tiling::Periodic => *self.values.get_wrapping(x, y),
+            Tiling::Clamped => {
+                let x = x.max(0).min(self.width() as isize - 1) as usize;
+                let y = y.max(0).min(self.height() as isize - 1) as usize;
+                self.values[(x, y)]
+            }
+        }
+    }
+
+    /// Returns values in row-major order.
+    pub fn values(&self) -> &Canvas<f32> {
+        &self.values
+    }
+
+    pub fn into_canvas(self) -> Canvas<f32> {
+        self.values
+    }
+}
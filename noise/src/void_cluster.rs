@@ -0,0 +1,178 @@
+//! Void-and-cluster blue noise generation.
+//!
+//! Pixels of binary pattern are ranked one by one, each time choosing
+//! the tightest cluster or the largest void as measured by energy
+//! of gaussian-filtered pattern.
+//! Every threshold of resulting mask is itself well distributed
+//! binary pattern, which makes it suitable for ordered dithering.
+
+use {
+    crate::{canvas::Canvas, texture::Tiling},
+    rand::{seq::SliceRandom as _, Rng},
+};
+
+/// Standard deviation of energy filter.
+const SIGMA: f32 = 1.5;
+
+/// Energy filter is truncated at this distance.
+const RADIUS: isize = 6;
+
+/// Fraction of pixels set in initial binary pattern.
+const INITIAL_DENSITY: f32 = 0.1;
+
+/// Generates rank-ordered mask with values in `[0, 1)`.
+pub(crate) fn generate_void_and_cluster(
+    width: usize,
+    height: usize,
+    tiling: Tiling,
+    rng: &mut impl Rng,
+) -> Canvas<f32> {
+    let mut pattern = Pattern::new(width, height, tiling);
+    let count = width * height;
+
+    // Start with random binary pattern.
+    let initial = ((count as f32 * INITIAL_DENSITY) as usize).max(1);
+    let mut indices: Vec<usize> = (0..count).collect();
+    indices.shuffle(rng);
+
+    for &index in &indices[..initial] {
+        pattern.toggle(index);
+    }
+
+    // Redistribute set pixels until tightest cluster becomes largest void.
+    for _ in 0..count {
+        let cluster = pattern.tightest_cluster();
+        pattern.toggle(cluster);
+
+        let void = pattern.largest_void();
+        pattern.toggle(void);
+
+        if void == cluster {
+            break;
+        }
+    }
+
+    let prototype = pattern.clone();
+    let mut ranks = vec![0; count];
+
+    // Rank set pixels by removing tightest clusters.
+    for rank in (0..initial).rev() {
+        let cluster = pattern.tightest_cluster();
+        pattern.toggle(cluster);
+        ranks[cluster] = rank;
+    }
+
+    // Rank remaining pixels by filling largest voids.
+    // Once majority of pixels are set, largest void among unset pixels
+    // is the tightest cluster of unset pixels, so the same rule applies.
+    let mut pattern = prototype;
+    for rank in initial..count {
+        let void = pattern.largest_void();
+        pattern.toggle(void);
+        ranks[void] = rank;
+    }
+
+    Canvas::from_fn(width, height, |x, y| {
+        ranks[x + y * width] as f32 / count as f32
+    })
+}
+
+/// Binary pattern with energy of each pixel.
+#[derive(Clone)]
+struct Pattern {
+    width: usize,
+    height: usize,
+    tiling: Tiling,
+    set: Vec<bool>,
+    energy: Vec<f32>,
+    kernel: Vec<f32>,
+}
+
+impl Pattern {
+    fn new(width: usize, height: usize, tiling: Tiling) -> Self {
+        let side = 2 * RADIUS + 1;
+        let kernel = (0..side * side)
+            .map(|index| {
+                let dx = (index % side - RADIUS) as f32;
+                let dy = (index / side - RADIUS) as f32;
+                (-(dx * dx + dy * dy) / (2.0 * SIGMA * SIGMA)).exp()
+            })
+            .collect();
+
+        Pattern {
+            width,
+            height,
+            tiling,
+            set: vec![false; width * height],
+            energy: vec![0.0; width * height],
+            kernel,
+        }
+    }
+
+    /// Flips pixel and updates energy around it.
+    fn toggle(&mut self, index: usize) {
+        self.set[index] = !self.set[index];
+        let sign = if self.set[index] { 1.0 } else { -1.0 };
+
+        let side = 2 * RADIUS + 1;
+        let x = (index % self.width) as isize;
+        let y = (index / self.width) as isize;
+
+        for dy in -RADIUS..=RADIUS {
+            for dx in -RADIUS..=RADIUS {
+                let (px, py) = match self.tiling {
+                    Tiling::Periodic => (
+                        (x + dx).rem_euclid(self.width as isize),
+                        (y + dy).rem_euclid(self.height as isize),
+                    ),
+                    Tiling::Clamped => {
+                        let px = x + dx;
+                        let py = y + dy;
+                        if px < 0
+                            || py < 0
+                            || px >= self.width as isize
+                            || py >= self.height as isize
+                        {
+                            continue;
+                        }
+                        (px, py)
+                    }
+                };
+
+                let weight = self.kernel
+                    [((dx + RADIUS) + (dy + RADIUS) * side) as usize];
+                self.energy[px as usize + py as usize * self.width] +=
+                    sign * weight;
+            }
+        }
+    }
+
+    /// Returns index of set pixel with highest energy.
+    fn tightest_cluster(&self) -> usize {
+        self.find(true, |a, b| a > b)
+    }
+
+    /// Returns index of unset pixel with lowest energy.
+    fn largest_void(&self) -> usize {
+        self.find(false, |a, b| a < b)
+    }
+
+    fn find(&self, set: bool, better: impl Fn(f32, f32) -> bool) -> usize {
+        let mut best = None;
+
+        for (index, (&s, &energy)) in
+            self.set.iter().zip(&self.energy).enumerate()
+        {
+            if s != set {
+                continue;
+            }
+
+            match best {
+                Some((_, e)) if !better(energy, e) => {}
+                _ => best = Some((index, energy)),
+            }
+        }
+
+        best.expect("Pattern must contain requested pixel").0
+    }
+}